@@ -11,13 +11,14 @@ import re
 import asyncio
 from datetime import datetime, timezone
 
+import crawler_protocol as protocol
+
+SCRIPT_VERSION = "2.0"
+
 try:
     from playwright.async_api import async_playwright
 except ImportError:
-    print(json.dumps({
-        "error": "缺少 Playwright. 请运行: pip install playwright && playwright install chromium"
-    }))
-    sys.exit(1)
+    protocol.fail("缺少 Playwright. 请运行: pip install playwright && playwright install chromium")
 
 # 站点配置
 COUNTRY_CONFIG = {
@@ -732,38 +733,36 @@ async def discover_subcategories(marketplace: str, parent_category: str, headles
 
 def main():
     """命令行入口"""
+    protocol.handshake(__file__, SCRIPT_VERSION)
     if len(sys.argv) < 3:
-        print(json.dumps({
-            "error": "用法: python amazon_bsr_crawler.py <command> <marketplace> [args...]\n"
-                    "命令:\n"
-                    "  bsr <marketplace> <category_id> [headless] - 爬取 BSR 数据\n"
-                    "  discover <marketplace> <parent_category> [headless] - 发现子类目"
-        }))
-        sys.exit(1)
+        protocol.fail(
+            "用法: python amazon_bsr_crawler.py <command> <marketplace> [args...]\n"
+            "命令:\n"
+            "  bsr <marketplace> <category_id> [headless] - 爬取 BSR 数据\n"
+            "  discover <marketplace> <parent_category> [headless] - 发现子类目"
+        )
 
     command = sys.argv[1]
 
     if command == "discover":
         if len(sys.argv) < 4:
-            print(json.dumps({"error": "用法: python amazon_bsr_crawler.py discover <marketplace> <parent_category> [headless]"}))
-            sys.exit(1)
+            protocol.fail("用法: python amazon_bsr_crawler.py discover <marketplace> <parent_category> [headless]")
         marketplace = sys.argv[2]
         parent_category = sys.argv[3]
         headless = sys.argv[4].lower() == 'true' if len(sys.argv) > 4 else True
         print(f"[DEBUG] 开始发现子类目: marketplace={marketplace}, parent={parent_category}, headless={headless}", file=sys.stderr)
         result = asyncio.run(discover_subcategories(marketplace, parent_category, headless))
-        print(json.dumps(result, ensure_ascii=False))
+        protocol.result(result)
 
     elif command == "bsr":
         if len(sys.argv) < 4:
-            print(json.dumps({"error": "用法: python amazon_bsr_crawler.py bsr <marketplace> <category_id> [headless]"}))
-            sys.exit(1)
+            protocol.fail("用法: python amazon_bsr_crawler.py bsr <marketplace> <category_id> [headless]")
         marketplace = sys.argv[2]
         category_id = sys.argv[3]
         headless = sys.argv[4].lower() == 'true' if len(sys.argv) > 4 else True
         print(f"[DEBUG] 开始爬取 BSR: marketplace={marketplace}, category={category_id}, headless={headless}", file=sys.stderr)
         result = asyncio.run(crawl_bsr(marketplace, category_id, headless))
-        print(json.dumps(result, ensure_ascii=False))
+        protocol.result(result)
 
     else:
         # 兼容旧的调用方式
@@ -772,7 +771,9 @@ def main():
         headless = sys.argv[3].lower() == 'true' if len(sys.argv) > 3 else True
         print(f"[DEBUG] 开始爬取 BSR: marketplace={marketplace}, category={category_id}, headless={headless}", file=sys.stderr)
         result = asyncio.run(crawl_bsr(marketplace, category_id, headless))
-        print(json.dumps(result, ensure_ascii=False))
+        protocol.result(result)
+
+    protocol.complete(1)
 
 
 if __name__ == "__main__":
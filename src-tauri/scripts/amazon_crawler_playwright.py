@@ -9,17 +9,73 @@ import sys
 import json
 import re
 import asyncio
+import threading
+import time
 from datetime import datetime, timezone
 from urllib.parse import quote_plus
 
+import crawl_archive as archive
+import crawler_protocol as protocol
+import delivery_location as location
+
+SCRIPT_VERSION = "2.0"
+
 try:
     from playwright.async_api import async_playwright
 except ImportError:
-    print(json.dumps({
-        "error": "缺少 Playwright. 请运行: pip install playwright && playwright install chromium"
-    }))
-    sys.exit(1)
+    protocol.fail("缺少 Playwright. 请运行: pip install playwright && playwright install chromium")
+
+
+
+class RunControl:
+    """
+    批量模式的运行控制：Rust 端在输入数据之后通过 stdin 逐行发送 pause / resume / cancel / skip <站点>
+    暂停或取消后，正在搜索的关键词会做完，之后不再开始新的关键词
+    skip 用于站点在批次中途进入限速冷却：之后不再开始该站点的关键词
+    """
 
+    def __init__(self):
+        self.paused = False
+        self.cancelled = False
+        self.skipped_countries = set()
+
+    def listen(self):
+        """后台线程读取控制命令，stdin 关闭时结束"""
+        def reader():
+            for line in sys.stdin:
+                command = line.strip().lower()
+                if command == 'pause':
+                    self.paused = True
+                elif command == 'resume':
+                    self.paused = False
+                elif command == 'cancel':
+                    self.cancelled = True
+                    print("[DEBUG] 收到取消命令，完成当前关键词后退出", file=sys.stderr)
+                elif command.startswith('skip '):
+                    country = command[5:].strip().upper()
+                    self.skipped_countries.add(country)
+                    print(f"[DEBUG] {country} 站点限速冷却，跳过剩余关键词", file=sys.stderr)
+
+        threading.Thread(target=reader, daemon=True).start()
+
+    async def checkpoint(self, country: str = None) -> bool:
+        """开始新的关键词前调用：暂停时等待，已取消或站点已跳过返回 False"""
+        while self.paused and not self.cancelled:
+            await asyncio.sleep(0.5)
+        if country and country.upper() in self.skipped_countries:
+            return False
+        return not self.cancelled
+
+
+RUN_CONTROL = RunControl()
+
+
+def annotate_result(result: dict, started_at: float, proxy: str = None, zipcode: str = None) -> dict:
+    """补充检测耗时、使用的代理（写入任务明细）和配送邮编（随排名历史保存）"""
+    result['duration_ms'] = int((time.monotonic() - started_at) * 1000)
+    result['proxy'] = proxy
+    result['location'] = zipcode
+    return result
 
 
 # 站点配置
@@ -87,6 +143,18 @@ EXPECTED_ADDRESS_KEYWORDS = {
 }
 
 
+def zipcode_for(country: str, override: str = None) -> str:
+    """站点使用的配送邮编：任务项指定 > 应用设置 > COUNTRY_CONFIG 默认"""
+    config = COUNTRY_CONFIG.get(country, COUNTRY_CONFIG["US"])
+    return location.zipcode(country, config['zipcode'], override)
+
+
+def address_ok(country: str, zipcode: str, address_text: str) -> bool:
+    """页面显示的配送地址是否已是目标邮编"""
+    config = COUNTRY_CONFIG.get(country, COUNTRY_CONFIG["US"])
+    return location.address_matches(address_text, zipcode, config['zipcode'], EXPECTED_ADDRESS_KEYWORDS.get(country, []))
+
+
 async def set_delivery_address(page, country: str, zipcode: str, max_retries: int = 5) -> tuple[bool, str]:
     """
     设置配送地址 - 基于用户确认的真实操作流程
@@ -100,9 +168,8 @@ async def set_delivery_address(page, country: str, zipcode: str, max_retries: in
         # 先检查当前地址是否已经正确
         try:
             current_addr = await page.locator('#glow-ingress-line2').text_content(timeout=2000)
-            current_addr = current_addr.strip() if current_addr else ""
-            expected_keywords = EXPECTED_ADDRESS_KEYWORDS.get(country, [])
-            if any(kw.lower() in current_addr.lower() for kw in expected_keywords) or zipcode in current_addr:
+            current_addr = location.clean_address(current_addr)
+            if address_ok(country, zipcode, current_addr):
                 print(f"[DEBUG] 邮编已设置正确: {current_addr}", file=sys.stderr)
                 return True, current_addr
         except:
@@ -281,17 +348,13 @@ async def set_delivery_address(page, country: str, zipcode: str, max_retries: in
             await page.wait_for_timeout(1000)
             try:
                 address_text = await page.locator('#glow-ingress-line2').text_content(timeout=3000)
-                address_text = address_text.strip() if address_text else ""
+                address_text = location.clean_address(address_text)
                 print(f"[DEBUG] 当前配送地址显示: {address_text}", file=sys.stderr)
 
-                # 检查地址是否包含目标国家的关键词
-                expected_keywords = EXPECTED_ADDRESS_KEYWORDS.get(country, [])
-                if any(kw.lower() in address_text.lower() for kw in expected_keywords):
+                # 检查地址是否与目标邮编一致（默认邮编时出现目标国家的关键词即可）
+                if address_ok(country, zipcode, address_text):
                     print(f"[DEBUG] 邮编设置成功!", file=sys.stderr)
                     return True, address_text
-                elif zipcode in address_text:
-                    print(f"[DEBUG] 邮编设置成功 (邮编可见)!", file=sys.stderr)
-                    return True, address_text
                 else:
                     print(f"[DEBUG] 地址验证失败，当前显示: {address_text}", file=sys.stderr)
             except Exception as e:
@@ -309,12 +372,12 @@ async def set_delivery_address(page, country: str, zipcode: str, max_retries: in
     # 所有尝试都失败
     try:
         address_text = await page.locator('#glow-ingress-line2').text_content(timeout=2000)
-        return False, address_text.strip() if address_text else ""
+        return False, location.clean_address(address_text)
     except:
         return False, ""
 
 
-async def search_keyword(keyword: str, target_asin: str, country: str, max_pages: int = 5, proxy: str = None, headless = "new") -> dict:
+async def search_keyword(keyword: str, target_asin: str, country: str, max_pages: int = 5, proxy: str = None, headless = "new", zipcode: str = None) -> dict:
     """
     使用 Playwright 搜索关键词并返回目标ASIN的排名
 
@@ -325,8 +388,10 @@ async def search_keyword(keyword: str, target_asin: str, country: str, max_pages
         max_pages: 最大搜索页数
         proxy: 代理地址，格式如 "http://127.0.0.1:7897"
         headless: 无头模式 ("new"=新版无头模式, True=传统无头, False=显示浏览器)
+        zipcode: 配送邮编，为空时使用站点设置
     """
     config = COUNTRY_CONFIG.get(country, COUNTRY_CONFIG["US"])
+    zipcode = zipcode_for(country, zipcode)
 
     result = {
         "keyword": keyword,
@@ -334,6 +399,7 @@ async def search_keyword(keyword: str, target_asin: str, country: str, max_pages
         "country": country,
         "organic_rank": None,
         "organic_page": None,
+        "organic_position": None,  # 自然结果中的绝对位置（前面各页的自然结果数 + 页内位置）
         "sponsored_rank": None,
         "sponsored_page": None,
         "sponsored_type": None,
@@ -343,7 +409,8 @@ async def search_keyword(keyword: str, target_asin: str, country: str, max_pages
         "checked_at": datetime.now(timezone.utc).isoformat().replace("+00:00", "Z"),
         "error": None,
         "warning": None,
-        "delivery_address": None  # 记录实际设置的配送地址
+        "delivery_address": None,  # 记录实际设置的配送地址
+        "location": zipcode
     }
 
     organic_position = 0
@@ -474,8 +541,8 @@ async def search_keyword(keyword: str, target_asin: str, country: str, max_pages
                 pass
 
             # 步骤2: 使用新的邮编设置函数（带重试和验证）
-            print(f"[DEBUG] 设置配送地址: {config['zipcode']}...", file=sys.stderr)
-            address_success, address_text = await set_delivery_address(page, country, config['zipcode'], max_retries=3)
+            print(f"[DEBUG] 设置配送地址: {zipcode}...", file=sys.stderr)
+            address_success, address_text = await set_delivery_address(page, country, zipcode, max_retries=3)
 
             result['delivery_address'] = address_text
             if address_success:
@@ -490,6 +557,7 @@ async def search_keyword(keyword: str, target_asin: str, country: str, max_pages
             # 步骤3: 执行搜索
             encoded_keyword = quote_plus(keyword)
             market_param = f"&{config['market_param']}" if config['market_param'] else ""
+            archive_run = archive.search_run(country, keyword, zipcode)
 
             for page_num in range(1, max_pages + 1):
                 # 自然排名和广告排名都找到了才停止搜索
@@ -511,6 +579,10 @@ async def search_keyword(keyword: str, target_asin: str, country: str, max_pages
                 await page.evaluate('window.scrollTo(0, 0)')
                 await page.wait_for_timeout(500)
 
+                # 开启归档时保存页面原文（验证码页也保存，便于排查）
+                if archive_run:
+                    archive.save_page(archive_run, page_num, await page.content())
+
                 # 检查是否有验证码
                 try:
                     robot_check = page.locator('text=Robot Check')
@@ -784,6 +856,7 @@ async def search_keyword(keyword: str, target_asin: str, country: str, max_pages
                                 if asin.upper() == target_asin.upper() and not found_organic:
                                     result['organic_rank'] = page_organic_position  # 页内自然位置
                                     result['organic_page'] = page_num
+                                    result['organic_position'] = organic_position  # 每页结果数不固定，按实际累计
                                     found_organic = True
                                     print(f"[DEBUG] 找到目标ASIN自然排名: 第{page_num}页自然第{page_organic_position}位", file=sys.stderr)
 
@@ -1031,7 +1104,7 @@ async def search_keyword(keyword: str, target_asin: str, country: str, max_pages
     return result
 
 
-async def search_country(p, country: str, keywords_dict: dict, max_pages: int, headless, progress_lock, proxy: str = None) -> list:
+async def search_country(p, country: str, keywords_dict: dict, max_pages: int, headless, proxy: str = None, zipcode: str = None) -> list:
     """
     处理单个国家的所有关键词（用于并发）
 
@@ -1041,11 +1114,12 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
         keywords_dict: {keyword_lower: {'original_keyword': str, 'targets': [(id, asin), ...]}}
         max_pages: 最大搜索页数
         headless: 无头模式
-        progress_lock: 进度输出锁（避免多线程输出混乱）
         proxy: 代理服务器地址（可选）
+        zipcode: 配送邮编（为空时使用站点设置）
 
     返回: [(monitoring_id, result), ...]
     """
+    zipcode = zipcode_for(country, zipcode)
     country_results = []
 
     unique_keyword_count = len(keywords_dict)
@@ -1143,7 +1217,7 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
         max_address_attempts = 3  # 最多尝试3轮（每轮内部还有5次重试）
 
         for addr_attempt in range(max_address_attempts):
-            address_success, address_text = await set_delivery_address(page, country, config['zipcode'], max_retries=5)
+            address_success, address_text = await set_delivery_address(page, country, zipcode, max_retries=5)
             if address_success:
                 print(f"[DEBUG] {country}: 邮编设置成功: {address_text}", file=sys.stderr)
                 break
@@ -1188,6 +1262,8 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
 
             print(f"[DEBUG] {country}: 检测 {kw_idx}/{unique_keyword_count} - {keyword} (监控 {len(targets)} 个产品: {', '.join(target_asin_list[:3])}{'...' if len(target_asin_list) > 3 else ''})", file=sys.stderr)
 
+            kw_started = time.monotonic()
+
             # 初始化每个目标的结果
             # {asin_upper: result_dict}
             results_by_asin = {}
@@ -1198,6 +1274,7 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
                     "country": country,
                     "organic_rank": None,
                     "organic_page": None,
+                    "organic_position": None,
                     "sponsored_rank": None,
                     "sponsored_page": None,
                     "sponsored_type": None,
@@ -1206,8 +1283,9 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
                     "sponsored_top_20": [],
                     "checked_at": datetime.now(timezone.utc).isoformat().replace("+00:00", "Z"),
                     "error": None,
-                    "warning": None,
-                    "delivery_address": address_text
+                    "warning": None if address_success else f'配送地址设置可能未生效，当前显示: {address_text}',
+                    "delivery_address": address_text,
+                    "pages_checked": 0
                 }
 
             try:
@@ -1222,6 +1300,8 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
                 # 共享的搜索结果记录（所有目标共享）
                 shared_organic_top_50 = []
                 shared_sponsored_top_20 = []
+                organic_position = 0  # 各页累计的自然位置
+                archive_run = archive.search_run(country, keyword, zipcode)
 
                 for page_num in range(1, max_pages + 1):
                     # 检查是否所有目标ASIN的自然和广告排名都找到了
@@ -1236,6 +1316,8 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
                         search_url += f"&page={page_num}"
 
                     await page.goto(search_url, wait_until="domcontentloaded", timeout=30000)
+                    for r in results_by_asin.values():
+                        r['pages_checked'] = page_num
                     await page.wait_for_timeout(4000)
 
                     # 滚动页面以触发懒加载广告
@@ -1243,6 +1325,9 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
                     await page.wait_for_timeout(500)
                     await page.evaluate('window.scrollTo(0, 0)')
 
+                    if archive_run:
+                        archive.save_page(archive_run, page_num, await page.content())
+
                     # 检查验证码
                     try:
                         if await page.locator('text=Robot Check').is_visible(timeout=1000):
@@ -1423,6 +1508,7 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
                                 if asin not in seen_organic_asins:
                                     seen_organic_asins.add(asin)
                                     page_organic_position += 1
+                                    organic_position += 1
 
                                     if len(shared_organic_top_50) < 50:
                                         shared_organic_top_50.append(asin)
@@ -1432,6 +1518,7 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
                                     if asin_upper in target_asins and not found_organic.get(asin_upper, True):
                                         results_by_asin[asin_upper]['organic_rank'] = page_organic_position
                                         results_by_asin[asin_upper]['organic_page'] = page_num
+                                        results_by_asin[asin_upper]['organic_position'] = organic_position
                                         found_organic[asin_upper] = True
                                         print(f"[DEBUG] 在搜索结果中找到 {asin_upper}: 第{page_num}页自然第{page_organic_position}位", file=sys.stderr)
 
@@ -1657,21 +1744,12 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
             # 为每个目标输出结果（使用锁避免并发输出混乱）
             for monitoring_id, asin in targets:
                 asin_upper = asin.upper()
-                result = results_by_asin[asin_upper]
+                result = annotate_result(results_by_asin[asin_upper], kw_started, proxy, zipcode)
 
                 country_results.append((monitoring_id, result))
 
                 # 输出进度（实时，带锁）
-                async with progress_lock:
-                    progress = {
-                        "type": "progress",
-                        "monitoring_id": monitoring_id,
-                        "result": result
-                    }
-                    output = json.dumps(progress, ensure_ascii=False)
-                    sys.stdout.buffer.write(output.encode('utf-8'))
-                    sys.stdout.buffer.write(b'\n')
-                    sys.stdout.flush()
+                protocol.result(result, monitoring_id)
 
             # 关键词间短延迟（同一国家内）
             if kw_idx < unique_keyword_count:
@@ -1686,78 +1764,82 @@ async def search_country(p, country: str, keywords_dict: dict, max_pages: int, h
     return country_results
 
 
-async def search_keywords_batch(keywords_list: list, max_pages: int = 5, headless = "new", max_browsers: int = 3, tabs_per_browser: int = 1, proxy_list: list = None) -> list:
+async def search_keywords_batch(keywords_list: list, max_pages: int = 5, headless = "new", max_browsers: int = 3, tabs_per_browser: int = 1, proxy_pool: dict = None) -> list:
     """
     批量搜索关键词 - 按国家分组，并发处理多个国家
     优化：同一关键词监控多个产品时，只搜索一次，同时记录所有产品的排名
 
     参数:
-        keywords_list: [(id, keyword, asin, country), ...]
+        keywords_list: [(id, keyword, asin, country[, zipcode]), ...]，zipcode 为空时使用站点设置
         max_pages: 最大搜索页数
         headless: 无头模式 ("new"=新版无头模式, True=传统无头, False=显示浏览器)
         max_browsers: 最大并发浏览器数量
         tabs_per_browser: 每个浏览器的标签页数量（用于同一国家内的关键词并发）
-        proxy_list: 代理服务器列表，可选
+        proxy_pool: 代理分配表 {站点: [代理, ...], "*": [通用代理, ...]}，可选
 
     返回: [(id, result), ...]
     """
-    # 按国家分组，再按关键词分组
-    # 结构: {country: {keyword: [(monitoring_id, asin), ...]}}
+    # 按国家和配送邮编分组（同一站点不同邮编需要不同的浏览器上下文），再按关键词分组
+    # 结构: {(country, zipcode): {keyword: [(monitoring_id, asin), ...]}}
     country_groups = {}
     for item in keywords_list:
-        monitoring_id, keyword, asin, country = item
+        monitoring_id, keyword, asin, country = item[:4]
         country = country.upper()
+        group_key = (country, zipcode_for(country, item[4] if len(item) > 4 else None))
         keyword_lower = keyword.lower().strip()
 
-        if country not in country_groups:
-            country_groups[country] = {}
-        if keyword_lower not in country_groups[country]:
-            country_groups[country][keyword_lower] = {
+        if group_key not in country_groups:
+            country_groups[group_key] = {}
+        if keyword_lower not in country_groups[group_key]:
+            country_groups[group_key][keyword_lower] = {
                 'original_keyword': keyword,  # 保留原始大小写
                 'targets': []
             }
-        country_groups[country][keyword_lower]['targets'].append((monitoring_id, asin))
+        country_groups[group_key][keyword_lower]['targets'].append((monitoring_id, asin))
 
     # 统计优化效果
     total_searches_before = len(keywords_list)
     total_searches_after = sum(len(kw_dict) for kw_dict in country_groups.values())
-    print(f"[DEBUG] 共 {len(keywords_list)} 个监控项，{len(country_groups)} 个国家，{total_searches_after} 个唯一关键词", file=sys.stderr)
+    print(f"[DEBUG] 共 {len(keywords_list)} 个监控项，{len(country_groups)} 个站点/邮编分组，{total_searches_after} 个唯一关键词", file=sys.stderr)
     print(f"[DEBUG] 并发模式: 最多 {max_browsers} 个浏览器同时运行，每浏览器 {tabs_per_browser} 个标签页", file=sys.stderr)
-    if proxy_list:
-        print(f"[DEBUG] 代理池: {len(proxy_list)} 个代理可用", file=sys.stderr)
+    if proxy_pool:
+        proxy_count = len({url for urls in proxy_pool.values() for url in urls})
+        print(f"[DEBUG] 代理池: {proxy_count} 个代理可用", file=sys.stderr)
     if total_searches_before > total_searches_after:
         print(f"[DEBUG] 优化: 减少 {total_searches_before - total_searches_after} 次搜索 ({100 - total_searches_after * 100 // total_searches_before}% 节省)", file=sys.stderr)
 
     all_results = []
 
-    # 创建进度输出锁
-    progress_lock = asyncio.Lock()
-
     # 使用信号量限制并发数
     semaphore = asyncio.Semaphore(max_browsers)
 
-    # 代理计数器，用于轮换代理
-    proxy_index = [0]  # 使用列表以便在闭包中修改
+    # 每个站点各自的代理计数器，用于轮换代理
+    proxy_index = {}
 
-    def get_next_proxy():
-        """获取下一个代理（轮换）"""
-        if not proxy_list:
+    def get_next_proxy(country):
+        """获取站点的下一个代理（轮换）；站点没有单独分配时使用通用代理"""
+        if not proxy_pool:
+            return None
+        candidates = proxy_pool.get(country, proxy_pool.get("*", []))
+        if not candidates:
             return None
-        proxy = proxy_list[proxy_index[0] % len(proxy_list)]
-        proxy_index[0] += 1
-        return proxy
+        index = proxy_index.get(country, 0)
+        proxy_index[country] = index + 1
+        return candidates[index % len(candidates)]
 
-    async def limited_search_country(p, country, keywords_dict):
+    async def limited_search_country(p, country, zipcode, keywords_dict):
         """带并发限制的国家搜索"""
         async with semaphore:
-            proxy = get_next_proxy()
-            return await search_country_with_tabs(p, country, keywords_dict, max_pages, headless, progress_lock, tabs_per_browser, proxy)
+            if not await RUN_CONTROL.checkpoint(country):
+                return []
+            proxy = get_next_proxy(country)
+            return await search_country_with_tabs(p, country, keywords_dict, max_pages, headless, tabs_per_browser, proxy, zipcode)
 
     async with async_playwright() as p:
         # 创建所有国家的并发任务
         tasks = [
-            limited_search_country(p, country, keywords_dict)
-            for country, keywords_dict in country_groups.items()
+            limited_search_country(p, country, zipcode, keywords_dict)
+            for (country, zipcode), keywords_dict in country_groups.items()
         ]
 
         # 并行执行所有国家
@@ -1773,7 +1855,7 @@ async def search_keywords_batch(keywords_list: list, max_pages: int = 5, headles
     return all_results
 
 
-async def search_country_with_tabs(p, country: str, keywords_dict: dict, max_pages: int, headless, progress_lock, tabs_per_browser: int = 1, proxy: str = None) -> list:
+async def search_country_with_tabs(p, country: str, keywords_dict: dict, max_pages: int, headless, tabs_per_browser: int = 1, proxy: str = None, zipcode: str = None) -> list:
     """
     使用多标签页并发搜索单个国家的关键词
 
@@ -1783,15 +1865,17 @@ async def search_country_with_tabs(p, country: str, keywords_dict: dict, max_pag
         keywords_dict: {keyword_lower: {'original_keyword': str, 'targets': [(id, asin), ...]}}
         max_pages: 最大搜索页数
         headless: 无头模式
-        progress_lock: 进度输出锁
         tabs_per_browser: 并发标签页数量
         proxy: 代理服务器地址
+        zipcode: 配送邮编（为空时使用站点设置）
 
     返回: [(monitoring_id, result), ...]
     """
+    zipcode = zipcode_for(country, zipcode)
+
     # 如果标签页数量为1，使用原有逻辑
     if tabs_per_browser <= 1:
-        return await search_country(p, country, keywords_dict, max_pages, headless, progress_lock, proxy)
+        return await search_country(p, country, keywords_dict, max_pages, headless, proxy, zipcode)
 
     country_results = []
     config = COUNTRY_CONFIG.get(country, COUNTRY_CONFIG["US"])
@@ -1900,7 +1984,7 @@ async def search_country_with_tabs(p, country: str, keywords_dict: dict, max_pag
             pass
 
         # 设置邮编
-        address_success, address_text = await set_delivery_address(main_page, country, config['zipcode'], max_retries=3)
+        address_success, address_text = await set_delivery_address(main_page, country, zipcode, max_retries=3)
         if not address_success:
             print(f"[DEBUG] {country}: 邮编设置失败，继续尝试...", file=sys.stderr)
 
@@ -1913,6 +1997,9 @@ async def search_country_with_tabs(p, country: str, keywords_dict: dict, max_pag
         async def search_keyword_in_tab(kw_idx, kw_lower, kw_data):
             """在单独的标签页中搜索关键词"""
             async with tab_semaphore:
+                if not await RUN_CONTROL.checkpoint(country):
+                    return []
+                kw_started = time.monotonic()
                 page = await context.new_page()
                 await page.set_extra_http_headers({
                     'Accept-Language': f'{config["language"]},en;q=0.9',
@@ -1925,53 +2012,39 @@ async def search_country_with_tabs(p, country: str, keywords_dict: dict, max_pag
                 try:
                     # 执行搜索
                     results_by_asin = await search_keyword_on_page(
-                        page, keyword, targets, country, config, max_pages, address_text
+                        page, keyword, targets, country, config, max_pages, address_text,
+                        archive_run=archive.search_run(country, keyword, zipcode)
                     )
+                    if not address_success:
+                        for r in results_by_asin.values():
+                            r['warning'] = r.get('warning') or f'配送地址设置可能未生效，当前显示: {address_text}'
 
                     # 输出进度
                     for monitoring_id, asin in targets:
                         asin_upper = asin.upper()
-                        result = results_by_asin.get(asin_upper, {
+                        result = annotate_result(results_by_asin.get(asin_upper, {
                             "keyword": keyword,
                             "target_asin": asin,
                             "country": country,
                             "error": "未找到结果"
-                        })
+                        }), kw_started, proxy, zipcode)
                         tab_results.append((monitoring_id, result))
 
-                        async with progress_lock:
-                            progress = {
-                                "type": "progress",
-                                "monitoring_id": monitoring_id,
-                                "result": result
-                            }
-                            output = json.dumps(progress, ensure_ascii=False)
-                            sys.stdout.buffer.write(output.encode('utf-8'))
-                            sys.stdout.buffer.write(b'\n')
-                            sys.stdout.flush()
+                        protocol.result(result, monitoring_id)
 
                 except Exception as e:
                     print(f"[DEBUG] {country}: 关键词 {keyword} 搜索失败: {e}", file=sys.stderr)
                     for monitoring_id, asin in targets:
-                        error_result = {
+                        error_result = annotate_result({
                             "keyword": keyword,
                             "target_asin": asin,
                             "country": country,
                             "error": str(e),
                             "checked_at": datetime.now(timezone.utc).isoformat().replace("+00:00", "Z"),
-                        }
+                        }, kw_started, proxy, zipcode)
                         tab_results.append((monitoring_id, error_result))
 
-                        async with progress_lock:
-                            progress = {
-                                "type": "progress",
-                                "monitoring_id": monitoring_id,
-                                "result": error_result
-                            }
-                            output = json.dumps(progress, ensure_ascii=False)
-                            sys.stdout.buffer.write(output.encode('utf-8'))
-                            sys.stdout.buffer.write(b'\n')
-                            sys.stdout.flush()
+                        protocol.result(error_result, monitoring_id)
                 finally:
                     await page.close()
 
@@ -2000,7 +2073,8 @@ async def search_country_with_tabs(p, country: str, keywords_dict: dict, max_pag
     return country_results
 
 
-async def search_keyword_on_page(page, keyword: str, targets: list, country: str, config: dict, max_pages: int, address_text: str) -> dict:
+async def search_keyword_on_page(page, keyword: str, targets: list, country: str, config: dict, max_pages: int, address_text: str,
+                                 archive_run: str = None, archived_pages: dict = None) -> dict:
     """
     在指定页面上搜索关键词并返回所有目标ASIN的排名结果
 
@@ -2012,6 +2086,8 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
         config: 国家配置
         max_pages: 最大搜索页数
         address_text: 配送地址文本
+        archive_run: 归档目录（archive.search_run 返回），为空时不归档
+        archived_pages: 重新解析归档时的 {页码: 路径}，不访问网络，页面不存在时停止
 
     返回: {asin_upper: result_dict, ...}
     """
@@ -2030,6 +2106,7 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
             "country": country,
             "organic_rank": None,
             "organic_page": None,
+            "organic_position": None,
             "sponsored_rank": None,
             "sponsored_page": None,
             "sponsored_type": None,
@@ -2039,7 +2116,8 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
             "checked_at": datetime.now(timezone.utc).isoformat().replace("+00:00", "Z"),
             "error": None,
             "warning": None,
-            "delivery_address": address_text
+            "delivery_address": address_text,
+            "pages_checked": 0
         }
 
     # 跟踪每个目标ASIN是否找到
@@ -2049,6 +2127,7 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
     # 共享的搜索结果记录
     shared_organic_top_50 = []
     shared_sponsored_top_20 = []
+    organic_position = 0  # 各页累计的自然位置
 
     encoded_keyword = quote_plus(keyword)
     market_param = f"&{config['market_param']}" if config['market_param'] else ""
@@ -2060,18 +2139,33 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
         if all_organic_found and all_sponsored_found:
             break
 
-        search_url = f"{config['base_url']}/s?k={encoded_keyword}{market_param}"
-        if page_num > 1:
-            search_url += f"&page={page_num}"
+        if archived_pages is not None:
+            if page_num not in archived_pages:
+                if page_num == 1:
+                    for r in results_by_asin.values():
+                        r['error'] = "归档中没有第 1 页"
+                break
+            await page.set_content(archive.load(archived_pages[page_num]), wait_until="domcontentloaded")
+            for r in results_by_asin.values():
+                r['pages_checked'] = page_num
+        else:
+            search_url = f"{config['base_url']}/s?k={encoded_keyword}{market_param}"
+            if page_num > 1:
+                search_url += f"&page={page_num}"
+
+            # 优化：减少页面加载超时时间
+            await page.goto(search_url, wait_until="domcontentloaded", timeout=20000)
+            for r in results_by_asin.values():
+                r['pages_checked'] = page_num
+            await page.wait_for_timeout(3000)  # 减少等待时间
 
-        # 优化：减少页面加载超时时间
-        await page.goto(search_url, wait_until="domcontentloaded", timeout=20000)
-        await page.wait_for_timeout(3000)  # 减少等待时间
+            # 快速滚动触发懒加载
+            await page.evaluate('window.scrollTo(0, 300)')
+            await page.wait_for_timeout(300)
+            await page.evaluate('window.scrollTo(0, 0)')
 
-        # 快速滚动触发懒加载
-        await page.evaluate('window.scrollTo(0, 300)')
-        await page.wait_for_timeout(300)
-        await page.evaluate('window.scrollTo(0, 0)')
+            if archive_run:
+                archive.save_page(archive_run, page_num, await page.content())
 
         # 检查验证码
         try:
@@ -2149,6 +2243,7 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
                     if asin not in seen_organic_asins:
                         seen_organic_asins.add(asin)
                         page_organic_position += 1
+                        organic_position += 1
 
                         if len(shared_organic_top_50) < 50:
                             shared_organic_top_50.append(asin)
@@ -2157,6 +2252,7 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
                         if asin_upper in target_asins and not found_organic.get(asin_upper, True):
                             results_by_asin[asin_upper]['organic_rank'] = page_organic_position
                             results_by_asin[asin_upper]['organic_page'] = page_num
+                            results_by_asin[asin_upper]['organic_position'] = organic_position
                             found_organic[asin_upper] = True
 
                             # 提取产品信息
@@ -2181,7 +2277,7 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
 
         # 页面间延迟（减少）
         all_found = all(found_organic.values()) and all(found_sponsored.values())
-        if page_num < max_pages and not all_found:
+        if page_num < max_pages and not all_found and archived_pages is None:
             await page.wait_for_timeout(1000)
 
     # 填充共享的top列表
@@ -2192,13 +2288,54 @@ async def search_keyword_on_page(page, keyword: str, targets: list, country: str
     return results_by_asin
 
 
+async def reparse_search(keyword: str, target_asin: str, country: str, run_dir: str) -> dict:
+    """
+    用批量检测的解析规则重新解析归档的一次检测（不访问网络）
+
+    参数:
+        keyword: 关键词（meta.json 中有原始关键词时以其为准）
+        target_asin: 目标ASIN
+        country: 国家代码
+        run_dir: 归档的检测目录
+
+    返回: 与单关键词模式相同的结果
+    """
+    meta = archive.run_meta(run_dir)
+    keyword = meta.get('keyword') or keyword
+    config = COUNTRY_CONFIG.get(country, COUNTRY_CONFIG["US"])
+    pages = archive.run_pages(run_dir)
+
+    async with async_playwright() as p:
+        browser = await p.chromium.launch(headless=True)
+        try:
+            page = await browser.new_page()
+            # 页面中的图片、脚本等外部资源一律不加载
+            await page.route("**/*", lambda route: route.abort())
+            results_by_asin = await search_keyword_on_page(
+                page, keyword, [(0, target_asin)], country, config, max(pages, default=1), None,
+                archived_pages=pages
+            )
+            result = results_by_asin[target_asin.upper()]
+        except Exception as e:
+            print(f"[DEBUG] 重新解析失败: {e}", file=sys.stderr)
+            result = {
+                "keyword": keyword,
+                "target_asin": target_asin,
+                "country": country,
+                "checked_at": datetime.now(timezone.utc).isoformat().replace("+00:00", "Z"),
+                "error": str(e),
+            }
+        finally:
+            await browser.close()
+
+    result['location'] = meta.get('location')
+    return result
+
 
 def main():
+    protocol.handshake(__file__, SCRIPT_VERSION)
     if len(sys.argv) < 2:
-        print(json.dumps({
-            "error": "用法: python amazon_crawler_playwright.py <keyword> <asin> <country> [max_pages] [proxy] [headless]\n或: python amazon_crawler_playwright.py --batch"
-        }))
-        sys.exit(1)
+        protocol.fail("用法: python amazon_crawler_playwright.py <keyword> <asin> <country> [max_pages] [proxy] [headless] [zipcode]\n或: python amazon_crawler_playwright.py --batch")
 
     # 批量模式：从stdin读取JSON
     if sys.argv[1] == '--batch':
@@ -2213,18 +2350,21 @@ def main():
         max_pages = int(sys.argv[3]) if len(sys.argv) > 3 else 5
         max_browsers = int(sys.argv[4]) if len(sys.argv) > 4 else 3  # 并发浏览器数量
         tabs_per_browser = int(sys.argv[5]) if len(sys.argv) > 5 else 1  # 每浏览器标签页数量
-        proxy_list_str = sys.argv[6] if len(sys.argv) > 6 and sys.argv[6] != 'none' else None  # 代理列表（逗号分隔）
+        proxy_arg = sys.argv[6] if len(sys.argv) > 6 and sys.argv[6] != 'none' else None  # 代理分配表（JSON）或代理列表（逗号分隔）
 
-        # 解析代理列表
-        proxy_list = None
-        if proxy_list_str:
-            proxy_list = [p.strip() for p in proxy_list_str.split(',') if p.strip()]
-            if not proxy_list:
-                proxy_list = None
+        # 解析代理：JSON 为按站点分配，逗号分隔的列表用于所有站点
+        proxy_pool = None
+        if proxy_arg:
+            if proxy_arg.lstrip().startswith('{'):
+                proxy_pool = json.loads(proxy_arg)
+            else:
+                proxy_list = [p.strip() for p in proxy_arg.split(',') if p.strip()]
+                proxy_pool = {"*": proxy_list} if proxy_list else None
 
-        # 从stdin读取JSON
-        input_data = sys.stdin.read()
+        # 从stdin读取JSON（第一行），之后的行为控制命令
+        input_data = sys.stdin.readline()
         keywords_list = json.loads(input_data)
+        RUN_CONTROL.listen()
 
         # 执行批量检测（并发模式，支持多标签页和代理池）
         results = asyncio.run(search_keywords_batch(
@@ -2233,22 +2373,25 @@ def main():
             headless,
             max_browsers,
             tabs_per_browser,
-            proxy_list
+            proxy_pool
         ))
 
         # 输出完成标记
-        complete = {"type": "complete", "total": len(results)}
-        output = json.dumps(complete, ensure_ascii=False)
-        sys.stdout.buffer.write(output.encode('utf-8'))
-        sys.stdout.buffer.write(b'\n')
+        protocol.complete(len(results), cancelled=RUN_CONTROL.cancelled)
+        sys.exit(0)
+
+    # 重新解析归档的检测: --reparse-search <keyword> <asin> <country> <检测目录>
+    if sys.argv[1] == '--reparse-search':
+        if len(sys.argv) < 6:
+            protocol.fail("用法: python amazon_crawler_playwright.py --reparse-search <keyword> <asin> <country> <run_dir>")
+        result = asyncio.run(reparse_search(sys.argv[2], sys.argv[3], sys.argv[4].upper(), sys.argv[5]))
+        protocol.result(result)
+        protocol.complete(1)
         sys.exit(0)
 
     # 单个关键词模式
     if len(sys.argv) < 4:
-        print(json.dumps({
-            "error": "用法: python amazon_crawler_playwright.py <keyword> <asin> <country> [max_pages] [proxy] [headless]"
-        }))
-        sys.exit(1)
+        protocol.fail("用法: python amazon_crawler_playwright.py <keyword> <asin> <country> [max_pages] [proxy] [headless] [zipcode]")
 
     keyword = sys.argv[1]
     target_asin = sys.argv[2]
@@ -2264,11 +2407,12 @@ def main():
     else:
         headless = True
 
-    result = asyncio.run(search_keyword(keyword, target_asin, country, max_pages, proxy, headless))
+    zipcode = sys.argv[7] if len(sys.argv) > 7 and sys.argv[7] != 'none' else None
+
+    result = asyncio.run(search_keyword(keyword, target_asin, country, max_pages, proxy, headless, zipcode))
 
-    output = json.dumps(result, ensure_ascii=False)
-    sys.stdout.buffer.write(output.encode('utf-8'))
-    sys.stdout.buffer.write(b'\n')
+    protocol.result(result)
+    protocol.complete(1)
 
 
 if __name__ == "__main__":
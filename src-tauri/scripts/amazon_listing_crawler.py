@@ -11,13 +11,16 @@ import re
 import asyncio
 from datetime import datetime, timezone
 
+import crawl_archive as archive
+import crawler_protocol as protocol
+import delivery_location as location
+
+SCRIPT_VERSION = "2.0"
+
 try:
     from playwright.async_api import async_playwright
 except ImportError:
-    print(json.dumps({
-        "error": "缺少 Playwright. 请运行: pip install playwright && playwright install chromium"
-    }))
-    sys.exit(1)
+    protocol.fail("缺少 Playwright. 请运行: pip install playwright && playwright install chromium")
 
 # 站点配置
 COUNTRY_CONFIG = {
@@ -91,6 +94,18 @@ EXPECTED_ADDRESS_KEYWORDS = {
 }
 
 
+def zipcode_for(country: str) -> str:
+    """站点使用的配送邮编：应用设置 > COUNTRY_CONFIG 默认"""
+    config = COUNTRY_CONFIG.get(country.upper(), COUNTRY_CONFIG["US"])
+    return location.zipcode(country, config['zipcode'])
+
+
+def address_ok(country: str, zipcode: str, address_text: str) -> bool:
+    """页面显示的配送地址是否已是目标邮编"""
+    config = COUNTRY_CONFIG.get(country.upper(), COUNTRY_CONFIG["US"])
+    return location.address_matches(address_text, zipcode, config['zipcode'], EXPECTED_ADDRESS_KEYWORDS.get(country.upper(), []))
+
+
 async def set_delivery_address(page, country: str, zipcode: str, max_retries: int = 3) -> tuple:
     """
     设置配送地址 - 与排名监控保持一致
@@ -102,9 +117,8 @@ async def set_delivery_address(page, country: str, zipcode: str, max_retries: in
         # 先检查当前地址是否已经正确
         try:
             current_addr = await page.locator('#glow-ingress-line2').text_content(timeout=2000)
-            current_addr = current_addr.strip() if current_addr else ""
-            expected_keywords = EXPECTED_ADDRESS_KEYWORDS.get(country, [])
-            if any(kw.lower() in current_addr.lower() for kw in expected_keywords) or zipcode in current_addr:
+            current_addr = location.clean_address(current_addr)
+            if address_ok(country, zipcode, current_addr):
                 print(f"[DEBUG] 邮编已设置正确: {current_addr}", file=sys.stderr)
                 return True, current_addr
         except:
@@ -264,11 +278,10 @@ async def set_delivery_address(page, country: str, zipcode: str, max_retries: in
             # 步骤6: 验证地址是否设置成功
             try:
                 address_text = await page.locator('#glow-ingress-line2').text_content(timeout=3000)
-                address_text = address_text.strip() if address_text else ""
+                address_text = location.clean_address(address_text)
                 print(f"[DEBUG] 当前配送地址显示: {address_text}", file=sys.stderr)
 
-                expected_keywords = EXPECTED_ADDRESS_KEYWORDS.get(country, [])
-                if any(kw.lower() in address_text.lower() for kw in expected_keywords) or zipcode in address_text:
+                if address_ok(country, zipcode, address_text):
                     print(f"[DEBUG] 邮编设置成功!", file=sys.stderr)
                     return True, address_text
             except Exception as e:
@@ -285,7 +298,7 @@ async def set_delivery_address(page, country: str, zipcode: str, max_retries: in
     # 所有重试都失败
     try:
         address_text = await page.locator('#glow-ingress-line2').text_content(timeout=2000)
-        return False, address_text.strip() if address_text else ""
+        return False, location.clean_address(address_text)
     except:
         return False, ""
 
@@ -490,6 +503,413 @@ async def set_currency(page, country: str) -> bool:
         return False
 
 
+async def extract_listing_fields(page, result: dict, country: str) -> dict:
+    """
+    从已打开的产品详情页提取 Listing 信息，写入 result（爬取和重新解析归档页面共用）
+    """
+    # 检查是否是有效的产品页
+    try:
+        not_found = page.locator('text=Page Not Found, text=Looking for something?')
+        if await not_found.is_visible(timeout=1000):
+            result['error'] = "产品页面不存在"
+            return result
+    except:
+        pass
+
+    # 1. 提取标题 (使用 span#productTitle 避免匹配到 hidden input)
+    try:
+        title_elem = page.locator('span#productTitle').first
+        title = await title_elem.text_content(timeout=5000)
+        result['title'] = title.strip() if title else None
+        print(f"[DEBUG] 标题: {result['title'][:50]}..." if result['title'] else "[DEBUG] 未找到标题", file=sys.stderr)
+    except Exception as e:
+        print(f"[DEBUG] 提取标题失败: {e}", file=sys.stderr)
+
+    # 1.5 提取主图 (与排名监控一致)
+    try:
+        img_selectors = [
+            '#landingImage',
+            '#imgBlkFront',
+            '#main-image',
+            '.a-dynamic-image',
+        ]
+        for img_sel in img_selectors:
+            img_elem = page.locator(img_sel).first
+            if await img_elem.count() > 0:
+                img_url = await img_elem.get_attribute('src')
+                if img_url and img_url.startswith('http'):
+                    result['image_url'] = img_url
+                    print(f"[DEBUG] 图片: {img_url[:60]}...", file=sys.stderr)
+                    break
+    except Exception as e:
+        print(f"[DEBUG] 提取图片失败: {e}", file=sys.stderr)
+
+    # 2. 提取价格 (与排名监控爬虫保持一致)
+    try:
+        price_elem = page.locator('.a-price .a-offscreen').first
+        if await page.locator('.a-price .a-offscreen').count() > 0:
+            price = await price_elem.text_content()
+            if price:
+                result['price'] = price.strip()
+                print(f"[DEBUG] 价格: {result['price']}", file=sys.stderr)
+    except Exception as e:
+        print(f"[DEBUG] 提取价格失败: {e}", file=sys.stderr)
+
+    # 3. 提取评分 (使用更精确的选择器，避免获取到变体或其他产品的评分)
+    try:
+        rating_found = False
+        # 方法1: 从主评分区域获取 (最可靠)
+        rating_selectors = [
+            '#acrPopover .a-icon-alt',  # 主评分弹出框
+            '#averageCustomerReviews .a-icon-alt',  # 平均评分区域
+            '#acrPopover span.a-icon-alt',
+            '#cm_cr_dp_d_rating_histogram .a-icon-alt',  # 评分直方图
+        ]
+        for selector in rating_selectors:
+            try:
+                rating_elem = page.locator(selector).first
+                if await rating_elem.count() > 0:
+                    rating_text = await rating_elem.text_content() or ''
+                    rating_match = re.search(r'([\d,\.]+)', rating_text)
+                    if rating_match:
+                        result['rating'] = rating_match.group(1).replace(',', '.')
+                        print(f"[DEBUG] 评分: {result['rating']} (from {selector})", file=sys.stderr)
+                        rating_found = True
+                        break
+            except:
+                continue
+
+        # 方法2: 如果上面没找到，使用更宽泛的选择器但限定在评论区域
+        if not rating_found:
+            rating_elem = page.locator('#reviewsMedley .a-icon-star .a-icon-alt, #averageCustomerReviews_feature_div .a-icon-alt').first
+            if await rating_elem.count() > 0:
+                rating_text = await rating_elem.text_content() or ''
+                rating_match = re.search(r'([\d,\.]+)', rating_text)
+                if rating_match:
+                    result['rating'] = rating_match.group(1).replace(',', '.')
+                    print(f"[DEBUG] 评分: {result['rating']} (from reviewsMedley)", file=sys.stderr)
+    except Exception as e:
+        print(f"[DEBUG] 提取评分失败: {e}", file=sys.stderr)
+
+    # 4. 提取评论数 (与排名监控爬虫保持一致)
+    try:
+        reviews_elem = page.locator('#acrCustomerReviewText').first
+        if await reviews_elem.count() > 0:
+            reviews_text = await reviews_elem.text_content() or ''
+            # 提取数字，处理各种格式如 "2,989", "2.989", "2989"
+            num_match = re.search(r'([\d,.\s]+)', reviews_text)
+            if num_match:
+                num_str = num_match.group(1).strip()
+                # 移除空格和千位分隔符
+                num_str = re.sub(r'[\s,.]', '', num_str)
+                if num_str.isdigit():
+                    result['review_count'] = int(num_str)
+                    print(f"[DEBUG] 评论数: {result['review_count']}", file=sys.stderr)
+    except Exception as e:
+        print(f"[DEBUG] 提取评论数失败: {e}", file=sys.stderr)
+
+    # 5. 提取 BSR 排名（包含类目名称）
+    try:
+        # 正则表达式：同时捕获排名数字和类目名称
+        # 各语言格式:
+        # 法语: "7 en Fraises à ongles électriques"
+        # 英语: "#7 in Nail Files"
+        # 德语: "Nr. 7 in Elektrische Nagelfeilen"
+        # 意大利语: "n. 7 in Lime elettriche per unghie"
+        # 西班牙语: "nº 7 en Limas de uñas eléctricas"
+        # 日语: "ビューティー - 116,737位" 或 "ヘアカーリングワンド - 11位"
+        bsr_with_category_patterns = [
+            # 法语: 数字 en 类目
+            r'(\d[\d.,]*)\s+en\s+([A-ZÀ-Úa-zà-ú][^\n\(#]{3,80})',
+            # 英语/美国: #数字 in 类目
+            r'#\s*(\d[\d.,]*)\s+in\s+([A-Za-zÀ-ú][^\n\(#]{3,80})',
+            # 英语/英国: 数字 in 类目（无 # 前缀）
+            r'(\d[\d,]*)\s+in\s+([A-Za-z][^\n\(#]{3,80})',
+            # 德语: Nr. 数字 in 类目
+            r'Nr\.?\s*(\d[\d.,]*)\s+in\s+([A-ZÄÖÜa-zäöüß][^\n\(#]{3,80})',
+            # 意大利语: n. 数字 in 类目
+            r'[nN]\.?\s*(\d[\d.,]*)\s+in\s+([A-Za-zÀ-ú][^\n\(#]{3,80})',
+            # 西班牙语: nº 数字 en 类目
+            r'[nN][º°]?\s*(\d[\d.,]*)\s+en\s+([A-Za-zÀ-ú][^\n\(#]{3,80})',
+        ]
+
+        # 日本站特殊处理：格式为 "类目 - 数字位"
+        # 注意：类目名可以包含长音符号 ー，分隔符是空格包围的短横线 " - "
+        jp_bsr_pattern = r'([ぁ-んァ-ヶー一-龥a-zA-Z&]+)\s+[-‐−]\s+(\d[\d,]*)\s*位'
+
+        def extract_bsr_with_category(text, is_japan=False):
+            """从文本中提取所有 BSR 排名及其类目，返回排名最小的那个"""
+            all_bsr = []
+
+            # 日本站特殊处理：格式为 "类目 - 数字位"
+            if is_japan:
+                jp_matches = re.findall(jp_bsr_pattern, text)
+                for category, rank_str in jp_matches:
+                    num_str = re.sub(r'[\s,]', '', rank_str)
+                    if num_str.isdigit():
+                        category = category.strip()
+                        # 清理日本站的类目名称，移除末尾的"の売れ筋ランキングを見る"等
+                        category = re.sub(r'\s*[（\(].*[）\)].*$', '', category)
+                        category = category.strip()
+                        print(f"[DEBUG] BSR 匹配(JP): rank={num_str}, 类目='{category}'", file=sys.stderr)
+                        if len(category) >= 2:
+                            all_bsr.append((int(num_str), category))
+
+            # 其他国家的处理
+            for pattern in bsr_with_category_patterns:
+                matches = re.findall(pattern, text, re.IGNORECASE | re.MULTILINE)
+                for rank_str, category in matches:
+                    num_str = re.sub(r'[\s,.]', '', rank_str)
+                    if num_str.isdigit():
+                        original_category = category.strip()
+                        category = original_category
+
+                        # 优先提取 "nella categoria X" 中的 X（意大利语）
+                        nella_match = re.search(r'nella categoria\s+(.+)$', category, re.IGNORECASE)
+                        if nella_match:
+                            category = nella_match.group(1).strip()
+                        else:
+                            # 提取 "in der Kategorie X" 中的 X（德语）
+                            der_match = re.search(r'in der Kategorie\s+(.+)$', category, re.IGNORECASE)
+                            if der_match:
+                                category = der_match.group(1).strip()
+                            else:
+                                # 清理 "Top 100 in X" -> "X"
+                                category = re.sub(r'^Top\s+\d+\s+in\s+', '', category, flags=re.IGNORECASE)
+                                # 清理尾部的 "Voir/See/Visualizza..."
+                                category = re.sub(r'\s*(Voir|See|Mehr|Ver|Vedi|Visualizza).*$', '', category, flags=re.IGNORECASE)
+
+                        category = category.strip()
+                        print(f"[DEBUG] BSR 匹配: rank={num_str}, 原始类目='{original_category}' -> 清理后='{category}'", file=sys.stderr)
+                        if len(category) > 3:
+                            all_bsr.append((int(num_str), category))
+
+            if all_bsr:
+                print(f"[DEBUG] BSR 所有匹配: {all_bsr}", file=sys.stderr)
+                # 返回排名最小的（子类目排名通常最小）
+                all_bsr.sort(key=lambda x: x[0])
+                return all_bsr[0]
+            return None
+
+        # 方法1: 从特定 ID 的产品详情表格提取
+        bsr_id_selectors = [
+            '#productDetails_detailBullets_sections1',
+            '#detailBulletsWrapper_feature_div',
+            '#prodDetails',
+            '#detailBullets_feature_div',
+        ]
+        is_japan = country == 'JP'
+        for selector in bsr_id_selectors:
+            try:
+                detail_elem = page.locator(selector).first
+                if await detail_elem.count() > 0:
+                    detail_text = await detail_elem.inner_text()
+                    bsr_info = extract_bsr_with_category(detail_text, is_japan=is_japan)
+                    if bsr_info:
+                        rank, category = bsr_info
+                        result['bsr_rank'] = f"#{rank} in {category}"
+                        print(f"[DEBUG] BSR: {result['bsr_rank']} (from {selector})", file=sys.stderr)
+                        break
+            except Exception as e:
+                print(f"[DEBUG] BSR选择器 {selector} 失败: {e}", file=sys.stderr)
+                continue
+
+        # 方法2: 遍历所有 .prodDetTable 表格
+        if not result['bsr_rank']:
+            try:
+                tables = page.locator('.prodDetTable')
+                table_count = await tables.count()
+                print(f"[DEBUG] 找到 {table_count} 个 prodDetTable", file=sys.stderr)
+                for i in range(table_count):
+                    table = tables.nth(i)
+                    table_text = await table.inner_text()
+                    bsr_info = extract_bsr_with_category(table_text, is_japan=is_japan)
+                    if bsr_info:
+                        rank, category = bsr_info
+                        result['bsr_rank'] = f"#{rank} in {category}"
+                        print(f"[DEBUG] BSR: {result['bsr_rank']} (from table {i})", file=sys.stderr)
+                        break
+            except Exception as e:
+                print(f"[DEBUG] 遍历表格失败: {e}", file=sys.stderr)
+
+        # 方法3: 如果上面没找到，尝试从整个页面搜索
+        if not result['bsr_rank']:
+            page_content = await page.content()
+            bsr_info = extract_bsr_with_category(page_content, is_japan=is_japan)
+            if bsr_info:
+                rank, category = bsr_info
+                result['bsr_rank'] = f"#{rank} in {category}"
+                print(f"[DEBUG] BSR (页面): {result['bsr_rank']}", file=sys.stderr)
+    except Exception as e:
+        print(f"[DEBUG] 提取BSR失败: {e}", file=sys.stderr)
+
+    # 5.5 提取上架时间 (Date First Available)
+    try:
+        # 各语言格式:
+        # 英语: "Date First Available : January 1, 2020" 或 "Date First Available: 1 Jan. 2020"
+        # 德语: "Im Angebot von Amazon.de seit : 1. Januar 2020"
+        # 法语: "Date de mise en ligne sur Amazon.fr : 1 janvier 2020"
+        # 意大利语: "Disponibile su Amazon.it a partire dal : 1 gennaio 2020"
+        # 西班牙语: "Fecha de disponibilidad en Amazon.es : 1 de enero de 2020"
+        # 日语: "Amazon.co.jpでの取り扱い開始日 : 2020/1/1"
+        date_patterns = [
+            # 英语 (US/UK/CA/AU)
+            r'Date First Available\s*[:\-]?\s*([A-Za-z]+\.?\s*\d{1,2},?\s*\d{4}|\d{1,2}\s+[A-Za-z]+\.?\s*\d{4})',
+            # 德语
+            r'Im Angebot von Amazon\.de seit\s*[:\-]?\s*(\d{1,2}\.?\s*[A-Za-zäöüÄÖÜß]+\.?\s*\d{4})',
+            r'Erstmals im Angebot auf Amazon\.de\s*[:\-]?\s*(\d{1,2}\.?\s*[A-Za-zäöüÄÖÜß]+\.?\s*\d{4})',
+            # 法语
+            r'Date de mise en ligne sur Amazon\.fr\s*[:\-]?\s*(\d{1,2}\s+[a-zéèàùâêîôûäëïöü]+\.?\s*\d{4})',
+            r'Disponible sur Amazon\.fr depuis le\s*[:\-]?\s*(\d{1,2}\s+[a-zéèàùâêîôûäëïöü]+\.?\s*\d{4})',
+            # 意大利语
+            r'Disponibile su Amazon\.it\s+(?:a partire\s+)?dal\s*[:\-]?\s*(\d{1,2}\s+[a-z]+\.?\s*\d{4})',
+            # 西班牙语 - "Producto en Amazon.es desde : 18 febrero 2023"
+            r'Producto en Amazon\.es desde\s*[:\-‏‎\s]*(\d{1,2}\s+(?:de\s+)?[a-z]+(?:\s+de)?\s+\d{4})',
+            r'Fecha de disponibilidad en Amazon\.es\s*[:\-]?\s*(\d{1,2}\s+(?:de\s+)?[a-z]+(?:\s+de)?\s+\d{4})',
+            # 日语 - 注意 Amazon.co.jp 和 での 之间可能有空格
+            r'Amazon\.co\.jp\s*での取り扱い開始日\s*[:\-]?\s*(\d{4}[/年]\d{1,2}[/月]\d{1,2}日?)',
+            # 日语备用格式
+            r'取り扱い開始日\s*[:\-]?\s*(\d{4}[/年]\d{1,2}[/月]\d{1,2}日?)',
+        ]
+
+        def extract_date_first_available(text):
+            for pattern in date_patterns:
+                match = re.search(pattern, text, re.IGNORECASE)
+                if match:
+                    date_str = match.group(1).strip()
+                    print(f"[DEBUG] 上架时间匹配: '{date_str}'", file=sys.stderr)
+                    return date_str
+            return None
+
+        # 从产品详情表格提取
+        for selector in bsr_id_selectors:
+            try:
+                detail_elem = page.locator(selector).first
+                if await detail_elem.count() > 0:
+                    detail_text = await detail_elem.inner_text()
+                    date_str = extract_date_first_available(detail_text)
+                    if date_str:
+                        result['date_first_available'] = date_str
+                        print(f"[DEBUG] 上架时间: {date_str} (from {selector})", file=sys.stderr)
+                        break
+            except:
+                continue
+
+        # 如果没找到，尝试从整个页面搜索
+        if not result['date_first_available']:
+            page_content = await page.content()
+            date_str = extract_date_first_available(page_content)
+            if date_str:
+                result['date_first_available'] = date_str
+                print(f"[DEBUG] 上架时间 (页面): {date_str}", file=sys.stderr)
+
+    except Exception as e:
+        print(f"[DEBUG] 提取上架时间失败: {e}", file=sys.stderr)
+
+    # 6. 提取五点描述 (Bullet Points)
+    try:
+        # 尝试多个选择器（从最精确到最宽泛）
+        bullet_selectors = [
+            '#feature-bullets ul li span.a-list-item',
+            '#featurebullets_feature_div ul li span.a-list-item',
+            '#productFactsDesktopExpander ul li span.a-list-item',
+            # 备用：关于此商品区域
+            '#productFactsDesktop_feature_div ul li',
+        ]
+        for selector in bullet_selectors:
+            bullets_elem = page.locator(selector)
+            count = await bullets_elem.count()
+            print(f"[DEBUG] 尝试选择器 {selector}: 找到 {count} 个", file=sys.stderr)
+            if count > 0:
+                bullets_list = await bullets_elem.all()
+                for bullet in bullets_list:
+                    text = await bullet.text_content()
+                    if text:
+                        text = text.strip()
+                        # 清理多余空白
+                        text = re.sub(r'\s+', ' ', text)
+                        # 过滤掉无关内容
+                        if (text and len(text) > 15
+                            and not text.startswith('Make sure')
+                            and not text.startswith('See more')
+                            and 'out of 5 stars' not in text
+                            and not text.startswith('Product Dimensions')
+                            and not text.startswith('Item model')
+                            and not text.startswith('Manufacturer')
+                            and not text.startswith('ASIN')
+                            and not text.startswith('Customer Reviews')
+                            and 'Reviewed in' not in text
+                            and 'star' not in text[:20]):
+                            result['bullets'].append(text)
+                if result['bullets']:
+                    print(f"[DEBUG] 五点描述: {len(result['bullets'])} 条", file=sys.stderr)
+                    break
+
+        # 如果上面没找到，尝试从页面其他区域获取（宽泛选择器）
+        if not result['bullets']:
+            # 之前能用的选择器
+            broad_bullets = page.locator('ul.a-unordered-list.a-vertical li span.a-list-item')
+            count = await broad_bullets.count()
+            print(f"[DEBUG] 宽泛选择器: 找到 {count} 个", file=sys.stderr)
+            if count > 0:
+                for i in range(min(count, 30)):  # 最多检查30个
+                    item = broad_bullets.nth(i)
+                    text = await item.text_content()
+                    if text:
+                        text = re.sub(r'\s+', ' ', text.strip())
+                        # 过滤掉明显无关的内容
+                        skip_keywords = [
+                            'out of 5 stars', 'Reviewed in',
+                            'Product Dimensions', 'Item model',
+                            'ASIN :', 'Customer Reviews:',
+                            'P.when(', 'function(A)', 'execute(',
+                            'dpAcrHas', '63%18%', 'star4 star',
+                            '<img src=', 'Verified Purchase'
+                        ]
+                        # 文本长度在合理范围且不包含脚本代码
+                        if (30 < len(text) < 400
+                            and not any(skip in text for skip in skip_keywords)
+                            and 'var ' not in text
+                            and 'P.when' not in text):
+                            result['bullets'].append(text)
+                            print(f"[DEBUG] 保留五点 {len(result['bullets'])}: {text[:50]}...", file=sys.stderr)
+                            if len(result['bullets']) >= 7:  # 最多7条
+                                break
+                if result['bullets']:
+                    print(f"[DEBUG] 五点描述 (宽泛): {len(result['bullets'])} 条", file=sys.stderr)
+    except Exception as e:
+        print(f"[DEBUG] 提取五点描述失败: {e}", file=sys.stderr)
+
+    # 7. 提取商品描述
+    try:
+        # 优先获取纯文本描述，避免 A+ 内容（A+ 通常包含大量 CSS/JS）
+        desc_selectors = [
+            '#productDescription p',
+            '#productDescription',
+        ]
+        for selector in desc_selectors:
+            desc_elem = page.locator(selector)
+            if await desc_elem.count() > 0:
+                # 使用 inner_text() 而不是 text_content()，只获取可见文本
+                desc_text = await desc_elem.inner_text()
+                if desc_text:
+                    desc_text = desc_text.strip()
+                    # 清理多余空白
+                    desc_text = re.sub(r'\s+', ' ', desc_text)
+                    # 过滤掉明显是 CSS/JS 的内容
+                    if (len(desc_text) > 20
+                        and not desc_text.startswith('.aplus')
+                        and 'function ' not in desc_text
+                        and '{' not in desc_text[:100]):
+                        result['description'] = desc_text[:5000]  # 限制长度
+                        print(f"[DEBUG] 描述: {len(result['description'])} 字符", file=sys.stderr)
+                        break
+    except Exception as e:
+        print(f"[DEBUG] 提取描述失败: {e}", file=sys.stderr)
+
+    return result
+
+
 async def fetch_listing_info(asin: str, country: str, headless="new") -> dict:
     """
     爬取单个 ASIN 的 Listing 信息
@@ -506,6 +926,7 @@ async def fetch_listing_info(asin: str, country: str, headless="new") -> dict:
         "bullets": [str, ...],  # 五点描述列表
         "description": str,
         "fetched_at": str,
+        "location": str,  # 配送邮编
         "error": str | null
     }
     """
@@ -524,6 +945,7 @@ async def fetch_listing_info(asin: str, country: str, headless="new") -> dict:
         "bullets": [],
         "description": None,
         "fetched_at": datetime.now(timezone.utc).isoformat().replace("+00:00", "Z"),
+        "location": zipcode_for(country),  # 配送邮编
         "error": None
     }
 
@@ -664,7 +1086,7 @@ async def fetch_listing_info(asin: str, country: str, headless="new") -> dict:
 
             # 设置配送地址/邮编（与排名监控一致）
             try:
-                address_success, address_text = await set_delivery_address(page, country, config['zipcode'], max_retries=3)
+                address_success, address_text = await set_delivery_address(page, country, result['location'], max_retries=3)
                 if address_success:
                     print(f"[DEBUG] 配送地址设置成功: {address_text}", file=sys.stderr)
                     # 刷新页面以获取正确的价格
@@ -702,410 +1124,59 @@ async def fetch_listing_info(asin: str, country: str, headless="new") -> dict:
             except Exception as e:
                 print(f"[DEBUG] 设置货币出错: {e}", file=sys.stderr)
 
-            # 检查是否是有效的产品页
-            try:
-                not_found = page.locator('text=Page Not Found, text=Looking for something?')
-                if await not_found.is_visible(timeout=1000):
-                    result['error'] = "产品页面不存在"
-                    return result
-            except:
-                pass
+            # 开启归档时保存页面原文，便于排查解析问题和重新解析
+            if archive.enabled():
+                archive.save("listing", country, asin, await page.content())
 
-            # 1. 提取标题 (使用 span#productTitle 避免匹配到 hidden input)
-            try:
-                title_elem = page.locator('span#productTitle').first
-                title = await title_elem.text_content(timeout=5000)
-                result['title'] = title.strip() if title else None
-                print(f"[DEBUG] 标题: {result['title'][:50]}..." if result['title'] else "[DEBUG] 未找到标题", file=sys.stderr)
-            except Exception as e:
-                print(f"[DEBUG] 提取标题失败: {e}", file=sys.stderr)
-
-            # 1.5 提取主图 (与排名监控一致)
-            try:
-                img_selectors = [
-                    '#landingImage',
-                    '#imgBlkFront',
-                    '#main-image',
-                    '.a-dynamic-image',
-                ]
-                for img_sel in img_selectors:
-                    img_elem = page.locator(img_sel).first
-                    if await img_elem.count() > 0:
-                        img_url = await img_elem.get_attribute('src')
-                        if img_url and img_url.startswith('http'):
-                            result['image_url'] = img_url
-                            print(f"[DEBUG] 图片: {img_url[:60]}...", file=sys.stderr)
-                            break
-            except Exception as e:
-                print(f"[DEBUG] 提取图片失败: {e}", file=sys.stderr)
+            await extract_listing_fields(page, result, country)
 
-            # 2. 提取价格 (与排名监控爬虫保持一致)
-            try:
-                price_elem = page.locator('.a-price .a-offscreen').first
-                if await page.locator('.a-price .a-offscreen').count() > 0:
-                    price = await price_elem.text_content()
-                    if price:
-                        result['price'] = price.strip()
-                        print(f"[DEBUG] 价格: {result['price']}", file=sys.stderr)
-            except Exception as e:
-                print(f"[DEBUG] 提取价格失败: {e}", file=sys.stderr)
-
-            # 3. 提取评分 (使用更精确的选择器，避免获取到变体或其他产品的评分)
-            try:
-                rating_found = False
-                # 方法1: 从主评分区域获取 (最可靠)
-                rating_selectors = [
-                    '#acrPopover .a-icon-alt',  # 主评分弹出框
-                    '#averageCustomerReviews .a-icon-alt',  # 平均评分区域
-                    '#acrPopover span.a-icon-alt',
-                    '#cm_cr_dp_d_rating_histogram .a-icon-alt',  # 评分直方图
-                ]
-                for selector in rating_selectors:
-                    try:
-                        rating_elem = page.locator(selector).first
-                        if await rating_elem.count() > 0:
-                            rating_text = await rating_elem.text_content() or ''
-                            rating_match = re.search(r'([\d,\.]+)', rating_text)
-                            if rating_match:
-                                result['rating'] = rating_match.group(1).replace(',', '.')
-                                print(f"[DEBUG] 评分: {result['rating']} (from {selector})", file=sys.stderr)
-                                rating_found = True
-                                break
-                    except:
-                        continue
-
-                # 方法2: 如果上面没找到，使用更宽泛的选择器但限定在评论区域
-                if not rating_found:
-                    rating_elem = page.locator('#reviewsMedley .a-icon-star .a-icon-alt, #averageCustomerReviews_feature_div .a-icon-alt').first
-                    if await rating_elem.count() > 0:
-                        rating_text = await rating_elem.text_content() or ''
-                        rating_match = re.search(r'([\d,\.]+)', rating_text)
-                        if rating_match:
-                            result['rating'] = rating_match.group(1).replace(',', '.')
-                            print(f"[DEBUG] 评分: {result['rating']} (from reviewsMedley)", file=sys.stderr)
-            except Exception as e:
-                print(f"[DEBUG] 提取评分失败: {e}", file=sys.stderr)
-
-            # 4. 提取评论数 (与排名监控爬虫保持一致)
-            try:
-                reviews_elem = page.locator('#acrCustomerReviewText').first
-                if await reviews_elem.count() > 0:
-                    reviews_text = await reviews_elem.text_content() or ''
-                    # 提取数字，处理各种格式如 "2,989", "2.989", "2989"
-                    num_match = re.search(r'([\d,.\s]+)', reviews_text)
-                    if num_match:
-                        num_str = num_match.group(1).strip()
-                        # 移除空格和千位分隔符
-                        num_str = re.sub(r'[\s,.]', '', num_str)
-                        if num_str.isdigit():
-                            result['review_count'] = int(num_str)
-                            print(f"[DEBUG] 评论数: {result['review_count']}", file=sys.stderr)
-            except Exception as e:
-                print(f"[DEBUG] 提取评论数失败: {e}", file=sys.stderr)
-
-            # 5. 提取 BSR 排名（包含类目名称）
-            try:
-                # 正则表达式：同时捕获排名数字和类目名称
-                # 各语言格式:
-                # 法语: "7 en Fraises à ongles électriques"
-                # 英语: "#7 in Nail Files"
-                # 德语: "Nr. 7 in Elektrische Nagelfeilen"
-                # 意大利语: "n. 7 in Lime elettriche per unghie"
-                # 西班牙语: "nº 7 en Limas de uñas eléctricas"
-                # 日语: "ビューティー - 116,737位" 或 "ヘアカーリングワンド - 11位"
-                bsr_with_category_patterns = [
-                    # 法语: 数字 en 类目
-                    r'(\d[\d.,]*)\s+en\s+([A-ZÀ-Úa-zà-ú][^\n\(#]{3,80})',
-                    # 英语/美国: #数字 in 类目
-                    r'#\s*(\d[\d.,]*)\s+in\s+([A-Za-zÀ-ú][^\n\(#]{3,80})',
-                    # 英语/英国: 数字 in 类目（无 # 前缀）
-                    r'(\d[\d,]*)\s+in\s+([A-Za-z][^\n\(#]{3,80})',
-                    # 德语: Nr. 数字 in 类目
-                    r'Nr\.?\s*(\d[\d.,]*)\s+in\s+([A-ZÄÖÜa-zäöüß][^\n\(#]{3,80})',
-                    # 意大利语: n. 数字 in 类目
-                    r'[nN]\.?\s*(\d[\d.,]*)\s+in\s+([A-Za-zÀ-ú][^\n\(#]{3,80})',
-                    # 西班牙语: nº 数字 en 类目
-                    r'[nN][º°]?\s*(\d[\d.,]*)\s+en\s+([A-Za-zÀ-ú][^\n\(#]{3,80})',
-                ]
-
-                # 日本站特殊处理：格式为 "类目 - 数字位"
-                # 注意：类目名可以包含长音符号 ー，分隔符是空格包围的短横线 " - "
-                jp_bsr_pattern = r'([ぁ-んァ-ヶー一-龥a-zA-Z&]+)\s+[-‐−]\s+(\d[\d,]*)\s*位'
-
-                def extract_bsr_with_category(text, is_japan=False):
-                    """从文本中提取所有 BSR 排名及其类目，返回排名最小的那个"""
-                    all_bsr = []
-
-                    # 日本站特殊处理：格式为 "类目 - 数字位"
-                    if is_japan:
-                        jp_matches = re.findall(jp_bsr_pattern, text)
-                        for category, rank_str in jp_matches:
-                            num_str = re.sub(r'[\s,]', '', rank_str)
-                            if num_str.isdigit():
-                                category = category.strip()
-                                # 清理日本站的类目名称，移除末尾的"の売れ筋ランキングを見る"等
-                                category = re.sub(r'\s*[（\(].*[）\)].*$', '', category)
-                                category = category.strip()
-                                print(f"[DEBUG] BSR 匹配(JP): rank={num_str}, 类目='{category}'", file=sys.stderr)
-                                if len(category) >= 2:
-                                    all_bsr.append((int(num_str), category))
-
-                    # 其他国家的处理
-                    for pattern in bsr_with_category_patterns:
-                        matches = re.findall(pattern, text, re.IGNORECASE | re.MULTILINE)
-                        for rank_str, category in matches:
-                            num_str = re.sub(r'[\s,.]', '', rank_str)
-                            if num_str.isdigit():
-                                original_category = category.strip()
-                                category = original_category
-
-                                # 优先提取 "nella categoria X" 中的 X（意大利语）
-                                nella_match = re.search(r'nella categoria\s+(.+)$', category, re.IGNORECASE)
-                                if nella_match:
-                                    category = nella_match.group(1).strip()
-                                else:
-                                    # 提取 "in der Kategorie X" 中的 X（德语）
-                                    der_match = re.search(r'in der Kategorie\s+(.+)$', category, re.IGNORECASE)
-                                    if der_match:
-                                        category = der_match.group(1).strip()
-                                    else:
-                                        # 清理 "Top 100 in X" -> "X"
-                                        category = re.sub(r'^Top\s+\d+\s+in\s+', '', category, flags=re.IGNORECASE)
-                                        # 清理尾部的 "Voir/See/Visualizza..."
-                                        category = re.sub(r'\s*(Voir|See|Mehr|Ver|Vedi|Visualizza).*$', '', category, flags=re.IGNORECASE)
-
-                                category = category.strip()
-                                print(f"[DEBUG] BSR 匹配: rank={num_str}, 原始类目='{original_category}' -> 清理后='{category}'", file=sys.stderr)
-                                if len(category) > 3:
-                                    all_bsr.append((int(num_str), category))
-
-                    if all_bsr:
-                        print(f"[DEBUG] BSR 所有匹配: {all_bsr}", file=sys.stderr)
-                        # 返回排名最小的（子类目排名通常最小）
-                        all_bsr.sort(key=lambda x: x[0])
-                        return all_bsr[0]
-                    return None
-
-                # 方法1: 从特定 ID 的产品详情表格提取
-                bsr_id_selectors = [
-                    '#productDetails_detailBullets_sections1',
-                    '#detailBulletsWrapper_feature_div',
-                    '#prodDetails',
-                    '#detailBullets_feature_div',
-                ]
-                is_japan = country == 'JP'
-                for selector in bsr_id_selectors:
-                    try:
-                        detail_elem = page.locator(selector).first
-                        if await detail_elem.count() > 0:
-                            detail_text = await detail_elem.inner_text()
-                            bsr_info = extract_bsr_with_category(detail_text, is_japan=is_japan)
-                            if bsr_info:
-                                rank, category = bsr_info
-                                result['bsr_rank'] = f"#{rank} in {category}"
-                                print(f"[DEBUG] BSR: {result['bsr_rank']} (from {selector})", file=sys.stderr)
-                                break
-                    except Exception as e:
-                        print(f"[DEBUG] BSR选择器 {selector} 失败: {e}", file=sys.stderr)
-                        continue
-
-                # 方法2: 遍历所有 .prodDetTable 表格
-                if not result['bsr_rank']:
-                    try:
-                        tables = page.locator('.prodDetTable')
-                        table_count = await tables.count()
-                        print(f"[DEBUG] 找到 {table_count} 个 prodDetTable", file=sys.stderr)
-                        for i in range(table_count):
-                            table = tables.nth(i)
-                            table_text = await table.inner_text()
-                            bsr_info = extract_bsr_with_category(table_text, is_japan=is_japan)
-                            if bsr_info:
-                                rank, category = bsr_info
-                                result['bsr_rank'] = f"#{rank} in {category}"
-                                print(f"[DEBUG] BSR: {result['bsr_rank']} (from table {i})", file=sys.stderr)
-                                break
-                    except Exception as e:
-                        print(f"[DEBUG] 遍历表格失败: {e}", file=sys.stderr)
-
-                # 方法3: 如果上面没找到，尝试从整个页面搜索
-                if not result['bsr_rank']:
-                    page_content = await page.content()
-                    bsr_info = extract_bsr_with_category(page_content, is_japan=is_japan)
-                    if bsr_info:
-                        rank, category = bsr_info
-                        result['bsr_rank'] = f"#{rank} in {category}"
-                        print(f"[DEBUG] BSR (页面): {result['bsr_rank']}", file=sys.stderr)
-            except Exception as e:
-                print(f"[DEBUG] 提取BSR失败: {e}", file=sys.stderr)
-
-            # 5.5 提取上架时间 (Date First Available)
-            try:
-                # 各语言格式:
-                # 英语: "Date First Available : January 1, 2020" 或 "Date First Available: 1 Jan. 2020"
-                # 德语: "Im Angebot von Amazon.de seit : 1. Januar 2020"
-                # 法语: "Date de mise en ligne sur Amazon.fr : 1 janvier 2020"
-                # 意大利语: "Disponibile su Amazon.it a partire dal : 1 gennaio 2020"
-                # 西班牙语: "Fecha de disponibilidad en Amazon.es : 1 de enero de 2020"
-                # 日语: "Amazon.co.jpでの取り扱い開始日 : 2020/1/1"
-                date_patterns = [
-                    # 英语 (US/UK/CA/AU)
-                    r'Date First Available\s*[:\-]?\s*([A-Za-z]+\.?\s*\d{1,2},?\s*\d{4}|\d{1,2}\s+[A-Za-z]+\.?\s*\d{4})',
-                    # 德语
-                    r'Im Angebot von Amazon\.de seit\s*[:\-]?\s*(\d{1,2}\.?\s*[A-Za-zäöüÄÖÜß]+\.?\s*\d{4})',
-                    r'Erstmals im Angebot auf Amazon\.de\s*[:\-]?\s*(\d{1,2}\.?\s*[A-Za-zäöüÄÖÜß]+\.?\s*\d{4})',
-                    # 法语
-                    r'Date de mise en ligne sur Amazon\.fr\s*[:\-]?\s*(\d{1,2}\s+[a-zéèàùâêîôûäëïöü]+\.?\s*\d{4})',
-                    r'Disponible sur Amazon\.fr depuis le\s*[:\-]?\s*(\d{1,2}\s+[a-zéèàùâêîôûäëïöü]+\.?\s*\d{4})',
-                    # 意大利语
-                    r'Disponibile su Amazon\.it\s+(?:a partire\s+)?dal\s*[:\-]?\s*(\d{1,2}\s+[a-z]+\.?\s*\d{4})',
-                    # 西班牙语 - "Producto en Amazon.es desde : 18 febrero 2023"
-                    r'Producto en Amazon\.es desde\s*[:\-‏‎\s]*(\d{1,2}\s+(?:de\s+)?[a-z]+(?:\s+de)?\s+\d{4})',
-                    r'Fecha de disponibilidad en Amazon\.es\s*[:\-]?\s*(\d{1,2}\s+(?:de\s+)?[a-z]+(?:\s+de)?\s+\d{4})',
-                    # 日语 - 注意 Amazon.co.jp 和 での 之间可能有空格
-                    r'Amazon\.co\.jp\s*での取り扱い開始日\s*[:\-]?\s*(\d{4}[/年]\d{1,2}[/月]\d{1,2}日?)',
-                    # 日语备用格式
-                    r'取り扱い開始日\s*[:\-]?\s*(\d{4}[/年]\d{1,2}[/月]\d{1,2}日?)',
-                ]
-
-                def extract_date_first_available(text):
-                    for pattern in date_patterns:
-                        match = re.search(pattern, text, re.IGNORECASE)
-                        if match:
-                            date_str = match.group(1).strip()
-                            print(f"[DEBUG] 上架时间匹配: '{date_str}'", file=sys.stderr)
-                            return date_str
-                    return None
-
-                # 从产品详情表格提取
-                for selector in bsr_id_selectors:
-                    try:
-                        detail_elem = page.locator(selector).first
-                        if await detail_elem.count() > 0:
-                            detail_text = await detail_elem.inner_text()
-                            date_str = extract_date_first_available(detail_text)
-                            if date_str:
-                                result['date_first_available'] = date_str
-                                print(f"[DEBUG] 上架时间: {date_str} (from {selector})", file=sys.stderr)
-                                break
-                    except:
-                        continue
+        except Exception as e:
+            result['error'] = str(e)
+            print(f"[DEBUG] 爬取失败: {e}", file=sys.stderr)
 
-                # 如果没找到，尝试从整个页面搜索
-                if not result['date_first_available']:
-                    page_content = await page.content()
-                    date_str = extract_date_first_available(page_content)
-                    if date_str:
-                        result['date_first_available'] = date_str
-                        print(f"[DEBUG] 上架时间 (页面): {date_str}", file=sys.stderr)
+        finally:
+            await browser.close()
 
-            except Exception as e:
-                print(f"[DEBUG] 提取上架时间失败: {e}", file=sys.stderr)
+    return result
 
-            # 6. 提取五点描述 (Bullet Points)
-            try:
-                # 尝试多个选择器（从最精确到最宽泛）
-                bullet_selectors = [
-                    '#feature-bullets ul li span.a-list-item',
-                    '#featurebullets_feature_div ul li span.a-list-item',
-                    '#productFactsDesktopExpander ul li span.a-list-item',
-                    # 备用：关于此商品区域
-                    '#productFactsDesktop_feature_div ul li',
-                ]
-                for selector in bullet_selectors:
-                    bullets_elem = page.locator(selector)
-                    count = await bullets_elem.count()
-                    print(f"[DEBUG] 尝试选择器 {selector}: 找到 {count} 个", file=sys.stderr)
-                    if count > 0:
-                        bullets_list = await bullets_elem.all()
-                        for bullet in bullets_list:
-                            text = await bullet.text_content()
-                            if text:
-                                text = text.strip()
-                                # 清理多余空白
-                                text = re.sub(r'\s+', ' ', text)
-                                # 过滤掉无关内容
-                                if (text and len(text) > 15
-                                    and not text.startswith('Make sure')
-                                    and not text.startswith('See more')
-                                    and 'out of 5 stars' not in text
-                                    and not text.startswith('Product Dimensions')
-                                    and not text.startswith('Item model')
-                                    and not text.startswith('Manufacturer')
-                                    and not text.startswith('ASIN')
-                                    and not text.startswith('Customer Reviews')
-                                    and 'Reviewed in' not in text
-                                    and 'star' not in text[:20]):
-                                    result['bullets'].append(text)
-                        if result['bullets']:
-                            print(f"[DEBUG] 五点描述: {len(result['bullets'])} 条", file=sys.stderr)
-                            break
 
-                # 如果上面没找到，尝试从页面其他区域获取（宽泛选择器）
-                if not result['bullets']:
-                    # 之前能用的选择器
-                    broad_bullets = page.locator('ul.a-unordered-list.a-vertical li span.a-list-item')
-                    count = await broad_bullets.count()
-                    print(f"[DEBUG] 宽泛选择器: 找到 {count} 个", file=sys.stderr)
-                    if count > 0:
-                        for i in range(min(count, 30)):  # 最多检查30个
-                            item = broad_bullets.nth(i)
-                            text = await item.text_content()
-                            if text:
-                                text = re.sub(r'\s+', ' ', text.strip())
-                                # 过滤掉明显无关的内容
-                                skip_keywords = [
-                                    'out of 5 stars', 'Reviewed in',
-                                    'Product Dimensions', 'Item model',
-                                    'ASIN :', 'Customer Reviews:',
-                                    'P.when(', 'function(A)', 'execute(',
-                                    'dpAcrHas', '63%18%', 'star4 star',
-                                    '<img src=', 'Verified Purchase'
-                                ]
-                                # 文本长度在合理范围且不包含脚本代码
-                                if (30 < len(text) < 400
-                                    and not any(skip in text for skip in skip_keywords)
-                                    and 'var ' not in text
-                                    and 'P.when' not in text):
-                                    result['bullets'].append(text)
-                                    print(f"[DEBUG] 保留五点 {len(result['bullets'])}: {text[:50]}...", file=sys.stderr)
-                                    if len(result['bullets']) >= 7:  # 最多7条
-                                        break
-                        if result['bullets']:
-                            print(f"[DEBUG] 五点描述 (宽泛): {len(result['bullets'])} 条", file=sys.stderr)
-            except Exception as e:
-                print(f"[DEBUG] 提取五点描述失败: {e}", file=sys.stderr)
+async def reparse_listing(asin: str, country: str, page_path: str) -> dict:
+    """
+    从归档的产品详情页重新提取 Listing 信息（不访问网络，解析规则与爬取时一致）
+    """
+    result = {
+        "asin": asin,
+        "country": country,
+        "title": None,
+        "price": None,
+        "rating": None,
+        "review_count": None,
+        "bsr_rank": None,
+        "date_first_available": None,
+        "image_url": None,
+        "bullets": [],
+        "description": None,
+        "fetched_at": datetime.now(timezone.utc).isoformat().replace("+00:00", "Z"),
+        "error": None
+    }
 
-            # 7. 提取商品描述
-            try:
-                # 优先获取纯文本描述，避免 A+ 内容（A+ 通常包含大量 CSS/JS）
-                desc_selectors = [
-                    '#productDescription p',
-                    '#productDescription',
-                ]
-                for selector in desc_selectors:
-                    desc_elem = page.locator(selector)
-                    if await desc_elem.count() > 0:
-                        # 使用 inner_text() 而不是 text_content()，只获取可见文本
-                        desc_text = await desc_elem.inner_text()
-                        if desc_text:
-                            desc_text = desc_text.strip()
-                            # 清理多余空白
-                            desc_text = re.sub(r'\s+', ' ', desc_text)
-                            # 过滤掉明显是 CSS/JS 的内容
-                            if (len(desc_text) > 20
-                                and not desc_text.startswith('.aplus')
-                                and 'function ' not in desc_text
-                                and '{' not in desc_text[:100]):
-                                result['description'] = desc_text[:5000]  # 限制长度
-                                print(f"[DEBUG] 描述: {len(result['description'])} 字符", file=sys.stderr)
-                                break
-            except Exception as e:
-                print(f"[DEBUG] 提取描述失败: {e}", file=sys.stderr)
+    try:
+        html = archive.load(page_path)
+    except Exception as e:
+        result['error'] = f"读取归档页面失败: {e}"
+        return result
 
+    async with async_playwright() as p:
+        browser = await p.chromium.launch(headless=True)
+        try:
+            page = await browser.new_page()
+            # 页面中的图片、脚本等外部资源一律不加载
+            await page.route("**/*", lambda route: route.abort())
+            await page.set_content(html, wait_until="domcontentloaded")
+            await extract_listing_fields(page, result, country)
         except Exception as e:
             result['error'] = str(e)
-            print(f"[DEBUG] 爬取失败: {e}", file=sys.stderr)
-
+            print(f"[DEBUG] 重新解析失败: {e}", file=sys.stderr)
         finally:
             await browser.close()
 
@@ -1206,6 +1277,7 @@ async def fetch_listings_batch(items: list, headless="new") -> list:
                     "bullets": [],
                     "description": None,
                     "fetched_at": datetime.now(timezone.utc).isoformat().replace("+00:00", "Z"),
+                    "location": zipcode_for(country),
                     "error": None
                 }
 
@@ -1266,7 +1338,7 @@ async def fetch_listings_batch(items: list, headless="new") -> list:
                     # 设置配送地址（每个国家只需设置一次）
                     if not address_set:
                         try:
-                            address_success, address_text = await set_delivery_address(page, country, config['zipcode'], max_retries=3)
+                            address_success, address_text = await set_delivery_address(page, country, result['location'], max_retries=3)
                             if address_success:
                                 print(f"[DEBUG] 配送地址设置成功: {address_text}", file=sys.stderr)
                                 address_set = True
@@ -1303,6 +1375,9 @@ async def fetch_listings_batch(items: list, headless="new") -> list:
                         except Exception as e:
                             print(f"[DEBUG] 设置货币出错: {e}", file=sys.stderr)
 
+                    if archive.enabled():
+                        archive.save("listing", country, asin, await page.content())
+
                     # 提取产品信息（与单个爬取一致）
                     # 1. 标题 (使用 span#productTitle 避免匹配到 hidden input)
                     try:
@@ -1539,16 +1614,8 @@ async def fetch_listings_batch(items: list, headless="new") -> list:
 
                 results.append((competitor_id, result))
 
-                # 输出进度
-                progress = {
-                    "type": "progress",
-                    "competitor_id": competitor_id,
-                    "result": result
-                }
-                output = json.dumps(progress, ensure_ascii=False)
-                sys.stdout.buffer.write(output.encode('utf-8'))
-                sys.stdout.buffer.write(b'\n')
-                sys.stdout.flush()
+                # 输出结果
+                protocol.result(result, competitor_id)
 
                 # 产品间隔（同一浏览器内切换页面，间隔可以短一些）
                 if total_idx < total_count:
@@ -1563,11 +1630,9 @@ async def fetch_listings_batch(items: list, headless="new") -> list:
 
 
 def main():
+    protocol.handshake(__file__, SCRIPT_VERSION)
     if len(sys.argv) < 2:
-        print(json.dumps({
-            "error": "用法: python amazon_listing_crawler.py <asin> <country> [headless]\n或: python amazon_listing_crawler.py --batch [headless]"
-        }))
-        sys.exit(1)
+        protocol.fail("用法: python amazon_listing_crawler.py <asin> <country> [headless]\n或: python amazon_listing_crawler.py --batch [headless]\n或: python amazon_listing_crawler.py --reparse <asin> <country> <page_path>")
 
     # 批量模式：从stdin读取JSON
     if sys.argv[1] == '--batch':
@@ -1586,18 +1651,21 @@ def main():
         results = asyncio.run(fetch_listings_batch(items, headless))
 
         # 输出完成标记
-        complete = {"type": "complete", "total": len(results)}
-        output = json.dumps(complete, ensure_ascii=False)
-        sys.stdout.buffer.write(output.encode('utf-8'))
-        sys.stdout.buffer.write(b'\n')
+        protocol.complete(len(results))
+        sys.exit(0)
+
+    # 重新解析归档页面: --reparse <asin> <country> <页面路径>
+    if sys.argv[1] == '--reparse':
+        if len(sys.argv) < 5:
+            protocol.fail("用法: python amazon_listing_crawler.py --reparse <asin> <country> <page_path>")
+        result = asyncio.run(reparse_listing(sys.argv[2], sys.argv[3].upper(), sys.argv[4]))
+        protocol.result(result)
+        protocol.complete(1)
         sys.exit(0)
 
     # 单个 ASIN 模式
     if len(sys.argv) < 3:
-        print(json.dumps({
-            "error": "用法: python amazon_listing_crawler.py <asin> <country> [headless]"
-        }))
-        sys.exit(1)
+        protocol.fail("用法: python amazon_listing_crawler.py <asin> <country> [headless]")
 
     asin = sys.argv[1]
     country = sys.argv[2].upper()
@@ -1611,9 +1679,8 @@ def main():
 
     result = asyncio.run(fetch_listing_info(asin, country, headless))
 
-    output = json.dumps(result, ensure_ascii=False)
-    sys.stdout.buffer.write(output.encode('utf-8'))
-    sys.stdout.buffer.write(b'\n')
+    protocol.result(result)
+    protocol.complete(1)
 
 
 if __name__ == "__main__":
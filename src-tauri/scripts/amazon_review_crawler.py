@@ -14,13 +14,14 @@ import re
 import asyncio
 from datetime import datetime, timezone
 
+import crawler_protocol as protocol
+
+SCRIPT_VERSION = "2.0"
+
 try:
     from playwright.async_api import async_playwright
 except ImportError:
-    print(json.dumps({
-        "error": "缺少 Playwright. 请运行: pip install playwright && playwright install chromium"
-    }))
-    sys.exit(1)
+    protocol.fail("缺少 Playwright. 请运行: pip install playwright && playwright install chromium")
 
 # 站点配置
 COUNTRY_CONFIG = {
@@ -250,11 +251,9 @@ async def fetch_reviews_from_product_page(asin: str, country: str, headless="new
 
 
 def main():
+    protocol.handshake(__file__, SCRIPT_VERSION)
     if len(sys.argv) < 3:
-        print(json.dumps({
-            "error": "用法: python amazon_review_crawler.py <asin> <country> [headless]"
-        }))
-        sys.exit(1)
+        protocol.fail("用法: python amazon_review_crawler.py <asin> <country> [headless]")
 
     asin = sys.argv[1]
     country = sys.argv[2].upper()
@@ -269,14 +268,8 @@ def main():
 
     result = asyncio.run(fetch_reviews_from_product_page(asin, country, headless))
 
-    # 输出完成标记
-    complete = {
-        "type": "complete",
-        "result": result
-    }
-    output = json.dumps(complete, ensure_ascii=False)
-    sys.stdout.buffer.write(output.encode('utf-8'))
-    sys.stdout.buffer.write(b'\n')
+    protocol.result(result)
+    protocol.complete(1)
 
 
 if __name__ == "__main__":
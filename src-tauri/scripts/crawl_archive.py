@@ -0,0 +1,120 @@
+#!/usr/bin/env python3
+# -*- coding: utf-8 -*-
+"""
+爬取页面归档（Rust 端见 src/crawl_archive.rs）
+
+应用开启归档时通过环境变量 THESAURUS_CRAWL_ARCHIVE 传入归档根目录，页面按 UTC 日期 gzip 保存：
+    <根目录>/<YYYY-MM-DD>/search/<站点>/<关键词>/<HHMMSS>-<引擎>[-<邮编>]/page<N>.html.gz
+    <根目录>/<YYYY-MM-DD>/listing/<站点>/<ASIN>.html.gz
+同一关键词每次检测单独一个目录，meta.json 记录引擎、关键词和邮编，重新解析时据此选择解析规则
+关键词和 ASIN 的目录名规则与 Rust 端 fixture_slug 一致；归档失败只写 stderr，不影响爬取
+"""
+
+import gzip
+import json
+import os
+import sys
+from datetime import datetime, timezone
+
+ARCHIVE_DIR_ENV = "THESAURUS_CRAWL_ARCHIVE"
+RUN_META_FILE = "meta.json"
+ENGINE_PLAYWRIGHT = "playwright"
+
+
+def enabled():
+    return bool(os.environ.get(ARCHIVE_DIR_ENV, "").strip())
+
+
+def slug(value):
+    """小写，字母数字以外的字符替换为 "-" """
+    out = []
+    for ch in str(value).strip().lower():
+        if ch.isalnum():
+            out.append(ch)
+        elif not out or out[-1] != '-':
+            out.append('-')
+    return ''.join(out).strip('-')
+
+
+def _write(path, html):
+    try:
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        with gzip.open(path, 'wt', encoding='utf-8') as f:
+            f.write(html)
+    except Exception as e:
+        print(f"[DEBUG] 归档页面失败 {path}: {e}", file=sys.stderr)
+
+
+def search_run(country, keyword, location=None, engine=ENGINE_PLAYWRIGHT):
+    """开始归档一次关键词检测，返回本次检测的目录；未开启归档或创建失败时返回 None"""
+    root = os.environ.get(ARCHIVE_DIR_ENV, "").strip()
+    if not root:
+        return None
+    now = datetime.now(timezone.utc)
+    keyword_dir = os.path.join(root, now.strftime("%Y-%m-%d"), "search", country.upper(), slug(keyword))
+    base = f"{now.strftime('%H%M%S')}-{engine}"
+    if location and slug(location):
+        base = f"{base}-{slug(location)}"
+    meta = {"engine": engine, "keyword": keyword, "location": location, "started_at": now.isoformat()}
+    try:
+        os.makedirs(keyword_dir, exist_ok=True)
+        n = 1
+        while True:
+            # 同一秒内重复检测时加 -2、-3 后缀
+            run_dir = os.path.join(keyword_dir, base if n == 1 else f"{base}-{n}")
+            try:
+                os.mkdir(run_dir)
+                break
+            except FileExistsError:
+                n += 1
+        with open(os.path.join(run_dir, RUN_META_FILE), 'w', encoding='utf-8') as f:
+            json.dump(meta, f, ensure_ascii=False)
+        return run_dir
+    except Exception as e:
+        print(f"[DEBUG] 创建归档目录失败 {keyword_dir}: {e}", file=sys.stderr)
+        return None
+
+
+def save_page(run_dir, page_num, html):
+    """保存一页搜索结果到 search_run 返回的目录；run_dir 为 None 时不保存"""
+    if run_dir and html:
+        _write(os.path.join(run_dir, f"page{page_num}.html.gz"), html)
+
+
+def save(kind, country, name, html):
+    """保存一个 Listing 等单页面（kind 为目录名）"""
+    root = os.environ.get(ARCHIVE_DIR_ENV, "").strip()
+    if not root or not html:
+        return
+    day = datetime.now(timezone.utc).strftime("%Y-%m-%d")
+    _write(os.path.join(root, day, kind, country.upper(), f"{slug(name)}.html.gz"), html)
+
+
+def run_pages(run_dir):
+    """检测目录中的页面: {页码: 路径}"""
+    pages = {}
+    for name in os.listdir(run_dir):
+        if name.startswith("page") and name.endswith(".html.gz"):
+            try:
+                pages[int(name[len("page"):-len(".html.gz")])] = os.path.join(run_dir, name)
+            except ValueError:
+                continue
+    return pages
+
+
+def run_meta(run_dir):
+    """读取检测目录的 meta.json，不存在时返回空字典"""
+    try:
+        with open(os.path.join(run_dir, RUN_META_FILE), encoding='utf-8') as f:
+            return json.load(f)
+    except (OSError, ValueError):
+        return {}
+
+
+def load(path):
+    """读取归档页面（.gz 或未压缩的 .html）"""
+    if path.endswith('.gz'):
+        with gzip.open(path, 'rt', encoding='utf-8') as f:
+            return f.read()
+    with open(path, encoding='utf-8') as f:
+        return f.read()
@@ -0,0 +1,70 @@
+#!/usr/bin/env python3
+# -*- coding: utf-8 -*-
+"""
+爬虫脚本与应用之间的输出协议（Rust 端见 src/crawler_protocol.rs）
+
+stdout 每行一条 JSON 消息，第一条必须是 handshake：
+    {"type": "handshake", "protocol": 1, "script": "amazon_listing_crawler", "version": "2.0"}
+    {"type": "progress", "completed": 3, "total": 10}        心跳/进度
+    {"type": "result", "id": 12, "result": {...}}            单项结果（单项模式 id 为 null）
+    {"type": "log", "message": "..."}
+    {"type": "warning", "message": "..."}
+    {"type": "error", "message": "...", "fatal": false}      fatal=true 表示脚本无法继续
+    {"type": "complete", "total": 10}                        全部完成
+
+调试信息写 stderr（应用会记入任务日志），stdout 只输出协议消息
+修改消息格式时与 Rust 端的 PROTOCOL_VERSION 一起递增
+"""
+
+import json
+import os
+import sys
+import threading
+
+PROTOCOL_VERSION = 1
+
+_lock = threading.Lock()
+
+
+def emit(msg_type, **fields):
+    """输出一条消息（线程安全，并发任务共用）"""
+    line = json.dumps({"type": msg_type, **fields}, ensure_ascii=False)
+    with _lock:
+        sys.stdout.buffer.write(line.encode('utf-8'))
+        sys.stdout.buffer.write(b'\n')
+        sys.stdout.flush()
+
+
+def handshake(script_file, version):
+    emit("handshake", protocol=PROTOCOL_VERSION,
+         script=os.path.splitext(os.path.basename(script_file))[0], version=version)
+
+
+def progress(completed, total):
+    emit("progress", completed=completed, total=total)
+
+
+def result(value, item_id=None):
+    emit("result", id=item_id, result=value)
+
+
+def log(message):
+    emit("log", message=message)
+
+
+def warning(message):
+    emit("warning", message=message)
+
+
+def error(message, fatal=False):
+    emit("error", message=message, fatal=fatal)
+
+
+def complete(total, **fields):
+    emit("complete", total=total, **fields)
+
+
+def fail(message):
+    """致命错误：输出后以状态码 1 退出"""
+    error(message, fatal=True)
+    sys.exit(1)
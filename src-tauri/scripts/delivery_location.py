@@ -0,0 +1,55 @@
+#!/usr/bin/env python3
+# -*- coding: utf-8 -*-
+"""
+配送地址（邮编）设置（Rust 端见 src/delivery_location.rs）
+
+应用通过环境变量 THESAURUS_DELIVERY_LOCATIONS 传入各站点的邮编 {"US": "90001", ...}；
+排名批量任务的每一项还可以单独指定邮编（同一关键词监控多个地区），优先级：任务项 > 站点设置 > 脚本默认
+"""
+
+import json
+import os
+import re
+
+LOCATIONS_ENV = "THESAURUS_DELIVERY_LOCATIONS"
+
+_locations = None
+
+
+def _load():
+    global _locations
+    if _locations is None:
+        try:
+            _locations = json.loads(os.environ.get(LOCATIONS_ENV) or "{}")
+        except ValueError:
+            _locations = {}
+    return _locations
+
+
+def zipcode(country, default, override=None):
+    """站点使用的邮编"""
+    if override and str(override).strip():
+        return str(override).strip()
+    value = _load().get(country.upper())
+    return value.strip() if isinstance(value, str) and value.strip() else default
+
+
+def clean_address(text):
+    """页头地址文本：去掉首尾空白和末尾的 &zwnj; 等零宽字符（str.strip 不会去掉）"""
+    return re.sub(r"[\u200b-\u200d\ufeff]", "", text or "").strip()
+
+
+def address_matches(address, zipcode, default_zipcode, expected_keywords):
+    """
+    页面显示的配送地址是否与邮编一致
+    默认邮编时出现站点的城市/国家名即可；自定义邮编必须能看到邮编（英国等只显示邮编前半部分）
+    """
+    text = (address or "").lower()
+    if not text:
+        return False
+    if zipcode == default_zipcode and any(kw.lower() in text for kw in expected_keywords):
+        return True
+    code = (zipcode or "").strip().lower()
+    if not code:
+        return False
+    return code.replace(' ', '') in text.replace(' ', '') or code.split()[0] in text
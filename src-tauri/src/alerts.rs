@@ -0,0 +1,438 @@
+// 排名告警规则：每次排名检测写入后按规则判断是否触发，触发的告警写入告警记录并按渠道发送
+// 规则条件：
+//   rank_crosses   自然排名越过第 N 名（进入或跌出）
+//   rank_drop      相比近 Y 天最佳排名下降超过 X 名（或跌出榜单）
+//   sponsored_lost 广告位丢失
+//   price_changed  价格变化超过 X%（0 表示任意变化）
+//   rating_below   评分跌破 Z
+
+use std::collections::{BTreeMap, HashMap};
+
+use tauri_plugin_notification::NotificationExt;
+
+use crate::channels;
+use crate::db;
+
+pub const CONDITION_TYPES: &[&str] = &["rank_crosses", "rank_drop", "sponsored_lost", "price_changed", "rating_below"];
+pub const CHANNELS: &[&str] = &["desktop", "none"];  // 桌面通知；外部渠道见 channel_id
+
+// 一次检测前/后的监控项状态
+#[derive(Debug, Clone, Default)]
+pub struct CheckSnapshot {
+    pub checked: bool,  // 是否已有检测结果（首次检测前为 false）
+    pub organic_rank: Option<i64>,      // 页内排名
+    pub organic_page: Option<i64>,
+    pub organic_position: Option<i64>,  // 各页累计后的绝对位置
+    pub sponsored_rank: Option<i64>,
+    pub price: Option<String>,
+    pub rating: Option<f64>,
+}
+
+impl CheckSnapshot {
+    pub fn organic(&self) -> Option<RankPosition> {
+        RankPosition::new(self.organic_rank, self.organic_page, self.organic_position)
+    }
+}
+
+// 自然排名：页码 + 页内排名，以及绝对位置（旧记录第 1 页之后没有）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RankPosition {
+    pub page: i64,
+    pub rank: i64,
+    pub position: Option<i64>,
+}
+
+impl RankPosition {
+    pub fn new(rank: Option<i64>, page: Option<i64>, position: Option<i64>) -> Option<Self> {
+        let rank = rank?;
+        let page = page.unwrap_or(1);
+        // 第 1 页的页内排名就是绝对位置
+        Some(Self { page, rank, position: position.or((page == 1).then_some(rank)) })
+    }
+
+    // 是否在前 n 名；没有绝对位置（旧记录的后续页）时视为不在
+    fn within(&self, n: i64) -> bool {
+        self.position.is_some_and(|p| p <= n)
+    }
+
+    // 按 (页码, 页内排名) 比较先后
+    fn key(&self) -> (i64, i64) {
+        (self.page, self.rank)
+    }
+}
+
+// 多次检测中最靠前的自然排名
+pub fn best_position(positions: impl IntoIterator<Item = RankPosition>) -> Option<RankPosition> {
+    positions.into_iter().min_by_key(|p| p.key())
+}
+
+// 校验规则参数
+pub fn validate_alert_rule(input: &db::AlertRuleInput) -> Result<(), String> {
+    if input.name.trim().is_empty() {
+        return Err("规则名称不能为空".to_string());
+    }
+    if !CONDITION_TYPES.contains(&input.condition_type.as_str()) {
+        return Err(format!("不支持的告警条件: {}", input.condition_type));
+    }
+    if !CHANNELS.contains(&input.channel.as_str()) {
+        return Err(format!("不支持的通知渠道: {}", input.channel));
+    }
+    let threshold = input.threshold.unwrap_or(0.0);
+    match input.condition_type.as_str() {
+        "rank_crosses" | "rank_drop" if threshold < 1.0 => Err("排名阈值必须大于 0".to_string()),
+        "rank_drop" if input.window_days.unwrap_or(0) < 1 => Err("统计天数必须大于 0".to_string()),
+        "price_changed" if threshold < 0.0 => Err("价格变化百分比不能为负数".to_string()),
+        "rating_below" if !(0.0..=5.0).contains(&threshold) || threshold == 0.0 => {
+            Err("评分阈值应在 0-5 之间".to_string())
+        }
+        _ => Ok(()),
+    }
+}
+
+// 被检测的监控项
+#[derive(Debug, Clone)]
+pub struct AlertTarget {
+    pub product_id: i64,
+    pub keyword: String,
+    pub country: String,
+    pub tags: Option<String>,
+}
+
+// 规则是否作用于该监控项（关键词不区分大小写；标签匹配 tags JSON 数组中的值）
+pub fn rule_matches_scope(rule: &db::AlertRule, item: &AlertTarget) -> bool {
+    if rule.product_id.is_some_and(|pid| pid != item.product_id) {
+        return false;
+    }
+    if rule.country.as_deref().is_some_and(|c| !c.eq_ignore_ascii_case(&item.country)) {
+        return false;
+    }
+    if rule.keyword.as_deref().is_some_and(|k| !k.trim().eq_ignore_ascii_case(item.keyword.trim())) {
+        return false;
+    }
+    if let Some(tag) = rule.tag.as_deref() {
+        let tags: Vec<String> = item
+            .tags
+            .as_deref()
+            .and_then(|t| serde_json::from_str(t).ok())
+            .unwrap_or_default();
+        if !tags.iter().any(|t| t == tag) {
+            return false;
+        }
+    }
+    true
+}
+
+// 解析价格文本（"$19.99"、"19,99 €"、"£1,299.00"、"1.299,00 €"）
+pub fn parse_price(text: &str) -> Option<f64> {
+    let cleaned: String = text.chars().filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',').collect();
+    if cleaned.is_empty() {
+        return None;
+    }
+    // 最后出现的分隔符后恰好 1-2 位数字时视为小数点，其余分隔符为千分位
+    let normalized = match cleaned.rfind(['.', ',']) {
+        Some(pos) if (1..=2).contains(&(cleaned.len() - pos - 1)) => {
+            let (int_part, frac_part) = cleaned.split_at(pos);
+            format!("{}.{}", int_part.replace(['.', ','], ""), &frac_part[1..])
+        }
+        _ => cleaned.replace(['.', ','], ""),
+    };
+    normalized.parse().ok()
+}
+
+// 判断规则是否触发，返回告警内容
+// window_best：rank_drop 条件统计窗口内（不含本次）的最佳自然排名
+// 自然排名按绝对位置比较（每页自然结果数不固定，不能只看页内排名）
+pub fn evaluate_rule(
+    rule: &db::AlertRule,
+    before: &CheckSnapshot,
+    after: &CheckSnapshot,
+    window_best: Option<RankPosition>,
+) -> Option<String> {
+    let threshold = rule.threshold.unwrap_or(0.0);
+    match rule.condition_type.as_str() {
+        "rank_crosses" => {
+            if !before.checked {
+                return None;
+            }
+            let n = threshold as i64;
+            let (old, new) = (before.organic(), after.organic());
+            let was_in = old.is_some_and(|p| p.within(n));
+            let is_in = new.is_some_and(|p| p.within(n));
+            match (was_in, is_in) {
+                (false, true) => Some(format!("进入前 {} 名：{} → {}", n, format_rank(old), format_rank(new))),
+                (true, false) => Some(format!("跌出前 {} 名：{} → {}", n, format_rank(old), format_rank(new))),
+                _ => None,
+            }
+        }
+        "rank_drop" => {
+            let best = best_position(window_best.into_iter().chain(before.organic()))?;
+            let days = rule.window_days.unwrap_or(7);
+            let now = match after.organic() {
+                None if before.checked => {
+                    return Some(format!("跌出榜单（近 {} 天最佳{}）", days, format_rank(Some(best))));
+                }
+                None => return None,
+                Some(now) => now,
+            };
+            // 没有绝对位置时同一页比较页内排名，翻到后面的页视为超过阈值
+            let dropped = match (now.position, best.position) {
+                (Some(a), Some(b)) => Some(a - b),
+                _ if now.page == best.page => Some(now.rank - best.rank),
+                _ => None,
+            };
+            match dropped {
+                Some(d) if d as f64 > threshold => Some(format!(
+                    "近 {} 天下降 {} 名：最佳{} → {}",
+                    days,
+                    d,
+                    format_rank(Some(best)),
+                    format_rank(Some(now))
+                )),
+                None if now.page > best.page => Some(format!(
+                    "近 {} 天下降到第{}页：最佳{} → {}",
+                    days,
+                    now.page,
+                    format_rank(Some(best)),
+                    format_rank(Some(now))
+                )),
+                _ => None,
+            }
+        }
+        "sponsored_lost" => match (before.sponsored_rank, after.sponsored_rank) {
+            (Some(old), None) if before.checked => Some(format!("广告位丢失（原广告第{}位）", old)),
+            _ => None,
+        },
+        "price_changed" => {
+            if !before.checked {
+                return None;
+            }
+            let (old_text, new_text) = (before.price.as_deref()?, after.price.as_deref()?);
+            match (parse_price(old_text), parse_price(new_text)) {
+                (Some(old), Some(new)) if old > 0.0 => {
+                    let pct = (new - old) / old * 100.0;
+                    if (new - old).abs() > f64::EPSILON && pct.abs() >= threshold {
+                        Some(format!("价格变化：{} → {}（{:+.1}%）", old_text, new_text, pct))
+                    } else {
+                        None
+                    }
+                }
+                _ if old_text.trim() != new_text.trim() => Some(format!("价格变化：{} → {}", old_text, new_text)),
+                _ => None,
+            }
+        }
+        "rating_below" => {
+            let new = after.rating?;
+            let was_below = before.rating.is_some_and(|r| r < threshold);
+            if new < threshold && !was_below {
+                Some(format!("评分跌破 {}：{} → {}", threshold, format_rating(before.rating), new))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+// 第 1 页显示名次，后续页带上页码和绝对位置
+fn format_rank(rank: Option<RankPosition>) -> String {
+    match rank {
+        None => "未上榜".to_string(),
+        Some(p) if p.page == 1 => format!("第{}名", p.rank),
+        Some(RankPosition { page, rank, position: Some(pos) }) => format!("第{}页第{}名（第{}位）", page, rank, pos),
+        Some(RankPosition { page, rank, .. }) => format!("第{}页第{}名", page, rank),
+    }
+}
+
+fn format_rating(rating: Option<f64>) -> String {
+    rating.map(|r| r.to_string()).unwrap_or_else(|| "-".to_string())
+}
+
+// 发送尚未发送的告警：桌面通知一次最多弹一条（多条时汇总），外部渠道每个渠道汇总为一条消息
+pub async fn dispatch_pending_alerts<R: tauri::Runtime>(app: &tauri::AppHandle<R>) {
+    let pending = match db::get_undelivered_alerts() {
+        Ok(pending) => pending,
+        Err(e) => {
+            println!("[Alerts] 读取待发送告警失败: {}", e);
+            return;
+        }
+    };
+    if pending.is_empty() {
+        return;
+    }
+
+    // 先标记为已发送，避免外部渠道重试期间被另一次检测重复发送
+    for alert in &pending {
+        let _ = db::mark_alert_delivered(alert.id, None);
+    }
+    let mut errors: HashMap<i64, Vec<String>> = HashMap::new();
+
+    let desktop: Vec<&db::AlertRecord> = pending.iter().filter(|a| a.channel == "desktop").collect();
+    if !desktop.is_empty() {
+        if let Err(e) = send_desktop_alerts(app, &desktop) {
+            for alert in &desktop {
+                errors.entry(alert.id).or_default().push(format!("桌面通知: {}", e));
+            }
+        }
+    }
+
+    let mut by_channel: BTreeMap<i64, Vec<&db::AlertRecord>> = BTreeMap::new();
+    for alert in &pending {
+        if let Some(channel_id) = alert.channel_id {
+            by_channel.entry(channel_id).or_default().push(alert);
+        }
+    }
+    for (channel_id, alerts) in by_channel {
+        let result = match db::get_notification_channel(channel_id) {
+            Ok(Some(channel)) if channel.is_enabled => {
+                channels::send_to_channel(&channel, &alert_message(&alerts)).await
+                    .map_err(|e| format!("{}: {}", channel.name, e))
+            }
+            Ok(_) => Ok(()),  // 渠道已停用或已删除
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            for alert in alerts {
+                errors.entry(alert.id).or_default().push(e.clone());
+            }
+        }
+    }
+
+    for (id, errors) in errors {
+        let _ = db::mark_alert_delivered(id, Some(&errors.join("；")));
+    }
+}
+
+// 告警通知的标题和内容（多条时汇总，最多列出 5 条）
+fn alert_message(alerts: &[&db::AlertRecord]) -> channels::ChannelMessage {
+    let (title, body) = match alerts {
+        [alert] => (format!("🔔 {}", alert.rule_name), format_alert_line(alert)),
+        _ => {
+            let mut lines: Vec<String> = alerts.iter().take(5).map(|a| format_alert_line(a)).collect();
+            if alerts.len() > 5 {
+                lines.push(format!("…另有 {} 条告警", alerts.len() - 5));
+            }
+            (format!("🔔 {} 条排名告警", alerts.len()), lines.join("\n"))
+        }
+    };
+    channels::ChannelMessage { title, body }
+}
+
+fn send_desktop_alerts<R: tauri::Runtime>(app: &tauri::AppHandle<R>, alerts: &[&db::AlertRecord]) -> Result<(), String> {
+    let message = alert_message(alerts);
+
+    app.notification()
+        .builder()
+        .title(&message.title)
+        .body(&message.body)
+        .show()
+        .map_err(|e| e.to_string())
+}
+
+fn format_alert_line(alert: &db::AlertRecord) -> String {
+    format!("{} [{}] {}", alert.keyword, alert.country, alert.message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(condition_type: &str, threshold: f64, window_days: Option<i64>) -> db::AlertRule {
+        db::AlertRule {
+            id: 1,
+            name: "test".to_string(),
+            product_id: None,
+            keyword: None,
+            tag: None,
+            country: None,
+            condition_type: condition_type.to_string(),
+            threshold: Some(threshold),
+            window_days,
+            channel: "desktop".to_string(),
+            channel_id: None,
+            is_enabled: true,
+            created_at: String::new(),
+        }
+    }
+
+    fn snapshot(organic: Option<i64>, sponsored: Option<i64>, price: Option<&str>, rating: Option<f64>) -> CheckSnapshot {
+        CheckSnapshot {
+            checked: true,
+            organic_rank: organic,
+            sponsored_rank: sponsored,
+            price: price.map(|p| p.to_string()),
+            rating,
+            ..Default::default()
+        }
+    }
+
+    fn on_page(page: i64, rank: i64, position: Option<i64>) -> CheckSnapshot {
+        CheckSnapshot {
+            checked: true,
+            organic_rank: Some(rank),
+            organic_page: Some(page),
+            organic_position: position,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_price() {
+        assert_eq!(parse_price("$19.99"), Some(19.99));
+        assert_eq!(parse_price("19,99 €"), Some(19.99));
+        assert_eq!(parse_price("£1,299.00"), Some(1299.0));
+        assert_eq!(parse_price("1.299,00 €"), Some(1299.0));
+        assert_eq!(parse_price("¥1,299"), Some(1299.0));
+        assert_eq!(parse_price("N/A"), None);
+    }
+
+    #[test]
+    fn test_evaluate_rules() {
+        let crosses = rule("rank_crosses", 10.0, None);
+        let before = snapshot(Some(8), Some(3), Some("$19.99"), Some(4.5));
+        assert!(evaluate_rule(&crosses, &before, &snapshot(Some(9), None, None, None), None).is_none());
+        assert!(evaluate_rule(&crosses, &before, &snapshot(Some(12), None, None, None), None).unwrap().contains("跌出前 10 名"));
+        // 首次检测不算越过
+        let first = CheckSnapshot::default();
+        assert!(evaluate_rule(&crosses, &first, &snapshot(Some(5), None, None, None), None).is_none());
+
+        // 近 7 天最佳第 5 名，本次第 20 名，下降 15 > 10
+        let drop = rule("rank_drop", 10.0, Some(7));
+        let best = RankPosition::new(Some(5), Some(1), None);
+        assert!(evaluate_rule(&drop, &snapshot(Some(12), None, None, None), &snapshot(Some(20), None, None, None), best).is_some());
+        assert!(evaluate_rule(&drop, &snapshot(Some(12), None, None, None), &snapshot(Some(14), None, None, None), best).is_none());
+
+        let sponsored = rule("sponsored_lost", 0.0, None);
+        assert!(evaluate_rule(&sponsored, &before, &snapshot(Some(8), None, None, None), None).is_some());
+
+        let price = rule("price_changed", 5.0, None);
+        assert!(evaluate_rule(&price, &before, &snapshot(None, None, Some("$20.49"), None), None).is_none());
+        assert!(evaluate_rule(&price, &before, &snapshot(None, None, Some("$17.99"), None), None).is_some());
+
+        // 已低于阈值时不重复触发
+        let rating = rule("rating_below", 4.3, None);
+        assert!(evaluate_rule(&rating, &before, &snapshot(None, None, None, Some(4.2)), None).is_some());
+        let low = snapshot(None, None, None, Some(4.1));
+        assert!(evaluate_rule(&rating, &low, &snapshot(None, None, None, Some(4.0)), None).is_none());
+    }
+
+    #[test]
+    fn test_rank_rules_compare_positions_across_pages() {
+        // 第 1 页第 8 名 → 第 2 页第 3 名（绝对位置 45）：页内排名变小，但已跌出前 10
+        let crosses = rule("rank_crosses", 10.0, None);
+        let message = evaluate_rule(&crosses, &on_page(1, 8, Some(8)), &on_page(2, 3, Some(45)), None).unwrap();
+        assert!(message.contains("第2页第3名（第45位）"));
+        assert!(evaluate_rule(&crosses, &on_page(2, 3, Some(45)), &on_page(1, 8, None), None).unwrap().contains("进入前 10 名"));
+
+        // 近 7 天最佳第 1 页第 20 名 → 第 2 页第 1 名（绝对位置 41）：下降 21 名
+        let drop = rule("rank_drop", 10.0, Some(7));
+        let best = RankPosition::new(Some(20), Some(1), None);
+        let message = evaluate_rule(&drop, &on_page(1, 25, Some(25)), &on_page(2, 1, Some(41)), best).unwrap();
+        assert!(message.contains("下降 21 名"));
+        // 窗口内取 (页码, 页内排名) 最靠前的一次
+        assert_eq!(best_position([on_page(2, 1, None).organic().unwrap(), best.unwrap()]), best);
+        // 旧记录没有绝对位置：翻到后面的页视为超过阈值，同一页比较页内排名
+        assert!(evaluate_rule(&drop, &on_page(1, 25, None), &on_page(3, 2, None), best).unwrap().contains("下降到第3页"));
+        let page2_best = RankPosition::new(Some(2), Some(2), None);
+        assert!(evaluate_rule(&drop, &on_page(2, 4, None), &on_page(2, 9, None), page2_best).is_none());
+    }
+}
@@ -0,0 +1,190 @@
+// 品牌词识别：从竞品标题提取品牌、按品牌词典标记关键词和词根
+// 亚马逊标题通常以品牌开头（"Anker PowerCore 10000 ..."、"BRIGHTECH - Sky LED ..."）
+
+use std::collections::{HashMap, HashSet};
+
+// 标题开头常见的非品牌词（营销词、规格词），遇到时不提取品牌
+const NON_BRAND_WORDS: &[&str] = &[
+    "the", "a", "an", "new", "newest", "latest", "upgraded", "upgrade", "updated", "improved",
+    "original", "genuine", "official", "premium", "professional", "pro", "best", "top", "hot",
+    "sale", "amazon", "basics", "pack", "set", "pcs", "pieces", "piece", "lot", "bundle",
+    "led", "usb", "mini", "large", "small", "portable", "wireless", "electric", "smart",
+    "neu", "neue", "neuer", "nouveau", "nouvelle", "nuovo", "nuova", "nuevo", "nueva",
+    "lot de", "set di", "juego de", "paquete",
+];
+
+// 品牌名最多取几个词（"Black+Decker"、"Home Depot" 之类）
+const MAX_BRAND_WORDS: usize = 3;
+
+// 整理标题中的品牌写法：去掉首尾标点、合并空白，保留大小写（"UGREEN"、"Power Cube"）
+fn clean_brand(text: &str) -> String {
+    text.split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '&' && c != '+'))
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// 归一化品牌/关键词：小写、去掉首尾标点、合并空白
+pub fn normalize_brand(text: &str) -> String {
+    clean_brand(text).to_lowercase()
+}
+
+fn is_brand_token(word: &str) -> bool {
+    let lower = word.to_lowercase();
+    word.chars().count() >= 2
+        && word.chars().any(|c| c.is_alphabetic())
+        && !NON_BRAND_WORDS.contains(&lower.as_str())
+}
+
+/// 从商品标题提取品牌，保留原写法
+/// 优先使用分隔符（" - "、"|"、":"、","）前的短前缀，否则取第一个词
+pub fn extract_brand_from_title(title: &str) -> Option<String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+
+    // "Brand - Product"、"Brand | Product"、"Brand: Product"
+    for sep in [" - ", " – ", " | ", ": ", ", "] {
+        if let Some(pos) = title.find(sep) {
+            let prefix = title[..pos].trim();
+            let words: Vec<&str> = prefix.split_whitespace().collect();
+            if !words.is_empty() && words.len() <= MAX_BRAND_WORDS && words.iter().all(|w| is_brand_token(w)) {
+                let brand = clean_brand(prefix);
+                if !brand.is_empty() {
+                    return Some(brand);
+                }
+            }
+        }
+    }
+
+    let first = title.split_whitespace().next()?;
+    let first = first.trim_matches(|c: char| !c.is_alphanumeric() && c != '&' && c != '+');
+    if !is_brand_token(first) {
+        return None;
+    }
+    Some(clean_brand(first))
+}
+
+// 从竞品标题统计出的品牌
+#[derive(Debug, Clone, PartialEq)]
+pub struct TitleBrand {
+    pub brand: String,      // 第一次出现时的写法，用于显示
+    pub asin_count: i64,    // 以它开头的不同 ASIN 数
+}
+
+/// 统计竞品标题中的品牌：归一化品牌 -> 品牌
+/// 在其他标题中间出现的次数不少于开头次数的词是品类词（"lamp"、"desk"），不算品牌
+pub fn collect_brands<'a>(items: impl IntoIterator<Item = (&'a str, &'a str)>) -> HashMap<String, TitleBrand> {
+    let mut leading: HashMap<String, (String, HashSet<String>)> = HashMap::new();
+    let mut titles: Vec<(String, String)> = Vec::new();
+    for (asin, title) in items {
+        let asin = asin.trim().to_uppercase();
+        let normalized = normalize_brand(title);
+        if let Some(brand) = extract_brand_from_title(title) {
+            leading
+                .entry(brand.to_lowercase())
+                .or_insert_with(|| (brand, HashSet::new()))
+                .1
+                .insert(asin.clone());
+        }
+        titles.push((asin, normalized));
+    }
+
+    leading
+        .into_iter()
+        .filter(|(brand_key, (_, asins))| {
+            let needle = format!(" {} ", brand_key);
+            let inside: HashSet<&str> = titles
+                .iter()
+                .filter(|(asin, title)| !asins.contains(asin) && format!(" {} ", title).contains(&needle))
+                .map(|(asin, _)| asin.as_str())
+                .collect();
+            inside.len() < asins.len()
+        })
+        .map(|(brand_key, (brand, asins))| (brand_key, TitleBrand { brand, asin_count: asins.len() as i64 }))
+        .collect()
+}
+
+// 词典条目（brand_key 为归一化后的品牌名）
+#[derive(Debug, Clone)]
+pub struct BrandEntry {
+    pub brand_key: String,
+    pub brand_type: String,  // own, competitor
+}
+
+/// 在文本中查找品牌：整词匹配，并兼容连写（"power cube" 可匹配 "powercube"）
+/// 多个品牌同时命中时取最长的；同样长度时自有品牌优先
+pub fn match_brand<'a>(text: &str, entries: &'a [BrandEntry]) -> Option<&'a BrandEntry> {
+    let normalized = normalize_brand(text);
+    if normalized.is_empty() {
+        return None;
+    }
+    let padded = format!(" {} ", normalized);
+    let words: Vec<&str> = normalized.split(' ').collect();
+
+    entries
+        .iter()
+        .filter(|e| {
+            if e.brand_key.is_empty() {
+                return false;
+            }
+            if padded.contains(&format!(" {} ", e.brand_key)) {
+                return true;
+            }
+            let compact = e.brand_key.replace(' ', "");
+            compact != e.brand_key && words.contains(&compact.as_str())
+        })
+        .max_by(|a, b| {
+            a.brand_key
+                .len()
+                .cmp(&b.brand_key.len())
+                .then_with(|| (a.brand_type == "own").cmp(&(b.brand_type == "own")))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_and_match_brand() {
+        assert_eq!(extract_brand_from_title("Anker PowerCore 10000 Portable Charger").as_deref(), Some("Anker"));
+        assert_eq!(extract_brand_from_title("Power Cube - Extension Socket 4 Way").as_deref(), Some("Power Cube"));
+        assert_eq!(extract_brand_from_title("Upgraded LED Desk Lamp with USB Port"), None);
+        assert_eq!(extract_brand_from_title("2 Pack Night Light"), None);
+
+        // 同一 ASIN 出现两次只算一次；显示名取第一次出现的写法
+        let brands = collect_brands([
+            ("B0ANKER001", "Anker Charger"),
+            ("B0ANKER002", "ANKER Cable"),
+            ("B0UGREEN01", "UGREEN Hub"),
+            ("b0ugreen01", "UGREEN Hub"),
+        ]);
+        assert_eq!(brands["anker"], TitleBrand { brand: "Anker".to_string(), asin_count: 2 });
+        assert_eq!(brands["ugreen"], TitleBrand { brand: "UGREEN".to_string(), asin_count: 1 });
+
+        // "Desk" 开头两个标题，但在更多标题中间出现，是品类词
+        let brands = collect_brands([
+            ("B0DESK0001", "Desk Lamp with Clamp"),
+            ("B0DESK0002", "Desk Lamp for Office"),
+            ("B0LUMI0001", "Lumina LED Desk Lamp"),
+            ("B0LUMI0002", "Lumina Desk Light"),
+            ("B0OTHER001", "TaoTronics Desk Lamp"),
+        ]);
+        let count = |key: &str| brands.get(key).map(|b| b.asin_count);
+        assert_eq!(count("desk"), None);
+        assert_eq!(count("lumina"), Some(2));
+        assert_eq!(count("taotronics"), Some(1));
+
+        let entries = vec![
+            BrandEntry { brand_key: "anker".to_string(), brand_type: "competitor".to_string() },
+            BrandEntry { brand_key: "power cube".to_string(), brand_type: "own".to_string() },
+        ];
+        assert_eq!(match_brand("anker power bank", &entries).unwrap().brand_key, "anker");
+        assert_eq!(match_brand("powercube socket", &entries).unwrap().brand_type, "own");
+        // 整词匹配："ankers" 不是品牌 "anker"
+        assert!(match_brand("ankers charger", &entries).is_none());
+    }
+}
@@ -0,0 +1,449 @@
+// 外部通知渠道：JSON Webhook（钉钉 / 飞书 / 企业微信 / Slack 等机器人）和 SMTP 邮件
+// 电脑无人值守时桌面通知没人看到，告警和定时检测的排名变化汇总可同时发送到这些渠道
+// 发送失败时按指数退避重试；4xx、地址错误等不可恢复的错误不重试
+
+use std::time::Duration;
+
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+use crate::notification;
+use crate::scheduler::RankChange;
+
+// 单次请求超时
+const SEND_TIMEOUT_SECS: u64 = 15;
+
+// 渠道配置（以 JSON 存储在 notification_channels.config）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChannelConfig {
+    Webhook(WebhookConfig),
+    Email(SmtpConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    // 自定义 JSON 模板（仅 generic 格式），可用占位符 {{title}} {{body}} {{text}}
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookFormat {
+    #[default]
+    Generic,   // {"title": ..., "body": ..., "text": ...}
+    Dingtalk,  // 钉钉自定义机器人
+    Feishu,    // 飞书自定义机器人
+    Wecom,     // 企业微信群机器人
+    Slack,     // Slack Incoming Webhook
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    #[default]
+    Starttls,  // 587
+    Tls,       // 465
+    None,      // 仅用于内网中继 / 本地测试
+}
+
+// 通知内容
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelMessage {
+    pub title: String,
+    pub body: String,
+}
+
+// 重试策略：第 n 次重试前等待 base_delay * 2^(n-1)
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SendError {
+    Retryable(String),
+    Permanent(String),
+}
+
+impl ChannelConfig {
+    pub fn channel_type(&self) -> &'static str {
+        match self {
+            ChannelConfig::Webhook(_) => "webhook",
+            ChannelConfig::Email(_) => "email",
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ChannelConfig::Webhook(cfg) => {
+                if !cfg.url.starts_with("http://") && !cfg.url.starts_with("https://") {
+                    return Err("Webhook 地址必须以 http:// 或 https:// 开头".to_string());
+                }
+                if let Some(template) = cfg.template.as_deref().filter(|t| !t.trim().is_empty()) {
+                    let sample = ChannelMessage { title: "title".to_string(), body: "body".to_string() };
+                    serde_json::from_str::<serde_json::Value>(&render_template(template, &sample))
+                        .map_err(|e| format!("模板不是合法的 JSON: {}", e))?;
+                }
+                Ok(())
+            }
+            ChannelConfig::Email(cfg) => {
+                if cfg.host.trim().is_empty() {
+                    return Err("SMTP 服务器不能为空".to_string());
+                }
+                cfg.from.parse::<Mailbox>().map_err(|e| format!("发件人地址无效: {}", e))?;
+                if cfg.to.is_empty() {
+                    return Err("至少需要一个收件人".to_string());
+                }
+                for to in &cfg.to {
+                    to.parse::<Mailbox>().map_err(|e| format!("收件人地址无效 {}: {}", to, e))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // 发送通知，失败时按策略重试；返回实际尝试次数
+    pub async fn send(&self, message: &ChannelMessage, policy: RetryPolicy) -> Result<u32, String> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let result = match self {
+                ChannelConfig::Webhook(cfg) => send_webhook(cfg, message).await,
+                ChannelConfig::Email(cfg) => send_email(cfg, message).await,
+            };
+            match result {
+                Ok(()) => return Ok(attempt),
+                Err(SendError::Permanent(e)) => return Err(e),
+                Err(SendError::Retryable(e)) if attempt >= policy.max_attempts.max(1) => {
+                    return Err(format!("{}（已重试 {} 次）", e, attempt - 1));
+                }
+                Err(SendError::Retryable(e)) => {
+                    let delay = policy.base_delay * 2u32.pow(attempt - 1);
+                    println!("[Channels] 发送失败，{} 毫秒后重试: {}", delay.as_millis(), e);
+                    tokio::time::sleep(delay).await;
+                }
+            }
+        }
+    }
+}
+
+// 替换模板占位符（值按 JSON 字符串转义，模板中应写在引号内）
+fn render_template(template: &str, message: &ChannelMessage) -> String {
+    let escape = |s: &str| {
+        let quoted = serde_json::to_string(s).unwrap_or_default();
+        quoted[1..quoted.len() - 1].to_string()
+    };
+    template
+        .replace("{{title}}", &escape(&message.title))
+        .replace("{{body}}", &escape(&message.body))
+        .replace("{{text}}", &escape(&format!("{}\n{}", message.title, message.body)))
+}
+
+// 按机器人格式生成请求体
+pub fn render_webhook_body(cfg: &WebhookConfig, message: &ChannelMessage) -> Result<serde_json::Value, String> {
+    let text = format!("{}\n{}", message.title, message.body);
+    let body = match cfg.format {
+        WebhookFormat::Generic => match cfg.template.as_deref().filter(|t| !t.trim().is_empty()) {
+            Some(template) => serde_json::from_str(&render_template(template, message))
+                .map_err(|e| format!("模板不是合法的 JSON: {}", e))?,
+            None => serde_json::json!({ "title": message.title, "body": message.body, "text": text }),
+        },
+        WebhookFormat::Dingtalk | WebhookFormat::Wecom => {
+            serde_json::json!({ "msgtype": "text", "text": { "content": text } })
+        }
+        WebhookFormat::Feishu => serde_json::json!({ "msg_type": "text", "content": { "text": text } }),
+        WebhookFormat::Slack => serde_json::json!({ "text": text }),
+    };
+    Ok(body)
+}
+
+async fn send_webhook(cfg: &WebhookConfig, message: &ChannelMessage) -> Result<(), SendError> {
+    let body = render_webhook_body(cfg, message).map_err(SendError::Permanent)?;
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(SEND_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| SendError::Permanent(e.to_string()))?;
+
+    let response = client
+        .post(&cfg.url)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| SendError::Retryable(format!("网络请求失败: {}", e)))?;
+
+    let status = response.status();
+    let text = response.text().await.unwrap_or_default();
+    if status.is_success() {
+        // 钉钉 / 企业微信 / 飞书在 HTTP 200 中通过 errcode / code 返回业务错误
+        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
+            let code = json.get("errcode").or_else(|| json.get("code")).and_then(|c| c.as_i64());
+            if let Some(code) = code.filter(|c| *c != 0) {
+                let msg = json.get("errmsg").or_else(|| json.get("msg")).and_then(|m| m.as_str()).unwrap_or("");
+                return Err(SendError::Permanent(format!("机器人返回错误 {}: {}", code, msg)));
+            }
+        }
+        return Ok(());
+    }
+
+    let error = format!("Webhook 返回 {}: {}", status, text.chars().take(200).collect::<String>());
+    if status.is_server_error() || status.as_u16() == 429 {
+        Err(SendError::Retryable(error))
+    } else {
+        Err(SendError::Permanent(error))
+    }
+}
+
+async fn send_email(cfg: &SmtpConfig, message: &ChannelMessage) -> Result<(), SendError> {
+    let permanent = |e: String| SendError::Permanent(e);
+
+    let from = cfg.from.parse::<Mailbox>().map_err(|e| permanent(format!("发件人地址无效: {}", e)))?;
+    let mut builder = Message::builder().from(from).subject(message.title.clone());
+    for to in &cfg.to {
+        let mailbox = to.parse::<Mailbox>().map_err(|e| permanent(format!("收件人地址无效 {}: {}", to, e)))?;
+        builder = builder.to(mailbox);
+    }
+    let email = builder
+        .header(ContentType::TEXT_PLAIN)
+        .body(message.body.clone())
+        .map_err(|e| permanent(format!("生成邮件失败: {}", e)))?;
+
+    let transport = match cfg.security {
+        SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&cfg.host),
+        SmtpSecurity::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&cfg.host),
+        SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&cfg.host)),
+    }
+    .map_err(|e| permanent(format!("SMTP 配置错误: {}", e)))?
+    .port(cfg.port)
+    .timeout(Some(Duration::from_secs(SEND_TIMEOUT_SECS)));
+
+    let transport = match (&cfg.username, &cfg.password) {
+        (Some(user), Some(pass)) if !user.is_empty() => {
+            transport.credentials(Credentials::new(user.clone(), pass.clone()))
+        }
+        _ => transport,
+    }
+    .build();
+
+    match transport.send(email).await {
+        Ok(_) => Ok(()),
+        // 5xx（如认证失败、收件人被拒）重试也不会成功
+        Err(e) if e.is_permanent() => Err(SendError::Permanent(format!("SMTP 错误: {}", e))),
+        Err(e) => Err(SendError::Retryable(format!("SMTP 错误: {}", e))),
+    }
+}
+
+// 发送到指定渠道并记录结果
+pub async fn send_to_channel(channel: &db::NotificationChannel, message: &ChannelMessage) -> Result<(), String> {
+    let result = channel.config.send(message, RetryPolicy::default()).await.map(|_| ());
+    let _ = db::record_notification_channel_result(channel.id, result.as_ref().err().map(|e| e.as_str()));
+    result
+}
+
+// 定时检测的排名变化汇总发送到开启了汇总的渠道
+pub async fn broadcast_rank_digest(changes: &[RankChange], checked: usize) {
+    let Some((title, body)) = notification::rank_digest_message(changes, checked) else {
+        return;
+    };
+    let message = ChannelMessage { title, body };
+
+    let channels = db::get_notification_channels().unwrap_or_default();
+    for channel in channels.iter().filter(|c| c.is_enabled && c.send_rank_digest) {
+        if let Err(e) = send_to_channel(channel, &message).await {
+            println!("[Channels] 渠道 \"{}\" 发送排名变化汇总失败: {}", channel.name, e);
+        }
+    }
+}
+
+// 测试发送
+pub async fn send_test_message(config: &ChannelConfig) -> Result<u32, String> {
+    config.validate()?;
+    let message = ChannelMessage {
+        title: "🔔 测试通知".to_string(),
+        body: format!(
+            "这是一条来自关键词管理工具的测试通知\n渠道类型: {}\n时间: {}",
+            config.channel_type(),
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S")
+        ),
+    };
+    config.send(&message, RetryPolicy { max_attempts: 1, ..Default::default() }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    fn fast_retry() -> RetryPolicy {
+        RetryPolicy { max_attempts: 3, base_delay: Duration::from_millis(10) }
+    }
+
+    fn message() -> ChannelMessage {
+        ChannelMessage { title: "📉 排名下降提醒".to_string(), body: "关键词: \"desk lamp\"\n排名: 5 → 18".to_string() }
+    }
+
+    // 本地 Webhook 模拟服务：按顺序返回给定状态码，记录收到的请求体
+    async fn mock_webhook(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let received = bodies.clone();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                // 读完请求头和 Content-Length 指定的请求体
+                loop {
+                    let n = socket.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some(pos) = text.find("\r\n\r\n") {
+                        let length = text[..pos]
+                            .lines()
+                            .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if buf.len() >= pos + 4 + length {
+                            received.lock().unwrap().push(String::from_utf8_lossy(&buf[pos + 4..]).to_string());
+                            break;
+                        }
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                }
+                let response = format!("HTTP/1.1 {} MOCK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}", status);
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, bodies)
+    }
+
+    #[tokio::test]
+    async fn test_webhook_retry_and_format() {
+        let (url, bodies) = mock_webhook(vec![500, 503, 200]).await;
+        let config = ChannelConfig::Webhook(WebhookConfig { url, format: WebhookFormat::Dingtalk, template: None });
+        assert_eq!(config.send(&message(), fast_retry()).await.unwrap(), 3);
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 3);
+        let json: serde_json::Value = serde_json::from_str(&bodies[2]).unwrap();
+        assert_eq!(json["msgtype"], "text");
+        assert!(json["text"]["content"].as_str().unwrap().contains("desk lamp"));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_client_error_not_retried() {
+        let (url, bodies) = mock_webhook(vec![400, 200]).await;
+        let config = ChannelConfig::Webhook(WebhookConfig { url, format: WebhookFormat::Slack, template: None });
+        let err = config.send(&message(), fast_retry()).await.unwrap_err();
+        assert!(err.contains("400"));
+        assert_eq!(bodies.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_webhook_template_escapes_values() {
+        let cfg = WebhookConfig {
+            url: "http://localhost/hook".to_string(),
+            format: WebhookFormat::Generic,
+            template: Some(r#"{"msg_type": "text", "content": {"text": "{{text}}"}}"#.to_string()),
+        };
+        let body = render_webhook_body(&cfg, &message()).unwrap();
+        assert_eq!(body["content"]["text"], "📉 排名下降提醒\n关键词: \"desk lamp\"\n排名: 5 → 18");
+    }
+
+    // 本地 SMTP 模拟服务：接收一封邮件，返回 DATA 内容
+    async fn mock_smtp() -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = socket.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 mock ESMTP\r\n").await.unwrap();
+            let mut data = String::new();
+            let mut in_data = false;
+            while let Ok(Some(line)) = lines.next_line().await {
+                if in_data {
+                    if line == "." {
+                        in_data = false;
+                        writer.write_all(b"250 OK queued\r\n").await.unwrap();
+                    } else {
+                        data.push_str(&line);
+                        data.push('\n');
+                    }
+                    continue;
+                }
+                let command = line.to_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    b"250-mock\r\n250 8BITMIME\r\n"
+                } else if command.starts_with("DATA") {
+                    in_data = true;
+                    b"354 End data with <CR><LF>.<CR><LF>\r\n"
+                } else if command.starts_with("QUIT") {
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+            data
+        });
+        (port, handle)
+    }
+
+    #[tokio::test]
+    async fn test_smtp_send() {
+        let (port, handle) = mock_smtp().await;
+        let config = ChannelConfig::Email(SmtpConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            security: SmtpSecurity::None,
+            username: None,
+            password: None,
+            from: "Monitor <monitor@example.com>".to_string(),
+            to: vec!["ops@example.com".to_string()],
+        });
+        config.validate().unwrap();
+        assert_eq!(config.send(&message(), fast_retry()).await.unwrap(), 1);
+
+        let data = handle.await.unwrap();
+        assert!(data.contains("To: ops@example.com"));
+        assert!(data.contains("Subject:"));
+    }
+}
@@ -0,0 +1,142 @@
+// 排名检测失败分类与重试策略
+// 可重试的失败进入持久化重试队列，按类型指数退避；连续失败过多的关键词自动暂停
+
+use serde::{Deserialize, Serialize};
+
+use crate::crawler::RankingResult;
+use crate::db;
+
+// 每个关键词最多重试次数，超过后移出队列，等下一次正常检测
+pub const MAX_RETRY_ATTEMPTS: i64 = 3;
+
+// 每轮最多重试的关键词数，避免重试挤占定时检测
+pub const RETRY_BATCH_LIMIT: i64 = 50;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    Captcha,        // 验证码 / Robot Check
+    GeoRestricted,  // 配送地址未生效或邮编设置失败（含 warning）
+    Timeout,        // 页面加载超时
+    ParseError,     // 页面结构变化、未找到结果
+    ProxyError,     // 代理连接失败
+    RateLimited,    // 站点限速冷却或预算用完，未发起请求（见 rate_limit）
+    Other,
+}
+
+impl FailureKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FailureKind::Captcha => "captcha",
+            FailureKind::GeoRestricted => "geo_restricted",
+            FailureKind::Timeout => "timeout",
+            FailureKind::ParseError => "parse_error",
+            FailureKind::ProxyError => "proxy_error",
+            FailureKind::RateLimited => "rate_limited",
+            FailureKind::Other => "other",
+        }
+    }
+
+    // 地理限制和解析错误短时间内重试也不会成功
+    pub fn retryable(&self) -> bool {
+        !matches!(self, FailureKind::GeoRestricted | FailureKind::ParseError)
+    }
+
+    // 第 attempt 次重试前的等待分钟数；不可重试或超过次数返回 None
+    pub fn retry_delay_minutes(&self, attempt: i64) -> Option<i64> {
+        if !self.retryable() || !(1..=MAX_RETRY_ATTEMPTS).contains(&attempt) {
+            return None;
+        }
+        let base = match self {
+            FailureKind::Captcha => 60,     // 验证码需要等风控冷却
+            FailureKind::ProxyError => 15,
+            FailureKind::Timeout => 10,
+            _ => 30,
+        };
+        Some(base << (attempt - 1))
+    }
+}
+
+fn contains_any(text: &str, patterns: &[&str]) -> bool {
+    patterns.iter().any(|p| text.contains(p))
+}
+
+// 按错误信息分类（Playwright / Chromium 的英文错误与脚本中的中文提示）
+pub fn classify_message(message: &str) -> FailureKind {
+    let text = message.to_lowercase();
+    if contains_any(&text, &["站点限速", "请求预算"]) {
+        FailureKind::RateLimited
+    } else if contains_any(&text, &["验证码", "captcha", "robot check", "http 503"]) {
+        FailureKind::Captcha
+    } else if contains_any(&text, &["err_proxy", "err_tunnel", "err_socks", "proxy", "代理"]) {
+        FailureKind::ProxyError
+    } else if contains_any(&text, &["timeout", "timed out", "超时"]) {
+        FailureKind::Timeout
+    } else if contains_any(&text, &["邮编", "配送地址", "地理"]) {
+        FailureKind::GeoRestricted
+    } else if contains_any(&text, &["未找到结果", "解析", "parse", "json", "selector"]) {
+        FailureKind::ParseError
+    } else {
+        FailureKind::Other
+    }
+}
+
+// 检测结果的失败类型；成功但带地理限制警告的也返回 GeoRestricted（排名照常保存，见 record_outcome）
+pub fn classify(result: &RankingResult) -> Option<(FailureKind, String)> {
+    if let Some(error) = &result.error {
+        return Some((classify_message(error), error.clone()));
+    }
+    result.warning.as_ref().map(|warning| (FailureKind::GeoRestricted, warning.clone()))
+}
+
+/// 记录一次检测结果：成功清零连续失败并移出重试队列，失败则记录原因、排入重试或自动暂停
+/// 限速跳过的检测没有请求站点，不计入关键词的失败
+/// 只有警告的检测排名已保存，只记录原因，不计入连续失败（否则仍在正常出排名的关键词会被自动暂停）
+/// auto_pause_after 为连续失败多少次后暂停，0 表示不自动暂停
+pub fn record_outcome(monitoring_id: i64, result: &RankingResult, auto_pause_after: i64) -> Option<FailureKind> {
+    match classify(result) {
+        None => {
+            db::clear_check_failures(monitoring_id).ok();
+            None
+        }
+        Some((FailureKind::RateLimited, _)) => Some(FailureKind::RateLimited),
+        Some((kind, message)) if result.error.is_none() => {
+            if let Err(e) = db::record_check_warning(monitoring_id, kind, &message) {
+                eprintln!("[Retry] 记录检测警告出错 (id={}): {}", monitoring_id, e);
+            }
+            Some(kind)
+        }
+        Some((kind, message)) => {
+            match db::record_check_failure(monitoring_id, kind, &message, auto_pause_after) {
+                Ok(outcome) if outcome.auto_paused => {
+                    println!("[Retry] 关键词连续失败 {} 次，已自动暂停 (id={})", outcome.consecutive_failures, monitoring_id);
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("[Retry] 记录检测失败出错 (id={}): {}", monitoring_id, e),
+            }
+            Some(kind)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_and_backoff() {
+        assert_eq!(classify_message("检测到验证码页面"), FailureKind::Captcha);
+        assert_eq!(classify_message("page.goto: net::ERR_PROXY_CONNECTION_FAILED at https://www.amazon.de"), FailureKind::ProxyError);
+        assert_eq!(classify_message("Timeout 30000ms exceeded."), FailureKind::Timeout);
+        assert_eq!(classify_message("邮编设置失败，无法检测 DE 站"), FailureKind::GeoRestricted);
+        assert_eq!(classify_message("未找到结果"), FailureKind::ParseError);
+        assert_eq!(classify_message("HTTP 503 Service Unavailable"), FailureKind::Captcha);
+        assert_eq!(classify_message("DE 站点限速冷却中（2 级），14:30 后恢复"), FailureKind::RateLimited);
+        assert_eq!(classify_message("启动 Python 脚本失败"), FailureKind::Other);
+
+        assert_eq!(FailureKind::Timeout.retry_delay_minutes(1), Some(10));
+        assert_eq!(FailureKind::Captcha.retry_delay_minutes(3), Some(240));
+        assert_eq!(FailureKind::Timeout.retry_delay_minutes(MAX_RETRY_ATTEMPTS + 1), None);
+        assert_eq!(FailureKind::GeoRestricted.retry_delay_minutes(1), None);
+    }
+}
@@ -0,0 +1,690 @@
+// 命令行模式：不启动 GUI，直接通过 db 模块操作同一个 thesaurus.db
+// 便于在 Linux 服务器上用 cron 编排导入 → 分析 → 导出 → 排名检测流程
+
+use crate::crawler;
+use crate::csv_text::{csv_line, parse_csv, BOM};
+use crate::intent;
+use crate::pipeline;
+use crate::db::{self, KeywordData, Product};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// 与 tauri.conf.json 中的 identifier 保持一致，保证和 GUI 使用同一个数据目录
+const APP_IDENTIFIER: &str = "com.chenapple.thesaurus-management";
+
+const USAGE: &str = "thesaurus-cli - 词库管理命令行工具
+
+用法: thesaurus-cli [--data-dir <目录>] <命令> [参数]
+
+命令:
+  products                                  列出所有产品
+  product-add <名称> [--country US]          创建产品，输出产品 ID
+  import <产品ID> <文件> [--no-backup]       导入关键词（.xlsx/.xls/.csv 同 GUI 列格式，.txt 每行一个关键词）
+  analyze <产品ID> [--steps <步骤>] [--resume]
+                                            运行分析流程，步骤: traffic_level,traffic_share,phrase_tag,orderliness
+                                            （默认全部）；--resume 从上次失败的步骤继续
+  classify-intent <产品ID>                   识别搜索意图（不覆盖人工/AI 填写的意图）
+  seed-brands <产品ID>                       从竞品标题提取品牌写入品牌词典，并标记品牌词
+  export <产品ID> <输出文件>                 导出关键词数据（.csv 或 .json）
+  backup <产品ID> [名称]                     创建备份
+  backups <产品ID>                           列出备份
+  check-rankings <产品ID> [--max-pages 5] [--hours 24] [--engine playwright|native]
+                                            检测待检测的关键词排名（--hours 0 表示不限时间；
+                                            --engine 指定本次检测引擎，默认使用设置中的引擎）
+  reparse <日期> <站点> <ASIN> [--keyword <关键词>] [--run <检测目录>]
+                                            从爬取页面归档重新解析（日期为 YYYY-MM-DD，UTC）：
+                                            指定 --keyword 时重新计算排名（--run 默认当天最新一次检测），
+                                            否则重新提取 Listing；输出 JSON
+
+数据目录默认与 GUI 相同，可通过 --data-dir 或环境变量 THESAURUS_DATA_DIR 指定
+设置环境变量 THESAURUS_CRAWLER_FIXTURES=<录制目录> 时所有爬取改为回放录制数据，不访问网络";
+
+// 简单参数解析：--key value 形式的选项、--flag 形式的开关，其余为位置参数
+struct Args {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    flags: Vec<String>,
+}
+
+// 只接受开关（不带值）的选项
+const FLAG_OPTIONS: &[&str] = &["no-backup", "resume", "help"];
+
+fn parse_args(raw: Vec<String>) -> Args {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    let mut flags = Vec::new();
+
+    let mut iter = raw.into_iter();
+    while let Some(arg) = iter.next() {
+        if let Some(name) = arg.strip_prefix("--") {
+            if let Some((key, value)) = name.split_once('=') {
+                options.insert(key.to_string(), value.to_string());
+            } else if FLAG_OPTIONS.contains(&name) {
+                flags.push(name.to_string());
+            } else if let Some(value) = iter.next() {
+                options.insert(name.to_string(), value);
+            } else {
+                flags.push(name.to_string());
+            }
+        } else if arg == "-h" {
+            flags.push("help".to_string());
+        } else {
+            positional.push(arg);
+        }
+    }
+
+    Args { positional, options, flags }
+}
+
+impl Args {
+    fn has_flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|f| f == name)
+    }
+
+    fn positional(&self, index: usize, name: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(|s| s.as_str())
+            .ok_or_else(|| format!("缺少参数: <{}>", name))
+    }
+
+    fn product_id(&self, index: usize) -> Result<i64, String> {
+        let raw = self.positional(index, "产品ID")?;
+        raw.parse::<i64>().map_err(|_| format!("无效的产品 ID: {}", raw))
+    }
+
+    fn option_i64(&self, name: &str) -> Result<Option<i64>, String> {
+        match self.options.get(name) {
+            Some(v) => v
+                .parse::<i64>()
+                .map(Some)
+                .map_err(|_| format!("--{} 需要整数，收到: {}", name, v)),
+            None => Ok(None),
+        }
+    }
+}
+
+// 数据目录：--data-dir > THESAURUS_DATA_DIR > GUI 默认目录
+fn resolve_data_dir(args: &Args) -> Result<PathBuf, String> {
+    if let Some(dir) = args.options.get("data-dir") {
+        return Ok(PathBuf::from(dir));
+    }
+    if let Ok(dir) = std::env::var("THESAURUS_DATA_DIR") {
+        if !dir.trim().is_empty() {
+            return Ok(PathBuf::from(dir));
+        }
+    }
+    dirs::data_dir()
+        .map(|d| d.join(APP_IDENTIFIER))
+        .ok_or_else(|| "无法确定数据目录，请使用 --data-dir 指定".to_string())
+}
+
+/// 命令行入口，返回进程退出码
+pub fn run(raw_args: Vec<String>) -> i32 {
+    let args = parse_args(raw_args);
+
+    if args.positional.is_empty() || args.has_flag("help") {
+        println!("{}", USAGE);
+        return if args.positional.is_empty() && !args.has_flag("help") { 2 } else { 0 };
+    }
+
+    let data_dir = match resolve_data_dir(&args) {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            return 1;
+        }
+    };
+
+    if let Err(e) = db::init_db(data_dir.clone()) {
+        eprintln!("错误: 打开数据库失败 ({}): {}", data_dir.display(), e);
+        return 1;
+    }
+
+    let result = match args.positional[0].as_str() {
+        "products" => cmd_products(),
+        "product-add" => cmd_product_add(&args),
+        "import" => cmd_import(&args),
+        "analyze" => cmd_analyze(&args),
+        "classify-intent" => cmd_classify_intent(&args),
+        "seed-brands" => cmd_seed_brands(&args),
+        "export" => cmd_export(&args),
+        "backup" => cmd_backup(&args),
+        "backups" => cmd_backups(&args),
+        "check-rankings" => cmd_check_rankings(&args),
+        "reparse" => cmd_reparse(&args),
+        other => Err(format!("未知命令: {}\n\n{}", other, USAGE)),
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            1
+        }
+    }
+}
+
+fn find_product(product_id: i64) -> Result<Product, String> {
+    db::get_products()
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|p| p.id == product_id)
+        .ok_or_else(|| format!("产品不存在: {}", product_id))
+}
+
+// ==================== 产品 ====================
+
+fn cmd_products() -> Result<(), String> {
+    let products = db::get_products().map_err(|e| e.to_string())?;
+    for p in products {
+        let count = db::get_keyword_data_stats(p.id).unwrap_or(0);
+        println!(
+            "{}\t{}\t{}\t{} 个关键词",
+            p.id,
+            p.country.as_deref().unwrap_or("-"),
+            p.name,
+            count
+        );
+    }
+    Ok(())
+}
+
+fn cmd_product_add(args: &Args) -> Result<(), String> {
+    let name = args.positional(1, "名称")?;
+    let country = args.options.get("country").map(|c| c.to_uppercase());
+    let id = db::create_product(name.to_string(), country).map_err(|e| e.to_string())?;
+    println!("{}", id);
+    Ok(())
+}
+
+// ==================== 导入 ====================
+
+fn cmd_import(args: &Args) -> Result<(), String> {
+    let product = find_product(args.product_id(1)?)?;
+    let file = PathBuf::from(args.positional(2, "文件")?);
+    let ext = file
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    let (headers, rows) = match ext.as_str() {
+        "xlsx" | "xls" | "xlsm" => read_excel_rows(&file)?,
+        "csv" => read_csv_rows(&file)?,
+        "txt" => {
+            // 纯关键词列表：只做词根分析
+            let content = std::fs::read_to_string(&file).map_err(|e| format!("读取文件失败: {}", e))?;
+            let keywords: Vec<String> = content
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect();
+            let count = keywords.len();
+            db::import_keywords(product.id, keywords).map_err(|e| e.to_string())?;
+            println!("已导入 {} 个关键词到 \"{}\"", count, product.name);
+            return Ok(());
+        }
+        _ => return Err(format!("不支持的文件格式: {}", file.display())),
+    };
+
+    let data_list = rows_to_keyword_data(product.id, &headers, &rows);
+    if data_list.is_empty() {
+        return Err("文件中没有找到关键词".to_string());
+    }
+
+    // 与 GUI 一致：已有数据时先自动备份
+    if !args.has_flag("no-backup") && db::get_keyword_data_stats(product.id).unwrap_or(0) > 0 {
+        let name = format!("导入前自动备份 - {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"));
+        let backup_id = db::create_backup(product.id, Some(name)).map_err(|e| e.to_string())?;
+        println!("已创建备份 #{}", backup_id);
+    }
+
+    // 保存表头（CPC 和竞价范围列包含货币符号）
+    let cpc_header = headers.get(7).filter(|h| !h.is_empty()).cloned();
+    let bid_range_header = headers.get(8).filter(|h| !h.is_empty()).cloned();
+    if cpc_header.is_some() || bid_range_header.is_some() {
+        db::update_product_headers(product.id, cpc_header, bid_range_header).map_err(|e| e.to_string())?;
+    }
+
+    let keywords: Vec<String> = data_list.iter().map(|d| d.keyword.clone()).collect();
+    let count = keywords.len();
+    db::import_keyword_data(product.id, data_list).map_err(|e| e.to_string())?;
+    db::import_keywords(product.id, keywords).map_err(|e| e.to_string())?;
+
+    println!("已导入 {} 个关键词到 \"{}\"", count, product.name);
+    Ok(())
+}
+
+// 读取 Excel 第一个工作表：(表头, 数据行)
+fn read_excel_rows(path: &Path) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    use calamine::{open_workbook_auto, Data, Reader};
+
+    let mut workbook = open_workbook_auto(path).map_err(|e| format!("Excel 解析失败: {}", e))?;
+    let sheet_name = workbook
+        .sheet_names()
+        .first()
+        .cloned()
+        .ok_or_else(|| "Excel 中没有工作表".to_string())?;
+    let range = workbook
+        .worksheet_range(&sheet_name)
+        .map_err(|e| format!("Excel 解析失败: {}", e))?;
+
+    let mut rows: Vec<Vec<String>> = range
+        .rows()
+        .map(|row| {
+            row.iter()
+                .map(|cell| match cell {
+                    Data::Int(i) => i.to_string(),
+                    Data::Float(f) => f.to_string(),
+                    Data::String(s) => s.clone(),
+                    Data::Bool(b) => b.to_string(),
+                    Data::DateTime(dt) => dt.to_string(),
+                    Data::DateTimeIso(dt) => dt.clone(),
+                    Data::DurationIso(d) => d.clone(),
+                    Data::Error(_) | Data::Empty => String::new(),
+                })
+                .collect()
+        })
+        .collect();
+
+    if rows.is_empty() {
+        return Err("Excel 中没有数据".to_string());
+    }
+    let headers = rows.remove(0);
+    Ok((headers, rows))
+}
+
+// 读取 CSV 文件：(表头, 数据行)
+fn read_csv_rows(path: &Path) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?;
+    parse_csv(&content)
+}
+
+// 按 GUI 的列映射转换（A-P 为固定列，P 之后为 ASIN 动态列）
+fn rows_to_keyword_data(product_id: i64, headers: &[String], rows: &[Vec<String>]) -> Vec<KeywordData> {
+    let text = |row: &Vec<String>, i: usize| -> Option<String> {
+        row.get(i).map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+    };
+    let number = |row: &Vec<String>, i: usize| -> Option<f64> {
+        text(row, i).and_then(|s| s.replace(',', "").parse::<f64>().ok())
+    };
+
+    let mut list = Vec::new();
+    for row in rows {
+        let Some(keyword) = text(row, 0) else { continue };
+
+        let mut asin_data = serde_json::Map::new();
+        for (i, header) in headers.iter().enumerate().skip(16) {
+            if header.is_empty() {
+                continue;
+            }
+            if let Some(value) = text(row, i) {
+                let value = value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(serde_json::Number::from_f64)
+                    .map(serde_json::Value::Number)
+                    .unwrap_or(serde_json::Value::String(value));
+                asin_data.insert(header.clone(), value);
+            }
+        }
+
+        list.push(KeywordData {
+            id: 0,
+            product_id,
+            keyword,
+            translation: text(row, 1),
+            relevance_score: text(row, 2),
+            relevance_level: text(row, 3),
+            traffic_total: number(row, 4),
+            avg_keyword_rank: text(row, 5),
+            avg_search_volume: number(row, 6),
+            cpc_bid: text(row, 7),
+            bid_range: text(row, 8),
+            click_rate: text(row, 9),
+            conversion_competition: text(row, 10),
+            competition_level: text(row, 11),
+            natural_position_flow: text(row, 12),
+            top3_click_share: text(row, 13),
+            avg_conversion_share: text(row, 14),
+            asin_count: number(row, 15).map(|n| n as i64),
+            traffic_level: None,
+            negative_word: None,
+            orderliness: None,
+            phrase_tag: None,
+            primary_category: None,
+            secondary_category: None,
+            search_intent: None,
+            traffic_share: None,
+            asin_data: if asin_data.is_empty() {
+                None
+            } else {
+                serde_json::to_string(&asin_data).ok()
+            },
+            search_intent_source: None,
+            search_intent_confidence: None,
+            brand_name: None,
+            brand_type: None,
+        });
+    }
+    list
+}
+
+// ==================== 分析 ====================
+
+fn cmd_analyze(args: &Args) -> Result<(), String> {
+    let product = find_product(args.product_id(1)?)?;
+
+    let resume_run_id = if args.has_flag("resume") {
+        let run = db::get_last_failed_pipeline_run(product.id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "没有可恢复的失败流程".to_string())?;
+        Some(run.id)
+    } else {
+        None
+    };
+    let steps = args
+        .options
+        .get("steps")
+        .map(|s| s.split(',').map(|x| x.trim().to_lowercase()).filter(|x| !x.is_empty()).collect());
+
+    let result = pipeline::run_pipeline(product.id, steps, resume_run_id, |p| match p.status.as_str() {
+        "completed" => println!("✓ [{}/{}] {}", p.step_index, p.total_steps, p.step),
+        "skipped" => println!("- [{}/{}] {}（已完成，跳过）", p.step_index, p.total_steps, p.step),
+        "failed" => println!("✗ [{}/{}] {}", p.step_index, p.total_steps, p.step),
+        _ => {}
+    })?;
+
+    println!("流程 #{} 完成（第 {} 次尝试）", result.run.id, result.run.attempts);
+    Ok(())
+}
+
+fn cmd_classify_intent(args: &Args) -> Result<(), String> {
+    let product = find_product(args.product_id(1)?)?;
+    let summary = intent::classify_product(product.id)?;
+    for item in &summary.by_intent {
+        println!("{}\t{}", item.label, item.count);
+    }
+    println!(
+        "已识别 {} 个关键词（平均置信度 {:.2}），保留人工/AI 意图 {} 个",
+        summary.classified, summary.avg_confidence, summary.skipped_manual
+    );
+    Ok(())
+}
+
+fn cmd_seed_brands(args: &Args) -> Result<(), String> {
+    let product = find_product(args.product_id(1)?)?;
+    let result = db::seed_brand_dictionary(product.id).map_err(|e| e.to_string())?;
+    println!(
+        "扫描竞品标题 {} 条，新增品牌 {} 个，更新 {} 个，删除 {} 个；标记品牌关键词 {} 个、词根 {} 个",
+        result.titles_scanned,
+        result.brands_added,
+        result.brands_updated,
+        result.brands_removed,
+        result.keywords_flagged,
+        result.roots_flagged
+    );
+    for s in db::get_brand_negative_suggestions(product.id).map_err(|e| e.to_string())? {
+        println!("否词建议\t{}\t{} 个关键词\t搜索量 {}", s.negative_phrase, s.keyword_count, s.total_search_volume);
+    }
+    Ok(())
+}
+
+// ==================== 导出 ====================
+
+fn cmd_export(args: &Args) -> Result<(), String> {
+    let product = find_product(args.product_id(1)?)?;
+    let out = PathBuf::from(args.positional(2, "输出文件")?);
+
+    // 分页读取全部数据（按 ID 升序，保持导入顺序）
+    let mut all: Vec<KeywordData> = Vec::new();
+    let page_size = 5000;
+    let mut page = 1;
+    loop {
+        let (data, total) = db::get_keyword_data(
+            product.id, None, None, None, None, None, None, None, Some("asc".to_string()), page, page_size,
+        )
+        .map_err(|e| e.to_string())?;
+        let fetched = data.len();
+        all.extend(data);
+        if fetched == 0 || all.len() as i64 >= total {
+            break;
+        }
+        page += 1;
+    }
+
+    let is_json = out.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("json")).unwrap_or(false);
+    let content = if is_json {
+        serde_json::to_string_pretty(&all).map_err(|e| e.to_string())?
+    } else {
+        keyword_data_to_csv(&all, &product)
+    };
+    std::fs::write(&out, content).map_err(|e| format!("写入文件失败: {}", e))?;
+
+    println!("已导出 {} 条数据到 {}", all.len(), out.display());
+    Ok(())
+}
+
+fn keyword_data_to_csv(list: &[KeywordData], product: &Product) -> String {
+    let headers = [
+        "关键词", "翻译", "相关性得分", "相关性档位", "流量总和", "周平均排名", "周平均搜索量",
+        product.cpc_header.as_deref().unwrap_or("CPC"),
+        product.bid_range_header.as_deref().unwrap_or("建议竞价范围"),
+        "点击转化率", "转化竞争", "竞争度", "自然流量位", "前三ASIN点击占比", "前三ASIN转化占比", "ASIN数",
+        "流量级别", "否词", "有序性", "词组标签", "一级分类", "二级分类", "搜索意图", "流量占比", "品牌",
+    ];
+
+    let opt = |v: &Option<String>| v.clone().unwrap_or_default();
+    let num = |v: Option<f64>| v.map(|n| n.to_string()).unwrap_or_default();
+
+    let mut out = String::from(BOM);
+    out.push_str(&csv_line(&headers));
+    out.push('\n');
+
+    for d in list {
+        let fields = [
+            d.keyword.clone(),
+            opt(&d.translation),
+            opt(&d.relevance_score),
+            opt(&d.relevance_level),
+            num(d.traffic_total),
+            opt(&d.avg_keyword_rank),
+            num(d.avg_search_volume),
+            opt(&d.cpc_bid),
+            opt(&d.bid_range),
+            opt(&d.click_rate),
+            opt(&d.conversion_competition),
+            opt(&d.competition_level),
+            opt(&d.natural_position_flow),
+            opt(&d.top3_click_share),
+            opt(&d.avg_conversion_share),
+            d.asin_count.map(|n| n.to_string()).unwrap_or_default(),
+            opt(&d.traffic_level),
+            opt(&d.negative_word),
+            opt(&d.orderliness),
+            opt(&d.phrase_tag),
+            opt(&d.primary_category),
+            opt(&d.secondary_category),
+            opt(&d.search_intent),
+            num(d.traffic_share),
+            match d.brand_type.as_deref() {
+                Some("own") => format!("{}（自有）", opt(&d.brand_name)),
+                Some(_) => format!("{}（竞品）", opt(&d.brand_name)),
+                None => String::new(),
+            },
+        ];
+        out.push_str(&csv_line(&fields));
+        out.push('\n');
+    }
+    out
+}
+
+// ==================== 备份 ====================
+
+fn cmd_backup(args: &Args) -> Result<(), String> {
+    let product = find_product(args.product_id(1)?)?;
+    let name = args
+        .positional
+        .get(2)
+        .cloned()
+        .unwrap_or_else(|| format!("命令行备份 - {}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S")));
+    let backup_id = db::create_backup(product.id, Some(name)).map_err(|e| e.to_string())?;
+    println!("{}", backup_id);
+    Ok(())
+}
+
+fn cmd_backups(args: &Args) -> Result<(), String> {
+    let product = find_product(args.product_id(1)?)?;
+    let backups = db::get_backups(product.id).map_err(|e| e.to_string())?;
+    for b in backups {
+        println!(
+            "{}\t{}\t{} 条\t{}",
+            b.id,
+            b.created_at,
+            b.keyword_data_count,
+            b.backup_name.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+// ==================== 排名检测 ====================
+
+fn cmd_check_rankings(args: &Args) -> Result<(), String> {
+    let product = find_product(args.product_id(1)?)?;
+    let max_pages = args.option_i64("max-pages")?.unwrap_or(5);
+    let hours = args.option_i64("hours")?.unwrap_or(24);
+
+    let pending = db::get_pending_monitoring_checks(product.id, hours).map_err(|e| e.to_string())?;
+    if pending.is_empty() {
+        println!("没有需要检测的关键词");
+        return Ok(());
+    }
+
+    let keywords: Vec<crawler::RankingItem> = pending
+        .into_iter()
+        .map(|m| (m.id, m.keyword, m.asin, m.country, m.location))
+        .collect();
+    let total = keywords.len() as i64;
+    println!("开始检测 {} 个关键词", total);
+
+    let task_id = db::create_task_log("cli", total).ok();
+    let mut settings = crate::load_crawler_settings();
+    match args.options.get("engine").map(String::as_str) {
+        None => {}
+        Some("playwright") => settings.engine = crawler::CrawlerEngine::Playwright,
+        Some("native") => settings.engine = crawler::CrawlerEngine::Native,
+        Some(other) => return Err(format!("--engine 只支持 playwright 或 native，收到: {}", other)),
+    }
+    let auto_pause_after = crate::scheduler::load_settings().auto_pause_after_failures as i64;
+
+    let control = crawler::RunControl::new("cli", total);
+    control.set_task_log(task_id);
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    let results = runtime.block_on(crawler::check_rankings_batch(
+        keywords,
+        max_pages,
+        settings,
+        control,
+        move |current, total, monitoring_id, result| {
+            eprintln!("[{}/{}] 已完成: {}", current, total, result.keyword);
+            crate::save_ranking_result(monitoring_id, result, auto_pause_after, task_id);
+            if let Some(id) = task_id {
+                db::update_task_progress(id, current, 0).ok();
+            }
+        },
+    ));
+
+    let success = results.iter().filter(|(_, r)| r.error.is_none()).count() as i64;
+    let failed = results.len() as i64 - success;
+    if let Some(id) = task_id {
+        db::complete_task_log(id, success, failed).ok();
+    }
+
+    for (_, r) in &results {
+        match &r.error {
+            Some(e) => println!("✗ {} [{}] {}", r.keyword, r.country, e),
+            None => println!(
+                "✓ {} [{}] 自然: {} 广告: {}",
+                r.keyword,
+                r.country,
+                r.organic_rank.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string()),
+                r.sponsored_rank.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string()),
+            ),
+        }
+    }
+    println!("完成: 成功 {}，失败 {}", success, failed);
+
+    if failed > 0 && success == 0 {
+        return Err("所有关键词检测失败".to_string());
+    }
+    Ok(())
+}
+
+fn cmd_reparse(args: &Args) -> Result<(), String> {
+    let date = args.positional(1, "日期")?.to_string();
+    let country = args.positional(2, "站点")?.to_uppercase();
+    let asin = args.positional(3, "ASIN")?.to_uppercase();
+
+    let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+    let output = match args.options.get("keyword") {
+        Some(keyword) => {
+            let result = runtime.block_on(crate::crawl_archive::reparse_ranking(
+                &date,
+                keyword.clone(),
+                asin,
+                country,
+                args.options.get("run").cloned(),
+            ))?;
+            serde_json::to_string_pretty(&result)
+        }
+        None => {
+            let result = runtime.block_on(crate::crawl_archive::reparse_listing(&date, asin, country))?;
+            serde_json::to_string_pretty(&result)
+        }
+    };
+    println!("{}", output.map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(raw: &[&str]) -> Args {
+        parse_args(raw.iter().map(|s| s.to_string()).collect())
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let a = args(&["--data-dir", "/tmp/db", "check-rankings", "3", "--max-pages=5", "--engine", "native", "--hours", "0"]);
+        assert_eq!(a.positional, vec!["check-rankings", "3"]);
+        assert_eq!(a.options.get("data-dir").map(String::as_str), Some("/tmp/db"));
+        assert_eq!(a.option_i64("max-pages").unwrap(), Some(5));
+        assert_eq!(a.option_i64("hours").unwrap(), Some(0));
+        assert_eq!(a.options.get("engine").map(String::as_str), Some("native"));
+        assert_eq!(a.product_id(1).unwrap(), 3);
+
+        // 开关不吃掉后面的位置参数；选项值可以带空格
+        let a = args(&["import", "--no-backup", "1", "kw.csv", "--keyword", "desk lamp"]);
+        assert!(a.has_flag("no-backup"));
+        assert_eq!(a.positional, vec!["import", "1", "kw.csv"]);
+        assert_eq!(a.options.get("keyword").map(String::as_str), Some("desk lamp"));
+
+        // 末尾没有值的选项当作开关；-h 等同 --help
+        let a = args(&["analyze", "2", "-h", "--steps"]);
+        assert!(a.has_flag("help") && a.has_flag("steps"));
+        assert!(a.options.is_empty());
+    }
+
+    #[test]
+    fn test_args_errors() {
+        let a = args(&["backup", "abc", "--hours", "soon"]);
+        assert_eq!(a.product_id(1).unwrap_err(), "无效的产品 ID: abc");
+        assert_eq!(a.option_i64("hours").unwrap_err(), "--hours 需要整数，收到: soon");
+        assert_eq!(a.option_i64("max-pages").unwrap(), None);
+        assert_eq!(a.positional(2, "名称").unwrap_err(), "缺少参数: <名称>");
+    }
+}
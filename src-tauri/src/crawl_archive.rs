@@ -0,0 +1,448 @@
+// 爬取页面归档：开启后排名搜索页和 Listing 详情页按日期 gzip 保存在数据目录的 crawl_archive 下，
+// 排名看起来不对时可以查看原始页面；解析规则修正后从归档页面重新提取结果，不必重新爬取
+// 按 UTC 日期分目录，文件名加 .gz；同一关键词每次检测单独一个目录，记录引擎和邮编（meta.json），
+// 同一天多次检测、不同邮编的结果互不覆盖，重新解析时按产生页面的引擎选择解析规则：
+//   <YYYY-MM-DD>/search/<站点>/<关键词>/<HHMMSS>-<引擎>[-<邮编>]/page<N>.html.gz
+//   <YYYY-MM-DD>/listing/<站点>/<ASIN>.html.gz
+// 旧版本的搜索页直接保存在关键词目录下（没有 meta.json），按原生引擎解析
+// Python 脚本通过环境变量 ARCHIVE_DIR_ENV 得到根目录，保存规则见 scripts/crawl_archive.py
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::crawler::{ListingResult, RankingResult};
+use crate::crawler_backend::fixture_slug;
+use crate::db;
+
+pub const ARCHIVE_DIR_ENV: &str = "THESAURUS_CRAWL_ARCHIVE";
+
+// 设置项：是否开启归档、保留天数
+pub const ENABLED_KEY: &str = "crawl_archive_enabled";
+pub const RETENTION_KEY: &str = "crawl_archive_retention_days";
+pub const DEFAULT_RETENTION_DAYS: i64 = 14;
+
+// 重新解析排名时最多读取的页数（实际以归档中存在的页为准）
+const REPARSE_MAX_PAGES: i64 = 20;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+const TIME_FORMAT: &str = "%H%M%S";
+const RUN_META_FILE: &str = "meta.json";
+
+// 产生搜索页的引擎，与 scripts/crawl_archive.py 一致
+pub const ENGINE_NATIVE: &str = "native";
+pub const ENGINE_PLAYWRIGHT: &str = "playwright";
+
+/// 归档中的一次关键词检测（所有页）或一个 Listing 页面
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub date: String,
+    pub kind: String,              // search / listing
+    pub country: String,
+    pub name: String,              // 关键词目录名（fixture_slug）或 ASIN
+    pub run: Option<String>,       // 检测目录名，旧版本归档和 Listing 为 None
+    pub engine: Option<String>,    // native / playwright，未记录时为 None
+    pub location: Option<String>,  // 检测时使用的邮编
+    pub pages: i64,
+    pub size_bytes: i64,
+}
+
+/// 一次搜索检测的归档信息（meta.json）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RunMeta {
+    engine: String,
+    keyword: String,
+    location: Option<String>,
+    started_at: String,
+}
+
+fn root() -> Option<PathBuf> {
+    db::data_dir().map(|dir| dir.join("crawl_archive"))
+}
+
+/// 开启归档时返回根目录，未开启或数据库未初始化时返回 None
+pub fn active_root() -> Option<PathBuf> {
+    if !db::is_initialized() {
+        return None;
+    }
+    let root = root()?;
+    let enabled = db::get_setting(ENABLED_KEY).ok().flatten();
+    (enabled.as_deref() == Some("true")).then_some(root)
+}
+
+pub fn retention_days() -> i64 {
+    db::get_setting(RETENTION_KEY)
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+        .unwrap_or(DEFAULT_RETENTION_DAYS)
+}
+
+fn keyword_dir(root: &Path, date: &str, country: &str, keyword: &str) -> PathBuf {
+    root.join(date).join("search").join(country.to_uppercase()).join(fixture_slug(keyword))
+}
+
+fn search_page_path(run_dir: &Path, page_num: i64) -> PathBuf {
+    run_dir.join(format!("page{}.html.gz", page_num))
+}
+
+fn listing_page_path(root: &Path, date: &str, country: &str, asin: &str) -> PathBuf {
+    root.join(date)
+        .join("listing")
+        .join(country.to_uppercase())
+        .join(format!("{}.html.gz", fixture_slug(asin)))
+}
+
+fn write_page(path: &Path, html: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let file = std::fs::File::create(path).map_err(|e| e.to_string())?;
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    encoder.write_all(html.as_bytes()).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn read_page(path: &Path) -> Result<String, String> {
+    let file = std::fs::File::open(path).map_err(|_| format!("归档页面不存在: {}", path.display()))?;
+    let mut html = String::new();
+    GzDecoder::new(file)
+        .read_to_string(&mut html)
+        .map_err(|e| format!("读取归档页面失败 {}: {}", path.display(), e))?;
+    Ok(html)
+}
+
+// 创建检测目录并写入 meta.json；目录名已存在（同一秒内重复检测）时加 -2、-3 后缀
+fn create_run_dir(keyword_dir: &Path, time: &str, meta: &RunMeta) -> Result<PathBuf, String> {
+    let mut base = format!("{}-{}", time, meta.engine);
+    if let Some(location) = meta.location.as_deref().map(fixture_slug).filter(|s| !s.is_empty()) {
+        base = format!("{}-{}", base, location);
+    }
+    std::fs::create_dir_all(keyword_dir).map_err(|e| e.to_string())?;
+    for n in 1.. {
+        let dir = keyword_dir.join(if n == 1 { base.clone() } else { format!("{}-{}", base, n) });
+        match std::fs::create_dir(&dir) {
+            Ok(()) => {
+                let json = serde_json::to_string(meta).map_err(|e| e.to_string())?;
+                std::fs::write(dir.join(RUN_META_FILE), json).map_err(|e| e.to_string())?;
+                return Ok(dir);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+    unreachable!()
+}
+
+/// 开始归档一次关键词检测（原生引擎调用），返回本次检测的目录；失败只打印日志，返回 None
+pub fn start_search_run(root: &Path, country: &str, keyword: &str, engine: &str, location: Option<&str>) -> Option<PathBuf> {
+    let now = Utc::now();
+    let meta = RunMeta {
+        engine: engine.to_string(),
+        keyword: keyword.to_string(),
+        location: location.map(str::to_string),
+        started_at: now.to_rfc3339(),
+    };
+    let dir = keyword_dir(root, &now.format(DATE_FORMAT).to_string(), country, keyword);
+    create_run_dir(&dir, &now.format(TIME_FORMAT).to_string(), &meta)
+        .inspect_err(|e| eprintln!("[Archive] 创建 {} 失败: {}", dir.display(), e))
+        .ok()
+}
+
+/// 保存一页搜索结果到检测目录；失败只打印日志，不影响检测
+pub fn save_search_page(run_dir: &Path, page_num: i64, html: &str) {
+    let path = search_page_path(run_dir, page_num);
+    if let Err(e) = write_page(&path, html) {
+        eprintln!("[Archive] 保存 {} 失败: {}", path.display(), e);
+    }
+}
+
+fn read_run_meta(run_dir: &Path) -> Option<RunMeta> {
+    let json = std::fs::read_to_string(run_dir.join(RUN_META_FILE)).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+fn valid_date(date: &str) -> Result<(), String> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT)
+        .map(|_| ())
+        .map_err(|_| format!("日期格式应为 YYYY-MM-DD，收到: {}", date))
+}
+
+fn dir_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+fn file_size(path: &Path) -> i64 {
+    std::fs::metadata(path).map(|m| m.len() as i64).unwrap_or(0)
+}
+
+fn page_files(dir: &Path) -> Vec<String> {
+    dir_names(dir)
+        .into_iter()
+        .filter(|name| name.starts_with("page") && name.ends_with(".html.gz"))
+        .collect()
+}
+
+fn search_entry(date: &str, country: &str, keyword: &str, dir: &Path, run: Option<String>) -> ArchiveEntry {
+    let pages = page_files(dir);
+    let meta = run.as_ref().and_then(|_| read_run_meta(dir));
+    ArchiveEntry {
+        date: date.to_string(),
+        kind: "search".to_string(),
+        country: country.to_string(),
+        name: keyword.to_string(),
+        run,
+        engine: meta.as_ref().map(|m| m.engine.clone()),
+        location: meta.and_then(|m| m.location),
+        pages: pages.len() as i64,
+        size_bytes: pages.iter().map(|p| file_size(&dir.join(p))).sum(),
+    }
+}
+
+fn list_in(root: &Path, date: Option<&str>) -> Vec<ArchiveEntry> {
+    let dates = match date {
+        Some(date) => vec![date.to_string()],
+        None => dir_names(root).into_iter().rev().collect(),
+    };
+    let mut entries = Vec::new();
+    for date in dates.iter().filter(|d| valid_date(d).is_ok()) {
+        let day = root.join(date);
+        for country in dir_names(&day.join("search")) {
+            for keyword in dir_names(&day.join("search").join(&country)) {
+                let dir = day.join("search").join(&country).join(&keyword);
+                // 旧版本直接保存在关键词目录下的页面
+                if !page_files(&dir).is_empty() {
+                    entries.push(search_entry(date, &country, &keyword, &dir, None));
+                }
+                // 同一关键词的多次检测，最新的在前
+                for run in dir_names(&dir).into_iter().rev().filter(|name| dir.join(name).is_dir()) {
+                    entries.push(search_entry(date, &country, &keyword, &dir.join(&run), Some(run)));
+                }
+            }
+        }
+        for country in dir_names(&day.join("listing")) {
+            for file in dir_names(&day.join("listing").join(&country)) {
+                let Some(asin) = file.strip_suffix(".html.gz") else { continue };
+                entries.push(ArchiveEntry {
+                    date: date.clone(),
+                    kind: "listing".to_string(),
+                    country: country.clone(),
+                    name: asin.to_uppercase(),
+                    run: None,
+                    engine: None,
+                    location: None,
+                    pages: 1,
+                    size_bytes: file_size(&day.join("listing").join(&country).join(&file)),
+                });
+            }
+        }
+    }
+    entries
+}
+
+/// 列出归档内容（date 为空时列出所有日期，最新的在前）
+pub fn list_entries(date: Option<&str>) -> Result<Vec<ArchiveEntry>, String> {
+    if let Some(date) = date {
+        valid_date(date)?;
+    }
+    let root = root().ok_or_else(|| "数据库未初始化".to_string())?;
+    Ok(list_in(&root, date))
+}
+
+fn cleanup_in(root: &Path, retention_days: i64, today: NaiveDate) -> usize {
+    let cutoff = today - Duration::days(retention_days.max(1));
+    let mut removed = 0;
+    for name in dir_names(root) {
+        let expired = NaiveDate::parse_from_str(&name, DATE_FORMAT).is_ok_and(|date| date < cutoff);
+        if expired && std::fs::remove_dir_all(root.join(&name)).is_ok() {
+            removed += 1;
+        }
+    }
+    removed
+}
+
+/// 删除超过保留天数的归档，返回删除的天数（未开启归档时也清理以前留下的归档）
+pub fn cleanup() -> usize {
+    match root() {
+        Some(root) => cleanup_in(&root, retention_days(), Utc::now().date_naive()),
+        None => 0,
+    }
+}
+
+// 找到要重新解析的检测目录：指定 run 时使用该目录，否则取最新一次检测；只有旧版本归档时使用关键词目录
+fn find_run_dir(root: &Path, date: &str, country: &str, keyword: &str, run: Option<&str>) -> Result<PathBuf, String> {
+    let dir = keyword_dir(root, date, country, keyword);
+    if let Some(run) = run {
+        if run.is_empty() || run.contains(['/', '\\']) || run.starts_with('.') {
+            return Err(format!("检测目录名无效: {}", run));
+        }
+        let run_dir = dir.join(run);
+        return if run_dir.is_dir() { Ok(run_dir) } else { Err(format!("归档页面不存在: {}", run_dir.display())) };
+    }
+    match dir_names(&dir).into_iter().rev().find(|name| dir.join(name).is_dir()) {
+        Some(latest) => Ok(dir.join(latest)),
+        None if !page_files(&dir).is_empty() => Ok(dir),
+        None => Err(format!("归档页面不存在: {}", dir.display())),
+    }
+}
+
+// 用原生引擎的解析规则重新解析检测目录中的页面
+async fn reparse_native(run_dir: &Path, keyword: String, asin: String, country: String, location: Option<String>) -> RankingResult {
+    let group = crate::native_crawler::KeywordGroup {
+        country: country.clone(),
+        keyword: keyword.clone(),
+        location,
+        targets: vec![(0, asin.clone())],
+    };
+    let results = crate::native_crawler::check_group(group, REPARSE_MAX_PAGES, None, |page_num| {
+        let page = read_page(&search_page_path(run_dir, page_num));
+        async move { page }
+    })
+    .await;
+    results
+        .into_iter()
+        .next()
+        .map(|(_, result)| result)
+        .unwrap_or_else(|| crate::crawler::failed_result(keyword, asin, country, "解析未返回结果".to_string()))
+}
+
+// 按检测目录记录的引擎选择解析规则；旧版本归档没有记录引擎，按原生引擎解析
+async fn reparse_ranking_in(
+    root: &Path,
+    date: &str,
+    keyword: String,
+    asin: String,
+    country: String,
+    run: Option<&str>,
+) -> Result<RankingResult, String> {
+    let run_dir = find_run_dir(root, date, &country, &keyword, run)?;
+    let meta = read_run_meta(&run_dir).unwrap_or_default();
+    let keyword = if meta.keyword.is_empty() { keyword } else { meta.keyword };
+    if meta.engine == ENGINE_PLAYWRIGHT {
+        return crate::python_crawler::reparse_ranking(keyword, asin, country, run_dir).await;
+    }
+    Ok(reparse_native(&run_dir, keyword, asin, country, meta.location).await)
+}
+
+/// 从归档的搜索页重新计算排名，run 为空时使用当天最新一次检测
+pub async fn reparse_ranking(
+    date: &str,
+    keyword: String,
+    asin: String,
+    country: String,
+    run: Option<String>,
+) -> Result<RankingResult, String> {
+    valid_date(date)?;
+    let root = root().ok_or_else(|| "数据库未初始化".to_string())?;
+    reparse_ranking_in(&root, date, keyword, asin, country, run.as_deref()).await
+}
+
+/// 从归档的详情页重新提取 Listing 信息（用 Python 脚本的提取规则，不访问网络）
+pub async fn reparse_listing(date: &str, asin: String, country: String) -> Result<ListingResult, String> {
+    valid_date(date)?;
+    let root = root().ok_or_else(|| "数据库未初始化".to_string())?;
+    let path = listing_page_path(&root, date, &country, &asin);
+    if !path.exists() {
+        return Err(format!("归档页面不存在: {}", path.display()));
+    }
+    crate::python_crawler::reparse_listing(asin, country, path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_reparse_and_cleanup() {
+        let root = std::env::temp_dir().join(format!("crawl-archive-test-{}", std::process::id()));
+        std::fs::remove_dir_all(&root).ok();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/search/US/desk-lamp");
+        let meta = |location: Option<&str>| RunMeta {
+            engine: ENGINE_NATIVE.to_string(),
+            keyword: "Desk Lamp".to_string(),
+            location: location.map(str::to_string),
+            started_at: "2026-01-02T08:00:00+00:00".to_string(),
+        };
+
+        // 同一秒内两次检测、不同邮编的检测各自一个目录，互不覆盖
+        let keyword = keyword_dir(&root, "2026-01-02", "us", "Desk Lamp");
+        let first = create_run_dir(&keyword, "080000", &meta(None)).unwrap();
+        let second = create_run_dir(&keyword, "080000", &meta(None)).unwrap();
+        let zip = create_run_dir(&keyword, "090000", &meta(Some("90001"))).unwrap();
+        assert!(first.ends_with("2026-01-02/search/US/desk-lamp/080000-native"));
+        assert!(second.ends_with("080000-native-2"));
+        assert!(zip.ends_with("090000-native-90001"));
+
+        // 按录制数据的两页搜索结果写入第一次检测，最新一次检测只有第 1 页
+        for page_num in 1..=2 {
+            let html = std::fs::read_to_string(fixtures.join(format!("page{}.html", page_num))).unwrap();
+            save_search_page(&first, page_num, &html);
+        }
+        let page1 = std::fs::read_to_string(fixtures.join("page1.html")).unwrap();
+        save_search_page(&zip, 1, &page1);
+        write_page(&listing_page_path(&root, "2026-01-02", "US", "B0TARGET01"), "<html></html>").unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let reparse = |date: &str, run: Option<&str>| {
+            runtime.block_on(reparse_ranking_in(
+                &root,
+                date,
+                "desk-lamp".to_string(),
+                "B0PAGE2001".to_string(),
+                "US".to_string(),
+                run,
+            ))
+        };
+        let result = reparse("2026-01-02", Some("080000-native")).unwrap();
+        assert_eq!((result.organic_rank, result.organic_page, result.pages_checked), (Some(2), Some(2), Some(2)));
+        assert_eq!(result.keyword, "Desk Lamp");
+        // 未指定检测目录时使用最新一次，邮编来自 meta.json
+        let latest = reparse("2026-01-02", None).unwrap();
+        assert_eq!((latest.organic_rank, latest.location.as_deref()), (None, Some("90001")));
+        assert!(reparse("2026-01-03", None).unwrap_err().contains("归档页面不存在"));
+        assert!(reparse("2026-01-02", Some("../listing")).unwrap_err().contains("检测目录名无效"));
+
+        // 旧版本直接保存在关键词目录下的页面按原生引擎解析
+        save_search_page(&keyword_dir(&root, "2026-01-01", "US", "desk lamp"), 1, &page1);
+        assert!(reparse("2026-01-01", None).unwrap().error.is_none());
+
+        let entries = list_in(&root, Some("2026-01-02"));
+        let runs: Vec<_> = entries.iter().map(|e| (e.kind.as_str(), e.run.as_deref(), e.pages)).collect();
+        assert_eq!(
+            runs,
+            vec![
+                ("search", Some("090000-native-90001"), 1),
+                ("search", Some("080000-native-2"), 0),
+                ("search", Some("080000-native"), 2),
+                ("listing", None, 1),
+            ]
+        );
+        assert_eq!((entries[0].engine.as_deref(), entries[0].location.as_deref()), (Some("native"), Some("90001")));
+        let legacy = list_in(&root, Some("2026-01-01"));
+        assert_eq!((legacy[0].name.as_str(), legacy[0].run.as_deref(), legacy[0].engine.as_deref()), ("desk-lamp", None, None));
+
+        // 保留 14 天：1 月 15 日两天都保留，16 日删除 1 月 1 日，17 日删除 1 月 2 日
+        let day = |d: &str| NaiveDate::parse_from_str(d, DATE_FORMAT).unwrap();
+        assert_eq!(cleanup_in(&root, 14, day("2026-01-15")), 0);
+        assert_eq!(cleanup_in(&root, 14, day("2026-01-16")), 1);
+        assert_eq!(cleanup_in(&root, 14, day("2026-01-17")), 1);
+        assert!(list_in(&root, None).is_empty());
+        std::fs::remove_dir_all(&root).ok();
+    }
+}
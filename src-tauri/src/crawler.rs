@@ -1,12 +1,15 @@
 use serde::{Deserialize, Serialize};
-use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader, Write};
+use std::collections::HashMap;
+use std::process::{Child, ChildStdin};
+use std::io::Write;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
 
-#[cfg(windows)]
-use std::os::windows::process::CommandExt;
+use crate::crawler_backend::{self, CrawlerBackend, ListingCallback, RankingCallback};
+use crate::{delivery_location, rate_limit};
 
-#[cfg(windows)]
-const CREATE_NO_WINDOW: u32 = 0x08000000;
 
 // 排名检测结果
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -17,6 +20,9 @@ pub struct RankingResult {
 
     pub organic_rank: Option<i64>,
     pub organic_page: Option<i64>,
+    // 自然结果中的绝对位置（按各页实际自然结果数累计，旧版爬虫不返回）
+    #[serde(default)]
+    pub organic_position: Option<i64>,
     pub sponsored_rank: Option<i64>,
     pub sponsored_page: Option<i64>,
 
@@ -28,8 +34,21 @@ pub struct RankingResult {
     pub checked_at: String,
     pub error: Option<String>,
     pub warning: Option<String>,  // 警告信息（如地理限制）
+
+    // 任务明细：单个关键词的检测耗时、使用的代理、实际翻到的页数（批量模式才有）
+    #[serde(default)]
+    pub duration_ms: Option<i64>,
+    #[serde(default)]
+    pub proxy: Option<String>,
+    #[serde(default)]
+    pub pages_checked: Option<i64>,
+    #[serde(default)]
+    pub location: Option<String>,  // 检测使用的配送邮编
 }
 
+// 排名检测项：(monitoring_id, keyword, asin, country, 配送邮编)，邮编为空时使用站点设置
+pub type RankingItem = (i64, String, String, String, Option<String>);
+
 // 产品详细信息
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ProductInfo {
@@ -42,348 +61,613 @@ pub struct ProductInfo {
     pub availability: Option<String>,  // 商品可用性信息
 }
 
-// 获取 Python 脚本路径 (Playwright 版本)
-fn get_script_path() -> Result<std::path::PathBuf, String> {
-    // 尝试多个可能的路径
-    let possible_paths = vec![
-        // 开发环境
-        std::env::current_dir()
-            .map(|p| p.join("scripts").join("amazon_crawler_playwright.py"))
-            .ok(),
-        // Tauri 资源目录
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| p.join("scripts").join("amazon_crawler_playwright.py")),
-        // macOS .app 包内
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| p.join("Resources").join("scripts").join("amazon_crawler_playwright.py")),
-        // 直接在 src-tauri 目录
-        Some(std::path::PathBuf::from("src-tauri/scripts/amazon_crawler_playwright.py")),
-    ];
-
-    for path in possible_paths.into_iter().flatten() {
-        if path.exists() {
-            return Ok(path);
+// ==================== 批量检测运行控制 ====================
+
+// 取消后等待 Python 脚本自行退出（关闭浏览器）的时间，超时强制结束进程
+const CANCEL_GRACE_SECS: u64 = 60;
+
+// 每个任务最多记录的子进程消息数
+const MAX_TASK_MESSAGES: i64 = 500;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunState {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+// 正在运行的批量检测概况（给前端展示）
+#[derive(Debug, Serialize, Clone)]
+pub struct RunInfo {
+    pub run_id: i64,
+    pub source: String,     // manual / auto / cron / cli
+    pub state: RunState,
+    pub total: i64,
+    pub completed: i64,
+    pub started_at: String,
+}
+
+// 批量检测的运行句柄，可跨线程克隆
+// 暂停/继续/取消通过 stdin 通知 Python 脚本：正在检测的关键词会做完，之后不再开始新的关键词
+#[derive(Clone)]
+pub struct RunControl {
+    pub id: i64,
+    source: String,
+    total: i64,
+    started_at: String,
+    state: Arc<Mutex<RunState>>,
+    completed: Arc<AtomicI64>,
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+    child: Arc<Mutex<Option<Child>>>,
+    task_id: Arc<Mutex<Option<i64>>>,
+    logged: Arc<AtomicI64>,
+}
+
+static NEXT_RUN_ID: AtomicI64 = AtomicI64::new(1);
+static RUNS: Lazy<Mutex<HashMap<i64, RunControl>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+impl RunControl {
+    // 创建不登记的句柄（如命令行），外部无法暂停或取消
+    pub fn new(source: &str, total: i64) -> Self {
+        Self {
+            id: NEXT_RUN_ID.fetch_add(1, Ordering::SeqCst),
+            source: source.to_string(),
+            total,
+            started_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            state: Arc::new(Mutex::new(RunState::Running)),
+            completed: Arc::new(AtomicI64::new(0)),
+            stdin: Arc::new(Mutex::new(None)),
+            child: Arc::new(Mutex::new(None)),
+            task_id: Arc::new(Mutex::new(None)),
+            logged: Arc::new(AtomicI64::new(0)),
         }
     }
 
-    Err("找不到 Python 爬虫脚本 amazon_crawler_playwright.py".to_string())
-}
+    // 创建并登记，运行结束后需调用 unregister
+    pub fn register(source: &str, total: i64) -> Self {
+        let control = Self::new(source, total);
+        RUNS.lock().insert(control.id, control.clone());
+        control
+    }
 
-// 检查 Python 是否可用
-fn check_python() -> Result<String, String> {
-    // 尝试不同的 Python 命令
-    for python_cmd in &["python3", "python"] {
-        let mut cmd = Command::new(python_cmd);
-        cmd.arg("--version");
+    pub fn unregister(&self) {
+        RUNS.lock().remove(&self.id);
+    }
 
-        #[cfg(windows)]
-        cmd.creation_flags(CREATE_NO_WINDOW);
+    pub fn get(run_id: i64) -> Option<Self> {
+        RUNS.lock().get(&run_id).cloned()
+    }
 
-        let result = cmd.output();
+    pub fn list() -> Vec<RunInfo> {
+        let mut runs: Vec<RunInfo> = RUNS.lock().values().map(|c| c.info()).collect();
+        runs.sort_by_key(|r| r.run_id);
+        runs
+    }
 
-        if let Ok(output) = result {
-            if output.status.success() {
-                return Ok(python_cmd.to_string());
-            }
+    pub fn info(&self) -> RunInfo {
+        RunInfo {
+            run_id: self.id,
+            source: self.source.clone(),
+            state: self.state(),
+            total: self.total,
+            completed: self.completed.load(Ordering::SeqCst),
+            started_at: self.started_at.clone(),
         }
     }
 
-    Err("未找到 Python。请确保已安装 Python 3 并添加到 PATH".to_string())
-}
+    pub fn state(&self) -> RunState {
+        *self.state.lock()
+    }
 
-// 检查依赖是否安装 (Playwright 版本)
-fn check_dependencies(python_cmd: &str) -> Result<(), String> {
-    let check_script = r#"
-import sys
-try:
-    from playwright.async_api import async_playwright
-    print("ok")
-except ImportError as e:
-    print(f"missing:{e}")
-    sys.exit(1)
-"#;
+    pub fn is_cancelled(&self) -> bool {
+        self.state() == RunState::Cancelled
+    }
 
-    let mut cmd = Command::new(python_cmd);
-    cmd.arg("-c").arg(check_script);
+    pub fn pause(&self) -> Result<(), String> {
+        self.transition(RunState::Running, RunState::Paused, "pause")
+    }
 
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
+    pub fn resume(&self) -> Result<(), String> {
+        self.transition(RunState::Paused, RunState::Running, "resume")
+    }
 
-    let output = cmd.output()
-        .map_err(|e| format!("检查依赖失败: {}", e))?;
+    // 取消：通知脚本停止，超过宽限时间仍未退出则结束进程
+    pub fn cancel(&self) -> Result<(), String> {
+        {
+            let mut state = self.state.lock();
+            if *state == RunState::Cancelled {
+                return Err("任务已取消".to_string());
+            }
+            *state = RunState::Cancelled;
+        }
+        self.send("cancel");
+
+        let child = self.child.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_secs(CANCEL_GRACE_SECS));
+            if let Some(child) = child.lock().as_mut() {
+                eprintln!("[Batch] 取消超时，强制结束 Python 进程");
+                let _ = child.kill();
+            }
+        });
+        Ok(())
+    }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
+    // 暂停期间等待，返回 false 表示已取消（用于多批次之间）
+    pub async fn wait_while_paused(&self) -> bool {
+        loop {
+            match self.state() {
+                RunState::Running => return true,
+                RunState::Cancelled => return false,
+                RunState::Paused => tokio::time::sleep(std::time::Duration::from_millis(500)).await,
+            }
+        }
+    }
 
-    if stdout.trim() == "ok" {
+    fn transition(&self, from: RunState, to: RunState, command: &str) -> Result<(), String> {
+        {
+            let mut state = self.state.lock();
+            if *state != from {
+                return Err(format!("当前状态无法{}", if to == RunState::Paused { "暂停" } else { "继续" }));
+            }
+            *state = to;
+        }
+        self.send(command);
         Ok(())
-    } else {
-        Err(format!(
-            "缺少 Playwright 依赖。请运行: {} -m pip install playwright && playwright install chromium",
-            python_cmd
-        ))
+    }
+
+    // 通知 Python 进程不再开始该站点的关键词（批次中途进入限速冷却），正在搜索的关键词会做完
+    pub(crate) fn skip_country(&self, country: &str) {
+        self.send(&format!("skip {}", country));
+    }
+
+    // 向当前 Python 进程发送控制命令；进程未启动时忽略（启动时会补发暂停）
+    fn send(&self, command: &str) {
+        if let Some(stdin) = self.stdin.lock().as_mut() {
+            let _ = writeln!(stdin, "{}", command).and_then(|_| stdin.flush());
+        }
+    }
+
+    // 子进程槽位：由 crawler_protocol 放入新启动的进程，取消超时和看门狗从这里结束进程
+    pub(crate) fn child_slot(&self) -> Arc<Mutex<Option<Child>>> {
+        self.child.clone()
+    }
+
+    // 挂接新启动进程的 stdin；持有 stdin 锁期间检查状态，避免与 pause 竞争漏发
+    pub(crate) fn attach(&self, stdin: ChildStdin) {
+        let mut guard = self.stdin.lock();
+        *guard = Some(stdin);
+        let command = match self.state() {
+            RunState::Paused => Some("pause"),
+            RunState::Cancelled => Some("cancel"),
+            RunState::Running => None,
+        };
+        if let (Some(command), Some(stdin)) = (command, guard.as_mut()) {
+            let _ = writeln!(stdin, "{}", command).and_then(|_| stdin.flush());
+        }
+    }
+
+    // 进程输出结束后不再发送控制命令
+    pub(crate) fn detach(&self) {
+        self.stdin.lock().take();
+    }
+
+    // 关联任务记录，之后爬虫子进程的日志和 stderr 会写入该任务
+    pub fn set_task_log(&self, task_id: Option<i64>) {
+        *self.task_id.lock() = task_id;
+    }
+
+    // 记录一条子进程消息；每个任务最多记录 MAX_TASK_MESSAGES 条，避免调试输出撑大数据库
+    pub(crate) fn log(&self, level: &str, source: &str, message: &str) {
+        eprintln!("[{}] {}: {}", source, level, message);
+        let Some(task_id) = *self.task_id.lock() else {
+            return;
+        };
+        let logged = self.logged.fetch_add(1, Ordering::SeqCst);
+        if logged < MAX_TASK_MESSAGES {
+            crate::db::add_task_message(task_id, level, source, message).ok();
+        } else if logged == MAX_TASK_MESSAGES {
+            crate::db::add_task_message(task_id, "warning", source, "消息过多，之后的日志不再记录").ok();
+        }
     }
 }
 
-// 调用 Python 脚本进行搜索
-fn call_python_crawler(
-    keyword: &str,
-    target_asin: &str,
-    country: &str,
-    max_pages: i64,
-) -> Result<RankingResult, String> {
-    // 检查 Python
-    let python_cmd = check_python()?;
-
-    // 检查依赖
-    check_dependencies(&python_cmd)?;
-
-    // 获取脚本路径
-    let script_path = get_script_path()?;
-
-    // 调用 Python 脚本
-    let mut cmd = Command::new(&python_cmd);
-    cmd.arg(&script_path)
-        .arg(keyword)
-        .arg(target_asin)
-        .arg(country)
-        .arg(max_pages.to_string())
-        .arg("none")   // proxy
-        .arg("false"); // headless=false，有头模式（窗口隐藏到屏幕外）
-
-    // Windows: 隐藏命令行窗口
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
-
-    let output = cmd.output()
-        .map_err(|e| format!("执行 Python 脚本失败: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Python 脚本执行错误: {}", stderr));
-    }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    // 解析 JSON 结果
-    serde_json::from_str(&stdout).map_err(|e| {
-        format!(
-            "解析 Python 输出失败: {}. 输出: {}",
-            e,
-            stdout.chars().take(500).collect::<String>()
-        )
-    })
+// 排名检测引擎
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CrawlerEngine {
+    #[default]
+    Playwright,  // Python + Playwright 浏览器
+    Native,      // 原生 HTTP 请求 + HTML 解析（native_crawler）
 }
 
-// 异步包装器
-pub async fn search_keyword(
-    keyword: String,
-    target_asin: String,
-    country: String,
-    max_pages: i64,
-) -> RankingResult {
-    tokio::task::spawn_blocking(move || {
-        match call_python_crawler(&keyword, &target_asin, &country, max_pages) {
-            Ok(result) => result,
-            Err(e) => RankingResult {
-                keyword,
-                target_asin,
-                country,
-                organic_rank: None,
-                organic_page: None,
-                sponsored_rank: None,
-                sponsored_page: None,
-                product_info: None,
-                organic_top_50: Vec::new(),
-                sponsored_top_20: Vec::new(),
-                checked_at: chrono::Utc::now().to_rfc3339(),
-                error: Some(e),
-                warning: None,
-            },
+impl CrawlerEngine {
+    // 设置值 crawler_engine，未设置或无法识别时使用 Playwright
+    pub fn from_setting(value: Option<&str>) -> Self {
+        match value {
+            Some("native") => CrawlerEngine::Native,
+            _ => CrawlerEngine::Playwright,
         }
-    })
-    .await
-    .unwrap_or_else(|e| RankingResult {
-        keyword: String::new(),
-        target_asin: String::new(),
-        country: String::new(),
+    }
+}
+
+// 批量检测的爬虫设置
+#[derive(Debug, Clone)]
+pub struct CrawlerSettings {
+    pub engine: CrawlerEngine,
+    pub max_browsers: i64,  // 并发浏览器数量（原生引擎为 浏览器数 × 标签页数 个并发请求）
+    pub tabs_per_browser: i64,  // 每浏览器标签页数量
+    pub proxy_assignment: Option<String>,  // 代理分配表（JSON：站点 -> 代理列表，见 proxy_pool）
+}
+
+impl CrawlerSettings {
+    pub fn concurrency(&self) -> i64 {
+        (self.max_browsers * self.tabs_per_browser).max(1)
+    }
+}
+
+impl RunControl {
+    // 完成数由 check_rankings_batch 统一累加（含回退重试的结果）
+    fn record_completed(&self) {
+        self.completed.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+pub(crate) fn failed_result(keyword: String, target_asin: String, country: String, error: String) -> RankingResult {
+    RankingResult {
+        keyword,
+        target_asin,
+        country,
         organic_rank: None,
         organic_page: None,
+        organic_position: None,
         sponsored_rank: None,
         sponsored_page: None,
         product_info: None,
         organic_top_50: Vec::new(),
         sponsored_top_20: Vec::new(),
         checked_at: chrono::Utc::now().to_rfc3339(),
-        error: Some(format!("任务执行失败: {}", e)),
+        error: Some(error),
         warning: None,
-    })
+        duration_ms: None,
+        proxy: None,
+        pages_checked: None,
+        location: None,
+    }
 }
 
-// 批量进度消息
-#[derive(Debug, Deserialize)]
-#[serde(tag = "type")]
-enum BatchMessage {
-    #[serde(rename = "progress")]
-    Progress {
-        monitoring_id: i64,
-        result: RankingResult,
-    },
-    #[serde(rename = "complete")]
-    Complete {
-        total: i64,
-    },
+// 失败结果也记录配送邮编，便于按邮编排查
+fn failed_at(keyword: String, target_asin: String, country: String, location: &str, error: String) -> RankingResult {
+    let mut result = failed_result(keyword, target_asin, country, error);
+    result.location = Some(location.to_string());
+    result
 }
 
-// 批量检测接口 - 优化版本，复用浏览器实例，支持并发
-pub async fn check_rankings_batch(
-    keywords: Vec<(i64, String, String, String)>, // (monitoring_id, keyword, asin, country)
+// 检测单个监控项：Playwright 引擎走单关键词脚本，原生引擎走批量接口（含 Python 回退）
+// location 为监控项指定的配送邮编，为空时使用站点设置
+pub async fn check_single(
+    monitoring_id: i64,
+    keyword: String,
+    target_asin: String,
+    country: String,
+    location: Option<String>,
     max_pages: i64,
-    max_browsers: i64,  // 并发浏览器数量
-    tabs_per_browser: i64,  // 每浏览器标签页数量
-    proxy_list: Option<String>,  // 代理列表（逗号分隔）
-    progress_callback: impl Fn(i64, i64, String) + Send + 'static,
-) -> Vec<(i64, RankingResult)> {
-    let total = keywords.len() as i64;
+    settings: CrawlerSettings,
+) -> RankingResult {
+    let location = delivery_location::resolve(&country, location.as_deref());
+    if settings.engine == CrawlerEngine::Playwright {
+        let backend = crawler_backend::for_engine(settings.engine);
+        if let Err(e) = admit_one(backend.as_ref(), &country) {
+            return failed_at(keyword, target_asin, country, &location, e);
+        }
+        let mut result = backend
+            .search_rank(keyword.clone(), target_asin.clone(), country.clone(), location.clone(), max_pages, settings)
+            .await
+            .unwrap_or_else(|e| failed_result(keyword, target_asin, country, e));
+        result.location.get_or_insert(location);
+        record_outcome(backend.as_ref(), &result.country, result.error.as_deref());
+        return result;
+    }
+    let item = (monitoring_id, keyword.clone(), target_asin.clone(), country.clone(), Some(location.clone()));
+    check_rankings_batch(vec![item], max_pages, settings, RunControl::new("single", 1), |_, _, _, _| {})
+        .await
+        .into_iter()
+        .next()
+        .map(|(_, result)| result)
+        .unwrap_or_else(|| failed_at(keyword, target_asin, country, &location, "检测未返回结果".to_string()))
+}
 
-    if total == 0 {
-        return Vec::new();
+// ==================== 站点限速 ====================
+
+// 单项爬取前占用一次请求预算；录制数据回放等不请求站点的后端不受限制
+fn admit_one(backend: &dyn CrawlerBackend, country: &str) -> Result<(), String> {
+    if backend.rate_limited() {
+        rate_limit::reserve(country, 1)?;
     }
+    Ok(())
+}
 
-    // 尝试使用批量模式（并发）
-    match call_python_crawler_batch(keywords.clone(), max_pages, max_browsers, tabs_per_browser, proxy_list, total, progress_callback).await {
-        Ok(results) => results,
-        Err(e) => {
-            // 批量模式失败，返回错误结果
-            eprintln!("[Batch] 批量检测失败: {}", e);
-            keywords.into_iter().map(|(id, keyword, asin, country)| {
-                (id, RankingResult {
-                    keyword,
-                    target_asin: asin,
-                    country,
-                    organic_rank: None,
-                    organic_page: None,
-                    sponsored_rank: None,
-                    sponsored_page: None,
-                    product_info: None,
-                    organic_top_50: Vec::new(),
-                    sponsored_top_20: Vec::new(),
-                    checked_at: chrono::Utc::now().to_rfc3339(),
-                    error: Some(e.clone()),
-                    warning: None,
-                })
-            }).collect()
-        }
+// 把爬取结果计入站点限速状态（验证码 / 503 升级限速，成功逐步恢复）
+fn record_outcome(backend: &dyn CrawlerBackend, country: &str, error: Option<&str>) {
+    if backend.rate_limited() {
+        rate_limit::record(country, error);
     }
 }
 
-// 调用 Python 脚本批量处理（支持并发）
-async fn call_python_crawler_batch(
-    keywords: Vec<(i64, String, String, String)>,
-    max_pages: i64,
-    max_browsers: i64,  // 并发浏览器数量
-    tabs_per_browser: i64,  // 每浏览器标签页数量
-    proxy_list: Option<String>,  // 代理列表（逗号分隔）
-    total: i64,
-    progress_callback: impl Fn(i64, i64, String) + Send + 'static,
-) -> Result<Vec<(i64, RankingResult)>, String> {
-    // 检查 Python
-    let python_cmd = check_python()?;
+/// 批次占用的请求预算：每项实际请求后 spend，批次结束（完成、取消或整体失败）时退还没用上的部分，
+/// 避免取消的批次把站点本小时的预算占满
+#[derive(Default)]
+struct Budget {
+    left: Mutex<HashMap<String, i64>>,
+    reserved_at: Option<chrono::NaiveDateTime>,
+}
 
-    // 检查依赖
-    check_dependencies(&python_cmd)?;
+impl Budget {
+    fn spend(&self, country: &str) {
+        if let Some(left) = self.left.lock().get_mut(country) {
+            *left = (*left - 1).max(0);
+        }
+    }
+}
 
-    // 获取脚本路径
-    let script_path = get_script_path()?;
+impl Drop for Budget {
+    fn drop(&mut self) {
+        let Some(reserved_at) = self.reserved_at else { return };
+        for (country, left) in self.left.get_mut().drain() {
+            rate_limit::refund(&country, left, reserved_at);
+        }
+    }
+}
 
-    // 准备输入数据: [[id, keyword, asin, country], ...]
-    let input_data: Vec<(i64, &str, &str, &str)> = keywords.iter()
-        .map(|(id, kw, asin, country)| (*id, kw.as_str(), asin.as_str(), country.as_str()))
+/// 按站点占用请求预算：冷却中或预算用完的站点整体跳过，预算不足时只保留靠前的项目
+/// 返回 (保留的项目, 跳过的项目及原因, 保留项目占用的预算)
+fn admit_by_country<T>(
+    backend: &dyn CrawlerBackend,
+    items: Vec<T>,
+    country_of: impl Fn(&T) -> &str,
+) -> (Vec<T>, Vec<(T, String)>, Arc<Budget>) {
+    if !backend.rate_limited() {
+        return (items, Vec::new(), Arc::new(Budget::default()));
+    }
+    let mut wanted: Vec<(String, i64)> = Vec::new();
+    for item in &items {
+        let country = country_of(item);
+        match wanted.iter_mut().find(|(c, _)| c == country) {
+            Some((_, count)) => *count += 1,
+            None => wanted.push((country.to_string(), 1)),
+        }
+    }
+    let mut allowance: HashMap<String, Result<i64, String>> = wanted
+        .into_iter()
+        .map(|(country, count)| {
+            let granted = rate_limit::reserve(&country, count);
+            (country, granted)
+        })
         .collect();
-    let input_json = serde_json::to_string(&input_data)
-        .map_err(|e| format!("序列化输入数据失败: {}", e))?;
-
-    // 处理代理列表
-    let proxy_arg = proxy_list.unwrap_or_else(|| "none".to_string());
-
-    // 在阻塞任务中执行
-    tokio::task::spawn_blocking(move || {
-        // 调用 Python 脚本 --batch 模式（带并发参数、标签页数量、代理列表）
-        let mut cmd = Command::new(&python_cmd);
-        cmd.arg(&script_path)
-            .arg("--batch")
-            .arg("false")  // headless=false，有头模式（窗口隐藏到屏幕外）
-            .arg(max_pages.to_string())
-            .arg(max_browsers.to_string())  // 并发浏览器数量
-            .arg(tabs_per_browser.to_string())  // 每浏览器标签页数量
-            .arg(&proxy_arg)  // 代理列表
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        // Windows: 隐藏命令行窗口
-        #[cfg(windows)]
-        cmd.creation_flags(CREATE_NO_WINDOW);
-
-        let mut child = cmd.spawn()
-            .map_err(|e| format!("启动 Python 脚本失败: {}", e))?;
-
-        // 写入 stdin
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(input_json.as_bytes())
-                .map_err(|e| format!("写入 stdin 失败: {}", e))?;
+    let reserved_at = chrono::Utc::now().naive_utc();
+
+    let mut admitted = Vec::new();
+    let mut skipped = Vec::new();
+    let mut reserved: HashMap<String, i64> = HashMap::new();
+    for item in items {
+        let country = country_of(&item).to_string();
+        match allowance.get_mut(&country) {
+            Some(Ok(left)) if *left > 0 => {
+                *left -= 1;
+                *reserved.entry(country).or_insert(0) += 1;
+                admitted.push(item);
+            }
+            Some(Ok(_)) => skipped.push((item, format!("{} 站点本小时请求预算不足", country))),
+            Some(Err(reason)) => skipped.push((item, reason.clone())),
+            None => admitted.push(item),
         }
+    }
+    let budget = Budget { left: Mutex::new(reserved), reserved_at: Some(reserved_at) };
+    (admitted, skipped, Arc::new(budget))
+}
+
+// 限速跳过的项目按原因汇总记入任务日志
+fn log_rate_limited<T>(control: &RunControl, skipped: &[(T, String)]) {
+    let mut reasons: Vec<(&str, usize)> = Vec::new();
+    for (_, reason) in skipped {
+        match reasons.iter_mut().find(|(r, _)| r == reason) {
+            Some((_, count)) => *count += 1,
+            None => reasons.push((reason, 1)),
+        }
+    }
+    for (reason, count) in reasons {
+        control.log("warning", "rate_limit", &format!("{}，跳过 {} 个关键词", reason, count));
+    }
+}
+
+// 按完成顺序编号并回调 on_result(已完成数, 总数, monitoring_id, 结果)，主检测和回退重试共用
+struct ResultEmitter<F> {
+    on_result: Arc<Mutex<F>>,
+    completed: Arc<AtomicI64>,
+    total: i64,
+    control: RunControl,
+}
 
-        // 读取 stdout
-        let stdout = child.stdout.take()
-            .ok_or_else(|| "无法获取 stdout".to_string())?;
-        let reader = BufReader::new(stdout);
+impl<F> Clone for ResultEmitter<F> {
+    fn clone(&self) -> Self {
+        Self {
+            on_result: self.on_result.clone(),
+            completed: self.completed.clone(),
+            total: self.total,
+            control: self.control.clone(),
+        }
+    }
+}
 
-        let mut results: Vec<(i64, RankingResult)> = Vec::new();
-        let mut completed_count = 0i64;
+impl<F: Fn(i64, i64, i64, &RankingResult)> ResultEmitter<F> {
+    fn emit(&self, monitoring_id: i64, result: &RankingResult) {
+        let on_result = self.on_result.lock();
+        let current = self.completed.fetch_add(1, Ordering::SeqCst) + 1;
+        self.control.record_completed();
+        (on_result)(current, self.total, monitoring_id, result);
+    }
+}
 
-        for line in reader.lines() {
-            let line = line.map_err(|e| format!("读取输出失败: {}", e))?;
-            let line = line.trim();
+// 原生引擎遇到验证码或页面结构无法解析时，用 Playwright 重新检测这些关键词
+fn needs_python_fallback(result: &RankingResult) -> bool {
+    result.error.as_deref().is_some_and(|e| {
+        matches!(
+            crate::check_failures::classify_message(e),
+            crate::check_failures::FailureKind::Captcha | crate::check_failures::FailureKind::ParseError
+        )
+    })
+}
 
-            if line.is_empty() {
-                continue;
-            }
+// 批量检测接口 - 按引擎选择爬虫后端，复用浏览器实例，支持并发
+// 每完成一个监控项调用一次 on_result(已完成数, 总数, monitoring_id, 结果)，调用方可在此实时保存和推送
+// 需要回退的结果先暂存，由回退后端重新检测，回退也失败时返回原结果
+// 站点限速冷却中或预算用完的关键词不检测，按失败结果回调（原因汇总记入任务日志），并发按限速等级降低
+// 取消后只返回已完成的结果；每项的配送邮编在这里确定，后端收到的邮编都不为空
+pub async fn check_rankings_batch(
+    keywords: Vec<RankingItem>,
+    max_pages: i64,
+    settings: CrawlerSettings,
+    control: RunControl,
+    on_result: impl Fn(i64, i64, i64, &RankingResult) + Send + 'static,
+) -> Vec<(i64, RankingResult)> {
+    if keywords.is_empty() || control.is_cancelled() {
+        return Vec::new();
+    }
 
-            // 尝试解析为 JSON
-            match serde_json::from_str::<BatchMessage>(line) {
-                Ok(BatchMessage::Progress { monitoring_id, result }) => {
-                    completed_count += 1;
-                    progress_callback(completed_count, total, format!("已完成: {}", result.keyword));
-                    results.push((monitoring_id, result));
-                }
-                Ok(BatchMessage::Complete { total: _ }) => {
-                    // 批量处理完成
-                    break;
-                }
-                Err(_) => {
-                    // 可能是调试输出，忽略
-                    eprintln!("[Batch] 忽略非 JSON 输出: {}", line);
-                }
+    let backend = crawler_backend::for_engine(settings.engine);
+    let fallback = crawler_backend::ranking_fallback(settings.engine);
+
+    let keywords: Vec<RankingItem> = keywords
+        .into_iter()
+        .map(|(id, keyword, asin, country, location)| {
+            let location = delivery_location::resolve(&country, location.as_deref());
+            (id, keyword, asin, country, Some(location))
+        })
+        .collect();
+    let total = keywords.len() as i64;
+    let (keywords, skipped, budget) = admit_by_country(backend.as_ref(), keywords, |(_, _, _, country, _)| country.as_str());
+    log_rate_limited(&control, &skipped);
+
+    let emitter = ResultEmitter {
+        on_result: Arc::new(Mutex::new(on_result)),
+        completed: Arc::new(AtomicI64::new(0)),
+        total,
+        control: control.clone(),
+    };
+
+    // 限速跳过的关键词同样回调，调用方照常统计、保存并放入重试队列
+    let mut results: Vec<(i64, RankingResult)> = skipped
+        .into_iter()
+        .map(|((id, keyword, asin, country, location), reason)| {
+            let result = failed_at(keyword, asin, country, location.as_deref().unwrap_or_default(), reason);
+            emitter.emit(id, &result);
+            (id, result)
+        })
+        .collect();
+    if keywords.is_empty() {
+        return results;
+    }
+    let settings = if backend.rate_limited() {
+        let countries: Vec<String> = keywords.iter().map(|(_, _, _, country, _)| country.clone()).collect();
+        let throttled = rate_limit::throttle_settings(&settings, &countries);
+        if throttled.concurrency() < settings.concurrency() {
+            control.log(
+                "warning",
+                "rate_limit",
+                &format!("站点限速中，并发降为 {} 个浏览器 × {} 个标签页", throttled.max_browsers, throttled.tabs_per_browser),
+            );
+        }
+        throttled
+    } else {
+        settings
+    };
+
+    let held: Arc<Mutex<Vec<(i64, RankingResult)>>> = Arc::new(Mutex::new(Vec::new()));
+    let callback: RankingCallback = {
+        let emitter = emitter.clone();
+        let held = held.clone();
+        let can_fallback = fallback.is_some();
+        let backend = backend.clone();
+        let budget = budget.clone();
+        Box::new(move |monitoring_id, result| {
+            budget.spend(&result.country);
+            record_outcome(backend.as_ref(), &result.country, result.error.as_deref());
+            if can_fallback && needs_python_fallback(result) {
+                held.lock().push((monitoring_id, result.clone()));
+            } else {
+                emitter.emit(monitoring_id, result);
             }
+        })
+    };
+
+    let outcome = backend
+        .check_rankings(keywords.clone(), max_pages, settings.clone(), control.clone(), callback)
+        .await;
+    match outcome {
+        Ok(checked) => results.extend(checked),
+        Err(e) => {
+            // 批量模式整体失败（环境未安装、脚本启动失败等），每项按失败结果回调，调用方照常保存和统计
+            eprintln!("[Batch] {} 批量检测失败: {}", backend.name(), e);
+            results.extend(keywords.into_iter().map(|(id, keyword, asin, country, location)| {
+                let result = failed_at(keyword, asin, country, location.as_deref().unwrap_or_default(), e.clone());
+                emitter.emit(id, &result);
+                (id, result)
+            }));
+            return results;
         }
+    }
 
-        // 等待进程结束
-        let status = child.wait()
-            .map_err(|e| format!("等待进程结束失败: {}", e))?;
+    let held = std::mem::take(&mut *held.lock());
+    results.retain(|(id, _)| !held.iter().any(|(held_id, _)| held_id == id));
+    let Some(fallback) = fallback else {
+        return results;
+    };
+    if held.is_empty() || control.is_cancelled() {
+        return results;
+    }
 
-        if !status.success() {
-            eprintln!("[Batch] Python 脚本非正常退出: {:?}", status);
+    println!(
+        "[Batch] {} 个关键词 {} 检测失败（验证码或解析错误），改用 {} 重新检测",
+        held.len(),
+        backend.name(),
+        fallback.name()
+    );
+    let retry: Vec<RankingItem> = keywords
+        .into_iter()
+        .filter(|(id, ..)| held.iter().any(|(held_id, _)| held_id == id))
+        .collect();
+    // 触发验证码后站点可能已进入冷却，冷却中的关键词不回退，保留原检测结果
+    let (retry, skipped, retry_budget) = admit_by_country(fallback.as_ref(), retry, |(_, _, _, country, _)| country.as_str());
+    log_rate_limited(&control, &skipped);
+    let mut unretried: Vec<(i64, RankingResult)> = held
+        .iter()
+        .filter(|(id, _)| !retry.iter().any(|(retry_id, ..)| retry_id == id))
+        .cloned()
+        .collect();
+    if !retry.is_empty() {
+        let callback: RankingCallback = {
+            let emitter = emitter.clone();
+            let fallback = fallback.clone();
+            Box::new(move |monitoring_id, result| {
+                retry_budget.spend(&result.country);
+                record_outcome(fallback.as_ref(), &result.country, result.error.as_deref());
+                emitter.emit(monitoring_id, result)
+            })
+        };
+        match fallback.check_rankings(retry, max_pages, settings, control, callback).await {
+            Ok(fallback_results) => results.extend(fallback_results),
+            Err(e) => {
+                eprintln!("[Batch] {} 回退失败，保留原检测结果: {}", fallback.name(), e);
+                unretried = held;
+            }
         }
-
-        Ok(results)
-    })
-    .await
-    .map_err(|e| format!("任务执行失败: {}", e))?
+    }
+    for (monitoring_id, result) in unretried {
+        emitter.emit(monitoring_id, &result);
+        results.push((monitoring_id, result));
+    }
+    results
 }
 
 // ==================== Listing 爬虫 ====================
@@ -404,61 +688,15 @@ pub struct ListingResult {
     pub description: Option<String>,
     pub fetched_at: String,
     pub error: Option<String>,
+    #[serde(default)]
+    pub location: Option<String>,  // 抓取使用的配送邮编（站点设置）
 }
 
-// 获取 Listing 爬虫脚本路径
-fn get_listing_script_path() -> Result<std::path::PathBuf, String> {
-    let possible_paths = vec![
-        std::env::current_dir()
-            .map(|p| p.join("scripts").join("amazon_listing_crawler.py"))
-            .ok(),
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| p.join("scripts").join("amazon_listing_crawler.py")),
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| p.join("Resources").join("scripts").join("amazon_listing_crawler.py")),
-        Some(std::path::PathBuf::from("src-tauri/scripts/amazon_listing_crawler.py")),
-    ];
-
-    for path in possible_paths.into_iter().flatten() {
-        if path.exists() {
-            return Ok(path);
-        }
-    }
-
-    Err("找不到 Python 爬虫脚本 amazon_listing_crawler.py".to_string())
-}
 
-// 爬取单个 ASIN 的 Listing 信息
-pub async fn fetch_listing_info(asin: String, country: String) -> ListingResult {
-    tokio::task::spawn_blocking(move || {
-        match call_listing_crawler(&asin, &country) {
-            Ok(result) => result,
-            Err(e) => ListingResult {
-                asin,
-                country,
-                title: None,
-                price: None,
-                rating: None,
-                review_count: None,
-                bsr_rank: None,
-                date_first_available: None,
-                image_url: None,
-                bullets: Vec::new(),
-                description: None,
-                fetched_at: chrono::Utc::now().to_rfc3339(),
-                error: Some(e),
-            },
-        }
-    })
-    .await
-    .unwrap_or_else(|e| ListingResult {
-        asin: String::new(),
-        country: String::new(),
+pub(crate) fn failed_listing(asin: String, country: String, error: String) -> ListingResult {
+    ListingResult {
+        asin,
+        country,
         title: None,
         price: None,
         rating: None,
@@ -469,60 +707,26 @@ pub async fn fetch_listing_info(asin: String, country: String) -> ListingResult
         bullets: Vec::new(),
         description: None,
         fetched_at: chrono::Utc::now().to_rfc3339(),
-        error: Some(format!("任务执行失败: {}", e)),
-    })
-}
-
-// 调用 Python Listing 爬虫
-fn call_listing_crawler(asin: &str, country: &str) -> Result<ListingResult, String> {
-    let python_cmd = check_python()?;
-    check_dependencies(&python_cmd)?;
-    let script_path = get_listing_script_path()?;
-
-    let mut cmd = Command::new(&python_cmd);
-    cmd.arg(&script_path)
-        .arg(asin)
-        .arg(country)
-        .arg("new"); // 无头模式
-
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
-
-    let output = cmd.output()
-        .map_err(|e| format!("执行 Python 脚本失败: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Python 脚本执行错误: {}", stderr));
+        error: Some(error),
+        location: None,
     }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    serde_json::from_str(&stdout).map_err(|e| {
-        format!(
-            "解析 Python 输出失败: {}. 输出: {}",
-            e,
-            stdout.chars().take(500).collect::<String>()
-        )
-    })
 }
 
-// 批量爬取进度消息
-#[derive(Debug, Deserialize)]
-#[serde(tag = "type")]
-enum ListingBatchMessage {
-    #[serde(rename = "progress")]
-    Progress {
-        competitor_id: i64,
-        result: ListingResult,
-    },
-    #[serde(rename = "complete")]
-    Complete {
-        total: i64,
-    },
+// 爬取单个 ASIN 的 Listing 信息
+pub async fn fetch_listing_info(asin: String, country: String) -> ListingResult {
+    let backend = crawler_backend::for_engine(CrawlerEngine::Playwright);
+    if let Err(e) = admit_one(backend.as_ref(), &country) {
+        return failed_listing(asin, country, e);
+    }
+    let result = backend
+        .fetch_listing(asin.clone(), country.clone())
+        .await
+        .unwrap_or_else(|e| failed_listing(asin, country, e));
+    record_outcome(backend.as_ref(), &result.country, result.error.as_deref());
+    result
 }
 
-// 批量爬取 Listing 信息
+// 批量爬取 Listing 信息；站点限速时跳过的 ASIN 返回带限速原因的失败结果
 pub async fn fetch_listings_batch(
     items: Vec<(i64, String, String)>, // (competitor_id, asin, country)
     progress_callback: impl Fn(i64, i64, String) + Send + 'static,
@@ -533,111 +737,43 @@ pub async fn fetch_listings_batch(
         return Vec::new();
     }
 
-    match call_listing_crawler_batch(items.clone(), total, progress_callback).await {
-        Ok(results) => results,
+    let backend = crawler_backend::for_engine(CrawlerEngine::Playwright);
+    let (items, skipped, budget) = admit_by_country(backend.as_ref(), items, |(_, _, country)| country.as_str());
+    let mut results: Vec<(i64, ListingResult)> = skipped
+        .into_iter()
+        .map(|((id, asin, country), reason)| (id, failed_listing(asin, country, reason)))
+        .collect();
+    for (current, (_, result)) in results.iter().enumerate() {
+        progress_callback(current as i64 + 1, total, format!("已跳过: {}", result.asin));
+    }
+    if items.is_empty() {
+        return results;
+    }
+
+    let mut completed_count = results.len() as i64;
+    let on_result: ListingCallback = {
+        let backend = backend.clone();
+        Box::new(move |_, result| {
+            budget.spend(&result.country);
+            record_outcome(backend.as_ref(), &result.country, result.error.as_deref());
+            completed_count += 1;
+            let title_preview = result.title.as_ref()
+                .map(|t| t.chars().take(30).collect::<String>())
+                .unwrap_or_else(|| result.asin.clone());
+            progress_callback(completed_count, total, format!("已获取: {}", title_preview));
+        })
+    };
+
+    match backend.fetch_listings_batch(items.clone(), on_result).await {
+        Ok(fetched) => results.extend(fetched),
         Err(e) => {
             eprintln!("[ListingBatch] 批量爬取失败: {}", e);
-            items.into_iter().map(|(id, asin, country)| {
-                (id, ListingResult {
-                    asin,
-                    country,
-                    title: None,
-                    price: None,
-                    rating: None,
-                    review_count: None,
-                    bsr_rank: None,
-                    date_first_available: None,
-                    image_url: None,
-                    bullets: Vec::new(),
-                    description: None,
-                    fetched_at: chrono::Utc::now().to_rfc3339(),
-                    error: Some(e.clone()),
-                })
-            }).collect()
+            results.extend(items.into_iter().map(|(id, asin, country)| {
+                (id, failed_listing(asin, country, e.clone()))
+            }));
         }
     }
-}
-
-// 调用 Python 脚本批量爬取 Listing
-async fn call_listing_crawler_batch(
-    items: Vec<(i64, String, String)>,
-    total: i64,
-    progress_callback: impl Fn(i64, i64, String) + Send + 'static,
-) -> Result<Vec<(i64, ListingResult)>, String> {
-    let python_cmd = check_python()?;
-    check_dependencies(&python_cmd)?;
-    let script_path = get_listing_script_path()?;
-
-    let input_data: Vec<(i64, &str, &str)> = items.iter()
-        .map(|(id, asin, country)| (*id, asin.as_str(), country.as_str()))
-        .collect();
-    let input_json = serde_json::to_string(&input_data)
-        .map_err(|e| format!("序列化输入数据失败: {}", e))?;
-
-    tokio::task::spawn_blocking(move || {
-        let mut cmd = Command::new(&python_cmd);
-        cmd.arg(&script_path)
-            .arg("--batch")
-            .arg("new")  // 无头模式
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
-
-        #[cfg(windows)]
-        cmd.creation_flags(CREATE_NO_WINDOW);
-
-        let mut child = cmd.spawn()
-            .map_err(|e| format!("启动 Python 脚本失败: {}", e))?;
-
-        if let Some(mut stdin) = child.stdin.take() {
-            stdin.write_all(input_json.as_bytes())
-                .map_err(|e| format!("写入 stdin 失败: {}", e))?;
-        }
-
-        let stdout = child.stdout.take()
-            .ok_or_else(|| "无法获取 stdout".to_string())?;
-        let reader = BufReader::new(stdout);
-
-        let mut results: Vec<(i64, ListingResult)> = Vec::new();
-        let mut completed_count = 0i64;
-
-        for line in reader.lines() {
-            let line = line.map_err(|e| format!("读取输出失败: {}", e))?;
-            let line = line.trim();
-
-            if line.is_empty() {
-                continue;
-            }
-
-            match serde_json::from_str::<ListingBatchMessage>(line) {
-                Ok(ListingBatchMessage::Progress { competitor_id, result }) => {
-                    completed_count += 1;
-                    let title_preview = result.title.as_ref()
-                        .map(|t| t.chars().take(30).collect::<String>())
-                        .unwrap_or_else(|| result.asin.clone());
-                    progress_callback(completed_count, total, format!("已获取: {}", title_preview));
-                    results.push((competitor_id, result));
-                }
-                Ok(ListingBatchMessage::Complete { total: _ }) => {
-                    break;
-                }
-                Err(_) => {
-                    eprintln!("[ListingBatch] 忽略非 JSON 输出: {}", line);
-                }
-            }
-        }
-
-        let status = child.wait()
-            .map_err(|e| format!("等待进程结束失败: {}", e))?;
-
-        if !status.success() {
-            eprintln!("[ListingBatch] Python 脚本非正常退出: {:?}", status);
-        }
-
-        Ok(results)
-    })
-    .await
-    .map_err(|e| format!("任务执行失败: {}", e))?
+    results
 }
 
 // ==================== 评论爬虫 ====================
@@ -670,118 +806,31 @@ pub struct ReviewSummary {
     pub by_star: std::collections::HashMap<String, i64>,
 }
 
-// 获取评论爬虫脚本路径
-fn get_review_script_path() -> Result<std::path::PathBuf, String> {
-    let possible_paths = vec![
-        std::env::current_dir()
-            .map(|p| p.join("scripts").join("amazon_review_crawler.py"))
-            .ok(),
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| p.join("scripts").join("amazon_review_crawler.py")),
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| p.join("Resources").join("scripts").join("amazon_review_crawler.py")),
-        Some(std::path::PathBuf::from("src-tauri/scripts/amazon_review_crawler.py")),
-    ];
-
-    for path in possible_paths.into_iter().flatten() {
-        if path.exists() {
-            return Ok(path);
-        }
-    }
-
-    Err("找不到 Python 爬虫脚本 amazon_review_crawler.py".to_string())
-}
-
-// Python 输出结构（用于解析）
-#[derive(Debug, Deserialize)]
-struct PythonReviewOutput {
-    #[serde(rename = "type")]
-    msg_type: String,
-    result: Option<ReviewResult>,
-}
 
 // 爬取单个 ASIN 的评论
 pub async fn fetch_reviews(asin: String, country: String) -> ReviewResult {
-    tokio::task::spawn_blocking(move || {
-        match call_review_crawler(&asin, &country) {
-            Ok(result) => result,
-            Err(e) => ReviewResult {
-                asin,
-                country,
-                reviews: Vec::new(),
-                summary: ReviewSummary {
-                    total: 0,
-                    by_star: std::collections::HashMap::new(),
-                },
-                fetched_at: chrono::Utc::now().to_rfc3339(),
-                error: Some(e),
-            },
-        }
-    })
-    .await
-    .unwrap_or_else(|e| ReviewResult {
-        asin: String::new(),
-        country: String::new(),
+    let backend = crawler_backend::for_engine(CrawlerEngine::Playwright);
+    let fetched = match admit_one(backend.as_ref(), &country) {
+        Ok(()) => backend.fetch_reviews(asin.clone(), country.clone()).await,
+        Err(e) => Err(e),
+    };
+    let result = fetched.unwrap_or_else(|e| failed_reviews(asin, country, e));
+    record_outcome(backend.as_ref(), &result.country, result.error.as_deref());
+    result
+}
+
+fn failed_reviews(asin: String, country: String, error: String) -> ReviewResult {
+    ReviewResult {
+        asin,
+        country,
         reviews: Vec::new(),
         summary: ReviewSummary {
             total: 0,
             by_star: std::collections::HashMap::new(),
         },
         fetched_at: chrono::Utc::now().to_rfc3339(),
-        error: Some(format!("任务执行失败: {}", e)),
-    })
-}
-
-// 调用 Python 评论爬虫
-fn call_review_crawler(asin: &str, country: &str) -> Result<ReviewResult, String> {
-    let python_cmd = check_python()?;
-    check_dependencies(&python_cmd)?;
-    let script_path = get_review_script_path()?;
-
-    let mut cmd = Command::new(&python_cmd);
-    cmd.arg(&script_path)
-        .arg(asin)
-        .arg(country)
-        .arg("new"); // headless=new 模式
-
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
-
-    let output = cmd.output()
-        .map_err(|e| format!("执行 Python 脚本失败: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Python 脚本执行错误: {}", stderr));
+        error: Some(error),
     }
-
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    // 解析输出（可能包含多行，我们只需要最后一行 complete 消息）
-    for line in stdout.lines().rev() {
-        let line = line.trim();
-        if line.is_empty() {
-            continue;
-        }
-
-        if let Ok(parsed) = serde_json::from_str::<PythonReviewOutput>(line) {
-            if parsed.msg_type == "complete" {
-                if let Some(result) = parsed.result {
-                    return Ok(result);
-                }
-            }
-        }
-    }
-
-    Err(format!(
-        "无法解析 Python 输出. 输出: {}",
-        stdout.chars().take(500).collect::<String>()
-    ))
 }
 
 // ==================== BSR 爬虫 ====================
@@ -809,55 +858,23 @@ pub struct BsrResult {
     pub error: Option<String>,
 }
 
-// 获取 BSR 爬虫脚本路径
-fn get_bsr_script_path() -> Result<std::path::PathBuf, String> {
-    let possible_paths = vec![
-        std::env::current_dir()
-            .map(|p| p.join("scripts").join("amazon_bsr_crawler.py"))
-            .ok(),
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| p.join("scripts").join("amazon_bsr_crawler.py")),
-        std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
-            .map(|p| p.join("Resources").join("scripts").join("amazon_bsr_crawler.py")),
-        Some(std::path::PathBuf::from("src-tauri/scripts/amazon_bsr_crawler.py")),
-    ];
-
-    for path in possible_paths.into_iter().flatten() {
-        if path.exists() {
-            return Ok(path);
-        }
-    }
-
-    Err("找不到 Python 爬虫脚本 amazon_bsr_crawler.py".to_string())
-}
 
 // 爬取类目 BSR 数据
 pub async fn fetch_category_bsr(marketplace: String, category_id: String) -> BsrResult {
-    tokio::task::spawn_blocking(move || {
-        match call_bsr_crawler(&marketplace, &category_id) {
-            Ok(result) => result,
-            Err(e) => BsrResult {
-                marketplace,
-                category_id,
-                products: Vec::new(),
-                snapshot_date: chrono::Utc::now().to_rfc3339(),
-                error: Some(e),
-            },
-        }
-    })
-    .await
-    .unwrap_or_else(|e| BsrResult {
-        marketplace: String::new(),
-        category_id: String::new(),
+    let backend = crawler_backend::for_engine(CrawlerEngine::Playwright);
+    let fetched = match admit_one(backend.as_ref(), &marketplace) {
+        Ok(()) => backend.fetch_category_bsr(marketplace.clone(), category_id.clone()).await,
+        Err(e) => Err(e),
+    };
+    let result = fetched.unwrap_or_else(|e| BsrResult {
+        marketplace,
+        category_id,
         products: Vec::new(),
         snapshot_date: chrono::Utc::now().to_rfc3339(),
-        error: Some(format!("任务执行失败: {}", e)),
-    })
+        error: Some(e),
+    });
+    record_outcome(backend.as_ref(), &result.marketplace, result.error.as_deref());
+    result
 }
 
 // ==================== 子类目发现 ====================
@@ -879,105 +896,99 @@ pub struct SubcategoryResult {
     pub error: Option<String>,
 }
 
+
 // 发现子类目
 pub async fn discover_subcategories(marketplace: String, parent_category: String) -> SubcategoryResult {
-    tokio::task::spawn_blocking(move || {
-        match call_discover_subcategories(&marketplace, &parent_category) {
-            Ok(result) => result,
-            Err(e) => SubcategoryResult {
-                marketplace,
-                parent_category,
-                subcategories: Vec::new(),
-                error: Some(e),
-            },
-        }
-    })
-    .await
-    .unwrap_or_else(|e| SubcategoryResult {
-        marketplace: String::new(),
-        parent_category: String::new(),
+    let backend = crawler_backend::for_engine(CrawlerEngine::Playwright);
+    let fetched = match admit_one(backend.as_ref(), &marketplace) {
+        Ok(()) => backend.discover_subcategories(marketplace.clone(), parent_category.clone()).await,
+        Err(e) => Err(e),
+    };
+    let result = fetched.unwrap_or_else(|e| SubcategoryResult {
+        marketplace,
+        parent_category,
         subcategories: Vec::new(),
-        error: Some(format!("任务执行失败: {}", e)),
-    })
+        error: Some(e),
+    });
+    record_outcome(backend.as_ref(), &result.marketplace, result.error.as_deref());
+    result
 }
 
-// 调用 Python 子类目发现
-fn call_discover_subcategories(marketplace: &str, parent_category: &str) -> Result<SubcategoryResult, String> {
-    let python_cmd = check_python()?;
-    check_dependencies(&python_cmd)?;
-    let script_path = get_bsr_script_path()?;
-
-    let mut cmd = Command::new(&python_cmd);
-    cmd.arg(&script_path)
-        .arg("discover")
-        .arg(marketplace)
-        .arg(parent_category)
-        .arg("true"); // headless 模式
-
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
-
-    let output = cmd.output()
-        .map_err(|e| format!("执行 Python 脚本失败: {}", e))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Python 脚本执行错误: {}", stderr));
+    #[test]
+    fn test_run_control_transitions() {
+        let control = RunControl::register("manual", 3);
+        assert!(RunControl::list().iter().any(|r| r.run_id == control.id));
+
+        assert!(control.resume().is_err());
+        control.pause().unwrap();
+        assert_eq!(RunControl::get(control.id).unwrap().state(), RunState::Paused);
+        assert!(control.pause().is_err());
+        control.resume().unwrap();
+        control.cancel().unwrap();
+        assert!(control.is_cancelled());
+        assert!(control.cancel().is_err());
+        assert!(!tokio::runtime::Runtime::new().unwrap().block_on(control.wait_while_paused()));
+
+        control.unregister();
+        assert!(RunControl::get(control.id).is_none());
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    serde_json::from_str(&stdout).map_err(|e| {
-        format!(
-            "解析 Python 输出失败: {}. 输出: {}",
-            e,
-            stdout.chars().take(500).collect::<String>()
-        )
-    })
-}
+    // 整个批量检测失败的后端（如 Python 环境未安装）
+    struct BrokenBackend;
 
-// 调用 Python BSR 爬虫
-fn call_bsr_crawler(marketplace: &str, category_id: &str) -> Result<BsrResult, String> {
-    let python_cmd = check_python()?;
-    check_dependencies(&python_cmd)?;
-    let script_path = get_bsr_script_path()?;
-
-    let mut cmd = Command::new(&python_cmd);
-    cmd.arg(&script_path)
-        .arg(marketplace)
-        .arg(category_id)
-        .arg("true"); // headless 模式
-
-    #[cfg(windows)]
-    cmd.creation_flags(CREATE_NO_WINDOW);
+    impl CrawlerBackend for BrokenBackend {
+        fn name(&self) -> &'static str {
+            "broken"
+        }
 
-    let output = cmd.output()
-        .map_err(|e| format!("执行 Python 脚本失败: {}", e))?;
+        fn rate_limited(&self) -> bool {
+            false
+        }
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Python 脚本执行错误: {}", stderr));
+        fn check_rankings(
+            &self,
+            _keywords: Vec<RankingItem>,
+            _max_pages: i64,
+            _settings: CrawlerSettings,
+            _control: RunControl,
+            _on_result: crawler_backend::RankingCallback,
+        ) -> crawler_backend::BoxFuture<'_, Result<Vec<(i64, RankingResult)>, String>> {
+            Box::pin(std::future::ready(Err("Python 环境未安装".to_string())))
+        }
     }
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-
-    serde_json::from_str(&stdout).map_err(|e| {
-        format!(
-            "解析 Python 输出失败: {}. 输出: {}",
-            e,
-            stdout.chars().take(500).collect::<String>()
-        )
-    })
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
     #[test]
-    fn test_check_python() {
-        // 这个测试假设系统安装了 Python
-        let result = check_python();
-        println!("Python check result: {:?}", result);
+    fn test_batch_failure_reports_every_item() {
+        let _guard = crawler_backend::override_for_test(Arc::new(BrokenBackend));
+        let keywords = vec![
+            (1, "desk lamp".to_string(), "B0TARGET01".to_string(), "US".to_string(), None),
+            (2, "lamp".to_string(), "B0TARGET01".to_string(), "DE".to_string(), Some("80331".to_string())),
+        ];
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let reported_clone = reported.clone();
+        let results = tokio::runtime::Runtime::new().unwrap().block_on(check_rankings_batch(
+            keywords,
+            1,
+            CrawlerSettings { engine: CrawlerEngine::Playwright, max_browsers: 1, tabs_per_browser: 1, proxy_assignment: None },
+            RunControl::new("test", 2),
+            move |current, total, id, result| {
+                reported_clone.lock().push((current, total, id, result.error.clone(), result.location.clone()));
+            },
+        ));
+
+        // 失败结果逐项回调，调用方才能保存、统计和加入重试队列
+        assert_eq!(results.len(), 2);
+        let error = Some("Python 环境未安装".to_string());
+        assert_eq!(
+            *reported.lock(),
+            vec![
+                (1, 2, 1, error.clone(), Some("10001".to_string())),
+                (2, 2, 2, error, Some("80331".to_string())),
+            ]
+        );
     }
 }
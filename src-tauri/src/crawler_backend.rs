@@ -0,0 +1,339 @@
+// 爬虫后端：排名检测、Listing、评论、BSR、子类目发现的统一接口
+// 实现：Python 子进程（python_crawler）、原生 HTTP（native_crawler，目前只支持排名检测）、
+// 录制数据回放（FixtureBackend，用于离线测试定时检测、智能文案和市场调研）
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use serde::de::DeserializeOwned;
+
+use crate::crawler::{
+    BsrResult, CrawlerEngine, CrawlerSettings, ListingResult, RankingItem, RankingResult, ReviewResult, RunControl,
+    SubcategoryResult,
+};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+// 每完成一个监控项回调一次：(monitoring_id, 结果)
+pub type RankingCallback = Box<dyn FnMut(i64, &RankingResult) + Send>;
+
+// 每完成一个 Listing 回调一次：(调用方传入的 id, 结果)
+pub type ListingCallback = Box<dyn FnMut(i64, &ListingResult) + Send>;
+
+// 设置后所有爬取都走录制数据（值为录制目录），便于命令行离线演练
+pub const FIXTURE_DIR_ENV: &str = "THESAURUS_CRAWLER_FIXTURES";
+
+fn unsupported<'a, T: Send + 'a>(backend: &str, task: &str) -> BoxFuture<'a, Result<T, String>> {
+    let message = format!("{} 后端不支持{}", backend, task);
+    Box::pin(std::future::ready(Err(message)))
+}
+
+/// 爬虫后端；失败统一返回 Err(错误信息)，由 crawler 中的入口函数转换为带 error 的结果
+pub trait CrawlerBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// 是否请求真实站点；是则受 rate_limit 的站点预算和冷却控制
+    fn rate_limited(&self) -> bool {
+        true
+    }
+
+    /// 批量检测排名；取消后只返回已完成的结果，每项的配送邮编已由 crawler 确定
+    fn check_rankings(
+        &self,
+        keywords: Vec<RankingItem>,
+        max_pages: i64,
+        settings: CrawlerSettings,
+        control: RunControl,
+        on_result: RankingCallback,
+    ) -> BoxFuture<'_, Result<Vec<(i64, RankingResult)>, String>>;
+
+    /// 检测单个关键词，默认按只有一项的批量检测处理
+    fn search_rank(
+        &self,
+        keyword: String,
+        target_asin: String,
+        country: String,
+        location: String,
+        max_pages: i64,
+        settings: CrawlerSettings,
+    ) -> BoxFuture<'_, Result<RankingResult, String>> {
+        Box::pin(async move {
+            let item = (0, keyword, target_asin, country, Some(location));
+            let results = self
+                .check_rankings(vec![item], max_pages, settings, RunControl::new("single", 1), Box::new(|_, _| {}))
+                .await?;
+            results.into_iter().next().map(|(_, r)| r).ok_or_else(|| "检测未返回结果".to_string())
+        })
+    }
+
+    fn fetch_listing(&self, _asin: String, _country: String) -> BoxFuture<'_, Result<ListingResult, String>> {
+        unsupported(self.name(), " Listing 抓取")
+    }
+
+    /// 批量抓取 Listing，默认逐个抓取；单个失败记在结果的 error 中
+    fn fetch_listings_batch(
+        &self,
+        items: Vec<(i64, String, String)>,  // (id, asin, country)
+        mut on_result: ListingCallback,
+    ) -> BoxFuture<'_, Result<Vec<(i64, ListingResult)>, String>> {
+        Box::pin(async move {
+            let mut results = Vec::with_capacity(items.len());
+            for (id, asin, country) in items {
+                let result = self
+                    .fetch_listing(asin.clone(), country.clone())
+                    .await
+                    .unwrap_or_else(|e| crate::crawler::failed_listing(asin, country, e));
+                on_result(id, &result);
+                results.push((id, result));
+            }
+            Ok(results)
+        })
+    }
+
+    fn fetch_reviews(&self, _asin: String, _country: String) -> BoxFuture<'_, Result<ReviewResult, String>> {
+        unsupported(self.name(), "评论抓取")
+    }
+
+    fn fetch_category_bsr(&self, _marketplace: String, _category_id: String) -> BoxFuture<'_, Result<BsrResult, String>> {
+        unsupported(self.name(), " BSR 抓取")
+    }
+
+    fn discover_subcategories(
+        &self,
+        _marketplace: String,
+        _parent_category: String,
+    ) -> BoxFuture<'_, Result<SubcategoryResult, String>> {
+        unsupported(self.name(), "子类目发现")
+    }
+}
+
+// ==================== 后端选择 ====================
+
+static OVERRIDE: Lazy<RwLock<Option<Arc<dyn CrawlerBackend>>>> = Lazy::new(|| RwLock::new(None));
+
+// 使用替换后端的测试串行执行（替换对整个进程生效）
+#[cfg(test)]
+static OVERRIDE_LOCK: Lazy<parking_lot::Mutex<()>> = Lazy::new(|| parking_lot::Mutex::new(()));
+
+/// 替换期间持有；释放时恢复按引擎选择（测试断言失败 panic 时同样恢复）
+#[cfg(test)]
+pub struct OverrideGuard {
+    _lock: parking_lot::MutexGuard<'static, ()>,
+}
+
+#[cfg(test)]
+impl Drop for OverrideGuard {
+    fn drop(&mut self) {
+        *OVERRIDE.write() = None;
+    }
+}
+
+/// 替换所有爬取使用的后端（测试用），返回的 guard 释放前一直有效
+#[cfg(test)]
+pub fn override_for_test(backend: Arc<dyn CrawlerBackend>) -> OverrideGuard {
+    let lock = OVERRIDE_LOCK.lock();
+    *OVERRIDE.write() = Some(backend);
+    OverrideGuard { _lock: lock }
+}
+
+fn override_backend() -> Option<Arc<dyn CrawlerBackend>> {
+    if let Some(backend) = OVERRIDE.read().clone() {
+        return Some(backend);
+    }
+    std::env::var(FIXTURE_DIR_ENV)
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(|dir| Arc::new(FixtureBackend::new(dir)) as Arc<dyn CrawlerBackend>)
+}
+
+/// 检测引擎对应的后端；Listing、评论、BSR 等只有 Python 实现，调用方传 Playwright
+pub fn for_engine(engine: CrawlerEngine) -> Arc<dyn CrawlerBackend> {
+    if let Some(backend) = override_backend() {
+        return backend;
+    }
+    match engine {
+        CrawlerEngine::Playwright => Arc::new(crate::python_crawler::PythonBackend),
+        CrawlerEngine::Native => Arc::new(crate::native_crawler::NativeBackend),
+    }
+}
+
+/// 原生引擎遇到验证码或解析失败时的回退后端：Python 环境可用才回退，替换了后端时不回退
+pub fn ranking_fallback(engine: CrawlerEngine) -> Option<Arc<dyn CrawlerBackend>> {
+    if engine != CrawlerEngine::Native || override_backend().is_some() || !crate::python_crawler::environment_ready() {
+        return None;
+    }
+    Some(Arc::new(crate::python_crawler::PythonBackend))
+}
+
+// ==================== 录制数据回放 ====================
+
+/// 从目录读取录制的页面和结果，目录结构：
+///   search/<站点>/<关键词>/page<N>.html      搜索结果页（按原生引擎的解析规则计算排名）
+///   listing/<站点>/<ASIN>.json               ListingResult
+///   reviews/<站点>/<ASIN>.json               ReviewResult
+///   bsr/<站点>/<类目ID>.json                 BsrResult
+///   subcategories/<站点>/<父类目>.json       SubcategoryResult
+/// 关键词和类目名按 fixture_slug 转换为目录名
+pub struct FixtureBackend {
+    root: PathBuf,
+}
+
+/// 录制目录中的名称：小写，字母数字以外的字符替换为 "-"
+pub fn fixture_slug(value: &str) -> String {
+    let mut slug = String::new();
+    for ch in value.trim().to_lowercase().chars() {
+        if ch.is_alphanumeric() {
+            slug.push(ch);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
+
+impl FixtureBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn read(&self, path: PathBuf) -> Result<String, String> {
+        std::fs::read_to_string(&path).map_err(|_| format!("录制数据不存在: {}", path.display()))
+    }
+
+    fn read_json<T: DeserializeOwned>(&self, kind: &str, country: &str, name: &str) -> Result<T, String> {
+        let path = self.root.join(kind).join(country).join(format!("{}.json", fixture_slug(name)));
+        let text = self.read(path.clone())?;
+        serde_json::from_str(&text).map_err(|e| format!("录制数据格式错误 {}: {}", path.display(), e))
+    }
+
+    fn search_page_path(&self, country: &str, keyword: &str, page_num: i64) -> PathBuf {
+        self.root.join("search").join(country).join(fixture_slug(keyword)).join(format!("page{}.html", page_num))
+    }
+}
+
+impl CrawlerBackend for FixtureBackend {
+    fn name(&self) -> &'static str {
+        "fixture"
+    }
+
+    fn rate_limited(&self) -> bool {
+        false
+    }
+
+    fn check_rankings(
+        &self,
+        keywords: Vec<RankingItem>,
+        max_pages: i64,
+        _settings: CrawlerSettings,
+        control: RunControl,
+        mut on_result: RankingCallback,
+    ) -> BoxFuture<'_, Result<Vec<(i64, RankingResult)>, String>> {
+        Box::pin(async move {
+            let mut results = Vec::with_capacity(keywords.len());
+            for group in crate::native_crawler::group_keywords(&keywords) {
+                if !control.wait_while_paused().await {
+                    break;
+                }
+                let (country, keyword) = (group.country.clone(), group.keyword.clone());
+                let group_results = crate::native_crawler::check_group(group, max_pages, None, |page_num| {
+                    let page = self.read(self.search_page_path(&country, &keyword, page_num));
+                    async move { page }
+                })
+                .await;
+                for (id, result) in group_results {
+                    on_result(id, &result);
+                    results.push((id, result));
+                }
+            }
+            Ok(results)
+        })
+    }
+
+    fn fetch_listing(&self, asin: String, country: String) -> BoxFuture<'_, Result<ListingResult, String>> {
+        Box::pin(async move { self.read_json("listing", &country, &asin) })
+    }
+
+    fn fetch_reviews(&self, asin: String, country: String) -> BoxFuture<'_, Result<ReviewResult, String>> {
+        Box::pin(async move { self.read_json("reviews", &country, &asin) })
+    }
+
+    fn fetch_category_bsr(&self, marketplace: String, category_id: String) -> BoxFuture<'_, Result<BsrResult, String>> {
+        Box::pin(async move { self.read_json("bsr", &marketplace, &category_id) })
+    }
+
+    fn discover_subcategories(
+        &self,
+        marketplace: String,
+        parent_category: String,
+    ) -> BoxFuture<'_, Result<SubcategoryResult, String>> {
+        Box::pin(async move { self.read_json("subcategories", &marketplace, &parent_category) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crawler;
+
+    #[test]
+    fn test_fixture_backend_through_crawler_entry_points() {
+        assert_eq!(fixture_slug("  Desk Lamp / LED  "), "desk-lamp-led");
+        assert_eq!(fixture_slug("Schreibtischlampe für Büro"), "schreibtischlampe-für-büro");
+
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let _guard = override_for_test(Arc::new(FixtureBackend::new(root)));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        // 排名检测：同一关键词的两个监控项共用录制页面，第 3 页不存在时保留前两页的结果
+        let keywords = vec![
+            (1, "Desk Lamp".to_string(), "B0TARGET01".to_string(), "US".to_string(), None),
+            (2, "desk lamp".to_string(), "B0PAGE2001".to_string(), "US".to_string(), None),
+            (3, "unknown keyword".to_string(), "B0TARGET01".to_string(), "US".to_string(), Some("94105".to_string())),
+        ];
+        let progress = Arc::new(parking_lot::Mutex::new(Vec::new()));
+        let progress_clone = progress.clone();
+        let settings = CrawlerSettings {
+            engine: CrawlerEngine::Playwright,
+            max_browsers: 1,
+            tabs_per_browser: 1,
+            proxy_assignment: None,
+        };
+        let results = runtime.block_on(crawler::check_rankings_batch(
+            keywords,
+            3,
+            settings,
+            RunControl::new("test", 3),
+            move |current, total, id, _| progress_clone.lock().push((current, total, id)),
+        ));
+        assert_eq!(*progress.lock(), vec![(1, 3, 1), (2, 3, 2), (3, 3, 3)]);
+        let by_id = |id: i64| &results.iter().find(|(i, _)| *i == id).unwrap().1;
+        assert_eq!((by_id(1).organic_rank, by_id(1).sponsored_rank, by_id(1).pages_checked), (Some(3), Some(6), Some(2)));
+        assert_eq!((by_id(2).organic_rank, by_id(2).organic_page), (Some(2), Some(2)));
+        assert!(by_id(3).error.as_deref().unwrap().contains("录制数据不存在"));
+        // 未指定邮编的项使用站点默认邮编，结果都带有检测使用的邮编
+        assert_eq!((by_id(1).location.as_deref(), by_id(3).location.as_deref()), (Some("10001"), Some("94105")));
+
+        // Listing（单个和批量）、评论、BSR、子类目
+        let listing = runtime.block_on(crawler::fetch_listing_info("B0TARGET01".to_string(), "US".to_string()));
+        assert_eq!(listing.title.as_deref(), Some("LED Desk Lamp with USB Charging Port, Dimmable"));
+        let batch = runtime.block_on(crawler::fetch_listings_batch(
+            vec![(10, "B0TARGET01".to_string(), "US".to_string()), (11, "B0MISSING1".to_string(), "US".to_string())],
+            |_, _, _| {},
+        ));
+        assert!(batch[0].1.error.is_none() && batch[1].1.error.is_some());
+
+        let reviews = runtime.block_on(crawler::fetch_reviews("B0TARGET01".to_string(), "US".to_string()));
+        assert_eq!((reviews.reviews.len(), reviews.summary.total), (2, 2));
+
+        let bsr = runtime.block_on(crawler::fetch_category_bsr("US".to_string(), "1063306".to_string()));
+        assert_eq!(bsr.products.iter().map(|p| p.rank).collect::<Vec<_>>(), vec![1, 2]);
+
+        let subcategories =
+            runtime.block_on(crawler::discover_subcategories("US".to_string(), "Home & Kitchen".to_string()));
+        assert_eq!(subcategories.subcategories.len(), 2);
+    }
+}
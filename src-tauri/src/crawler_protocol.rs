@@ -0,0 +1,414 @@
+// 爬虫子进程协议：脚本 stdout 每行一条 JSON 消息（见 scripts/crawler_protocol.py），stderr 逐行记入任务日志
+// 第一条消息必须是 handshake，协议版本或脚本名不符时结束进程并报告不匹配
+// 每个子进程配一个看门狗：连续无输出或总运行时间超限时强制结束
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::crawler::{RunControl, RunState};
+
+// 协议版本，修改消息格式时与 scripts/crawler_protocol.py 中的 PROTOCOL_VERSION 一起递增
+pub const PROTOCOL_VERSION: i64 = 1;
+
+// 异常退出时附在错误信息中的 stderr 末尾行数
+const STDERR_TAIL_LINES: usize = 10;
+
+// 看门狗检查间隔
+const WATCHDOG_TICK: Duration = Duration::from_millis(200);
+
+// stdout 关闭后等待进程退出的最长时间（不超过空闲时限），超时强制结束
+const EXIT_GRACE: Duration = Duration::from_secs(10);
+const EXIT_POLL: Duration = Duration::from_millis(20);
+
+// 脚本输出的消息；progress 只作为心跳，result 的内容按调用方的结果类型再校验
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    Handshake {
+        protocol: i64,
+        script: String,
+        #[serde(default)]
+        version: Option<String>,
+    },
+    Progress {},
+    Result {
+        #[serde(default)]
+        id: Option<i64>,
+        result: serde_json::Value,
+    },
+    Log {
+        message: String,
+    },
+    Warning {
+        message: String,
+    },
+    Error {
+        message: String,
+        #[serde(default)]
+        fatal: bool,
+    },
+    Complete {},
+}
+
+// 子进程运行时限
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Limits {
+    pub idle: Duration,          // 连续无输出（stdout 和 stderr）的最长时间，暂停期间不计
+    pub total: Option<Duration>, // 总运行时间上限
+}
+
+// 看门狗与读取线程共享的状态
+struct Watch {
+    last_activity: Mutex<Instant>,
+    finished: AtomicBool,
+    expired: Mutex<Option<String>>,
+}
+
+impl Watch {
+    fn touch(&self) {
+        *self.last_activity.lock() = Instant::now();
+    }
+}
+
+fn log(control: Option<&RunControl>, level: &str, source: &str, message: &str) {
+    match control {
+        Some(control) => control.log(level, source, message),
+        None => eprintln!("[{}] {}: {}", source, level, message),
+    }
+}
+
+// 等待进程退出，返回 (退出状态, 是否因超时被强制结束)
+// 每次轮询只短暂持有槽位锁，取消超时和看门狗仍能结束进程；进程回收后才从槽位取出
+fn wait_for_exit(slot: &Mutex<Option<Child>>, grace: Duration) -> (Option<ExitStatus>, bool) {
+    let deadline = Instant::now() + grace;
+    let mut hung = false;
+    loop {
+        {
+            let mut slot = slot.lock();
+            let Some(child) = slot.as_mut() else { return (None, hung) };
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    slot.take();
+                    return (Some(status), hung);
+                }
+                Ok(None) if !hung && Instant::now() > deadline => {
+                    let _ = child.kill();
+                    hung = true;
+                }
+                Ok(None) => {}
+                Err(_) => {
+                    slot.take();
+                    return (None, hung);
+                }
+            }
+        }
+        std::thread::sleep(EXIT_POLL);
+    }
+}
+
+fn check_handshake(expected: &str, protocol: i64, script: &str, version: Option<&str>) -> Result<(), String> {
+    let version = version.unwrap_or("未知版本");
+    if script != expected {
+        return Err(format!("脚本不匹配：应为 {}，实际启动了 {}（{}）", expected, script, version));
+    }
+    if protocol != PROTOCOL_VERSION {
+        return Err(format!(
+            "脚本版本不匹配：{} {} 使用协议 v{}，应用需要 v{}，请更新脚本或应用",
+            script, version, protocol, PROTOCOL_VERSION
+        ));
+    }
+    Ok(())
+}
+
+/// 运行脚本并按协议读取输出，每条 result 消息回调一次 on_result(id, 结果)
+/// input 写入 stdin 第一行：传了 control 时 stdin 保持打开用于暂停/取消，否则写完即关闭
+/// 返回 Err 表示脚本没有正常完成（启动失败、致命错误、协议不符、超时、异常退出），已回调的结果仍然有效
+pub(crate) fn run<T: DeserializeOwned>(
+    mut command: Command,
+    script_file: &str,
+    limits: Limits,
+    control: Option<&RunControl>,
+    input: Option<&str>,
+    mut on_result: impl FnMut(Option<i64>, T),
+) -> Result<(), String> {
+    let script = script_file.trim_end_matches(".py");
+    let mut child = command
+        .stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("启动 Python 脚本失败: {}", e))?;
+
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        let _ = child.kill();
+        return Err("无法获取脚本输出".to_string());
+    };
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        if let Err(e) = writeln!(stdin, "{}", input).and_then(|_| stdin.flush()) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("写入 stdin 失败: {}", e));
+        }
+        if let Some(control) = control {
+            control.attach(stdin);
+        }
+    }
+
+    let slot: Arc<Mutex<Option<Child>>> = control.map(|c| c.child_slot()).unwrap_or_default();
+    *slot.lock() = Some(child);
+
+    let watch = Arc::new(Watch {
+        last_activity: Mutex::new(Instant::now()),
+        finished: AtomicBool::new(false),
+        expired: Mutex::new(None),
+    });
+
+    // stderr：逐行记入任务日志，保留末尾几行用于异常退出时的错误信息
+    let tail: Arc<Mutex<VecDeque<String>>> = Arc::new(Mutex::new(VecDeque::new()));
+    let stderr_reader = {
+        let (watch, tail, control, source) = (watch.clone(), tail.clone(), control.cloned(), script.to_string());
+        std::thread::spawn(move || {
+            for line in BufReader::new(stderr).lines() {
+                let Ok(line) = line else { break };
+                watch.touch();
+                if line.trim().is_empty() {
+                    continue;
+                }
+                log(control.as_ref(), "stderr", &source, &line);
+                let mut tail = tail.lock();
+                if tail.len() == STDERR_TAIL_LINES {
+                    tail.pop_front();
+                }
+                tail.push_back(line);
+            }
+        })
+    };
+
+    // 看门狗
+    {
+        let (watch, slot, control, source) = (watch.clone(), slot.clone(), control.cloned(), script.to_string());
+        std::thread::spawn(move || {
+            let started = Instant::now();
+            while !watch.finished.load(Ordering::SeqCst) {
+                std::thread::sleep(WATCHDOG_TICK);
+                // 暂停期间脚本不输出，不计入空闲时间
+                if control.as_ref().is_some_and(|c| c.state() == RunState::Paused) {
+                    watch.touch();
+                    continue;
+                }
+                let reason = if watch.last_activity.lock().elapsed() > limits.idle {
+                    format!("{} 超过 {} 秒没有输出，已强制结束", source, limits.idle.as_secs())
+                } else if limits.total.is_some_and(|total| started.elapsed() > total) {
+                    format!("{} 运行超过 {} 秒，已强制结束", source, limits.total.unwrap_or_default().as_secs())
+                } else {
+                    continue;
+                };
+                *watch.expired.lock() = Some(reason);
+                if let Some(child) = slot.lock().as_mut() {
+                    let _ = child.kill();
+                }
+                break;
+            }
+        });
+    }
+
+    let mut handshake = false;
+    let mut completed = false;
+    let mut fatal: Option<String> = None;       // 脚本报告的致命错误
+    let mut violation: Option<String> = None;   // 协议不符，需要结束进程
+    for (line_no, line) in BufReader::new(stdout).lines().enumerate() {
+        // 进程被强制结束时读取可能出错，已读取的结果照常返回
+        let Ok(line) = line else { break };
+        watch.touch();
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let message = match serde_json::from_str::<Message>(line) {
+            Ok(message) => message,
+            Err(e) => {
+                let preview: String = line.chars().take(200).collect();
+                log(control, "warning", script, &format!("无法识别的输出（{}）: {}", e, preview));
+                continue;
+            }
+        };
+        // 缺少依赖时脚本可能在握手前就报错退出
+        if !handshake && !matches!(message, Message::Handshake { .. } | Message::Log { .. } | Message::Error { .. }) {
+            violation = Some(format!(
+                "{} 没有发送握手消息，可能是旧版本脚本，请更新脚本或应用（应用需要协议 v{}）",
+                script, PROTOCOL_VERSION
+            ));
+            break;
+        }
+
+        match message {
+            Message::Handshake { protocol, script: name, version } => {
+                if let Err(e) = check_handshake(script, protocol, &name, version.as_deref()) {
+                    violation = Some(e);
+                    break;
+                }
+                handshake = true;
+            }
+            Message::Progress {} => {}
+            Message::Result { id, result } => match serde_json::from_value::<T>(result) {
+                Ok(result) => on_result(id, result),
+                Err(e) => log(control, "error", script, &format!("第 {} 行结果格式与协议不符: {}", line_no + 1, e)),
+            },
+            Message::Log { message } => log(control, "info", script, &message),
+            Message::Warning { message } => log(control, "warning", script, &message),
+            Message::Error { message, fatal: is_fatal } => {
+                log(control, "error", script, &message);
+                if is_fatal {
+                    fatal = Some(message);
+                }
+            }
+            Message::Complete {} => {
+                completed = true;
+                break;
+            }
+        }
+    }
+
+    if let Some(control) = control {
+        control.detach();
+    }
+    if violation.is_some() {
+        if let Some(child) = slot.lock().as_mut() {
+            let _ = child.kill();
+        }
+    }
+    // 看门狗在进程退出前一直工作（如关闭浏览器时卡住）
+    let (status, hung) = wait_for_exit(&slot, limits.idle.min(EXIT_GRACE));
+    watch.finished.store(true, Ordering::SeqCst);
+
+    // 浏览器等孙进程可能继承 stderr，不无限等待读取线程
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while !stderr_reader.is_finished() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    if let Some(message) = fatal {
+        return Err(message);
+    }
+    let cancelled = control.is_some_and(|c| c.is_cancelled());
+    // 已发送完成消息的结果都已收到，之后卡住被结束只记警告
+    if completed && violation.is_none() && (hung || watch.expired.lock().is_some()) {
+        log(control, "warning", script, &format!("{} 发送完成消息后没有退出，已强制结束", script));
+        return Ok(());
+    }
+    let hung = hung.then(|| format!("{} 输出结束后没有退出，已强制结束", script));
+    let failure = violation.or_else(|| watch.expired.lock().take()).or(hung).or_else(|| match status {
+        Some(status) if !status.success() && !cancelled => Some(format!("{} 异常退出（{}）", script, status)),
+        _ if !completed && !cancelled => Some(format!("{} 未发送完成消息就结束了", script)),
+        _ => None,
+    });
+    let Some(failure) = failure else {
+        return Ok(());
+    };
+
+    let tail = tail.lock();
+    let failure = if tail.is_empty() {
+        failure
+    } else {
+        format!("{}\nstderr:\n{}", failure, tail.iter().cloned().collect::<Vec<_>>().join("\n"))
+    };
+    log(control, "error", script, &failure);
+    Err(failure)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    type Collected = Vec<(Option<i64>, i64)>;
+
+    fn run_shell(body: &str, limits: Limits) -> (Collected, Result<(), String>) {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(body);
+        let mut results = Vec::new();
+        let outcome = run(command, "fake.py", limits, None, None, |id, value: i64| results.push((id, value)));
+        (results, outcome)
+    }
+
+    const LIMITS: Limits = Limits { idle: Duration::from_secs(10), total: None };
+    const HANDSHAKE: &str = r#"echo '{"type":"handshake","protocol":1,"script":"fake","version":"1.0"}'"#;
+
+    #[test]
+    fn test_protocol_session() {
+        let body = format!(
+            r#"{}
+echo 'debug line'
+echo '{{"type":"log","message":"started"}}'
+echo '{{"type":"progress","completed":0,"total":2}}'
+echo '{{"type":"result","id":7,"result":70}}'
+echo '{{"type":"result","id":8,"result":"not a number"}}'
+echo 'stderr output' >&2
+echo '{{"type":"warning","message":"slow"}}'
+echo '{{"type":"result","result":90}}'
+echo '{{"type":"complete","total":2}}'"#,
+            HANDSHAKE
+        );
+        let (results, outcome) = run_shell(&body, LIMITS);
+        assert_eq!(outcome, Ok(()));
+        assert_eq!(results, vec![(Some(7), 70), (None, 90)]);
+    }
+
+    #[test]
+    fn test_protocol_failures() {
+        // 版本不符
+        let (_, outcome) = run_shell(r#"echo '{"type":"handshake","protocol":99,"script":"fake","version":"0.1"}'; sleep 5"#, LIMITS);
+        assert!(outcome.unwrap_err().contains("协议 v99"));
+
+        // 旧脚本：没有握手直接输出结果
+        let (results, outcome) = run_shell(r#"echo '{"type":"result","id":1,"result":1}'"#, LIMITS);
+        assert!(results.is_empty() && outcome.unwrap_err().contains("握手"));
+
+        // 握手前的致命错误（缺少依赖）
+        let (_, outcome) = run_shell(r#"echo '{"type":"error","message":"缺少 Playwright","fatal":true}'; exit 1"#, LIMITS);
+        assert_eq!(outcome, Err("缺少 Playwright".to_string()));
+
+        // 崩溃：错误信息带上 stderr
+        let body = format!("{}\necho '{{\"type\":\"result\",\"id\":1,\"result\":1}}'\necho 'Traceback: boom' >&2\nexit 3", HANDSHAKE);
+        let (results, outcome) = run_shell(&body, LIMITS);
+        let error = outcome.unwrap_err();
+        assert_eq!(results, vec![(Some(1), 1)]);
+        assert!(error.contains("异常退出") && error.contains("Traceback: boom"), "{}", error);
+
+        // 看门狗：卡住的脚本被结束
+        let started = Instant::now();
+        let idle = Limits { idle: Duration::from_secs(1), total: None };
+        let (_, outcome) = run_shell(&format!("{}\nexec sleep 30", HANDSHAKE), idle);
+        assert!(outcome.unwrap_err().contains("没有输出"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_process_hanging_after_complete_is_reaped() {
+        let idle = Limits { idle: Duration::from_secs(1), total: None };
+
+        // 发送完成消息后卡住（如关闭浏览器卡住）：结果有效，进程被结束
+        let started = Instant::now();
+        let body = format!(
+            "{}\necho '{{\"type\":\"result\",\"id\":1,\"result\":1}}'\necho '{{\"type\":\"complete\"}}'\nexec sleep 30",
+            HANDSHAKE
+        );
+        let (results, outcome) = run_shell(&body, idle);
+        assert_eq!((results, outcome), (vec![(Some(1), 1)], Ok(())));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        // 关闭 stdout 后卡住、没有完成消息：报告失败
+        let started = Instant::now();
+        let (_, outcome) = run_shell(&format!("{}\nexec sleep 30 >&-", HANDSHAKE), idle);
+        assert!(outcome.unwrap_err().contains("已强制结束"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+}
@@ -0,0 +1,124 @@
+// CSV 文本读写：命令行导入导出、关键词监控列表导入导出共用
+// 字段含逗号、双引号、换行时用双引号包裹（引号写成两个）；读取时去掉开头的 BOM，兼容 \r\n 换行
+
+// 写出的文件开头加 BOM，Excel 直接打开不乱码
+pub const BOM: &str = "\u{feff}";
+
+pub fn csv_escape(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') || value.contains('\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// 一行 CSV（不含换行符）
+pub fn csv_line<S: AsRef<str>>(fields: &[S]) -> String {
+    fields.iter().map(|f| csv_escape(f.as_ref())).collect::<Vec<_>>().join(",")
+}
+
+/// 解析 CSV 文本：(表头, 数据行)
+pub fn parse_csv(content: &str) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    let content = content.trim_start_matches(BOM);
+
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut row_started = false;  // 当前行已有内容（最后一行只有 "" 时也要保留）
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => {
+                in_quotes = !in_quotes;
+                row_started = true;
+            }
+            ',' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                row_started = true;
+            }
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+                row_started = false;
+            }
+            _ => {
+                field.push(c);
+                row_started = true;
+            }
+        }
+    }
+    if in_quotes {
+        return Err(format!("CSV 第 {} 行的引号没有闭合", rows.len() + 1));
+    }
+    if row_started {
+        row.push(field);
+        rows.push(row);
+    }
+
+    if rows.is_empty() {
+        return Err("CSV 中没有数据".to_string());
+    }
+    let headers = rows.remove(0);
+    Ok((headers, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_round_trip() {
+        let rows: Vec<Vec<String>> = vec![
+            vec!["关键词", "备注", "数量"],
+            vec!["desk lamp, led", "说 \"好用\"", "1,234"],
+            vec!["第一行\n第二行", "windows\r\n换行", ""],
+            vec!["  前后空格  ", "\"", "\"\""],
+            vec!["", "", "末尾"],
+        ]
+        .into_iter()
+        .map(|r| r.into_iter().map(str::to_string).collect())
+        .collect();
+
+        let mut content = String::from(BOM);
+        for row in &rows {
+            content.push_str(&csv_line(row));
+            content.push('\n');
+        }
+
+        let (headers, data) = parse_csv(&content).unwrap();
+        assert_eq!(headers, rows[0]);
+        assert_eq!(data, rows[1..]);
+    }
+
+    #[test]
+    fn test_parse_csv_formats() {
+        // BOM + \r\n + 最后一行没有换行、只有一个空的引号字段
+        let (headers, rows) = parse_csv("\u{feff}a,b\r\n1,\"x\r\ny\"\r\n\"\"").unwrap();
+        assert_eq!(headers, vec!["a", "b"]);
+        assert_eq!(rows, vec![vec!["1".to_string(), "x\r\ny".to_string()], vec![String::new()]]);
+
+        // 只有表头
+        let (headers, rows) = parse_csv("a,b\n").unwrap();
+        assert_eq!(headers, vec!["a", "b"]);
+        assert!(rows.is_empty());
+
+        assert_eq!(parse_csv("\u{feff}").unwrap_err(), "CSV 中没有数据");
+        assert_eq!(parse_csv("a,b\n1,\"abc\n2,3\n").unwrap_err(), "CSV 第 2 行的引号没有闭合");
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("a\rb"), "\"a\rb\"");
+        assert_eq!(csv_line(&["a", "b,c", ""]), "a,\"b,c\",");
+    }
+}
@@ -3,10 +3,20 @@ use parking_lot::Mutex;
 use rusqlite::{Connection, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 static DB: OnceCell<Mutex<Connection>> = OnceCell::new();
 
+// 数据目录（数据库所在目录），爬取页面归档等文件也放在这里
+static DATA_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+// 测试中每个线程使用自己的数据库（见 init_test_db），并行的测试互不影响，也不初始化全局数据库
+#[cfg(test)]
+thread_local! {
+    static TEST_DB: std::cell::Cell<Option<(&'static Mutex<Connection>, &'static PathBuf)>> =
+        const { std::cell::Cell::new(None) };
+}
+
 // 多语言停用词表（英语、德语、法语、意大利语、西班牙语）
 fn get_stopwords() -> HashSet<&'static str> {
     [
@@ -140,6 +150,8 @@ pub struct RootWithCategories {
     pub percentage: f64,
     pub categories: Vec<i64>,
     pub is_negative: bool,  // 是否为否词词根
+    pub brand_name: Option<String>,  // 命中的品牌词典条目
+    pub brand_type: Option<String>,  // own, competitor
 }
 
 // 关键词完整数据结构
@@ -175,14 +187,56 @@ pub struct KeywordData {
     pub traffic_share: Option<f64>,             // 流量占比
     // ASIN动态列（JSON格式存储）
     pub asin_data: Option<String>,
+    // 搜索意图来源 (manual/ai/rule) 和规则识别置信度
+    #[serde(default)]
+    pub search_intent_source: Option<String>,
+    #[serde(default)]
+    pub search_intent_confidence: Option<f64>,
+    // 品牌词标记（按品牌词典自动识别）
+    #[serde(default)]
+    pub brand_name: Option<String>,
+    #[serde(default)]
+    pub brand_type: Option<String>,             // own: 自有品牌, competitor: 竞品品牌
 }
 
 pub fn init_db(app_data_dir: PathBuf) -> Result<()> {
-    std::fs::create_dir_all(&app_data_dir).ok();
+    let conn = open_database(&app_data_dir)?;
+    DB.set(Mutex::new(conn))
+        .map_err(|_| rusqlite::Error::InvalidQuery)?;
+    // 数据库可用之后再记录数据目录，data_dir() 有值时 get_db() 一定可用
+    DATA_DIR.set(app_data_dir).ok();
+
+    Ok(())
+}
+
+/// 在临时目录建一个新数据库，当前线程的 get_db() / data_dir() 改用它（测试用）
+#[cfg(test)]
+pub fn init_test_db(name: &str) -> PathBuf {
+    static SEQ: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+    let seq = SEQ.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+    let dir = std::env::temp_dir().join(format!("thesaurus-test-{}-{}-{}", name, std::process::id(), seq));
+    std::fs::remove_dir_all(&dir).ok();
+    let conn = open_database(&dir).expect("Failed to initialize test database");
+    let db: &'static Mutex<Connection> = Box::leak(Box::new(Mutex::new(conn)));
+    let data_dir: &'static PathBuf = Box::leak(Box::new(dir.clone()));
+    TEST_DB.with(|test_db| test_db.set(Some((db, data_dir))));
+    dir
+}
+
+// 打开数据库并完成建表和迁移
+fn open_database(app_data_dir: &Path) -> Result<Connection> {
+    std::fs::create_dir_all(app_data_dir).ok();
     let db_path = app_data_dir.join("thesaurus.db");
     let conn = Connection::open(db_path)?;
 
-    // 显式禁用外键约束（解决 Windows 上的兼容性问题）
+    // GUI 和命令行工具可能同时访问数据库，遇到锁时等待而不是直接报错
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+
+    // 建表和迁移阶段关闭外键约束：
+    // 1. 旧版迁移会重命名/重建 keywords、roots、keyword_data 等父表，SQLite 要求此时外键处于关闭状态
+    // 2. root_categories / keyword_roots 早于 roots / keywords 创建，开启约束时会因父表不存在而报错
+    // 以前为了绕开这些报错在各处直接关闭外键，导致 CASCADE 全部失效、孤儿数据不断累积；
+    // 现在只在迁移阶段关闭，迁移结束后清理孤儿数据并开启外键约束
     conn.execute("PRAGMA foreign_keys = OFF", [])?;
 
     // 先创建产品表（不依赖其他表）
@@ -365,10 +419,21 @@ pub fn init_db(app_data_dir: PathBuf) -> Result<()> {
 
     // 迁移关键词监控表：添加 tags 列
     migrate_keyword_monitoring_tags(&conn)?;
+    migrate_keyword_monitoring_max_pages(&conn)?;
+    migrate_keyword_monitoring_failures(&conn)?;
+    migrate_keyword_monitoring_location(&conn)?;
+    migrate_ranking_organic_position(&conn)?;
+    migrate_ranking_snapshot_location(&conn)?;
 
     // 迁移词根表：添加 is_negative 列
     migrate_roots_add_is_negative(&conn)?;
 
+    // 迁移 keyword_data 表：添加搜索意图来源和置信度列
+    migrate_keyword_data_intent_source(&conn)?;
+
+    // 迁移 keyword_data / roots 表：添加品牌标记列
+    migrate_brand_flags(&conn)?;
+
     // 初始化知识库表
     init_knowledge_base_tables(&conn)?;
 
@@ -393,10 +458,44 @@ pub fn init_db(app_data_dir: PathBuf) -> Result<()> {
     // 初始化工作周报表
     init_weekly_report_tables(&conn)?;
 
-    DB.set(Mutex::new(conn))
-        .map_err(|_| rusqlite::Error::InvalidQuery)?;
+    // 初始化分析流程运行记录表
+    init_pipeline_tables(&conn)?;
 
-    Ok(())
+    // 初始化品牌词典表
+    init_brand_tables(&conn)?;
+
+    // 初始化排名检测计划表
+    init_ranking_schedule_tables(&conn)?;
+
+    // 初始化排名历史汇总表
+    init_ranking_rollup_tables(&conn)?;
+
+    // 初始化检测失败记录和重试队列表
+    init_check_failure_tables(&conn)?;
+
+    // 初始化任务明细表
+    init_task_item_tables(&conn)?;
+
+    // 初始化代理池表（并导入旧版 proxy_list 设置）
+    init_proxy_tables(&conn)?;
+
+    // 初始化站点限速状态表
+    init_rate_limit_tables(&conn)?;
+
+    // 初始化排名告警表和外部通知渠道表
+    init_alert_tables(&conn)?;
+    init_notification_channel_tables(&conn)?;
+    migrate_alert_rules_channel_id(&conn)?;
+
+    // 清理外键关闭期间遗留的孤儿数据，然后开启外键约束（CASCADE / SET NULL 从此自动生效）
+    match repair_orphans(&conn) {
+        Ok(fixed) if fixed > 0 => println!("[DB] Removed {} orphaned rows before enabling foreign keys", fixed),
+        Ok(_) => {}
+        Err(e) => eprintln!("[DB] Orphan cleanup failed: {}", e),
+    }
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
+
+    Ok(conn)
 }
 
 // 数据库迁移：为产品表添加表头字段
@@ -549,6 +648,133 @@ fn migrate_keyword_monitoring_tags(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+// 数据库迁移：为关键词监控表添加 max_pages 字段（单个关键词的检测页数）
+fn migrate_keyword_monitoring_max_pages(conn: &Connection) -> Result<()> {
+    let has_max_pages: bool = conn
+        .prepare("SELECT max_pages FROM keyword_monitoring LIMIT 1")
+        .is_ok();
+
+    if !has_max_pages {
+        conn.execute("ALTER TABLE keyword_monitoring ADD COLUMN max_pages INTEGER", [])?;
+    }
+
+    Ok(())
+}
+
+// 数据库迁移：为关键词监控表添加检测失败状态（连续失败次数、最近失败原因、自动暂停时间）
+fn migrate_keyword_monitoring_failures(conn: &Connection) -> Result<()> {
+    let has_failures: bool = conn
+        .prepare("SELECT consecutive_failures FROM keyword_monitoring LIMIT 1")
+        .is_ok();
+
+    if !has_failures {
+        conn.execute_batch(
+            "ALTER TABLE keyword_monitoring ADD COLUMN consecutive_failures INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE keyword_monitoring ADD COLUMN last_failure_kind TEXT;
+             ALTER TABLE keyword_monitoring ADD COLUMN last_failure_message TEXT;
+             ALTER TABLE keyword_monitoring ADD COLUMN last_failure_at TEXT;
+             ALTER TABLE keyword_monitoring ADD COLUMN auto_paused_at TEXT;"
+        )?;
+    }
+
+    Ok(())
+}
+
+// 数据库迁移：为关键词监控表添加配送邮编 location（空字符串表示使用站点设置），排名历史记录检测使用的邮编
+// 同一关键词可以按不同邮编分别监控，唯一约束需要加上 location，SQLite 只能重建表（迁移阶段外键已关闭，引用不受影响）
+fn migrate_keyword_monitoring_location(conn: &Connection) -> Result<()> {
+    let has_history_location: bool = conn
+        .prepare("SELECT location FROM keyword_ranking_history LIMIT 1")
+        .is_ok();
+    if !has_history_location {
+        conn.execute("ALTER TABLE keyword_ranking_history ADD COLUMN location TEXT", [])?;
+    }
+
+    let has_location: bool = conn
+        .prepare("SELECT location FROM keyword_monitoring LIMIT 1")
+        .is_ok();
+    if has_location {
+        return Ok(());
+    }
+
+    // 加列和重建在同一事务中，失败时下次启动重新迁移
+    conn.execute("BEGIN TRANSACTION", [])?;
+    let result = (|| -> Result<()> {
+        conn.execute("ALTER TABLE keyword_monitoring ADD COLUMN location TEXT NOT NULL DEFAULT ''", [])?;
+        let create_sql: String = conn.query_row(
+            "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'keyword_monitoring'",
+            [],
+            |row| row.get(0),
+        )?;
+        let old_unique = "UNIQUE(keyword, asin, country, product_id)";
+        if !create_sql.contains(old_unique) {
+            return Ok(());
+        }
+        let new_sql = create_sql
+            .replacen(old_unique, "UNIQUE(keyword, asin, country, product_id, location)", 1)
+            .replacen("keyword_monitoring", "keyword_monitoring_new", 1);
+        conn.execute_batch(&format!(
+            "{};
+             INSERT INTO keyword_monitoring_new SELECT * FROM keyword_monitoring;
+             DROP TABLE keyword_monitoring;
+             ALTER TABLE keyword_monitoring_new RENAME TO keyword_monitoring;
+             CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_product ON keyword_monitoring(product_id);
+             CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_active ON keyword_monitoring(is_active);
+             CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_country ON keyword_monitoring(country);",
+            new_sql
+        ))
+    })();
+
+    match result {
+        Ok(_) => {
+            conn.execute("COMMIT", [])?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
+}
+
+// 数据库迁移：排名历史记录和监控项最新排名加上自然结果的绝对位置（由爬虫按各页实际自然结果数累计）
+// 旧记录没有位置信息，只有第 1 页的页内排名就是绝对位置，其余页保持为空
+fn migrate_ranking_organic_position(conn: &Connection) -> Result<()> {
+    let has_position: bool = conn
+        .prepare("SELECT organic_position FROM keyword_ranking_history LIMIT 1")
+        .is_ok();
+    if !has_position {
+        conn.execute_batch(
+            "ALTER TABLE keyword_ranking_history ADD COLUMN organic_position INTEGER;
+             UPDATE keyword_ranking_history SET organic_position = organic_rank
+             WHERE organic_rank IS NOT NULL AND COALESCE(organic_page, 1) = 1;"
+        )?;
+    }
+
+    let has_latest_position: bool = conn
+        .prepare("SELECT latest_organic_position FROM keyword_monitoring LIMIT 1")
+        .is_ok();
+    if !has_latest_position {
+        conn.execute_batch(
+            "ALTER TABLE keyword_monitoring ADD COLUMN latest_organic_position INTEGER;
+             UPDATE keyword_monitoring SET latest_organic_position = latest_organic_rank
+             WHERE latest_organic_rank IS NOT NULL AND COALESCE(latest_organic_page, 1) = 1;"
+        )?;
+    }
+    Ok(())
+}
+
+// 数据库迁移：竞品快照记录检测使用的配送邮编（旧快照为空）
+fn migrate_ranking_snapshot_location(conn: &Connection) -> Result<()> {
+    let has_location: bool = conn
+        .prepare("SELECT location FROM ranking_snapshots LIMIT 1")
+        .is_ok();
+    if !has_location {
+        conn.execute("ALTER TABLE ranking_snapshots ADD COLUMN location TEXT", [])?;
+    }
+    Ok(())
+}
+
 // 数据库迁移：为词根表添加 is_negative 字段
 fn migrate_roots_add_is_negative(conn: &Connection) -> Result<()> {
     // 检查 roots 表是否存在 is_negative 列
@@ -564,6 +790,47 @@ fn migrate_roots_add_is_negative(conn: &Connection) -> Result<()> {
     Ok(())
 }
 
+// 数据库迁移：为 keyword_data 和 roots 表添加品牌标记字段
+fn migrate_brand_flags(conn: &Connection) -> Result<()> {
+    let has_keyword_brand: bool = conn
+        .prepare("SELECT brand_type FROM keyword_data LIMIT 1")
+        .is_ok();
+    if !has_keyword_brand {
+        conn.execute("ALTER TABLE keyword_data ADD COLUMN brand_name TEXT", [])?;
+        conn.execute("ALTER TABLE keyword_data ADD COLUMN brand_type TEXT", [])?;
+    }
+
+    let has_root_brand: bool = conn
+        .prepare("SELECT brand_type FROM roots LIMIT 1")
+        .is_ok();
+    if !has_root_brand {
+        conn.execute("ALTER TABLE roots ADD COLUMN brand_name TEXT", [])?;
+        conn.execute("ALTER TABLE roots ADD COLUMN brand_type TEXT", [])?;
+    }
+
+    Ok(())
+}
+
+// 数据库迁移：为 keyword_data 表添加搜索意图来源 / 置信度字段
+fn migrate_keyword_data_intent_source(conn: &Connection) -> Result<()> {
+    let has_source: bool = conn
+        .prepare("SELECT search_intent_source FROM keyword_data LIMIT 1")
+        .is_ok();
+
+    if !has_source {
+        conn.execute("ALTER TABLE keyword_data ADD COLUMN search_intent_source TEXT", [])?;
+        conn.execute("ALTER TABLE keyword_data ADD COLUMN search_intent_confidence REAL", [])?;
+        // 已有的搜索意图无法区分来源，按人工填写处理，避免被规则识别覆盖
+        conn.execute(
+            "UPDATE keyword_data SET search_intent_source = 'manual'
+             WHERE search_intent IS NOT NULL AND search_intent != ''",
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
 // 数据库迁移：检查并重建 keyword_data 表（列名变更）
 fn migrate_keyword_data_table(conn: &Connection) -> Result<()> {
     // 检查 keyword_data 表是否存在
@@ -753,9 +1020,31 @@ fn init_categories(conn: &Connection) -> Result<()> {
 }
 
 pub fn get_db() -> &'static Mutex<Connection> {
+    #[cfg(test)]
+    if let Some((db, _)) = TEST_DB.with(|test_db| test_db.get()) {
+        return db;
+    }
     DB.get().expect("Database not initialized")
 }
 
+// 数据库是否已初始化（init_db 完成迁移之前为 false）
+pub fn is_initialized() -> bool {
+    #[cfg(test)]
+    if TEST_DB.with(|test_db| test_db.get()).is_some() {
+        return true;
+    }
+    DB.get().is_some()
+}
+
+// 数据目录，init_db 完成之前为 None
+pub fn data_dir() -> Option<&'static PathBuf> {
+    #[cfg(test)]
+    if let Some((_, dir)) = TEST_DB.with(|test_db| test_db.get()) {
+        return Some(dir);
+    }
+    DATA_DIR.get()
+}
+
 // 获取所有分类
 pub fn get_categories() -> Result<Vec<Category>> {
     let conn = get_db().lock();
@@ -825,22 +1114,29 @@ pub fn update_product_headers(id: i64, cpc_header: Option<String>, bid_range_hea
     Ok(())
 }
 
-// 删除产品（同时删除关联的关键词和词根）
+// 删除产品（关键词、词根、完整数据、备份、监控等通过 CASCADE 自动删除）
 pub fn delete_product(id: i64) -> Result<()> {
     let conn = get_db().lock();
+    conn.execute("BEGIN TRANSACTION", [])?;
 
-    // 显式禁用外键约束（解决 Windows 兼容性问题）
-    // 注意：禁用外键后级联删除不会自动工作，需要手动删除关联数据
-    conn.execute("PRAGMA foreign_keys = OFF", [])?;
-
-    // 手动删除关联数据（因为外键被禁用，CASCADE 不会工作）
-    conn.execute("DELETE FROM keyword_roots WHERE root_id IN (SELECT id FROM roots WHERE product_id = ?1)", [id])?;
-    conn.execute("DELETE FROM root_categories WHERE root_id IN (SELECT id FROM roots WHERE product_id = ?1)", [id])?;
-    conn.execute("DELETE FROM roots WHERE product_id = ?1", [id])?;
-    conn.execute("DELETE FROM keywords WHERE product_id = ?1", [id])?;
-    conn.execute("DELETE FROM products WHERE id = ?1", [id])?;
+    let result = (|| {
+        // 智能文案/智能广告项目只是可选关联产品，保留项目并解除关联
+        conn.execute("UPDATE sc_projects SET product_id = NULL WHERE product_id = ?1", [id])?;
+        conn.execute("UPDATE ad_projects SET product_id = NULL WHERE product_id = ?1", [id])?;
+        conn.execute("DELETE FROM products WHERE id = ?1", [id])?;
+        Ok::<(), rusqlite::Error>(())
+    })();
 
-    Ok(())
+    match result {
+        Ok(_) => {
+            conn.execute("COMMIT", [])?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
 }
 
 // ==================== 关键词和词根 ====================
@@ -849,9 +1145,6 @@ pub fn delete_product(id: i64) -> Result<()> {
 pub fn import_keywords(product_id: i64, keywords: Vec<String>) -> Result<()> {
     let conn = get_db().lock();
 
-    // 显式禁用外键约束（解决 Windows 兼容性问题）
-    conn.execute("PRAGMA foreign_keys = OFF", [])?;
-
     // 使用事务大幅提升导入速度（特别是在 Windows 上）
     conn.execute("BEGIN TRANSACTION", [])?;
 
@@ -908,6 +1201,8 @@ pub fn import_keywords(product_id: i64, keywords: Vec<String>) -> Result<()> {
                 )?;
             }
         }
+        // 新词根按品牌词典标记
+        apply_brand_flags(&conn, product_id)?;
         Ok::<(), rusqlite::Error>(())
     })();
 
@@ -928,6 +1223,7 @@ pub fn get_roots(
     product_id: Option<i64>,
     search: Option<String>,
     category_ids: Option<Vec<i64>>,
+    brand_types: Option<Vec<String>>,
     sort_by: Option<String>,
     sort_order: Option<String>,
     page: i64,
@@ -951,7 +1247,8 @@ pub fn get_roots(
         "
         SELECT DISTINCT r.id, r.word, r.translation,
                (SELECT COUNT(*) FROM keyword_roots WHERE root_id = r.id) as contains_count,
-               COALESCE(r.is_negative, 0) as is_negative
+               COALESCE(r.is_negative, 0) as is_negative,
+               r.brand_name, r.brand_type
         FROM roots r
         ",
     );
@@ -983,6 +1280,16 @@ pub fn get_roots(
         }
     }
 
+    // 品牌筛选：own / competitor / none（非品牌词）
+    if let Some(ref types) = brand_types {
+        if let Some(condition) = brand_type_condition("r.brand_type", types, |_| "?".to_string()) {
+            conditions.push(condition);
+            for t in types.iter().filter(|t| t.as_str() != "none") {
+                params.push(Box::new(t.clone()));
+            }
+        }
+    }
+
     if !conditions.is_empty() {
         sql.push_str(" WHERE ");
         sql.push_str(&conditions.join(" AND "));
@@ -1026,6 +1333,8 @@ pub fn get_roots(
                 percentage,
                 categories: Vec::new(),
                 is_negative: is_negative_int != 0,
+                brand_name: row.get(5)?,
+                brand_type: row.get(6)?,
             })
         })?
         .collect::<Result<Vec<_>>>()?;
@@ -1222,19 +1531,7 @@ pub fn get_category_counts(product_id: i64) -> Result<Vec<(i64, i64)>> {
 pub fn clear_product_data(product_id: i64) -> Result<()> {
     let conn = get_db().lock();
 
-    // 显式禁用外键约束（解决 Windows 兼容性问题）
-    conn.execute("PRAGMA foreign_keys = OFF", [])?;
-
-    // 先删除关联表数据
-    conn.execute(
-        "DELETE FROM keyword_roots WHERE keyword_id IN (SELECT id FROM keywords WHERE product_id = ?1)",
-        [product_id],
-    )?;
-    conn.execute(
-        "DELETE FROM root_categories WHERE root_id IN (SELECT id FROM roots WHERE product_id = ?1)",
-        [product_id],
-    )?;
-    // 再删除关键词和词根
+    // 删除关键词和词根（keyword_roots、root_categories 通过 CASCADE 自动删除）
     conn.execute("DELETE FROM keywords WHERE product_id = ?1", [product_id])?;
     conn.execute("DELETE FROM roots WHERE product_id = ?1", [product_id])?;
     // 删除关键词完整数据
@@ -1261,9 +1558,6 @@ pub fn batch_update_root_analysis(
 ) -> Result<()> {
     let conn = get_db().lock();
 
-    // 显式禁用外键约束（解决 Windows 兼容性问题）
-    conn.execute("PRAGMA foreign_keys = OFF", [])?;
-
     // 使用事务提升批量更新性能
     conn.execute("BEGIN TRANSACTION", [])?;
 
@@ -1346,7 +1640,6 @@ pub fn batch_update_root_analysis(
 // 导入关键词完整数据
 pub fn import_keyword_data(product_id: i64, data_list: Vec<KeywordData>) -> Result<()> {
     let conn = get_db().lock();
-    conn.execute("PRAGMA foreign_keys = OFF", [])?;
     conn.execute("BEGIN TRANSACTION", [])?;
 
     let result = (|| {
@@ -1389,6 +1682,8 @@ pub fn import_keyword_data(product_id: i64, data_list: Vec<KeywordData>) -> Resu
                 ],
             )?;
         }
+        // 新导入的关键词按品牌词典标记
+        apply_brand_flags(&conn, product_id)?;
         Ok::<(), rusqlite::Error>(())
     })();
 
@@ -1412,6 +1707,7 @@ pub fn get_keyword_data(
     relevance_levels: Option<Vec<String>>,
     primary_categories: Option<Vec<String>>,
     orderliness_values: Option<Vec<String>>,
+    brand_types: Option<Vec<String>>,
     sort_by: Option<String>,
     sort_order: Option<String>,
     page: i64,
@@ -1424,7 +1720,8 @@ pub fn get_keyword_data(
                 traffic_total, avg_keyword_rank, avg_search_volume, cpc_bid, bid_range,
                 click_rate, conversion_competition, competition_level, natural_position_flow,
                 top3_click_share, avg_conversion_share, asin_count, traffic_level, negative_word, orderliness,
-                phrase_tag, primary_category, secondary_category, search_intent, traffic_share, asin_data
+                phrase_tag, primary_category, secondary_category, search_intent, traffic_share, asin_data,
+                search_intent_source, search_intent_confidence, brand_name, brand_type
          FROM keyword_data WHERE product_id = ?1",
     );
 
@@ -1504,6 +1801,20 @@ pub fn get_keyword_data(
             for val in values {
                 params.push(Box::new(val.clone()));
             }
+            param_index += values.len();
+        }
+    }
+
+    // 品牌筛选：own / competitor / none（非品牌词）
+    if let Some(ref types) = brand_types {
+        let values: Vec<&String> = types.iter().filter(|t| t.as_str() != "none").collect();
+        if let Some(condition) = brand_type_condition("brand_type", types, |i| format!("?{}", param_index + i)) {
+            let condition = format!(" AND {}", condition);
+            sql.push_str(&condition);
+            count_sql.push_str(&condition);
+            for t in values {
+                params.push(Box::new(t.clone()));
+            }
         }
     }
 
@@ -1564,6 +1875,10 @@ pub fn get_keyword_data(
                 search_intent: row.get(24)?,
                 traffic_share: row.get(25)?,
                 asin_data: row.get(26)?,
+                search_intent_source: row.get(27)?,
+                search_intent_confidence: row.get(28)?,
+                brand_name: row.get(29)?,
+                brand_type: row.get(30)?,
             })
         })?
         .collect::<Result<Vec<_>>>()?;
@@ -1595,6 +1910,16 @@ pub fn update_keyword_field(id: i64, field: &str, value: &str) -> Result<()> {
 
     let sql = format!("UPDATE keyword_data SET {} = ?1 WHERE id = ?2", field);
     conn.execute(&sql, rusqlite::params![value, id])?;
+
+    // 手动修改搜索意图后标记为人工来源，规则识别不再覆盖（清空则恢复为可自动识别）
+    if field == "search_intent" {
+        conn.execute(
+            "UPDATE keyword_data SET search_intent_source = CASE WHEN ?1 = '' THEN NULL ELSE 'manual' END,
+                search_intent_confidence = NULL
+             WHERE id = ?2",
+            rusqlite::params![value, id],
+        )?;
+    }
     Ok(())
 }
 
@@ -1631,7 +1956,10 @@ pub fn update_product_thresholds(id: i64, big_word_threshold: i64, medium_word_t
 // 根据阈值计算并更新流量级别
 pub fn calculate_traffic_levels(product_id: i64, big_threshold: i64, medium_threshold: i64) -> Result<()> {
     let conn = get_db().lock();
+    apply_traffic_levels(&conn, product_id, big_threshold, medium_threshold)
+}
 
+fn apply_traffic_levels(conn: &Connection, product_id: i64, big_threshold: i64, medium_threshold: i64) -> Result<()> {
     // 使用 CASE WHEN 批量更新流量级别（基于周平均排名）
     // 大词: avg_keyword_rank <= big_threshold
     // 中词: big_threshold < avg_keyword_rank <= medium_threshold
@@ -1715,7 +2043,10 @@ pub fn recommend_threshold(product_id: i64, target_big_count: i64) -> Result<i64
 // 计算并更新流量占比
 pub fn calculate_traffic_share(product_id: i64) -> Result<()> {
     let conn = get_db().lock();
+    apply_traffic_share(&conn, product_id)
+}
 
+fn apply_traffic_share(conn: &Connection, product_id: i64) -> Result<()> {
     // 1. 计算该产品所有关键词的流量总和
     let total_traffic: f64 = conn.query_row(
         "SELECT COALESCE(SUM(traffic_total), 0) FROM keyword_data WHERE product_id = ?1",
@@ -1746,6 +2077,85 @@ pub fn calculate_traffic_share(product_id: i64) -> Result<()> {
     Ok(())
 }
 
+// ==================== 搜索意图识别 ====================
+
+// 搜索意图识别输入
+#[derive(Debug, Clone)]
+pub struct IntentClassificationInput {
+    pub country: String,
+    pub keywords: Vec<(i64, String, Option<String>)>,                      // (id, keyword, 品牌词典标记的品牌)
+    pub root_categories: std::collections::HashMap<String, Vec<String>>,  // 词根 -> 分类 name_en
+}
+
+// 获取搜索意图识别输入，只返回可被自动识别的关键词（跳过人工和 AI 填写的意图）
+pub fn get_intent_classification_input(product_id: i64) -> Result<IntentClassificationInput> {
+    let conn = get_db().lock();
+
+    let country: Option<String> = conn
+        .query_row("SELECT country FROM products WHERE id = ?1", [product_id], |row| row.get(0))
+        .unwrap_or(None);
+
+    let mut stmt = conn.prepare(
+        "SELECT id, keyword, brand_name FROM keyword_data
+         WHERE product_id = ?1
+           AND (search_intent IS NULL OR search_intent = '' OR search_intent_source = 'rule')
+         ORDER BY id",
+    )?;
+    let keywords = stmt
+        .query_map([product_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<Vec<(i64, String, Option<String>)>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT LOWER(r.word), c.name_en FROM roots r
+         JOIN root_categories rc ON rc.root_id = r.id
+         JOIN categories c ON c.id = rc.category_id
+         WHERE r.product_id = ?1 AND c.name_en IS NOT NULL",
+    )?;
+    let mut root_categories: std::collections::HashMap<String, Vec<String>> = std::collections::HashMap::new();
+    for row in stmt.query_map([product_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))? {
+        let (word, category) = row?;
+        root_categories.entry(word).or_default().push(category);
+    }
+
+    Ok(IntentClassificationInput {
+        country: country.unwrap_or_else(|| "US".to_string()),
+        keywords,
+        root_categories,
+    })
+}
+
+// 批量写入规则识别结果：(keyword_data.id, 意图标签, 置信度)
+// WHERE 条件再次排除人工/AI 来源，防止识别期间被手动修改的值被覆盖
+pub fn apply_intent_classifications(updates: &[(i64, String, f64)]) -> Result<i64> {
+    let conn = get_db().lock();
+    conn.execute("BEGIN TRANSACTION", [])?;
+
+    let result = (|| {
+        let mut updated = 0i64;
+        for (id, label, confidence) in updates {
+            updated += conn.execute(
+                "UPDATE keyword_data
+                 SET search_intent = ?1, search_intent_source = 'rule', search_intent_confidence = ?2
+                 WHERE id = ?3
+                   AND (search_intent IS NULL OR search_intent = '' OR search_intent_source = 'rule')",
+                rusqlite::params![label, confidence, id],
+            )? as i64;
+        }
+        Ok::<i64, rusqlite::Error>(updated)
+    })();
+
+    match result {
+        Ok(updated) => {
+            conn.execute("COMMIT", [])?;
+            Ok(updated)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
+}
+
 // ==================== 关键词分类管理 ====================
 
 // 未分类关键词结构体
@@ -1787,7 +2197,8 @@ pub fn batch_update_keyword_categories(
     let result = (|| {
         for (keyword, primary_category, secondary_category, search_intent) in updates {
             conn.execute(
-                "UPDATE keyword_data SET primary_category = ?1, secondary_category = ?2, search_intent = ?3
+                "UPDATE keyword_data SET primary_category = ?1, secondary_category = ?2, search_intent = ?3,
+                    search_intent_source = 'ai', search_intent_confidence = NULL
                  WHERE keyword = ?4 AND product_id = ?5",
                 rusqlite::params![primary_category, secondary_category, search_intent, keyword, product_id],
             )?;
@@ -1879,6 +2290,22 @@ fn phrase_to_singular(phrase: &str, country: &str) -> String {
 pub fn calculate_phrase_tags(product_id: i64) -> Result<()> {
     let conn = get_db().lock();
 
+    // 使用事务批量更新
+    conn.execute("BEGIN TRANSACTION", [])?;
+
+    match apply_phrase_tags(&conn, product_id) {
+        Ok(_) => {
+            conn.execute("COMMIT", [])?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
+}
+
+fn apply_phrase_tags(conn: &Connection, product_id: i64) -> Result<()> {
     // 获取产品的国家代码
     let country: Option<String> = conn.query_row(
         "SELECT country FROM products WHERE id = ?1",
@@ -1901,43 +2328,32 @@ pub fn calculate_phrase_tags(product_id: i64) -> Result<()> {
         .query_map([product_id], |row| row.get(0))?
         .collect::<Result<Vec<_>>>()?;
 
-    // 2. 使用事务批量更新
-    conn.execute("BEGIN TRANSACTION", [])?;
-
-    let result = (|| {
-        for candidate in &candidates {
-            // 将候选词组转换为单数形式用于匹配
-            let singular_candidate = phrase_to_singular(candidate, country_code);
-            // 使用 LIKE 匹配包含该词组的关键词，且 phrase_tag 为空
-            let pattern = format!("%{}%", singular_candidate);
-            conn.execute(
-                "UPDATE keyword_data
-                 SET phrase_tag = ?1
-                 WHERE product_id = ?2
-                   AND keyword LIKE ?3
-                   AND (phrase_tag IS NULL OR phrase_tag = '')",
-                rusqlite::params![singular_candidate, product_id, pattern],
-            )?;
-        }
-        Ok::<(), rusqlite::Error>(())
-    })();
-
-    match result {
-        Ok(_) => {
-            conn.execute("COMMIT", [])?;
-            Ok(())
-        }
-        Err(e) => {
-            conn.execute("ROLLBACK", []).ok();
-            Err(e)
-        }
+    // 2. 批量更新（事务由调用方管理）
+    for candidate in &candidates {
+        // 将候选词组转换为单数形式用于匹配
+        let singular_candidate = phrase_to_singular(candidate, country_code);
+        // 使用 LIKE 匹配包含该词组的关键词，且 phrase_tag 为空
+        let pattern = format!("%{}%", singular_candidate);
+        conn.execute(
+            "UPDATE keyword_data
+             SET phrase_tag = ?1
+             WHERE product_id = ?2
+               AND keyword LIKE ?3
+               AND (phrase_tag IS NULL OR phrase_tag = '')",
+            rusqlite::params![singular_candidate, product_id, pattern],
+        )?;
     }
+
+    Ok(())
 }
 
 // 计算有序性：根据 phrase_tag 出现次数判断
 pub fn calculate_orderliness(product_id: i64) -> Result<()> {
     let conn = get_db().lock();
+    apply_orderliness(&conn, product_id)
+}
 
+fn apply_orderliness(conn: &Connection, product_id: i64) -> Result<()> {
     // 使用子查询统计每个 phrase_tag 的出现次数，然后更新 orderliness
     // - phrase_tag 为空 → orderliness = NULL
     // - phrase_tag 出现次数 < 4 → orderliness = '无序'
@@ -1963,7 +2379,7 @@ pub fn calculate_orderliness(product_id: i64) -> Result<()> {
 // ==================== 流程状态 ====================
 
 // 流程状态结构体
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkflowStatus {
     pub has_data: bool,           // 是否有关键词数据
     pub has_traffic_level: bool,  // 是否有流量级别
@@ -2020,15 +2436,191 @@ pub fn get_workflow_status(product_id: i64) -> Result<WorkflowStatus> {
     })
 }
 
-// ==================== 备份功能 ====================
+// ==================== 分析流程运行记录 ====================
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct BackupInfo {
-    pub id: i64,
-    pub product_id: i64,
-    pub backup_name: Option<String>,
-    pub created_at: String,
-    pub keyword_data_count: i64,
+fn init_pipeline_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        -- 分析流程运行记录（流量级别 → 流量占比 → 词组打标 → 有序性）
+        CREATE TABLE IF NOT EXISTS analysis_pipeline_runs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            steps TEXT NOT NULL,                 -- JSON 数组：本次要执行的步骤
+            completed_steps TEXT NOT NULL DEFAULT '[]',
+            current_step TEXT,
+            status TEXT NOT NULL DEFAULT 'running', -- running, completed, failed
+            error_message TEXT,
+            attempts INTEGER NOT NULL DEFAULT 1, -- 从失败步骤恢复时 +1
+            started_at TEXT NOT NULL DEFAULT (datetime('now', '+8 hours')),
+            ended_at TEXT,
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_pipeline_runs_product ON analysis_pipeline_runs(product_id);
+        ",
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineRun {
+    pub id: i64,
+    pub product_id: i64,
+    pub steps: Vec<String>,
+    pub completed_steps: Vec<String>,
+    pub current_step: Option<String>,
+    pub status: String,
+    pub error_message: Option<String>,
+    pub attempts: i64,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+}
+
+fn row_to_pipeline_run(row: &rusqlite::Row) -> Result<PipelineRun> {
+    let steps: String = row.get(2)?;
+    let completed_steps: String = row.get(3)?;
+    Ok(PipelineRun {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        steps: serde_json::from_str(&steps).unwrap_or_default(),
+        completed_steps: serde_json::from_str(&completed_steps).unwrap_or_default(),
+        current_step: row.get(4)?,
+        status: row.get(5)?,
+        error_message: row.get(6)?,
+        attempts: row.get(7)?,
+        started_at: row.get(8)?,
+        ended_at: row.get(9)?,
+    })
+}
+
+const PIPELINE_RUN_COLUMNS: &str = "id, product_id, steps, completed_steps, current_step, status, error_message, attempts, started_at, ended_at";
+
+// 创建流程运行记录
+pub fn create_pipeline_run(product_id: i64, steps: &[String]) -> Result<i64> {
+    let conn = get_db().lock();
+    conn.execute(
+        "INSERT INTO analysis_pipeline_runs (product_id, steps) VALUES (?1, ?2)",
+        rusqlite::params![product_id, serde_json::to_string(steps).unwrap_or_else(|_| "[]".to_string())],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// 获取单条流程运行记录
+pub fn get_pipeline_run(run_id: i64) -> Result<Option<PipelineRun>> {
+    let conn = get_db().lock();
+    let result = conn.query_row(
+        &format!("SELECT {} FROM analysis_pipeline_runs WHERE id = ?1", PIPELINE_RUN_COLUMNS),
+        [run_id],
+        row_to_pipeline_run,
+    );
+    match result {
+        Ok(run) => Ok(Some(run)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// 获取产品的流程运行记录（最新在前）
+pub fn get_pipeline_runs(product_id: i64, limit: i64) -> Result<Vec<PipelineRun>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM analysis_pipeline_runs WHERE product_id = ?1 ORDER BY id DESC LIMIT ?2",
+        PIPELINE_RUN_COLUMNS
+    ))?;
+    let runs = stmt
+        .query_map(rusqlite::params![product_id, limit], row_to_pipeline_run)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(runs)
+}
+
+// 获取产品最近一次失败的流程（用于恢复）
+pub fn get_last_failed_pipeline_run(product_id: i64) -> Result<Option<PipelineRun>> {
+    Ok(get_pipeline_runs(product_id, 1)?
+        .into_iter()
+        .find(|r| r.status == "failed"))
+}
+
+// 重新开始一次失败的流程：状态改回 running，尝试次数 +1
+pub fn restart_pipeline_run(run_id: i64) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE analysis_pipeline_runs
+         SET status = 'running', error_message = NULL, ended_at = NULL, attempts = attempts + 1
+         WHERE id = ?1",
+        [run_id],
+    )?;
+    Ok(())
+}
+
+// 更新流程进度
+pub fn update_pipeline_run_progress(run_id: i64, current_step: Option<&str>, completed_steps: &[String]) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE analysis_pipeline_runs SET current_step = ?1, completed_steps = ?2 WHERE id = ?3",
+        rusqlite::params![
+            current_step,
+            serde_json::to_string(completed_steps).unwrap_or_else(|_| "[]".to_string()),
+            run_id
+        ],
+    )?;
+    Ok(())
+}
+
+// 结束流程（completed 或 failed）
+pub fn finish_pipeline_run(run_id: i64, status: &str, error_message: Option<&str>) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE analysis_pipeline_runs
+         SET status = ?1, error_message = ?2, ended_at = datetime('now', '+8 hours')
+         WHERE id = ?3",
+        rusqlite::params![status, error_message, run_id],
+    )?;
+    Ok(())
+}
+
+// 执行单个分析步骤（每个步骤一个事务，失败时整体回滚）
+pub fn run_analysis_step(product_id: i64, step: &str) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute("BEGIN TRANSACTION", [])?;
+
+    let result = (|| {
+        match step {
+            "traffic_level" => {
+                // 阈值使用产品保存的设置，未设置时用默认值（与前端一致）
+                let (big, medium): (Option<i64>, Option<i64>) = conn.query_row(
+                    "SELECT big_word_threshold, medium_word_threshold FROM products WHERE id = ?1",
+                    [product_id],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )?;
+                apply_traffic_levels(&conn, product_id, big.unwrap_or(20000), medium.unwrap_or(100000))
+            }
+            "traffic_share" => apply_traffic_share(&conn, product_id),
+            "phrase_tag" => apply_phrase_tags(&conn, product_id),
+            "orderliness" => apply_orderliness(&conn, product_id),
+            _ => Err(rusqlite::Error::InvalidParameterName(step.to_string())),
+        }
+    })();
+
+    match result {
+        Ok(_) => {
+            conn.execute("COMMIT", [])?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
+}
+
+// ==================== 备份功能 ====================
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BackupInfo {
+    pub id: i64,
+    pub product_id: i64,
+    pub backup_name: Option<String>,
+    pub created_at: String,
+    pub keyword_data_count: i64,
 }
 
 // 创建备份
@@ -2201,1086 +2793,4053 @@ pub fn delete_backup(backup_id: i64) -> Result<()> {
     Ok(())
 }
 
-// ==================== 设置管理 ====================
-
-// 获取设置值
-pub fn get_setting(key: &str) -> Result<Option<String>> {
-    let conn = get_db().lock();
-    let result = conn.query_row(
-        "SELECT value FROM settings WHERE key = ?1",
-        [key],
-        |row| row.get(0),
-    );
-    match result {
-        Ok(value) => Ok(Some(value)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e),
-    }
-}
+// ==================== 品牌词典 ====================
 
-// 设置值
-pub fn set_setting(key: &str, value: &str) -> Result<()> {
-    let conn = get_db().lock();
-    conn.execute(
-        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-        rusqlite::params![key, value],
+// 初始化品牌词典表（按产品 + 站点维护）
+fn init_brand_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS brand_dictionary (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            marketplace TEXT NOT NULL,
+            brand TEXT NOT NULL,
+            brand_key TEXT NOT NULL,
+            brand_type TEXT NOT NULL DEFAULT 'competitor' CHECK(brand_type IN ('own', 'competitor')),
+            source TEXT NOT NULL DEFAULT 'manual',
+            title_count INTEGER DEFAULT 0,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+            UNIQUE(product_id, marketplace, brand_key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_brand_dictionary_product ON brand_dictionary(product_id, marketplace);
+        ",
     )?;
     Ok(())
 }
 
-// 删除设置
-pub fn delete_setting(key: &str) -> Result<()> {
-    let conn = get_db().lock();
-    conn.execute("DELETE FROM settings WHERE key = ?1", [key])?;
-    Ok(())
-}
-
-// ==================== 关键词排名监控 ====================
-
-// 关键词监控结构体
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct KeywordMonitoring {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BrandDictionaryEntry {
     pub id: i64,
     pub product_id: i64,
-    pub keyword: String,
-    pub asin: String,
-    pub country: String,           // US/UK/DE/FR/IT/ES
-    pub priority: String,          // high/medium/low
-    pub is_active: bool,
-
-    // 最新排名
-    pub latest_organic_rank: Option<i64>,
-    pub latest_organic_page: Option<i64>,
-    pub latest_sponsored_rank: Option<i64>,
-    pub latest_sponsored_page: Option<i64>,
-
-    // 产品信息
-    pub image_url: Option<String>,
-    pub price: Option<String>,
-    pub reviews_count: Option<i64>,
-    pub rating: Option<f64>,
-
-    pub last_checked: Option<String>,
+    pub marketplace: String,
+    pub brand: String,
+    pub brand_type: String,     // own, competitor
+    pub source: String,         // manual, competitor_title
+    pub title_count: i64,       // 在竞品标题中出现的次数
+    pub keyword_count: i64,     // 命中的关键词数
     pub created_at: String,
-    pub tags: Option<String>,  // JSON array: ["high_traffic", "high_conversion"]
 }
 
-// 排名历史结构体
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct RankingHistory {
-    pub id: i64,
-    pub monitoring_id: i64,
-    pub check_date: String,
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BrandSeedResult {
+    pub titles_scanned: i64,
+    pub brands_added: i64,
+    pub brands_updated: i64,
+    pub brands_removed: i64,  // 不再满足条件而删除的自动提取条目
+    pub keywords_flagged: i64,
+    pub roots_flagged: i64,
+}
 
-    pub organic_rank: Option<i64>,
-    pub organic_page: Option<i64>,
-    pub sponsored_rank: Option<i64>,
-    pub sponsored_page: Option<i64>,
+// 竞品品牌否词建议
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BrandNegativeSuggestion {
+    pub brand: String,
+    pub keyword_count: i64,
+    pub total_search_volume: f64,
+    pub sample_keywords: Vec<String>,
+    // 可直接设为否词的词根（单词品牌）；多词品牌只能作为广告否定词组
+    pub root_words: Vec<String>,
+    pub negative_phrase: String,
+    pub already_negative: bool,
+}
+
+// 品牌筛选条件：types 可包含 own / competitor / none（未命中品牌）
+// placeholder(i) 生成第 i 个参数的占位符，参数只包含非 none 的值
+fn brand_type_condition(
+    column: &str,
+    types: &[String],
+    placeholder: impl Fn(usize) -> String,
+) -> Option<String> {
+    let values: Vec<&String> = types.iter().filter(|t| t.as_str() != "none").collect();
+    let include_none = types.iter().any(|t| t == "none");
+
+    let mut parts = Vec::new();
+    if !values.is_empty() {
+        let placeholders: Vec<String> = (0..values.len()).map(&placeholder).collect();
+        parts.push(format!("{} IN ({})", column, placeholders.join(",")));
+    }
+    if include_none {
+        parts.push(format!("{} IS NULL", column));
+    }
 
-    pub checked_at: String,
+    if parts.is_empty() {
+        None
+    } else {
+        Some(format!("({})", parts.join(" OR ")))
+    }
 }
 
-// 监控项的迷你图数据
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct MonitoringSparkline {
-    pub monitoring_id: i64,
-    pub organic_ranks: Vec<Option<i64>>,
-    pub sponsored_ranks: Vec<Option<i64>>,
+// 产品所在站点（未设置时按 US）
+fn product_marketplace(conn: &Connection, product_id: i64) -> Result<String> {
+    let country: Option<String> = conn.query_row(
+        "SELECT country FROM products WHERE id = ?1",
+        [product_id],
+        |row| row.get(0),
+    )?;
+    Ok(country.filter(|c| !c.is_empty()).unwrap_or_else(|| "US".to_string()))
 }
 
-// 竞品快照结构体
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct RankingSnapshot {
-    pub id: i64,
-    pub keyword: String,
-    pub country: String,
-    pub snapshot_date: String,
+// 按品牌词典重新标记产品的关键词和词根，返回 (关键词数, 词根数)
+fn apply_brand_flags(conn: &Connection, product_id: i64) -> Result<(i64, i64)> {
+    let marketplace = product_marketplace(conn, product_id)?;
 
-    pub organic_top_50: Option<String>,    // JSON: ["ASIN1", "ASIN2", ...]
-    pub sponsored_top_20: Option<String>,  // JSON
+    let mut stmt = conn.prepare(
+        "SELECT brand, brand_key, brand_type FROM brand_dictionary
+         WHERE product_id = ?1 AND marketplace = ?2",
+    )?;
+    let rows = stmt
+        .query_map(rusqlite::params![product_id, marketplace], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    let names: std::collections::HashMap<String, String> =
+        rows.iter().map(|(brand, key, _)| (key.clone(), brand.clone())).collect();
+    let entries: Vec<crate::brand::BrandEntry> = rows
+        .into_iter()
+        .map(|(_, brand_key, brand_type)| crate::brand::BrandEntry { brand_key, brand_type })
+        .collect();
 
-    pub created_at: String,
+    conn.execute(
+        "UPDATE keyword_data SET brand_name = NULL, brand_type = NULL WHERE product_id = ?1",
+        [product_id],
+    )?;
+    conn.execute(
+        "UPDATE roots SET brand_name = NULL, brand_type = NULL WHERE product_id = ?1",
+        [product_id],
+    )?;
+    if entries.is_empty() {
+        return Ok((0, 0));
+    }
+
+    let flag = |table: &str, text_column: &str| -> Result<i64> {
+        let mut select = conn.prepare(&format!(
+            "SELECT id, {} FROM {} WHERE product_id = ?1",
+            text_column, table
+        ))?;
+        let items = select
+            .query_map([product_id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>>>()?;
+        let mut update = conn.prepare(&format!(
+            "UPDATE {} SET brand_name = ?1, brand_type = ?2 WHERE id = ?3",
+            table
+        ))?;
+        let mut flagged = 0;
+        for (id, text) in items {
+            if let Some(entry) = crate::brand::match_brand(&text, &entries) {
+                let name = names.get(&entry.brand_key).unwrap_or(&entry.brand_key);
+                update.execute(rusqlite::params![name, entry.brand_type, id])?;
+                flagged += 1;
+            }
+        }
+        Ok(flagged)
+    };
+
+    let keywords = flag("keyword_data", "keyword")?;
+    let roots = flag("roots", "word")?;
+    Ok((keywords, roots))
 }
 
-// 监控统计结构体
-#[derive(Debug, Serialize, Deserialize)]
-pub struct MonitoringStats {
-    pub total: i64,
-    pub active: i64,
-    pub top10_organic: i64,
-    pub top30_organic: i64,
-    pub with_sponsored: i64,
+// 重新标记品牌词（词典变更后调用）
+pub fn refresh_brand_flags(product_id: i64) -> Result<(i64, i64)> {
+    let conn = get_db().lock();
+    conn.execute("BEGIN TRANSACTION", [])?;
+    match apply_brand_flags(&conn, product_id) {
+        Ok(counts) => {
+            conn.execute("COMMIT", [])?;
+            Ok(counts)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
 }
 
-// 初始化关键词监控相关表
-pub fn init_monitoring_tables(conn: &Connection) -> Result<()> {
-    conn.execute_batch(
-        "
-        -- 关键词监控表
-        CREATE TABLE IF NOT EXISTS keyword_monitoring (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            product_id INTEGER NOT NULL,
-            keyword TEXT NOT NULL,
-            asin TEXT NOT NULL,
-            country TEXT NOT NULL DEFAULT 'US',
-            priority TEXT DEFAULT 'medium',
-            is_active INTEGER DEFAULT 1,
+// 获取品牌词典（默认当前产品站点）
+pub fn get_brand_dictionary(product_id: i64, marketplace: Option<String>) -> Result<Vec<BrandDictionaryEntry>> {
+    let conn = get_db().lock();
+    let marketplace = match marketplace {
+        Some(m) if !m.is_empty() => m,
+        _ => product_marketplace(&conn, product_id)?,
+    };
 
-            -- 最新排名
-            latest_organic_rank INTEGER,
-            latest_organic_page INTEGER,
-            latest_sponsored_rank INTEGER,
-            latest_sponsored_page INTEGER,
+    let mut stmt = conn.prepare(
+        "SELECT b.id, b.product_id, b.marketplace, b.brand, b.brand_type, b.source, b.title_count,
+                (SELECT COUNT(*) FROM keyword_data k WHERE k.product_id = b.product_id AND k.brand_name = b.brand),
+                b.created_at
+         FROM brand_dictionary b
+         WHERE b.product_id = ?1 AND b.marketplace = ?2
+         ORDER BY b.brand_type DESC, b.title_count DESC, b.brand",
+    )?;
+    let entries = stmt
+        .query_map(rusqlite::params![product_id, marketplace], |row| {
+            Ok(BrandDictionaryEntry {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                marketplace: row.get(2)?,
+                brand: row.get(3)?,
+                brand_type: row.get(4)?,
+                source: row.get(5)?,
+                title_count: row.get(6)?,
+                keyword_count: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(entries)
+}
 
-            -- 产品信息
-            image_url TEXT,
-            price TEXT,
-            reviews_count INTEGER,
-            rating REAL,
+// 手动添加品牌（已存在时更新品牌类型），并重新标记
+pub fn add_brand(product_id: i64, marketplace: Option<String>, brand: &str, brand_type: &str) -> Result<i64> {
+    let brand_key = crate::brand::normalize_brand(brand);
+    if brand_key.is_empty() {
+        return Err(rusqlite::Error::InvalidParameterName("品牌名称不能为空".to_string()));
+    }
 
-            last_checked TIMESTAMP,
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(keyword, asin, country, product_id),
-            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
-        );
+    let id = {
+        let conn = get_db().lock();
+        let marketplace = match marketplace {
+            Some(m) if !m.is_empty() => m,
+            _ => product_marketplace(&conn, product_id)?,
+        };
+        conn.query_row(
+            "INSERT INTO brand_dictionary (product_id, marketplace, brand, brand_key, brand_type, source)
+             VALUES (?1, ?2, ?3, ?4, ?5, 'manual')
+             ON CONFLICT(product_id, marketplace, brand_key) DO UPDATE SET
+                brand = excluded.brand, brand_type = excluded.brand_type
+             RETURNING id",
+            rusqlite::params![product_id, marketplace, brand.trim(), brand_key, brand_type],
+            |row| row.get(0),
+        )?
+    };
 
-        -- 排名历史表
-        CREATE TABLE IF NOT EXISTS keyword_ranking_history (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            monitoring_id INTEGER NOT NULL,
-            check_date DATE NOT NULL,
+    refresh_brand_flags(product_id)?;
+    Ok(id)
+}
 
-            organic_rank INTEGER,
-            organic_page INTEGER,
-            sponsored_rank INTEGER,
-            sponsored_page INTEGER,
+// 修改品牌类型（自有 / 竞品）
+pub fn update_brand_type(id: i64, brand_type: &str) -> Result<()> {
+    let product_id: i64 = {
+        let conn = get_db().lock();
+        conn.execute(
+            "UPDATE brand_dictionary SET brand_type = ?1 WHERE id = ?2",
+            rusqlite::params![brand_type, id],
+        )?;
+        conn.query_row("SELECT product_id FROM brand_dictionary WHERE id = ?1", [id], |row| row.get(0))?
+    };
+    refresh_brand_flags(product_id)?;
+    Ok(())
+}
 
-            checked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (monitoring_id) REFERENCES keyword_monitoring(id) ON DELETE CASCADE
-        );
+// 删除品牌
+pub fn delete_brand(id: i64) -> Result<()> {
+    let product_id: Option<i64> = {
+        let conn = get_db().lock();
+        let product_id = conn
+            .query_row("SELECT product_id FROM brand_dictionary WHERE id = ?1", [id], |row| row.get(0))
+            .ok();
+        conn.execute("DELETE FROM brand_dictionary WHERE id = ?1", [id])?;
+        product_id
+    };
+    if let Some(product_id) = product_id {
+        refresh_brand_flags(product_id)?;
+    }
+    Ok(())
+}
 
-        -- 竞品快照表
-        CREATE TABLE IF NOT EXISTS ranking_snapshots (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            keyword TEXT NOT NULL,
-            country TEXT NOT NULL,
-            snapshot_date DATE NOT NULL,
+// 从竞品标题提取品牌写入词典：
+// - 智能文案中关联该产品、同站点项目的竞品（sc_competitors.title）
+// - 竞品情报监控中同站点、监控的是该产品 ASIN（产品 ASIN 或上述项目的 my_asin）的任务下的竞品 ASIN（competitor_asins.title）
+// 自己的 ASIN（my_asin）不参与；已有条目只更新出现次数，不改品牌类型
+// 品牌按标题中第一次出现的写法显示（"UGREEN"），brand_key 只用于匹配
+pub fn seed_brand_dictionary(product_id: i64) -> Result<BrandSeedResult> {
+    let conn = get_db().lock();
+    let marketplace = product_marketplace(&conn, product_id)?;
 
-            organic_top_50 TEXT,
-            sponsored_top_20 TEXT,
+    let mut titles: Vec<(String, String)> = Vec::new();
+    let mut stmt = conn.prepare(
+        "SELECT c.asin, c.title FROM sc_competitors c
+         JOIN sc_projects p ON p.id = c.project_id
+         WHERE p.product_id = ?1 AND p.marketplace = ?2
+           AND c.title IS NOT NULL AND c.title != ''
+           AND (p.my_asin IS NULL OR c.asin != p.my_asin)
+         ORDER BY c.id",
+    )?;
+    for item in stmt.query_map(rusqlite::params![product_id, marketplace], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        titles.push(item?);
+    }
+    let mut stmt = conn.prepare(
+        "SELECT a.asin, a.title FROM competitor_asins a
+         JOIN competitor_tasks t ON t.id = a.task_id
+         WHERE t.marketplace = ?2
+           AND UPPER(t.my_asin) IN (
+               SELECT UPPER(asin) FROM products WHERE id = ?1 AND asin IS NOT NULL AND asin != ''
+               UNION
+               SELECT UPPER(my_asin) FROM sc_projects WHERE product_id = ?1 AND marketplace = ?2 AND my_asin IS NOT NULL
+           )
+           AND a.title IS NOT NULL AND a.title != ''
+           AND a.asin != t.my_asin
+         ORDER BY a.id",
+    )?;
+    for item in stmt.query_map(rusqlite::params![product_id, marketplace], |row| Ok((row.get(0)?, row.get(1)?)))? {
+        titles.push(item?);
+    }
 
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-            UNIQUE(keyword, country, snapshot_date)
-        );
+    let brands = crate::brand::collect_brands(titles.iter().map(|(asin, title)| (asin.as_str(), title.as_str())));
 
-        -- 定时任务记录表
-        CREATE TABLE IF NOT EXISTS scheduler_task_logs (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            started_at TIMESTAMP NOT NULL,
-            ended_at TIMESTAMP,
-            status TEXT NOT NULL DEFAULT 'running',  -- running, completed, failed
-            total_keywords INTEGER DEFAULT 0,
-            success_count INTEGER DEFAULT 0,
-            failed_count INTEGER DEFAULT 0,
-            trigger_type TEXT DEFAULT 'auto',  -- auto(定时), manual(手动)
-            error_message TEXT,
-            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
-        );
+    conn.execute("BEGIN TRANSACTION", [])?;
+    let result = (|| {
+        let mut added = 0;
+        let mut updated = 0;
+        for (brand_key, found) in &brands {
+            let exists: bool = conn
+                .query_row(
+                    "SELECT 1 FROM brand_dictionary WHERE product_id = ?1 AND marketplace = ?2 AND brand_key = ?3",
+                    rusqlite::params![product_id, marketplace, brand_key],
+                    |_| Ok(()),
+                )
+                .is_ok();
+            // 以前自动提取的条目存的是小写品牌名（brand = brand_key），顺便改成标题中的写法
+            conn.execute(
+                "INSERT INTO brand_dictionary (product_id, marketplace, brand, brand_key, brand_type, source, title_count)
+                 VALUES (?1, ?2, ?3, ?4, 'competitor', 'competitor_title', ?5)
+                 ON CONFLICT(product_id, marketplace, brand_key) DO UPDATE SET
+                    title_count = excluded.title_count,
+                    brand = CASE WHEN brand = brand_key THEN excluded.brand ELSE brand END",
+                rusqlite::params![product_id, marketplace, found.brand, brand_key, found.asin_count],
+            )?;
+            if exists {
+                updated += 1;
+            } else {
+                added += 1;
+            }
+        }
+        // 之前自动提取、现在达不到条件的竞品条目删除（手动添加或改成自有品牌的保留）
+        let mut removed = 0;
+        let stale = {
+            let mut stmt = conn.prepare(
+                "SELECT id, brand_key FROM brand_dictionary
+                 WHERE product_id = ?1 AND marketplace = ?2 AND source = 'competitor_title' AND brand_type = 'competitor'",
+            )?;
+            let rows = stmt
+                .query_map(rusqlite::params![product_id, marketplace], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            rows
+        };
+        for (id, brand_key) in stale {
+            if !brands.contains_key(&brand_key) {
+                removed += conn.execute("DELETE FROM brand_dictionary WHERE id = ?1", [id])? as i64;
+            }
+        }
 
-        -- 优化事件表
-        CREATE TABLE IF NOT EXISTS optimization_events (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            product_id INTEGER NOT NULL,
-            event_date TEXT NOT NULL,
-            event_type TEXT NOT NULL,
-            title TEXT NOT NULL,
-            description TEXT,
-            affected_keywords TEXT,
-            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
-        );
+        let (keywords_flagged, roots_flagged) = apply_brand_flags(&conn, product_id)?;
+        Ok::<_, rusqlite::Error>(BrandSeedResult {
+            titles_scanned: titles.len() as i64,
+            brands_added: added,
+            brands_updated: updated,
+            brands_removed: removed,
+            keywords_flagged,
+            roots_flagged,
+        })
+    })();
 
-        -- 索引
-        CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_product ON keyword_monitoring(product_id);
-        CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_active ON keyword_monitoring(is_active);
-        CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_country ON keyword_monitoring(country);
-        CREATE INDEX IF NOT EXISTS idx_ranking_history_monitoring ON keyword_ranking_history(monitoring_id);
-        CREATE INDEX IF NOT EXISTS idx_ranking_history_date ON keyword_ranking_history(check_date);
-        CREATE INDEX IF NOT EXISTS idx_ranking_snapshots_keyword ON ranking_snapshots(keyword, country);
-        CREATE INDEX IF NOT EXISTS idx_scheduler_task_logs_started ON scheduler_task_logs(started_at);
-        CREATE INDEX IF NOT EXISTS idx_events_product ON optimization_events(product_id);
-        CREATE INDEX IF NOT EXISTS idx_events_date ON optimization_events(event_date);
-        "
-    )?;
-    Ok(())
+    match result {
+        Ok(r) => {
+            conn.execute("COMMIT", [])?;
+            Ok(r)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
 }
 
-// 添加关键词监控
-pub fn add_keyword_monitoring(
-    product_id: i64,
-    keyword: String,
-    asin: String,
-    country: String,
-    priority: Option<String>,
-) -> Result<i64> {
+// 竞品品牌否词建议：按品牌汇总命中的关键词，按搜索量排序
+pub fn get_brand_negative_suggestions(product_id: i64) -> Result<Vec<BrandNegativeSuggestion>> {
     let conn = get_db().lock();
-    let priority = priority.unwrap_or_else(|| "medium".to_string());
 
-    conn.execute(
-        "INSERT OR IGNORE INTO keyword_monitoring (product_id, keyword, asin, country, priority)
-         VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![product_id, keyword, asin, country, priority],
+    let mut stmt = conn.prepare(
+        "SELECT brand_name, COUNT(*), COALESCE(SUM(avg_search_volume), 0)
+         FROM keyword_data
+         WHERE product_id = ?1 AND brand_type = 'competitor' AND brand_name IS NOT NULL
+         GROUP BY brand_name
+         ORDER BY 3 DESC, 2 DESC",
     )?;
+    let groups = stmt
+        .query_map([product_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, f64>(2)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
 
-    // 获取插入或已存在的ID
-    let id: i64 = conn.query_row(
-        "SELECT id FROM keyword_monitoring WHERE keyword = ?1 AND asin = ?2 AND country = ?3 AND product_id = ?4",
-        rusqlite::params![keyword, asin, country, product_id],
-        |row| row.get(0),
-    )?;
+    let mut sample_stmt = conn.prepare(
+        "SELECT keyword FROM keyword_data
+         WHERE product_id = ?1 AND brand_name = ?2
+         ORDER BY avg_search_volume DESC NULLS LAST LIMIT 5",
+    )?;
+    let mut root_stmt = conn.prepare(
+        "SELECT word, COALESCE(is_negative, 0) FROM roots
+         WHERE product_id = ?1 AND brand_name = ?2 AND brand_type = 'competitor'",
+    )?;
+
+    let mut suggestions = Vec::new();
+    for (brand, keyword_count, total_search_volume) in groups {
+        let sample_keywords = sample_stmt
+            .query_map(rusqlite::params![product_id, brand], |row| row.get(0))?
+            .collect::<Result<Vec<String>>>()?;
+        let roots = root_stmt
+            .query_map(rusqlite::params![product_id, brand], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? != 0))
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        let already_negative = !roots.is_empty() && roots.iter().all(|(_, negative)| *negative);
+
+        suggestions.push(BrandNegativeSuggestion {
+            negative_phrase: crate::brand::normalize_brand(&brand),
+            brand,
+            keyword_count,
+            total_search_volume,
+            sample_keywords,
+            root_words: roots.into_iter().map(|(word, _)| word).collect(),
+            already_negative,
+        });
+    }
 
-    Ok(id)
+    Ok(suggestions)
 }
 
-// 获取关键词监控列表
-pub fn get_keyword_monitoring_list(
-    product_id: i64,
-    country: Option<String>,
-    priority: Option<String>,
-    is_active: Option<bool>,
-    search: Option<String>,
-    sort_by: Option<String>,
-    sort_order: Option<String>,
-    page: i64,
-    page_size: i64,
-) -> Result<(Vec<KeywordMonitoring>, i64)> {
-    let conn = get_db().lock();
-
-    let mut sql = String::from(
-        "SELECT id, product_id, keyword, asin, country, priority, is_active,
-                latest_organic_rank, latest_organic_page, latest_sponsored_rank, latest_sponsored_page,
-                image_url, price, reviews_count, rating, last_checked, created_at, tags
-         FROM keyword_monitoring WHERE product_id = ?1"
-    );
+// ==================== 数据完整性检查 ====================
 
-    let mut count_sql = String::from("SELECT COUNT(*) FROM keyword_monitoring WHERE product_id = ?1");
-    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(product_id)];
-    let mut param_index = 2;
+// 孤儿数据问题（按表 + 外键列汇总）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrphanIssue {
+    pub table: String,
+    pub column: String,
+    pub parent_table: String,
+    pub action: String, // delete: 删除孤儿行, set_null: 解除关联
+    pub count: i64,
+}
 
-    // 筛选条件
-    if let Some(ref c) = country {
-        let condition = format!(" AND country = ?{}", param_index);
-        sql.push_str(&condition);
-        count_sql.push_str(&condition);
-        params.push(Box::new(c.clone()));
-        param_index += 1;
+// 完整性检查报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    pub integrity_ok: bool,
+    pub integrity_messages: Vec<String>,
+    pub foreign_keys_enabled: bool,
+    pub tables_checked: i64,
+    pub issues: Vec<OrphanIssue>,
+    pub total_orphans: i64,
+    pub repaired: bool,
+    pub remaining_orphans: i64,
+}
+
+// 外键定义：(外键ID, 子表列, 父表, 修复方式)
+struct ForeignKeyDef {
+    id: i64,
+    columns: Vec<String>,
+    parent_table: String,
+    set_null: bool,
+}
+
+// 读取表的外键定义；ON DELETE SET NULL 或可为空的列用 set_null 修复，其余删除整行
+fn get_foreign_keys(conn: &Connection, table: &str) -> Result<Vec<ForeignKeyDef>> {
+    let mut nullable: HashSet<String> = HashSet::new();
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info(\"{}\")", table))?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, i64>(3)?, row.get::<_, i64>(5)?)))?;
+    for (name, notnull, pk) in rows.flatten() {
+        if notnull == 0 && pk == 0 {
+            nullable.insert(name);
+        }
     }
 
-    if let Some(ref p) = priority {
-        let condition = format!(" AND priority = ?{}", param_index);
-        sql.push_str(&condition);
-        count_sql.push_str(&condition);
-        params.push(Box::new(p.clone()));
-        param_index += 1;
+    let mut defs: Vec<ForeignKeyDef> = Vec::new();
+    let mut stmt = conn.prepare(&format!("PRAGMA foreign_key_list(\"{}\")", table))?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, String>(6)?,
+        ))
+    })?;
+    for (id, parent_table, column, on_delete) in rows.flatten() {
+        let column_nullable = nullable.contains(&column);
+        if let Some(def) = defs.iter_mut().find(|d| d.id == id) {
+            def.set_null = def.set_null && column_nullable;
+            def.columns.push(column);
+        } else {
+            defs.push(ForeignKeyDef {
+                id,
+                columns: vec![column],
+                parent_table,
+                set_null: column_nullable || on_delete.eq_ignore_ascii_case("SET NULL"),
+            });
+        }
     }
+    Ok(defs)
+}
 
-    if let Some(active) = is_active {
-        let condition = format!(" AND is_active = ?{}", param_index);
-        sql.push_str(&condition);
-        count_sql.push_str(&condition);
-        params.push(Box::new(if active { 1i64 } else { 0i64 }));
-        param_index += 1;
+// 扫描所有外键违规：返回 (表, 外键ID) -> 违规行 rowid 列表
+fn scan_orphans(conn: &Connection) -> Result<Vec<(String, i64, Vec<i64>)>> {
+    let mut stmt = conn.prepare("PRAGMA foreign_key_check")?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<i64>>(1)?, row.get::<_, i64>(3)?))
+    })?;
+
+    let mut grouped: Vec<(String, i64, Vec<i64>)> = Vec::new();
+    for (table, rowid, fk_id) in rows.flatten() {
+        let Some(rowid) = rowid else { continue };
+        match grouped.iter_mut().find(|(t, id, _)| *t == table && *id == fk_id) {
+            Some((_, _, ids)) => ids.push(rowid),
+            None => grouped.push((table, fk_id, vec![rowid])),
+        }
     }
+    Ok(grouped)
+}
+
+// 汇总孤儿数据问题（不修改数据）
+fn collect_orphan_issues(conn: &Connection) -> Result<Vec<OrphanIssue>> {
+    let mut issues = Vec::new();
+    for (table, fk_id, rowids) in scan_orphans(conn)? {
+        let defs = get_foreign_keys(conn, &table)?;
+        let Some(def) = defs.iter().find(|d| d.id == fk_id) else { continue };
+        issues.push(OrphanIssue {
+            table,
+            column: def.columns.join(","),
+            parent_table: def.parent_table.clone(),
+            action: if def.set_null { "set_null" } else { "delete" }.to_string(),
+            count: rowids.len() as i64,
+        });
+    }
+    Ok(issues)
+}
 
-    if let Some(ref s) = search {
-        if !s.is_empty() {
-            let condition = format!(" AND (keyword LIKE ?{} OR asin LIKE ?{})", param_index, param_index);
-            sql.push_str(&condition);
-            count_sql.push_str(&condition);
-            params.push(Box::new(format!("%{}%", s)));
-            // param_index += 1;  // 同一个参数用两次
+// 修复孤儿数据，返回处理的行数
+// 删除孤儿行后其子表可能产生新的孤儿（外键关闭时不会级联），因此循环直到没有违规
+fn repair_orphans(conn: &Connection) -> Result<i64> {
+    let mut fixed = 0i64;
+    for _ in 0..10 {
+        let orphans = scan_orphans(conn)?;
+        if orphans.is_empty() {
+            break;
+        }
+        for (table, fk_id, rowids) in orphans {
+            let defs = get_foreign_keys(conn, &table)?;
+            let Some(def) = defs.iter().find(|d| d.id == fk_id) else { continue };
+            let sql = if def.set_null {
+                let sets: Vec<String> = def.columns.iter().map(|c| format!("\"{}\" = NULL", c)).collect();
+                format!("UPDATE \"{}\" SET {} WHERE rowid = ?1", table, sets.join(", "))
+            } else {
+                format!("DELETE FROM \"{}\" WHERE rowid = ?1", table)
+            };
+            for rowid in rowids {
+                fixed += conn.execute(&sql, [rowid])? as i64;
+            }
         }
     }
+    Ok(fixed)
+}
 
-    // 排序
-    let sort_column = match sort_by.as_deref() {
-        Some("keyword") => "keyword",
-        Some("latest_organic_rank") => "latest_organic_rank",
-        Some("latest_sponsored_rank") => "latest_sponsored_rank",
-        Some("last_checked") => "last_checked",
-        Some("priority") => "priority",
-        _ => "created_at",
-    };
-    let order = match sort_order.as_deref() {
-        Some("asc") => "ASC",
-        _ => "DESC",
+// 检查数据库完整性，repair = true 时修复孤儿数据
+pub fn check_db_integrity(repair: bool) -> Result<IntegrityReport> {
+    let conn = get_db().lock();
+
+    let integrity_messages: Vec<String> = {
+        let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<Result<Vec<_>>>()?
     };
-    sql.push_str(&format!(" ORDER BY {} {}", sort_column, order));
+    let integrity_ok = integrity_messages.len() == 1 && integrity_messages[0] == "ok";
 
-    // 分页
-    sql.push_str(&format!(" LIMIT {} OFFSET {}", page_size, (page - 1) * page_size));
+    let foreign_keys_enabled: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get::<_, i64>(0))? == 1;
+    let tables_checked: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%'",
+        [],
+        |row| row.get(0),
+    )?;
 
-    let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    let issues = collect_orphan_issues(&conn)?;
+    let total_orphans: i64 = issues.iter().map(|i| i.count).sum();
 
-    let mut stmt = conn.prepare(&sql)?;
-    let data = stmt
-        .query_map(params_refs.as_slice(), |row| {
-            Ok(KeywordMonitoring {
-                id: row.get(0)?,
-                product_id: row.get(1)?,
-                keyword: row.get(2)?,
-                asin: row.get(3)?,
-                country: row.get(4)?,
-                priority: row.get(5)?,
-                is_active: row.get::<_, i64>(6)? == 1,
-                latest_organic_rank: row.get(7)?,
-                latest_organic_page: row.get(8)?,
-                latest_sponsored_rank: row.get(9)?,
-                latest_sponsored_page: row.get(10)?,
-                image_url: row.get(11)?,
-                price: row.get(12)?,
-                reviews_count: row.get(13)?,
-                rating: row.get(14)?,
-                last_checked: row.get(15)?,
-                created_at: row.get(16)?,
-                tags: row.get(17)?,
-            })
-        })?
-        .collect::<Result<Vec<_>>>()?;
+    let mut repaired = false;
+    if repair && total_orphans > 0 {
+        conn.execute("BEGIN TRANSACTION", [])?;
+        match repair_orphans(&conn) {
+            Ok(_) => {
+                conn.execute("COMMIT", [])?;
+                repaired = true;
+            }
+            Err(e) => {
+                conn.execute("ROLLBACK", []).ok();
+                return Err(e);
+            }
+        }
+    }
 
-    // 获取总数
-    let count_params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-    let total: i64 = conn.query_row(&count_sql, count_params_refs.as_slice(), |row| row.get(0))?;
+    let remaining_orphans: i64 = if repaired {
+        collect_orphan_issues(&conn)?.iter().map(|i| i.count).sum()
+    } else {
+        total_orphans
+    };
 
-    Ok((data, total))
+    Ok(IntegrityReport {
+        integrity_ok,
+        integrity_messages,
+        foreign_keys_enabled,
+        tables_checked,
+        issues,
+        total_orphans,
+        repaired,
+        remaining_orphans,
+    })
+}
+
+// ==================== 设置管理 ====================
+
+// 获取设置值
+pub fn get_setting(key: &str) -> Result<Option<String>> {
+    let conn = get_db().lock();
+    let result = conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        [key],
+        |row| row.get(0),
+    );
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// 设置值
+pub fn set_setting(key: &str, value: &str) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        rusqlite::params![key, value],
+    )?;
+    Ok(())
+}
+
+// 删除设置
+pub fn delete_setting(key: &str) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute("DELETE FROM settings WHERE key = ?1", [key])?;
+    Ok(())
+}
+
+// ==================== 关键词排名监控 ====================
+
+// 关键词监控结构体
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KeywordMonitoring {
+    pub id: i64,
+    pub product_id: i64,
+    pub keyword: String,
+    pub asin: String,
+    pub country: String,           // US/UK/DE/FR/IT/ES
+    pub priority: String,          // high/medium/low
+    pub is_active: bool,
+
+    // 最新排名
+    pub latest_organic_rank: Option<i64>,
+    pub latest_organic_page: Option<i64>,
+    pub latest_sponsored_rank: Option<i64>,
+    pub latest_sponsored_page: Option<i64>,
+
+    // 产品信息
+    pub image_url: Option<String>,
+    pub price: Option<String>,
+    pub reviews_count: Option<i64>,
+    pub rating: Option<f64>,
+
+    pub last_checked: Option<String>,
+    pub created_at: String,
+    pub tags: Option<String>,  // JSON array: ["high_traffic", "high_conversion"]
+    #[serde(default)]
+    pub max_pages: Option<i64>,  // 单独设置的检测页数，为空时使用全局设置
+    #[serde(default)]
+    pub location: Option<String>,  // 单独指定的配送邮编，为空时使用站点设置
+}
+
+const KEYWORD_MONITORING_COLUMNS: &str = "id, product_id, keyword, asin, country, priority, is_active,
+    latest_organic_rank, latest_organic_page, latest_sponsored_rank, latest_sponsored_page,
+    image_url, price, reviews_count, rating, last_checked, created_at, tags, max_pages, location";
+
+fn row_to_monitoring(row: &rusqlite::Row) -> rusqlite::Result<KeywordMonitoring> {
+    Ok(KeywordMonitoring {
+        id: row.get(0)?,
+        product_id: row.get(1)?,
+        keyword: row.get(2)?,
+        asin: row.get(3)?,
+        country: row.get(4)?,
+        priority: row.get(5)?,
+        is_active: row.get::<_, i64>(6)? == 1,
+        latest_organic_rank: row.get(7)?,
+        latest_organic_page: row.get(8)?,
+        latest_sponsored_rank: row.get(9)?,
+        latest_sponsored_page: row.get(10)?,
+        image_url: row.get(11)?,
+        price: row.get(12)?,
+        reviews_count: row.get(13)?,
+        rating: row.get(14)?,
+        last_checked: row.get(15)?,
+        created_at: row.get(16)?,
+        tags: row.get(17)?,
+        max_pages: row.get(18)?,
+        location: row.get::<_, Option<String>>(19)?.filter(|l| !l.is_empty()),
+    })
+}
+
+// 排名历史结构体
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RankingHistory {
+    pub id: i64,                    // 汇总数据为 0
+    pub monitoring_id: i64,
+    pub check_date: String,         // 按周聚合时为周一
+
+    // 周期内的最佳排名（页码 + 页内排名）
+    pub organic_rank: Option<i64>,
+    pub organic_page: Option<i64>,
+    pub sponsored_rank: Option<i64>,
+    pub sponsored_page: Option<i64>,
+
+    // 周期内自然结果的最佳/平均/最差绝对位置（没有位置信息的旧记录不参与）
+    #[serde(default)]
+    pub organic_position: Option<i64>,
+    pub organic_avg: Option<f64>,
+    pub organic_worst: Option<i64>,
+    pub sponsored_avg: Option<f64>,
+    pub sponsored_worst: Option<i64>,
+    pub check_count: i64,
+    pub resolution: String,         // day, week
+
+    pub checked_at: String,
+    #[serde(default)]
+    pub location: Option<String>,   // 检测使用的配送邮编（同一周期不同邮编分别返回），未记录时为 None
+}
+
+// 监控项的迷你图数据
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MonitoringSparkline {
+    pub monitoring_id: i64,
+    pub organic_ranks: Vec<Option<i64>>,
+    pub sponsored_ranks: Vec<Option<i64>>,
+}
+
+// 竞品快照结构体
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RankingSnapshot {
+    pub id: i64,
+    pub keyword: String,
+    pub country: String,
+    pub snapshot_date: String,
+
+    pub organic_top_50: Option<String>,    // JSON: ["ASIN1", "ASIN2", ...]
+    pub sponsored_top_20: Option<String>,  // JSON
+
+    pub created_at: String,
+    #[serde(default)]
+    pub location: Option<String>,          // 检测使用的配送邮编，旧快照为 None
+}
+
+// 监控统计结构体
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MonitoringStats {
+    pub total: i64,
+    pub active: i64,
+    pub top10_organic: i64,
+    pub top30_organic: i64,
+    pub with_sponsored: i64,
+}
+
+// 初始化关键词监控相关表
+pub fn init_monitoring_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        -- 关键词监控表
+        CREATE TABLE IF NOT EXISTS keyword_monitoring (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            keyword TEXT NOT NULL,
+            asin TEXT NOT NULL,
+            country TEXT NOT NULL DEFAULT 'US',
+            priority TEXT DEFAULT 'medium',
+            is_active INTEGER DEFAULT 1,
+
+            -- 最新排名
+            latest_organic_rank INTEGER,
+            latest_organic_page INTEGER,
+            latest_sponsored_rank INTEGER,
+            latest_sponsored_page INTEGER,
+            latest_organic_position INTEGER,    -- 自然结果中的绝对位置（用于告警比较）
+
+            -- 产品信息
+            image_url TEXT,
+            price TEXT,
+            reviews_count INTEGER,
+            rating REAL,
+
+            last_checked TIMESTAMP,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            location TEXT NOT NULL DEFAULT '',  -- 配送邮编，空字符串表示使用站点设置
+            UNIQUE(keyword, asin, country, product_id, location),
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+        );
+
+        -- 排名历史表
+        CREATE TABLE IF NOT EXISTS keyword_ranking_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            monitoring_id INTEGER NOT NULL,
+            check_date DATE NOT NULL,
+
+            organic_rank INTEGER,
+            organic_page INTEGER,
+            sponsored_rank INTEGER,
+            sponsored_page INTEGER,
+            organic_position INTEGER,   -- 自然结果中的绝对位置（旧记录只有第 1 页有值）
+
+            checked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            location TEXT,              -- 检测使用的配送邮编
+            FOREIGN KEY (monitoring_id) REFERENCES keyword_monitoring(id) ON DELETE CASCADE
+        );
+
+        -- 竞品快照表
+        CREATE TABLE IF NOT EXISTS ranking_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            keyword TEXT NOT NULL,
+            country TEXT NOT NULL,
+            snapshot_date DATE NOT NULL,
+
+            organic_top_50 TEXT,
+            sponsored_top_20 TEXT,
+
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+            location TEXT,              -- 检测使用的配送邮编（只保存站点设置邮编的检测）
+            UNIQUE(keyword, country, snapshot_date)
+        );
+
+        -- 定时任务记录表
+        CREATE TABLE IF NOT EXISTS scheduler_task_logs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            started_at TIMESTAMP NOT NULL,
+            ended_at TIMESTAMP,
+            status TEXT NOT NULL DEFAULT 'running',  -- running, completed, failed
+            total_keywords INTEGER DEFAULT 0,
+            success_count INTEGER DEFAULT 0,
+            failed_count INTEGER DEFAULT 0,
+            trigger_type TEXT DEFAULT 'auto',  -- auto(定时), manual(手动)
+            error_message TEXT,
+            created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+        );
+
+        -- 优化事件表
+        CREATE TABLE IF NOT EXISTS optimization_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            product_id INTEGER NOT NULL,
+            event_date TEXT NOT NULL,
+            event_type TEXT NOT NULL,
+            title TEXT NOT NULL,
+            description TEXT,
+            affected_keywords TEXT,
+            created_at TEXT DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+        );
+
+        -- 索引
+        CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_product ON keyword_monitoring(product_id);
+        CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_active ON keyword_monitoring(is_active);
+        CREATE INDEX IF NOT EXISTS idx_keyword_monitoring_country ON keyword_monitoring(country);
+        CREATE INDEX IF NOT EXISTS idx_ranking_history_monitoring ON keyword_ranking_history(monitoring_id);
+        CREATE INDEX IF NOT EXISTS idx_ranking_history_date ON keyword_ranking_history(check_date);
+        CREATE INDEX IF NOT EXISTS idx_ranking_snapshots_keyword ON ranking_snapshots(keyword, country);
+        CREATE INDEX IF NOT EXISTS idx_scheduler_task_logs_started ON scheduler_task_logs(started_at);
+        CREATE INDEX IF NOT EXISTS idx_events_product ON optimization_events(product_id);
+        CREATE INDEX IF NOT EXISTS idx_events_date ON optimization_events(event_date);
+        "
+    )?;
+    Ok(())
+}
+
+// 添加关键词监控；location 为单独指定的配送邮编（为空时使用站点设置），同一关键词不同邮编分别监控
+pub fn add_keyword_monitoring(
+    product_id: i64,
+    keyword: String,
+    asin: String,
+    country: String,
+    priority: Option<String>,
+    location: Option<String>,
+) -> Result<i64> {
+    let conn = get_db().lock();
+    let priority = priority.unwrap_or_else(|| "medium".to_string());
+    let location = location.unwrap_or_default();
+
+    conn.execute(
+        "INSERT OR IGNORE INTO keyword_monitoring (product_id, keyword, asin, country, priority, location)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![product_id, keyword, asin, country, priority, location],
+    )?;
+
+    // 获取插入或已存在的ID
+    let id: i64 = conn.query_row(
+        "SELECT id FROM keyword_monitoring
+         WHERE keyword = ?1 AND asin = ?2 AND country = ?3 AND product_id = ?4 AND location = ?5",
+        rusqlite::params![keyword, asin, country, product_id, location],
+        |row| row.get(0),
+    )?;
+
+    Ok(id)
+}
+
+// 按词库筛选条件批量添加监控用的筛选条件（各列表为空表示不限）
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KeywordDataFilter {
+    pub search: Option<String>,
+    pub traffic_levels: Vec<String>,
+    pub relevance_levels: Vec<String>,
+    pub primary_categories: Vec<String>,
+    pub brand_types: Vec<String>,       // own / competitor / none
+    pub min_search_volume: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkMonitoringResult {
+    pub keyword_count: usize,   // 命中筛选的关键词数
+    pub candidates: usize,      // 关键词 × ASIN × 站点
+    pub created: usize,
+    pub existing: usize,        // 已在监控中（按 UNIQUE(keyword, asin, country, product_id, location) 去重）
+}
+
+// 匹配筛选条件的词库关键词
+fn filter_keyword_data(conn: &Connection, product_id: i64, filter: &KeywordDataFilter) -> Result<Vec<String>> {
+    let mut sql = String::from("SELECT keyword FROM keyword_data WHERE product_id = ?1");
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(product_id)];
+
+    if let Some(search) = filter.search.as_deref().filter(|s| !s.is_empty()) {
+        sql.push_str(&format!(" AND keyword LIKE ?{}", params.len() + 1));
+        params.push(Box::new(format!("%{}%", search)));
+    }
+    for (column, values) in [
+        ("traffic_level", &filter.traffic_levels),
+        ("relevance_level", &filter.relevance_levels),
+        ("primary_category", &filter.primary_categories),
+    ] {
+        if values.is_empty() {
+            continue;
+        }
+        let placeholders: Vec<String> = (0..values.len()).map(|i| format!("?{}", params.len() + 1 + i)).collect();
+        sql.push_str(&format!(" AND {} IN ({})", column, placeholders.join(",")));
+        for value in values {
+            params.push(Box::new(value.clone()));
+        }
+    }
+    let start = params.len() + 1;
+    if let Some(condition) = brand_type_condition("brand_type", &filter.brand_types, |i| format!("?{}", start + i)) {
+        sql.push_str(&format!(" AND {}", condition));
+        for t in filter.brand_types.iter().filter(|t| t.as_str() != "none") {
+            params.push(Box::new(t.clone()));
+        }
+    }
+    if let Some(min) = filter.min_search_volume {
+        sql.push_str(&format!(" AND avg_search_volume >= ?{}", params.len() + 1));
+        params.push(Box::new(min));
+    }
+    sql.push_str(" ORDER BY avg_search_volume DESC");
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    let mut stmt = conn.prepare(&sql)?;
+    let keywords = stmt
+        .query_map(params_refs.as_slice(), |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(keywords)
+}
+
+// 词库筛选 × ASIN × 站点批量添加监控，已存在的组合跳过；dry_run 只统计不写入
+pub fn bulk_add_monitoring_from_keyword_data(
+    product_id: i64,
+    filter: &KeywordDataFilter,
+    asins: &[String],
+    countries: &[String],
+    priority: Option<String>,
+    dry_run: bool,
+) -> Result<BulkMonitoringResult> {
+    let conn = get_db().lock();
+    let keywords = filter_keyword_data(&conn, product_id, filter)?;
+    // 去空、去重、统一大写
+    let normalize = |values: &[String]| -> Vec<String> {
+        values
+            .iter()
+            .map(|v| v.trim().to_uppercase())
+            .filter(|v| !v.is_empty())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    };
+    let (asins, countries) = (normalize(asins), normalize(countries));
+    let priority = priority.unwrap_or_else(|| "medium".to_string());
+    let candidates = keywords.len() * asins.len() * countries.len();
+
+    conn.execute("BEGIN TRANSACTION", [])?;
+    let result = (|| -> Result<usize> {
+        let mut exists = conn.prepare(
+            "SELECT 1 FROM keyword_monitoring
+             WHERE keyword = ?1 AND asin = ?2 AND country = ?3 AND product_id = ?4 AND location = ''"
+        )?;
+        let mut insert = conn.prepare(
+            "INSERT OR IGNORE INTO keyword_monitoring (product_id, keyword, asin, country, priority)
+             VALUES (?1, ?2, ?3, ?4, ?5)"
+        )?;
+        let mut created = 0;
+        for keyword in &keywords {
+            for asin in &asins {
+                for country in &countries {
+                    created += if dry_run {
+                        usize::from(!exists.exists(rusqlite::params![keyword, asin, country, product_id])?)
+                    } else {
+                        insert.execute(rusqlite::params![product_id, keyword, asin, country, priority])?
+                    };
+                }
+            }
+        }
+        Ok(created)
+    })();
+
+    match result {
+        Ok(created) => {
+            conn.execute(if dry_run { "ROLLBACK" } else { "COMMIT" }, [])?;
+            Ok(BulkMonitoringResult {
+                keyword_count: keywords.len(),
+                candidates,
+                created,
+                existing: candidates - created,
+            })
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
+}
+
+// 监控列表导入/导出的一行
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MonitoringTransferRow {
+    pub keyword: String,
+    pub asin: String,
+    pub country: String,
+    pub priority: String,
+    pub tags: Vec<String>,
+    pub is_active: bool,
+    pub max_pages: Option<i64>,
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MonitoringImportResult {
+    pub created: usize,
+    pub updated: usize,
+    pub errors: Vec<String>,    // 解析失败的行（行号 + 原因）
+}
+
+pub fn get_monitoring_transfer_rows(product_id: i64) -> Result<Vec<MonitoringTransferRow>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(
+        "SELECT keyword, asin, country, priority, tags, is_active, max_pages, location
+         FROM keyword_monitoring WHERE product_id = ?1
+         ORDER BY country, keyword, asin, location"
+    )?;
+    let rows = stmt
+        .query_map([product_id], |row| {
+            let tags: Option<String> = row.get(4)?;
+            Ok(MonitoringTransferRow {
+                keyword: row.get(0)?,
+                asin: row.get(1)?,
+                country: row.get(2)?,
+                priority: row.get::<_, Option<String>>(3)?.unwrap_or_else(|| "medium".to_string()),
+                tags: tags.and_then(|t| serde_json::from_str(&t).ok()).unwrap_or_default(),
+                is_active: row.get(5)?,
+                max_pages: row.get(6)?,
+                location: row.get::<_, Option<String>>(7)?.filter(|l| !l.is_empty()),
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+// 导入监控列表：已存在的组合（含邮编）更新优先级、标签、状态和检测页数
+pub fn import_monitoring_rows(product_id: i64, rows: &[MonitoringTransferRow]) -> Result<(usize, usize)> {
+    let conn = get_db().lock();
+    conn.execute("BEGIN TRANSACTION", [])?;
+
+    let result = (|| -> Result<(usize, usize)> {
+        let mut exists = conn.prepare(
+            "SELECT 1 FROM keyword_monitoring
+             WHERE keyword = ?1 AND asin = ?2 AND country = ?3 AND product_id = ?4 AND location = ?5"
+        )?;
+        let mut upsert = conn.prepare(
+            "INSERT INTO keyword_monitoring (product_id, keyword, asin, country, priority, tags, is_active, max_pages, location)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT(keyword, asin, country, product_id, location) DO UPDATE SET
+                priority = excluded.priority,
+                tags = excluded.tags,
+                is_active = excluded.is_active,
+                max_pages = excluded.max_pages"
+        )?;
+        let (mut created, mut updated) = (0, 0);
+        for row in rows {
+            let location = row.location.clone().unwrap_or_default();
+            if exists.exists(rusqlite::params![row.keyword, row.asin, row.country, product_id, location])? {
+                updated += 1;
+            } else {
+                created += 1;
+            }
+            let tags = (!row.tags.is_empty()).then(|| serde_json::to_string(&row.tags).unwrap_or_default());
+            upsert.execute(rusqlite::params![
+                product_id, row.keyword, row.asin, row.country, row.priority, tags, row.is_active, row.max_pages, location
+            ])?;
+        }
+        Ok((created, updated))
+    })();
+
+    match result {
+        Ok(counts) => {
+            conn.execute("COMMIT", [])?;
+            Ok(counts)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
+}
+
+// 获取关键词监控列表
+pub fn get_keyword_monitoring_list(
+    product_id: i64,
+    country: Option<String>,
+    priority: Option<String>,
+    is_active: Option<bool>,
+    search: Option<String>,
+    sort_by: Option<String>,
+    sort_order: Option<String>,
+    page: i64,
+    page_size: i64,
+) -> Result<(Vec<KeywordMonitoring>, i64)> {
+    let conn = get_db().lock();
+
+    let mut sql = format!("SELECT {} FROM keyword_monitoring WHERE product_id = ?1", KEYWORD_MONITORING_COLUMNS);
+
+    let mut count_sql = String::from("SELECT COUNT(*) FROM keyword_monitoring WHERE product_id = ?1");
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(product_id)];
+    let mut param_index = 2;
+
+    // 筛选条件
+    if let Some(ref c) = country {
+        let condition = format!(" AND country = ?{}", param_index);
+        sql.push_str(&condition);
+        count_sql.push_str(&condition);
+        params.push(Box::new(c.clone()));
+        param_index += 1;
+    }
+
+    if let Some(ref p) = priority {
+        let condition = format!(" AND priority = ?{}", param_index);
+        sql.push_str(&condition);
+        count_sql.push_str(&condition);
+        params.push(Box::new(p.clone()));
+        param_index += 1;
+    }
+
+    if let Some(active) = is_active {
+        let condition = format!(" AND is_active = ?{}", param_index);
+        sql.push_str(&condition);
+        count_sql.push_str(&condition);
+        params.push(Box::new(if active { 1i64 } else { 0i64 }));
+        param_index += 1;
+    }
+
+    if let Some(ref s) = search {
+        if !s.is_empty() {
+            let condition = format!(" AND (keyword LIKE ?{} OR asin LIKE ?{})", param_index, param_index);
+            sql.push_str(&condition);
+            count_sql.push_str(&condition);
+            params.push(Box::new(format!("%{}%", s)));
+            // param_index += 1;  // 同一个参数用两次
+        }
+    }
+
+    // 排序
+    let sort_column = match sort_by.as_deref() {
+        Some("keyword") => "keyword",
+        Some("latest_organic_rank") => "latest_organic_rank",
+        Some("latest_sponsored_rank") => "latest_sponsored_rank",
+        Some("last_checked") => "last_checked",
+        Some("priority") => "priority",
+        _ => "created_at",
+    };
+    let order = match sort_order.as_deref() {
+        Some("asc") => "ASC",
+        _ => "DESC",
+    };
+    sql.push_str(&format!(" ORDER BY {} {}", sort_column, order));
+
+    // 分页
+    sql.push_str(&format!(" LIMIT {} OFFSET {}", page_size, (page - 1) * page_size));
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+
+    let mut stmt = conn.prepare(&sql)?;
+    let data = stmt
+        .query_map(params_refs.as_slice(), row_to_monitoring)?
+        .collect::<Result<Vec<_>>>()?;
+
+    // 获取总数
+    let count_params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    let total: i64 = conn.query_row(&count_sql, count_params_refs.as_slice(), |row| row.get(0))?;
+
+    Ok((data, total))
 }
 
 // 更新关键词监控
 pub fn update_keyword_monitoring(
     id: i64,
-    priority: Option<String>,
-    is_active: Option<bool>,
+    priority: Option<String>,
+    is_active: Option<bool>,
+) -> Result<()> {
+    let conn = get_db().lock();
+
+    if let Some(p) = priority {
+        conn.execute(
+            "UPDATE keyword_monitoring SET priority = ?1 WHERE id = ?2",
+            rusqlite::params![p, id],
+        )?;
+    }
+
+    if let Some(active) = is_active {
+        conn.execute(
+            "UPDATE keyword_monitoring SET is_active = ?1 WHERE id = ?2",
+            rusqlite::params![if active { 1 } else { 0 }, id],
+        )?;
+        // 手动恢复被自动暂停的关键词时重新计数
+        if active {
+            conn.execute(
+                "UPDATE keyword_monitoring SET consecutive_failures = 0, auto_paused_at = NULL WHERE id = ?1",
+                [id],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+// 批量设置关键词的检测页数（None 表示使用全局设置）
+pub fn set_monitoring_max_pages(ids: &[i64], max_pages: Option<i64>) -> Result<i64> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare("UPDATE keyword_monitoring SET max_pages = ?1 WHERE id = ?2")?;
+    let mut affected = 0;
+    for id in ids {
+        affected += stmt.execute(rusqlite::params![max_pages, id])? as i64;
+    }
+    Ok(affected)
+}
+
+// 删除关键词监控（排名历史通过 CASCADE 自动删除）
+pub fn delete_keyword_monitoring(id: i64) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute("DELETE FROM keyword_monitoring WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+// 批量删除关键词监控
+pub fn batch_delete_keyword_monitoring(ids: Vec<i64>) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute("BEGIN TRANSACTION", [])?;
+
+    let result = (|| {
+        for id in ids {
+            conn.execute("DELETE FROM keyword_monitoring WHERE id = ?1", [id])?;
+        }
+        Ok::<(), rusqlite::Error>(())
+    })();
+
+    match result {
+        Ok(_) => {
+            conn.execute("COMMIT", [])?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
+}
+
+// 更新排名结果，历史记录保存检测使用的配送邮编
+pub fn update_ranking_result(
+    monitoring_id: i64,
+    location: Option<&str>,
+    organic_rank: Option<i64>,
+    organic_page: Option<i64>,
+    organic_position: Option<i64>,
+    sponsored_rank: Option<i64>,
+    sponsored_page: Option<i64>,
+    image_url: Option<String>,
+    price: Option<String>,
+    reviews_count: Option<i64>,
+    rating: Option<f64>,
+) -> Result<()> {
+    // 爬虫没有返回绝对位置时，第 1 页的页内排名就是绝对位置
+    let organic_position = organic_position.or(organic_rank.filter(|_| organic_page.unwrap_or(1) == 1));
+    let conn = get_db().lock();
+    conn.execute("BEGIN TRANSACTION", [])?;
+
+    let result = (|| {
+        // 写入前的状态，用于评估告警规则
+        let before = conn.query_row(
+            "SELECT latest_organic_rank, latest_organic_page, latest_organic_position,
+                    latest_sponsored_rank, price, rating, last_checked
+             FROM keyword_monitoring WHERE id = ?1",
+            [monitoring_id],
+            |row| {
+                Ok(crate::alerts::CheckSnapshot {
+                    organic_rank: row.get(0)?,
+                    organic_page: row.get(1)?,
+                    organic_position: row.get(2)?,
+                    sponsored_rank: row.get(3)?,
+                    price: row.get(4)?,
+                    rating: row.get(5)?,
+                    checked: row.get::<_, Option<String>>(6)?.is_some(),
+                })
+            },
+        ).unwrap_or_default();
+
+        // 如果价格为空，尝试从同 ASIN+country 的其他记录中获取
+        let final_price = if price.is_none() {
+            // 先获取当前记录的 asin 和 country
+            let asin_country: Option<(String, String)> = conn.query_row(
+                "SELECT asin, country FROM keyword_monitoring WHERE id = ?1",
+                [monitoring_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).ok();
+
+            if let Some((asin, country)) = asin_country {
+                // 从同 asin+country 的其他记录中查找最近有价格的
+                conn.query_row(
+                    "SELECT price FROM keyword_monitoring
+                     WHERE asin = ?1 AND country = ?2 AND price IS NOT NULL AND price != ''
+                     ORDER BY last_checked DESC LIMIT 1",
+                    rusqlite::params![asin, country],
+                    |row| row.get(0),
+                ).ok()
+            } else {
+                None
+            }
+        } else {
+            price
+        };
+
+        // 同样处理 image_url, reviews_count, rating
+        let (final_image_url, final_reviews_count, final_rating) = if image_url.is_none() || reviews_count.is_none() || rating.is_none() {
+            let asin_country: Option<(String, String)> = conn.query_row(
+                "SELECT asin, country FROM keyword_monitoring WHERE id = ?1",
+                [monitoring_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).ok();
+
+            if let Some((asin, country)) = asin_country {
+                let fallback: Option<(Option<String>, Option<i64>, Option<f64>)> = conn.query_row(
+                    "SELECT image_url, reviews_count, rating FROM keyword_monitoring
+                     WHERE asin = ?1 AND country = ?2 AND (image_url IS NOT NULL OR reviews_count IS NOT NULL OR rating IS NOT NULL)
+                     ORDER BY last_checked DESC LIMIT 1",
+                    rusqlite::params![asin, country],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                ).ok();
+
+                if let Some((fb_img, fb_reviews, fb_rating)) = fallback {
+                    (
+                        image_url.or(fb_img),
+                        reviews_count.or(fb_reviews),
+                        rating.or(fb_rating),
+                    )
+                } else {
+                    (image_url, reviews_count, rating)
+                }
+            } else {
+                (image_url, reviews_count, rating)
+            }
+        } else {
+            (image_url, reviews_count, rating)
+        };
+
+        // 更新最新排名
+        conn.execute(
+            "UPDATE keyword_monitoring SET
+                latest_organic_rank = ?1, latest_organic_page = ?2,
+                latest_sponsored_rank = ?3, latest_sponsored_page = ?4,
+                image_url = ?5, price = ?6, reviews_count = ?7, rating = ?8,
+                last_checked = datetime('now'), latest_organic_position = ?10
+             WHERE id = ?9",
+            rusqlite::params![
+                organic_rank, organic_page,
+                sponsored_rank, sponsored_page,
+                final_image_url, final_price, final_reviews_count, final_rating,
+                monitoring_id, organic_position
+            ],
+        )?;
+
+        // 插入历史记录（使用北京时间的日期）
+        conn.execute(
+            "INSERT INTO keyword_ranking_history
+                (monitoring_id, check_date, organic_rank, organic_page, organic_position, sponsored_rank, sponsored_page, location)
+             VALUES (?1, date('now', '+8 hours'), ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![monitoring_id, organic_rank, organic_page, organic_position, sponsored_rank, sponsored_page, location],
+        )?;
+
+        // 评估告警规则（失败不影响排名写入）
+        let after = crate::alerts::CheckSnapshot {
+            checked: true,
+            organic_rank,
+            organic_page,
+            organic_position,
+            sponsored_rank,
+            price: final_price,
+            rating: final_rating,
+        };
+        if let Err(e) = evaluate_alert_rules(&conn, monitoring_id, &before, &after) {
+            println!("[Alerts] 评估告警规则失败 (id={}): {}", monitoring_id, e);
+        }
+
+        Ok::<(), rusqlite::Error>(())
+    })();
+
+    match result {
+        Ok(_) => {
+            conn.execute("COMMIT", [])?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
+    }
+}
+
+// 获取排名历史（按日期和配送邮编聚合，每天每个邮编只取最佳排名）
+// 原始检测、日汇总、周汇总三层数据合并查询，范围超过 WEEKLY_RESOLUTION_DAYS 时按周聚合
+// 不同邮编的排名不可比，分别返回；未记录邮编的旧数据 location 为 None
+pub fn get_ranking_history(monitoring_id: i64, days: i64) -> Result<Vec<RankingHistory>> {
+    let conn = get_db().lock();
+    let days_str = format!("-{} days", days);
+    let (bucket, resolution) = if days > WEEKLY_RESOLUTION_DAYS {
+        ("date(period, 'weekday 0', '-6 days')", "week")
+    } else {
+        ("period", "day")
+    };
+
+    // 最佳排名取周期内页码最小、页内排名最小的那次检测
+    let sql = format!(
+        "WITH points AS ({}),
+         bounded AS (SELECT *, {} AS bucket FROM points WHERE period >= date(date('now', '+8 hours', ?2), ?3)),
+         ranked AS (SELECT *, ROW_NUMBER() OVER (PARTITION BY bucket, location {}) AS best FROM bounded)
+         SELECT
+            COALESCE(MIN(id), 0),
+            bucket,
+            SUM(check_count),
+            MIN(organic_best),
+            SUM(organic_sum) / NULLIF(SUM(organic_count), 0),
+            MAX(organic_worst),
+            MIN(sponsored_best),
+            SUM(sponsored_sum) / NULLIF(SUM(sponsored_count), 0),
+            MAX(sponsored_worst),
+            MIN(sponsored_best_page),
+            MAX(last_checked_at),
+            NULLIF(location, ''),
+            MAX(CASE WHEN best = 1 THEN organic_best_page END),
+            MAX(CASE WHEN best = 1 THEN organic_best_rank END)
+         FROM ranked
+         GROUP BY bucket, location
+         ORDER BY bucket ASC, location",
+        RANKING_POINTS_SQL, bucket, ORGANIC_BEST_ORDER
+    );
+    // 周汇总的 period 是周一，范围起点向前放宽 6 天
+    let mut stmt = conn.prepare(&sql)?;
+    let history = stmt
+        .query_map(rusqlite::params![monitoring_id, days_str, if resolution == "week" { "-6 days" } else { "+0 days" }], |row| {
+            Ok(RankingHistory {
+                id: row.get(0)?,
+                monitoring_id,
+                check_date: row.get(1)?,
+                organic_rank: row.get(13)?,
+                organic_page: row.get(12)?,
+                sponsored_rank: row.get(6)?,
+                sponsored_page: row.get(9)?,
+                organic_position: row.get(3)?,
+                organic_avg: row.get(4)?,
+                organic_worst: row.get(5)?,
+                sponsored_avg: row.get(7)?,
+                sponsored_worst: row.get(8)?,
+                check_count: row.get(2)?,
+                resolution: resolution.to_string(),
+                checked_at: row.get::<_, Option<String>>(10)?.unwrap_or_default(),
+                location: row.get(11)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(history)
+}
+
+// 获取上一次的自然排名（页码、页内排名和绝对位置，用于计算排名变化）
+pub fn get_previous_ranking(monitoring_id: i64) -> Result<Option<crate::alerts::RankPosition>> {
+    let conn = get_db().lock();
+
+    let result = conn.query_row(
+        "SELECT organic_rank, organic_page, organic_position FROM keyword_ranking_history
+         WHERE monitoring_id = ?1
+         ORDER BY checked_at DESC, id DESC
+         LIMIT 1",
+        [monitoring_id],
+        |row| Ok(crate::alerts::RankPosition::new(row.get(0)?, row.get(1)?, row.get(2)?)),
+    );
+
+    match result {
+        Ok(rank) => Ok(rank),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// 保存竞品快照（location 为检测使用的配送邮编）
+pub fn save_ranking_snapshot(
+    keyword: &str,
+    country: &str,
+    location: Option<&str>,
+    organic_top_50: Option<String>,
+    sponsored_top_20: Option<String>,
+) -> Result<()> {
+    let conn = get_db().lock();
+
+    conn.execute(
+        "INSERT OR REPLACE INTO ranking_snapshots
+            (keyword, country, snapshot_date, organic_top_50, sponsored_top_20, location)
+         VALUES (?1, ?2, date('now', '+8 hours'), ?3, ?4, ?5)",
+        rusqlite::params![keyword, country, organic_top_50, sponsored_top_20, location],
+    )?;
+
+    Ok(())
+}
+
+// 获取竞品快照
+pub fn get_ranking_snapshots(keyword: &str, country: &str, days: i64) -> Result<Vec<RankingSnapshot>> {
+    let conn = get_db().lock();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, keyword, country, snapshot_date, organic_top_50, sponsored_top_20, created_at, location
+         FROM ranking_snapshots
+         WHERE keyword = ?1 AND country = ?2 AND snapshot_date >= date('now', '+8 hours', ?3)
+         ORDER BY snapshot_date DESC"
+    )?;
+
+    let days_str = format!("-{} days", days);
+    let snapshots = stmt
+        .query_map(rusqlite::params![keyword, country, days_str], |row| {
+            Ok(RankingSnapshot {
+                id: row.get(0)?,
+                keyword: row.get(1)?,
+                country: row.get(2)?,
+                snapshot_date: row.get(3)?,
+                organic_top_50: row.get(4)?,
+                sponsored_top_20: row.get(5)?,
+                created_at: row.get(6)?,
+                location: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(snapshots)
+}
+
+// 搜索份额分析的数据源：产品在某站点监控的关键词、我们的 ASIN、搜索量和近 N 天快照
+pub fn get_share_of_search_source(
+    product_id: i64,
+    country: &str,
+    days: i64,
+) -> Result<crate::share_of_search::ShareOfSearchSource> {
+    let conn = get_db().lock();
+    let mut source = crate::share_of_search::ShareOfSearchSource::default();
+
+    let mut stmt = conn.prepare(
+        "SELECT km.keyword, km.asin,
+                (SELECT kd.avg_search_volume FROM keyword_data kd
+                 WHERE kd.product_id = km.product_id AND LOWER(kd.keyword) = LOWER(km.keyword)
+                 LIMIT 1)
+         FROM keyword_monitoring km
+         WHERE km.product_id = ?1 AND km.country = ?2"
+    )?;
+    let rows = stmt
+        .query_map(rusqlite::params![product_id, country], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<f64>>(2)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    for (keyword, asin, volume) in rows {
+        if let Some(volume) = volume {
+            source.volumes.insert(keyword.clone(), volume);
+        }
+        source.our_asins.entry(keyword).or_default().insert(asin.to_uppercase());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT keyword, snapshot_date, organic_top_50, sponsored_top_20
+         FROM ranking_snapshots
+         WHERE country = ?1 AND snapshot_date >= date('now', '+8 hours', ?2)
+         ORDER BY snapshot_date"
+    )?;
+    let days_str = format!("-{} days", days);
+    let parse = |json: Option<String>| -> Vec<String> {
+        json.and_then(|j| serde_json::from_str::<Vec<String>>(&j).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|a| a.to_uppercase())
+            .collect()
+    };
+    let snapshots = stmt
+        .query_map(rusqlite::params![country, days_str], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    source.snapshots = snapshots
+        .into_iter()
+        .filter(|(keyword, ..)| source.our_asins.contains_key(keyword))
+        .map(|(keyword, date, organic, sponsored)| crate::share_of_search::KeywordSnapshot {
+            keyword,
+            date,
+            organic: parse(organic),
+            sponsored: parse(sponsored),
+        })
+        .collect();
+
+    Ok(source)
+}
+
+// 获取监控统计
+pub fn get_monitoring_stats(product_id: i64) -> Result<MonitoringStats> {
+    let conn = get_db().lock();
+
+    let total: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM keyword_monitoring WHERE product_id = ?1",
+        [product_id],
+        |row| row.get(0),
+    )?;
+
+    let active: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM keyword_monitoring WHERE product_id = ?1 AND is_active = 1",
+        [product_id],
+        |row| row.get(0),
+    )?;
+
+    let top10_organic: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM keyword_monitoring
+         WHERE product_id = ?1 AND latest_organic_rank IS NOT NULL AND latest_organic_rank <= 10",
+        [product_id],
+        |row| row.get(0),
+    )?;
+
+    let top30_organic: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM keyword_monitoring
+         WHERE product_id = ?1 AND latest_organic_rank IS NOT NULL AND latest_organic_rank <= 30",
+        [product_id],
+        |row| row.get(0),
+    )?;
+
+    let with_sponsored: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM keyword_monitoring
+         WHERE product_id = ?1 AND latest_sponsored_rank IS NOT NULL",
+        [product_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(MonitoringStats {
+        total,
+        active,
+        top10_organic,
+        top30_organic,
+        with_sponsored,
+    })
+}
+
+// 获取单个监控记录
+pub fn get_keyword_monitoring_by_id(id: i64) -> Result<Option<KeywordMonitoring>> {
+    let conn = get_db().lock();
+
+    let result = conn.query_row(
+        &format!("SELECT {} FROM keyword_monitoring WHERE id = ?1", KEYWORD_MONITORING_COLUMNS),
+        [id],
+        row_to_monitoring,
+    );
+
+    match result {
+        Ok(m) => Ok(Some(m)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+// 获取有活跃监控项的站点列表
+pub fn get_active_monitoring_countries() -> Result<Vec<String>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT country FROM keyword_monitoring WHERE is_active = 1 ORDER BY country",
+    )?;
+    let countries = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<String>>>()?;
+    Ok(countries)
+}
+
+// 获取待检测的监控记录（活跃且未检测或超过指定时间未检测）
+// hours_since_last_check = 0 表示无时间限制，返回所有活跃的监控项
+pub fn get_pending_monitoring_checks(product_id: i64, hours_since_last_check: i64) -> Result<Vec<KeywordMonitoring>> {
+    let conn = get_db().lock();
+
+    // 如果 hours_since_last_check 为 0，则不限制时间，返回所有活跃监控项
+    let sql = if hours_since_last_check == 0 {
+        format!(
+            "SELECT {}
+             FROM keyword_monitoring
+             WHERE product_id = ?1 AND is_active = 1
+             ORDER BY
+               CASE priority
+                 WHEN 'high' THEN 1
+                 WHEN 'medium' THEN 2
+                 ELSE 3
+               END,
+               last_checked ASC NULLS FIRST",
+            KEYWORD_MONITORING_COLUMNS
+        )
+    } else {
+        let hours_str = format!("-{} hours", hours_since_last_check);
+        format!(
+            "SELECT {}
+             FROM keyword_monitoring
+             WHERE product_id = ?1 AND is_active = 1
+               AND (last_checked IS NULL OR last_checked < datetime('now', '{}'))
+             ORDER BY
+               CASE priority
+                 WHEN 'high' THEN 1
+                 WHEN 'medium' THEN 2
+                 ELSE 3
+               END,
+               last_checked ASC NULLS FIRST",
+            KEYWORD_MONITORING_COLUMNS, hours_str
+        )
+    };
+
+    let mut stmt = conn.prepare(&sql)?;
+
+    let data = stmt
+        .query_map(rusqlite::params![product_id], row_to_monitoring)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(data)
+}
+
+// 获取到期待检测的监控记录（全部产品）：按优先级使用不同的检测间隔（小时）
+// 结果按优先级、最久未检测排序，时间不够时可从末尾（低优先级）开始舍弃
+pub fn get_due_monitoring_checks(high_hours: i64, medium_hours: i64, low_hours: i64) -> Result<Vec<KeywordMonitoring>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM keyword_monitoring
+         WHERE is_active = 1
+           AND (last_checked IS NULL OR last_checked < datetime('now', '-' ||
+                CASE priority WHEN 'high' THEN ?1 WHEN 'medium' THEN ?2 ELSE ?3 END || ' hours'))
+         ORDER BY
+           CASE priority
+             WHEN 'high' THEN 1
+             WHEN 'medium' THEN 2
+             ELSE 3
+           END,
+           last_checked ASC NULLS FIRST",
+        KEYWORD_MONITORING_COLUMNS
+    ))?;
+
+    let data = stmt
+        .query_map(rusqlite::params![high_hours, medium_hours, low_hours], row_to_monitoring)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(data)
+}
+
+// 根据ID列表获取监控记录（用于选中检测）
+pub fn get_monitoring_by_ids(ids: &[i64]) -> Result<Vec<KeywordMonitoring>> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let conn = get_db().lock();
+    let placeholders: Vec<String> = ids.iter().map(|_| "?".to_string()).collect();
+    let sql = format!(
+        "SELECT {}
+         FROM keyword_monitoring
+         WHERE id IN ({})
+         ORDER BY
+           CASE priority
+             WHEN 'high' THEN 1
+             WHEN 'medium' THEN 2
+             ELSE 3
+           END",
+        KEYWORD_MONITORING_COLUMNS,
+        placeholders.join(",")
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let params: Vec<&dyn rusqlite::ToSql> = ids.iter().map(|id| id as &dyn rusqlite::ToSql).collect();
+
+    let data = stmt
+        .query_map(params.as_slice(), row_to_monitoring)?
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(data)
+}
+
+// 更新关键词监控标签
+pub fn update_keyword_monitoring_tags(id: i64, tags: Option<String>) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE keyword_monitoring SET tags = ?1 WHERE id = ?2",
+        rusqlite::params![tags, id],
+    )?;
+    Ok(())
+}
+
+// 批量获取监控项的迷你图数据（最近N天的排名，按日期聚合取最佳）
+pub fn get_monitoring_sparklines(product_id: i64, days: i64) -> Result<Vec<MonitoringSparkline>> {
+    let conn = get_db().lock();
+    let days_str = format!("-{} days", days);
+
+    // 按日期分组，每天取最佳排名（最小值）
+    let mut stmt = conn.prepare(
+        "SELECT h.monitoring_id, h.check_date,
+                MIN(h.organic_rank) as best_organic_rank,
+                MIN(h.sponsored_rank) as best_sponsored_rank
+         FROM keyword_ranking_history h
+         JOIN keyword_monitoring m ON h.monitoring_id = m.id
+         WHERE m.product_id = ?1 AND h.check_date >= date('now', '+8 hours', ?2)
+         GROUP BY h.monitoring_id, h.check_date
+         ORDER BY h.monitoring_id, h.check_date ASC"
+    )?;
+
+    let rows = stmt.query_map(rusqlite::params![product_id, days_str], |row| {
+        Ok((
+            row.get::<_, i64>(0)?,         // monitoring_id
+            row.get::<_, String>(1)?,      // check_date
+            row.get::<_, Option<i64>>(2)?, // organic_rank (best)
+            row.get::<_, Option<i64>>(3)?, // sponsored_rank (best)
+        ))
+    })?;
+
+    // 按 monitoring_id 分组，同时存储 organic 和 sponsored
+    let mut sparklines_map: std::collections::HashMap<i64, (Vec<Option<i64>>, Vec<Option<i64>>)> =
+        std::collections::HashMap::new();
+
+    for row in rows {
+        let (monitoring_id, _check_date, organic_rank, sponsored_rank) = row?;
+        let entry = sparklines_map
+            .entry(monitoring_id)
+            .or_insert_with(|| (Vec::new(), Vec::new()));
+        entry.0.push(organic_rank);
+        entry.1.push(sponsored_rank);
+    }
+
+    // 转换为 Vec<MonitoringSparkline>
+    let sparklines: Vec<MonitoringSparkline> = sparklines_map
+        .into_iter()
+        .map(|(monitoring_id, (organic_ranks, sponsored_ranks))| MonitoringSparkline {
+            monitoring_id,
+            organic_ranks,
+            sponsored_ranks,
+        })
+        .collect();
+
+    Ok(sparklines)
+}
+
+// ============ 市场调研监控相关 ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BsrSnapshot {
+    pub id: i64,
+    pub marketplace: String,
+    pub category_id: String,
+    pub category_name: Option<String>,
+    pub snapshot_date: String,
+    pub products_json: String,
+    pub product_count: i64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketResearchTask {
+    pub id: i64,
+    pub name: String,
+    pub marketplace: String,
+    pub category_id: String,
+    pub category_name: Option<String>,
+    pub ai_provider: String,
+    pub ai_model: Option<String>,   // 具体模型名称
+    pub schedule_type: String,      // daily / weekly
+    pub schedule_days: Option<String>,  // JSON array like [1,3,5]
+    pub schedule_time: String,      // HH:MM
+    pub is_enabled: bool,
+    pub last_run_at: Option<String>,
+    pub last_run_status: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketResearchRun {
+    pub id: i64,
+    pub task_id: i64,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub status: String,             // running / completed / failed
+    pub report_summary: Option<String>,
+    pub report_content: Option<String>,
+    pub snapshot_id: Option<i64>,
+    pub error_message: Option<String>,
+    pub created_at: String,
+}
+
+// ============ 定时任务记录相关 ============
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchedulerTaskLog {
+    pub id: i64,
+    pub started_at: String,
+    pub ended_at: Option<String>,
+    pub status: String,
+    pub total_keywords: i64,
+    pub success_count: i64,
+    pub failed_count: i64,
+    pub trigger_type: String,
+    pub error_message: Option<String>,
+}
+
+// 创建新的任务记录（开始任务时调用）
+pub fn create_task_log(trigger_type: &str, total_keywords: i64) -> Result<i64> {
+    let conn = get_db().lock();
+    conn.execute(
+        "INSERT INTO scheduler_task_logs (started_at, status, total_keywords, trigger_type)
+         VALUES (datetime('now'), 'running', ?1, ?2)",
+        rusqlite::params![total_keywords, trigger_type],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+// 更新任务进度
+pub fn update_task_progress(task_id: i64, success_count: i64, failed_count: i64) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE scheduler_task_logs SET success_count = ?1, failed_count = ?2 WHERE id = ?3",
+        rusqlite::params![success_count, failed_count, task_id],
+    )?;
+    Ok(())
+}
+
+// 完成任务记录
+pub fn complete_task_log(task_id: i64, success_count: i64, failed_count: i64) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE scheduler_task_logs
+         SET ended_at = datetime('now'), status = 'completed',
+             success_count = ?1, failed_count = ?2
+         WHERE id = ?3",
+        rusqlite::params![success_count, failed_count, task_id],
+    )?;
+    Ok(())
+}
+
+// 记录任务备注（如时间不足跳过的关键词），不改变任务状态
+pub fn set_task_log_message(task_id: i64, message: &str) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE scheduler_task_logs SET error_message = ?1 WHERE id = ?2",
+        rusqlite::params![message, task_id],
+    )?;
+    Ok(())
+}
+
+// 标记任务失败
+pub fn fail_task_log(task_id: i64, error_message: &str) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE scheduler_task_logs
+         SET ended_at = datetime('now'), status = 'failed', error_message = ?1
+         WHERE id = ?2",
+        rusqlite::params![error_message, task_id],
+    )?;
+    Ok(())
+}
+
+// 获取任务记录列表（最近N条）
+pub fn get_task_logs(limit: i64) -> Result<Vec<SchedulerTaskLog>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(
+        "SELECT id, started_at, ended_at, status, total_keywords,
+                success_count, failed_count, trigger_type, error_message
+         FROM scheduler_task_logs
+         ORDER BY started_at DESC
+         LIMIT ?1"
+    )?;
+
+    let logs = stmt.query_map([limit], |row| {
+        Ok(SchedulerTaskLog {
+            id: row.get(0)?,
+            started_at: row.get(1)?,
+            ended_at: row.get(2)?,
+            status: row.get(3)?,
+            total_keywords: row.get(4)?,
+            success_count: row.get(5)?,
+            failed_count: row.get(6)?,
+            trigger_type: row.get(7)?,
+            error_message: row.get(8)?,
+        })
+    })?
+    .collect::<Result<Vec<_>>>()?;
+
+    Ok(logs)
+}
+
+// 获取正在运行的任务
+pub fn get_running_task() -> Result<Option<SchedulerTaskLog>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(
+        "SELECT id, started_at, ended_at, status, total_keywords,
+                success_count, failed_count, trigger_type, error_message
+         FROM scheduler_task_logs
+         WHERE status = 'running'
+         ORDER BY started_at DESC
+         LIMIT 1"
+    )?;
+
+    let mut rows = stmt.query([])?;
+    if let Some(row) = rows.next()? {
+        Ok(Some(SchedulerTaskLog {
+            id: row.get(0)?,
+            started_at: row.get(1)?,
+            ended_at: row.get(2)?,
+            status: row.get(3)?,
+            total_keywords: row.get(4)?,
+            success_count: row.get(5)?,
+            failed_count: row.get(6)?,
+            trigger_type: row.get(7)?,
+            error_message: row.get(8)?,
+        }))
+    } else {
+        Ok(None)
+    }
+}
+
+// ==================== 任务明细（每个关键词的检测结果） ====================
+
+fn init_task_item_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS scheduler_task_items (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id INTEGER NOT NULL,
+            monitoring_id INTEGER,
+            keyword TEXT NOT NULL,
+            asin TEXT NOT NULL,
+            country TEXT NOT NULL,
+            status TEXT NOT NULL,           -- success / warning / failed
+            failure_kind TEXT,              -- 与 ranking_check_failures.kind 相同
+            error TEXT,
+            warning TEXT,
+            organic_rank INTEGER,
+            organic_page INTEGER,
+            sponsored_rank INTEGER,
+            duration_ms INTEGER,
+            proxy TEXT,
+            pages_checked INTEGER,          -- 实际打开到第几页
+            checked_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (task_id) REFERENCES scheduler_task_logs(id) ON DELETE CASCADE,
+            FOREIGN KEY (monitoring_id) REFERENCES keyword_monitoring(id) ON DELETE SET NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_task_items_task ON scheduler_task_items(task_id);
+
+        -- 任务中爬虫子进程的日志、警告、错误和 stderr 输出
+        CREATE TABLE IF NOT EXISTS scheduler_task_messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_id INTEGER NOT NULL,
+            level TEXT NOT NULL,            -- info / warning / error / stderr
+            source TEXT NOT NULL,           -- 产生消息的脚本
+            message TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (task_id) REFERENCES scheduler_task_logs(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_task_messages_task ON scheduler_task_messages(task_id);
+        "
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskItem {
+    pub id: i64,
+    pub monitoring_id: Option<i64>,
+    pub keyword: String,
+    pub asin: String,
+    pub country: String,
+    pub status: String,
+    pub failure_kind: Option<String>,
+    pub error: Option<String>,
+    pub warning: Option<String>,
+    pub organic_rank: Option<i64>,
+    pub organic_page: Option<i64>,
+    pub sponsored_rank: Option<i64>,
+    pub duration_ms: Option<i64>,
+    pub proxy: Option<String>,
+    pub pages_checked: Option<i64>,
+    pub checked_at: String,
+}
+
+// 记录任务中一个监控项的检测结果，failure_kind 由 check_failures::record_outcome 分类得到
+pub fn add_task_item(
+    task_id: i64,
+    monitoring_id: i64,
+    result: &crate::crawler::RankingResult,
+    failure_kind: Option<&str>,
+) -> Result<()> {
+    let status = if result.error.is_some() {
+        "failed"
+    } else if result.warning.is_some() {
+        "warning"
+    } else {
+        "success"
+    };
+    let conn = get_db().lock();
+    conn.execute(
+        "INSERT INTO scheduler_task_items
+            (task_id, monitoring_id, keyword, asin, country, status, failure_kind, error, warning,
+             organic_rank, organic_page, sponsored_rank, duration_ms, proxy, pages_checked)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        rusqlite::params![
+            task_id,
+            monitoring_id,
+            result.keyword,
+            result.target_asin,
+            result.country,
+            status,
+            failure_kind,
+            result.error,
+            result.warning,
+            result.organic_rank,
+            result.organic_page,
+            result.sponsored_rank,
+            result.duration_ms,
+            result.proxy,
+            result.pages_checked,
+        ],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskMessage {
+    pub id: i64,
+    pub level: String,
+    pub source: String,
+    pub message: String,
+    pub created_at: String,
+}
+
+// 记录任务中爬虫子进程的一条消息
+pub fn add_task_message(task_id: i64, level: &str, source: &str, message: &str) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "INSERT INTO scheduler_task_messages (task_id, level, source, message) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![task_id, level, source, message],
+    )?;
+    Ok(())
+}
+
+// 按代理或站点分组的统计
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct TaskGroupStat {
+    pub key: String,
+    pub total: i64,
+    pub failed: i64,
+    pub duration_p50_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct TaskRunStats {
+    pub item_count: i64,
+    pub success_count: i64,
+    pub warning_count: i64,
+    pub failed_count: i64,
+    pub duration_avg_ms: Option<i64>,
+    pub duration_p50_ms: Option<i64>,
+    pub duration_p90_ms: Option<i64>,
+    pub duration_p95_ms: Option<i64>,
+    pub duration_max_ms: Option<i64>,
+    pub avg_pages_checked: Option<f64>,
+    pub failures_by_kind: Vec<(String, i64)>, // 次数多的在前
+    pub by_proxy: Vec<TaskGroupStat>,         // 未使用代理的记为 "直连"
+    pub by_country: Vec<TaskGroupStat>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskLogDetail {
+    pub log: SchedulerTaskLog,
+    pub items: Vec<TaskItem>,
+    pub stats: TaskRunStats,
+    pub messages: Vec<TaskMessage>,
+}
+
+// 最近秩法求百分位，sorted 须已升序
+fn percentile(sorted: &[i64], p: usize) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
+fn sorted_durations<'a>(items: impl Iterator<Item = &'a TaskItem>) -> Vec<i64> {
+    let mut durations: Vec<i64> = items.filter_map(|i| i.duration_ms).collect();
+    durations.sort_unstable();
+    durations
+}
+
+fn group_stats(items: &[TaskItem], key: impl Fn(&TaskItem) -> String) -> Vec<TaskGroupStat> {
+    let mut groups: std::collections::BTreeMap<String, Vec<&TaskItem>> = std::collections::BTreeMap::new();
+    for item in items {
+        groups.entry(key(item)).or_default().push(item);
+    }
+    let mut stats: Vec<TaskGroupStat> = groups
+        .into_iter()
+        .map(|(key, group)| TaskGroupStat {
+            key,
+            total: group.len() as i64,
+            failed: group.iter().filter(|i| i.status == "failed").count() as i64,
+            duration_p50_ms: percentile(&sorted_durations(group.into_iter()), 50),
+        })
+        .collect();
+    stats.sort_by(|a, b| b.failed.cmp(&a.failed).then(b.total.cmp(&a.total)));
+    stats
+}
+
+pub fn summarize_task_items(items: &[TaskItem]) -> TaskRunStats {
+    let count_status = |status: &str| items.iter().filter(|i| i.status == status).count() as i64;
+    let durations = sorted_durations(items.iter());
+    let pages: Vec<i64> = items.iter().filter_map(|i| i.pages_checked).collect();
+
+    let mut by_kind: std::collections::HashMap<String, i64> = std::collections::HashMap::new();
+    for kind in items.iter().filter_map(|i| i.failure_kind.clone()) {
+        *by_kind.entry(kind).or_default() += 1;
+    }
+    let mut failures_by_kind: Vec<(String, i64)> = by_kind.into_iter().collect();
+    failures_by_kind.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    TaskRunStats {
+        item_count: items.len() as i64,
+        success_count: count_status("success"),
+        warning_count: count_status("warning"),
+        failed_count: count_status("failed"),
+        duration_avg_ms: (!durations.is_empty())
+            .then(|| durations.iter().sum::<i64>() / durations.len() as i64),
+        duration_p50_ms: percentile(&durations, 50),
+        duration_p90_ms: percentile(&durations, 90),
+        duration_p95_ms: percentile(&durations, 95),
+        duration_max_ms: durations.last().copied(),
+        avg_pages_checked: (!pages.is_empty())
+            .then(|| pages.iter().sum::<i64>() as f64 / pages.len() as f64),
+        failures_by_kind,
+        by_proxy: group_stats(items, |i| i.proxy.clone().unwrap_or_else(|| "直连".to_string())),
+        by_country: group_stats(items, |i| i.country.clone()),
+    }
+}
+
+// 任务详情：任务记录 + 每个关键词的检测结果 + 统计，任务不存在返回 None
+pub fn get_task_log_detail(task_id: i64) -> Result<Option<TaskLogDetail>> {
+    let conn = get_db().lock();
+    let log = match conn.query_row(
+        "SELECT id, started_at, ended_at, status, total_keywords,
+                success_count, failed_count, trigger_type, error_message
+         FROM scheduler_task_logs
+         WHERE id = ?1",
+        [task_id],
+        |row| {
+            Ok(SchedulerTaskLog {
+                id: row.get(0)?,
+                started_at: row.get(1)?,
+                ended_at: row.get(2)?,
+                status: row.get(3)?,
+                total_keywords: row.get(4)?,
+                success_count: row.get(5)?,
+                failed_count: row.get(6)?,
+                trigger_type: row.get(7)?,
+                error_message: row.get(8)?,
+            })
+        },
+    ) {
+        Ok(log) => log,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT id, monitoring_id, keyword, asin, country, status, failure_kind, error, warning,
+                organic_rank, organic_page, sponsored_rank, duration_ms, proxy, pages_checked, checked_at
+         FROM scheduler_task_items
+         WHERE task_id = ?1
+         ORDER BY id"
+    )?;
+    let items = stmt
+        .query_map([task_id], |row| {
+            Ok(TaskItem {
+                id: row.get(0)?,
+                monitoring_id: row.get(1)?,
+                keyword: row.get(2)?,
+                asin: row.get(3)?,
+                country: row.get(4)?,
+                status: row.get(5)?,
+                failure_kind: row.get(6)?,
+                error: row.get(7)?,
+                warning: row.get(8)?,
+                organic_rank: row.get(9)?,
+                organic_page: row.get(10)?,
+                sponsored_rank: row.get(11)?,
+                duration_ms: row.get(12)?,
+                proxy: row.get(13)?,
+                pages_checked: row.get(14)?,
+                checked_at: row.get(15)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, level, source, message, created_at
+         FROM scheduler_task_messages
+         WHERE task_id = ?1
+         ORDER BY id"
+    )?;
+    let messages = stmt
+        .query_map([task_id], |row| {
+            Ok(TaskMessage {
+                id: row.get(0)?,
+                level: row.get(1)?,
+                source: row.get(2)?,
+                message: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    let stats = summarize_task_items(&items);
+    Ok(Some(TaskLogDetail { log, items, stats, messages }))
+}
+
+// ==================== 排名检测计划（Cron） ====================
+
+// 初始化排名检测计划表
+// product_id / priority 为空表示不限；时间均为 UTC（datetime('now') 格式）
+fn init_ranking_schedule_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS ranking_schedules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            product_id INTEGER,
+            priority TEXT CHECK(priority IS NULL OR priority IN ('high', 'medium', 'low')),
+            cron_expr TEXT NOT NULL,
+            timezone TEXT NOT NULL DEFAULT 'Asia/Shanghai',
+            catch_up INTEGER DEFAULT 1,
+            is_enabled INTEGER DEFAULT 1,
+            last_run_at TIMESTAMP,
+            last_run_status TEXT,
+            last_run_message TEXT,
+            next_run_at TIMESTAMP,
+            created_at TIMESTAMP DEFAULT (datetime('now')),
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+        );
+        ",
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RankingSchedule {
+    pub id: i64,
+    pub name: String,
+    pub product_id: Option<i64>,
+    pub priority: Option<String>,       // high/medium/low，空表示全部优先级
+    pub cron_expr: String,
+    pub timezone: String,
+    pub catch_up: bool,                 // 错过的计划（如电脑休眠）是否在唤醒后补跑
+    pub is_enabled: bool,
+    pub last_run_at: Option<String>,
+    pub last_run_status: Option<String>,  // completed, skipped, failed
+    pub last_run_message: Option<String>,
+    pub next_run_at: Option<String>,
+    pub created_at: String,
+}
+
+const RANKING_SCHEDULE_COLUMNS: &str = "id, name, product_id, priority, cron_expr, timezone, catch_up, is_enabled,
+    last_run_at, last_run_status, last_run_message, next_run_at, created_at";
+
+fn row_to_ranking_schedule(row: &rusqlite::Row) -> rusqlite::Result<RankingSchedule> {
+    Ok(RankingSchedule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        product_id: row.get(2)?,
+        priority: row.get(3)?,
+        cron_expr: row.get(4)?,
+        timezone: row.get(5)?,
+        catch_up: row.get::<_, i64>(6)? != 0,
+        is_enabled: row.get::<_, i64>(7)? != 0,
+        last_run_at: row.get(8)?,
+        last_run_status: row.get(9)?,
+        last_run_message: row.get(10)?,
+        next_run_at: row.get(11)?,
+        created_at: row.get(12)?,
+    })
+}
+
+pub fn get_ranking_schedules() -> Result<Vec<RankingSchedule>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM ranking_schedules ORDER BY id",
+        RANKING_SCHEDULE_COLUMNS
+    ))?;
+    let schedules = stmt
+        .query_map([], row_to_ranking_schedule)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(schedules)
+}
+
+// 新建 / 修改计划的参数
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RankingScheduleInput {
+    pub name: String,
+    pub product_id: Option<i64>,
+    pub priority: Option<String>,
+    pub cron_expr: String,
+    pub timezone: String,
+    pub catch_up: bool,
+    pub is_enabled: bool,
+}
+
+pub fn create_ranking_schedule(input: &RankingScheduleInput, next_run_at: Option<&str>) -> Result<i64> {
+    let conn = get_db().lock();
+    conn.execute(
+        "INSERT INTO ranking_schedules (name, product_id, priority, cron_expr, timezone, catch_up, is_enabled, next_run_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![
+            input.name,
+            input.product_id,
+            input.priority,
+            input.cron_expr,
+            input.timezone,
+            input.catch_up as i64,
+            input.is_enabled as i64,
+            next_run_at,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_ranking_schedule(id: i64, input: &RankingScheduleInput, next_run_at: Option<&str>) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE ranking_schedules
+         SET name = ?1, product_id = ?2, priority = ?3, cron_expr = ?4, timezone = ?5,
+             catch_up = ?6, is_enabled = ?7, next_run_at = ?8
+         WHERE id = ?9",
+        rusqlite::params![
+            input.name,
+            input.product_id,
+            input.priority,
+            input.cron_expr,
+            input.timezone,
+            input.catch_up as i64,
+            input.is_enabled as i64,
+            next_run_at,
+            id,
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn delete_ranking_schedule(id: i64) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute("DELETE FROM ranking_schedules WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+// 记录计划的执行情况（run_at / next_run_at 为 UTC）
+pub fn mark_ranking_schedule_run(
+    id: i64,
+    run_at: &str,
+    status: &str,
+    message: Option<&str>,
+    next_run_at: Option<&str>,
 ) -> Result<()> {
     let conn = get_db().lock();
+    conn.execute(
+        "UPDATE ranking_schedules
+         SET last_run_at = ?1, last_run_status = ?2, last_run_message = ?3, next_run_at = ?4
+         WHERE id = ?5",
+        rusqlite::params![run_at, status, message, next_run_at, id],
+    )?;
+    Ok(())
+}
+
+// 获取计划范围内的活跃监控项（按优先级、最久未检测排序）
+// skip_recent_minutes: 跳过最近 N 分钟内已检测过的（多个计划同时触发时避免重复检测）
+pub fn get_monitoring_for_schedule(
+    product_id: Option<i64>,
+    priority: Option<&str>,
+    skip_recent_minutes: i64,
+) -> Result<Vec<KeywordMonitoring>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM keyword_monitoring
+         WHERE is_active = 1
+           AND (?1 IS NULL OR product_id = ?1)
+           AND (?2 IS NULL OR priority = ?2)
+           AND (last_checked IS NULL OR last_checked < datetime('now', ?3))
+         ORDER BY
+           CASE priority
+             WHEN 'high' THEN 1
+             WHEN 'medium' THEN 2
+             ELSE 3
+           END,
+           last_checked ASC NULLS FIRST",
+        KEYWORD_MONITORING_COLUMNS
+    ))?;
 
-    if let Some(p) = priority {
-        conn.execute(
-            "UPDATE keyword_monitoring SET priority = ?1 WHERE id = ?2",
-            rusqlite::params![p, id],
-        )?;
-    }
+    let data = stmt
+        .query_map(rusqlite::params![product_id, priority, format!("-{} minutes", skip_recent_minutes)], row_to_monitoring)?
+        .collect::<Result<Vec<_>>>()?;
 
-    if let Some(active) = is_active {
-        conn.execute(
-            "UPDATE keyword_monitoring SET is_active = ?1 WHERE id = ?2",
-            rusqlite::params![if active { 1 } else { 0 }, id],
-        )?;
-    }
+    Ok(data)
+}
 
+
+// 清空任务记录
+pub fn clear_task_logs() -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute("DELETE FROM scheduler_task_logs", [])?;
     Ok(())
 }
 
-// 删除关键词监控
-pub fn delete_keyword_monitoring(id: i64) -> Result<()> {
+// ==================== 排名告警规则 ====================
+
+fn init_alert_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS alert_rules (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            product_id INTEGER,
+            keyword TEXT,
+            tag TEXT,
+            country TEXT,
+            condition_type TEXT NOT NULL,
+            threshold REAL,
+            window_days INTEGER,
+            channel TEXT NOT NULL DEFAULT 'desktop',
+            channel_id INTEGER REFERENCES notification_channels(id) ON DELETE SET NULL,
+            is_enabled INTEGER DEFAULT 1,
+            created_at TIMESTAMP DEFAULT (datetime('now')),
+            FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+        );
+
+        CREATE TABLE IF NOT EXISTS alert_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            rule_id INTEGER NOT NULL,
+            monitoring_id INTEGER NOT NULL,
+            message TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'open' CHECK(status IN ('open', 'acknowledged', 'snoozed')),
+            fired_at TIMESTAMP DEFAULT (datetime('now')),
+            acknowledged_at TIMESTAMP,
+            snoozed_until TIMESTAMP,
+            delivered_at TIMESTAMP,
+            delivery_error TEXT,
+            FOREIGN KEY (rule_id) REFERENCES alert_rules(id) ON DELETE CASCADE,
+            FOREIGN KEY (monitoring_id) REFERENCES keyword_monitoring(id) ON DELETE CASCADE
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_alert_history_rule ON alert_history(rule_id, monitoring_id);
+        CREATE INDEX IF NOT EXISTS idx_alert_history_status ON alert_history(status, fired_at);
+        ",
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlertRule {
+    pub id: i64,
+    pub name: String,
+    // 作用范围，均为空表示全部监控项
+    pub product_id: Option<i64>,
+    pub keyword: Option<String>,
+    pub tag: Option<String>,
+    pub country: Option<String>,
+    pub condition_type: String,     // rank_crosses, rank_drop, sponsored_lost, price_changed, rating_below
+    pub threshold: Option<f64>,     // 名次 N / 下降名次 X / 价格变化百分比 / 评分 Z
+    pub window_days: Option<i64>,   // rank_drop 的统计天数 Y
+    pub channel: String,            // desktop, none（不弹桌面通知）
+    #[serde(default)]
+    pub channel_id: Option<i64>,    // 同时发送到的外部通知渠道（Webhook / 邮件）
+    pub is_enabled: bool,
+    pub created_at: String,
+}
+
+// 新建 / 修改告警规则的参数
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlertRuleInput {
+    pub name: String,
+    pub product_id: Option<i64>,
+    pub keyword: Option<String>,
+    pub tag: Option<String>,
+    pub country: Option<String>,
+    pub condition_type: String,
+    pub threshold: Option<f64>,
+    pub window_days: Option<i64>,
+    pub channel: String,
+    #[serde(default)]
+    pub channel_id: Option<i64>,
+    pub is_enabled: bool,
+}
+
+const ALERT_RULE_COLUMNS: &str = "id, name, product_id, keyword, tag, country, condition_type, threshold, window_days,
+    channel, is_enabled, created_at, channel_id";
+
+fn row_to_alert_rule(row: &rusqlite::Row) -> rusqlite::Result<AlertRule> {
+    Ok(AlertRule {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        product_id: row.get(2)?,
+        keyword: row.get(3)?,
+        tag: row.get(4)?,
+        country: row.get(5)?,
+        condition_type: row.get(6)?,
+        threshold: row.get(7)?,
+        window_days: row.get(8)?,
+        channel: row.get(9)?,
+        channel_id: row.get(12)?,
+        is_enabled: row.get::<_, i64>(10)? != 0,
+        created_at: row.get(11)?,
+    })
+}
+
+// 获取告警规则（指定产品时包含全局规则）
+pub fn get_alert_rules(product_id: Option<i64>) -> Result<Vec<AlertRule>> {
     let conn = get_db().lock();
-    conn.execute("PRAGMA foreign_keys = OFF", [])?;
-    // 先删除历史记录
-    conn.execute("DELETE FROM keyword_ranking_history WHERE monitoring_id = ?1", [id])?;
-    // 再删除监控记录
-    conn.execute("DELETE FROM keyword_monitoring WHERE id = ?1", [id])?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM alert_rules
+         WHERE ?1 IS NULL OR product_id IS NULL OR product_id = ?1
+         ORDER BY id",
+        ALERT_RULE_COLUMNS
+    ))?;
+    let rules = stmt
+        .query_map([product_id], row_to_alert_rule)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(rules)
+}
+
+// 空字符串视为不限
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+pub fn create_alert_rule(input: &AlertRuleInput) -> Result<i64> {
+    let conn = get_db().lock();
+    conn.execute(
+        "INSERT INTO alert_rules (name, product_id, keyword, tag, country, condition_type, threshold, window_days, channel, is_enabled, channel_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        rusqlite::params![
+            input.name.trim(),
+            input.product_id,
+            non_empty(&input.keyword),
+            non_empty(&input.tag),
+            non_empty(&input.country),
+            input.condition_type,
+            input.threshold,
+            input.window_days,
+            input.channel,
+            input.is_enabled as i64,
+            input.channel_id,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+pub fn update_alert_rule(id: i64, input: &AlertRuleInput) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE alert_rules
+         SET name = ?1, product_id = ?2, keyword = ?3, tag = ?4, country = ?5,
+             condition_type = ?6, threshold = ?7, window_days = ?8, channel = ?9, is_enabled = ?10, channel_id = ?12
+         WHERE id = ?11",
+        rusqlite::params![
+            input.name.trim(),
+            input.product_id,
+            non_empty(&input.keyword),
+            non_empty(&input.tag),
+            non_empty(&input.country),
+            input.condition_type,
+            input.threshold,
+            input.window_days,
+            input.channel,
+            input.is_enabled as i64,
+            id,
+            input.channel_id,
+        ],
+    )?;
     Ok(())
 }
 
-// 批量删除关键词监控
-pub fn batch_delete_keyword_monitoring(ids: Vec<i64>) -> Result<()> {
+pub fn delete_alert_rule(id: i64) -> Result<()> {
     let conn = get_db().lock();
-    conn.execute("PRAGMA foreign_keys = OFF", [])?;
-    conn.execute("BEGIN TRANSACTION", [])?;
+    conn.execute("DELETE FROM alert_rules WHERE id = ?1", [id])?;
+    Ok(())
+}
 
-    let result = (|| {
-        for id in ids {
-            conn.execute("DELETE FROM keyword_ranking_history WHERE monitoring_id = ?1", [id])?;
-            conn.execute("DELETE FROM keyword_monitoring WHERE id = ?1", [id])?;
-        }
-        Ok::<(), rusqlite::Error>(())
-    })();
+// 告警记录（含规则和监控项信息）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AlertRecord {
+    pub id: i64,
+    pub rule_id: i64,
+    pub rule_name: String,
+    pub condition_type: String,
+    pub channel: String,
+    pub channel_id: Option<i64>,
+    pub monitoring_id: i64,
+    pub product_id: i64,
+    pub keyword: String,
+    pub asin: String,
+    pub country: String,
+    pub message: String,
+    pub status: String,                   // open, acknowledged, snoozed（暂停到期后视为 open）
+    pub fired_at: String,
+    pub acknowledged_at: Option<String>,
+    pub snoozed_until: Option<String>,
+    pub delivered_at: Option<String>,
+    pub delivery_error: Option<String>,
+}
+
+// 暂停到期的告警重新视为未确认
+const ALERT_EFFECTIVE_STATUS: &str =
+    "CASE WHEN h.status = 'snoozed' AND h.snoozed_until <= datetime('now') THEN 'open' ELSE h.status END";
+
+const ALERT_RECORD_SELECT: &str = "SELECT h.id, h.rule_id, r.name, r.condition_type, r.channel,
+        h.monitoring_id, m.product_id, m.keyword, m.asin, m.country, h.message,
+        CASE WHEN h.status = 'snoozed' AND h.snoozed_until <= datetime('now') THEN 'open' ELSE h.status END,
+        h.fired_at, h.acknowledged_at, h.snoozed_until, h.delivered_at, h.delivery_error, r.channel_id
+     FROM alert_history h
+     JOIN alert_rules r ON r.id = h.rule_id
+     JOIN keyword_monitoring m ON m.id = h.monitoring_id";
+
+fn row_to_alert_record(row: &rusqlite::Row) -> rusqlite::Result<AlertRecord> {
+    Ok(AlertRecord {
+        id: row.get(0)?,
+        rule_id: row.get(1)?,
+        rule_name: row.get(2)?,
+        condition_type: row.get(3)?,
+        channel: row.get(4)?,
+        channel_id: row.get(17)?,
+        monitoring_id: row.get(5)?,
+        product_id: row.get(6)?,
+        keyword: row.get(7)?,
+        asin: row.get(8)?,
+        country: row.get(9)?,
+        message: row.get(10)?,
+        status: row.get(11)?,
+        fired_at: row.get(12)?,
+        acknowledged_at: row.get(13)?,
+        snoozed_until: row.get(14)?,
+        delivered_at: row.get(15)?,
+        delivery_error: row.get(16)?,
+    })
+}
 
-    match result {
-        Ok(_) => {
-            conn.execute("COMMIT", [])?;
-            Ok(())
-        }
-        Err(e) => {
-            conn.execute("ROLLBACK", []).ok();
-            Err(e)
-        }
+// 获取告警记录（status: open / acknowledged / snoozed，为空表示全部）
+pub fn get_alert_history(product_id: Option<i64>, status: Option<String>, limit: i64) -> Result<Vec<AlertRecord>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE (?1 IS NULL OR m.product_id = ?1) AND (?2 IS NULL OR {} = ?2)
+         ORDER BY h.fired_at DESC, h.id DESC
+         LIMIT ?3",
+        ALERT_RECORD_SELECT, ALERT_EFFECTIVE_STATUS
+    ))?;
+    let records = stmt
+        .query_map(rusqlite::params![product_id, status, limit], row_to_alert_record)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(records)
+}
+
+// 未确认的告警数（不含暂停中的）
+pub fn get_open_alert_count(product_id: Option<i64>) -> Result<i64> {
+    let conn = get_db().lock();
+    conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM alert_history h
+             JOIN keyword_monitoring m ON m.id = h.monitoring_id
+             WHERE {} = 'open' AND (?1 IS NULL OR m.product_id = ?1)",
+            ALERT_EFFECTIVE_STATUS
+        ),
+        [product_id],
+        |row| row.get(0),
+    )
+}
+
+// 确认告警
+pub fn acknowledge_alerts(ids: &[i64]) -> Result<i64> {
+    let conn = get_db().lock();
+    let mut count = 0i64;
+    for id in ids {
+        count += conn.execute(
+            "UPDATE alert_history SET status = 'acknowledged', acknowledged_at = datetime('now') WHERE id = ?1",
+            [id],
+        )? as i64;
     }
+    Ok(count)
 }
 
-// 更新排名结果
-pub fn update_ranking_result(
-    monitoring_id: i64,
-    organic_rank: Option<i64>,
-    organic_page: Option<i64>,
-    sponsored_rank: Option<i64>,
-    sponsored_page: Option<i64>,
-    image_url: Option<String>,
-    price: Option<String>,
-    reviews_count: Option<i64>,
-    rating: Option<f64>,
-) -> Result<()> {
+// 暂停告警：暂停期间同一规则对同一监控项不再触发
+pub fn snooze_alerts(ids: &[i64], hours: i64) -> Result<i64> {
     let conn = get_db().lock();
-    conn.execute("BEGIN TRANSACTION", [])?;
+    let mut count = 0i64;
+    for id in ids {
+        count += conn.execute(
+            "UPDATE alert_history SET status = 'snoozed', snoozed_until = datetime('now', ?2) WHERE id = ?1",
+            rusqlite::params![id, format!("+{} hours", hours.max(1))],
+        )? as i64;
+    }
+    Ok(count)
+}
 
-    let result = (|| {
-        // 如果价格为空，尝试从同 ASIN+country 的其他记录中获取
-        let final_price = if price.is_none() {
-            // 先获取当前记录的 asin 和 country
-            let asin_country: Option<(String, String)> = conn.query_row(
-                "SELECT asin, country FROM keyword_monitoring WHERE id = ?1",
-                [monitoring_id],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            ).ok();
+// 尚未发送的告警（按触发顺序）
+pub fn get_undelivered_alerts() -> Result<Vec<AlertRecord>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE h.delivered_at IS NULL ORDER BY h.id",
+        ALERT_RECORD_SELECT
+    ))?;
+    let records = stmt
+        .query_map([], row_to_alert_record)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(records)
+}
 
-            if let Some((asin, country)) = asin_country {
-                // 从同 asin+country 的其他记录中查找最近有价格的
-                conn.query_row(
-                    "SELECT price FROM keyword_monitoring
-                     WHERE asin = ?1 AND country = ?2 AND price IS NOT NULL AND price != ''
-                     ORDER BY last_checked DESC LIMIT 1",
-                    rusqlite::params![asin, country],
-                    |row| row.get(0),
-                ).ok()
-            } else {
-                None
-            }
-        } else {
-            price
-        };
+// 记录告警发送结果（error 为空表示发送成功）
+pub fn mark_alert_delivered(id: i64, error: Option<&str>) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE alert_history SET delivered_at = datetime('now'), delivery_error = ?2 WHERE id = ?1",
+        rusqlite::params![id, error],
+    )?;
+    Ok(())
+}
+
+// 检测结果写入后评估告警规则（在 update_ranking_result 的事务内调用），返回触发数
+fn evaluate_alert_rules(
+    conn: &Connection,
+    monitoring_id: i64,
+    before: &crate::alerts::CheckSnapshot,
+    after: &crate::alerts::CheckSnapshot,
+) -> Result<i64> {
+    let target = conn.query_row(
+        "SELECT product_id, keyword, country, tags FROM keyword_monitoring WHERE id = ?1",
+        [monitoring_id],
+        |row| {
+            Ok(crate::alerts::AlertTarget {
+                product_id: row.get(0)?,
+                keyword: row.get(1)?,
+                country: row.get(2)?,
+                tags: row.get(3)?,
+            })
+        },
+    )?;
 
-        // 同样处理 image_url, reviews_count, rating
-        let (final_image_url, final_reviews_count, final_rating) = if image_url.is_none() || reviews_count.is_none() || rating.is_none() {
-            let asin_country: Option<(String, String)> = conn.query_row(
-                "SELECT asin, country FROM keyword_monitoring WHERE id = ?1",
-                [monitoring_id],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            ).ok();
+    let rules: Vec<AlertRule> = {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM alert_rules WHERE is_enabled = 1 AND (product_id IS NULL OR product_id = ?1)",
+            ALERT_RULE_COLUMNS
+        ))?;
+        let rules = stmt
+            .query_map([target.product_id], row_to_alert_rule)?
+            .collect::<Result<Vec<_>>>()?;
+        rules
+    };
 
-            if let Some((asin, country)) = asin_country {
-                let fallback: Option<(Option<String>, Option<i64>, Option<f64>)> = conn.query_row(
-                    "SELECT image_url, reviews_count, rating FROM keyword_monitoring
-                     WHERE asin = ?1 AND country = ?2 AND (image_url IS NOT NULL OR reviews_count IS NOT NULL OR rating IS NOT NULL)
-                     ORDER BY last_checked DESC LIMIT 1",
-                    rusqlite::params![asin, country],
-                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
-                ).ok();
+    let mut fired = 0i64;
+    for rule in rules.iter().filter(|r| crate::alerts::rule_matches_scope(r, &target)) {
+        // 暂停中：同一规则对同一监控项不触发；下降类条件在未确认前也不重复触发
+        let suppressed: bool = conn.query_row(
+            "SELECT EXISTS(
+                SELECT 1 FROM alert_history
+                WHERE rule_id = ?1 AND monitoring_id = ?2
+                  AND ((status = 'snoozed' AND snoozed_until > datetime('now'))
+                       OR (?3 AND status = 'open'))
+             )",
+            rusqlite::params![rule.id, monitoring_id, rule.condition_type == "rank_drop"],
+            |row| row.get(0),
+        )?;
+        if suppressed {
+            continue;
+        }
 
-                if let Some((fb_img, fb_reviews, fb_rating)) = fallback {
-                    (
-                        image_url.or(fb_img),
-                        reviews_count.or(fb_reviews),
-                        rating.or(fb_rating),
-                    )
-                } else {
-                    (image_url, reviews_count, rating)
-                }
-            } else {
-                (image_url, reviews_count, rating)
-            }
+        // 统计窗口内（不含本次）的最佳排名，按 (页码, 页内排名) 比较
+        let window_best = if rule.condition_type == "rank_drop" {
+            let mut stmt = conn.prepare(
+                "SELECT organic_rank, organic_page, organic_position FROM keyword_ranking_history
+                 WHERE monitoring_id = ?1 AND checked_at >= datetime('now', ?2) AND organic_rank IS NOT NULL
+                   AND id < (SELECT MAX(id) FROM keyword_ranking_history WHERE monitoring_id = ?1)",
+            )?;
+            let positions = stmt
+                .query_map(rusqlite::params![monitoring_id, format!("-{} days", rule.window_days.unwrap_or(7))], |row| {
+                    Ok(crate::alerts::RankPosition::new(row.get(0)?, row.get(1)?, row.get(2)?))
+                })?
+                .collect::<Result<Vec<_>>>()?;
+            crate::alerts::best_position(positions.into_iter().flatten())
         } else {
-            (image_url, reviews_count, rating)
+            None
         };
 
-        // 更新最新排名
-        conn.execute(
-            "UPDATE keyword_monitoring SET
-                latest_organic_rank = ?1, latest_organic_page = ?2,
-                latest_sponsored_rank = ?3, latest_sponsored_page = ?4,
-                image_url = ?5, price = ?6, reviews_count = ?7, rating = ?8,
-                last_checked = datetime('now')
-             WHERE id = ?9",
-            rusqlite::params![
-                organic_rank, organic_page,
-                sponsored_rank, sponsored_page,
-                final_image_url, final_price, final_reviews_count, final_rating,
-                monitoring_id
-            ],
-        )?;
+        if let Some(message) = crate::alerts::evaluate_rule(rule, before, after, window_best) {
+            conn.execute(
+                "INSERT INTO alert_history (rule_id, monitoring_id, message) VALUES (?1, ?2, ?3)",
+                rusqlite::params![rule.id, monitoring_id, message],
+            )?;
+            fired += 1;
+        }
+    }
+    Ok(fired)
+}
 
-        // 插入历史记录（使用北京时间的日期）
+// ==================== 外部通知渠道 ====================
+
+fn init_notification_channel_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS notification_channels (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            channel_type TEXT NOT NULL,
+            config TEXT NOT NULL,
+            is_enabled INTEGER DEFAULT 1,
+            send_rank_digest INTEGER DEFAULT 0,
+            last_sent_at TIMESTAMP,
+            last_error TEXT,
+            created_at TIMESTAMP DEFAULT (datetime('now'))
+        );
+        ",
+    )?;
+    Ok(())
+}
+
+// 数据库迁移：告警规则添加外部通知渠道
+fn migrate_alert_rules_channel_id(conn: &Connection) -> Result<()> {
+    let has_channel_id: bool = conn
+        .prepare("SELECT channel_id FROM alert_rules LIMIT 1")
+        .is_ok();
+
+    if !has_channel_id {
         conn.execute(
-            "INSERT INTO keyword_ranking_history
-                (monitoring_id, check_date, organic_rank, organic_page, sponsored_rank, sponsored_page)
-             VALUES (?1, date('now', '+8 hours'), ?2, ?3, ?4, ?5)",
-            rusqlite::params![monitoring_id, organic_rank, organic_page, sponsored_rank, sponsored_page],
+            "ALTER TABLE alert_rules ADD COLUMN channel_id INTEGER REFERENCES notification_channels(id) ON DELETE SET NULL",
+            [],
         )?;
+    }
 
-        Ok::<(), rusqlite::Error>(())
-    })();
+    Ok(())
+}
 
-    match result {
-        Ok(_) => {
-            conn.execute("COMMIT", [])?;
-            Ok(())
-        }
-        Err(e) => {
-            conn.execute("ROLLBACK", []).ok();
-            Err(e)
-        }
-    }
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationChannel {
+    pub id: i64,
+    pub name: String,
+    pub channel_type: String,           // webhook, email
+    pub config: crate::channels::ChannelConfig,
+    pub is_enabled: bool,
+    pub send_rank_digest: bool,         // 接收定时检测的排名变化汇总
+    pub last_sent_at: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: String,
 }
 
-// 获取排名历史（按日期聚合，每天只取最佳排名）
-pub fn get_ranking_history(monitoring_id: i64, days: i64) -> Result<Vec<RankingHistory>> {
-    let conn = get_db().lock();
-    let days_str = format!("-{} days", days);
+// 新建 / 修改通知渠道的参数
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NotificationChannelInput {
+    pub name: String,
+    pub config: crate::channels::ChannelConfig,
+    pub is_enabled: bool,
+    pub send_rank_digest: bool,
+}
+
+fn row_to_notification_channel(row: &rusqlite::Row) -> rusqlite::Result<NotificationChannel> {
+    let config: String = row.get(3)?;
+    Ok(NotificationChannel {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        channel_type: row.get(2)?,
+        config: serde_json::from_str(&config).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        is_enabled: row.get::<_, i64>(4)? != 0,
+        send_rank_digest: row.get::<_, i64>(5)? != 0,
+        last_sent_at: row.get(6)?,
+        last_error: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
 
-    // 按日期分组，取每天的最佳排名（最小值）
-    // 页码取最小排名对应的页码
+pub fn get_notification_channels() -> Result<Vec<NotificationChannel>> {
+    let conn = get_db().lock();
     let mut stmt = conn.prepare(
-        "SELECT
-            MIN(id) as id,
-            monitoring_id,
-            check_date,
-            MIN(organic_rank) as organic_rank,
-            MIN(organic_page) as organic_page,
-            MIN(sponsored_rank) as sponsored_rank,
-            MIN(sponsored_page) as sponsored_page,
-            MIN(checked_at) as checked_at
-         FROM keyword_ranking_history
-         WHERE monitoring_id = ?1 AND check_date >= date('now', '+8 hours', ?2)
-         GROUP BY check_date
-         ORDER BY check_date ASC"
+        "SELECT id, name, channel_type, config, is_enabled, send_rank_digest, last_sent_at, last_error, created_at
+         FROM notification_channels ORDER BY id",
     )?;
-
-    let history = stmt
-        .query_map(rusqlite::params![monitoring_id, days_str], |row| {
-            Ok(RankingHistory {
-                id: row.get(0)?,
-                monitoring_id: row.get(1)?,
-                check_date: row.get(2)?,
-                organic_rank: row.get(3)?,
-                organic_page: row.get(4)?,
-                sponsored_rank: row.get(5)?,
-                sponsored_page: row.get(6)?,
-                checked_at: row.get(7)?,
-            })
-        })?
+    let channels = stmt
+        .query_map([], row_to_notification_channel)?
         .collect::<Result<Vec<_>>>()?;
-
-    Ok(history)
+    Ok(channels)
 }
 
-// 获取上一次的自然排名（用于计算排名变化）
-pub fn get_previous_ranking(monitoring_id: i64) -> Result<Option<i64>> {
+pub fn get_notification_channel(id: i64) -> Result<Option<NotificationChannel>> {
     let conn = get_db().lock();
-
-    let result = conn.query_row(
-        "SELECT organic_rank FROM keyword_ranking_history
-         WHERE monitoring_id = ?1
-         ORDER BY checked_at DESC
-         LIMIT 1",
-        [monitoring_id],
-        |row| row.get(0),
-    );
-
-    match result {
-        Ok(rank) => Ok(rank),
+    match conn.query_row(
+        "SELECT id, name, channel_type, config, is_enabled, send_rank_digest, last_sent_at, last_error, created_at
+         FROM notification_channels WHERE id = ?1",
+        [id],
+        row_to_notification_channel,
+    ) {
+        Ok(c) => Ok(Some(c)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
         Err(e) => Err(e),
     }
 }
 
-// 保存竞品快照
-pub fn save_ranking_snapshot(
-    keyword: &str,
-    country: &str,
-    organic_top_50: Option<String>,
-    sponsored_top_20: Option<String>,
-) -> Result<()> {
-    let conn = get_db().lock();
+fn channel_config_json(config: &crate::channels::ChannelConfig) -> Result<String> {
+    serde_json::to_string(config).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
 
+pub fn create_notification_channel(input: &NotificationChannelInput) -> Result<i64> {
+    let conn = get_db().lock();
     conn.execute(
-        "INSERT OR REPLACE INTO ranking_snapshots
-            (keyword, country, snapshot_date, organic_top_50, sponsored_top_20)
-         VALUES (?1, ?2, date('now', '+8 hours'), ?3, ?4)",
-        rusqlite::params![keyword, country, organic_top_50, sponsored_top_20],
+        "INSERT INTO notification_channels (name, channel_type, config, is_enabled, send_rank_digest)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![
+            input.name.trim(),
+            input.config.channel_type(),
+            channel_config_json(&input.config)?,
+            input.is_enabled as i64,
+            input.send_rank_digest as i64,
+        ],
     )?;
+    Ok(conn.last_insert_rowid())
+}
 
+pub fn update_notification_channel(id: i64, input: &NotificationChannelInput) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE notification_channels
+         SET name = ?1, channel_type = ?2, config = ?3, is_enabled = ?4, send_rank_digest = ?5
+         WHERE id = ?6",
+        rusqlite::params![
+            input.name.trim(),
+            input.config.channel_type(),
+            channel_config_json(&input.config)?,
+            input.is_enabled as i64,
+            input.send_rank_digest as i64,
+            id,
+        ],
+    )?;
     Ok(())
 }
 
-// 获取竞品快照
-pub fn get_ranking_snapshots(keyword: &str, country: &str, days: i64) -> Result<Vec<RankingSnapshot>> {
+// 删除渠道（引用它的告警规则 channel_id 置空）
+pub fn delete_notification_channel(id: i64) -> Result<()> {
     let conn = get_db().lock();
+    conn.execute("DELETE FROM notification_channels WHERE id = ?1", [id])?;
+    Ok(())
+}
 
-    let mut stmt = conn.prepare(
-        "SELECT id, keyword, country, snapshot_date, organic_top_50, sponsored_top_20, created_at
-         FROM ranking_snapshots
-         WHERE keyword = ?1 AND country = ?2 AND snapshot_date >= date('now', '+8 hours', ?3)
-         ORDER BY snapshot_date DESC"
+// 记录渠道最近一次发送结果（error 为空表示成功）
+pub fn record_notification_channel_result(id: i64, error: Option<&str>) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE notification_channels SET last_sent_at = datetime('now'), last_error = ?2 WHERE id = ?1",
+        rusqlite::params![id, error],
     )?;
+    Ok(())
+}
 
-    let days_str = format!("-{} days", days);
-    let snapshots = stmt
-        .query_map(rusqlite::params![keyword, country, days_str], |row| {
-            Ok(RankingSnapshot {
-                id: row.get(0)?,
-                keyword: row.get(1)?,
-                country: row.get(2)?,
-                snapshot_date: row.get(3)?,
-                organic_top_50: row.get(4)?,
-                sponsored_top_20: row.get(5)?,
-                created_at: row.get(6)?,
-            })
-        })?
-        .collect::<Result<Vec<_>>>()?;
+// ============ 排名历史归档（原始 → 日汇总 → 周汇总） ============
+
+// 查询范围超过该天数时按周聚合
+const WEEKLY_RESOLUTION_DAYS: i64 = 120;
+
+// 原始数据至少保留的天数：告警规则的回看窗口最长 90 天，直接读原始检测记录
+pub const MIN_RAW_RETENTION_DAYS: i64 = 90;
+
+// 三层数据统一成相同的列，供查询合并；?1 = monitoring_id
+// 自然排名的最佳/平均/最差用爬虫记录的绝对位置（每页自然结果数不固定），最佳排名另存页码和页内排名
+// 广告位数量不固定，广告排名直接用页内排名；配送邮编为空字符串表示未记录（旧版本数据）
+const RANKING_POINTS_SQL: &str = "
+    SELECT id, check_date AS period, 1 AS check_count,
+           organic_position AS organic_best, organic_position AS organic_worst,
+           organic_position * 1.0 AS organic_sum, organic_position IS NOT NULL AS organic_count,
+           sponsored_rank AS sponsored_best, sponsored_rank AS sponsored_worst,
+           sponsored_rank * 1.0 AS sponsored_sum, sponsored_rank IS NOT NULL AS sponsored_count,
+           sponsored_page AS sponsored_best_page, checked_at AS last_checked_at, 'raw' AS source, COALESCE(location, '') AS location,
+           CASE WHEN organic_rank IS NOT NULL THEN COALESCE(organic_page, 1) END AS organic_best_page,
+           organic_rank AS organic_best_rank
+    FROM keyword_ranking_history WHERE monitoring_id = ?1
+    UNION ALL
+    SELECT NULL, period_start, check_count, organic_best, organic_worst, organic_avg * organic_count, organic_count,
+           sponsored_best, sponsored_worst, sponsored_avg * sponsored_count, sponsored_count,
+           sponsored_best_page, last_checked_at, 'day', location, organic_best_page, organic_best_rank
+    FROM ranking_history_daily WHERE monitoring_id = ?1
+    UNION ALL
+    SELECT NULL, period_start, check_count, organic_best, organic_worst, organic_avg * organic_count, organic_count,
+           sponsored_best, sponsored_worst, sponsored_avg * sponsored_count, sponsored_count,
+           sponsored_best_page, last_checked_at, 'week', location, organic_best_page, organic_best_rank
+    FROM ranking_history_weekly WHERE monitoring_id = ?1";
+
+// 窗口函数排序：按 (页码, 页内排名) 取最佳的一次检测，没有自然排名的排在最后
+const ORGANIC_BEST_ORDER: &str = "ORDER BY organic_best_page IS NULL, organic_best_page, organic_best_rank";
+
+// 汇总表建表语句，日表和周表结构相同（period_start 为当天 / 当周周一）
+// 同一监控项不同配送邮编的检测分别汇总，location 为空字符串表示未记录邮编
+fn rollup_table_sql(table: &str) -> String {
+    format!(
+        "
+        CREATE TABLE IF NOT EXISTS {table} (
+            monitoring_id INTEGER NOT NULL,
+            period_start DATE NOT NULL,
+            check_count INTEGER NOT NULL DEFAULT 0,
+            organic_count INTEGER NOT NULL DEFAULT 0,    -- 有自然排名的检测次数
+            organic_best INTEGER,
+            organic_avg REAL,
+            organic_worst INTEGER,
+            sponsored_count INTEGER NOT NULL DEFAULT 0,
+            sponsored_best INTEGER,
+            sponsored_avg REAL,
+            sponsored_worst INTEGER,
+            sponsored_best_page INTEGER,
+            last_checked_at TIMESTAMP,
+            organic_best_page INTEGER,                   -- 最佳自然排名所在页码和页内排名
+            organic_best_rank INTEGER,
+            location TEXT NOT NULL DEFAULT '',           -- 检测使用的配送邮编
+            PRIMARY KEY (monitoring_id, period_start, location),
+            FOREIGN KEY (monitoring_id) REFERENCES keyword_monitoring(id) ON DELETE CASCADE
+        );
+        "
+    )
+}
+
+// 初始化排名历史汇总表
+fn init_ranking_rollup_tables(conn: &Connection) -> Result<()> {
+    for table in ["ranking_history_daily", "ranking_history_weekly"] {
+        conn.execute_batch(&rollup_table_sql(table))?;
+
+        // 旧版本的汇总行只有按每页 48 个换算的绝对位置，据此估算最佳排名的页码和页内排名
+        let has_best_page: bool = conn
+            .prepare(&format!("SELECT organic_best_page FROM {table} LIMIT 1"))
+            .is_ok();
+        if !has_best_page {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {table} ADD COLUMN organic_best_page INTEGER;
+                 ALTER TABLE {table} ADD COLUMN organic_best_rank INTEGER;
+                 UPDATE {table} SET organic_best_page = (organic_best - 1) / 48 + 1,
+                                    organic_best_rank = (organic_best - 1) % 48 + 1
+                 WHERE organic_best IS NOT NULL;"
+            ))?;
+        }
 
-    Ok(snapshots)
+        // 旧版本的汇总不区分邮编，主键需要加上 location，只能重建表；旧汇总行的邮编记为未记录
+        let has_location: bool = conn
+            .prepare(&format!("SELECT location FROM {table} LIMIT 1"))
+            .is_ok();
+        if !has_location {
+            let columns = "monitoring_id, period_start, check_count, organic_count, organic_best, organic_avg, organic_worst,
+                sponsored_count, sponsored_best, sponsored_avg, sponsored_worst, sponsored_best_page, last_checked_at,
+                organic_best_page, organic_best_rank";
+            conn.execute_batch(&format!(
+                "BEGIN TRANSACTION;
+                 ALTER TABLE {table} RENAME TO {table}_old;
+                 {create}
+                 INSERT INTO {table} ({columns}) SELECT {columns} FROM {table}_old;
+                 DROP TABLE {table}_old;
+                 COMMIT;",
+                create = rollup_table_sql(table)
+            ))
+            .inspect_err(|_| {
+                conn.execute("ROLLBACK", []).ok();
+            })?;
+        }
+    }
+    Ok(())
 }
 
-// 获取监控统计
-pub fn get_monitoring_stats(product_id: i64) -> Result<MonitoringStats> {
-    let conn = get_db().lock();
+// 把汇总行写入目标表，已存在的周期按检测次数加权合并
+fn merge_rollup(conn: &Connection, table: &str, select: &str, cutoff: &str) -> Result<usize> {
+    // 新数据的 (页码, 页内排名) 更靠前时替换最佳排名
+    let better = "excluded.organic_best_page IS NOT NULL AND (organic_best_page IS NULL
+        OR excluded.organic_best_page < organic_best_page
+        OR (excluded.organic_best_page = organic_best_page AND excluded.organic_best_rank < organic_best_rank))";
+    conn.execute(
+        &format!(
+            "INSERT INTO {table} (monitoring_id, period_start, check_count,
+                organic_count, organic_best, organic_avg, organic_worst,
+                sponsored_count, sponsored_best, sponsored_avg, sponsored_worst,
+                sponsored_best_page, last_checked_at, organic_best_page, organic_best_rank, location)
+             {select}
+             ON CONFLICT(monitoring_id, period_start, location) DO UPDATE SET
+                check_count = check_count + excluded.check_count,
+                organic_count = organic_count + excluded.organic_count,
+                organic_avg = (COALESCE(organic_avg * organic_count, 0) + COALESCE(excluded.organic_avg * excluded.organic_count, 0))
+                    / NULLIF(organic_count + excluded.organic_count, 0),
+                organic_best = MIN(COALESCE(organic_best, excluded.organic_best), COALESCE(excluded.organic_best, organic_best)),
+                organic_worst = MAX(COALESCE(organic_worst, excluded.organic_worst), COALESCE(excluded.organic_worst, organic_worst)),
+                sponsored_count = sponsored_count + excluded.sponsored_count,
+                sponsored_avg = (COALESCE(sponsored_avg * sponsored_count, 0) + COALESCE(excluded.sponsored_avg * excluded.sponsored_count, 0))
+                    / NULLIF(sponsored_count + excluded.sponsored_count, 0),
+                sponsored_best = MIN(COALESCE(sponsored_best, excluded.sponsored_best), COALESCE(excluded.sponsored_best, sponsored_best)),
+                sponsored_worst = MAX(COALESCE(sponsored_worst, excluded.sponsored_worst), COALESCE(excluded.sponsored_worst, sponsored_worst)),
+                sponsored_best_page = MIN(COALESCE(sponsored_best_page, excluded.sponsored_best_page), COALESCE(excluded.sponsored_best_page, sponsored_best_page)),
+                last_checked_at = MAX(COALESCE(last_checked_at, ''), COALESCE(excluded.last_checked_at, '')),
+                organic_best_page = CASE WHEN {better} THEN excluded.organic_best_page ELSE organic_best_page END,
+                organic_best_rank = CASE WHEN {better} THEN excluded.organic_best_rank ELSE organic_best_rank END"
+        ),
+        [cutoff],
+    )
+}
 
-    let total: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM keyword_monitoring WHERE product_id = ?1",
-        [product_id],
-        |row| row.get(0),
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryMaintenanceReport {
+    pub raw_retention_days: i64,
+    pub daily_retention_days: i64,
+    pub check_log_retention_days: i64,
+    pub raw_rows_compacted: usize,
+    pub daily_rows_compacted: usize,
+    pub raw_rows: i64,
+    pub daily_rows: i64,
+    pub weekly_rows: i64,
+    pub size_before: i64,       // 字节
+    pub size_after: i64,
+    pub vacuumed: bool,
+}
+
+// 早于 raw_cutoff 的原始检测汇总为日数据，早于 daily_cutoff 的日数据汇总为周数据（日期均为 YYYY-MM-DD）
+fn compact_ranking_history(conn: &Connection, raw_cutoff: &str, daily_cutoff: &str) -> Result<(usize, usize)> {
+    merge_rollup(
+        conn,
+        "ranking_history_daily",
+        &format!(
+            "SELECT monitoring_id, check_date, COUNT(*),
+                    COUNT(organic_position), MIN(organic_position), AVG(organic_position), MAX(organic_position),
+                    COUNT(sponsored_rank), MIN(sponsored_rank), AVG(sponsored_rank), MAX(sponsored_rank),
+                    MIN(sponsored_page), MAX(checked_at),
+                    MAX(CASE WHEN best = 1 THEN organic_best_page END), MAX(CASE WHEN best = 1 THEN organic_best_rank END),
+                    zip
+             FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY monitoring_id, check_date, zip {ORGANIC_BEST_ORDER}) AS best
+                   FROM (SELECT *, CASE WHEN organic_rank IS NOT NULL THEN COALESCE(organic_page, 1) END AS organic_best_page,
+                                organic_rank AS organic_best_rank, COALESCE(location, '') AS zip
+                         FROM keyword_ranking_history WHERE check_date < ?1))
+             GROUP BY monitoring_id, check_date, zip"
+        ),
+        raw_cutoff,
     )?;
+    let raw_compacted = conn.execute("DELETE FROM keyword_ranking_history WHERE check_date < ?1", [raw_cutoff])?;
 
-    let active: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM keyword_monitoring WHERE product_id = ?1 AND is_active = 1",
-        [product_id],
-        |row| row.get(0),
+    merge_rollup(
+        conn,
+        "ranking_history_weekly",
+        &format!(
+            "SELECT monitoring_id, week, SUM(check_count),
+                    SUM(organic_count), MIN(organic_best),
+                    SUM(organic_avg * organic_count) / NULLIF(SUM(organic_count), 0), MAX(organic_worst),
+                    SUM(sponsored_count), MIN(sponsored_best),
+                    SUM(sponsored_avg * sponsored_count) / NULLIF(SUM(sponsored_count), 0), MAX(sponsored_worst),
+                    MIN(sponsored_best_page), MAX(last_checked_at),
+                    MAX(CASE WHEN best = 1 THEN organic_best_page END), MAX(CASE WHEN best = 1 THEN organic_best_rank END),
+                    location
+             FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY monitoring_id, week, location {ORGANIC_BEST_ORDER}) AS best
+                   FROM (SELECT *, date(period_start, 'weekday 0', '-6 days') AS week
+                         FROM ranking_history_daily WHERE period_start < ?1))
+             GROUP BY monitoring_id, week, location"
+        ),
+        daily_cutoff,
     )?;
+    let daily_compacted = conn.execute("DELETE FROM ranking_history_daily WHERE period_start < ?1", [daily_cutoff])?;
 
-    let top10_organic: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM keyword_monitoring
-         WHERE product_id = ?1 AND latest_organic_rank IS NOT NULL AND latest_organic_rank <= 10",
-        [product_id],
-        |row| row.get(0),
-    )?;
+    Ok((raw_compacted, daily_compacted))
+}
 
-    let top30_organic: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM keyword_monitoring
-         WHERE product_id = ?1 AND latest_organic_rank IS NOT NULL AND latest_organic_rank <= 30",
-        [product_id],
+fn database_size(conn: &Connection) -> Result<i64> {
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
         |row| row.get(0),
+    )
+}
+
+pub fn vacuum_database() -> Result<()> {
+    get_db().lock().execute_batch("VACUUM")
+}
+
+// 排名历史维护：按保留天数逐级汇总，vacuum = true 时再 VACUUM 回收空间
+// 检测失败记录和任务明细按 log_days 删除（任务记录本身保留）
+// 保留天数不合法时报错而不是自动调整（设置保存时已校验，这里防止旧设置或手动修改的数据）
+pub fn run_history_maintenance(raw_days: i64, daily_days: i64, log_days: i64, vacuum: bool) -> Result<HistoryMaintenanceReport> {
+    if raw_days < MIN_RAW_RETENTION_DAYS {
+        return Err(rusqlite::Error::InvalidParameterName(format!(
+            "原始排名数据至少保留 {} 天，当前设置为 {} 天",
+            MIN_RAW_RETENTION_DAYS, raw_days
+        )));
+    }
+    if daily_days < raw_days {
+        return Err(rusqlite::Error::InvalidParameterName("日汇总保留天数不能少于原始数据保留天数".to_string()));
+    }
+    if log_days < 1 {
+        return Err(rusqlite::Error::InvalidParameterName("检测明细至少保留 1 天".to_string()));
+    }
+    let conn = get_db().lock();
+    let cutoff = |days: i64| -> Result<String> {
+        conn.query_row("SELECT date('now', '+8 hours', ?1)", [format!("-{} days", days)], |row| row.get(0))
+    };
+    let (raw_cutoff, daily_cutoff) = (cutoff(raw_days)?, cutoff(daily_days)?);
+    let size_before = database_size(&conn)?;
+
+    conn.execute("BEGIN TRANSACTION", [])?;
+    let (raw_rows_compacted, daily_rows_compacted) = match compact_ranking_history(&conn, &raw_cutoff, &daily_cutoff) {
+        Ok(counts) => {
+            conn.execute("COMMIT", [])?;
+            counts
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            return Err(e);
+        }
+    };
+
+    conn.execute(
+        "DELETE FROM ranking_check_failures WHERE created_at < datetime('now', ?1)",
+        [format!("-{} days", log_days)],
+    )?;
+    conn.execute(
+        "DELETE FROM scheduler_task_items WHERE checked_at < datetime('now', ?1)",
+        [format!("-{} days", log_days)],
     )?;
 
-    let with_sponsored: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM keyword_monitoring
-         WHERE product_id = ?1 AND latest_sponsored_rank IS NOT NULL",
-        [product_id],
-        |row| row.get(0),
+    if vacuum {
+        conn.execute_batch("VACUUM")?;
+    }
+
+    let count = |table: &str| -> Result<i64> {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+    };
+    Ok(HistoryMaintenanceReport {
+        raw_retention_days: raw_days,
+        daily_retention_days: daily_days,
+        check_log_retention_days: log_days,
+        raw_rows_compacted,
+        daily_rows_compacted,
+        raw_rows: count("keyword_ranking_history")?,
+        daily_rows: count("ranking_history_daily")?,
+        weekly_rows: count("ranking_history_weekly")?,
+        size_before,
+        size_after: database_size(&conn)?,
+        vacuumed: vacuum,
+    })
+}
+
+// ============ 排名检测失败与重试队列 ============
+
+// 失败统计的回看天数
+const FAILURE_STATS_DAYS: i64 = 30;
+
+fn init_check_failure_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        -- 检测失败记录（按类型统计长期失败的关键词）
+        CREATE TABLE IF NOT EXISTS ranking_check_failures (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            monitoring_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,         -- captcha / geo_restricted / timeout / parse_error / proxy_error / other
+            message TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (monitoring_id) REFERENCES keyword_monitoring(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_check_failures_monitoring ON ranking_check_failures(monitoring_id, created_at);
+
+        -- 重试队列：每个关键词最多一条，next_retry_at 为 UTC 时间
+        CREATE TABLE IF NOT EXISTS ranking_retry_queue (
+            monitoring_id INTEGER PRIMARY KEY,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            kind TEXT NOT NULL,
+            next_retry_at TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            FOREIGN KEY (monitoring_id) REFERENCES keyword_monitoring(id) ON DELETE CASCADE
+        );
+        CREATE INDEX IF NOT EXISTS idx_retry_queue_next ON ranking_retry_queue(next_retry_at);
+        "
     )?;
+    Ok(())
+}
 
-    Ok(MonitoringStats {
-        total,
-        active,
-        top10_organic,
-        top30_organic,
-        with_sponsored,
-    })
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckFailureOutcome {
+    pub consecutive_failures: i64,
+    pub retry_at: Option<String>,   // 排入重试队列的时间，None 表示不再重试
+    pub auto_paused: bool,
 }
 
-// 获取单个监控记录
-pub fn get_keyword_monitoring_by_id(id: i64) -> Result<Option<KeywordMonitoring>> {
+// 记录一次检测失败：写入失败记录，累加连续失败次数，然后自动暂停或按退避排入重试队列
+pub fn record_check_failure(
+    monitoring_id: i64,
+    kind: crate::check_failures::FailureKind,
+    message: &str,
+    auto_pause_after: i64,
+) -> Result<CheckFailureOutcome> {
     let conn = get_db().lock();
+    conn.execute("BEGIN TRANSACTION", [])?;
 
-    let result = conn.query_row(
-        "SELECT id, product_id, keyword, asin, country, priority, is_active,
-                latest_organic_rank, latest_organic_page, latest_sponsored_rank, latest_sponsored_page,
-                image_url, price, reviews_count, rating, last_checked, created_at, tags
-         FROM keyword_monitoring WHERE id = ?1",
-        [id],
-        |row| {
-            Ok(KeywordMonitoring {
-                id: row.get(0)?,
-                product_id: row.get(1)?,
-                keyword: row.get(2)?,
-                asin: row.get(3)?,
-                country: row.get(4)?,
-                priority: row.get(5)?,
-                is_active: row.get::<_, i64>(6)? == 1,
-                latest_organic_rank: row.get(7)?,
-                latest_organic_page: row.get(8)?,
-                latest_sponsored_rank: row.get(9)?,
-                latest_sponsored_page: row.get(10)?,
-                image_url: row.get(11)?,
-                price: row.get(12)?,
-                reviews_count: row.get(13)?,
-                rating: row.get(14)?,
-                last_checked: row.get(15)?,
-                created_at: row.get(16)?,
-                tags: row.get(17)?,
-            })
-        },
-    );
+    let result = (|| {
+        conn.execute(
+            "INSERT INTO ranking_check_failures (monitoring_id, kind, message) VALUES (?1, ?2, ?3)",
+            rusqlite::params![monitoring_id, kind.as_str(), message],
+        )?;
+        conn.execute(
+            "UPDATE keyword_monitoring
+             SET consecutive_failures = consecutive_failures + 1,
+                 last_failure_kind = ?1, last_failure_message = ?2, last_failure_at = datetime('now')
+             WHERE id = ?3",
+            rusqlite::params![kind.as_str(), message, monitoring_id],
+        )?;
+        let consecutive_failures: i64 = conn.query_row(
+            "SELECT consecutive_failures FROM keyword_monitoring WHERE id = ?1",
+            [monitoring_id],
+            |row| row.get(0),
+        )?;
+
+        if auto_pause_after > 0 && consecutive_failures >= auto_pause_after {
+            conn.execute(
+                "UPDATE keyword_monitoring SET is_active = 0, auto_paused_at = datetime('now') WHERE id = ?1",
+                [monitoring_id],
+            )?;
+            conn.execute("DELETE FROM ranking_retry_queue WHERE monitoring_id = ?1", [monitoring_id])?;
+            return Ok(CheckFailureOutcome { consecutive_failures, retry_at: None, auto_paused: true });
+        }
+
+        let previous_attempts: i64 = conn.query_row(
+            "SELECT COALESCE((SELECT attempts FROM ranking_retry_queue WHERE monitoring_id = ?1), 0)",
+            [monitoring_id],
+            |row| row.get(0),
+        )?;
+        let attempts = previous_attempts + 1;
+        let retry_at = match kind.retry_delay_minutes(attempts) {
+            Some(delay) => {
+                let retry_at: String = conn.query_row(
+                    "SELECT datetime('now', ?1)",
+                    [format!("+{} minutes", delay)],
+                    |row| row.get(0),
+                )?;
+                conn.execute(
+                    "INSERT INTO ranking_retry_queue (monitoring_id, attempts, kind, next_retry_at)
+                     VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(monitoring_id) DO UPDATE SET
+                        attempts = excluded.attempts, kind = excluded.kind, next_retry_at = excluded.next_retry_at",
+                    rusqlite::params![monitoring_id, attempts, kind.as_str(), retry_at],
+                )?;
+                Some(retry_at)
+            }
+            None => {
+                conn.execute("DELETE FROM ranking_retry_queue WHERE monitoring_id = ?1", [monitoring_id])?;
+                None
+            }
+        };
+        Ok(CheckFailureOutcome { consecutive_failures, retry_at, auto_paused: false })
+    })();
 
     match result {
-        Ok(m) => Ok(Some(m)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e),
+        Ok(outcome) => {
+            conn.execute("COMMIT", [])?;
+            Ok(outcome)
+        }
+        Err(e) => {
+            conn.execute("ROLLBACK", []).ok();
+            Err(e)
+        }
     }
 }
 
-// 获取待检测的监控记录（活跃且未检测或超过指定时间未检测）
-// hours_since_last_check = 0 表示无时间限制，返回所有活跃的监控项
-pub fn get_pending_monitoring_checks(product_id: i64, hours_since_last_check: i64) -> Result<Vec<KeywordMonitoring>> {
+// 记录只有警告的检测（排名已保存）：写入失败记录，连续失败次数不变，移出重试队列
+pub fn record_check_warning(monitoring_id: i64, kind: crate::check_failures::FailureKind, message: &str) -> Result<()> {
     let conn = get_db().lock();
+    conn.execute(
+        "INSERT INTO ranking_check_failures (monitoring_id, kind, message) VALUES (?1, ?2, ?3)",
+        rusqlite::params![monitoring_id, kind.as_str(), message],
+    )?;
+    conn.execute("DELETE FROM ranking_retry_queue WHERE monitoring_id = ?1", [monitoring_id])?;
+    Ok(())
+}
 
-    // 如果 hours_since_last_check 为 0，则不限制时间，返回所有活跃监控项
-    let sql = if hours_since_last_check == 0 {
-        "SELECT id, product_id, keyword, asin, country, priority, is_active,
-                latest_organic_rank, latest_organic_page, latest_sponsored_rank, latest_sponsored_page,
-                image_url, price, reviews_count, rating, last_checked, created_at, tags
-         FROM keyword_monitoring
-         WHERE product_id = ?1 AND is_active = 1
-         ORDER BY
-           CASE priority
-             WHEN 'high' THEN 1
-             WHEN 'medium' THEN 2
-             ELSE 3
-           END,
-           last_checked ASC NULLS FIRST".to_string()
-    } else {
-        let hours_str = format!("-{} hours", hours_since_last_check);
-        format!(
-            "SELECT id, product_id, keyword, asin, country, priority, is_active,
-                    latest_organic_rank, latest_organic_page, latest_sponsored_rank, latest_sponsored_page,
-                    image_url, price, reviews_count, rating, last_checked, created_at, tags
-             FROM keyword_monitoring
-             WHERE product_id = ?1 AND is_active = 1
-               AND (last_checked IS NULL OR last_checked < datetime('now', '{}'))
-             ORDER BY
-               CASE priority
-                 WHEN 'high' THEN 1
-                 WHEN 'medium' THEN 2
-                 ELSE 3
-               END,
-               last_checked ASC NULLS FIRST",
-            hours_str
-        )
-    };
-
-    let mut stmt = conn.prepare(&sql)?;
-
-    let data = stmt
-        .query_map(rusqlite::params![product_id], |row| {
-            Ok(KeywordMonitoring {
-                id: row.get(0)?,
-                product_id: row.get(1)?,
-                keyword: row.get(2)?,
-                asin: row.get(3)?,
-                country: row.get(4)?,
-                priority: row.get(5)?,
-                is_active: row.get::<_, i64>(6)? == 1,
-                latest_organic_rank: row.get(7)?,
-                latest_organic_page: row.get(8)?,
-                latest_sponsored_rank: row.get(9)?,
-                latest_sponsored_page: row.get(10)?,
-                image_url: row.get(11)?,
-                price: row.get(12)?,
-                reviews_count: row.get(13)?,
-                rating: row.get(14)?,
-                last_checked: row.get(15)?,
-                created_at: row.get(16)?,
-                tags: row.get(17)?,
-            })
-        })?
-        .collect::<Result<Vec<_>>>()?;
-
-    Ok(data)
+// 检测成功：清零连续失败次数并移出重试队列（保留最近失败原因供查看）
+pub fn clear_check_failures(monitoring_id: i64) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE keyword_monitoring SET consecutive_failures = 0 WHERE id = ?1 AND consecutive_failures != 0",
+        [monitoring_id],
+    )?;
+    conn.execute("DELETE FROM ranking_retry_queue WHERE monitoring_id = ?1", [monitoring_id])?;
+    Ok(())
 }
 
-// 根据ID列表获取监控记录（用于选中检测）
-pub fn get_monitoring_by_ids(ids: &[i64]) -> Result<Vec<KeywordMonitoring>> {
+// 已到重试时间的监控项（只取启用中的）
+pub fn get_due_retries(limit: i64) -> Result<Vec<KeywordMonitoring>> {
+    let ids: Vec<i64> = {
+        let conn = get_db().lock();
+        let mut stmt = conn.prepare(
+            "SELECT q.monitoring_id FROM ranking_retry_queue q
+             JOIN keyword_monitoring m ON m.id = q.monitoring_id
+             WHERE m.is_active = 1 AND q.next_retry_at <= datetime('now')
+             ORDER BY q.next_retry_at
+             LIMIT ?1"
+        )?;
+        let ids = stmt.query_map([limit], |row| row.get(0))?.collect::<Result<Vec<_>>>()?;
+        ids
+    };
     if ids.is_empty() {
         return Ok(Vec::new());
     }
+    get_monitoring_by_ids(&ids)
+}
+
+// 监控项的失败状态（只返回有失败记录或被自动暂停的）
+#[derive(Debug, Clone, Serialize)]
+pub struct MonitoringFailureStatus {
+    pub monitoring_id: i64,
+    pub consecutive_failures: i64,
+    pub last_failure_kind: Option<String>,
+    pub last_failure_message: Option<String>,
+    pub last_failure_at: Option<String>,
+    pub auto_paused_at: Option<String>,
+    pub retry_attempts: Option<i64>,
+    pub next_retry_at: Option<String>,
+    pub recent_failures: i64,               // 最近 30 天失败次数
+    pub recent_by_kind: Vec<(String, i64)>, // 最近 30 天按类型统计，次数多的在前
+}
 
+pub fn get_monitoring_failures(product_id: i64) -> Result<Vec<MonitoringFailureStatus>> {
     let conn = get_db().lock();
-    let placeholders: Vec<String> = ids.iter().map(|_| "?".to_string()).collect();
-    let sql = format!(
-        "SELECT id, product_id, keyword, asin, country, priority, is_active,
-                latest_organic_rank, latest_organic_page, latest_sponsored_rank, latest_sponsored_page,
-                image_url, price, reviews_count, rating, last_checked, created_at, tags
-         FROM keyword_monitoring
-         WHERE id IN ({})
-         ORDER BY
-           CASE priority
-             WHEN 'high' THEN 1
-             WHEN 'medium' THEN 2
-             ELSE 3
-           END",
-        placeholders.join(",")
-    );
+    let since = format!("-{} days", FAILURE_STATS_DAYS);
 
-    let mut stmt = conn.prepare(&sql)?;
-    let params: Vec<&dyn rusqlite::ToSql> = ids.iter().map(|id| id as &dyn rusqlite::ToSql).collect();
+    let mut by_kind: std::collections::HashMap<i64, Vec<(String, i64)>> = std::collections::HashMap::new();
+    let mut stmt = conn.prepare(
+        "SELECT f.monitoring_id, f.kind, COUNT(*) AS n
+         FROM ranking_check_failures f
+         JOIN keyword_monitoring m ON m.id = f.monitoring_id
+         WHERE m.product_id = ?1 AND f.created_at >= datetime('now', ?2)
+         GROUP BY f.monitoring_id, f.kind
+         ORDER BY n DESC"
+    )?;
+    let rows = stmt.query_map(rusqlite::params![product_id, since], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+    })?;
+    for row in rows {
+        let (monitoring_id, kind, count) = row?;
+        by_kind.entry(monitoring_id).or_default().push((kind, count));
+    }
 
-    let data = stmt
-        .query_map(params.as_slice(), |row| {
-            Ok(KeywordMonitoring {
-                id: row.get(0)?,
-                product_id: row.get(1)?,
-                keyword: row.get(2)?,
-                asin: row.get(3)?,
-                country: row.get(4)?,
-                priority: row.get(5)?,
-                is_active: row.get::<_, i64>(6)? == 1,
-                latest_organic_rank: row.get(7)?,
-                latest_organic_page: row.get(8)?,
-                latest_sponsored_rank: row.get(9)?,
-                latest_sponsored_page: row.get(10)?,
-                image_url: row.get(11)?,
-                price: row.get(12)?,
-                reviews_count: row.get(13)?,
-                rating: row.get(14)?,
-                last_checked: row.get(15)?,
-                created_at: row.get(16)?,
-                tags: row.get(17)?,
+    let mut stmt = conn.prepare(
+        "SELECT m.id, m.consecutive_failures, m.last_failure_kind, m.last_failure_message, m.last_failure_at,
+                m.auto_paused_at, q.attempts, q.next_retry_at
+         FROM keyword_monitoring m
+         LEFT JOIN ranking_retry_queue q ON q.monitoring_id = m.id
+         WHERE m.product_id = ?1
+           AND (m.consecutive_failures > 0 OR m.auto_paused_at IS NOT NULL OR q.monitoring_id IS NOT NULL)"
+    )?;
+    let mut statuses: Vec<MonitoringFailureStatus> = stmt
+        .query_map([product_id], |row| {
+            Ok(MonitoringFailureStatus {
+                monitoring_id: row.get(0)?,
+                consecutive_failures: row.get(1)?,
+                last_failure_kind: row.get(2)?,
+                last_failure_message: row.get(3)?,
+                last_failure_at: row.get(4)?,
+                auto_paused_at: row.get(5)?,
+                retry_attempts: row.get(6)?,
+                next_retry_at: row.get(7)?,
+                recent_failures: 0,
+                recent_by_kind: Vec::new(),
             })
         })?
         .collect::<Result<Vec<_>>>()?;
 
-    Ok(data)
-}
+    // 目前正常但近期失败较多的也返回，便于发现长期不稳定的关键词
+    for (monitoring_id, kinds) in by_kind {
+        let status = match statuses.iter_mut().position(|s| s.monitoring_id == monitoring_id) {
+            Some(i) => &mut statuses[i],
+            None => {
+                statuses.push(MonitoringFailureStatus {
+                    monitoring_id,
+                    consecutive_failures: 0,
+                    last_failure_kind: None,
+                    last_failure_message: None,
+                    last_failure_at: None,
+                    auto_paused_at: None,
+                    retry_attempts: None,
+                    next_retry_at: None,
+                    recent_failures: 0,
+                    recent_by_kind: Vec::new(),
+                });
+                statuses.last_mut().unwrap()
+            }
+        };
+        status.recent_failures = kinds.iter().map(|(_, n)| n).sum();
+        status.recent_by_kind = kinds;
+    }
 
-// 更新关键词监控标签
-pub fn update_keyword_monitoring_tags(id: i64, tags: Option<String>) -> Result<()> {
-    let conn = get_db().lock();
-    conn.execute(
-        "UPDATE keyword_monitoring SET tags = ?1 WHERE id = ?2",
-        rusqlite::params![tags, id],
-    )?;
-    Ok(())
+    Ok(statuses)
 }
 
-// 批量获取监控项的迷你图数据（最近N天的排名，按日期聚合取最佳）
-pub fn get_monitoring_sparklines(product_id: i64, days: i64) -> Result<Vec<MonitoringSparkline>> {
-    let conn = get_db().lock();
-    let days_str = format!("-{} days", days);
+// ============ 代理池 ============
 
-    // 按日期分组，每天取最佳排名（最小值）
-    let mut stmt = conn.prepare(
-        "SELECT h.monitoring_id, h.check_date,
-                MIN(h.organic_rank) as best_organic_rank,
-                MIN(h.sponsored_rank) as best_sponsored_rank
-         FROM keyword_ranking_history h
-         JOIN keyword_monitoring m ON h.monitoring_id = m.id
-         WHERE m.product_id = ?1 AND h.check_date >= date('now', '+8 hours', ?2)
-         GROUP BY h.monitoring_id, h.check_date
-         ORDER BY h.monitoring_id, h.check_date ASC"
+fn init_proxy_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS proxies (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            url TEXT NOT NULL UNIQUE,
+            label TEXT,
+            proxy_type TEXT NOT NULL DEFAULT 'datacenter',  -- datacenter / residential / mobile
+            countries TEXT NOT NULL DEFAULT '',             -- 分配的站点，逗号分隔；空表示所有站点
+            is_enabled INTEGER NOT NULL DEFAULT 1,
+            disabled_reason TEXT,                           -- auto（连续失败自动停用）/ manual
+            disabled_at TEXT,
+            success_count INTEGER NOT NULL DEFAULT 0,
+            failure_count INTEGER NOT NULL DEFAULT 0,
+            consecutive_failures INTEGER NOT NULL DEFAULT 0,
+            avg_duration_ms INTEGER,                        -- 检测单个关键词的耗时（滑动平均）
+            last_used_at TEXT,
+            last_error TEXT,
+            last_check_at TEXT,
+            last_check_ok INTEGER,
+            last_check_latency_ms INTEGER,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        "
     )?;
 
-    let rows = stmt.query_map(rusqlite::params![product_id, days_str], |row| {
-        Ok((
-            row.get::<_, i64>(0)?,         // monitoring_id
-            row.get::<_, String>(1)?,      // check_date
-            row.get::<_, Option<i64>>(2)?, // organic_rank (best)
-            row.get::<_, Option<i64>>(3)?, // sponsored_rank (best)
-        ))
-    })?;
+    // 旧版的 proxy_list 设置（每行一个代理）导入代理池，导入后删除设置，避免清空代理池后再次导入
+    let legacy: Option<String> = conn
+        .query_row("SELECT value FROM settings WHERE key = 'proxy_list'", [], |row| row.get(0))
+        .ok();
+    if let Some(legacy) = legacy {
+        for url in legacy.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            conn.execute("INSERT OR IGNORE INTO proxies (url) VALUES (?1)", [url])?;
+        }
+        conn.execute("DELETE FROM settings WHERE key = 'proxy_list'", [])?;
+    }
+    Ok(())
+}
 
-    // 按 monitoring_id 分组，同时存储 organic 和 sponsored
-    let mut sparklines_map: std::collections::HashMap<i64, (Vec<Option<i64>>, Vec<Option<i64>>)> =
-        std::collections::HashMap::new();
+#[derive(Debug, Clone, Serialize)]
+pub struct Proxy {
+    pub id: i64,
+    pub url: String,
+    pub label: Option<String>,
+    pub proxy_type: String,
+    pub countries: Vec<String>,
+    pub is_enabled: bool,
+    pub disabled_reason: Option<String>,
+    pub disabled_at: Option<String>,
+    pub success_count: i64,
+    pub failure_count: i64,
+    pub consecutive_failures: i64,
+    pub avg_duration_ms: Option<i64>,
+    pub last_used_at: Option<String>,
+    pub last_error: Option<String>,
+    pub last_check_at: Option<String>,
+    pub last_check_ok: Option<bool>,
+    pub last_check_latency_ms: Option<i64>,
+    pub created_at: String,
+}
 
-    for row in rows {
-        let (monitoring_id, _check_date, organic_rank, sponsored_rank) = row?;
-        let entry = sparklines_map
-            .entry(monitoring_id)
-            .or_insert_with(|| (Vec::new(), Vec::new()));
-        entry.0.push(organic_rank);
-        entry.1.push(sponsored_rank);
-    }
+// 新增或编辑代理时可修改的字段
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProxyInput {
+    pub label: Option<String>,
+    pub proxy_type: String,
+    pub countries: Vec<String>,
+    pub is_enabled: bool,
+}
 
-    // 转换为 Vec<MonitoringSparkline>
-    let sparklines: Vec<MonitoringSparkline> = sparklines_map
-        .into_iter()
-        .map(|(monitoring_id, (organic_ranks, sponsored_ranks))| MonitoringSparkline {
-            monitoring_id,
-            organic_ranks,
-            sponsored_ranks,
-        })
-        .collect();
+const PROXY_COLUMNS: &str = "id, url, label, proxy_type, countries, is_enabled, disabled_reason, disabled_at,
+    success_count, failure_count, consecutive_failures, avg_duration_ms, last_used_at, last_error,
+    last_check_at, last_check_ok, last_check_latency_ms, created_at";
+
+fn row_to_proxy(row: &rusqlite::Row) -> rusqlite::Result<Proxy> {
+    let countries: String = row.get(4)?;
+    Ok(Proxy {
+        id: row.get(0)?,
+        url: row.get(1)?,
+        label: row.get(2)?,
+        proxy_type: row.get(3)?,
+        countries: countries.split(',').filter(|c| !c.is_empty()).map(str::to_string).collect(),
+        is_enabled: row.get::<_, i64>(5)? != 0,
+        disabled_reason: row.get(6)?,
+        disabled_at: row.get(7)?,
+        success_count: row.get(8)?,
+        failure_count: row.get(9)?,
+        consecutive_failures: row.get(10)?,
+        avg_duration_ms: row.get(11)?,
+        last_used_at: row.get(12)?,
+        last_error: row.get(13)?,
+        last_check_at: row.get(14)?,
+        last_check_ok: row.get::<_, Option<i64>>(15)?.map(|v| v != 0),
+        last_check_latency_ms: row.get(16)?,
+        created_at: row.get(17)?,
+    })
+}
 
-    Ok(sparklines)
+fn join_countries(countries: &[String]) -> String {
+    countries.iter().map(|c| c.trim().to_uppercase()).filter(|c| !c.is_empty()).collect::<Vec<_>>().join(",")
 }
 
-// ============ 市场调研监控相关 ============
+pub fn get_proxies() -> Result<Vec<Proxy>> {
+    let conn = get_db().lock();
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM proxies ORDER BY id", PROXY_COLUMNS))?;
+    let proxies = stmt.query_map([], row_to_proxy)?.collect::<Result<Vec<_>>>()?;
+    Ok(proxies)
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BsrSnapshot {
-    pub id: i64,
-    pub marketplace: String,
-    pub category_id: String,
-    pub category_name: Option<String>,
-    pub snapshot_date: String,
-    pub products_json: String,
-    pub product_count: i64,
-    pub created_at: String,
+// 批量添加代理，已存在的地址跳过，返回新增数量
+pub fn add_proxies(urls: &[String], input: &ProxyInput) -> Result<i64> {
+    let conn = get_db().lock();
+    let countries = join_countries(&input.countries);
+    let mut added = 0i64;
+    for url in urls {
+        added += conn.execute(
+            "INSERT OR IGNORE INTO proxies (url, label, proxy_type, countries, is_enabled) VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![url, input.label, input.proxy_type, countries, input.is_enabled as i64],
+        )? as i64;
+    }
+    Ok(added)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MarketResearchTask {
-    pub id: i64,
-    pub name: String,
-    pub marketplace: String,
-    pub category_id: String,
-    pub category_name: Option<String>,
-    pub ai_provider: String,
-    pub ai_model: Option<String>,   // 具体模型名称
-    pub schedule_type: String,      // daily / weekly
-    pub schedule_days: Option<String>,  // JSON array like [1,3,5]
-    pub schedule_time: String,      // HH:MM
-    pub is_enabled: bool,
-    pub last_run_at: Option<String>,
-    pub last_run_status: Option<String>,
-    pub created_at: String,
+// 编辑代理；手动启用时清零连续失败，手动停用时记为 manual（不会被健康检查自动恢复）
+pub fn update_proxy(id: i64, input: &ProxyInput) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute(
+        "UPDATE proxies SET
+            label = ?1, proxy_type = ?2, countries = ?3,
+            consecutive_failures = CASE WHEN ?4 = 1 AND is_enabled = 0 THEN 0 ELSE consecutive_failures END,
+            disabled_reason = CASE WHEN ?4 = 1 THEN NULL WHEN is_enabled = 1 THEN 'manual' ELSE disabled_reason END,
+            disabled_at = CASE WHEN ?4 = 1 THEN NULL WHEN is_enabled = 1 THEN datetime('now') ELSE disabled_at END,
+            is_enabled = ?4
+         WHERE id = ?5",
+        rusqlite::params![input.label, input.proxy_type, join_countries(&input.countries), input.is_enabled as i64, id],
+    )?;
+    Ok(())
+}
+
+pub fn delete_proxy(id: i64) -> Result<()> {
+    let conn = get_db().lock();
+    conn.execute("DELETE FROM proxies WHERE id = ?1", [id])?;
+    Ok(())
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MarketResearchRun {
-    pub id: i64,
-    pub task_id: i64,
-    pub started_at: String,
-    pub ended_at: Option<String>,
-    pub status: String,             // running / completed / failed
-    pub report_summary: Option<String>,
-    pub report_content: Option<String>,
-    pub snapshot_id: Option<i64>,
-    pub error_message: Option<String>,
-    pub created_at: String,
+// 连续失败达到阈值时自动停用，返回是否本次被停用
+fn auto_disable_proxy(conn: &Connection, id: i64, disable_after: i64) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE proxies SET is_enabled = 0, disabled_reason = 'auto', disabled_at = datetime('now')
+         WHERE id = ?1 AND is_enabled = 1 AND consecutive_failures >= ?2",
+        rusqlite::params![id, disable_after],
+    )?;
+    Ok(changed > 0)
 }
 
-// ============ 定时任务记录相关 ============
+/// 记录代理在检测中的一次使用结果（按地址匹配，代理池中没有的地址忽略）
+/// error 为 None 表示成功，返回代理是否因此被自动停用
+pub fn record_proxy_result(url: &str, duration_ms: Option<i64>, error: Option<&str>, disable_after: i64) -> Result<bool> {
+    let conn = get_db().lock();
+    let id: i64 = match conn.query_row("SELECT id FROM proxies WHERE url = ?1", [url], |row| row.get(0)) {
+        Ok(id) => id,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    match error {
+        None => {
+            conn.execute(
+                "UPDATE proxies SET
+                    success_count = success_count + 1, consecutive_failures = 0, last_used_at = datetime('now'),
+                    avg_duration_ms = CASE
+                        WHEN ?1 IS NULL THEN avg_duration_ms
+                        WHEN avg_duration_ms IS NULL THEN ?1
+                        ELSE (avg_duration_ms * 4 + ?1) / 5 END
+                 WHERE id = ?2",
+                rusqlite::params![duration_ms, id],
+            )?;
+            Ok(false)
+        }
+        Some(error) => {
+            conn.execute(
+                "UPDATE proxies SET
+                    failure_count = failure_count + 1, consecutive_failures = consecutive_failures + 1,
+                    last_used_at = datetime('now'), last_error = ?1
+                 WHERE id = ?2",
+                rusqlite::params![error, id],
+            )?;
+            auto_disable_proxy(&conn, id, disable_after)
+        }
+    }
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SchedulerTaskLog {
-    pub id: i64,
-    pub started_at: String,
-    pub ended_at: Option<String>,
-    pub status: String,
-    pub total_keywords: i64,
-    pub success_count: i64,
-    pub failed_count: i64,
-    pub trigger_type: String,
-    pub error_message: Option<String>,
+/// 记录一次健康检查：成功时清零连续失败并恢复被自动停用的代理，失败时累计连续失败
+/// 返回代理是否因此被自动停用
+pub fn record_proxy_check(id: i64, latency_ms: Option<i64>, error: Option<&str>, disable_after: i64) -> Result<bool> {
+    let conn = get_db().lock();
+    match error {
+        None => {
+            conn.execute(
+                "UPDATE proxies SET
+                    last_check_at = datetime('now'), last_check_ok = 1, last_check_latency_ms = ?1,
+                    consecutive_failures = 0,
+                    is_enabled = CASE WHEN disabled_reason = 'auto' THEN 1 ELSE is_enabled END,
+                    disabled_at = CASE WHEN disabled_reason = 'auto' THEN NULL ELSE disabled_at END,
+                    disabled_reason = CASE WHEN disabled_reason = 'auto' THEN NULL ELSE disabled_reason END
+                 WHERE id = ?2",
+                rusqlite::params![latency_ms, id],
+            )?;
+            Ok(false)
+        }
+        Some(error) => {
+            conn.execute(
+                "UPDATE proxies SET
+                    last_check_at = datetime('now'), last_check_ok = 0, last_check_latency_ms = NULL,
+                    consecutive_failures = consecutive_failures + 1, last_error = ?1
+                 WHERE id = ?2",
+                rusqlite::params![error, id],
+            )?;
+            auto_disable_proxy(&conn, id, disable_after)
+        }
+    }
 }
 
-// 创建新的任务记录（开始任务时调用）
-pub fn create_task_log(trigger_type: &str, total_keywords: i64) -> Result<i64> {
+// 需要健康检查的代理：启用中或被自动停用的，且超过间隔未检查
+pub fn get_proxies_due_for_check(interval_minutes: i64) -> Result<Vec<Proxy>> {
     let conn = get_db().lock();
-    conn.execute(
-        "INSERT INTO scheduler_task_logs (started_at, status, total_keywords, trigger_type)
-         VALUES (datetime('now'), 'running', ?1, ?2)",
-        rusqlite::params![total_keywords, trigger_type],
-    )?;
-    Ok(conn.last_insert_rowid())
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM proxies
+         WHERE (is_enabled = 1 OR disabled_reason = 'auto')
+           AND (last_check_at IS NULL OR last_check_at <= datetime('now', ?1))
+         ORDER BY last_check_at IS NOT NULL, last_check_at",
+        PROXY_COLUMNS
+    ))?;
+    let proxies = stmt
+        .query_map([format!("-{} minutes", interval_minutes)], row_to_proxy)?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(proxies)
 }
 
-// 更新任务进度
-pub fn update_task_progress(task_id: i64, success_count: i64, failed_count: i64) -> Result<()> {
+// 清零统计（更换线路后重新计算）
+pub fn reset_proxy_stats(id: i64) -> Result<()> {
     let conn = get_db().lock();
     conn.execute(
-        "UPDATE scheduler_task_logs SET success_count = ?1, failed_count = ?2 WHERE id = ?3",
-        rusqlite::params![success_count, failed_count, task_id],
+        "UPDATE proxies SET success_count = 0, failure_count = 0, consecutive_failures = 0,
+            avg_duration_ms = NULL, last_error = NULL
+         WHERE id = ?1",
+        [id],
     )?;
     Ok(())
 }
 
-// 完成任务记录
-pub fn complete_task_log(task_id: i64, success_count: i64, failed_count: i64) -> Result<()> {
-    let conn = get_db().lock();
-    conn.execute(
-        "UPDATE scheduler_task_logs
-         SET ended_at = datetime('now'), status = 'completed',
-             success_count = ?1, failed_count = ?2
-         WHERE id = ?3",
-        rusqlite::params![success_count, failed_count, task_id],
+// ==================== 站点限速 ====================
+
+fn init_rate_limit_tables(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        -- 每个站点一行，时间均为 UTC（见 rate_limit）
+        CREATE TABLE IF NOT EXISTS crawl_rate_limits (
+            country TEXT PRIMARY KEY,
+            hourly_budget INTEGER NOT NULL,             -- 每小时请求预算，0 表示不限
+            level INTEGER NOT NULL DEFAULT 0,           -- 限速等级，验证码 / 503 后升级
+            success_streak INTEGER NOT NULL DEFAULT 0,
+            window_start TEXT,                          -- 当前计数窗口开始时间
+            window_requests INTEGER NOT NULL DEFAULT 0,
+            cooldown_until TEXT,
+            blocked_count INTEGER NOT NULL DEFAULT 0,
+            last_blocked_at TEXT,
+            last_blocked_reason TEXT,
+            updated_at TEXT
+        );
+        "
     )?;
     Ok(())
 }
 
-// 标记任务失败
-pub fn fail_task_log(task_id: i64, error_message: &str) -> Result<()> {
-    let conn = get_db().lock();
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RateLimitState {
+    pub country: String,
+    pub hourly_budget: i64,
+    pub level: i64,
+    pub success_streak: i64,
+    pub window_start: Option<String>,
+    pub window_requests: i64,
+    pub cooldown_until: Option<String>,
+    pub blocked_count: i64,
+    pub last_blocked_at: Option<String>,
+    pub last_blocked_reason: Option<String>,
+    pub updated_at: Option<String>,
+}
+
+const RATE_LIMIT_COLUMNS: &str = "country, hourly_budget, level, success_streak, window_start, window_requests,
+    cooldown_until, blocked_count, last_blocked_at, last_blocked_reason, updated_at";
+
+fn row_to_rate_limit(row: &rusqlite::Row) -> rusqlite::Result<RateLimitState> {
+    Ok(RateLimitState {
+        country: row.get(0)?,
+        hourly_budget: row.get(1)?,
+        level: row.get(2)?,
+        success_streak: row.get(3)?,
+        window_start: row.get(4)?,
+        window_requests: row.get(5)?,
+        cooldown_until: row.get(6)?,
+        blocked_count: row.get(7)?,
+        last_blocked_at: row.get(8)?,
+        last_blocked_reason: row.get(9)?,
+        updated_at: row.get(10)?,
+    })
+}
+
+// 站点的限速状态，没有记录时为初始状态
+fn load_rate_limit(conn: &Connection, country: &str, default_budget: i64) -> Result<RateLimitState> {
+    let state = conn.query_row(
+        &format!("SELECT {} FROM crawl_rate_limits WHERE country = ?1", RATE_LIMIT_COLUMNS),
+        [country],
+        row_to_rate_limit,
+    );
+    match state {
+        Ok(state) => Ok(state),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(RateLimitState {
+            country: country.to_string(),
+            hourly_budget: default_budget,
+            level: 0,
+            success_streak: 0,
+            window_start: None,
+            window_requests: 0,
+            cooldown_until: None,
+            blocked_count: 0,
+            last_blocked_at: None,
+            last_blocked_reason: None,
+            updated_at: None,
+        }),
+        Err(e) => Err(e),
+    }
+}
+
+fn save_rate_limit(conn: &Connection, state: &RateLimitState) -> Result<()> {
     conn.execute(
-        "UPDATE scheduler_task_logs
-         SET ended_at = datetime('now'), status = 'failed', error_message = ?1
-         WHERE id = ?2",
-        rusqlite::params![error_message, task_id],
+        "INSERT INTO crawl_rate_limits
+            (country, hourly_budget, level, success_streak, window_start, window_requests,
+             cooldown_until, blocked_count, last_blocked_at, last_blocked_reason, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, datetime('now'))
+         ON CONFLICT(country) DO UPDATE SET
+            hourly_budget = excluded.hourly_budget, level = excluded.level,
+            success_streak = excluded.success_streak, window_start = excluded.window_start,
+            window_requests = excluded.window_requests, cooldown_until = excluded.cooldown_until,
+            blocked_count = excluded.blocked_count, last_blocked_at = excluded.last_blocked_at,
+            last_blocked_reason = excluded.last_blocked_reason, updated_at = excluded.updated_at",
+        rusqlite::params![
+            state.country,
+            state.hourly_budget,
+            state.level,
+            state.success_streak,
+            state.window_start,
+            state.window_requests,
+            state.cooldown_until,
+            state.blocked_count,
+            state.last_blocked_at,
+            state.last_blocked_reason,
+        ],
     )?;
     Ok(())
 }
 
-// 获取任务记录列表（最近N条）
-pub fn get_task_logs(limit: i64) -> Result<Vec<SchedulerTaskLog>> {
+pub fn get_rate_limit(country: &str, default_budget: i64) -> Result<RateLimitState> {
     let conn = get_db().lock();
-    let mut stmt = conn.prepare(
-        "SELECT id, started_at, ended_at, status, total_keywords,
-                success_count, failed_count, trigger_type, error_message
-         FROM scheduler_task_logs
-         ORDER BY started_at DESC
-         LIMIT ?1"
-    )?;
-
-    let logs = stmt.query_map([limit], |row| {
-        Ok(SchedulerTaskLog {
-            id: row.get(0)?,
-            started_at: row.get(1)?,
-            ended_at: row.get(2)?,
-            status: row.get(3)?,
-            total_keywords: row.get(4)?,
-            success_count: row.get(5)?,
-            failed_count: row.get(6)?,
-            trigger_type: row.get(7)?,
-            error_message: row.get(8)?,
-        })
-    })?
-    .collect::<Result<Vec<_>>>()?;
-
-    Ok(logs)
+    load_rate_limit(&conn, country, default_budget)
 }
 
-// 获取正在运行的任务
-pub fn get_running_task() -> Result<Option<SchedulerTaskLog>> {
+/// 读取、修改并保存站点的限速状态；持有数据库锁期间完成，并发的检测任务不会互相覆盖
+pub fn update_rate_limit(
+    country: &str,
+    default_budget: i64,
+    update: impl FnOnce(&mut RateLimitState),
+) -> Result<RateLimitState> {
     let conn = get_db().lock();
-    let mut stmt = conn.prepare(
-        "SELECT id, started_at, ended_at, status, total_keywords,
-                success_count, failed_count, trigger_type, error_message
-         FROM scheduler_task_logs
-         WHERE status = 'running'
-         ORDER BY started_at DESC
-         LIMIT 1"
-    )?;
+    let mut state = load_rate_limit(&conn, country, default_budget)?;
+    update(&mut state);
+    save_rate_limit(&conn, &state)?;
+    Ok(state)
+}
 
-    let mut rows = stmt.query([])?;
-    if let Some(row) = rows.next()? {
-        Ok(Some(SchedulerTaskLog {
-            id: row.get(0)?,
-            started_at: row.get(1)?,
-            ended_at: row.get(2)?,
-            status: row.get(3)?,
-            total_keywords: row.get(4)?,
-            success_count: row.get(5)?,
-            failed_count: row.get(6)?,
-            trigger_type: row.get(7)?,
-            error_message: row.get(8)?,
-        }))
-    } else {
-        Ok(None)
+// 所有站点的限速状态（没有记录的站点用初始状态补齐）
+pub fn get_rate_limits(countries: &[&str], default_budget: i64) -> Result<Vec<RateLimitState>> {
+    let conn = get_db().lock();
+    let mut states = countries
+        .iter()
+        .map(|country| load_rate_limit(&conn, country, default_budget))
+        .collect::<Result<Vec<_>>>()?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM crawl_rate_limits ORDER BY country", RATE_LIMIT_COLUMNS))?;
+    for state in stmt.query_map([], row_to_rate_limit)? {
+        let state = state?;
+        if !states.iter().any(|s| s.country == state.country) {
+            states.push(state);
+        }
     }
+    Ok(states)
 }
 
-// 清空任务记录
-pub fn clear_task_logs() -> Result<()> {
+// 手动恢复：清除冷却、限速等级和本小时计数（保留预算和触发记录）
+pub fn reset_rate_limit(country: &str) -> Result<()> {
     let conn = get_db().lock();
-    conn.execute("DELETE FROM scheduler_task_logs", [])?;
+    conn.execute(
+        "UPDATE crawl_rate_limits SET level = 0, success_streak = 0, cooldown_until = NULL,
+            window_start = NULL, window_requests = 0, updated_at = datetime('now')
+         WHERE country = ?1",
+        [country],
+    )?;
     Ok(())
 }
 
@@ -3299,6 +6858,8 @@ pub struct OptimizationEvent {
     pub affected_keywords: Option<String>,   // 关联关键词 JSON 数组（可选）
     pub screenshots: Option<String>,         // 截图文件名 JSON 数组（可选）
     pub created_at: String,
+    #[serde(skip_deserializing)]
+    pub impact: Option<crate::event_impact::EventImpact>,  // 前后排名对比（仅列表查询时计算）
 }
 
 // 添加优化事件
@@ -3327,6 +6888,7 @@ pub fn get_optimization_events(
     product_id: i64,
     start_date: Option<String>,
     end_date: Option<String>,
+    window_days: i64,
 ) -> Result<Vec<OptimizationEvent>> {
     let conn = get_db().lock();
 
@@ -3354,7 +6916,7 @@ pub fn get_optimization_events(
     let params_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
     let mut stmt = conn.prepare(&sql)?;
 
-    let events = stmt.query_map(params_refs.as_slice(), |row| {
+    let mut events = stmt.query_map(params_refs.as_slice(), |row| {
         Ok(OptimizationEvent {
             id: row.get(0)?,
             product_id: row.get(1)?,
@@ -3367,13 +6929,78 @@ pub fn get_optimization_events(
             affected_keywords: row.get(8)?,
             screenshots: row.get(9)?,
             created_at: row.get(10)?,
+            impact: None,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
 
+    let today = chrono::Local::now().date_naive();
+    for event in events.iter_mut() {
+        let keywords = get_event_affected_keywords(&conn, event, window_days)?;
+        event.impact = crate::event_impact::analyze_event(&event.event_date, keywords, window_days, today);
+    }
+
     Ok(events)
 }
 
+// 事件关联的监控项及其前后窗口内的排名历史
+// 指定了关键词按关键词匹配，指定了 ASIN 按 ASIN 匹配，都没有则为整个产品
+fn get_event_affected_keywords(
+    conn: &Connection,
+    event: &OptimizationEvent,
+    window_days: i64,
+) -> Result<Vec<crate::event_impact::MonitoredKeyword>> {
+    let parse_list = |value: &Option<String>| -> Vec<String> {
+        match value.as_deref().map(str::trim) {
+            Some(v) if v.starts_with('[') => serde_json::from_str(v).unwrap_or_default(),
+            Some(v) if !v.is_empty() => vec![v.to_string()],
+            _ => Vec::new(),
+        }
+    };
+    let keywords: HashSet<String> = parse_list(&event.affected_keywords).iter().map(|k| k.to_lowercase()).collect();
+    let asins: HashSet<String> = parse_list(&event.target_asin).iter().map(|a| a.to_uppercase()).collect();
+
+    let mut stmt = conn.prepare(
+        "SELECT id, keyword, asin, country FROM keyword_monitoring WHERE product_id = ?1"
+    )?;
+    let monitored = stmt
+        .query_map([event.product_id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+        })?
+        .collect::<Result<Vec<_>>>()?;
+
+    // 原始检测和日汇总（周汇总粒度太粗，不参与前后对比）
+    let mut history_stmt = conn.prepare(&format!(
+        "SELECT period, organic_sum / NULLIF(organic_count, 0), sponsored_sum / NULLIF(sponsored_count, 0)
+         FROM ({})
+         WHERE source != 'week' AND period BETWEEN date(?2, ?3) AND date(?2, ?4)
+         ORDER BY period",
+        RANKING_POINTS_SQL
+    ))?;
+    let (from, to) = (format!("-{} days", window_days), format!("+{} days", window_days));
+
+    let mut result = Vec::new();
+    for (monitoring_id, keyword, asin, country) in monitored {
+        if (!keywords.is_empty() && !keywords.contains(&keyword.to_lowercase()))
+            || (!asins.is_empty() && !asins.contains(&asin.to_uppercase()))
+        {
+            continue;
+        }
+        let history = history_stmt
+            .query_map(rusqlite::params![monitoring_id, event.event_date, from, to], |row| {
+                Ok(crate::event_impact::RankPoint {
+                    date: row.get(0)?,
+                    organic_position: row.get(1)?,
+                    sponsored_rank: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>>>()?;
+        result.push(crate::event_impact::MonitoredKeyword { monitoring_id, keyword, asin, country, history });
+    }
+
+    Ok(result)
+}
+
 // 更新优化事件
 pub fn update_optimization_event(
     id: i64,
@@ -5268,7 +8895,8 @@ pub fn sc_get_project_keywords(project_id: i64, limit: i64) -> Result<Vec<Keywor
                 click_rate, conversion_competition, competition_level, natural_position_flow,
                 top3_click_share, avg_conversion_share, asin_count,
                 traffic_level, negative_word, orderliness, phrase_tag,
-                primary_category, secondary_category, search_intent, traffic_share, asin_data
+                primary_category, secondary_category, search_intent, traffic_share, asin_data,
+                search_intent_source, search_intent_confidence, brand_name, brand_type
          FROM keyword_data
          WHERE product_id = ?1
          ORDER BY avg_search_volume DESC NULLS LAST
@@ -5304,6 +8932,10 @@ pub fn sc_get_project_keywords(project_id: i64, limit: i64) -> Result<Vec<Keywor
             search_intent: row.get(24)?,
             traffic_share: row.get(25)?,
             asin_data: row.get(26)?,
+            search_intent_source: row.get(27)?,
+            search_intent_confidence: row.get(28)?,
+            brand_name: row.get(29)?,
+            brand_type: row.get(30)?,
         })
     })?;
 
@@ -5852,13 +9484,20 @@ pub fn save_bsr_snapshot(
     let conn = get_db().lock();
     let today = chrono::Local::now().format("%Y-%m-%d").to_string();
 
-    conn.execute(
-        "INSERT OR REPLACE INTO bsr_snapshots
+    // 使用 UPSERT 而不是 INSERT OR REPLACE：REPLACE 会先删除旧行，
+    // 已被 market_research_runs.snapshot_id 引用的快照会触发外键错误
+    conn.query_row(
+        "INSERT INTO bsr_snapshots
          (marketplace, category_id, category_name, snapshot_date, products_json, product_count)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(marketplace, category_id, snapshot_date) DO UPDATE SET
+            category_name = excluded.category_name,
+            products_json = excluded.products_json,
+            product_count = excluded.product_count
+         RETURNING id",
         rusqlite::params![marketplace, category_id, category_name, today, products_json, product_count],
-    )?;
-    Ok(conn.last_insert_rowid())
+        |row| row.get(0),
+    )
 }
 
 pub fn get_bsr_snapshot(marketplace: &str, category_id: &str, date: &str) -> Result<Option<BsrSnapshot>> {
@@ -6465,11 +10104,14 @@ pub fn update_competitor_task_status(id: i64, status: &str) -> Result<()> {
 
 pub fn add_competitor_asin(task_id: i64, asin: &str, title: Option<&str>, tags: Option<&str>) -> Result<i64> {
     let conn = get_db().lock();
-    conn.execute(
-        "INSERT OR REPLACE INTO competitor_asins (task_id, asin, title, tags) VALUES (?1, ?2, ?3, ?4)",
+    // 使用 UPSERT 保留原有行 ID，避免 REPLACE 级联删除该 ASIN 的历史快照
+    conn.query_row(
+        "INSERT INTO competitor_asins (task_id, asin, title, tags) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(task_id, asin) DO UPDATE SET title = excluded.title, tags = excluded.tags
+         RETURNING id",
         rusqlite::params![task_id, asin, title, tags],
-    )?;
-    Ok(conn.last_insert_rowid())
+        |row| row.get(0),
+    )
 }
 
 pub fn remove_competitor_asin(task_id: i64, asin: &str) -> Result<()> {
@@ -7465,6 +11107,7 @@ pub fn get_week_optimization_events_all(week_start: &str, week_end: &str) -> Res
             affected_keywords: row.get(8)?,
             screenshots: row.get(9)?,
             created_at: row.get(10)?,
+            impact: None,
         })
     })?
     .collect::<Result<Vec<_>>>()?;
@@ -7558,3 +11201,305 @@ pub fn delete_weekly_report(week_start: &str) -> Result<()> {
     conn.execute("DELETE FROM weekly_reports WHERE week_start = ?1", [week_start])?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repair_orphans() {
+        let conn = Connection::open_in_memory().unwrap();
+        // 模拟外键关闭期间写入的历史数据
+        conn.execute_batch(
+            "
+            PRAGMA foreign_keys = OFF;
+            CREATE TABLE products (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE keywords (
+                id INTEGER PRIMARY KEY,
+                product_id INTEGER NOT NULL,
+                FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+            );
+            CREATE TABLE keyword_roots (
+                keyword_id INTEGER NOT NULL,
+                root_id INTEGER NOT NULL,
+                FOREIGN KEY (keyword_id) REFERENCES keywords(id) ON DELETE CASCADE
+            );
+            CREATE TABLE sc_projects (
+                id INTEGER PRIMARY KEY,
+                product_id INTEGER REFERENCES products(id)
+            );
+            INSERT INTO products (id, name) VALUES (1, 'a');
+            INSERT INTO keywords (id, product_id) VALUES (1, 1), (2, 99);
+            INSERT INTO keyword_roots (keyword_id, root_id) VALUES (1, 1), (2, 1);
+            INSERT INTO sc_projects (id, product_id) VALUES (1, 99);
+            ",
+        )
+        .unwrap();
+
+        let issues = collect_orphan_issues(&conn).unwrap();
+        assert_eq!(issues.len(), 2);
+        assert!(issues.iter().any(|i| i.table == "keywords" && i.action == "delete"));
+        assert!(issues.iter().any(|i| i.table == "sc_projects" && i.action == "set_null"));
+
+        // keywords(2) 删除后 keyword_roots 中引用它的行在下一轮被清理
+        assert_eq!(repair_orphans(&conn).unwrap(), 3);
+        assert!(scan_orphans(&conn).unwrap().is_empty());
+
+        let keyword_roots: i64 = conn.query_row("SELECT COUNT(*) FROM keyword_roots", [], |r| r.get(0)).unwrap();
+        assert_eq!(keyword_roots, 1);
+        let project_product: Option<i64> = conn.query_row("SELECT product_id FROM sc_projects WHERE id = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(project_product, None);
+    }
+
+    #[test]
+    fn test_compact_ranking_history() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            PRAGMA foreign_keys = ON;
+            CREATE TABLE keyword_monitoring (id INTEGER PRIMARY KEY);
+            CREATE TABLE keyword_ranking_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                monitoring_id INTEGER NOT NULL,
+                check_date DATE NOT NULL,
+                organic_rank INTEGER,
+                organic_page INTEGER,
+                sponsored_rank INTEGER,
+                sponsored_page INTEGER,
+                organic_position INTEGER,
+                checked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+                location TEXT
+            );
+            INSERT INTO keyword_monitoring (id) VALUES (1);
+            -- 周一两次检测（第 2 页第 2 名，第 1 页只有 42 个自然结果 = 绝对位置 44）、
+            -- 周二一次未上榜和一次旧版本记录（第 3 页没有绝对位置）、3 月一次保留原始数据；
+            -- 周一另有一次按邮编 90001 的检测，单独汇总
+            INSERT INTO keyword_ranking_history (monitoring_id, check_date, organic_rank, organic_page, organic_position, sponsored_rank, checked_at, location) VALUES
+                (1, '2024-01-01', 10, 1, 10, 3, '2024-01-01 01:00:00', NULL),
+                (1, '2024-01-01', 2, 2, 44, NULL, '2024-01-01 10:00:00', NULL),
+                (1, '2024-01-01', 1, 1, 1, NULL, '2024-01-01 11:00:00', '90001'),
+                (1, '2024-01-02', NULL, NULL, NULL, NULL, '2024-01-02 01:00:00', NULL),
+                (1, '2024-01-02', 7, 3, NULL, NULL, '2024-01-02 02:00:00', NULL),
+                (1, '2024-03-01', 5, 1, 5, NULL, '2024-03-01 01:00:00', NULL);
+            ",
+        )
+        .unwrap();
+        init_ranking_rollup_tables(&conn).unwrap();
+
+        let (raw, daily) = compact_ranking_history(&conn, "2024-02-01", "2023-12-01").unwrap();
+        assert_eq!((raw, daily), (5, 0));
+        let row: (i64, i64, i64, f64, i64, i64, i64, i64) = conn
+            .query_row(
+                "SELECT check_count, organic_count, organic_best, organic_avg, organic_worst, sponsored_best,
+                        organic_best_page, organic_best_rank
+                 FROM ranking_history_daily WHERE period_start = '2024-01-01' AND location = ''",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?, r.get(6)?, r.get(7)?)),
+            )
+            .unwrap();
+        assert_eq!(row, (2, 2, 10, 27.0, 44, 3, 1, 10));
+        let zip: (i64, i64) = conn
+            .query_row(
+                "SELECT check_count, organic_best FROM ranking_history_daily WHERE period_start = '2024-01-01' AND location = '90001'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(zip, (1, 1));
+
+        // 没有绝对位置的旧记录只保留页码和页内排名
+        let legacy: (i64, i64, Option<i64>, i64, i64) = conn
+            .query_row(
+                "SELECT check_count, organic_count, organic_best, organic_best_page, organic_best_rank
+                 FROM ranking_history_daily WHERE period_start = '2024-01-02'",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?)),
+            )
+            .unwrap();
+        assert_eq!(legacy, (2, 0, None, 3, 7));
+
+        // 迟到的原始数据合并进已有的日汇总，最佳排名不变
+        conn.execute(
+            "INSERT INTO keyword_ranking_history (monitoring_id, check_date, organic_rank, organic_page, organic_position)
+             VALUES (1, '2024-01-01', 30, 1, 30)",
+            [],
+        )
+        .unwrap();
+        compact_ranking_history(&conn, "2024-02-01", "2023-12-01").unwrap();
+        let (count, avg, best_rank): (i64, f64, i64) = conn
+            .query_row(
+                "SELECT check_count, organic_avg, organic_best_rank FROM ranking_history_daily
+                 WHERE period_start = '2024-01-01' AND location = ''",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!((count, avg, best_rank), (3, 28.0, 10));
+
+        // 日汇总再汇总为周（周一开始）
+        let (_, daily) = compact_ranking_history(&conn, "2024-02-01", "2024-02-01").unwrap();
+        assert_eq!(daily, 3);
+        let weekly_rows: i64 = conn.query_row("SELECT COUNT(*) FROM ranking_history_weekly", [], |r| r.get(0)).unwrap();
+        assert_eq!(weekly_rows, 2);
+        let week: (String, i64, i64, f64, i64, i64) = conn
+            .query_row(
+                "SELECT period_start, check_count, organic_count, organic_avg, organic_best_page, organic_best_rank
+                 FROM ranking_history_weekly WHERE location = ''",
+                [],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?, r.get(4)?, r.get(5)?)),
+            )
+            .unwrap();
+        assert_eq!(week, ("2024-01-01".to_string(), 5, 3, 28.0, 1, 10));
+        let raw_left: i64 = conn.query_row("SELECT COUNT(*) FROM keyword_ranking_history", [], |r| r.get(0)).unwrap();
+        assert_eq!(raw_left, 1);
+    }
+
+    #[test]
+    fn test_migrate_keyword_monitoring_location() {
+        let conn = Connection::open_in_memory().unwrap();
+        // 与 init_db 一致：迁移阶段关闭外键，重建父表时不级联删除排名历史
+        conn.execute_batch(
+            "
+            PRAGMA foreign_keys = OFF;
+            CREATE TABLE keyword_monitoring (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                product_id INTEGER NOT NULL,
+                keyword TEXT NOT NULL,
+                asin TEXT NOT NULL,
+                country TEXT NOT NULL,
+                is_active INTEGER DEFAULT 1,
+                UNIQUE(keyword, asin, country, product_id)
+            );
+            CREATE TABLE keyword_ranking_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                monitoring_id INTEGER NOT NULL,
+                organic_rank INTEGER,
+                FOREIGN KEY (monitoring_id) REFERENCES keyword_monitoring(id) ON DELETE CASCADE
+            );
+            INSERT INTO keyword_monitoring (id, product_id, keyword, asin, country) VALUES (1, 1, 'desk lamp', 'B000TEST01', 'US');
+            INSERT INTO keyword_ranking_history (monitoring_id, organic_rank) VALUES (1, 5);
+            ",
+        )
+        .unwrap();
+
+        migrate_keyword_monitoring_location(&conn).unwrap();
+        // 重复执行不报错
+        migrate_keyword_monitoring_location(&conn).unwrap();
+
+        // 原有监控项邮编为空（使用站点设置），同一关键词可以按其他邮编再添加一条
+        let location: String = conn.query_row("SELECT location FROM keyword_monitoring WHERE id = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(location, "");
+        conn.execute(
+            "INSERT INTO keyword_monitoring (product_id, keyword, asin, country, location) VALUES (1, 'desk lamp', 'B000TEST01', 'US', '94105')",
+            [],
+        )
+        .unwrap();
+        assert!(conn
+            .execute(
+                "INSERT INTO keyword_monitoring (product_id, keyword, asin, country) VALUES (1, 'desk lamp', 'B000TEST01', 'US')",
+                [],
+            )
+            .is_err());
+
+        conn.execute("INSERT INTO keyword_ranking_history (monitoring_id, organic_rank, location) VALUES (1, 3, '10001')", [])
+            .unwrap();
+        let history: i64 = conn.query_row("SELECT COUNT(*) FROM keyword_ranking_history WHERE monitoring_id = 1", [], |r| r.get(0)).unwrap();
+        assert_eq!(history, 2);
+    }
+
+    #[test]
+    fn test_evaluate_alert_rules_scope_and_snooze() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE products (id INTEGER PRIMARY KEY, name TEXT);
+            CREATE TABLE keyword_monitoring (
+                id INTEGER PRIMARY KEY, product_id INTEGER, keyword TEXT, asin TEXT, country TEXT, tags TEXT
+            );
+            CREATE TABLE keyword_ranking_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, monitoring_id INTEGER, organic_rank INTEGER,
+                organic_page INTEGER, organic_position INTEGER, checked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+            );
+            INSERT INTO products (id, name) VALUES (1, 'a');
+            INSERT INTO keyword_monitoring VALUES (1, 1, 'desk lamp', 'B000TEST01', 'US', '[\"high_traffic\"]');
+            -- 第 2 页第 1 名的页内排名更小，但不是窗口内的最佳排名
+            INSERT INTO keyword_ranking_history (monitoring_id, organic_rank, organic_page, organic_position)
+                VALUES (1, 1, 2, 45), (1, 4, 1, 4), (1, 30, 1, 30);
+            ",
+        )
+        .unwrap();
+        init_alert_tables(&conn).unwrap();
+        init_notification_channel_tables(&conn).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO alert_rules (name, condition_type, threshold, window_days, tag)
+                VALUES ('大词下滑', 'rank_drop', 10, 7, 'high_traffic');
+            INSERT INTO alert_rules (name, condition_type, threshold, country)
+                VALUES ('德国 Top10', 'rank_crosses', 10, 'DE');
+            ",
+        )
+        .unwrap();
+
+        let snapshot = |rank: Option<i64>| crate::alerts::CheckSnapshot { checked: true, organic_rank: rank, ..Default::default() };
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM alert_history", [], |r| r.get(0)).unwrap()
+        };
+
+        // 近 7 天最佳第 4 名 → 第 30 名；DE 规则不在范围内
+        assert_eq!(evaluate_alert_rules(&conn, 1, &snapshot(Some(6)), &snapshot(Some(30))).unwrap(), 1);
+        let message: String = conn.query_row("SELECT message FROM alert_history", [], |r| r.get(0)).unwrap();
+        assert!(message.contains("下降 26 名：最佳第4名"));
+        // 未确认前不重复触发
+        assert_eq!(evaluate_alert_rules(&conn, 1, &snapshot(Some(30)), &snapshot(Some(31))).unwrap(), 0);
+
+        // 暂停期间不触发，确认后可再次触发
+        conn.execute("UPDATE alert_history SET status = 'snoozed', snoozed_until = datetime('now', '+1 hours')", []).unwrap();
+        assert_eq!(evaluate_alert_rules(&conn, 1, &snapshot(Some(30)), &snapshot(Some(31))).unwrap(), 0);
+        conn.execute("UPDATE alert_history SET status = 'acknowledged', snoozed_until = NULL", []).unwrap();
+        assert_eq!(evaluate_alert_rules(&conn, 1, &snapshot(Some(30)), &snapshot(Some(31))).unwrap(), 1);
+        assert_eq!(count(&conn), 2);
+    }
+
+    #[test]
+    fn test_summarize_task_items() {
+        let item = |country: &str, status: &str, kind: Option<&str>, duration: Option<i64>, proxy: Option<&str>| TaskItem {
+            id: 0,
+            monitoring_id: Some(1),
+            keyword: "desk lamp".to_string(),
+            asin: "B000TEST01".to_string(),
+            country: country.to_string(),
+            status: status.to_string(),
+            failure_kind: kind.map(str::to_string),
+            error: None,
+            warning: None,
+            organic_rank: None,
+            organic_page: None,
+            sponsored_rank: None,
+            duration_ms: duration,
+            proxy: proxy.map(str::to_string),
+            pages_checked: duration.map(|_| 2),
+            checked_at: String::new(),
+        };
+        let mut items: Vec<TaskItem> = (1..=10)
+            .map(|i| item("US", "success", None, Some(i * 1000), Some("http://p1:8080")))
+            .collect();
+        items.push(item("DE", "failed", Some("captcha"), None, Some("http://p2:8080")));
+        items.push(item("DE", "failed", Some("captcha"), Some(30000), None));
+        items.push(item("DE", "warning", Some("geo_restricted"), Some(500), None));
+
+        let stats = summarize_task_items(&items);
+        assert_eq!((stats.item_count, stats.success_count, stats.warning_count, stats.failed_count), (13, 10, 1, 2));
+        // 12 个耗时：500, 1000..10000, 30000
+        assert_eq!(stats.duration_p50_ms, Some(5000));
+        assert_eq!(stats.duration_p90_ms, Some(10000));
+        assert_eq!(stats.duration_p95_ms, Some(30000));
+        assert_eq!(stats.duration_max_ms, Some(30000));
+        assert_eq!(stats.avg_pages_checked, Some(2.0));
+        assert_eq!(stats.failures_by_kind, vec![("captcha".to_string(), 2), ("geo_restricted".to_string(), 1)]);
+        assert_eq!(stats.by_country[0], TaskGroupStat { key: "DE".to_string(), total: 3, failed: 2, duration_p50_ms: Some(500) });
+        assert_eq!(stats.by_proxy.iter().map(|g| g.key.as_str()).collect::<Vec<_>>(), vec!["直连", "http://p2:8080", "http://p1:8080"]);
+
+        let empty = summarize_task_items(&[]);
+        assert_eq!((empty.item_count, empty.duration_p50_ms, empty.avg_pages_checked), (0, None, None));
+    }
+}
@@ -0,0 +1,154 @@
+// 配送地址（邮编）：搜索排名和价格随配送地址变化，每个站点可设置爬取使用的邮编，
+// 监控项还可以单独指定邮编（同一关键词按多个地区分别监控）；优先级：监控项 > 站点设置 > 默认邮编
+// 站点设置保存为 JSON {站点: 邮编}，通过环境变量 LOCATIONS_ENV 传给 Python 脚本（scripts/delivery_location.py）
+// 检测结果和排名历史记录实际使用的邮编，修改设置前后的排名可以区分
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+
+pub const LOCATIONS_ENV: &str = "THESAURUS_DELIVERY_LOCATIONS";
+
+// 设置项：各站点的邮编（JSON），未设置的站点使用默认邮编
+pub const SETTING_KEY: &str = "delivery_locations";
+
+// 默认邮编，与 amazon_crawler_playwright.py 的 COUNTRY_CONFIG 一致
+const DEFAULT_ZIPCODES: &[(&str, &str)] = &[
+    ("US", "10001"),
+    ("UK", "SW1A 1AA"),
+    ("DE", "10115"),
+    ("FR", "75001"),
+    ("IT", "00100"),
+    ("ES", "28001"),
+    ("JP", "100-0001"),
+];
+
+const MAX_ZIPCODE_LEN: usize = 12;
+
+/// 设置界面的一行：站点、默认邮编、设置的邮编（为空时使用默认）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MarketplaceLocation {
+    pub country: String,
+    pub default_zipcode: String,
+    pub zipcode: Option<String>,
+}
+
+pub fn default_zipcode(country: &str) -> &'static str {
+    let country = country.to_uppercase();
+    DEFAULT_ZIPCODES
+        .iter()
+        .find(|(c, _)| *c == country)
+        .map(|(_, zip)| *zip)
+        .unwrap_or(DEFAULT_ZIPCODES[0].1)
+}
+
+/// 整理用户输入的邮编：去掉首尾空白，空值返回 None；只允许字母、数字、空格和 "-"
+pub fn normalize(zipcode: &str) -> Result<Option<String>, String> {
+    let zipcode = zipcode.trim();
+    if zipcode.is_empty() {
+        return Ok(None);
+    }
+    let valid = zipcode.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ' || c == '-');
+    if !valid || zipcode.chars().count() > MAX_ZIPCODE_LEN {
+        return Err(format!("邮编格式无效: {}", zipcode));
+    }
+    Ok(Some(zipcode.to_uppercase()))
+}
+
+fn parse(json: Option<&str>) -> HashMap<String, String> {
+    json.and_then(|json| serde_json::from_str::<HashMap<String, String>>(json).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(country, zip)| Some((country.to_uppercase(), normalize(&zip).ok()??)))
+        .collect()
+}
+
+// 站点设置；数据库未初始化（如录制数据回放测试）时视为未设置
+fn configured() -> HashMap<String, String> {
+    if !db::is_initialized() {
+        return HashMap::new();
+    }
+    parse(db::get_setting(SETTING_KEY).ok().flatten().as_deref())
+}
+
+fn resolve_in(configured: &HashMap<String, String>, country: &str, location: Option<&str>) -> String {
+    if let Some(zip) = location.and_then(|zip| normalize(zip).ok().flatten()) {
+        return zip;
+    }
+    configured
+        .get(&country.to_uppercase())
+        .cloned()
+        .unwrap_or_else(|| default_zipcode(country).to_string())
+}
+
+/// 爬取实际使用的邮编（监控项指定的邮编优先）
+pub fn resolve(country: &str, location: Option<&str>) -> String {
+    resolve_in(&configured(), country, location)
+}
+
+/// 传给 Python 脚本的站点设置，未设置任何站点时返回 None
+pub fn env_json() -> Option<String> {
+    let configured = configured();
+    if configured.is_empty() {
+        return None;
+    }
+    serde_json::to_string(&configured).ok()
+}
+
+/// 所有站点的邮编设置
+pub fn list() -> Vec<MarketplaceLocation> {
+    let configured = configured();
+    DEFAULT_ZIPCODES
+        .iter()
+        .map(|(country, default)| MarketplaceLocation {
+            country: country.to_string(),
+            default_zipcode: default.to_string(),
+            zipcode: configured.get(*country).cloned(),
+        })
+        .collect()
+}
+
+/// 保存站点邮编设置：空值或与默认邮编相同的站点不保存
+pub fn save(locations: &[MarketplaceLocation]) -> Result<Vec<MarketplaceLocation>, String> {
+    let mut map = HashMap::new();
+    for location in locations {
+        let country = location.country.to_uppercase();
+        let Some(zip) = normalize(location.zipcode.as_deref().unwrap_or(""))? else { continue };
+        if zip != default_zipcode(&country) {
+            map.insert(country, zip);
+        }
+    }
+    if map.is_empty() {
+        db::delete_setting(SETTING_KEY).map_err(|e| e.to_string())?;
+    } else {
+        let json = serde_json::to_string(&map).map_err(|e| e.to_string())?;
+        db::set_setting(SETTING_KEY, &json).map_err(|e| e.to_string())?;
+    }
+    Ok(list())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_priority_and_normalize() {
+        assert_eq!(normalize("  sw1a 1aa ").unwrap().as_deref(), Some("SW1A 1AA"));
+        assert_eq!(normalize("   ").unwrap(), None);
+        assert!(normalize("90001; DROP").is_err());
+        assert!(normalize("1234567890123").is_err());
+
+        // 设置中的无效值忽略，站点名统一大写
+        let configured = parse(Some(r#"{"us": "90001", "DE": "", "FR": "<script>"}"#));
+        assert_eq!(configured.len(), 1);
+
+        assert_eq!(resolve_in(&configured, "US", Some("94105")), "94105");
+        assert_eq!(resolve_in(&configured, "US", Some(" ")), "90001");
+        assert_eq!(resolve_in(&configured, "us", None), "90001");
+        assert_eq!(resolve_in(&configured, "DE", None), "10115");
+        assert_eq!(resolve_in(&configured, "UK", None), "SW1A 1AA");
+        assert_eq!(default_zipcode("XX"), "10001");
+    }
+}
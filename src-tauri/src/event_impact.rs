@@ -0,0 +1,275 @@
+// 优化事件影响分析：比较事件前后 N 天关联关键词的排名
+// 事件当天不计入任一窗口；显著性用 Welch t 检验（单个关键词）和配对 t 检验（关键词汇总）的 |t| 粗略判断
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+// 默认前后窗口天数
+pub const DEFAULT_WINDOW_DAYS: i64 = 7;
+
+// |t| 阈值：约等于 p < 0.05 / 有一定迹象
+const T_SIGNIFICANT: f64 = 2.0;
+const T_WEAK: f64 = 1.0;
+
+// 每个窗口至少需要的有排名天数 / 汇总至少需要的关键词数
+const MIN_DAYS: usize = 3;
+const MIN_KEYWORDS: usize = 3;
+
+// 排名变化小于 1 位视为持平
+const MIN_RANK_CHANGE: f64 = 1.0;
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Significance {
+    Significant,
+    Weak,
+    None,
+    Insufficient,  // 数据不足
+}
+
+// 事件前后排名对比，delta = 之后 - 之前，负数表示排名上升
+#[derive(Debug, Serialize, Clone)]
+pub struct RankComparison {
+    pub before_mean: Option<f64>,
+    pub after_mean: Option<f64>,
+    pub before_days: usize,
+    pub after_days: usize,
+    pub delta: Option<f64>,
+    pub significance: Significance,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct KeywordImpact {
+    pub monitoring_id: i64,
+    pub keyword: String,
+    pub asin: String,
+    pub country: String,
+    pub organic: RankComparison,
+    pub sponsored: RankComparison,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ImpactSummary {
+    pub keyword_count: usize,     // 前后都有排名的关键词数
+    pub improved: usize,
+    pub worsened: usize,
+    pub mean_delta: Option<f64>,
+    pub significance: Significance,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EventImpact {
+    pub window_days: i64,
+    pub after_complete: bool,     // 事件后的窗口是否已经过完
+    pub organic: ImpactSummary,
+    pub sponsored: ImpactSummary,
+    pub keywords: Vec<KeywordImpact>,
+}
+
+// 某监控项的一条排名历史（原始检测或日汇总的平均值）
+// 自然排名为绝对位置；广告位数量不固定，直接用页内排名
+#[derive(Debug, Clone)]
+pub struct RankPoint {
+    pub date: String,
+    pub organic_position: Option<f64>,
+    pub sponsored_rank: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MonitoredKeyword {
+    pub monitoring_id: i64,
+    pub keyword: String,
+    pub asin: String,
+    pub country: String,
+    pub history: Vec<RankPoint>,
+}
+
+pub fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()
+}
+
+fn organic_position(point: &RankPoint) -> Option<f64> {
+    point.organic_position
+}
+
+fn sponsored_position(point: &RankPoint) -> Option<f64> {
+    point.sponsored_rank
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+fn variance(values: &[f64], mean: f64) -> f64 {
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() as f64 - 1.0)
+}
+
+fn classify(t: f64) -> Significance {
+    if t.abs() >= T_SIGNIFICANT {
+        Significance::Significant
+    } else if t.abs() >= T_WEAK {
+        Significance::Weak
+    } else {
+        Significance::None
+    }
+}
+
+// Welch t 统计量；两组方差都为 0 时，均值不同视为显著
+fn welch_significance(before: &[f64], after: &[f64]) -> Significance {
+    if before.len() < MIN_DAYS || after.len() < MIN_DAYS {
+        return Significance::Insufficient;
+    }
+    let (mb, ma) = (mean(before).unwrap_or(0.0), mean(after).unwrap_or(0.0));
+    let se = (variance(before, mb) / before.len() as f64 + variance(after, ma) / after.len() as f64).sqrt();
+    if se == 0.0 {
+        return if (ma - mb).abs() >= MIN_RANK_CHANGE { Significance::Significant } else { Significance::None };
+    }
+    classify((ma - mb) / se)
+}
+
+// 单个窗口内按天取均值（同一天可能检测多次）
+fn daily_values(history: &[RankPoint], event: NaiveDate, from: i64, to: i64, pick: fn(&RankPoint) -> Option<f64>) -> Vec<f64> {
+    let mut by_day: std::collections::BTreeMap<i64, Vec<f64>> = std::collections::BTreeMap::new();
+    for point in history {
+        let Some(date) = parse_date(&point.date) else { continue };
+        let offset = (date - event).num_days();
+        if offset < from || offset > to {
+            continue;
+        }
+        if let Some(value) = pick(point) {
+            by_day.entry(offset).or_default().push(value);
+        }
+    }
+    by_day.values().filter_map(|v| mean(v)).collect()
+}
+
+fn compare(history: &[RankPoint], event: NaiveDate, window_days: i64, pick: fn(&RankPoint) -> Option<f64>) -> RankComparison {
+    let before = daily_values(history, event, -window_days, -1, pick);
+    let after = daily_values(history, event, 1, window_days, pick);
+    let (before_mean, after_mean) = (mean(&before), mean(&after));
+    RankComparison {
+        before_mean,
+        after_mean,
+        before_days: before.len(),
+        after_days: after.len(),
+        delta: before_mean.zip(after_mean).map(|(b, a)| a - b),
+        significance: welch_significance(&before, &after),
+    }
+}
+
+// 汇总：对各关键词的 delta 做配对 t 检验；关键词太少时沿用单个关键词的结论
+fn summarize(comparisons: &[&RankComparison]) -> ImpactSummary {
+    let deltas: Vec<f64> = comparisons.iter().filter_map(|c| c.delta).collect();
+    let mean_delta = mean(&deltas);
+
+    let significance = if deltas.len() >= MIN_KEYWORDS {
+        let m = mean_delta.unwrap_or(0.0);
+        let se = (variance(&deltas, m) / deltas.len() as f64).sqrt();
+        if se == 0.0 {
+            if m.abs() >= MIN_RANK_CHANGE { Significance::Significant } else { Significance::None }
+        } else {
+            classify(m / se)
+        }
+    } else if let [only] = comparisons.iter().filter(|c| c.delta.is_some()).collect::<Vec<_>>()[..] {
+        only.significance
+    } else {
+        Significance::Insufficient
+    };
+
+    ImpactSummary {
+        keyword_count: deltas.len(),
+        improved: deltas.iter().filter(|d| **d <= -MIN_RANK_CHANGE).count(),
+        worsened: deltas.iter().filter(|d| **d >= MIN_RANK_CHANGE).count(),
+        mean_delta,
+        significance,
+    }
+}
+
+/// 计算单个事件的影响；事件日期无法解析时返回 None
+pub fn analyze_event(event_date: &str, keywords: Vec<MonitoredKeyword>, window_days: i64, today: NaiveDate) -> Option<EventImpact> {
+    let event = parse_date(event_date)?;
+    let window_days = window_days.max(1);
+
+    let keywords: Vec<KeywordImpact> = keywords
+        .into_iter()
+        .map(|k| KeywordImpact {
+            organic: compare(&k.history, event, window_days, organic_position),
+            sponsored: compare(&k.history, event, window_days, sponsored_position),
+            monitoring_id: k.monitoring_id,
+            keyword: k.keyword,
+            asin: k.asin,
+            country: k.country,
+        })
+        .collect();
+
+    Some(EventImpact {
+        window_days,
+        after_complete: (today - event).num_days() > window_days,
+        organic: summarize(&keywords.iter().map(|k| &k.organic).collect::<Vec<_>>()),
+        sponsored: summarize(&keywords.iter().map(|k| &k.sponsored).collect::<Vec<_>>()),
+        keywords,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyword(id: i64, ranks: &[(&str, i64)]) -> MonitoredKeyword {
+        MonitoredKeyword {
+            monitoring_id: id,
+            keyword: format!("kw{}", id),
+            asin: "B0TEST".to_string(),
+            country: "US".to_string(),
+            history: ranks
+                .iter()
+                .map(|(date, rank)| RankPoint {
+                    date: date.to_string(),
+                    organic_position: Some(*rank as f64),
+                    sponsored_rank: None,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_event_impact_improvement() {
+        let today = parse_date("2024-03-30").unwrap();
+        let history = [
+            ("2024-03-07", 40), ("2024-03-08", 42), ("2024-03-09", 38),
+            ("2024-03-10", 1),  // 事件当天不计入
+            ("2024-03-11", 12), ("2024-03-12", 10), ("2024-03-13", 11),
+            ("2024-03-20", 99), // 窗口之外
+        ];
+        let impact = analyze_event("2024-03-10", vec![keyword(1, &history)], 3, today).unwrap();
+        let organic = &impact.keywords[0].organic;
+        assert_eq!((organic.before_days, organic.after_days), (3, 3));
+        assert_eq!(organic.before_mean, Some(40.0));
+        assert_eq!(organic.delta, Some(-29.0));
+        assert_eq!(organic.significance, Significance::Significant);
+        assert!(impact.after_complete);
+
+        // 单个关键词时汇总沿用其结论；广告无数据
+        assert_eq!(impact.organic.improved, 1);
+        assert_eq!(impact.organic.significance, Significance::Significant);
+        assert_eq!(impact.sponsored.significance, Significance::Insufficient);
+    }
+
+    #[test]
+    fn test_event_impact_summary_mixed() {
+        let today = parse_date("2024-03-12").unwrap();
+        let up = [("2024-03-09", 20), ("2024-03-11", 10)];
+        let down = [("2024-03-09", 10), ("2024-03-11", 20)];
+        let page2 = [("2024-03-09", 53), ("2024-03-11", 5)];
+        let third = keyword(3, &page2);
+
+        let impact = analyze_event("2024-03-10", vec![keyword(1, &up), keyword(2, &down), third], 7, today).unwrap();
+        assert!(!impact.after_complete);
+        assert_eq!(impact.keywords[2].organic.before_mean, Some(53.0));
+        assert_eq!(impact.organic.keyword_count, 3);
+        assert_eq!((impact.organic.improved, impact.organic.worsened), (2, 1));
+        // 方向不一致，配对检验不显著；单关键词天数不足
+        assert_eq!(impact.organic.significance, Significance::None);
+        assert_eq!(impact.keywords[0].organic.significance, Significance::Insufficient);
+    }
+}
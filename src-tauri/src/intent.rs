@@ -0,0 +1,322 @@
+// 搜索意图识别：基于规则 + 多语言词典（与停用词表覆盖的语言一致：英/德/法/意/西）
+// 词根的 AI 分类（品牌、颜色、适用人群等）作为额外信号
+
+use crate::db;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+// 意图类型：(代码, 写入 search_intent 的中文标签)
+pub const INTENTS: &[(&str, &str)] = &[
+    ("brand", "品牌"),
+    ("comparison", "对比"),
+    ("gift", "送礼"),
+    ("problem", "问题解决"),
+    ("compatibility", "适配兼容"),
+    ("price", "价格导向"),
+    ("scenario", "场景人群"),
+    ("feature", "功能属性"),
+    ("product_type", "品类"),
+];
+
+pub fn intent_label(code: &str) -> &'static str {
+    INTENTS
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, label)| *label)
+        .unwrap_or("品类")
+}
+
+// 识别结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntentMatch {
+    pub intent: &'static str,
+    pub label: &'static str,
+    pub confidence: f64,
+}
+
+// 根据站点确定词典语言（英语词典始终参与匹配，欧洲站也常有英文搜索词）
+pub fn language_for_country(country: &str) -> &'static str {
+    match country {
+        "DE" => "de",
+        "FR" => "fr",
+        "IT" => "it",
+        "ES" | "MX" => "es",
+        _ => "en",
+    }
+}
+
+// 各意图的词典：(意图, 语言, 词/短语)
+// 短语按整词匹配；"or"、"like"、"best"、"kitchen" 这类在普通搜索词里随处可见的词不收录，
+// 地点类场景只收 "for office" 这样的短语（"office chair"、"kitchen scale" 是品类）
+const LEXICON: &[(&str, &str, &[&str])] = &[
+    // ---- 对比 ----
+    ("comparison", "en", &["vs", "versus", "alternative", "alternatives", "compared", "comparison", "similar to", "instead of", "replacement for"]),
+    ("comparison", "de", &["vs", "alternative", "vergleich", "ähnlich wie", "statt", "ersatz für"]),
+    ("comparison", "fr", &["vs", "alternative", "comparatif", "comparaison", "similaire à"]),
+    ("comparison", "it", &["vs", "alternativa", "confronto", "simile a"]),
+    ("comparison", "es", &["vs", "alternativa", "comparación", "comparativa", "similar a"]),
+    // ---- 送礼 ----
+    ("gift", "en", &["gift", "gifts", "present", "presents", "for him", "for her", "christmas", "xmas", "birthday", "anniversary", "valentine", "valentines", "mothers day", "fathers day", "stocking stuffer", "stocking stuffers", "secret santa"]),
+    ("gift", "de", &["geschenk", "geschenke", "geschenkidee", "geschenkideen", "weihnachten", "weihnachtsgeschenk", "geburtstag", "geburtstagsgeschenk", "jahrestag", "valentinstag", "muttertag", "vatertag", "adventskalender", "für ihn", "für sie"]),
+    ("gift", "fr", &["cadeau", "cadeaux", "idée cadeau", "noël", "noel", "anniversaire", "saint valentin", "fête des mères", "fête des pères", "pour lui", "pour elle"]),
+    ("gift", "it", &["regalo", "regali", "idea regalo", "natale", "compleanno", "anniversario", "san valentino", "festa della mamma", "festa del papà", "per lui", "per lei"]),
+    ("gift", "es", &["regalo", "regalos", "regalo original", "navidad", "cumpleaños", "aniversario", "san valentín", "día de la madre", "día del padre", "para él", "para ella"]),
+    // ---- 问题解决 ----
+    ("problem", "en", &["relief", "pain", "remover", "removal", "repair", "fix", "anti", "prevent", "prevention", "stop", "treatment", "cure", "killer", "repellent", "protector", "protection", "cleaner", "against", "reduce", "reducer", "eliminator", "for back pain", "for sleeping"]),
+    ("problem", "de", &["gegen", "schmerzen", "entferner", "reparatur", "anti", "schutz", "vorbeugung", "reiniger", "linderung", "abwehr"]),
+    ("problem", "fr", &["contre", "douleur", "douleurs", "anti", "réparation", "protection", "nettoyant", "soulagement", "détachant", "répulsif"]),
+    ("problem", "it", &["contro", "dolore", "dolori", "anti", "riparazione", "protezione", "pulitore", "sollievo", "repellente", "rimozione"]),
+    ("problem", "es", &["contra", "dolor", "dolores", "anti", "reparación", "protección", "limpiador", "alivio", "repelente", "quitar", "eliminar"]),
+    // ---- 适配兼容 ----
+    ("compatibility", "en", &["compatible", "compatible with", "replacement", "refill", "refills", "fits", "for iphone", "for samsung", "for ipad", "for macbook", "for car", "adapter", "spare", "parts"]),
+    ("compatibility", "de", &["kompatibel", "passend für", "passend", "ersatz", "ersatzteil", "nachfüllung", "adapter", "fürs auto", "für auto"]),
+    ("compatibility", "fr", &["compatible", "compatible avec", "remplacement", "recharge", "adaptateur", "pièce", "pièces", "pour voiture", "pour la voiture"]),
+    ("compatibility", "it", &["compatibile", "compatibile con", "ricambio", "ricambi", "ricarica", "adattatore", "per auto"]),
+    ("compatibility", "es", &["compatible", "compatible con", "repuesto", "repuestos", "recambio", "adaptador", "para coche", "para el coche"]),
+    // ---- 价格导向（"under 30" 这类价格上限另见 PRICE_CAP_WORDS）----
+    ("price", "en", &["cheap", "cheapest", "budget", "affordable", "deal", "deals", "sale", "discount", "clearance", "bulk", "wholesale", "value pack"]),
+    ("price", "de", &["günstig", "billig", "angebot", "angebote", "reduziert", "rabatt", "sale", "großpackung"]),
+    ("price", "fr", &["pas cher", "moins cher", "promo", "promotion", "soldes", "réduction", "lot"]),
+    ("price", "it", &["economico", "economica", "offerta", "offerte", "sconto", "saldi", "prezzo"]),
+    ("price", "es", &["barato", "barata", "baratos", "oferta", "ofertas", "descuento", "rebajas", "precio"]),
+    // ---- 场景人群 ----
+    ("scenario", "en", &["for men", "for women", "for kids", "for adults", "for baby", "for toddlers", "for boys", "for girls", "for seniors", "for dogs", "for cats", "outdoor", "indoor", "for camping", "for travel", "for office", "for home", "for kitchen", "for bathroom", "for bedroom", "for garden", "for gym", "for school", "for party", "for wedding"]),
+    ("scenario", "de", &["für männer", "für frauen", "für kinder", "herren", "damen", "kinder", "baby", "hund", "katze", "outdoor", "für camping", "für reisen", "fürs büro", "für büro", "für die küche", "für küche", "fürs bad", "für das bad", "für den garten", "für garten", "für die schule", "für party", "für hochzeit"]),
+    ("scenario", "fr", &["pour homme", "pour femme", "pour enfant", "homme", "femme", "enfant", "enfants", "bébé", "chien", "chat", "extérieur", "pour camping", "pour voyage", "pour bureau", "pour le bureau", "pour cuisine", "pour la cuisine", "pour salle de bain", "pour chambre", "pour jardin", "pour le jardin", "pour école", "pour fête", "pour mariage"]),
+    ("scenario", "it", &["per uomo", "per donna", "per bambini", "uomo", "donna", "bambini", "bambino", "neonato", "cane", "gatto", "esterno", "per campeggio", "per viaggio", "per ufficio", "per cucina", "per la cucina", "per bagno", "per camera", "per giardino", "per scuola", "per la scuola", "per feste", "per matrimonio"]),
+    ("scenario", "es", &["para hombre", "para mujer", "para niños", "hombre", "mujer", "niños", "niña", "bebé", "perro", "gato", "exterior", "para camping", "para viaje", "para oficina", "para la oficina", "para cocina", "para la cocina", "para baño", "para dormitorio", "para jardín", "para el jardín", "para escuela", "para fiestas", "para bodas"]),
+    // ---- 功能属性 ----
+    ("feature", "en", &["wireless", "cordless", "waterproof", "portable", "rechargeable", "adjustable", "foldable", "electric", "automatic", "smart", "heavy duty", "large", "small", "mini", "xl", "extra large", "lightweight", "stainless steel", "wood", "wooden", "metal", "plastic", "leather", "cotton", "silicone", "glass", "black", "white", "red", "blue", "green", "pink", "grey", "gray", "gold", "silver", "round", "square", "led", "usb", "bluetooth", "set", "pack"]),
+    ("feature", "de", &["kabellos", "wasserdicht", "tragbar", "wiederaufladbar", "verstellbar", "faltbar", "elektrisch", "automatisch", "groß", "gross", "klein", "leicht", "edelstahl", "holz", "metall", "kunststoff", "leder", "baumwolle", "silikon", "glas", "schwarz", "weiß", "weiss", "rot", "blau", "grün", "rosa", "grau", "gold", "silber", "rund", "eckig", "set"]),
+    ("feature", "fr", &["sans fil", "étanche", "portable", "rechargeable", "réglable", "pliable", "électrique", "automatique", "grand", "grande", "petit", "petite", "léger", "inox", "acier inoxydable", "bois", "métal", "plastique", "cuir", "coton", "silicone", "verre", "noir", "blanc", "rouge", "bleu", "vert", "rose", "gris", "doré", "argent", "rond", "carré", "lot de"]),
+    ("feature", "it", &["senza fili", "wireless", "impermeabile", "portatile", "ricaricabile", "regolabile", "pieghevole", "elettrico", "automatico", "grande", "piccolo", "leggero", "acciaio inox", "acciaio", "legno", "metallo", "plastica", "pelle", "cotone", "silicone", "vetro", "nero", "bianco", "rosso", "blu", "verde", "rosa", "grigio", "oro", "argento", "rotondo", "quadrato", "set"]),
+    ("feature", "es", &["inalámbrico", "inalambrico", "impermeable", "portátil", "portatil", "recargable", "ajustable", "plegable", "eléctrico", "automático", "grande", "pequeño", "ligero", "acero inoxidable", "madera", "metal", "plástico", "cuero", "algodón", "silicona", "vidrio", "negro", "blanco", "rojo", "azul", "verde", "rosa", "gris", "dorado", "plata", "redondo", "cuadrado", "juego de", "set"]),
+];
+
+// 后面跟数字时表示价格上限的词（"under 30"、"unter 50 euro"）；单独出现不算价格意图（"under desk"）
+const PRICE_CAP_WORDS: &[&str] = &["under", "below", "unter", "moins de", "sotto", "menos de"];
+
+// 词根分类（categories.name_en）对应的意图
+fn intent_for_root_category(category: &str) -> Option<&'static str> {
+    match category {
+        "brand" => Some("brand"),
+        "category" => Some("product_type"),
+        "color" | "shape" | "function" | "material" | "size" => Some("feature"),
+        "target_audience" | "scenario" | "location" => Some("scenario"),
+        "holiday" | "emotion" => Some("gift"),
+        "compatibility" => Some("compatibility"),
+        _ => None,
+    }
+}
+
+// 整词/短语匹配：两端补空格后查找
+fn contains_phrase(padded: &str, phrase: &str) -> bool {
+    padded.contains(&format!(" {} ", phrase))
+}
+
+/// 识别单个关键词的搜索意图
+/// root_categories: 词根 -> 该词根的分类（categories.name_en），来自词根 AI 分析
+/// brand_name: 品牌词典标记的品牌（keyword_data.brand_name），有值时按品牌词处理
+pub fn classify_keyword(
+    keyword: &str,
+    language: &str,
+    root_categories: &HashMap<String, Vec<String>>,
+    brand_name: Option<&str>,
+) -> Option<IntentMatch> {
+    let normalized: String = keyword
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '\'' { c } else { ' ' })
+        .collect();
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    if words.is_empty() {
+        return None;
+    }
+    let padded = format!(" {} ", words.join(" "));
+
+    // 每个意图累计得分：第一个信号权重高，后续信号递减
+    let mut scores: HashMap<&'static str, f64> = HashMap::new();
+    let mut add = |intent: &'static str, weight: f64| {
+        let score = scores.entry(intent).or_insert(0.0);
+        *score = if *score == 0.0 { weight } else { *score + weight * 0.3 };
+    };
+
+    for (intent, lang, terms) in LEXICON {
+        if *lang != "en" && *lang != language {
+            continue;
+        }
+        for term in terms.iter() {
+            if contains_phrase(&padded, term) {
+                // 场景人群、功能属性是修饰性信号，与其他意图同时出现时让位；多词短语更具体，权重更高
+                let base = if matches!(*intent, "scenario" | "feature") { 0.5 } else { 0.6 };
+                let weight = if term.contains(' ') { base + 0.1 } else { base };
+                add(intent, weight);
+            }
+        }
+    }
+
+    // "under 30"：价格上限后面跟数字
+    let price_cap = PRICE_CAP_WORDS.iter().any(|cap| {
+        padded
+            .match_indices(&format!(" {} ", cap))
+            .any(|(pos, m)| padded[pos + m.len()..].starts_with(|c: char| c.is_ascii_digit()))
+    });
+    if price_cap {
+        add("price", 0.7);
+    }
+
+    // 含数字的词（10 inch、2 pack、1000mah）通常是规格属性
+    if !price_cap && words.iter().any(|w| w.chars().any(|c| c.is_ascii_digit())) {
+        add("feature", 0.5);
+    }
+
+    // 品牌词典命中（与品牌词根同等的强信号），与品牌词标记保持一致
+    if brand_name.is_some_and(|brand| !brand.trim().is_empty()) {
+        add("brand", 0.85);
+    }
+
+    // 词根分类信号
+    for word in &words {
+        if let Some(categories) = root_categories.get(*word) {
+            for category in categories {
+                if let Some(intent) = intent_for_root_category(category) {
+                    // 品牌词根是强信号（品牌只能由 AI 或人工标注）
+                    let weight = if intent == "brand" { 0.85 } else { 0.5 };
+                    add(intent, weight);
+                }
+            }
+        }
+    }
+
+    // 选择得分最高的意图；同分时按 INTENTS 顺序（越靠前越具体）
+    let best = INTENTS
+        .iter()
+        .filter_map(|(code, _)| scores.get(code).map(|s| (*code, *s)))
+        .fold(None::<(&'static str, f64)>, |best, (code, score)| match best {
+            Some((_, s)) if s >= score => best,
+            _ => Some((code, score)),
+        });
+
+    match best {
+        Some((intent, score)) => Some(IntentMatch {
+            intent,
+            label: intent_label(intent),
+            confidence: (score.min(0.95) * 100.0).round() / 100.0,
+        }),
+        None => {
+            // 没有任何修饰信号：短词视为品类词，越短越确定
+            let confidence = match words.len() {
+                1 | 2 => 0.6,
+                3 => 0.45,
+                _ => 0.3,
+            };
+            Some(IntentMatch {
+                intent: "product_type",
+                label: intent_label("product_type"),
+                confidence,
+            })
+        }
+    }
+}
+
+// 按意图统计
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentCount {
+    pub intent: String,
+    pub label: String,
+    pub count: i64,
+}
+
+// 产品级识别结果汇总
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IntentClassifySummary {
+    pub total_keywords: i64,
+    pub classified: i64,
+    pub skipped_manual: i64,    // 人工或 AI 填写的意图，保持不变
+    pub avg_confidence: f64,
+    pub by_intent: Vec<IntentCount>,
+}
+
+/// 对产品的全部关键词执行搜索意图识别（不覆盖人工/AI 填写的意图）
+pub fn classify_product(product_id: i64) -> Result<IntentClassifySummary, String> {
+    let total_keywords = db::get_keyword_data_stats(product_id).map_err(|e| e.to_string())?;
+    let input = db::get_intent_classification_input(product_id).map_err(|e| e.to_string())?;
+    let language = language_for_country(&input.country);
+
+    let mut updates: Vec<(i64, String, f64)> = Vec::new();
+    let mut counts: HashMap<&'static str, i64> = HashMap::new();
+    for (id, keyword, brand_name) in &input.keywords {
+        if let Some(m) = classify_keyword(keyword, language, &input.root_categories, brand_name.as_deref()) {
+            *counts.entry(m.intent).or_insert(0) += 1;
+            updates.push((*id, m.label.to_string(), m.confidence));
+        }
+    }
+
+    let classified = db::apply_intent_classifications(&updates).map_err(|e| e.to_string())?;
+    let avg_confidence = if updates.is_empty() {
+        0.0
+    } else {
+        let sum: f64 = updates.iter().map(|(_, _, c)| c).sum();
+        (sum / updates.len() as f64 * 100.0).round() / 100.0
+    };
+
+    let by_intent = INTENTS
+        .iter()
+        .filter_map(|(code, label)| {
+            counts.get(code).map(|count| IntentCount {
+                intent: code.to_string(),
+                label: label.to_string(),
+                count: *count,
+            })
+        })
+        .collect();
+
+    Ok(IntentClassifySummary {
+        total_keywords,
+        classified,
+        skipped_manual: total_keywords - input.keywords.len() as i64,
+        avg_confidence,
+        by_intent,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_keyword() {
+        let mut roots: HashMap<String, Vec<String>> = HashMap::new();
+        roots.insert("brightech".to_string(), vec!["brand".to_string()]);
+
+        let intent = |keyword: &str, language: &str| classify_keyword(keyword, language, &roots, None).unwrap().intent;
+
+        assert_eq!(intent("desk lamp", "en"), "product_type");
+        assert_eq!(intent("brightech floor lamp", "en"), "brand");
+        assert_eq!(intent("lamp gifts for mom", "en"), "gift");
+        assert_eq!(classify_keyword("ikea vs brightech", "en", &HashMap::new(), None).unwrap().intent, "comparison");
+        assert_eq!(intent("wireless led lamp", "en"), "feature");
+        assert_eq!(intent("geschenk für männer", "de"), "gift");
+        assert_eq!(intent("lampe contre moustiques", "fr"), "problem");
+
+        // 地点名词本身是品类的一部分，"for office" 才是场景；"for car" 只算适配
+        assert_eq!(intent("office chair", "en"), "product_type");
+        assert_eq!(intent("kitchen scale", "en"), "product_type");
+        assert_eq!(intent("desk lamp for office", "en"), "scenario");
+        assert_eq!(intent("phone holder for car", "en"), "compatibility");
+        assert_eq!(intent("küchenwaage für die küche", "de"), "scenario");
+
+        // "best"、"like"、"or"、"o" 不是意图信号；"under" 后面跟价格才是
+        assert_eq!(intent("best desk lamp", "en"), "product_type");
+        assert_eq!(intent("lamp like sunlight", "en"), "product_type");
+        assert_eq!(intent("black or white lamp", "en"), "feature");
+        assert_eq!(intent("lampada o lampadario", "it"), "product_type");
+        assert_eq!(intent("under desk lamp", "en"), "product_type");
+        assert_eq!(intent("desk lamp under 30", "en"), "price");
+
+        // 品牌词典标记的关键词是品牌意图
+        let m = classify_keyword("anker power bank", "en", &HashMap::new(), Some("Anker")).unwrap();
+        assert_eq!((m.intent, m.confidence), ("brand", 0.85));
+        assert_eq!(classify_keyword("anker power bank", "en", &HashMap::new(), None).unwrap().intent, "product_type");
+    }
+}
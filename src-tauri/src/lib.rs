@@ -6,6 +6,26 @@ mod installer;
 mod knowledge_base;
 mod ai;
 mod keychain;
+mod cli;
+mod csv_text;
+mod pipeline;
+mod intent;
+mod brand;
+mod alerts;
+mod channels;
+mod share_of_search;
+mod event_impact;
+mod monitoring_csv;
+mod check_failures;
+mod proxy_pool;
+mod rate_limit;
+mod search_parser;
+mod native_crawler;
+mod crawler_backend;
+mod python_crawler;
+mod crawler_protocol;
+mod crawl_archive;
+mod delivery_location;
 
 use db::{BackupInfo, Category, KeywordData, KeywordMonitoring, MonitoringSparkline, MonitoringStats, Product, RankingHistory, RankingSnapshot, RootWithCategories, TrafficLevelStats, UncategorizedKeyword, WorkflowStatus};
 use db::{KbCategory, KbDocument, KbChunk, KbSearchResult, KbConversation, KbMessage, KbDocumentLink, KbDocumentCategory};
@@ -65,12 +85,13 @@ fn get_roots(
     product_id: Option<i64>,
     search: Option<String>,
     category_ids: Option<Vec<i64>>,
+    brand_types: Option<Vec<String>>,
     sort_by: Option<String>,
     sort_order: Option<String>,
     page: i64,
     page_size: i64,
 ) -> Result<(Vec<RootWithCategories>, i64), String> {
-    db::get_roots(product_id, search, category_ids, sort_by, sort_order, page, page_size)
+    db::get_roots(product_id, search, category_ids, brand_types, sort_by, sort_order, page, page_size)
         .map_err(|e| e.to_string())
 }
 
@@ -151,12 +172,13 @@ fn get_keyword_data(
     relevance_levels: Option<Vec<String>>,
     primary_categories: Option<Vec<String>>,
     orderliness_values: Option<Vec<String>>,
+    brand_types: Option<Vec<String>>,
     sort_by: Option<String>,
     sort_order: Option<String>,
     page: i64,
     page_size: i64,
 ) -> Result<(Vec<KeywordData>, i64), String> {
-    db::get_keyword_data(product_id, search, traffic_levels, relevance_levels, primary_categories, orderliness_values, sort_by, sort_order, page, page_size)
+    db::get_keyword_data(product_id, search, traffic_levels, relevance_levels, primary_categories, orderliness_values, brand_types, sort_by, sort_order, page, page_size)
         .map_err(|e| e.to_string())
 }
 
@@ -236,6 +258,92 @@ fn get_workflow_status(product_id: i64) -> Result<WorkflowStatus, String> {
     db::get_workflow_status(product_id).map_err(|e| e.to_string())
 }
 
+// ==================== 搜索意图识别 ====================
+
+// 规则 + 词典识别搜索意图（跳过人工/AI 填写的意图）
+#[tauri::command]
+async fn classify_search_intent(product_id: i64) -> Result<intent::IntentClassifySummary, String> {
+    tokio::task::spawn_blocking(move || intent::classify_product(product_id))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+// ==================== 品牌词典 ====================
+
+#[tauri::command]
+fn get_brand_dictionary(product_id: i64, marketplace: Option<String>) -> Result<Vec<db::BrandDictionaryEntry>, String> {
+    db::get_brand_dictionary(product_id, marketplace).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn add_brand(product_id: i64, marketplace: Option<String>, brand: String, brand_type: String) -> Result<i64, String> {
+    db::add_brand(product_id, marketplace, &brand, &brand_type).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn update_brand_type(id: i64, brand_type: String) -> Result<(), String> {
+    db::update_brand_type(id, &brand_type).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_brand(id: i64) -> Result<(), String> {
+    db::delete_brand(id).map_err(|e| e.to_string())
+}
+
+// 从竞品标题提取品牌，写入词典并重新标记关键词/词根
+#[tauri::command]
+fn seed_brand_dictionary(product_id: i64) -> Result<db::BrandSeedResult, String> {
+    db::seed_brand_dictionary(product_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn refresh_brand_flags(product_id: i64) -> Result<(i64, i64), String> {
+    db::refresh_brand_flags(product_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_brand_negative_suggestions(product_id: i64) -> Result<Vec<db::BrandNegativeSuggestion>, String> {
+    db::get_brand_negative_suggestions(product_id).map_err(|e| e.to_string())
+}
+
+// ==================== 一键分析流程 ====================
+
+// 执行分析流程（steps 为空表示全部步骤；resume_run_id 用于从失败步骤继续）
+#[tauri::command]
+async fn run_analysis_pipeline(
+    app: tauri::AppHandle,
+    product_id: i64,
+    steps: Option<Vec<String>>,
+    resume_run_id: Option<i64>,
+) -> Result<pipeline::PipelineResult, String> {
+    let app_clone = app.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        pipeline::run_pipeline(product_id, steps, resume_run_id, |progress| {
+            app_clone.emit("analysis-pipeline-progress", progress).ok();
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+
+    app.emit("analysis-pipeline-complete", serde_json::json!({
+        "product_id": product_id,
+        "success": result.is_ok(),
+        "error": result.as_ref().err(),
+    })).ok();
+
+    result
+}
+
+#[tauri::command]
+fn get_pipeline_runs(product_id: i64, limit: Option<i64>) -> Result<Vec<db::PipelineRun>, String> {
+    db::get_pipeline_runs(product_id, limit.unwrap_or(20)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_last_failed_pipeline_run(product_id: i64) -> Result<Option<db::PipelineRun>, String> {
+    db::get_last_failed_pipeline_run(product_id).map_err(|e| e.to_string())
+}
+
 // ==================== 备份管理 ====================
 
 #[tauri::command]
@@ -258,6 +366,26 @@ fn delete_backup(backup_id: i64) -> Result<(), String> {
     db::delete_backup(backup_id).map_err(|e| e.to_string())
 }
 
+// ==================== 数据完整性检查 ====================
+
+#[tauri::command]
+fn check_db_integrity(repair: bool) -> Result<db::IntegrityReport, String> {
+    db::check_db_integrity(repair).map_err(|e| e.to_string())
+}
+
+// 手动执行排名历史归档（按调度器设置中的保留天数）
+#[tauri::command]
+async fn run_history_maintenance(vacuum: bool) -> Result<db::HistoryMaintenanceReport, String> {
+    let settings = get_scheduler_settings().await?;
+    db::run_history_maintenance(
+        settings.history_raw_days as i64,
+        settings.history_daily_days as i64,
+        settings.check_log_days as i64,
+        vacuum,
+    )
+    .map_err(|e| e.to_string())
+}
+
 // ==================== API Key 存储 ====================
 // 注意：keychain 存储在某些环境下不稳定，暂时使用 SQLite 存储
 // TODO: 调查 keyring 库兼容性问题后再考虑启用 keychain
@@ -294,6 +422,7 @@ fn migrate_api_keys() -> Result<Vec<String>, String> {
 
 // ==================== 关键词排名监控 ====================
 
+// location: 单独指定的配送邮编，为空时使用站点设置
 #[tauri::command]
 fn add_keyword_monitoring(
     product_id: i64,
@@ -301,8 +430,10 @@ fn add_keyword_monitoring(
     asin: String,
     country: String,
     priority: Option<String>,
+    location: Option<String>,
 ) -> Result<i64, String> {
-    db::add_keyword_monitoring(product_id, keyword, asin, country, priority)
+    let location = delivery_location::normalize(location.as_deref().unwrap_or(""))?;
+    db::add_keyword_monitoring(product_id, keyword, asin, country, priority, location)
         .map_err(|e| e.to_string())
 }
 
@@ -338,6 +469,12 @@ fn update_keyword_monitoring_tags(id: i64, tags: Option<String>) -> Result<(), S
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn set_monitoring_max_pages(ids: Vec<i64>, max_pages: Option<i64>) -> Result<i64, String> {
+    db::set_monitoring_max_pages(&ids, max_pages)
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn delete_keyword_monitoring(id: i64) -> Result<(), String> {
     db::delete_keyword_monitoring(id).map_err(|e| e.to_string())
@@ -363,14 +500,33 @@ fn get_ranking_snapshots(keyword: String, country: String, days: i64) -> Result<
     db::get_ranking_snapshots(&keyword, &country, days).map_err(|e| e.to_string())
 }
 
+// 搜索份额分析：份额趋势、竞品进出榜、排在我们前面的 ASIN
+#[tauri::command]
+fn get_share_of_search(
+    product_id: i64,
+    country: String,
+    days: i64,
+    top_n: Option<usize>,
+) -> Result<share_of_search::ShareOfSearchReport, String> {
+    let source = db::get_share_of_search_source(product_id, &country, days).map_err(|e| e.to_string())?;
+    Ok(share_of_search::build_report(&source, top_n.unwrap_or(10)))
+}
+
 #[tauri::command]
 fn get_monitoring_sparklines(product_id: i64, days: i64) -> Result<Vec<MonitoringSparkline>, String> {
     db::get_monitoring_sparklines(product_id, days).map_err(|e| e.to_string())
 }
 
+// 监控项的检测失败状态（连续失败、重试队列、近 30 天失败统计）
+#[tauri::command]
+fn get_monitoring_failures(product_id: i64) -> Result<Vec<db::MonitoringFailureStatus>, String> {
+    db::get_monitoring_failures(product_id).map_err(|e| e.to_string())
+}
+
 // 检测单个关键词排名
 #[tauri::command]
 async fn check_single_ranking(
+    app: tauri::AppHandle,
     monitoring_id: i64,
     max_pages: Option<i64>,
 ) -> Result<crawler::RankingResult, String> {
@@ -379,12 +535,15 @@ async fn check_single_ranking(
         .map_err(|e| e.to_string())?
         .ok_or_else(|| "监控记录不存在".to_string())?;
 
-    // 使用异步爬虫检测
-    let result = crawler::search_keyword(
+    // 使用异步爬虫检测（按设置选择检测引擎）
+    let result = crawler::check_single(
+        monitoring_id,
         monitoring.keyword.clone(),
         monitoring.asin.clone(),
         monitoring.country.clone(),
+        monitoring.location.clone(),
         max_pages.unwrap_or(5),
+        load_crawler_settings(),
     )
     .await;
 
@@ -393,8 +552,10 @@ async fn check_single_ranking(
         let product_info = result.product_info.as_ref();
         db::update_ranking_result(
             monitoring_id,
+            result.location.as_deref(),
             result.organic_rank,
             result.organic_page,
+            result.organic_position,
             result.sponsored_rank,
             result.sponsored_page,
             product_info.and_then(|p| p.image_url.clone()),
@@ -405,51 +566,30 @@ async fn check_single_ranking(
         .map_err(|e| e.to_string())?;
 
         // 保存竞品快照
-        if !result.organic_top_50.is_empty() || !result.sponsored_top_20.is_empty() {
+        if snapshot_wanted(&result) {
             db::save_ranking_snapshot(
                 &monitoring.keyword,
                 &monitoring.country,
+                result.location.as_deref(),
                 Some(serde_json::to_string(&result.organic_top_50).unwrap_or_default()),
                 Some(serde_json::to_string(&result.sponsored_top_20).unwrap_or_default()),
             )
             .ok();
         }
-    }
-
-    Ok(result)
-}
 
-// 批量检测排名（带进度回调）
-#[tauri::command]
-async fn check_all_rankings(
-    app: tauri::AppHandle,
-    product_id: i64,
-    max_pages: Option<i64>,
-    hours_since_last_check: Option<i64>,
-) -> Result<Vec<(i64, crawler::RankingResult)>, String> {
-    // 获取待检测的监控记录
-    // hours_since_last_check: None 默认24小时，Some(0) 表示无时间限制
-    let pending = db::get_pending_monitoring_checks(product_id, hours_since_last_check.unwrap_or(24))
-        .map_err(|e| e.to_string())?;
-
-    if pending.is_empty() {
-        return Ok(Vec::new());
+        alerts::dispatch_pending_alerts(&app).await;
     }
 
-    let total = pending.len() as i64;
+    // 记录失败原因（成功时清零连续失败次数）
+    check_failures::record_outcome(monitoring_id, &result, scheduler::load_settings().auto_pause_after_failures as i64);
 
-    // 发送开始事件
-    app.emit("ranking-check-start", serde_json::json!({
-        "total": total
-    })).ok();
+    Ok(result)
+}
 
-    // 准备检测数据
-    let keywords: Vec<(i64, String, String, String)> = pending
-        .into_iter()
-        .map(|m| (m.id, m.keyword, m.asin, m.country))
-        .collect();
+// 读取爬虫设置：检测引擎、并发浏览器数、每浏览器标签页数、代理分配表
+fn load_crawler_settings() -> crawler::CrawlerSettings {
+    let engine = crawler::CrawlerEngine::from_setting(db::get_setting("crawler_engine").ok().flatten().as_deref());
 
-    // 获取爬虫设置
     let max_browsers = db::get_setting("max_browsers")
         .ok()
         .flatten()
@@ -462,69 +602,179 @@ async fn check_all_rankings(
         .and_then(|s| s.parse::<i64>().ok())
         .unwrap_or(1);  // 默认1个标签页
 
-    let proxy_list = db::get_setting("proxy_list")
-        .ok()
-        .flatten()
-        .filter(|s| !s.trim().is_empty())
-        .map(|s| s.lines().filter(|l| !l.trim().is_empty()).collect::<Vec<_>>().join(","));
+    // 代理池中启用的代理，按站点分配
+    let proxy_assignment = proxy_pool::load_assignment();
+
+    crawler::CrawlerSettings {
+        engine,
+        max_browsers,
+        tabs_per_browser,
+        proxy_assignment,
+    }
+}
+
+// 竞品快照按站点保存，只记录站点设置邮编的检测结果，单独指定邮编的监控项不覆盖
+fn snapshot_wanted(result: &crawler::RankingResult) -> bool {
+    let has_results = !result.organic_top_50.is_empty() || !result.sponsored_top_20.is_empty();
+    let marketplace_location = result
+        .location
+        .as_deref()
+        .is_none_or(|location| location == delivery_location::resolve(&result.country, None));
+    has_results && marketplace_location
+}
 
-    // 执行批量检测（并发模式）
+// 保存单条检测结果（排名 + 竞品快照），失败的检测只记录失败原因并排入重试队列
+// 手动检测和定时检测共用，返回失败分类（成功且没有警告时为 None）
+pub(crate) fn save_ranking_result(
+    monitoring_id: i64,
+    result: &crawler::RankingResult,
+    auto_pause_after: i64,
+    task_id: Option<i64>,
+) -> Option<check_failures::FailureKind> {
+    let kind = check_failures::record_outcome(monitoring_id, result, auto_pause_after);
+    proxy_pool::record_crawl_result(result, kind);
+    if let Some(task_id) = task_id {
+        db::add_task_item(task_id, monitoring_id, result, kind.map(|k| k.as_str())).ok();
+    }
+    if result.error.is_some() {
+        return kind;
+    }
+    let product_info = result.product_info.as_ref();
+    db::update_ranking_result(
+        monitoring_id,
+        result.location.as_deref(),
+        result.organic_rank,
+        result.organic_page,
+        result.organic_position,
+        result.sponsored_rank,
+        result.sponsored_page,
+        product_info.and_then(|p| p.image_url.clone()),
+        product_info.and_then(|p| p.price.clone()),
+        product_info.and_then(|p| p.reviews_count),
+        product_info.and_then(|p| p.rating),
+    )
+    .ok();
+
+    // 保存竞品快照
+    if snapshot_wanted(result) {
+        db::save_ranking_snapshot(
+            &result.keyword,
+            &result.country,
+            result.location.as_deref(),
+            Some(serde_json::to_string(&result.organic_top_50).unwrap_or_default()),
+            Some(serde_json::to_string(&result.sponsored_top_20).unwrap_or_default()),
+        )
+        .ok();
+    }
+    kind
+}
+
+// 手动触发的批量检测：登记运行句柄，每条结果到达时立即保存并推送 ranking-check-result 事件
+async fn run_manual_ranking_check(
+    app: tauri::AppHandle,
+    keywords: Vec<crawler::RankingItem>,
+    max_pages: i64,
+    engine: Option<crawler::CrawlerEngine>,
+) -> Vec<(i64, crawler::RankingResult)> {
+    let total = keywords.len() as i64;
+    let control = crawler::RunControl::register("manual", total);
+    let run_id = control.id;
+    let task_id = db::create_task_log("manual", total).ok();
+    control.set_task_log(task_id);
+
+    // 发送开始事件
+    app.emit("ranking-check-start", serde_json::json!({
+        "run_id": run_id,
+        "source": "manual",
+        "total": total
+    })).ok();
+
+    // 获取爬虫设置，本次指定的检测引擎优先
+    let mut settings = load_crawler_settings();
+    if let Some(engine) = engine {
+        settings.engine = engine;
+    }
+    let auto_pause_after = scheduler::load_settings().auto_pause_after_failures as i64;
+
+    // 执行批量检测（并发模式），写入时评估告警规则
     let app_clone = app.clone();
     let results = crawler::check_rankings_batch(
         keywords,
-        max_pages.unwrap_or(5),
-        max_browsers,
-        tabs_per_browser,
-        proxy_list,
-        move |current, total, message| {
+        max_pages,
+        settings,
+        control.clone(),
+        move |current, total, monitoring_id, result| {
+            save_ranking_result(monitoring_id, result, auto_pause_after, task_id);
+            app_clone.emit("ranking-check-result", serde_json::json!({
+                "run_id": run_id,
+                "monitoring_id": monitoring_id,
+                "current": current,
+                "total": total,
+                "result": result
+            })).ok();
             // 发送进度事件到前端
             app_clone.emit("ranking-check-progress", serde_json::json!({
+                "run_id": run_id,
                 "current": current,
                 "total": total,
-                "message": message
+                "message": format!("已完成: {}", result.keyword)
             })).ok();
         },
     )
     .await;
-
-    // 更新数据库
-    for (monitoring_id, ref result) in &results {
-        if result.error.is_none() {
-            let product_info = result.product_info.as_ref();
-            db::update_ranking_result(
-                *monitoring_id,
-                result.organic_rank,
-                result.organic_page,
-                result.sponsored_rank,
-                result.sponsored_page,
-                product_info.and_then(|p| p.image_url.clone()),
-                product_info.and_then(|p| p.price.clone()),
-                product_info.and_then(|p| p.reviews_count),
-                product_info.and_then(|p| p.rating),
-            )
-            .ok();
-
-            // 保存竞品快照
-            if !result.organic_top_50.is_empty() || !result.sponsored_top_20.is_empty() {
-                db::save_ranking_snapshot(
-                    &result.keyword,
-                    &result.country,
-                    Some(serde_json::to_string(&result.organic_top_50).unwrap_or_default()),
-                    Some(serde_json::to_string(&result.sponsored_top_20).unwrap_or_default()),
-                )
-                .ok();
-            }
+    control.unregister();
+
+    let success = results.iter().filter(|(_, r)| r.error.is_none()).count() as i64;
+    let failed = results.len() as i64 - success;
+    let skipped = total - results.len() as i64;
+    if let Some(tid) = task_id {
+        if control.is_cancelled() {
+            db::set_task_log_message(tid, &format!("已取消，{} 个关键词未检测", skipped)).ok();
         }
+        db::complete_task_log(tid, success, failed).ok();
     }
 
+    // 发送触发的告警
+    alerts::dispatch_pending_alerts(&app).await;
+
     // 发送完成事件
     app.emit("ranking-check-complete", serde_json::json!({
+        "run_id": run_id,
         "total": results.len(),
-        "success": results.iter().filter(|(_, r)| r.error.is_none()).count(),
-        "failed": results.iter().filter(|(_, r)| r.error.is_some()).count()
+        "success": success,
+        "failed": failed,
+        "cancelled": control.is_cancelled(),
+        "skipped": skipped
     })).ok();
 
-    Ok(results)
+    results
+}
+
+// 批量检测排名（带进度回调）
+#[tauri::command]
+async fn check_all_rankings(
+    app: tauri::AppHandle,
+    product_id: i64,
+    max_pages: Option<i64>,
+    hours_since_last_check: Option<i64>,
+    engine: Option<crawler::CrawlerEngine>,
+) -> Result<Vec<(i64, crawler::RankingResult)>, String> {
+    // 获取待检测的监控记录
+    // hours_since_last_check: None 默认24小时，Some(0) 表示无时间限制
+    let pending = db::get_pending_monitoring_checks(product_id, hours_since_last_check.unwrap_or(24))
+        .map_err(|e| e.to_string())?;
+
+    if pending.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // 准备检测数据
+    let keywords: Vec<crawler::RankingItem> = pending
+        .into_iter()
+        .map(|m| (m.id, m.keyword, m.asin, m.country, m.location))
+        .collect();
+
+    Ok(run_manual_ranking_check(app, keywords, max_pages.unwrap_or(5), engine).await)
 }
 
 // 检测选中的关键词排名
@@ -533,6 +783,7 @@ async fn check_selected_rankings(
     app: tauri::AppHandle,
     ids: Vec<i64>,
     max_pages: Option<i64>,
+    engine: Option<crawler::CrawlerEngine>,
 ) -> Result<Vec<(i64, crawler::RankingResult)>, String> {
     // 根据ID列表获取监控记录
     let pending = db::get_monitoring_by_ids(&ids)
@@ -542,112 +793,85 @@ async fn check_selected_rankings(
         return Ok(Vec::new());
     }
 
-    let total = pending.len() as i64;
-
-    // 发送开始事件
-    app.emit("ranking-check-start", serde_json::json!({
-        "total": total
-    })).ok();
-
     // 准备检测数据
-    let keywords: Vec<(i64, String, String, String)> = pending
+    let keywords: Vec<crawler::RankingItem> = pending
         .into_iter()
-        .map(|m| (m.id, m.keyword, m.asin, m.country))
+        .map(|m| (m.id, m.keyword, m.asin, m.country, m.location))
         .collect();
 
-    // 获取爬虫设置
-    let max_browsers = db::get_setting("max_browsers")
-        .ok()
-        .flatten()
-        .and_then(|s| s.parse::<i64>().ok())
-        .unwrap_or(3);  // 默认3个并发浏览器
-
-    let tabs_per_browser = db::get_setting("tabs_per_browser")
-        .ok()
-        .flatten()
-        .and_then(|s| s.parse::<i64>().ok())
-        .unwrap_or(1);  // 默认1个标签页
-
-    let proxy_list = db::get_setting("proxy_list")
-        .ok()
-        .flatten()
-        .filter(|s| !s.trim().is_empty())
-        .map(|s| s.lines().filter(|l| !l.trim().is_empty()).collect::<Vec<_>>().join(","));
+    Ok(run_manual_ranking_check(app, keywords, max_pages.unwrap_or(5), engine).await)
+}
 
-    // 执行批量检测（并发模式）
-    let app_clone = app.clone();
-    let results = crawler::check_rankings_batch(
-        keywords,
-        max_pages.unwrap_or(5),
-        max_browsers,
-        tabs_per_browser,
-        proxy_list,
-        move |current, total, message| {
-            // 发送进度事件到前端
-            app_clone.emit("ranking-check-progress", serde_json::json!({
-                "current": current,
-                "total": total,
-                "message": message
-            })).ok();
-        },
-    )
-    .await;
+// ==================== 批量检测运行控制 ====================
 
-    // 更新数据库
-    for (monitoring_id, ref result) in &results {
-        if result.error.is_none() {
-            let product_info = result.product_info.as_ref();
-            db::update_ranking_result(
-                *monitoring_id,
-                result.organic_rank,
-                result.organic_page,
-                result.sponsored_rank,
-                result.sponsored_page,
-                product_info.and_then(|p| p.image_url.clone()),
-                product_info.and_then(|p| p.price.clone()),
-                product_info.and_then(|p| p.reviews_count),
-                product_info.and_then(|p| p.rating),
-            )
-            .ok();
+#[tauri::command]
+fn list_ranking_runs() -> Vec<crawler::RunInfo> {
+    crawler::RunControl::list()
+}
 
-            // 保存竞品快照
-            if !result.organic_top_50.is_empty() || !result.sponsored_top_20.is_empty() {
-                db::save_ranking_snapshot(
-                    &result.keyword,
-                    &result.country,
-                    Some(serde_json::to_string(&result.organic_top_50).unwrap_or_default()),
-                    Some(serde_json::to_string(&result.sponsored_top_20).unwrap_or_default()),
-                )
-                .ok();
-            }
-        }
+// 暂停 / 继续 / 取消正在运行的批量检测，并广播 ranking-run-state 事件
+#[tauri::command]
+fn control_ranking_run(app: tauri::AppHandle, run_id: i64, action: String) -> Result<crawler::RunInfo, String> {
+    let control = crawler::RunControl::get(run_id).ok_or("检测任务不存在或已结束")?;
+    match action.as_str() {
+        "pause" => control.pause()?,
+        "resume" => control.resume()?,
+        "cancel" => control.cancel()?,
+        _ => return Err(format!("未知操作: {}", action)),
     }
-
-    // 发送完成事件
-    app.emit("ranking-check-complete", serde_json::json!({
-        "total": results.len(),
-        "success": results.iter().filter(|(_, r)| r.error.is_none()).count(),
-        "failed": results.iter().filter(|(_, r)| r.error.is_some()).count()
-    })).ok();
-
-    Ok(results)
+    let info = control.info();
+    app.emit("ranking-run-state", &info).ok();
+    Ok(info)
 }
 
+// 批量添加的单个监控项：(keyword, asin, country, priority, location)
+type MonitoringItem = (String, String, String, Option<String>, Option<String>);
+
 // 批量添加关键词监控
 #[tauri::command]
 fn batch_add_keyword_monitoring(
     product_id: i64,
-    items: Vec<(String, String, String, Option<String>)>, // (keyword, asin, country, priority)
+    items: Vec<MonitoringItem>,
 ) -> Result<Vec<i64>, String> {
     let mut ids = Vec::new();
-    for (keyword, asin, country, priority) in items {
-        let id = db::add_keyword_monitoring(product_id, keyword, asin, country, priority)
+    for (keyword, asin, country, priority, location) in items {
+        let location = delivery_location::normalize(location.as_deref().unwrap_or(""))?;
+        let id = db::add_keyword_monitoring(product_id, keyword, asin, country, priority, location)
             .map_err(|e| e.to_string())?;
         ids.push(id);
     }
     Ok(ids)
 }
 
+// 按词库筛选条件 × ASIN × 站点批量添加监控；dry_run 只统计不写入
+#[tauri::command]
+fn bulk_add_monitoring(
+    product_id: i64,
+    filter: db::KeywordDataFilter,
+    asins: Vec<String>,
+    countries: Vec<String>,
+    priority: Option<String>,
+    dry_run: bool,
+) -> Result<db::BulkMonitoringResult, String> {
+    db::bulk_add_monitoring_from_keyword_data(product_id, &filter, &asins, &countries, priority, dry_run)
+        .map_err(|e| e.to_string())
+}
+
+// 导出监控列表为 CSV 文本
+#[tauri::command]
+fn export_monitoring_csv(product_id: i64) -> Result<String, String> {
+    let rows = db::get_monitoring_transfer_rows(product_id).map_err(|e| e.to_string())?;
+    Ok(monitoring_csv::to_csv(&rows))
+}
+
+// 从 CSV 导入监控列表（已存在的更新优先级、标签、状态和检测页数）
+#[tauri::command]
+fn import_monitoring_csv(product_id: i64, content: String) -> Result<db::MonitoringImportResult, String> {
+    let (rows, errors) = monitoring_csv::parse(&content)?;
+    let (created, updated) = db::import_monitoring_rows(product_id, &rows).map_err(|e| e.to_string())?;
+    Ok(db::MonitoringImportResult { created, updated, errors })
+}
+
 // ==================== 调度器管理 ====================
 
 #[tauri::command]
@@ -662,6 +886,17 @@ async fn get_scheduler_settings() -> Result<SchedulerSettings, String> {
 
 #[tauri::command]
 async fn update_scheduler_settings(settings: SchedulerSettings) -> Result<(), String> {
+    // 告警规则直接读原始检测记录，保留天数不能低于最长回看窗口
+    if (settings.history_raw_days as i64) < db::MIN_RAW_RETENTION_DAYS {
+        return Err(format!("原始排名数据至少保留 {} 天", db::MIN_RAW_RETENTION_DAYS));
+    }
+    if settings.history_daily_days < settings.history_raw_days {
+        return Err("日汇总保留天数不能少于原始数据保留天数".to_string());
+    }
+    if settings.check_log_days == 0 {
+        return Err("检测明细至少保留 1 天".to_string());
+    }
+
     // 保存到数据库
     let json = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
     db::set_setting("scheduler_settings", &json).map_err(|e| e.to_string())?;
@@ -673,7 +908,7 @@ async fn update_scheduler_settings(settings: SchedulerSettings) -> Result<(), St
 }
 
 #[tauri::command]
-async fn start_scheduler() -> Result<(), String> {
+async fn start_scheduler(app: tauri::AppHandle) -> Result<(), String> {
     // 加载设置
     if let Ok(Some(json)) = db::get_setting("scheduler_settings") {
         if let Ok(settings) = serde_json::from_str::<SchedulerSettings>(&json) {
@@ -681,7 +916,7 @@ async fn start_scheduler() -> Result<(), String> {
         }
     }
 
-    SCHEDULER.start().await;
+    SCHEDULER.start(app).await;
     Ok(())
 }
 
@@ -691,6 +926,115 @@ fn stop_scheduler() -> Result<(), String> {
     Ok(())
 }
 
+// ==================== 排名检测计划（Cron） ====================
+
+#[tauri::command]
+fn get_ranking_schedules() -> Result<Vec<db::RankingSchedule>, String> {
+    db::get_ranking_schedules().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn create_ranking_schedule(schedule: db::RankingScheduleInput) -> Result<i64, String> {
+    let next_run_at = scheduler::validate_ranking_schedule(&schedule)?;
+    db::create_ranking_schedule(&schedule, next_run_at.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn update_ranking_schedule(id: i64, schedule: db::RankingScheduleInput) -> Result<(), String> {
+    let next_run_at = scheduler::validate_ranking_schedule(&schedule)?;
+    db::update_ranking_schedule(id, &schedule, next_run_at.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_ranking_schedule(id: i64) -> Result<(), String> {
+    db::delete_ranking_schedule(id).map_err(|e| e.to_string())
+}
+
+// 预览 cron 表达式接下来的触发时间（RFC3339）
+#[tauri::command]
+fn preview_cron_schedule(cron_expr: String, timezone: String, count: Option<usize>) -> Result<Vec<String>, String> {
+    let times = scheduler::preview_cron(&cron_expr, &timezone, count.unwrap_or(5))?;
+    Ok(times.iter().map(|t| t.to_rfc3339()).collect())
+}
+
+// ==================== 排名告警 ====================
+
+#[tauri::command]
+fn get_alert_rules(product_id: Option<i64>) -> Result<Vec<db::AlertRule>, String> {
+    db::get_alert_rules(product_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn create_alert_rule(rule: db::AlertRuleInput) -> Result<i64, String> {
+    alerts::validate_alert_rule(&rule)?;
+    db::create_alert_rule(&rule).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn update_alert_rule(id: i64, rule: db::AlertRuleInput) -> Result<(), String> {
+    alerts::validate_alert_rule(&rule)?;
+    db::update_alert_rule(id, &rule).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_alert_rule(id: i64) -> Result<(), String> {
+    db::delete_alert_rule(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_alert_history(
+    product_id: Option<i64>,
+    status: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<db::AlertRecord>, String> {
+    db::get_alert_history(product_id, status, limit.unwrap_or(200)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn get_open_alert_count(product_id: Option<i64>) -> Result<i64, String> {
+    db::get_open_alert_count(product_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn acknowledge_alerts(ids: Vec<i64>) -> Result<i64, String> {
+    db::acknowledge_alerts(&ids).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn snooze_alerts(ids: Vec<i64>, hours: i64) -> Result<i64, String> {
+    db::snooze_alerts(&ids, hours).map_err(|e| e.to_string())
+}
+
+// ==================== 外部通知渠道（Webhook / 邮件） ====================
+
+#[tauri::command]
+fn get_notification_channels() -> Result<Vec<db::NotificationChannel>, String> {
+    db::get_notification_channels().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn create_notification_channel(channel: db::NotificationChannelInput) -> Result<i64, String> {
+    channel.config.validate()?;
+    db::create_notification_channel(&channel).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn update_notification_channel(id: i64, channel: db::NotificationChannelInput) -> Result<(), String> {
+    channel.config.validate()?;
+    db::update_notification_channel(id, &channel).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_notification_channel(id: i64) -> Result<(), String> {
+    db::delete_notification_channel(id).map_err(|e| e.to_string())
+}
+
+// 测试发送（可用于保存前的配置）
+#[tauri::command]
+async fn test_notification_channel(config: channels::ChannelConfig) -> Result<(), String> {
+    channels::send_test_message(&config).await.map(|_| ())
+}
+
 #[tauri::command]
 async fn get_scheduler_status() -> Result<SchedulerStatus, String> {
     Ok(SCHEDULER.get_status().await)
@@ -701,6 +1045,11 @@ fn get_task_logs(limit: Option<i64>) -> Result<Vec<db::SchedulerTaskLog>, String
     db::get_task_logs(limit.unwrap_or(20)).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+fn get_task_log_detail(task_id: i64) -> Result<Option<db::TaskLogDetail>, String> {
+    db::get_task_log_detail(task_id).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 fn get_running_task() -> Result<Option<db::SchedulerTaskLog>, String> {
     db::get_running_task().map_err(|e| e.to_string())
@@ -711,6 +1060,111 @@ fn clear_task_logs() -> Result<(), String> {
     db::clear_task_logs().map_err(|e| e.to_string())
 }
 
+// ==================== 代理池 ====================
+
+#[tauri::command]
+fn get_proxies() -> Result<Vec<db::Proxy>, String> {
+    db::get_proxies().map_err(|e| e.to_string())
+}
+
+// 批量添加代理（每行一个），返回新增数量
+#[tauri::command]
+fn add_proxies(urls: String, input: db::ProxyInput) -> Result<i64, String> {
+    let urls = proxy_pool::parse_proxy_urls(&urls)?;
+    db::add_proxies(&urls, &input).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn update_proxy(id: i64, input: db::ProxyInput) -> Result<(), String> {
+    db::update_proxy(id, &input).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn delete_proxy(id: i64) -> Result<(), String> {
+    db::delete_proxy(id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn reset_proxy_stats(id: i64) -> Result<(), String> {
+    db::reset_proxy_stats(id).map_err(|e| e.to_string())
+}
+
+// 健康检查；ids 为空时检查全部代理
+#[tauri::command]
+async fn check_proxies(ids: Option<Vec<i64>>) -> Result<Vec<proxy_pool::ProxyCheckResult>, String> {
+    let proxies = db::get_proxies().map_err(|e| e.to_string())?;
+    let proxies: Vec<db::Proxy> = match ids {
+        Some(ids) => proxies.into_iter().filter(|p| ids.contains(&p.id)).collect(),
+        None => proxies,
+    };
+    Ok(proxy_pool::check_proxies(&proxies).await)
+}
+
+// ==================== 站点限速 ====================
+
+#[tauri::command]
+fn get_crawl_rate_limits() -> Result<Vec<db::RateLimitState>, String> {
+    rate_limit::list()
+}
+
+#[tauri::command]
+fn set_crawl_rate_budget(country: String, hourly_budget: i64) -> Result<db::RateLimitState, String> {
+    rate_limit::set_budget(&country, hourly_budget)
+}
+
+// 手动解除限速和冷却（如更换了代理）
+#[tauri::command]
+fn reset_crawl_rate_limit(country: String) -> Result<(), String> {
+    db::reset_rate_limit(&country).map_err(|e| e.to_string())
+}
+
+// ==================== 爬取页面归档 ====================
+
+// 列出归档内容（date 为 YYYY-MM-DD，为空时列出全部日期）
+#[tauri::command]
+fn get_crawl_archive_entries(date: Option<String>) -> Result<Vec<crawl_archive::ArchiveEntry>, String> {
+    crawl_archive::list_entries(date.as_deref())
+}
+
+// 从归档页面重新解析排名，不重新爬取、不写入历史（run 为空时使用当天最新一次检测）
+#[tauri::command]
+async fn reparse_archived_ranking(
+    date: String,
+    keyword: String,
+    asin: String,
+    country: String,
+    run: Option<String>,
+) -> Result<crawler::RankingResult, String> {
+    crawl_archive::reparse_ranking(&date, keyword, asin, country, run).await
+}
+
+#[tauri::command]
+async fn reparse_archived_listing(date: String, asin: String, country: String) -> Result<crawler::ListingResult, String> {
+    crawl_archive::reparse_listing(&date, asin, country).await
+}
+
+// 立即按保留天数清理归档，返回删除的天数
+#[tauri::command]
+fn cleanup_crawl_archive() -> usize {
+    crawl_archive::cleanup()
+}
+
+// ==================== 配送地址 ====================
+
+// 各站点爬取使用的配送邮编
+#[tauri::command]
+fn get_delivery_locations() -> Vec<delivery_location::MarketplaceLocation> {
+    delivery_location::list()
+}
+
+// 保存各站点的配送邮编，zipcode 为空的站点恢复默认邮编
+#[tauri::command]
+fn set_delivery_locations(
+    locations: Vec<delivery_location::MarketplaceLocation>,
+) -> Result<Vec<delivery_location::MarketplaceLocation>, String> {
+    delivery_location::save(&locations)
+}
+
 // ==================== 依赖安装 ====================
 
 #[tauri::command]
@@ -755,8 +1209,10 @@ fn get_optimization_events(
     product_id: i64,
     start_date: Option<String>,
     end_date: Option<String>,
+    window_days: Option<i64>,
 ) -> Result<Vec<db::OptimizationEvent>, String> {
-    db::get_optimization_events(product_id, start_date, end_date)
+    let window_days = window_days.unwrap_or(event_impact::DEFAULT_WINDOW_DAYS);
+    db::get_optimization_events(product_id, start_date, end_date, window_days)
         .map_err(|e| e.to_string())
 }
 
@@ -2265,6 +2721,11 @@ fn delete_weekly_report(week_start: String) -> Result<(), String> {
     db::delete_weekly_report(&week_start).map_err(|e| e.to_string())
 }
 
+/// 命令行模式入口（thesaurus-cli），返回进程退出码
+pub fn run_cli(args: Vec<String>) -> i32 {
+    cli::run(args)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -2288,7 +2749,7 @@ pub fn run() {
                     if let Ok(settings) = serde_json::from_str::<scheduler::SchedulerSettings>(&json) {
                         if settings.enabled {
                             SCHEDULER.update_settings(settings).await;
-                            SCHEDULER.start().await;
+                            SCHEDULER.start(app_handle.clone()).await;
                             println!("[Scheduler] Auto-started on app launch");
                         }
                     }
@@ -2385,11 +2846,28 @@ pub fn run() {
             calculate_orderliness,
             // 流程状态
             get_workflow_status,
+            // 搜索意图识别
+            classify_search_intent,
+            // 品牌词典
+            get_brand_dictionary,
+            add_brand,
+            update_brand_type,
+            delete_brand,
+            seed_brand_dictionary,
+            refresh_brand_flags,
+            get_brand_negative_suggestions,
+            // 一键分析流程
+            run_analysis_pipeline,
+            get_pipeline_runs,
+            get_last_failed_pipeline_run,
             // 备份管理
             create_backup,
             get_backups,
             restore_backup,
             delete_backup,
+            // 数据完整性检查
+            check_db_integrity,
+            run_history_maintenance,
             // API Key 存储
             set_api_key,
             get_api_key,
@@ -2401,16 +2879,25 @@ pub fn run() {
             get_keyword_monitoring_list,
             update_keyword_monitoring,
             update_keyword_monitoring_tags,
+            set_monitoring_max_pages,
             delete_keyword_monitoring,
             batch_delete_keyword_monitoring,
             get_monitoring_stats,
             get_ranking_history,
             get_ranking_snapshots,
+            get_share_of_search,
             get_monitoring_sparklines,
+            get_monitoring_failures,
             check_single_ranking,
             check_all_rankings,
             check_selected_rankings,
             batch_add_keyword_monitoring,
+            bulk_add_monitoring,
+            export_monitoring_csv,
+            import_monitoring_csv,
+            // 批量检测运行控制
+            list_ranking_runs,
+            control_ranking_run,
             // 调度器管理
             get_scheduler_settings,
             update_scheduler_settings,
@@ -2418,8 +2905,49 @@ pub fn run() {
             stop_scheduler,
             get_scheduler_status,
             get_task_logs,
+            get_task_log_detail,
             get_running_task,
             clear_task_logs,
+            // 代理池
+            get_proxies,
+            add_proxies,
+            update_proxy,
+            delete_proxy,
+            reset_proxy_stats,
+            check_proxies,
+            // 站点限速
+            get_crawl_rate_limits,
+            set_crawl_rate_budget,
+            reset_crawl_rate_limit,
+            // 爬取页面归档
+            get_crawl_archive_entries,
+            reparse_archived_ranking,
+            reparse_archived_listing,
+            cleanup_crawl_archive,
+            // 配送地址
+            get_delivery_locations,
+            set_delivery_locations,
+            // 排名检测计划（Cron）
+            get_ranking_schedules,
+            create_ranking_schedule,
+            update_ranking_schedule,
+            delete_ranking_schedule,
+            preview_cron_schedule,
+            // 排名告警
+            get_alert_rules,
+            create_alert_rule,
+            update_alert_rule,
+            delete_alert_rule,
+            get_alert_history,
+            get_open_alert_count,
+            acknowledge_alerts,
+            snooze_alerts,
+            // 外部通知渠道
+            get_notification_channels,
+            create_notification_channel,
+            update_notification_channel,
+            delete_notification_channel,
+            test_notification_channel,
             // 依赖安装
             check_dependencies,
             install_all_dependencies,
@@ -0,0 +1,173 @@
+// 关键词监控列表的 CSV 导入/导出
+// 表头用中文，导入时也接受英文列名；标签为英文 key，多个用 ";" 分隔；邮编为空表示使用站点设置
+
+use crate::csv_text::{csv_line, parse_csv, BOM};
+use crate::db::MonitoringTransferRow;
+use crate::delivery_location;
+
+const HEADERS: [&str; 8] = ["关键词", "ASIN", "站点", "优先级", "标签", "状态", "检测页数", "邮编"];
+
+// 列别名 -> 列序号（与 HEADERS 对应）
+fn column_index(header: &str) -> Option<usize> {
+    match header.trim().to_lowercase().as_str() {
+        "关键词" | "keyword" => Some(0),
+        "asin" => Some(1),
+        "站点" | "country" | "marketplace" => Some(2),
+        "优先级" | "priority" => Some(3),
+        "标签" | "tags" => Some(4),
+        "状态" | "is_active" | "status" => Some(5),
+        "检测页数" | "max_pages" => Some(6),
+        "邮编" | "location" | "zipcode" | "postcode" => Some(7),
+        _ => None,
+    }
+}
+
+fn parse_priority(value: &str) -> Option<String> {
+    match value.trim().to_lowercase().as_str() {
+        "" | "medium" | "中" => Some("medium".to_string()),
+        "high" | "高" => Some("high".to_string()),
+        "low" | "低" => Some("low".to_string()),
+        _ => None,
+    }
+}
+
+fn parse_active(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "" | "1" | "true" | "active" | "启用" | "活跃" => Some(true),
+        "0" | "false" | "paused" | "暂停" => Some(false),
+        _ => None,
+    }
+}
+
+pub fn to_csv(rows: &[MonitoringTransferRow]) -> String {
+    let mut out = String::from(BOM);
+    out.push_str(&csv_line(&HEADERS));
+    out.push('\n');
+    for row in rows {
+        let fields = [
+            row.keyword.clone(),
+            row.asin.clone(),
+            row.country.clone(),
+            row.priority.clone(),
+            row.tags.join(";"),
+            if row.is_active { "启用" } else { "暂停" }.to_string(),
+            row.max_pages.map(|p| p.to_string()).unwrap_or_default(),
+            row.location.clone().unwrap_or_default(),
+        ];
+        out.push_str(&csv_line(&fields));
+        out.push('\n');
+    }
+    out
+}
+
+/// 解析导入的 CSV，返回 (有效行, 错误信息)
+/// 同一 (关键词, ASIN, 站点, 邮编) 出现多次时以最后一行为准
+pub fn parse(content: &str) -> Result<(Vec<MonitoringTransferRow>, Vec<String>), String> {
+    let (headers, rows) = parse_csv(content)?;
+    let columns: Vec<Option<usize>> = headers.iter().map(|h| column_index(h)).collect();
+    for (required, name) in [(0, "关键词"), (1, "ASIN"), (2, "站点")] {
+        if !columns.contains(&Some(required)) {
+            return Err(format!("缺少必需的列：{}", name));
+        }
+    }
+
+    let mut result: Vec<MonitoringTransferRow> = Vec::new();
+    let mut errors = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        if row.iter().all(|f| f.trim().is_empty()) {
+            continue;
+        }
+        let line = i + 2;  // 表头是第 1 行
+        let mut fields = vec![""; HEADERS.len()];
+        for (value, column) in row.iter().zip(&columns) {
+            if let Some(c) = column {
+                fields[*c] = value.trim();
+            }
+        }
+
+        let (keyword, asin, country) = (fields[0], fields[1].to_uppercase(), fields[2].to_uppercase());
+        if keyword.is_empty() || asin.is_empty() || country.is_empty() {
+            errors.push(format!("第 {} 行：关键词、ASIN、站点不能为空", line));
+            continue;
+        }
+        let Some(priority) = parse_priority(fields[3]) else {
+            errors.push(format!("第 {} 行：无法识别的优先级 {}", line, fields[3]));
+            continue;
+        };
+        let Some(is_active) = parse_active(fields[5]) else {
+            errors.push(format!("第 {} 行：无法识别的状态 {}", line, fields[5]));
+            continue;
+        };
+        let max_pages = match fields[6] {
+            "" => None,
+            value => match value.parse::<i64>() {
+                Ok(p) if (1..=20).contains(&p) => Some(p),
+                _ => {
+                    errors.push(format!("第 {} 行：检测页数应为 1-20", line));
+                    continue;
+                }
+            },
+        };
+        let location = match delivery_location::normalize(fields[7]) {
+            Ok(location) => location,
+            Err(e) => {
+                errors.push(format!("第 {} 行：{}", line, e));
+                continue;
+            }
+        };
+        let tags = fields[4]
+            .split([';', '|', '，'])
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+
+        let item = MonitoringTransferRow {
+            keyword: keyword.to_string(),
+            asin,
+            country,
+            priority,
+            tags,
+            is_active,
+            max_pages,
+            location,
+        };
+        result.retain(|r| {
+            (&r.keyword, &r.asin, &r.country, &r.location) != (&item.keyword, &item.asin, &item.country, &item.location)
+        });
+        result.push(item);
+    }
+
+    Ok((result, errors))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_monitoring_csv_round_trip() {
+        let rows = vec![MonitoringTransferRow {
+            keyword: "desk lamp, led".to_string(),
+            asin: "B0TEST0001".to_string(),
+            country: "US".to_string(),
+            priority: "high".to_string(),
+            tags: vec!["high_traffic".to_string(), "high_conversion".to_string()],
+            is_active: false,
+            max_pages: Some(3),
+            location: Some("SW1A 1AA".to_string()),
+        }];
+        let (parsed, errors) = parse(&to_csv(&rows)).unwrap();
+        assert!(errors.is_empty());
+        assert_eq!(parsed, rows);
+
+        // 英文列名、中文取值、重复行以最后一行为准、错误行带行号
+        let content = "keyword,asin,country,priority,status\nlamp,b0x,de,高,暂停\nlamp,B0X,DE,低,\n,B0Y,US,,\nlight,B0Z,US,urgent,\n";
+        let (parsed, errors) = parse(content).unwrap();
+        assert_eq!(parsed.len(), 1);
+        assert_eq!((parsed[0].asin.as_str(), parsed[0].priority.as_str(), parsed[0].is_active), ("B0X", "low", true));
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("第 4 行"));
+
+        assert!(parse("keyword,asin\nlamp,B0X\n").is_err());
+    }
+}
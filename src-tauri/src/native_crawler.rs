@@ -0,0 +1,501 @@
+// 原生排名爬虫：直接请求 Amazon 搜索页并用 search_parser 解析，不依赖 Python / Playwright
+// 无法像浏览器那样点击设置邮编，只通过站点 Cookie 固定语言和币种；配送地址与要求的邮编不符时给出警告
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::crawler::{CrawlerSettings, RankingItem, RankingResult, RunControl};
+use crate::crawler_backend::{BoxFuture, CrawlerBackend, RankingCallback};
+use crate::search_parser::{self, RankTracker};
+
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/131.0.0.0 Safari/537.36";
+
+const REQUEST_TIMEOUT_SECS: u64 = 30;
+
+// 翻页之间的随机间隔（毫秒），降低触发风控的概率
+const PAGE_DELAY_MS: (u64, u64) = (1500, 3500);
+
+// 站点配置，与 amazon_crawler_playwright.py 的 COUNTRY_CONFIG 一致
+struct Marketplace {
+    base_url: &'static str,
+    language: &'static str,
+    market_param: Option<(&'static str, &'static str)>,
+    cookie: &'static str,
+    expected_address: &'static [&'static str],  // 配送地址中应出现的关键词
+}
+
+fn marketplace(country: &str) -> Marketplace {
+    match country {
+        "UK" => Marketplace {
+            base_url: "https://www.amazon.co.uk",
+            language: "en-GB",
+            market_param: None,
+            cookie: "lc-acbuk=en_GB; i18n-prefs=GBP",
+            expected_address: &["United Kingdom", "UK", "GB", "SW1A", "London", "Britain"],
+        },
+        "DE" => Marketplace {
+            base_url: "https://www.amazon.de",
+            language: "de-DE",
+            market_param: Some(("__mk_de_DE", "ÅMÅŽÕÑ")),
+            cookie: "lc-acbde=de_DE; i18n-prefs=EUR",
+            expected_address: &["Deutschland", "Germany", "10115", "Berlin", "Deutsch"],
+        },
+        "FR" => Marketplace {
+            base_url: "https://www.amazon.fr",
+            language: "fr-FR",
+            market_param: Some(("__mk_fr_FR", "ÅMÅŽÕÑ")),
+            cookie: "lc-acbfr=fr_FR; i18n-prefs=EUR",
+            expected_address: &["France", "Frankreich", "75001", "Paris"],
+        },
+        "IT" => Marketplace {
+            base_url: "https://www.amazon.it",
+            language: "it-IT",
+            market_param: Some(("__mk_it_IT", "ÅMÅŽÕÑ")),
+            cookie: "lc-acbit=it_IT; i18n-prefs=EUR",
+            expected_address: &["Italia", "Italy", "Italien", "00100", "Roma", "Rom"],
+        },
+        "ES" => Marketplace {
+            base_url: "https://www.amazon.es",
+            language: "es-ES",
+            market_param: Some(("__mk_es_ES", "ÅMÅŽÕÑ")),
+            cookie: "lc-acbes=es_ES; i18n-prefs=EUR",
+            expected_address: &["España", "Spain", "Spanien", "28001", "Madrid"],
+        },
+        "JP" => Marketplace {
+            base_url: "https://www.amazon.co.jp",
+            language: "ja-JP",
+            market_param: Some(("__mk_ja_JP", "カタカナ")),
+            cookie: "lc-acbjp=ja_JP; i18n-prefs=JPY",
+            expected_address: &["Japan", "日本", "100-0001", "Tokyo", "東京", "千代田区"],
+        },
+        _ => Marketplace {
+            base_url: "https://www.amazon.com",
+            language: "en-US",
+            market_param: None,
+            cookie: "lc-main=en_US; i18n-prefs=USD",
+            expected_address: &["United States", "USA", "10001", "New York"],
+        },
+    }
+}
+
+fn search_url(market: &Marketplace, keyword: &str, page_num: i64) -> Result<reqwest::Url, String> {
+    let mut params: Vec<(&str, String)> = vec![("k", keyword.to_string())];
+    if let Some((name, value)) = market.market_param {
+        params.push((name, value.to_string()));
+    }
+    if page_num > 1 {
+        params.push(("page", page_num.to_string()));
+    }
+    reqwest::Url::parse_with_params(&format!("{}/s", market.base_url), &params).map_err(|e| e.to_string())
+}
+
+// 配送地址与要求的邮编不符时的警告（与 Python 脚本的提示一致，会被归类为地理限制）
+// 默认邮编时地址中出现站点的城市/国家名即可；自定义邮编必须能看到邮编（英国等只显示邮编前半部分）
+fn address_warning(market: &Marketplace, country: &str, zipcode: Option<&str>, location: Option<&str>) -> Option<String> {
+    let location = location?;
+    let lower = location.to_lowercase();
+    let zipcode = zipcode.map(|zip| zip.trim().to_lowercase()).filter(|zip| !zip.is_empty());
+    let matched = match &zipcode {
+        Some(zip) if *zip != crate::delivery_location::default_zipcode(country).to_lowercase() => {
+            lower.replace(' ', "").contains(&zip.replace(' ', ""))
+                || zip.split_whitespace().next().is_some_and(|part| lower.contains(part))
+        }
+        _ => market.expected_address.iter().any(|kw| lower.contains(&kw.to_lowercase())),
+    };
+    if matched {
+        return None;
+    }
+    Some(format!("配送地址设置可能未生效，当前显示: {}", location))
+}
+
+// 请求一页搜索结果；HTTP 503 多为风控拦截，按验证码处理
+async fn fetch_page(client: &reqwest::Client, market: &Marketplace, url: reqwest::Url, via_proxy: bool) -> Result<String, String> {
+    let response = client
+        .get(url)
+        .header(reqwest::header::ACCEPT_LANGUAGE, format!("{},en;q=0.9", market.language))
+        .header(reqwest::header::ACCEPT, "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8")
+        .header(reqwest::header::COOKIE, market.cookie)
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_timeout() {
+                "页面加载超时".to_string()
+            } else if via_proxy && (e.is_connect() || e.is_request()) {
+                format!("代理连接失败: {}", e)
+            } else {
+                format!("请求失败: {}", e)
+            }
+        })?;
+    let status = response.status();
+    if status.as_u16() == 503 {
+        return Err("HTTP 503，疑似触发验证码".to_string());
+    }
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status.as_u16()));
+    }
+    response.text().await.map_err(|e| format!("读取页面失败: {}", e))
+}
+
+// 同一站点、同一配送邮编、同一关键词的检测目标，只搜索一次
+pub(crate) struct KeywordGroup {
+    pub country: String,
+    pub keyword: String,
+    pub location: Option<String>,     // 要求的配送邮编，为空时只检查地址是否在站点所在国家
+    pub targets: Vec<(i64, String)>,  // (monitoring_id, asin)
+}
+
+pub(crate) fn group_keywords(keywords: &[RankingItem]) -> Vec<KeywordGroup> {
+    let mut groups: Vec<KeywordGroup> = Vec::new();
+    for (id, keyword, asin, country, location) in keywords {
+        let existing = groups.iter_mut().find(|g| {
+            g.country == *country && g.location == *location && g.keyword.to_lowercase() == keyword.to_lowercase()
+        });
+        match existing {
+            Some(group) => group.targets.push((*id, asin.clone())),
+            None => groups.push(KeywordGroup {
+                country: country.clone(),
+                keyword: keyword.clone(),
+                location: location.clone(),
+                targets: vec![(*id, asin.clone())],
+            }),
+        }
+    }
+    groups
+}
+
+fn empty_result(keyword: &str, asin: &str, country: &str) -> RankingResult {
+    RankingResult {
+        keyword: keyword.to_string(),
+        target_asin: asin.to_string(),
+        country: country.to_string(),
+        organic_rank: None,
+        organic_page: None,
+        organic_position: None,
+        sponsored_rank: None,
+        sponsored_page: None,
+        product_info: None,
+        organic_top_50: Vec::new(),
+        sponsored_top_20: Vec::new(),
+        checked_at: chrono::Utc::now().to_rfc3339(),
+        error: None,
+        warning: None,
+        duration_ms: None,
+        proxy: None,
+        pages_checked: None,
+        location: None,
+    }
+}
+
+// 请求一页搜索结果，翻页前随机等待
+async fn fetch_search_page(
+    client: reqwest::Client,
+    country: String,
+    keyword: String,
+    page_num: i64,
+    via_proxy: bool,
+) -> Result<String, String> {
+    if page_num > 1 {
+        let delay = rand::thread_rng().gen_range(PAGE_DELAY_MS.0..=PAGE_DELAY_MS.1);
+        tokio::time::sleep(Duration::from_millis(delay)).await;
+    }
+    let market = marketplace(&country);
+    let url = search_url(&market, &keyword, page_num)?;
+    fetch_page(&client, &market, url, via_proxy).await
+}
+
+/// 检测一个关键词分组：load_page(页码) 返回该页 HTML，所有目标的自然和广告排名都找到或没有更多结果时停止
+/// 第一页失败时所有目标都记为失败；之后的页失败则保留已找到的排名（录制数据回放也用这里的排名规则）
+pub(crate) async fn check_group<F, Fut>(
+    group: KeywordGroup,
+    max_pages: i64,
+    proxy: Option<String>,
+    mut load_page: F,
+) -> Vec<(i64, RankingResult)>
+where
+    F: FnMut(i64) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let started = Instant::now();
+    let market = marketplace(&group.country);
+    let mut tracker = RankTracker::new(group.targets.iter().map(|(_, asin)| asin.as_str()));
+    let mut error = None;
+    let mut warning = None;
+    let mut pages_checked = 0i64;
+
+    for page_num in 1..=max_pages.max(1) {
+        let page = load_page(page_num).await.and_then(|html| search_parser::parse_search_page(&html));
+
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                if page_num == 1 {
+                    error = Some(e);
+                } else {
+                    eprintln!("[Native] {} [{}] 第 {} 页失败，保留已有结果: {}", group.keyword, group.country, page_num, e);
+                }
+                break;
+            }
+        };
+        pages_checked = page_num;
+        if page_num == 1 {
+            warning = address_warning(&market, &group.country, group.location.as_deref(), page.delivery_location.as_deref());
+        }
+        tracker.apply_page(page_num, &page);
+        if tracker.all_found() || page.results.is_empty() {
+            break;
+        }
+    }
+
+    let duration_ms = started.elapsed().as_millis() as i64;
+    group
+        .targets
+        .iter()
+        .map(|(id, asin)| {
+            let mut result = empty_result(&group.keyword, asin, &group.country);
+            result.duration_ms = Some(duration_ms);
+            result.proxy = proxy.clone();
+            result.pages_checked = Some(pages_checked);
+            result.location = group.location.clone();
+            match &error {
+                Some(e) => result.error = Some(e.clone()),
+                None => {
+                    let rank = tracker.ranks.get(&asin.to_uppercase()).cloned().unwrap_or_default();
+                    result.organic_rank = rank.organic_rank;
+                    result.organic_page = rank.organic_page;
+                    result.organic_position = rank.organic_position;
+                    result.sponsored_rank = rank.sponsored_rank;
+                    result.sponsored_page = rank.sponsored_page;
+                    result.product_info = rank.product_info;
+                    result.organic_top_50 = tracker.organic_top_50.clone();
+                    result.sponsored_top_20 = tracker.sponsored_top_20.clone();
+                    result.warning = warning.clone();
+                }
+            }
+            (*id, result)
+        })
+        .collect()
+}
+
+// 按站点轮换代理（分配表格式见 proxy_pool::build_assignment），每个代理一个复用的 HTTP 客户端
+struct ProxyRotation {
+    assignment: HashMap<String, Vec<String>>,
+    next: AtomicUsize,
+    clients: HashMap<Option<String>, reqwest::Client>,
+}
+
+impl ProxyRotation {
+    fn new(proxy_assignment: Option<&str>) -> Result<Self, String> {
+        let assignment: HashMap<String, Vec<String>> = proxy_assignment
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default();
+
+        let mut clients = HashMap::new();
+        let mut urls: Vec<Option<String>> = vec![None];
+        urls.extend(assignment.values().flatten().cloned().map(Some));
+        for url in urls {
+            if clients.contains_key(&url) {
+                continue;
+            }
+            let mut builder = reqwest::Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS));
+            if let Some(proxy_url) = &url {
+                let proxy = reqwest::Proxy::all(proxy_url).map_err(|e| format!("代理地址无效 {}: {}", proxy_url, e))?;
+                builder = builder.proxy(proxy);
+            }
+            clients.insert(url, builder.build().map_err(|e| e.to_string())?);
+        }
+        Ok(Self { assignment, next: AtomicUsize::new(0), clients })
+    }
+
+    fn pick(&self, country: &str) -> (Option<String>, reqwest::Client) {
+        let proxies = self
+            .assignment
+            .get(country)
+            .filter(|list| !list.is_empty())
+            .or_else(|| self.assignment.get("*"))
+            .filter(|list| !list.is_empty());
+        let proxy = proxies.map(|list| list[self.next.fetch_add(1, Ordering::Relaxed) % list.len()].clone());
+        let client = self.clients[&proxy].clone();
+        (proxy, client)
+    }
+}
+
+/// 批量检测排名：concurrency 个关键词并发（对应浏览器数 × 标签页数），每完成一个监控项调用一次 on_result(monitoring_id, 结果)
+/// 暂停时不再开始新的关键词，取消后只返回已完成的结果；检测中途站点进入限速冷却时，该站点剩余的关键词跳过
+/// 开启页面归档时，每个关键词的检测单独一个归档目录，成功请求的搜索页保存在其中
+pub async fn check_rankings(
+    keywords: &[RankingItem],
+    max_pages: i64,
+    concurrency: i64,
+    proxy_assignment: Option<&str>,
+    control: &RunControl,
+    mut on_result: impl FnMut(i64, &RankingResult),
+) -> Result<Vec<(i64, RankingResult)>, String> {
+    let rotation = Arc::new(ProxyRotation::new(proxy_assignment)?);
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1) as usize));
+    let archive_root = crate::crawl_archive::active_root();
+
+    let mut tasks = JoinSet::new();
+    for group in group_keywords(keywords) {
+        let (rotation, semaphore, control) = (rotation.clone(), semaphore.clone(), control.clone());
+        let archive_root = archive_root.clone();
+        tasks.spawn(async move {
+            let Ok(_permit) = semaphore.acquire_owned().await else { return Vec::new() };
+            if !control.wait_while_paused().await {
+                return Vec::new();
+            }
+            if let Err(reason) = crate::rate_limit::check(&group.country) {
+                control.log("warning", "rate_limit", &format!("{}，跳过关键词 {}", reason, group.keyword));
+                return Vec::new();
+            }
+            let (proxy, client) = rotation.pick(&group.country);
+            let (country, keyword, via_proxy) = (group.country.clone(), group.keyword.clone(), proxy.is_some());
+            let archive_run = archive_root.as_deref().and_then(|root| {
+                crate::crawl_archive::start_search_run(root, &country, &keyword, crate::crawl_archive::ENGINE_NATIVE, group.location.as_deref())
+            });
+            check_group(group, max_pages, proxy, |page_num| {
+                let page = fetch_search_page(client.clone(), country.clone(), keyword.clone(), page_num, via_proxy);
+                let archive_run = archive_run.clone();
+                async move {
+                    let html = page.await?;
+                    if let Some(run_dir) = &archive_run {
+                        crate::crawl_archive::save_search_page(run_dir, page_num, &html);
+                    }
+                    Ok(html)
+                }
+            })
+            .await
+        });
+    }
+
+    let mut results = Vec::with_capacity(keywords.len());
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok(group_results) => {
+                for (id, result) in group_results {
+                    on_result(id, &result);
+                    results.push((id, result));
+                }
+            }
+            Err(e) => eprintln!("[Native] 检测任务异常: {}", e),
+        }
+    }
+    Ok(results)
+}
+
+// 原生 HTTP 后端，目前只支持排名检测
+pub struct NativeBackend;
+
+impl CrawlerBackend for NativeBackend {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn check_rankings(
+        &self,
+        keywords: Vec<RankingItem>,
+        max_pages: i64,
+        settings: CrawlerSettings,
+        control: RunControl,
+        on_result: RankingCallback,
+    ) -> BoxFuture<'_, Result<Vec<(i64, RankingResult)>, String>> {
+        Box::pin(async move {
+            check_rankings(&keywords, max_pages, settings.concurrency(), settings.proxy_assignment.as_deref(), &control, on_result)
+                .await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_search_url_and_grouping() {
+        let de = marketplace("DE");
+        let url = search_url(&de, "led lampe", 2).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://www.amazon.de/s?k=led+lampe&__mk_de_DE=%C3%85M%C3%85%C5%BD%C3%95%C3%91&page=2"
+        );
+        assert_eq!(search_url(&marketplace("US"), "desk lamp", 1).unwrap().as_str(), "https://www.amazon.com/s?k=desk+lamp");
+
+        assert_eq!(address_warning(&de, "DE", Some("10115"), Some("Lieferung an Berlin 10115")), None);
+        assert!(address_warning(&de, "DE", None, Some("Deliver to China")).unwrap().contains("配送地址"));
+        // 自定义邮编：只出现城市名不算生效，英国地址只显示邮编前半部分
+        assert!(address_warning(&de, "DE", Some("80331"), Some("Lieferung an Berlin 10115")).is_some());
+        assert_eq!(address_warning(&de, "DE", Some("80331"), Some("Lieferung an München 80331")), None);
+        let uk = marketplace("UK");
+        assert_eq!(address_warning(&uk, "UK", Some("M1 1AE"), Some("Deliver to Manchester M1")), None);
+
+        let zip = |z: &str| Some(z.to_string());
+        let keywords = vec![
+            (1, "Desk Lamp".to_string(), "B0A".to_string(), "US".to_string(), zip("10001")),
+            (2, "desk lamp".to_string(), "B0B".to_string(), "US".to_string(), zip("10001")),
+            (3, "desk lamp".to_string(), "B0A".to_string(), "UK".to_string(), zip("SW1A 1AA")),
+            (4, "desk lamp".to_string(), "B0A".to_string(), "US".to_string(), zip("90001")),
+        ];
+        let groups = group_keywords(&keywords);
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].targets, vec![(1, "B0A".to_string()), (2, "B0B".to_string())]);
+        assert_eq!((groups[2].location.as_deref(), groups[2].targets.len()), (Some("90001"), 1));
+    }
+
+    #[test]
+    fn test_check_group_against_local_server() {
+        // 本地 HTTP 服务返回保存的搜索页
+        let html = std::fs::read_to_string(
+            std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/search/US/desk-lamp/page1.html"),
+        )
+        .unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url: &'static str = Box::leak(format!("http://{}", listener.local_addr().unwrap()).into_boxed_str());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 4096];
+            let n = stream.read(&mut buf).unwrap();
+            let header = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                html.len()
+            );
+            stream.write_all(header.as_bytes()).and_then(|_| stream.write_all(html.as_bytes())).unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let (proxy, client) = ProxyRotation::new(None).unwrap().pick("US");
+        assert_eq!(proxy, None);
+        let market = Marketplace { base_url, ..marketplace("US") };
+        let group = KeywordGroup {
+            country: "US".to_string(),
+            keyword: "desk lamp".to_string(),
+            location: None,
+            targets: vec![(7, "B0TARGET01".to_string()), (8, "B0MISSING1".to_string())],
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let results = runtime.block_on(check_group(group, 1, proxy, |page_num| {
+            let (client, url) = (client.clone(), search_url(&market, "desk lamp", page_num));
+            async move { fetch_page(&client, &marketplace("US"), url?, false).await }
+        }));
+
+        let request = handle.join().unwrap();
+        assert!(request.starts_with("GET /s?k=desk+lamp HTTP/1.1"));
+        assert!(request.to_lowercase().contains("accept-language: en-us,en;q=0.9"));
+
+        let (id, target) = &results[0];
+        assert_eq!(*id, 7);
+        assert_eq!((target.organic_rank, target.sponsored_rank), (Some(3), Some(6)));
+        assert_eq!((target.pages_checked, target.error.as_deref(), target.warning.as_deref()), (Some(1), None, None));
+        assert_eq!(results[1].1.organic_rank, None);
+        assert_eq!(results[1].1.organic_top_50.len(), 4);
+    }
+}
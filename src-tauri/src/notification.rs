@@ -19,13 +19,7 @@ pub fn send_rank_change_notification<R: tauri::Runtime>(
     app: &tauri::AppHandle<R>,
     change: &RankChange,
 ) -> Result<(), String> {
-    let (title, icon) = match change.change_type {
-        RankChangeType::Improved | RankChangeType::EnteredTop10 => ("📈 排名上升提醒", "up"),
-        RankChangeType::Declined | RankChangeType::ExitedTop10 => ("📉 排名下降提醒", "down"),
-        RankChangeType::NewRank => ("🎉 新上榜提醒", "new"),
-        RankChangeType::LostRank => ("⚠️ 跌出榜单提醒", "lost"),
-    };
-
+    let title = rank_change_title(change);
     let body = format_notification_body(change);
 
     app.notification()
@@ -38,8 +32,18 @@ pub fn send_rank_change_notification<R: tauri::Runtime>(
     Ok(())
 }
 
-// 格式化通知内容
-fn format_notification_body(change: &RankChange) -> String {
+// 单条排名变化的通知标题
+pub fn rank_change_title(change: &RankChange) -> &'static str {
+    match change.change_type {
+        RankChangeType::Improved | RankChangeType::EnteredTop10 => "📈 排名上升提醒",
+        RankChangeType::Declined | RankChangeType::ExitedTop10 => "📉 排名下降提醒",
+        RankChangeType::NewRank => "🎉 新上榜提醒",
+        RankChangeType::LostRank => "⚠️ 跌出榜单提醒",
+    }
+}
+
+// 格式化通知内容（桌面通知和外部通知渠道共用）
+pub fn format_notification_body(change: &RankChange) -> String {
     let country_emoji = get_country_emoji(&change.country);
 
     match change.change_type {
@@ -104,6 +108,82 @@ fn format_notification_body(change: &RankChange) -> String {
     }
 }
 
+// 汇总通知最多列出的变化条数
+const DIGEST_MAX_LINES: usize = 5;
+
+// 发送排名变化汇总通知：一次检测只发一条，只有一个变化时使用单条通知的格式
+pub fn send_rank_change_digest<R: tauri::Runtime>(
+    app: &tauri::AppHandle<R>,
+    changes: &[RankChange],
+    checked: usize,
+) -> Result<(), String> {
+    if let [change] = changes {
+        return send_rank_change_notification(app, change);
+    }
+    let Some((title, body)) = rank_digest_message(changes, checked) else {
+        return Ok(());
+    };
+
+    app.notification()
+        .builder()
+        .title(&title)
+        .body(&body)
+        .show()
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+// 排名变化汇总的标题和内容：只有一个变化时使用单条通知的格式
+pub fn rank_digest_message(changes: &[RankChange], checked: usize) -> Option<(String, String)> {
+    match changes {
+        [] => None,
+        [change] => Some((rank_change_title(change).to_string(), format_notification_body(change))),
+        _ => Some((
+            format!("📊 排名变化汇总（{} 个关键词）", changes.len()),
+            format_digest_body(changes, checked),
+        )),
+    }
+}
+
+// 格式化汇总通知：按变化类型计数，再列出变化最大的几条
+fn format_digest_body(changes: &[RankChange], checked: usize) -> String {
+    let count = |types: &[RankChangeType]| changes.iter().filter(|c| types.contains(&c.change_type)).count();
+    let up = count(&[RankChangeType::Improved, RankChangeType::EnteredTop10, RankChangeType::NewRank]);
+    let down = count(&[RankChangeType::Declined, RankChangeType::ExitedTop10, RankChangeType::LostRank]);
+
+    let mut lines = vec![format!("已检测 {} 个关键词：📈 {} 个上升，📉 {} 个下降", checked, up, down)];
+
+    // 进出 Top10、上榜/掉榜优先，其次按变化幅度
+    let mut sorted: Vec<&RankChange> = changes.iter().collect();
+    sorted.sort_by_key(|c| {
+        let milestone = !matches!(c.change_type, RankChangeType::Improved | RankChangeType::Declined);
+        (!milestone, -c.change.abs())
+    });
+    for change in sorted.iter().take(DIGEST_MAX_LINES) {
+        lines.push(format_digest_line(change));
+    }
+    if changes.len() > DIGEST_MAX_LINES {
+        lines.push(format!("…另有 {} 个变化", changes.len() - DIGEST_MAX_LINES));
+    }
+    lines.join("\n")
+}
+
+fn format_digest_line(change: &RankChange) -> String {
+    let country_emoji = get_country_emoji(&change.country);
+    let old = change.old_rank.unwrap_or(0);
+    let new = change.new_rank.unwrap_or(0);
+    let detail = match change.change_type {
+        RankChangeType::Improved => format!("{} → {} (↑{})", old, new, change.change),
+        RankChangeType::Declined => format!("{} → {} (↓{})", old, new, change.change.abs()),
+        RankChangeType::EnteredTop10 => format!("{} → {} 🏆进入Top10", old, new),
+        RankChangeType::ExitedTop10 => format!("{} → {} 跌出Top10", old, new),
+        RankChangeType::NewRank => format!("新上榜第{}名", new),
+        RankChangeType::LostRank => format!("原第{}名已跌出榜单", old),
+    };
+    format!("{} {}: {}", country_emoji, change.keyword, detail)
+}
+
 // 发送批量检测完成通知
 pub fn send_batch_complete_notification<R: tauri::Runtime>(
     app: &tauri::AppHandle<R>,
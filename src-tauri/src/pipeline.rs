@@ -0,0 +1,232 @@
+// 一键分析流程：按顺序执行 流量级别 → 流量占比 → 词组打标 → 有序性
+// 每个步骤独立事务，运行记录写入 analysis_pipeline_runs，失败后可从失败步骤继续
+
+use crate::db::{self, PipelineRun, WorkflowStatus};
+use serde::{Deserialize, Serialize};
+
+// 全部步骤（固定顺序：后面的步骤依赖前面的结果）
+pub const PIPELINE_STEPS: &[&str] = &["traffic_level", "traffic_share", "phrase_tag", "orderliness"];
+
+// 进度事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineProgress {
+    pub run_id: i64,
+    pub product_id: i64,
+    pub step: String,
+    pub step_index: usize,   // 从 1 开始
+    pub total_steps: usize,
+    pub status: String,      // running, completed, skipped, failed
+    pub error: Option<String>,
+}
+
+// 流程执行结果（附带最新的流程状态，前端无需再单独查询）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PipelineResult {
+    pub run: PipelineRun,
+    pub workflow_status: WorkflowStatus,
+}
+
+// 校验并按固定顺序整理步骤；None 或空列表表示全部步骤
+pub fn normalize_steps(steps: Option<Vec<String>>) -> Result<Vec<String>, String> {
+    let requested = steps.unwrap_or_default();
+    if let Some(bad) = requested.iter().find(|s| !PIPELINE_STEPS.contains(&s.as_str())) {
+        return Err(format!("未知的分析步骤: {}（可选: {}）", bad, PIPELINE_STEPS.join(", ")));
+    }
+    Ok(PIPELINE_STEPS
+        .iter()
+        .filter(|s| requested.is_empty() || requested.iter().any(|r| r == *s))
+        .map(|s| s.to_string())
+        .collect())
+}
+
+/// 执行分析流程
+/// resume_run_id: 传入失败的运行记录 ID 时，跳过已完成的步骤，从失败步骤继续
+pub fn run_pipeline(
+    product_id: i64,
+    steps: Option<Vec<String>>,
+    resume_run_id: Option<i64>,
+    on_progress: impl Fn(&PipelineProgress),
+) -> Result<PipelineResult, String> {
+    let (run_id, steps, mut completed) = match resume_run_id {
+        Some(id) => {
+            let run = db::get_pipeline_run(id)
+                .map_err(|e| e.to_string())?
+                .ok_or_else(|| format!("流程记录不存在: {}", id))?;
+            if run.product_id != product_id {
+                return Err("流程记录不属于该产品".to_string());
+            }
+            if run.status != "failed" {
+                return Err(format!("只能恢复失败的流程（当前状态: {}）", run.status));
+            }
+            db::restart_pipeline_run(id).map_err(|e| e.to_string())?;
+            (id, run.steps, run.completed_steps)
+        }
+        None => {
+            let steps = normalize_steps(steps)?;
+            let id = db::create_pipeline_run(product_id, &steps).map_err(|e| e.to_string())?;
+            (id, steps, Vec::new())
+        }
+    };
+
+    let total_steps = steps.len();
+    let progress = |step: &str, index: usize, status: &str, error: Option<String>| {
+        on_progress(&PipelineProgress {
+            run_id,
+            product_id,
+            step: step.to_string(),
+            step_index: index + 1,
+            total_steps,
+            status: status.to_string(),
+            error,
+        });
+    };
+
+    for (index, step) in steps.iter().enumerate() {
+        if completed.contains(step) {
+            progress(step, index, "skipped", None);
+            continue;
+        }
+
+        progress(step, index, "running", None);
+        db::update_pipeline_run_progress(run_id, Some(step), &completed).ok();
+
+        if let Err(e) = db::run_analysis_step(product_id, step) {
+            let message = format!("{} 失败: {}", step, e);
+            db::finish_pipeline_run(run_id, "failed", Some(&message)).ok();
+            progress(step, index, "failed", Some(message.clone()));
+            return Err(message);
+        }
+
+        completed.push(step.clone());
+        db::update_pipeline_run_progress(run_id, None, &completed).ok();
+        progress(step, index, "completed", None);
+    }
+
+    db::finish_pipeline_run(run_id, "completed", None).map_err(|e| e.to_string())?;
+
+    let run = db::get_pipeline_run(run_id)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("流程记录不存在: {}", run_id))?;
+    let workflow_status = db::get_workflow_status(product_id).map_err(|e| e.to_string())?;
+
+    Ok(PipelineResult { run, workflow_status })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[test]
+    fn test_resume_failed_pipeline() {
+        let dir = db::init_test_db("pipeline");
+
+        let product_id = {
+            let conn = db::get_db().lock();
+            conn.execute("INSERT INTO products (name, country) VALUES ('desk lamp', 'US')", []).unwrap();
+            let product_id = conn.last_insert_rowid();
+            // 只有 "desk lamp" 是候选词组（大词 + 强相关），其余 4 个词都打上它
+            for (keyword, relevance, rank) in [
+                ("desk lamp", "强相关", "1000"),
+                ("led desk lamp", "弱相关", "5000"),
+                ("desk lamp with usb port", "弱相关", "60000"),
+                ("desk lamp for office", "弱相关", "80000"),
+                ("small desk lamp", "弱相关", "150000"),
+            ] {
+                conn.execute(
+                    "INSERT INTO keyword_data (product_id, keyword, relevance_level, traffic_total, avg_keyword_rank)
+                     VALUES (?1, ?2, ?3, 100, ?4)",
+                    rusqlite::params![product_id, keyword, relevance, rank],
+                )
+                .unwrap();
+            }
+            // 词组打标写入时报错，模拟第 3 步中途失败
+            conn.execute_batch(
+                "CREATE TEMP TRIGGER fail_phrase_tag BEFORE UPDATE OF phrase_tag ON keyword_data
+                 BEGIN SELECT RAISE(ABORT, 'disk I/O error'); END;",
+            )
+            .unwrap();
+            product_id
+        };
+
+        let events = RefCell::new(Vec::new());
+        let record = |p: &PipelineProgress| events.borrow_mut().push(format!("{}:{}", p.step, p.status));
+
+        let err = run_pipeline(product_id, None, None, record).unwrap_err();
+        assert!(err.starts_with("phrase_tag 失败"), "{}", err);
+        assert_eq!(
+            events.take(),
+            vec![
+                "traffic_level:running",
+                "traffic_level:completed",
+                "traffic_share:running",
+                "traffic_share:completed",
+                "phrase_tag:running",
+                "phrase_tag:failed",
+            ]
+        );
+
+        let failed = db::get_last_failed_pipeline_run(product_id).unwrap().unwrap();
+        assert_eq!(failed.completed_steps, vec!["traffic_level", "traffic_share"]);
+        assert_eq!(failed.current_step.as_deref(), Some("phrase_tag"));
+
+        {
+            let conn = db::get_db().lock();
+            // 失败步骤的事务已回滚
+            let tagged: i64 = conn
+                .query_row("SELECT COUNT(*) FROM keyword_data WHERE phrase_tag IS NOT NULL", [], |r| r.get(0))
+                .unwrap();
+            assert_eq!(tagged, 0);
+            conn.execute_batch("DROP TRIGGER fail_phrase_tag").unwrap();
+            // 已完成的步骤如果重新执行，"desk lamp" 会变成小词
+            conn.execute(
+                "UPDATE keyword_data SET avg_keyword_rank = '900000' WHERE product_id = ?1 AND keyword = 'desk lamp'",
+                [product_id],
+            )
+            .unwrap();
+        }
+
+        let result = run_pipeline(product_id, None, Some(failed.id), record).unwrap();
+        assert_eq!(
+            events.take(),
+            vec![
+                "traffic_level:skipped",
+                "traffic_share:skipped",
+                "phrase_tag:running",
+                "phrase_tag:completed",
+                "orderliness:running",
+                "orderliness:completed",
+            ]
+        );
+        assert_eq!(result.run.id, failed.id);
+        assert_eq!(result.run.status, "completed");
+        assert_eq!(result.run.attempts, 2);
+        assert_eq!(result.run.completed_steps, PIPELINE_STEPS);
+        assert!(result.workflow_status.has_phrase_tag && result.workflow_status.has_orderliness);
+
+        {
+            let conn = db::get_db().lock();
+            let level: String = conn
+                .query_row(
+                    "SELECT traffic_level FROM keyword_data WHERE product_id = ?1 AND keyword = 'desk lamp'",
+                    [product_id],
+                    |r| r.get(0),
+                )
+                .unwrap();
+            assert_eq!(level, "大词");
+            let ordered: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM keyword_data WHERE product_id = ?1 AND phrase_tag = 'desk lamp' AND orderliness = '有序'",
+                    [product_id],
+                    |r| r.get(0),
+                )
+                .unwrap();
+            assert_eq!(ordered, 5);
+        }
+
+        // 已完成的流程不能再恢复
+        assert!(run_pipeline(product_id, None, Some(failed.id), |_| {}).unwrap_err().contains("只能恢复失败的流程"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
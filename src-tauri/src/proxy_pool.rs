@@ -0,0 +1,271 @@
+// 代理池：按站点分配代理、健康检查、从检测结果统计成功率和耗时
+// 连续失败的代理自动停用；自动停用的代理仍会定期健康检查，恢复后自动启用
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::check_failures::FailureKind;
+use crate::crawler::RankingResult;
+use crate::db::{self, Proxy};
+
+// 连续失败多少次后自动停用
+pub const AUTO_DISABLE_AFTER_FAILURES: i64 = 5;
+
+// 健康检查间隔（分钟）
+pub const HEALTH_CHECK_INTERVAL_MINUTES: i64 = 30;
+
+const HEALTH_CHECK_TIMEOUT_SECS: u64 = 15;
+
+// 分配给所有站点的代理在分配表中的 key
+const ANY_COUNTRY: &str = "*";
+
+fn marketplace_url(country: &str) -> &'static str {
+    match country {
+        "UK" => "https://www.amazon.co.uk",
+        "DE" => "https://www.amazon.de",
+        "FR" => "https://www.amazon.fr",
+        "IT" => "https://www.amazon.it",
+        "ES" => "https://www.amazon.es",
+        "JP" => "https://www.amazon.co.jp",
+        _ => "https://www.amazon.com",
+    }
+}
+
+/// 爬虫使用的代理分配表（JSON：站点 -> 代理列表，"*" 为未指定站点时使用的代理）
+/// 指定了站点的代理只用于这些站点，且排在通用代理之前；没有可用代理时返回 None（直连）
+pub fn build_assignment(proxies: &[Proxy]) -> Option<String> {
+    let enabled: Vec<&Proxy> = proxies.iter().filter(|p| p.is_enabled).collect();
+    if enabled.is_empty() {
+        return None;
+    }
+    let generic: Vec<String> = enabled.iter().filter(|p| p.countries.is_empty()).map(|p| p.url.clone()).collect();
+
+    let mut assignment: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for proxy in enabled.iter().filter(|p| !p.countries.is_empty()) {
+        for country in &proxy.countries {
+            assignment.entry(country.clone()).or_default().push(proxy.url.clone());
+        }
+    }
+    for urls in assignment.values_mut() {
+        urls.extend(generic.iter().cloned());
+    }
+    assignment.insert(ANY_COUNTRY.to_string(), generic);
+    serde_json::to_string(&assignment).ok()
+}
+
+// 当前代理池的分配表
+pub fn load_assignment() -> Option<String> {
+    build_assignment(&db::get_proxies().unwrap_or_default())
+}
+
+/// 从检测结果统计代理：成功计入耗时；代理连接失败、超时、验证码算作代理失败；
+/// 其他失败（解析错误、地区限制等）与代理无关，不计入
+pub fn record_crawl_result(result: &RankingResult, kind: Option<FailureKind>) {
+    let Some(proxy) = result.proxy.as_deref() else { return };
+    let error = match kind {
+        Some(FailureKind::ProxyError | FailureKind::Timeout | FailureKind::Captcha) => {
+            result.error.as_deref().or(result.warning.as_deref())
+        }
+        _ if result.error.is_some() => return,
+        _ => None,
+    };
+    match db::record_proxy_result(proxy, result.duration_ms, error, AUTO_DISABLE_AFTER_FAILURES) {
+        Ok(true) => println!("[Proxy] 代理连续失败 {} 次，已自动停用: {}", AUTO_DISABLE_AFTER_FAILURES, proxy),
+        Ok(false) => {}
+        Err(e) => eprintln!("[Proxy] 记录代理统计失败: {}", e),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyCheckResult {
+    pub proxy_id: i64,
+    pub url: String,
+    pub ok: bool,
+    pub latency_ms: Option<i64>,
+    pub error: Option<String>,
+    pub skipped: bool,  // 不支持检查的协议（SOCKS），不影响代理状态
+    pub auto_disabled: bool,
+}
+
+fn supports_health_check(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// 通过代理请求 target，返回耗时（毫秒）；非 2xx/3xx 视为失败（多为被风控）
+pub async fn probe(proxy_url: &str, target: &str) -> Result<i64, String> {
+    let proxy = reqwest::Proxy::all(proxy_url).map_err(|e| format!("代理地址无效: {}", e))?;
+    let client = reqwest::Client::builder()
+        .proxy(proxy)
+        .timeout(Duration::from_secs(HEALTH_CHECK_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let started = Instant::now();
+    let response = client.get(target).send().await.map_err(|e| {
+        if e.is_timeout() {
+            "代理请求超时".to_string()
+        } else {
+            format!("代理连接失败: {}", e)
+        }
+    })?;
+    let status = response.status();
+    if !(status.is_success() || status.is_redirection()) {
+        return Err(format!("HTTP {}（可能被风控）", status.as_u16()));
+    }
+    Ok(started.elapsed().as_millis() as i64)
+}
+
+// 检查一个代理：请求分配的第一个站点（未分配时用美国站）的 robots.txt
+pub async fn check_proxy(proxy: &Proxy) -> ProxyCheckResult {
+    let mut result = ProxyCheckResult {
+        proxy_id: proxy.id,
+        url: proxy.url.clone(),
+        ok: false,
+        latency_ms: None,
+        error: None,
+        skipped: false,
+        auto_disabled: false,
+    };
+    if !supports_health_check(&proxy.url) {
+        result.skipped = true;
+        result.error = Some("SOCKS 代理暂不支持健康检查".to_string());
+        return result;
+    }
+
+    let country = proxy.countries.first().map(String::as_str).unwrap_or("US");
+    let target = format!("{}/robots.txt", marketplace_url(country));
+    match probe(&proxy.url, &target).await {
+        Ok(latency) => {
+            result.ok = true;
+            result.latency_ms = Some(latency);
+        }
+        Err(e) => result.error = Some(e),
+    }
+    match db::record_proxy_check(proxy.id, result.latency_ms, result.error.as_deref(), AUTO_DISABLE_AFTER_FAILURES) {
+        Ok(disabled) => result.auto_disabled = disabled,
+        Err(e) => eprintln!("[Proxy] 记录健康检查结果失败: {}", e),
+    }
+    result
+}
+
+// 依次检查多个代理
+pub async fn check_proxies(proxies: &[Proxy]) -> Vec<ProxyCheckResult> {
+    let mut results = Vec::with_capacity(proxies.len());
+    for proxy in proxies {
+        results.push(check_proxy(proxy).await);
+    }
+    results
+}
+
+// 定时任务调用：检查超过间隔未检查的代理
+pub async fn run_due_health_checks() {
+    let due: Vec<Proxy> = db::get_proxies_due_for_check(HEALTH_CHECK_INTERVAL_MINUTES)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|p| supports_health_check(&p.url))
+        .collect();
+    if due.is_empty() {
+        return;
+    }
+    let results = check_proxies(&due).await;
+    let failed = results.iter().filter(|r| !r.ok).count();
+    println!("[Proxy] 健康检查 {} 个代理，失败 {} 个", results.len(), failed);
+}
+
+/// 解析用户输入的代理地址（每行一个，可用逗号分隔），没有协议的按 http 处理
+pub fn parse_proxy_urls(text: &str) -> Result<Vec<String>, String> {
+    let mut urls: Vec<String> = Vec::new();
+    for raw in text.split(['\n', ',']).map(str::trim).filter(|s| !s.is_empty()) {
+        let url = if raw.contains("://") { raw.to_string() } else { format!("http://{}", raw) };
+        let scheme = url.split("://").next().unwrap_or_default().to_lowercase();
+        if !matches!(scheme.as_str(), "http" | "https" | "socks5" | "socks4") {
+            return Err(format!("不支持的代理协议: {}", raw));
+        }
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    Ok(urls)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn proxy(id: i64, url: &str, countries: &[&str], is_enabled: bool) -> Proxy {
+        Proxy {
+            id,
+            url: url.to_string(),
+            label: None,
+            proxy_type: "datacenter".to_string(),
+            countries: countries.iter().map(|c| c.to_string()).collect(),
+            is_enabled,
+            disabled_reason: None,
+            disabled_at: None,
+            success_count: 0,
+            failure_count: 0,
+            consecutive_failures: 0,
+            avg_duration_ms: None,
+            last_used_at: None,
+            last_error: None,
+            last_check_at: None,
+            last_check_ok: None,
+            last_check_latency_ms: None,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_build_assignment() {
+        let proxies = vec![
+            proxy(1, "http://any:1", &[], true),
+            proxy(2, "http://us:1", &["US"], true),
+            proxy(3, "http://eu:1", &["DE", "FR"], true),
+            proxy(4, "http://dead:1", &[], false),
+        ];
+        let assignment: BTreeMap<String, Vec<String>> =
+            serde_json::from_str(&build_assignment(&proxies).unwrap()).unwrap();
+        assert_eq!(assignment["*"], vec!["http://any:1"]);
+        assert_eq!(assignment["US"], vec!["http://us:1", "http://any:1"]);
+        assert_eq!(assignment["FR"], vec!["http://eu:1", "http://any:1"]);
+        assert!(!assignment.contains_key("UK"));
+
+        assert_eq!(build_assignment(&[proxy(4, "http://dead:1", &[], false)]), None);
+
+        assert_eq!(
+            parse_proxy_urls("127.0.0.1:8080\nsocks5://10.0.0.1:1080, 127.0.0.1:8080").unwrap(),
+            vec!["http://127.0.0.1:8080", "socks5://10.0.0.1:1080"]
+        );
+        assert!(parse_proxy_urls("ftp://10.0.0.1").is_err());
+    }
+
+    #[test]
+    fn test_probe_through_local_proxy() {
+        // 本地假代理：收到请求后直接返回 200，不转发
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let n = stream.read(&mut buf).unwrap();
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok").unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let latency = runtime.block_on(probe(&proxy_url, "http://health.test/robots.txt"));
+        assert!(latency.is_ok(), "{:?}", latency);
+        // 经代理发出的请求使用完整 URL
+        assert!(handle.join().unwrap().starts_with("GET http://health.test/robots.txt"));
+
+        // 端口已关闭的代理
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dead_url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        assert!(runtime.block_on(probe(&dead_url, "http://health.test/robots.txt")).is_err());
+    }
+}
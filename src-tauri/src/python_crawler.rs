@@ -0,0 +1,403 @@
+// Python 爬虫后端：通过子进程调用 scripts 下的 Playwright 脚本
+// 脚本输出遵循 crawler_protocol；批量任务从 stdin 写入任务列表
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+
+use crate::crawler::{
+    BsrResult, CrawlerSettings, ListingResult, RankingItem, RankingResult, ReviewResult, RunControl, SubcategoryResult,
+};
+use crate::crawler_backend::{BoxFuture, CrawlerBackend, ListingCallback, RankingCallback};
+use crate::crawler_protocol::{self, Limits};
+use crate::rate_limit;
+
+#[cfg(windows)]
+use std::os::windows::process::CommandExt;
+
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+const RANKING_SCRIPT: &str = "amazon_crawler_playwright.py";
+const LISTING_SCRIPT: &str = "amazon_listing_crawler.py";
+const REVIEW_SCRIPT: &str = "amazon_review_crawler.py";
+const BSR_SCRIPT: &str = "amazon_bsr_crawler.py";
+
+// 获取 Python 脚本路径
+fn script_path(name: &str) -> Result<PathBuf, String> {
+    // 尝试多个可能的路径
+    let possible_paths = vec![
+        // 开发环境
+        std::env::current_dir().map(|p| p.join("scripts").join(name)).ok(),
+        // Tauri 资源目录
+        std::env::current_exe()
+            .ok()
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+            .map(|p| p.join("scripts").join(name)),
+        // macOS .app 包内
+        std::env::current_exe()
+            .ok()
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+            .map(|p| p.join("Resources").join("scripts").join(name)),
+        // 直接在 src-tauri 目录
+        Some(PathBuf::from("src-tauri/scripts").join(name)),
+    ];
+
+    for path in possible_paths.into_iter().flatten() {
+        if path.exists() {
+            return Ok(path);
+        }
+    }
+
+    Err(format!("找不到 Python 爬虫脚本 {}", name))
+}
+
+// Windows: 隐藏命令行窗口
+#[cfg(windows)]
+fn command(program: &str) -> Command {
+    let mut cmd = Command::new(program);
+    cmd.creation_flags(CREATE_NO_WINDOW);
+    cmd
+}
+
+#[cfg(not(windows))]
+fn command(program: &str) -> Command {
+    Command::new(program)
+}
+
+// 检查 Python 是否可用
+pub fn check_python() -> Result<String, String> {
+    // 尝试不同的 Python 命令
+    for python_cmd in &["python3", "python"] {
+        if let Ok(output) = command(python_cmd).arg("--version").output() {
+            if output.status.success() {
+                return Ok(python_cmd.to_string());
+            }
+        }
+    }
+
+    Err("未找到 Python。请确保已安装 Python 3 并添加到 PATH".to_string())
+}
+
+// 检查依赖是否安装 (Playwright 版本)
+fn check_dependencies(python_cmd: &str) -> Result<(), String> {
+    let check_script = r#"
+import sys
+try:
+    from playwright.async_api import async_playwright
+    print("ok")
+except ImportError as e:
+    print(f"missing:{e}")
+    sys.exit(1)
+"#;
+
+    let output = command(python_cmd)
+        .arg("-c")
+        .arg(check_script)
+        .output()
+        .map_err(|e| format!("检查依赖失败: {}", e))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+
+    if stdout.trim() == "ok" {
+        Ok(())
+    } else {
+        Err(format!(
+            "缺少 Playwright 依赖。请运行: {} -m pip install playwright && playwright install chromium",
+            python_cmd
+        ))
+    }
+}
+
+// 检查 Python、Playwright 和脚本，返回 (Python 命令, 脚本路径)
+fn prepare(script: &str) -> Result<(String, PathBuf), String> {
+    let python_cmd = check_python()?;
+    check_dependencies(&python_cmd)?;
+    Ok((python_cmd, script_path(script)?))
+}
+
+// Python 环境和排名脚本都可用（原生引擎据此决定能否回退）
+pub fn environment_ready() -> bool {
+    prepare(RANKING_SCRIPT).is_ok()
+}
+
+// 单项任务：空闲或总时间超限视为卡死
+const SINGLE_LIMITS: Limits = Limits {
+    idle: Duration::from_secs(300),
+    total: Some(Duration::from_secs(600)),
+};
+
+// 批量任务：只限制连续无输出的时间（每完成一项都有输出）
+const BATCH_LIMITS: Limits = Limits {
+    idle: Duration::from_secs(300),
+    total: None,
+};
+
+// 开启页面归档时把归档目录传给脚本（排名和 Listing 脚本会保存页面原文）；各站点的配送邮编设置也通过环境变量传入
+fn script_command(python_cmd: &str) -> Command {
+    let mut cmd = command(python_cmd);
+    if let Some(root) = crate::crawl_archive::active_root() {
+        cmd.env(crate::crawl_archive::ARCHIVE_DIR_ENV, root);
+    }
+    if let Some(locations) = crate::delivery_location::env_json() {
+        cmd.env(crate::delivery_location::LOCATIONS_ENV, locations);
+    }
+    cmd
+}
+
+// 运行单项任务脚本，返回脚本的 result 消息
+fn run_single<T: DeserializeOwned>(script: &str, args: &[&str]) -> Result<T, String> {
+    let (python_cmd, script_path) = prepare(script)?;
+    let mut cmd = script_command(&python_cmd);
+    cmd.arg(&script_path).args(args);
+
+    let mut output: Option<T> = None;
+    let outcome = crawler_protocol::run(cmd, script, SINGLE_LIMITS, None, None, |_, result| output = Some(result));
+    match (output, outcome) {
+        (Some(result), _) => Ok(result),
+        (None, Err(e)) => Err(e),
+        (None, Ok(())) => Err(format!("{} 没有返回结果", script)),
+    }
+}
+
+// 在阻塞线程中执行
+async fn blocking<T: Send + 'static>(task: impl FnOnce() -> Result<T, String> + Send + 'static) -> Result<T, String> {
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| format!("任务执行失败: {}", e))?
+}
+
+// 调用 Python 脚本批量检测排名（支持并发），暂停/继续/取消通过 stdin 发送给脚本
+// 站点在批次中途进入限速冷却时通知脚本跳过该站点，未检测的关键词记为限速跳过
+// 脚本中途失败时，已返回的结果保留，其余关键词记为失败；一个结果都没有则整体失败
+fn check_rankings_batch(
+    keywords: Vec<RankingItem>,
+    max_pages: i64,
+    settings: CrawlerSettings,
+    control: RunControl,
+    mut on_result: RankingCallback,
+) -> Result<Vec<(i64, RankingResult)>, String> {
+    let (python_cmd, script_path) = prepare(RANKING_SCRIPT)?;
+
+    // 准备输入数据: [[id, keyword, asin, country, zipcode], ...]
+    let input_json = serde_json::to_string(&keywords)
+        .map_err(|e| format!("序列化输入数据失败: {}", e))?;
+
+    // 处理代理分配表
+    let proxy_arg = settings.proxy_assignment.unwrap_or_else(|| "none".to_string());
+
+    // 调用 Python 脚本 --batch 模式（带并发参数、标签页数量、代理分配表）
+    let mut cmd = script_command(&python_cmd);
+    cmd.arg(&script_path)
+        .arg("--batch")
+        .arg("false")  // headless=false，有头模式（窗口隐藏到屏幕外）
+        .arg(max_pages.to_string())
+        .arg(settings.max_browsers.to_string())  // 并发浏览器数量
+        .arg(settings.tabs_per_browser.to_string())  // 每浏览器标签页数量
+        .arg(&proxy_arg);  // 代理分配表
+
+    let mut results: Vec<(i64, RankingResult)> = Vec::new();
+    let mut skipped: HashMap<String, String> = HashMap::new();  // 站点 → 限速原因
+    let outcome = crawler_protocol::run(
+        cmd,
+        RANKING_SCRIPT,
+        BATCH_LIMITS,
+        Some(&control),
+        Some(&input_json),
+        |monitoring_id, result: RankingResult| match monitoring_id {
+            Some(monitoring_id) => {
+                // on_result 已把结果计入站点限速状态，验证码可能让站点进入冷却
+                on_result(monitoring_id, &result);
+                if result.error.is_some() && !skipped.contains_key(&result.country) {
+                    if let Err(reason) = rate_limit::check(&result.country) {
+                        control.skip_country(&result.country);
+                        control.log("warning", "rate_limit", &format!("{}，跳过该站点剩余的关键词", reason));
+                        skipped.insert(result.country.clone(), reason);
+                    }
+                }
+                results.push((monitoring_id, result));
+            }
+            None => control.log("warning", RANKING_SCRIPT, "批量结果缺少 id，已忽略"),
+        },
+    );
+
+    for (id, keyword, asin, country, location) in &keywords {
+        let Some(reason) = skipped.get(country) else { continue };
+        if !results.iter().any(|(done, _)| done == id) {
+            let mut result = crate::crawler::failed_result(keyword.clone(), asin.clone(), country.clone(), reason.clone());
+            result.location = location.clone();
+            on_result(*id, &result);
+            results.push((*id, result));
+        }
+    }
+
+    if let Err(e) = outcome {
+        if results.is_empty() {
+            return Err(e);
+        }
+        if !control.is_cancelled() {
+            for (id, keyword, asin, country, location) in keywords {
+                if !results.iter().any(|(done, _)| *done == id) {
+                    let mut result = crate::crawler::failed_result(keyword, asin, country, e.clone());
+                    result.location = location;
+                    on_result(id, &result);
+                    results.push((id, result));
+                }
+            }
+        }
+    }
+    Ok(results)
+}
+
+// 调用 Python 脚本批量爬取 Listing（复用同一个浏览器）
+fn fetch_listings_batch(
+    items: Vec<(i64, String, String)>,
+    mut on_result: ListingCallback,
+) -> Result<Vec<(i64, ListingResult)>, String> {
+    let (python_cmd, script_path) = prepare(LISTING_SCRIPT)?;
+
+    let input_json = serde_json::to_string(&items)
+        .map_err(|e| format!("序列化输入数据失败: {}", e))?;
+
+    let mut cmd = script_command(&python_cmd);
+    cmd.arg(&script_path)
+        .arg("--batch")
+        .arg("new");  // 无头模式
+
+    // 写入后关闭 stdin，脚本读到 EOF 后开始处理
+    let mut results: Vec<(i64, ListingResult)> = Vec::new();
+    let outcome = crawler_protocol::run(
+        cmd,
+        LISTING_SCRIPT,
+        BATCH_LIMITS,
+        None,
+        Some(&input_json),
+        |competitor_id, result: ListingResult| {
+            if let Some(competitor_id) = competitor_id {
+                on_result(competitor_id, &result);
+                results.push((competitor_id, result));
+            }
+        },
+    );
+
+    if let Err(e) = outcome {
+        if results.is_empty() {
+            return Err(e);
+        }
+        for (id, asin, country) in items {
+            if !results.iter().any(|(done, _)| *done == id) {
+                let result = crate::crawler::failed_listing(asin, country, e.clone());
+                on_result(id, &result);
+                results.push((id, result));
+            }
+        }
+    }
+    Ok(results)
+}
+
+/// 用 Playwright 脚本的解析规则重新解析归档的一次搜索检测（crawl_archive 调用，不访问网络）
+pub async fn reparse_ranking(keyword: String, asin: String, country: String, run_dir: PathBuf) -> Result<RankingResult, String> {
+    blocking(move || {
+        let run_dir = run_dir.to_string_lossy().to_string();
+        run_single(RANKING_SCRIPT, &["--reparse-search", &keyword, &asin, &country, &run_dir])
+    })
+    .await
+}
+
+/// 用 Listing 脚本的提取规则重新解析归档的详情页（crawl_archive 调用，不访问网络）
+pub async fn reparse_listing(asin: String, country: String, page_path: PathBuf) -> Result<ListingResult, String> {
+    blocking(move || {
+        let page_path = page_path.to_string_lossy().to_string();
+        run_single(LISTING_SCRIPT, &["--reparse", &asin, &country, &page_path])
+    })
+    .await
+}
+
+// Python 子进程后端，支持全部爬取任务
+pub struct PythonBackend;
+
+impl CrawlerBackend for PythonBackend {
+    fn name(&self) -> &'static str {
+        "python"
+    }
+
+    fn check_rankings(
+        &self,
+        keywords: Vec<RankingItem>,
+        max_pages: i64,
+        settings: CrawlerSettings,
+        control: RunControl,
+        on_result: RankingCallback,
+    ) -> BoxFuture<'_, Result<Vec<(i64, RankingResult)>, String>> {
+        Box::pin(blocking(move || check_rankings_batch(keywords, max_pages, settings, control, on_result)))
+    }
+
+    // 单关键词模式：有头浏览器，不使用代理
+    fn search_rank(
+        &self,
+        keyword: String,
+        target_asin: String,
+        country: String,
+        location: String,
+        max_pages: i64,
+        _settings: CrawlerSettings,
+    ) -> BoxFuture<'_, Result<RankingResult, String>> {
+        Box::pin(blocking(move || {
+            let max_pages = max_pages.to_string();
+            // 参数: 关键词 ASIN 站点 页数 代理 headless（false 为有头模式，窗口隐藏到屏幕外） 邮编
+            run_single(RANKING_SCRIPT, &[&keyword, &target_asin, &country, &max_pages, "none", "false", &location])
+        }))
+    }
+
+    fn fetch_listing(&self, asin: String, country: String) -> BoxFuture<'_, Result<ListingResult, String>> {
+        Box::pin(blocking(move || {
+            run_single(LISTING_SCRIPT, &[&asin, &country, "new"])  // 无头模式
+        }))
+    }
+
+    fn fetch_listings_batch(
+        &self,
+        items: Vec<(i64, String, String)>,
+        on_result: ListingCallback,
+    ) -> BoxFuture<'_, Result<Vec<(i64, ListingResult)>, String>> {
+        Box::pin(blocking(move || fetch_listings_batch(items, on_result)))
+    }
+
+    fn fetch_reviews(&self, asin: String, country: String) -> BoxFuture<'_, Result<ReviewResult, String>> {
+        Box::pin(blocking(move || {
+            run_single(REVIEW_SCRIPT, &[&asin, &country, "new"])  // headless=new 模式
+        }))
+    }
+
+    fn fetch_category_bsr(&self, marketplace: String, category_id: String) -> BoxFuture<'_, Result<BsrResult, String>> {
+        Box::pin(blocking(move || {
+            run_single(BSR_SCRIPT, &[&marketplace, &category_id, "true"])  // headless 模式
+        }))
+    }
+
+    fn discover_subcategories(
+        &self,
+        marketplace: String,
+        parent_category: String,
+    ) -> BoxFuture<'_, Result<SubcategoryResult, String>> {
+        Box::pin(blocking(move || {
+            run_single(BSR_SCRIPT, &["discover", &marketplace, &parent_category, "true"])
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_python() {
+        // 这个测试假设系统安装了 Python
+        let result = check_python();
+        println!("Python check result: {:?}", result);
+    }
+}
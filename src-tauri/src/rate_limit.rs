@@ -0,0 +1,297 @@
+// 站点访问频率控制：所有爬取入口共用，状态持久化在数据库中，定时检测、手动检测和命令行共享
+// 每个站点每小时有请求预算（一次关键词搜索、Listing、评论、BSR 或子类目抓取记一次）；
+// 遇到验证码或 HTTP 503 时提高限速等级：等级越高预算和并发（浏览器数、标签页数）越低，
+// 从 COOLDOWN_FROM_LEVEL 级起还要进入冷却期，冷却期内不再请求该站点；之后连续成功逐级恢复
+
+use chrono::{Duration, NaiveDateTime, Utc};
+
+use crate::check_failures::{classify_message, FailureKind};
+use crate::crawler::CrawlerSettings;
+use crate::db::{self, RateLimitState};
+
+// 每个站点每小时默认请求预算
+pub const DEFAULT_HOURLY_BUDGET: i64 = 300;
+
+// 最高限速等级
+pub const MAX_LEVEL: i64 = 3;
+
+// 达到该等级起每次触发都进入冷却，冷却分钟数每升一级翻倍
+const COOLDOWN_FROM_LEVEL: i64 = 2;
+const COOLDOWN_BASE_MINUTES: i64 = 10;
+
+// 连续成功多少次后降低一级
+const RECOVER_AFTER_SUCCESSES: i64 = 20;
+
+// 设置界面列出的站点
+const MARKETPLACES: &[&str] = &["US", "UK", "DE", "FR", "IT", "ES", "JP"];
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+fn parse_time(value: Option<&str>) -> Option<NaiveDateTime> {
+    value.and_then(|v| NaiveDateTime::parse_from_str(v, TIME_FORMAT).ok())
+}
+
+fn format_time(time: NaiveDateTime) -> String {
+    time.format(TIME_FORMAT).to_string()
+}
+
+// 提示中显示的本地时间
+fn local_time(time: NaiveDateTime) -> String {
+    time.and_utc().with_timezone(&chrono::Local).format("%H:%M").to_string()
+}
+
+// 当前限速等级下的每小时预算，0 表示不限
+fn effective_budget(state: &RateLimitState) -> i64 {
+    if state.hourly_budget <= 0 {
+        return 0;
+    }
+    (state.hourly_budget >> state.level).max(1)
+}
+
+// 超过一小时开始新的计数窗口
+fn roll_window(state: &mut RateLimitState, now: NaiveDateTime) {
+    let expired = parse_time(state.window_start.as_deref()).is_none_or(|start| now - start >= Duration::hours(1));
+    if expired {
+        state.window_start = Some(format_time(now));
+        state.window_requests = 0;
+    }
+}
+
+/// 现在能否请求该站点；冷却中或本小时预算用完时返回提示（会被归类为限速，不计入关键词的连续失败）
+fn blocked_reason(state: &RateLimitState, now: NaiveDateTime) -> Option<String> {
+    if let Some(until) = parse_time(state.cooldown_until.as_deref()).filter(|until| *until > now) {
+        return Some(format!("{} 站点限速冷却中（{} 级），{} 后恢复", state.country, state.level, local_time(until)));
+    }
+    let budget = effective_budget(state);
+    let window_start = parse_time(state.window_start.as_deref())?;
+    if budget > 0 && state.window_requests >= budget && now - window_start < Duration::hours(1) {
+        return Some(format!(
+            "{} 站点本小时请求预算已用完（{} 次），{} 后恢复",
+            state.country,
+            budget,
+            local_time(window_start + Duration::hours(1))
+        ));
+    }
+    None
+}
+
+// 占用最多 wanted 次请求，返回实际占用的次数
+fn take(state: &mut RateLimitState, wanted: i64, now: NaiveDateTime) -> Result<i64, String> {
+    roll_window(state, now);
+    if let Some(reason) = blocked_reason(state, now) {
+        return Err(reason);
+    }
+    let budget = effective_budget(state);
+    let granted = if budget > 0 { wanted.min(budget - state.window_requests) } else { wanted };
+    state.window_requests += granted;
+    Ok(granted)
+}
+
+// 退还占用后没有用上的请求；reserved_at 之后已开始新的计数窗口时不退还（旧窗口的占用已作废）
+fn give_back(state: &mut RateLimitState, count: i64, reserved_at: NaiveDateTime) {
+    let current = parse_time(state.window_start.as_deref()).is_some_and(|start| start <= reserved_at);
+    if current {
+        state.window_requests = (state.window_requests - count).max(0);
+    }
+}
+
+// 记录一次请求结果：验证码 / 503 升级并冷却，成功累计到一定次数降级，其他失败不影响
+fn apply_outcome(state: &mut RateLimitState, error: Option<&str>, now: NaiveDateTime) {
+    match error {
+        Some(error) if classify_message(error) == FailureKind::Captcha => {
+            state.level = (state.level + 1).min(MAX_LEVEL);
+            state.success_streak = 0;
+            state.blocked_count += 1;
+            state.last_blocked_at = Some(format_time(now));
+            state.last_blocked_reason = Some(error.to_string());
+            if state.level >= COOLDOWN_FROM_LEVEL {
+                let minutes = COOLDOWN_BASE_MINUTES << (state.level - COOLDOWN_FROM_LEVEL);
+                state.cooldown_until = Some(format_time(now + Duration::minutes(minutes)));
+            }
+        }
+        Some(_) => {}
+        None => {
+            state.success_streak += 1;
+            if state.level > 0 && state.success_streak >= RECOVER_AFTER_SUCCESSES {
+                state.level -= 1;
+                state.success_streak = 0;
+            }
+        }
+    }
+}
+
+fn now() -> NaiveDateTime {
+    Utc::now().naive_utc()
+}
+
+/// 检查站点当前是否可以请求（不占用预算）
+pub fn check(country: &str) -> Result<(), String> {
+    let state = db::get_rate_limit(country, DEFAULT_HOURLY_BUDGET).map_err(|e| e.to_string())?;
+    match blocked_reason(&state, now()) {
+        Some(reason) => Err(reason),
+        None => Ok(()),
+    }
+}
+
+/// 为 wanted 次请求占用预算，返回允许的次数（可能少于 wanted）；冷却中或预算用完返回 Err(提示)
+/// 数据库出错时不限制，避免因为统计失败而停止爬取
+pub fn reserve(country: &str, wanted: i64) -> Result<i64, String> {
+    let now = now();
+    let mut outcome = Ok(wanted);
+    if let Err(e) = db::update_rate_limit(country, DEFAULT_HOURLY_BUDGET, |state| outcome = take(state, wanted, now)) {
+        eprintln!("[RateLimit] 读取 {} 限速状态失败: {}", country, e);
+        return Ok(wanted);
+    }
+    outcome
+}
+
+/// 退还批次取消或失败后没有用上的预算（reserved_at 为占用时间）
+pub fn refund(country: &str, count: i64, reserved_at: NaiveDateTime) {
+    if count <= 0 {
+        return;
+    }
+    if let Err(e) = db::update_rate_limit(country, DEFAULT_HOURLY_BUDGET, |state| give_back(state, count, reserved_at)) {
+        eprintln!("[RateLimit] 退还 {} 请求预算失败: {}", country, e);
+    }
+}
+
+/// 记录一次爬取结果（error 为结果中的错误信息）
+pub fn record(country: &str, error: Option<&str>) {
+    let now = now();
+    let blocked = error.is_some_and(|e| classify_message(e) == FailureKind::Captcha);
+    match db::update_rate_limit(country, DEFAULT_HOURLY_BUDGET, |state| apply_outcome(state, error, now)) {
+        Ok(state) if blocked => println!(
+            "[RateLimit] {} 触发验证码，限速等级 {}，冷却至 {} (UTC)",
+            country,
+            state.level,
+            state.cooldown_until.as_deref().unwrap_or("-")
+        ),
+        Ok(_) => {}
+        Err(e) => eprintln!("[RateLimit] 记录 {} 限速状态失败: {}", country, e),
+    }
+}
+
+// 各站点的限速状态
+pub fn list() -> Result<Vec<RateLimitState>, String> {
+    db::get_rate_limits(MARKETPLACES, DEFAULT_HOURLY_BUDGET).map_err(|e| e.to_string())
+}
+
+// 设置站点每小时预算，0 表示不限
+pub fn set_budget(country: &str, hourly_budget: i64) -> Result<RateLimitState, String> {
+    if hourly_budget < 0 {
+        return Err("请求预算不能为负数".to_string());
+    }
+    db::update_rate_limit(country, DEFAULT_HOURLY_BUDGET, |state| state.hourly_budget = hourly_budget)
+        .map_err(|e| e.to_string())
+}
+
+/// 按批次中站点的最高限速等级降低并发：每升一级浏览器数减半，限速后每个浏览器只开一个标签页
+pub fn throttle_settings(settings: &CrawlerSettings, countries: &[String]) -> CrawlerSettings {
+    let level = countries
+        .iter()
+        .filter_map(|country| db::get_rate_limit(country, DEFAULT_HOURLY_BUDGET).ok())
+        .map(|state| state.level)
+        .max()
+        .unwrap_or(0);
+    throttle_for_level(settings, level)
+}
+
+fn throttle_for_level(settings: &CrawlerSettings, level: i64) -> CrawlerSettings {
+    let mut throttled = settings.clone();
+    if level > 0 {
+        throttled.max_browsers = (settings.max_browsers >> level.min(MAX_LEVEL)).max(1);
+        throttled.tabs_per_browser = 1;
+    }
+    throttled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, TIME_FORMAT).unwrap()
+    }
+
+    fn state(budget: i64) -> RateLimitState {
+        RateLimitState {
+            country: "DE".to_string(),
+            hourly_budget: budget,
+            level: 0,
+            success_streak: 0,
+            window_start: None,
+            window_requests: 0,
+            cooldown_until: None,
+            blocked_count: 0,
+            last_blocked_at: None,
+            last_blocked_reason: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn test_budget_window() {
+        let mut s = state(10);
+        assert_eq!(take(&mut s, 6, at("2026-01-01 10:00:00")), Ok(6));
+        assert_eq!(take(&mut s, 6, at("2026-01-01 10:10:00")), Ok(4));
+        assert!(take(&mut s, 1, at("2026-01-01 10:20:00")).unwrap_err().contains("预算已用完"));
+        // 一小时后重新计数
+        assert_eq!(take(&mut s, 3, at("2026-01-01 11:00:00")), Ok(3));
+        assert_eq!(s.window_requests, 3);
+
+        // 0 表示不限
+        // 退还没用上的请求；进入新窗口后旧窗口的占用不再退还
+        give_back(&mut s, 2, at("2026-01-01 11:05:00"));
+        assert_eq!(s.window_requests, 1);
+        give_back(&mut s, 5, at("2026-01-01 11:05:00"));
+        assert_eq!(s.window_requests, 0);
+        assert_eq!(take(&mut s, 4, at("2026-01-01 12:30:00")), Ok(4));
+        give_back(&mut s, 4, at("2026-01-01 11:05:00"));
+        assert_eq!(s.window_requests, 4);
+
+        let mut unlimited = state(0);
+        assert_eq!(take(&mut unlimited, 1000, at("2026-01-01 10:00:00")), Ok(1000));
+    }
+
+    #[test]
+    fn test_captcha_slowdown_and_recovery() {
+        let mut s = state(100);
+        let t0 = at("2026-01-01 10:00:00");
+        apply_outcome(&mut s, Some("page.goto: Timeout 30000ms exceeded."), t0);
+        assert_eq!(s.level, 0);
+
+        // 第 1 级只减半预算，不冷却
+        apply_outcome(&mut s, Some("HTTP 503，疑似触发验证码"), t0);
+        assert_eq!((s.level, s.cooldown_until.as_deref()), (1, None));
+        assert_eq!(take(&mut s, 80, at("2026-01-01 10:01:00")), Ok(50));
+
+        apply_outcome(&mut s, Some("检测到验证码页面"), t0);
+        assert_eq!((s.level, s.cooldown_until.as_deref()), (2, Some("2026-01-01 10:10:00")));
+        assert!(take(&mut s, 1, at("2026-01-01 10:05:00")).unwrap_err().contains("冷却中"));
+
+        apply_outcome(&mut s, Some("Robot Check"), t0);
+        apply_outcome(&mut s, Some("Robot Check"), t0);
+        assert_eq!((s.level, s.blocked_count), (MAX_LEVEL, 4));
+        assert_eq!(s.cooldown_until.as_deref(), Some("2026-01-01 10:20:00"));
+
+        for _ in 0..RECOVER_AFTER_SUCCESSES {
+            apply_outcome(&mut s, None, t0);
+        }
+        assert_eq!((s.level, s.success_streak), (MAX_LEVEL - 1, 0));
+    }
+
+    #[test]
+    fn test_throttle_for_level() {
+        let settings = CrawlerSettings {
+            engine: crate::crawler::CrawlerEngine::Playwright,
+            max_browsers: 5,
+            tabs_per_browser: 3,
+            proxy_assignment: None,
+        };
+        let same = throttle_for_level(&settings, 0);
+        assert_eq!((same.max_browsers, same.tabs_per_browser), (5, 3));
+        let slowed = throttle_for_level(&settings, 1);
+        assert_eq!((slowed.max_browsers, slowed.tabs_per_browser), (2, 1));
+        assert_eq!(throttle_for_level(&settings, MAX_LEVEL).max_browsers, 1);
+    }
+}
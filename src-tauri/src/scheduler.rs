@@ -1,20 +1,60 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use chrono::{Utc, Timelike, FixedOffset, Datelike};
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, NaiveTime, TimeZone, Utc, Timelike, FixedOffset, Datelike};
+use chrono_tz::Tz;
 use tauri::Emitter;
 
+use crate::alerts::RankPosition;
 use crate::crawler;
 use crate::db;
 use crate::ai;
+use crate::notification;
 
 // 调度器状态
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct SchedulerStatus {
     pub is_running: bool,
     pub last_check_time: Option<String>,
-    pub next_check_time: Option<String>,
+    pub next_check_time: Option<String>,    // 各站点中最早的下次检测时间
     pub current_task: Option<String>,
+    #[serde(default)]
+    pub marketplaces: Vec<MarketplaceCheckStatus>,
+}
+
+// 单个站点的检测窗口状态
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MarketplaceCheckStatus {
+    pub country: String,
+    pub timezone: String,
+    pub local_time: String,                 // 站点当地时间 HH:MM
+    pub in_window: bool,
+    pub next_check_time: Option<String>,
+}
+
+// 站点检测窗口（站点当地时间，小时，左闭右开）
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MarketplaceWindow {
+    pub timezone: Option<String>,   // IANA 时区，为空时按站点默认时区
+    pub morning_start: u32,
+    pub morning_end: u32,
+    pub evening_start: u32,
+    pub evening_end: u32,
+}
+
+impl Default for MarketplaceWindow {
+    fn default() -> Self {
+        let defaults = SchedulerSettings::default();
+        Self {
+            timezone: None,
+            morning_start: defaults.morning_start,
+            morning_end: defaults.morning_end,
+            evening_start: defaults.evening_start,
+            evening_end: defaults.evening_end,
+        }
+    }
 }
 
 // 调度器设置
@@ -31,7 +71,20 @@ pub struct SchedulerSettings {
     pub notify_on_exit_top10: bool,
     pub notify_on_new_rank: bool,
     pub notify_on_lost_rank: bool,
-    pub max_pages: u32,         // 监控页数: 1/3/5
+    pub max_pages: u32,         // 监控页数: 1/3/5（关键词可单独设置）
+    // 各优先级的检测间隔（小时）：距上次检测超过该时长才会再次检测
+    pub high_priority_hours: u32,    // 4
+    pub medium_priority_hours: u32,  // 8
+    pub low_priority_hours: u32,     // 20
+    // 按站点配置的检测窗口（站点当地时间），未配置的站点使用上面的默认窗口
+    pub marketplace_windows: HashMap<String, MarketplaceWindow>,
+    // 排名历史保留：原始检测保留天数（至少 90），之后汇总为日数据；日数据保留天数，之后汇总为周数据
+    pub history_raw_days: u32,     // 90
+    pub history_daily_days: u32,   // 365
+    // 检测失败记录和任务明细（每次任务中各关键词的检测结果）保留天数，只用于近期排查
+    pub check_log_days: u32,       // 30
+    // 连续检测失败多少次后自动暂停该关键词，0 表示不自动暂停
+    pub auto_pause_after_failures: u32,  // 5
 }
 
 impl Default for SchedulerSettings {
@@ -48,42 +101,167 @@ impl Default for SchedulerSettings {
             notify_on_new_rank: true,
             notify_on_lost_rank: true,
             max_pages: 5,  // 默认监控前5页
+            high_priority_hours: 4,
+            medium_priority_hours: 8,
+            low_priority_hours: 20,  // 约每天一次（早晚窗口间隔不足 24 小时）
+            marketplace_windows: HashMap::new(),
+            history_raw_days: 90,
+            history_daily_days: 365,
+            check_log_days: 30,
+            auto_pause_after_failures: 5,
         }
     }
 }
 
-// 检查当前时间是否在检测窗口内（使用北京时间 UTC+8）
-pub fn is_in_check_window(_country: &str, settings: &SchedulerSettings) -> bool {
-    // 北京时间 UTC+8
-    let beijing_offset = FixedOffset::east_opt(8 * 3600).unwrap();
-    let beijing_time = Utc::now().with_timezone(&beijing_offset);
-    let hour = beijing_time.hour();
+// 从数据库读取调度器设置，未保存过或解析失败时使用默认值
+pub fn load_settings() -> SchedulerSettings {
+    db::get_setting("scheduler_settings")
+        .ok()
+        .flatten()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
 
-    // 检查是否在早间窗口
-    if hour >= settings.morning_start && hour < settings.morning_end {
-        return true;
+// 站点默认时区（夏令时由时区规则自动处理）
+pub fn default_timezone(country: &str) -> Tz {
+    match country {
+        "US" => chrono_tz::America::New_York,
+        "CA" => chrono_tz::America::Toronto,
+        "MX" => chrono_tz::America::Mexico_City,
+        "BR" => chrono_tz::America::Sao_Paulo,
+        "UK" | "GB" => chrono_tz::Europe::London,
+        "DE" => chrono_tz::Europe::Berlin,
+        "FR" => chrono_tz::Europe::Paris,
+        "IT" => chrono_tz::Europe::Rome,
+        "ES" => chrono_tz::Europe::Madrid,
+        "NL" => chrono_tz::Europe::Amsterdam,
+        "SE" => chrono_tz::Europe::Stockholm,
+        "PL" => chrono_tz::Europe::Warsaw,
+        "JP" => chrono_tz::Asia::Tokyo,
+        "IN" => chrono_tz::Asia::Kolkata,
+        "AE" => chrono_tz::Asia::Dubai,
+        "SG" => chrono_tz::Asia::Singapore,
+        "AU" => chrono_tz::Australia::Sydney,
+        _ => chrono_tz::Asia::Shanghai,
     }
+}
+
+// 获取站点的检测窗口和时区（自定义时区无效时回退到站点默认时区）
+pub fn marketplace_window(country: &str, settings: &SchedulerSettings) -> (MarketplaceWindow, Tz) {
+    let window = settings
+        .marketplace_windows
+        .get(country)
+        .cloned()
+        .unwrap_or(MarketplaceWindow {
+            timezone: None,
+            morning_start: settings.morning_start,
+            morning_end: settings.morning_end,
+            evening_start: settings.evening_start,
+            evening_end: settings.evening_end,
+        });
+    let tz = window
+        .timezone
+        .as_deref()
+        .and_then(|name| name.parse::<Tz>().ok())
+        .unwrap_or_else(|| default_timezone(country));
+    (window, tz)
+}
+
+fn hour_in_window(hour: u32, window: &MarketplaceWindow) -> bool {
+    (hour >= window.morning_start && hour < window.morning_end)
+        || (hour >= window.evening_start && hour < window.evening_end)
+}
+
+// 检查指定时间是否在站点的检测窗口内（站点当地时间）
+pub fn is_in_check_window_at(country: &str, settings: &SchedulerSettings, at: DateTime<Utc>) -> bool {
+    let (window, tz) = marketplace_window(country, settings);
+    hour_in_window(at.with_timezone(&tz).hour(), &window)
+}
 
-    // 检查是否在晚间窗口
-    if hour >= settings.evening_start && hour < settings.evening_end {
-        return true;
+// 站点当前检测窗口的剩余时间；不在窗口内时返回 None
+pub fn window_remaining(country: &str, settings: &SchedulerSettings, at: DateTime<Utc>) -> Option<Duration> {
+    let (window, tz) = marketplace_window(country, settings);
+    let local = at.with_timezone(&tz);
+    let hour = local.hour();
+    let end_hour = if hour >= window.morning_start && hour < window.morning_end {
+        window.morning_end
+    } else if hour >= window.evening_start && hour < window.evening_end {
+        window.evening_end
+    } else {
+        return None;
+    };
+
+    // 窗口结束于 24 点时即次日 0 点；结束时刻落在夏令时跳过的时段时取最早的有效时刻
+    let (date, end_hour) = if end_hour >= 24 {
+        (local.date_naive() + Duration::days(1), 0)
+    } else {
+        (local.date_naive(), end_hour)
+    };
+    let end = (end_hour..24).find_map(|h| {
+        let naive = date.and_time(NaiveTime::from_hms_opt(h, 0, 0)?);
+        tz.from_local_datetime(&naive).earliest()
+    })?;
+    Some(end.with_timezone(&Utc) - at)
+}
+
+// 检查当前时间是否在站点的检测窗口内
+pub fn is_in_check_window(country: &str, settings: &SchedulerSettings) -> bool {
+    is_in_check_window_at(country, settings, Utc::now())
+}
+
+// 计算不早于 earliest 的下次可检测时间：earliest 在窗口内时即为 earliest，否则为下一个窗口的开始
+// 窗口开始时刻落在夏令时跳过的时段内时（如 2:00 → 3:00），顺延到跳变后的第一个整点
+pub fn next_check_time(country: &str, settings: &SchedulerSettings, earliest: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let (window, tz) = marketplace_window(country, settings);
+    if hour_in_window(earliest.with_timezone(&tz).hour(), &window) {
+        return Some(earliest);
     }
 
-    false
+    let mut starts: Vec<u32> = [
+        (window.morning_start, window.morning_end),
+        (window.evening_start, window.evening_end),
+    ]
+    .iter()
+    .filter(|(start, end)| start < end && *start < 24)
+    .map(|(start, _)| *start)
+    .collect();
+    starts.sort_unstable();
+
+    let local_date = earliest.with_timezone(&tz).date_naive();
+    for day in 0..3 {
+        let date = local_date + Duration::days(day);
+        for start in &starts {
+            let candidate = (*start..24).find_map(|hour| {
+                let naive = date.and_time(NaiveTime::from_hms_opt(hour, 0, 0)?);
+                tz.from_local_datetime(&naive).earliest()
+            });
+            if let Some(local) = candidate {
+                let utc = local.with_timezone(&Utc);
+                if utc > earliest {
+                    return Some(utc);
+                }
+            }
+        }
+    }
+    None
 }
 
 // 排名变化结果
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct RankChange {
+    pub monitoring_id: i64,
+    pub product_id: i64,
     pub keyword: String,
+    pub asin: String,
     pub country: String,
-    pub old_rank: Option<i64>,
+    pub old_rank: Option<i64>,  // 自然结果中的绝对位置（跨页累计）
     pub new_rank: Option<i64>,
     pub change: i64,
     pub change_type: RankChangeType,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RankChangeType {
     Improved,      // 排名上升
     Declined,      // 排名下降
@@ -144,6 +322,449 @@ pub fn should_notify(
     }
 }
 
+// 对比上次排名，返回需要通知的变化；首次检测的监控项没有可比较的排名，不产生变化
+// 按绝对位置比较（页内排名跨页不可比）；旧记录在第 1 页之后没有绝对位置，无法计算变化
+pub fn detect_rank_change(
+    item: &db::KeywordMonitoring,
+    previous: Option<RankPosition>,
+    current: Option<RankPosition>,
+    settings: &SchedulerSettings,
+) -> Option<RankChange> {
+    item.last_checked.as_ref()?;
+    if [previous, current].iter().flatten().any(|p| p.position.is_none()) {
+        return None;
+    }
+    let (previous_rank, new_rank) = (previous.and_then(|p| p.position), current.and_then(|p| p.position));
+    let (change, change_type) = calculate_rank_change(previous_rank, new_rank)?;
+    if !should_notify(change, &change_type, settings) {
+        return None;
+    }
+    Some(RankChange {
+        monitoring_id: item.id,
+        product_id: item.product_id,
+        keyword: item.keyword.clone(),
+        asin: item.asin.clone(),
+        country: item.country.clone(),
+        old_rank: previous_rank,
+        new_rank,
+        change,
+        change_type,
+    })
+}
+
+// 一次定时检测的排名变化汇总（随 ranking-changes 事件发送给前端）
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RankChangeDigest {
+    pub task_id: Option<i64>,
+    pub trigger_type: String,
+    pub checked: i64,
+    pub failed: i64,
+    pub changes: Vec<RankChange>,
+}
+
+// ==================== Cron 计划 ====================
+
+// 计划的到期宽限：触发时间过去不超过该时长视为按时执行，超过则视为错过（如电脑休眠）
+const CRON_GRACE_MINUTES: i64 = 10;
+// 计划执行时跳过最近已检测过的监控项
+const CRON_SKIP_RECENT_MINUTES: i64 = 30;
+
+// 解析 cron 表达式：支持标准 5 段（分 时 日 月 周）以及带秒的 6/7 段
+pub fn parse_cron(expr: &str) -> Result<cron::Schedule, String> {
+    let expr = expr.trim();
+    let normalized = if expr.split_whitespace().count() == 5 {
+        format!("0 {}", expr)
+    } else {
+        expr.to_string()
+    };
+    normalized
+        .parse::<cron::Schedule>()
+        .map_err(|e| format!("无效的 cron 表达式 \"{}\": {}", expr, e))
+}
+
+fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>().map_err(|_| format!("无效的时区: {}", name))
+}
+
+// 计算 after 之后的下一次触发时间（按计划时区解析表达式）
+pub fn next_cron_run(expr: &str, timezone: &str, after: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, String> {
+    let schedule = parse_cron(expr)?;
+    let tz = parse_timezone(timezone)?;
+    Ok(schedule
+        .after(&after.with_timezone(&tz))
+        .next()
+        .map(|t| t.with_timezone(&Utc)))
+}
+
+// 预览接下来的 count 次触发时间
+pub fn preview_cron(expr: &str, timezone: &str, count: usize) -> Result<Vec<DateTime<Utc>>, String> {
+    let schedule = parse_cron(expr)?;
+    let tz = parse_timezone(timezone)?;
+    Ok(schedule
+        .after(&Utc::now().with_timezone(&tz))
+        .take(count)
+        .map(|t| t.with_timezone(&Utc))
+        .collect())
+}
+
+// 校验计划参数，返回首次触发时间（数据库 UTC 格式）
+pub fn validate_ranking_schedule(input: &db::RankingScheduleInput) -> Result<Option<String>, String> {
+    if input.name.trim().is_empty() {
+        return Err("计划名称不能为空".to_string());
+    }
+    if let Some(priority) = input.priority.as_deref() {
+        if !["high", "medium", "low"].contains(&priority) {
+            return Err(format!("无效的优先级: {}", priority));
+        }
+    }
+    let next = next_cron_run(&input.cron_expr, &input.timezone, Utc::now())?;
+    if next.is_none() {
+        return Err("该 cron 表达式没有未来的触发时间".to_string());
+    }
+    Ok(next.map(format_db_utc))
+}
+
+// 计划到期判断结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum CronDue {
+    NotDue,
+    Run { missed: usize },   // 执行一次；missed 为合并掉的错过次数
+    Skip { missed: usize },  // 错过的计划不补跑（catch_up 关闭）
+}
+
+// 判断计划是否到期：last_run 之后到 now 为止的所有触发合并为一次
+pub fn cron_due(expr: &str, timezone: &str, last_run: DateTime<Utc>, now: DateTime<Utc>, catch_up: bool) -> Result<CronDue, String> {
+    let schedule = parse_cron(expr)?;
+    let tz = parse_timezone(timezone)?;
+
+    let mut count = 0usize;
+    let mut latest: Option<DateTime<Utc>> = None;
+    for fire in schedule.after(&last_run.with_timezone(&tz)).take(10_000) {
+        let fire = fire.with_timezone(&Utc);
+        if fire > now {
+            break;
+        }
+        count += 1;
+        latest = Some(fire);
+    }
+
+    match latest {
+        None => Ok(CronDue::NotDue),
+        Some(latest) if now - latest <= Duration::minutes(CRON_GRACE_MINUTES) => {
+            Ok(CronDue::Run { missed: count - 1 })
+        }
+        Some(_) if catch_up => Ok(CronDue::Run { missed: count - 1 }),
+        Some(_) => Ok(CronDue::Skip { missed: count }),
+    }
+}
+
+// 数据库时间（UTC，datetime('now') 格式）转换为 DateTime<Utc>
+fn parse_db_utc(value: &str) -> Option<DateTime<Utc>> {
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|t| t.and_utc())
+}
+
+fn format_db_utc(time: DateTime<Utc>) -> String {
+    time.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// 监控项是否由启用的 Cron 计划负责
+fn is_covered_by_schedule(item: &db::KeywordMonitoring, schedules: &[db::RankingSchedule]) -> bool {
+    schedules.iter().any(|s| {
+        s.is_enabled
+            && s.product_id.is_none_or(|pid| pid == item.product_id)
+            && s.priority.as_deref().is_none_or(|p| p == item.priority)
+    })
+}
+
+// 计划到期时执行一次检测，并记录执行情况和下次触发时间
+async fn run_schedule_if_due(
+    app: &tauri::AppHandle,
+    schedule: &db::RankingSchedule,
+    now: DateTime<Utc>,
+    settings: &SchedulerSettings,
+) {
+    let last_run = schedule
+        .last_run_at
+        .as_deref()
+        .or(Some(schedule.created_at.as_str()))
+        .and_then(parse_db_utc)
+        .unwrap_or(now);
+    let next = next_cron_run(&schedule.cron_expr, &schedule.timezone, now)
+        .ok()
+        .flatten()
+        .map(format_db_utc);
+    let run_at = format_db_utc(now);
+
+    match cron_due(&schedule.cron_expr, &schedule.timezone, last_run, now, schedule.catch_up) {
+        Ok(CronDue::NotDue) => {}
+        Ok(CronDue::Skip { missed }) => {
+            let message = format!("错过 {} 次，未补跑", missed);
+            println!("[Scheduler] 计划 \"{}\" {}", schedule.name, message);
+            let _ = db::mark_ranking_schedule_run(schedule.id, &run_at, "skipped", Some(&message), next.as_deref());
+        }
+        Ok(CronDue::Run { missed }) => {
+            // 先记录执行时间，避免检测耗时较长时重复触发
+            let _ = db::mark_ranking_schedule_run(schedule.id, &run_at, "running", None, next.as_deref());
+
+            let items = db::get_monitoring_for_schedule(
+                schedule.product_id,
+                schedule.priority.as_deref(),
+                CRON_SKIP_RECENT_MINUTES,
+            )
+            .unwrap_or_default();
+            println!(
+                "[Scheduler] 执行计划 \"{}\"，共 {} 个关键词{}",
+                schedule.name,
+                items.len(),
+                if missed > 0 { format!("（补跑，合并错过的 {} 次）", missed) } else { String::new() }
+            );
+
+            let (success, failed) = if items.is_empty() {
+                (0, 0)
+            } else {
+                run_ranking_checks(app, items, "cron", settings, None).await
+            };
+            let mut message = format!("成功 {}，失败 {}", success, failed);
+            if missed > 0 {
+                message.push_str(&format!("；补跑错过的 {} 次", missed));
+            }
+            let _ = db::mark_ranking_schedule_run(schedule.id, &run_at, "completed", Some(&message), next.as_deref());
+        }
+        Err(e) => {
+            println!("[Scheduler] 计划 \"{}\" 配置错误: {}", schedule.name, e);
+            let _ = db::mark_ranking_schedule_run(schedule.id, &run_at, "failed", Some(&e), None);
+        }
+    }
+}
+
+// 单页检测的预估耗时（秒，单个浏览器标签页）
+const ESTIMATED_SECONDS_PER_PAGE: f64 = 6.0;
+
+// 按检测窗口剩余时间安排本轮检测：items 已按优先级排序，预计超时的从低优先级开始跳过
+// concurrency 为并发标签页总数；返回 (本轮检测, 跳过)
+pub fn plan_within_window(
+    items: Vec<db::KeywordMonitoring>,
+    settings: &SchedulerSettings,
+    now: DateTime<Utc>,
+    concurrency: i64,
+) -> (Vec<db::KeywordMonitoring>, Vec<db::KeywordMonitoring>) {
+    let concurrency = concurrency.max(1) as f64;
+
+    // 先按优先级稳定排序，同优先级保持原有顺序（最久未检测的在前）
+    let mut items = items;
+    items.sort_by_key(|m| priority_rank(&m.priority));
+
+    let mut elapsed = 0.0;
+    let mut kept = Vec::new();
+    let mut skipped = Vec::new();
+    for item in items {
+        let pages = item.max_pages.unwrap_or(settings.max_pages as i64).max(1) as f64;
+        let cost = pages * ESTIMATED_SECONDS_PER_PAGE / concurrency;
+        let fits = match window_remaining(&item.country, settings, now) {
+            Some(remaining) => elapsed + cost <= remaining.num_seconds() as f64,
+            None => true,  // 不受窗口限制（如手动触发）
+        };
+        if fits {
+            elapsed += cost;
+            kept.push(item);
+        } else {
+            skipped.push(item);
+        }
+    }
+    (kept, skipped)
+}
+
+fn priority_rank(priority: &str) -> u8 {
+    match priority {
+        "high" => 1,
+        "medium" => 2,
+        _ => 3,
+    }
+}
+
+
+// 批量检测过程中累计的结果（在爬虫回调线程中更新）
+#[derive(Default)]
+struct CheckTally {
+    success: i64,
+    failed: i64,
+    changes: Vec<RankChange>,
+}
+
+// 推送给前端的事件（事件名, 内容）；检测流程不直接依赖 AppHandle，测试时收集事件
+type EventSink = Arc<dyn Fn(&str, serde_json::Value) + Send + Sync>;
+
+// 批量检测一组监控项并写入结果，返回 (成功数, 失败数)
+// 检测完成后发送本次触发的告警，对比上次排名，按通知设置汇总为一条通知，并发送 ranking-changes 事件
+async fn run_ranking_checks(
+    app: &tauri::AppHandle,
+    items: Vec<db::KeywordMonitoring>,
+    trigger_type: &str,
+    settings: &SchedulerSettings,
+    skipped_note: Option<String>,
+) -> (i64, i64) {
+    let events: EventSink = {
+        let app = app.clone();
+        Arc::new(move |event: &str, payload: serde_json::Value| {
+            let _ = app.emit(event, payload);
+        })
+    };
+    let (task_id, tally) = check_and_save(items, trigger_type, settings, skipped_note, events).await;
+    let CheckTally { success: success_count, failed: failed_count, changes } = tally;
+
+    // 发送本次检测触发的告警规则
+    crate::alerts::dispatch_pending_alerts(app).await;
+
+    // 排名变化：一次检测只发一条通知（桌面 + 开启了汇总的外部渠道）
+    if !changes.is_empty() {
+        if let Err(e) = notification::send_rank_change_digest(app, &changes, success_count as usize) {
+            println!("[Scheduler] 发送排名变化通知失败: {}", e);
+        }
+        crate::channels::broadcast_rank_digest(&changes, success_count as usize).await;
+    }
+    let _ = app.emit("ranking-changes", RankChangeDigest {
+        task_id,
+        trigger_type: trigger_type.to_string(),
+        checked: success_count,
+        failed: failed_count,
+        changes,
+    });
+
+    (success_count, failed_count)
+}
+
+// 检测并保存一组监控项，返回 (任务记录 ID, 检测结果汇总)
+// 检测页数：关键词单独设置的优先，否则使用全局设置；不同页数分批执行
+// 每条结果到达时立即写入并推送 ranking-check-result 事件；运行期间可通过运行句柄暂停或取消
+async fn check_and_save(
+    items: Vec<db::KeywordMonitoring>,
+    trigger_type: &str,
+    settings: &SchedulerSettings,
+    skipped_note: Option<String>,
+    events: EventSink,
+) -> (Option<i64>, CheckTally) {
+    let total = items.len() as i64;
+    let default_pages = settings.max_pages.max(1) as i64;
+
+    // 创建任务记录
+    let task_id = db::create_task_log(trigger_type, total).ok();
+    println!("[Scheduler] 开始定时检测，共 {} 个关键词，任务ID: {:?}", total, task_id);
+    if let (Some(tid), Some(note)) = (task_id, skipped_note.as_deref()) {
+        let _ = db::set_task_log_message(tid, note);
+    }
+
+    // 按检测页数分组（保持优先级顺序）: pages -> [(monitoring_id, keyword, asin, country, location)]
+    let mut groups: Vec<(i64, Vec<crawler::RankingItem>)> = Vec::new();
+    for m in &items {
+        let pages = m.max_pages.unwrap_or(default_pages).max(1);
+        let entry = (m.id, m.keyword.clone(), m.asin.clone(), m.country.clone(), m.location.clone());
+        match groups.iter_mut().find(|(p, _)| *p == pages) {
+            Some((_, list)) => list.push(entry),
+            None => groups.push((pages, vec![entry])),
+        }
+    }
+
+    // 获取爬虫设置
+    let crawler_settings = crate::load_crawler_settings();
+
+    let control = crawler::RunControl::register(trigger_type, total);
+    control.set_task_log(task_id);
+    events("ranking-check-start", serde_json::json!({
+        "run_id": control.id,
+        "source": trigger_type,
+        "total": total
+    }));
+
+    // 使用批量模式检测（并发模式，同一站点复用浏览器）
+    let items_by_id: Arc<HashMap<i64, db::KeywordMonitoring>> = Arc::new(items.into_iter().map(|m| (m.id, m)).collect());
+    let tally = Arc::new(parking_lot::Mutex::new(CheckTally::default()));
+    let mut done = 0i64;
+    for (pages, keywords) in groups {
+        // 暂停时在批次之间等待；取消后不再开始新的批次
+        if !control.wait_while_paused().await {
+            break;
+        }
+        let group_size = keywords.len() as i64;
+        let offset = done;
+        let (events, items_by_id, tally, settings, run_id) =
+            (events.clone(), items_by_id.clone(), tally.clone(), settings.clone(), control.id);
+        crawler::check_rankings_batch(
+            keywords,
+            pages,
+            crawler_settings.clone(),
+            control.clone(),
+            move |completed, _total, monitoring_id, result| {
+                let mut tally = tally.lock();
+                if result.error.is_none() {
+                    // 写入前先取上次排名用于对比
+                    if let Some(item) = items_by_id.get(&monitoring_id) {
+                        let previous = db::get_previous_ranking(monitoring_id).unwrap_or(None);
+                        let current = RankPosition::new(result.organic_rank, result.organic_page, result.organic_position);
+                        if let Some(change) = detect_rank_change(item, previous, current, &settings) {
+                            tally.changes.push(change);
+                        }
+                    }
+                    tally.success += 1;
+                } else {
+                    tally.failed += 1;
+                }
+
+                // 与手动检测相同的保存流程：排名、竞品快照、失败记录和重试队列、任务明细
+                let kind = crate::save_ranking_result(monitoring_id, result, settings.auto_pause_after_failures as i64, task_id);
+                if let Some(kind) = kind {
+                    println!("[Scheduler] 检测失败 (id={}, {}): {:?}", monitoring_id, kind.as_str(), result.error.as_ref().or(result.warning.as_ref()));
+                }
+
+                if let Some(tid) = task_id {
+                    let _ = db::update_task_progress(tid, tally.success, tally.failed);
+                }
+                events("ranking-check-result", serde_json::json!({
+                    "run_id": run_id,
+                    "monitoring_id": monitoring_id,
+                    "current": offset + completed,
+                    "total": total,
+                    "result": result
+                }));
+            },
+        ).await;
+        done += group_size;
+    }
+    control.unregister();
+
+    let tally = std::mem::take(&mut *tally.lock());
+    let (success_count, failed_count) = (tally.success, tally.failed);
+    let cancelled = control.is_cancelled();
+    if cancelled {
+        let remaining = total - success_count - failed_count;
+        println!("[Scheduler] 检测已取消，{} 个关键词未检测", remaining);
+        if let Some(tid) = task_id {
+            let note = match skipped_note.as_deref() {
+                Some(note) => format!("{}；已取消，{} 个关键词未检测", note, remaining),
+                None => format!("已取消，{} 个关键词未检测", remaining),
+            };
+            let _ = db::set_task_log_message(tid, &note);
+        }
+    }
+    events("ranking-check-complete", serde_json::json!({
+        "run_id": control.id,
+        "total": success_count + failed_count,
+        "success": success_count,
+        "failed": failed_count,
+        "cancelled": cancelled,
+        "skipped": total - success_count - failed_count
+    }));
+
+    // 完成任务记录
+    if let Some(tid) = task_id {
+        let _ = db::complete_task_log(tid, success_count, failed_count);
+        println!("[Scheduler] 定时检测完成，成功: {}, 失败: {}", success_count, failed_count);
+    }
+
+    (task_id, tally)
+}
+
 // 调度器
 pub struct Scheduler {
     running: Arc<AtomicBool>,
@@ -173,7 +794,7 @@ impl Scheduler {
         self.running.load(Ordering::SeqCst)
     }
 
-    pub async fn start(&self) {
+    pub async fn start(&self, app_handle: tauri::AppHandle) {
         if self.running.load(Ordering::SeqCst) {
             return;
         }
@@ -185,9 +806,44 @@ impl Scheduler {
         let last_check = self.last_check.clone();
 
         tokio::spawn(async move {
+            let mut last_maintenance: Option<chrono::NaiveDate> = None;
             while running.load(Ordering::SeqCst) {
                 let current_settings = settings.lock().await.clone();
 
+                // 每天一次排名历史归档和爬取页面归档清理（不受自动检测开关影响）
+                let today = chrono::Local::now().date_naive();
+                if last_maintenance != Some(today) {
+                    last_maintenance = Some(today);
+                    match db::run_history_maintenance(
+                        current_settings.history_raw_days as i64,
+                        current_settings.history_daily_days as i64,
+                        current_settings.check_log_days as i64,
+                        false,
+                    ) {
+                        // 有数据被汇总时才 VACUUM
+                        Ok(report) if report.raw_rows_compacted + report.daily_rows_compacted > 0 => {
+                            println!(
+                                "[Scheduler] 排名历史归档：原始 {} 条、日汇总 {} 条",
+                                report.raw_rows_compacted, report.daily_rows_compacted
+                            );
+                            if let Err(e) = db::vacuum_database() {
+                                eprintln!("[Scheduler] VACUUM 失败: {}", e);
+                            }
+                        }
+                        Ok(_) => {}
+                        Err(e) => eprintln!("[Scheduler] 排名历史归档失败: {}", e),
+                    }
+
+                    // 清理超过保留天数的爬取页面归档
+                    let removed = crate::crawl_archive::cleanup();
+                    if removed > 0 {
+                        println!("[Scheduler] 清理爬取页面归档 {} 天", removed);
+                    }
+                }
+
+                // 代理池健康检查（含自动停用的代理，恢复后自动启用）
+                crate::proxy_pool::run_due_health_checks().await;
+
                 if current_settings.enabled {
                     // 检查是否需要执行检测（每小时最多一次）
                     let should_check = {
@@ -201,100 +857,61 @@ impl Scheduler {
                         }
                     };
 
+                    // Cron 计划：到期（含休眠期间错过）的计划依次执行
+                    let schedules: Vec<db::RankingSchedule> = db::get_ranking_schedules()
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|s| s.is_enabled)
+                        .collect();
+                    for schedule in &schedules {
+                        run_schedule_if_due(&app_handle, schedule, Utc::now(), &current_settings).await;
+                    }
+
                     if should_check {
-                        // 收集所有需要检测的关键词
-                        let mut all_pending = Vec::new();
-
-                        if let Ok(products) = db::get_products() {
-                            for product in products {
-                                let country = product.country.as_deref().unwrap_or("US");
-                                if is_in_check_window(country, &current_settings) {
-                                    if let Ok(pending) = db::get_pending_monitoring_checks(product.id, 4) {
-                                        all_pending.extend(pending);
-                                    }
-                                }
-                            }
-                        }
+                        // 收集到期的关键词：按优先级使用不同检测间隔，已被 Cron 计划覆盖的由计划负责
+                        // 按监控项所在站点的当地时间判断检测窗口
+                        let all_pending: Vec<db::KeywordMonitoring> = db::get_due_monitoring_checks(
+                            current_settings.high_priority_hours as i64,
+                            current_settings.medium_priority_hours as i64,
+                            current_settings.low_priority_hours as i64,
+                        )
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter(|m| {
+                            !is_covered_by_schedule(m, &schedules)
+                                && is_in_check_window(&m.country, &current_settings)
+                        })
+                        .collect();
 
                         if !all_pending.is_empty() {
-                            let total = all_pending.len() as i64;
-
-                            // 创建任务记录
-                            let task_id = db::create_task_log("auto", total).ok();
-                            println!("[Scheduler] 开始定时检测，共 {} 个关键词，任务ID: {:?}", total, task_id);
-
-                            // 准备批量检测数据: (monitoring_id, keyword, asin, country)
-                            let keywords: Vec<(i64, String, String, String)> = all_pending
-                                .iter()
-                                .map(|m| (m.id, m.keyword.clone(), m.asin.clone(), m.country.clone()))
-                                .collect();
-
-                            // 获取爬虫设置
-                            let max_browsers = db::get_setting("max_browsers")
-                                .ok()
-                                .flatten()
-                                .and_then(|s| s.parse::<i64>().ok())
-                                .unwrap_or(3);  // 默认3个并发浏览器
-
-                            let tabs_per_browser = db::get_setting("tabs_per_browser")
-                                .ok()
-                                .flatten()
-                                .and_then(|s| s.parse::<i64>().ok())
-                                .unwrap_or(1);  // 默认1个标签页
-
-                            let proxy_list = db::get_setting("proxy_list")
-                                .ok()
-                                .flatten()
-                                .filter(|s| !s.trim().is_empty())
-                                .map(|s| s.lines().filter(|l| !l.trim().is_empty()).collect::<Vec<_>>().join(","));
-
-                            // 使用批量模式检测（并发模式，同一站点复用浏览器）
-                            let task_id_clone = task_id;
-                            let results = crawler::check_rankings_batch(
-                                keywords,
-                                5, // max_pages
-                                max_browsers,
-                                tabs_per_browser,
-                                proxy_list,
-                                move |completed, _total, msg| {
-                                    println!("[Scheduler] {}", msg);
-                                    // 更新任务进度（这里无法准确统计成功/失败，在结果处理时再统计）
-                                    if let Some(tid) = task_id_clone {
-                                        let _ = db::update_task_progress(tid, completed, 0);
-                                    }
-                                },
-                            ).await;
-
-                            // 处理结果
-                            let mut success_count = 0i64;
-                            let mut failed_count = 0i64;
-
-                            for (monitoring_id, result) in results {
-                                if result.error.is_none() {
-                                    // 更新数据库
-                                    let product_info = result.product_info.as_ref();
-                                    let _ = db::update_ranking_result(
-                                        monitoring_id,
-                                        result.organic_rank,
-                                        result.organic_page,
-                                        result.sponsored_rank,
-                                        result.sponsored_page,
-                                        product_info.and_then(|p| p.image_url.clone()),
-                                        product_info.and_then(|p| p.price.clone()),
-                                        product_info.and_then(|p| p.reviews_count),
-                                        product_info.and_then(|p| p.rating),
-                                    );
-                                    success_count += 1;
-                                } else {
-                                    println!("[Scheduler] 检测失败 (id={}): {:?}", monitoring_id, result.error);
-                                    failed_count += 1;
-                                }
-                            }
-
-                            // 完成任务记录
-                            if let Some(tid) = task_id {
-                                let _ = db::complete_task_log(tid, success_count, failed_count);
-                                println!("[Scheduler] 定时检测完成，成功: {}, 失败: {}", success_count, failed_count);
+                            // 预计在窗口结束前完不成时，从低优先级开始跳过（下个窗口再检测）
+                            let (planned, skipped) = plan_within_window(
+                                all_pending,
+                                &current_settings,
+                                Utc::now(),
+                                crate::load_crawler_settings().concurrency(),
+                            );
+                            let note = if skipped.is_empty() {
+                                None
+                            } else {
+                                let low = skipped.iter().filter(|m| m.priority == "low").count();
+                                let note = format!(
+                                    "检测窗口时间不足，跳过 {} 个关键词（其中低优先级 {} 个）",
+                                    skipped.len(),
+                                    low
+                                );
+                                println!("[Scheduler] {}", note);
+                                Some(note)
+                            };
+                            if !planned.is_empty() {
+                                run_ranking_checks(
+                                    &app_handle,
+                                    planned,
+                                    "auto",
+                                    &current_settings,
+                                    note,
+                                )
+                                .await;
                             }
                         }
 
@@ -302,6 +919,13 @@ impl Scheduler {
                         let mut last = last_check.lock().await;
                         *last = Some(Utc::now());
                     }
+
+                    // 重试队列：到退避时间的失败检测重新检测（不受检测窗口限制）
+                    let retries = db::get_due_retries(crate::check_failures::RETRY_BATCH_LIMIT).unwrap_or_default();
+                    if !retries.is_empty() {
+                        println!("[Scheduler] 重试 {} 个失败的检测", retries.len());
+                        run_ranking_checks(&app_handle, retries, "retry", &current_settings, None).await;
+                    }
                 }
 
                 // 每分钟检查一次
@@ -318,11 +942,50 @@ impl Scheduler {
         let settings = self.settings.lock().await;
         let last = self.last_check.lock().await;
 
+        let now = Utc::now();
+        // 两次检测至少间隔 1 小时
+        let earliest = match *last {
+            Some(last_time) if last_time + Duration::hours(1) > now => last_time + Duration::hours(1),
+            _ => now,
+        };
+
+        let countries = db::get_active_monitoring_countries().unwrap_or_default();
+        let marketplaces: Vec<MarketplaceCheckStatus> = countries
+            .iter()
+            .map(|country| {
+                let (_, tz) = marketplace_window(country, &settings);
+                MarketplaceCheckStatus {
+                    country: country.clone(),
+                    timezone: tz.name().to_string(),
+                    local_time: now.with_timezone(&tz).format("%H:%M").to_string(),
+                    in_window: is_in_check_window_at(country, &settings, now),
+                    next_check_time: next_check_time(country, &settings, earliest).map(|t| t.to_rfc3339()),
+                }
+            })
+            .collect();
+
+        // 检测窗口与 Cron 计划中最早的一次
+        let next = if settings.enabled {
+            let schedules = db::get_ranking_schedules().unwrap_or_default();
+            let cron_next = schedules
+                .iter()
+                .filter(|s| s.is_enabled)
+                .filter_map(|s| s.next_run_at.as_deref().and_then(parse_db_utc));
+            countries
+                .iter()
+                .filter_map(|country| next_check_time(country, &settings, earliest))
+                .chain(cron_next)
+                .min()
+        } else {
+            None
+        };
+
         SchedulerStatus {
             is_running: self.is_running() && settings.enabled,
             last_check_time: last.map(|t| t.to_rfc3339()),
-            next_check_time: None, // TODO: 计算下次检测时间
+            next_check_time: next.map(|t| t.to_rfc3339()),
             current_task: None,
+            marketplaces,
         }
     }
 }
@@ -550,3 +1213,218 @@ impl MarketResearchScheduler {
 }
 
 pub static MARKET_RESEARCH_SCHEDULER: Lazy<MarketResearchScheduler> = Lazy::new(|| MarketResearchScheduler::new());
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_window_local_time_and_dst() {
+        let settings = SchedulerSettings::default();  // 8-10, 18-21
+
+        // 美东 2026-03-08 进入夏令时：前一天 8:00 EST = 13:00 UTC，当天之后 8:00 EDT = 12:00 UTC
+        let before = Utc.with_ymd_and_hms(2026, 3, 7, 13, 30, 0).unwrap();
+        assert!(is_in_check_window_at("US", &settings, before));
+        let after = Utc.with_ymd_and_hms(2026, 3, 9, 12, 30, 0).unwrap();
+        assert!(is_in_check_window_at("US", &settings, after));
+        assert!(!is_in_check_window_at("US", &settings, Utc.with_ymd_and_hms(2026, 3, 7, 12, 30, 0).unwrap()));
+
+        // 德国 3 月 29 日进入夏令时：下次早间窗口 8:00 CEST = 06:00 UTC
+        let night = Utc.with_ymd_and_hms(2026, 3, 28, 22, 0, 0).unwrap();
+        assert_eq!(
+            next_check_time("DE", &settings, night),
+            Some(Utc.with_ymd_and_hms(2026, 3, 29, 6, 0, 0).unwrap())
+        );
+
+        // 站点自定义窗口：英国 2:00-3:00 当地时间
+        let mut custom = settings.clone();
+        custom.marketplace_windows.insert(
+            "UK".to_string(),
+            MarketplaceWindow { timezone: None, morning_start: 2, morning_end: 3, evening_start: 0, evening_end: 0 },
+        );
+        let summer = Utc.with_ymd_and_hms(2026, 7, 1, 1, 15, 0).unwrap();  // 02:15 BST
+        assert!(is_in_check_window_at("UK", &custom, summer));
+        assert_eq!(next_check_time("UK", &custom, summer), Some(summer));
+    }
+
+    #[test]
+    fn test_cron_due_and_catch_up() {
+        let tz = "Asia/Shanghai";
+        assert!(parse_cron("0 */2 * * *").is_ok());
+        assert!(parse_cron("not a cron").is_err());
+
+        // 每 2 小时：北京时间 10:00 = 02:00 UTC
+        let last = Utc.with_ymd_and_hms(2026, 5, 1, 1, 0, 0).unwrap();
+        assert_eq!(
+            next_cron_run("0 */2 * * *", tz, last).unwrap(),
+            Some(Utc.with_ymd_and_hms(2026, 5, 1, 2, 0, 0).unwrap())
+        );
+
+        // 未到时间
+        let now = Utc.with_ymd_and_hms(2026, 5, 1, 1, 30, 0).unwrap();
+        assert_eq!(cron_due("0 */2 * * *", tz, last, now, true).unwrap(), CronDue::NotDue);
+
+        // 按时触发（宽限期内）
+        let now = Utc.with_ymd_and_hms(2026, 5, 1, 2, 3, 0).unwrap();
+        assert_eq!(cron_due("0 */2 * * *", tz, last, now, true).unwrap(), CronDue::Run { missed: 0 });
+
+        // 休眠 7 小时后唤醒：错过 02:00 / 04:00 / 06:00 三次，合并为一次补跑
+        let now = Utc.with_ymd_and_hms(2026, 5, 1, 8, 0, 0).unwrap() - Duration::minutes(30);
+        assert_eq!(cron_due("0 */2 * * *", tz, last, now, true).unwrap(), CronDue::Run { missed: 2 });
+        assert_eq!(cron_due("0 */2 * * *", tz, last, now, false).unwrap(), CronDue::Skip { missed: 3 });
+    }
+
+    fn monitoring(id: i64, priority: &str, max_pages: Option<i64>) -> db::KeywordMonitoring {
+        db::KeywordMonitoring {
+            id,
+            product_id: 1,
+            keyword: format!("kw{}", id),
+            asin: "B000TEST01".to_string(),
+            country: "US".to_string(),
+            priority: priority.to_string(),
+            is_active: true,
+            latest_organic_rank: None,
+            latest_organic_page: None,
+            latest_sponsored_rank: None,
+            latest_sponsored_page: None,
+            image_url: None,
+            price: None,
+            reviews_count: None,
+            rating: None,
+            last_checked: None,
+            created_at: String::new(),
+            tags: None,
+            max_pages,
+            location: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_within_window_skips_low_priority_first() {
+        let settings = SchedulerSettings::default();  // 默认 5 页，每项预估 30 秒（单标签页）
+
+        // 美东夏令时 9:58，早间窗口剩余 2 分钟
+        let now = Utc.with_ymd_and_hms(2026, 7, 1, 13, 58, 0).unwrap();
+        assert_eq!(window_remaining("US", &settings, now), Some(Duration::minutes(2)));
+
+        let items = vec![
+            monitoring(1, "low", Some(10)),
+            monitoring(2, "high", None),
+            monitoring(3, "medium", Some(1)),
+            monitoring(4, "high", None),
+            monitoring(5, "low", Some(1)),
+        ];
+        let (planned, skipped) = plan_within_window(items, &settings, now, 1);
+        let planned: Vec<i64> = planned.iter().map(|m| m.id).collect();
+        let skipped: Vec<i64> = skipped.iter().map(|m| m.id).collect();
+        // 30 + 30 + 6 = 66 秒后，低优先级 #1 (10 页 60 秒) 超出 → 跳过；#5 (6 秒) 仍可完成
+        assert_eq!(planned, vec![2, 4, 3, 5]);
+        assert_eq!(skipped, vec![1]);
+
+        // 窗口外（手动触发）不受限制
+        let outside = Utc.with_ymd_and_hms(2026, 7, 1, 16, 0, 0).unwrap();
+        assert_eq!(window_remaining("US", &settings, outside), None);
+    }
+
+    #[test]
+    fn test_detect_rank_change_uses_settings() {
+        let settings = SchedulerSettings::default();  // 阈值 10，进出 Top10 通知
+        let mut item = monitoring(1, "high", None);
+
+        let page1 = |rank: i64| RankPosition::new(Some(rank), Some(1), None);
+        let later = |page: i64, rank: i64, position: Option<i64>| RankPosition::new(Some(rank), Some(page), position);
+
+        // 首次检测不产生变化
+        assert!(detect_rank_change(&item, None, page1(8), &settings).is_none());
+
+        item.last_checked = Some("2026-05-01 08:00:00".to_string());
+        let entered = detect_rank_change(&item, page1(15), page1(8), &settings).unwrap();
+        assert_eq!(entered.change_type, RankChangeType::EnteredTop10);
+        assert_eq!(entered.change, 7);
+
+        // 低于阈值的普通波动不通知
+        assert!(detect_rank_change(&item, page1(30), page1(25), &settings).is_none());
+        assert_eq!(
+            detect_rank_change(&item, page1(30), page1(45), &settings).unwrap().change_type,
+            RankChangeType::Declined
+        );
+
+        // 第 1 页第 8 名 → 第 2 页第 3 名：按绝对位置 8 → 45 计算
+        let exited = detect_rank_change(&item, page1(8), later(2, 3, Some(45)), &settings).unwrap();
+        assert_eq!((exited.change_type, exited.old_rank, exited.new_rank), (RankChangeType::ExitedTop10, Some(8), Some(45)));
+        // 第 3 页第 2 名 → 第 2 页第 40 名：页内排名变大，实际上升
+        let improved = detect_rank_change(&item, later(3, 2, Some(90)), later(2, 40, Some(78)), &settings).unwrap();
+        assert_eq!((improved.change_type, improved.change), (RankChangeType::Improved, 12));
+        // 旧记录没有绝对位置时不比较
+        assert!(detect_rank_change(&item, later(3, 2, None), page1(30), &settings).is_none());
+
+        let mut quiet = settings.clone();
+        quiet.notify_on_lost_rank = false;
+        assert!(detect_rank_change(&item, page1(30), None, &quiet).is_none());
+    }
+
+    #[test]
+    fn test_check_and_save_with_fixtures() {
+        use crate::crawler_backend::{override_for_test, FixtureBackend};
+
+        let dir = db::init_test_db("scheduler");
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let _guard = override_for_test(Arc::new(FixtureBackend::new(root)));
+
+        let product_id = {
+            let conn = db::get_db().lock();
+            conn.execute("INSERT INTO products (name, country) VALUES ('desk lamp', 'US')", []).unwrap();
+            conn.last_insert_rowid()
+        };
+        let add = |keyword: &str, asin: &str| {
+            db::add_keyword_monitoring(product_id, keyword.to_string(), asin.to_string(), "US".to_string(), None, None)
+                .unwrap()
+        };
+        let (target, page2, missing) = (add("desk lamp", "B0TARGET01"), add("desk lamp", "B0PAGE2001"), add("unknown keyword", "B0TARGET01"));
+        // 上次检测在第 40 位，本次进入前 10
+        db::update_ranking_result(target, Some("10001"), Some(40), Some(1), Some(40), None, None, None, None, None, None).unwrap();
+        let items: Vec<db::KeywordMonitoring> = [target, page2, missing]
+            .into_iter()
+            .map(|id| db::get_keyword_monitoring_by_id(id).unwrap().unwrap())
+            .collect();
+
+        let events: Arc<parking_lot::Mutex<Vec<(String, serde_json::Value)>>> = Arc::default();
+        let sink: EventSink = {
+            let events = events.clone();
+            Arc::new(move |event: &str, payload: serde_json::Value| events.lock().push((event.to_string(), payload)))
+        };
+        let settings = SchedulerSettings { max_pages: 3, ..SchedulerSettings::default() };
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let (task_id, tally) = runtime.block_on(check_and_save(items, "scheduled", &settings, None, sink));
+
+        assert_eq!((tally.success, tally.failed), (2, 1));
+        assert_eq!(tally.changes.len(), 1);
+        assert_eq!((tally.changes[0].monitoring_id, tally.changes[0].old_rank, tally.changes[0].new_rank), (target, Some(40), Some(3)));
+
+        // 每条结果都推送了进度，最后一条到达总数
+        let events = events.lock();
+        let progress: Vec<i64> = events
+            .iter()
+            .filter(|(event, _)| event == "ranking-check-result")
+            .map(|(_, payload)| payload["current"].as_i64().unwrap())
+            .collect();
+        assert_eq!(progress, vec![1, 2, 3]);
+        let (_, complete) = events.iter().find(|(event, _)| event == "ranking-check-complete").unwrap();
+        assert_eq!((complete["success"].as_i64(), complete["skipped"].as_i64()), (Some(2), Some(0)));
+
+        // 与手动检测相同的保存结果：排名、竞品快照、失败记录、任务明细
+        let conn = db::get_db().lock();
+        let count = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        let page2_rank: (Option<i64>, Option<i64>) = conn
+            .query_row("SELECT latest_organic_rank, latest_organic_page FROM keyword_monitoring WHERE id = ?1", [page2], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(page2_rank, (Some(2), Some(2)));
+        assert_eq!(count("SELECT COUNT(*) FROM ranking_snapshots WHERE keyword = 'desk lamp' AND country = 'US'"), 1);
+        assert_eq!(count(&format!("SELECT COUNT(*) FROM ranking_check_failures WHERE monitoring_id = {}", missing)), 1);
+        assert_eq!(count(&format!("SELECT COUNT(*) FROM scheduler_task_items WHERE task_id = {}", task_id.unwrap())), 3);
+        drop(conn);
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,532 @@
+// Amazon 搜索结果页解析与排名计算（原生 HTTP 检测引擎使用）
+// 规则与 amazon_crawler_playwright.py 保持一致：横幅广告、顶部广告区、搜索结果中的广告和视频广告依次计入广告位，
+// 自然位与广告位分开计数，排名为页内位置
+
+use std::collections::{HashMap, HashSet};
+
+use kuchikiki::traits::TendrilSink;
+use kuchikiki::NodeRef;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::crawler::ProductInfo;
+
+// 横幅广告（Sponsored Brands）容器选择器，按顺序取第一个存在的
+const BANNER_SELECTORS: &[&str] = &[
+    "[class*=\"sbx-desktop\"]",
+    "[class*=\"_bXVsd_container\"]",
+    "[class*=\"sb-desktop\"]",
+    "[data-component-type=\"sbx\"]",
+    "[data-component-type=\"sp-sponsored-brands\"]",
+    "[class*=\"AdHolder\"]",
+    "[class*=\"s-top-slot\"] [class*=\"a-carousel\"]",
+    "[data-component-type*=\"brand\"]",
+    ".s-top-slot [class*=\"sponsored\"]",
+    "[class*=\"sponsored-brand\"]",
+    ".puis-carousel",
+    "[class*=\"brands-storefronts\"]",
+    "[cel_widget_id*=\"MAIN-SEARCH_RESULTS-SBX\"]",
+    "[cel_widget_id*=\"sponsoredBrands\"]",
+];
+
+// 视频广告（Sponsored Brands Video）
+const VIDEO_SELECTORS: &[&str] = &[
+    "[class*=\"sbv-video\"]",
+    "[class*=\"video-single-product\"]",
+    "[data-component-type=\"sbv\"]",
+    "[cel_widget_id*=\"VIDEO\"]",
+];
+
+// 顶部广告产品区（横幅下方、搜索结果上方）
+const TOP_AD_SELECTORS: &[&str] = &[
+    "[data-component-type=\"sp-sponsored-products\"]",
+    "[cel_widget_id*=\"MAIN-TOP_BANNER\"]",
+    "[cel_widget_id*=\"TOP_BANNER_SP\"]",
+    ".s-top-slot [data-component-type*=\"sp-\"]",
+    "[data-component-type=\"s-ads-metrics\"]",
+];
+
+const SEARCH_RESULT_SELECTOR: &str = "[data-component-type=\"s-search-result\"]";
+
+static DP_ASIN: Lazy<Regex> = Lazy::new(|| Regex::new(r"/dp/([A-Z0-9]{10})").unwrap());
+static LP_ASINS: Lazy<Regex> = Lazy::new(|| Regex::new(r"lp_asins=([A-Z0-9%,]+)").unwrap());
+static DATA_ASIN: Lazy<Regex> = Lazy::new(|| Regex::new(r#"data-asin="([A-Z0-9]{10})""#).unwrap());
+static ASIN_PARAM: Lazy<Regex> = Lazy::new(|| Regex::new(r"[?&](?:amp;)?asin=([A-Z0-9]{10})").unwrap());
+static SPONSORED_LABEL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)Sponsored|Sponsorisé|Gesponsert|Sponsorizzato|Patrocinado|Anzeige").unwrap());
+static STAR_CLASS: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"a-star-(?:mini-|small-|medium-|large-)?(\d)(?:-(\d))?").unwrap());
+static STAR_TEXT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"([\d,.]+)\s*(?:out of|von|sur|su|de|étoiles|Sternen|5つ星)").unwrap());
+static REVIEWS_LABEL: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)([\d\s,.\u{00a0}\u{202f}]+)\s*(ratings?|reviews?|évaluations?|bewertungen?|recensioni?|valoraciones?|avis|件の評価)").unwrap()
+});
+static REVIEWS_K: Lazy<Regex> = Lazy::new(|| Regex::new(r"([\d\s.,\u{00a0}\u{202f}]+)\s*[Kk]").unwrap());
+static DIGITS: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+").unwrap());
+
+// 搜索结果中的一个商品
+#[derive(Debug, Clone)]
+pub struct SearchItem {
+    pub asin: String,
+    pub sponsored: bool,
+    pub info: ProductInfo,
+}
+
+#[derive(Debug, Clone)]
+pub struct VideoAd {
+    pub asin: String,
+    pub results_before: usize,  // 视频广告之前的搜索结果数（按文档顺序）
+}
+
+// 一页搜索结果
+#[derive(Debug, Clone, Default)]
+pub struct SearchPage {
+    pub banner_asins: Vec<String>,
+    pub top_sponsored_asins: Vec<String>,
+    pub video_ad: Option<VideoAd>,
+    pub results: Vec<SearchItem>,
+    pub delivery_location: Option<String>,  // 页头显示的配送地址
+}
+
+fn dedup(asins: Vec<String>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    asins.into_iter().filter(|a| seen.insert(a.clone())).collect()
+}
+
+fn captures(re: &Regex, html: &str) -> Vec<String> {
+    re.captures_iter(html).map(|c| c[1].to_string()).collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    value.replace("%2C", ",").replace("%2c", ",")
+}
+
+// 从广告容器 HTML 中提取 ASIN：/dp/ 链接 → lp_asins 参数 → data-asin → asin= 参数
+fn extract_ad_asins(html: &str) -> Vec<String> {
+    let mut asins = captures(&DP_ASIN, html);
+    if asins.is_empty() {
+        if let Some(c) = LP_ASINS.captures(html) {
+            asins = percent_decode(&c[1])
+                .split(',')
+                .filter(|a| a.len() == 10 && a.chars().all(|ch| ch.is_ascii_alphanumeric()))
+                .map(str::to_string)
+                .collect();
+        }
+    }
+    if asins.is_empty() {
+        asins = captures(&DATA_ASIN, html);
+    }
+    if asins.is_empty() {
+        asins = captures(&ASIN_PARAM, html);
+    }
+    dedup(asins)
+}
+
+// 第一个命中的选择器的第一个匹配；跳过搜索结果本身及其内部（广告结果也带 AdHolder 等类名）
+fn first_match(document: &NodeRef, selectors: &[&str], results: &[NodeRef]) -> Option<NodeRef> {
+    selectors.iter().find_map(|s| {
+        document
+            .select(s)
+            .ok()?
+            .map(|m| m.as_node().clone())
+            .find(|node| !node.inclusive_ancestors().any(|a| results.contains(&a)))
+    })
+}
+
+fn attr(node: &NodeRef, name: &str) -> Option<String> {
+    node.as_element().and_then(|e| e.attributes.borrow().get(name).map(str::to_string))
+}
+
+fn select_text(node: &NodeRef, selector: &str) -> Option<String> {
+    let text = node.select_first(selector).ok()?.text_contents();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    (!text.is_empty()).then_some(text)
+}
+
+// 页头地址末尾带 &zwnj; 等零宽字符，split_whitespace 去不掉
+fn clean_location(text: &str) -> String {
+    text.chars()
+        .filter(|ch| !matches!(ch, '\u{200b}'..='\u{200d}' | '\u{feff}'))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn select_attr(node: &NodeRef, selector: &str, name: &str) -> Option<String> {
+    node.select_first(selector).ok().and_then(|m| attr(m.as_node(), name))
+}
+
+// 验证码 / Robot Check 页面
+pub fn is_captcha_page(html: &str) -> bool {
+    html.contains("/errors/validateCaptcha")
+        || html.contains("captchacharacters")
+        || html.contains("<title>Robot Check</title>")
+        || html.contains("<title dir=\"ltr\">Robot Check</title>")
+}
+
+fn is_sponsored(node: &NodeRef, html: &str) -> bool {
+    if SPONSORED_LABEL.is_match(html) {
+        return true;
+    }
+    if attr(node, "data-component-type").is_some_and(|t| t.to_lowercase().contains("sp-")) {
+        return true;
+    }
+    let class = attr(node, "class").unwrap_or_default();
+    class.contains("AdHolder") || class.to_lowercase().contains("sponsored")
+}
+
+fn parse_rating(node: &NodeRef) -> Option<f64> {
+    if let Some(class) = select_attr(node, "[class*=\"a-icon-star\"]", "class") {
+        if let Some(c) = STAR_CLASS.captures(&class) {
+            let value = match c.get(2) {
+                Some(decimal) => format!("{}.{}", &c[1], decimal.as_str()),
+                None => c[1].to_string(),
+            };
+            return value.parse().ok();
+        }
+    }
+    // 备用：aria-label 或星级图标的替代文字，如 "4.5 out of 5 stars"、"4,5 von 5 Sternen"
+    let label = select_attr(node, "[aria-label*=\"stars\"], [aria-label*=\"étoiles\"], [aria-label*=\"Sternen\"]", "aria-label")
+        .or_else(|| select_text(node, ".a-icon-alt"))?;
+    STAR_TEXT.captures(&label)?[1].replace(',', ".").parse().ok()
+}
+
+fn parse_reviews_count(node: &NodeRef) -> Option<i64> {
+    let link = node
+        .select_first("a[href*=\"#customerReviews\"], a[href*=\"product-reviews\"]")
+        .ok()?;
+    let link = link.as_node();
+
+    // 优先 aria-label 中的精确数字，如 "3,611 ratings"、"3 611 évaluations"
+    if let Some(label) = attr(link, "aria-label") {
+        if let Some(c) = REVIEWS_LABEL.captures(&label) {
+            let digits: String = c[1].chars().filter(|ch| ch.is_ascii_digit()).collect();
+            if let Ok(count) = digits.parse() {
+                return Some(count);
+            }
+        }
+    }
+
+    // 回退到文本："(3.6K)"、"3,6 K"、"(1,234)"
+    let text = link.text_contents();
+    if let Some(c) = REVIEWS_K.captures(&text) {
+        let number: String = c[1].chars().filter(|ch| !ch.is_whitespace()).collect::<String>().replace(',', ".");
+        if let Ok(value) = number.parse::<f64>() {
+            return Some((value * 1000.0) as i64);
+        }
+    }
+    let cleaned: String = text.chars().filter(|ch| !ch.is_whitespace() && *ch != ',' && *ch != '.').collect();
+    let numbers: Vec<i64> = DIGITS.find_iter(&cleaned).filter_map(|m| m.as_str().parse().ok()).collect();
+    // 过滤掉可能的星级数字（1-5），取最大值
+    numbers.iter().copied().filter(|n| *n > 5).max().or(numbers.last().copied())
+}
+
+fn parse_item(node: &NodeRef, asin: &str) -> ProductInfo {
+    ProductInfo {
+        asin: asin.to_string(),
+        title: select_text(node, "h2"),
+        price: select_text(node, ".a-price .a-offscreen"),
+        rating: parse_rating(node),
+        reviews_count: parse_reviews_count(node),
+        image_url: select_attr(node, "img.s-image", "src"),
+        availability: None,
+    }
+}
+
+// 视频广告之前的搜索结果数（文档顺序）
+fn results_before(document: &NodeRef, target: &NodeRef, results: &[NodeRef]) -> usize {
+    let mut count = 0;
+    for node in document.inclusive_descendants() {
+        if &node == target {
+            break;
+        }
+        if results.contains(&node) {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// 解析一页搜索结果；验证码页面或找不到搜索结果区域时返回错误信息（与 check_failures 的分类关键词一致）
+pub fn parse_search_page(html: &str) -> Result<SearchPage, String> {
+    if is_captcha_page(html) {
+        return Err("检测到验证码页面".to_string());
+    }
+    let document = kuchikiki::parse_html().one(html).document_node;
+
+    let result_nodes: Vec<NodeRef> = document
+        .select(SEARCH_RESULT_SELECTOR)
+        .map(|s| s.map(|m| m.as_node().clone()).collect())
+        .unwrap_or_default();
+    if result_nodes.is_empty() && document.select_first(".s-main-slot, .s-result-list").is_err() {
+        return Err("页面解析失败：未找到搜索结果区域".to_string());
+    }
+
+    let mut page = SearchPage {
+        delivery_location: select_text(&document, "#glow-ingress-line2")
+            .or_else(|| select_text(&document, "#glow-ingress-block"))
+            .map(|text| clean_location(&text))
+            .filter(|text| !text.is_empty()),
+        ..Default::default()
+    };
+
+    if let Some(banner) = first_match(&document, BANNER_SELECTORS, &result_nodes) {
+        page.banner_asins = extract_ad_asins(&banner.to_string());
+    }
+
+    for selector in VIDEO_SELECTORS {
+        let Ok(video) = document.select_first(selector) else { continue };
+        let video = video.as_node();
+        let html = video.to_string();
+        let mut asins = captures(&DP_ASIN, &html);
+        if asins.is_empty() {
+            if let Some(c) = LP_ASINS.captures(&html) {
+                asins = percent_decode(&c[1]).split(',').filter(|a| a.len() == 10).map(str::to_string).collect();
+            }
+        }
+        if let Some(asin) = asins.into_iter().next() {
+            page.video_ad = Some(VideoAd { asin, results_before: results_before(&document, video, &result_nodes) });
+        }
+        break;
+    }
+
+    for selector in TOP_AD_SELECTORS {
+        let Some(container) = first_match(&document, &[selector], &result_nodes) else { continue };
+        let html = container.to_string();
+        let mut asins = captures(&DP_ASIN, &html);
+        if asins.is_empty() {
+            asins = captures(&DATA_ASIN, &html);
+        }
+        if !asins.is_empty() {
+            page.top_sponsored_asins = dedup(asins);
+            break;
+        }
+    }
+
+    for node in &result_nodes {
+        let Some(asin) = attr(node, "data-asin").filter(|a| a.len() == 10) else { continue };
+        let html = node.to_string();
+        page.results.push(SearchItem {
+            sponsored: is_sponsored(node, &html),
+            info: parse_item(node, &asin),
+            asin,
+        });
+    }
+
+    Ok(page)
+}
+
+// 单个目标 ASIN 的排名
+#[derive(Debug, Clone, Default)]
+pub struct TargetRank {
+    pub organic_rank: Option<i64>,
+    pub organic_page: Option<i64>,
+    pub organic_position: Option<i64>,  // 各页自然结果累计后的绝对位置
+    pub sponsored_rank: Option<i64>,
+    pub sponsored_page: Option<i64>,
+    pub product_info: Option<ProductInfo>,
+}
+
+/// 跨页累计同一关键词下多个目标 ASIN 的排名（同一关键词只搜索一次）
+#[derive(Debug, Default)]
+pub struct RankTracker {
+    pub ranks: HashMap<String, TargetRank>,  // key 为大写 ASIN
+    pub organic_top_50: Vec<String>,
+    pub sponsored_top_20: Vec<String>,
+    organic_seen: i64,  // 已处理页面的自然结果总数（每页数量不固定）
+}
+
+impl RankTracker {
+    pub fn new<'a>(targets: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            ranks: targets.into_iter().map(|a| (a.to_uppercase(), TargetRank::default())).collect(),
+            ..Default::default()
+        }
+    }
+
+    // 所有目标的自然排名和广告排名都已找到
+    pub fn all_found(&self) -> bool {
+        self.ranks.values().all(|r| r.organic_rank.is_some() && r.sponsored_rank.is_some())
+    }
+
+    fn set_sponsored(&mut self, asin: &str, rank: i64, page_num: i64) {
+        if let Some(target) = self.ranks.get_mut(&asin.to_uppercase()) {
+            if target.sponsored_rank.is_none() {
+                target.sponsored_rank = Some(rank);
+                target.sponsored_page = Some(page_num);
+            }
+        }
+    }
+
+    pub fn apply_page(&mut self, page_num: i64, page: &SearchPage) {
+        // 1. 横幅广告：按横幅内位置
+        for (idx, asin) in page.banner_asins.iter().enumerate() {
+            self.set_sponsored(asin, idx as i64 + 1, page_num);
+        }
+        // 2. 顶部广告区：排在横幅之后
+        let banner_count = page.banner_asins.len() as i64;
+        for (idx, asin) in page.top_sponsored_asins.iter().enumerate() {
+            self.set_sponsored(asin, banner_count + idx as i64 + 1, page_num);
+        }
+
+        // 3. 搜索结果：广告位从顶部广告数之后开始计数，自然位独立计数
+        let top_ad_count = banner_count + page.top_sponsored_asins.len() as i64;
+        let mut seen_organic = HashSet::new();
+        let mut seen_sponsored = HashSet::new();
+        let mut organic_position = 0i64;
+        let mut sponsored_position = top_ad_count;
+        let mut sponsored_before_video = 0i64;
+
+        for (idx, item) in page.results.iter().enumerate() {
+            if item.sponsored {
+                if !seen_sponsored.insert(item.asin.clone()) {
+                    continue;
+                }
+                sponsored_position += 1;
+                if page.video_ad.as_ref().is_some_and(|v| idx < v.results_before) {
+                    sponsored_before_video += 1;
+                }
+                if self.sponsored_top_20.len() < 20 {
+                    self.sponsored_top_20.push(item.asin.clone());
+                }
+                self.set_sponsored(&item.asin, sponsored_position, page_num);
+            } else {
+                if !seen_organic.insert(item.asin.clone()) {
+                    continue;
+                }
+                organic_position += 1;
+                self.organic_seen += 1;
+                if self.organic_top_50.len() < 50 {
+                    self.organic_top_50.push(item.asin.clone());
+                }
+                if let Some(target) = self.ranks.get_mut(&item.asin.to_uppercase()) {
+                    if target.organic_rank.is_none() {
+                        target.organic_rank = Some(organic_position);
+                        target.organic_page = Some(page_num);
+                        target.organic_position = Some(self.organic_seen);
+                        target.product_info = Some(item.info.clone());
+                    }
+                }
+            }
+        }
+
+        // 4. 视频广告：横幅 + 顶部广告区 + 视频之前的普通广告之后
+        if let Some(video) = &page.video_ad {
+            self.set_sponsored(&video.asin, top_ad_count + sponsored_before_video + 1, page_num);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/search").join(name);
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("读取 {:?} 失败: {}", path, e))
+    }
+
+    #[test]
+    fn test_parse_us_search_page() {
+        let page = parse_search_page(&fixture("US/desk-lamp/page1.html")).unwrap();
+        assert_eq!(page.banner_asins, vec!["B0BANNER01", "B0BANNER02", "B0BANNER03"]);
+        assert_eq!(page.top_sponsored_asins, vec!["B0TOPAD001"]);
+        assert_eq!(page.delivery_location.as_deref(), Some("New York 10001"));
+        assert_eq!(page.results.len(), 6);
+        let sponsored: Vec<&str> = page.results.iter().filter(|r| r.sponsored).map(|r| r.asin.as_str()).collect();
+        assert_eq!(sponsored, vec!["B0SPONS001", "B0TARGET01"]);
+
+        let video = page.video_ad.as_ref().unwrap();
+        assert_eq!((video.asin.as_str(), video.results_before), ("B0VIDEO001", 3));
+
+        let info = &page.results.iter().find(|r| r.asin == "B0TARGET01" && !r.sponsored).unwrap().info;
+        assert_eq!(info.title.as_deref(), Some("LED Desk Lamp with USB Charging Port, Dimmable"));
+        assert_eq!(info.price.as_deref(), Some("$29.99"));
+        assert_eq!(info.rating, Some(4.5));
+        assert_eq!(info.reviews_count, Some(3611));
+        assert_eq!(info.image_url.as_deref(), Some("https://m.media-amazon.com/images/I/71Target01L._AC_UL320_.jpg"));
+    }
+
+    #[test]
+    fn test_rank_tracker_across_pages() {
+        let page1 = parse_search_page(&fixture("US/desk-lamp/page1.html")).unwrap();
+        let page2 = parse_search_page(&fixture("US/desk-lamp/page2.html")).unwrap();
+
+        let mut tracker = RankTracker::new(["B0TARGET01", "b0video001", "B0PAGE2001", "B0MISSING1"]);
+        tracker.apply_page(1, &page1);
+        tracker.apply_page(2, &page2);
+
+        // 自然位：B0ORGANIC1, B0ORGANIC2, B0TARGET01 → 第 3 位；广告位：横幅 3 + 顶部 1 + B0SPONS001 → 第 6 位
+        let target = &tracker.ranks["B0TARGET01"];
+        assert_eq!((target.organic_rank, target.organic_page), (Some(3), Some(1)));
+        assert_eq!(target.organic_position, Some(3));
+        assert_eq!((target.sponsored_rank, target.sponsored_page), (Some(6), Some(1)));
+        assert_eq!(target.product_info.as_ref().unwrap().reviews_count, Some(3611));
+
+        // 视频广告在第 3 个结果之后，之前有 1 个普通广告：3 + 1 + 1 + 1
+        assert_eq!(tracker.ranks["B0VIDEO001"].sponsored_rank, Some(6));
+
+        // 第 2 页按页内位置计算，绝对位置接在第 1 页的 4 个自然结果之后
+        let page2_target = &tracker.ranks["B0PAGE2001"];
+        assert_eq!((page2_target.organic_rank, page2_target.organic_page), (Some(2), Some(2)));
+        assert_eq!(page2_target.organic_position, Some(6));
+
+        assert_eq!(tracker.ranks["B0MISSING1"].organic_rank, None);
+        assert!(!tracker.all_found());
+        assert_eq!(tracker.organic_top_50.len(), 7);
+        assert_eq!(tracker.sponsored_top_20, vec!["B0SPONS001", "B0TARGET01"]);
+    }
+
+    #[test]
+    fn test_parse_list_layout_with_video() {
+        // 列表布局（sg-col-20-of-24）：前两个是广告，视频广告插在第 3 个结果之后
+        let page = parse_search_page(&fixture("US/usb-c-cable/page1.html")).unwrap();
+        assert!(page.banner_asins.is_empty());
+        assert_eq!(page.delivery_location.as_deref(), Some("Seattle 98109"));
+        let asins: Vec<&str> = page.results.iter().map(|r| r.asin.as_str()).collect();
+        assert_eq!(asins, vec!["B0USBSP001", "B0USBSP002", "B0USBOR001", "B0USBOR002", "B0USBSP003", "B0USBOR003"]);
+        let sponsored: Vec<bool> = page.results.iter().map(|r| r.sponsored).collect();
+        assert_eq!(sponsored, vec![true, true, false, false, true, false]);
+
+        let video = page.video_ad.as_ref().unwrap();
+        assert_eq!((video.asin.as_str(), video.results_before), ("B0USBVID01", 3));
+
+        let info = &page.results[2].info;
+        assert_eq!(info.title.as_deref(), Some("USB C Charger Cable, Fast Charging"));
+        assert_eq!(info.price.as_deref(), Some("$7.64"));
+        assert_eq!(info.rating, Some(4.5));
+        assert_eq!(info.reviews_count, Some(104566));
+
+        let mut tracker = RankTracker::new(["B0USBOR002", "B0USBVID01", "B0USBSP002"]);
+        tracker.apply_page(1, &page);
+        assert_eq!(tracker.ranks["B0USBOR002"].organic_rank, Some(2));
+        // 视频之前有 2 个普通广告：2 + 1
+        assert_eq!(tracker.ranks["B0USBVID01"].sponsored_rank, Some(3));
+        assert_eq!(tracker.ranks["B0USBSP002"].sponsored_rank, Some(2));
+    }
+
+    #[test]
+    fn test_parse_localized_page() {
+        let page = parse_search_page(&fixture("DE/led-lampe/page1.html")).unwrap();
+        assert_eq!(page.banner_asins, vec!["B0DEBANR01", "B0DEBANR02"]);  // 来自 lp_asins 参数
+        assert!(page.results[0].sponsored);  // "Gesponsert"
+        let info = &page.results[1].info;
+        assert_eq!(info.price.as_deref(), Some("19,99 €"));
+        assert_eq!(info.rating, Some(4.3));
+        assert_eq!(info.reviews_count, Some(1234));
+        assert_eq!(page.delivery_location.as_deref(), Some("Berlin 10115"));
+
+        // 评论数只有 "3,6K" 文本时按千计算
+        assert_eq!(page.results[2].info.reviews_count, Some(3600));
+    }
+
+    #[test]
+    fn test_parse_error_pages() {
+        assert_eq!(parse_search_page(&fixture("captcha.html")).unwrap_err(), "检测到验证码页面");
+        assert!(parse_search_page("<html><body><div id=\"dp\"></div></body></html>").unwrap_err().contains("解析"));
+
+        // 没有结果的搜索页不是错误
+        let empty = parse_search_page(&fixture("no_results.html")).unwrap();
+        assert!(empty.results.is_empty());
+    }
+}
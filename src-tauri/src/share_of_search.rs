@@ -0,0 +1,418 @@
+// 搜索份额分析：基于 ranking_snapshots 的自然前 50 / 广告前 20 快照
+// 计算各 ASIN 在一组关键词上的曝光份额、竞品进出榜时间线、以及最常排在我们前面的 ASIN
+
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+// 进出榜只看自然前 N 位（前 50 尾部波动太大）
+pub const MOVEMENT_TOP_POSITIONS: usize = 20;
+
+// 进出榜记录最多返回条数
+const MAX_MOVEMENTS: usize = 300;
+
+// 某关键词某天的快照（已解析 JSON）
+#[derive(Debug, Clone)]
+pub struct KeywordSnapshot {
+    pub keyword: String,
+    pub date: String,
+    pub organic: Vec<String>,
+    pub sponsored: Vec<String>,
+}
+
+// 分析输入：监控的关键词、我们的 ASIN、搜索量
+#[derive(Debug, Clone, Default)]
+pub struct ShareOfSearchSource {
+    pub our_asins: HashMap<String, HashSet<String>>,  // 关键词 -> 该词下监控的 ASIN
+    pub volumes: HashMap<String, f64>,                 // 关键词 -> 月均搜索量
+    pub snapshots: Vec<KeywordSnapshot>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct AsinShare {
+    pub asin: String,
+    pub is_ours: bool,
+    pub organic_share: f64,      // 0-1，按天平均
+    pub sponsored_share: f64,
+    pub keyword_count: usize,    // 出现过的关键词数（自然或广告）
+    pub avg_organic_position: Option<f64>,
+}
+
+// 与 dates 一一对应
+#[derive(Debug, Serialize, Clone)]
+pub struct AsinShareTrend {
+    pub asin: String,
+    pub is_ours: bool,
+    pub organic: Vec<f64>,
+    pub sponsored: Vec<f64>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Outranker {
+    pub asin: String,
+    pub times: i64,              // 排在我们前面的（关键词, 天）次数
+    pub keyword_count: usize,
+    pub trend: Vec<i64>,         // 与 dates 一一对应
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MovementKind {
+    Entered,
+    Exited,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct CompetitorMovement {
+    pub date: String,
+    pub keyword: String,
+    pub asin: String,
+    pub kind: MovementKind,
+    pub position: usize,         // 进榜时的新位置 / 出榜前的位置
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ShareOfSearchReport {
+    pub keyword_count: usize,
+    pub snapshot_count: usize,
+    pub volume_weighted: bool,   // 是否有关键词使用了搜索量加权
+    pub our_asins: Vec<String>,
+    pub dates: Vec<String>,
+    pub shares: Vec<AsinShare>,
+    pub trend: Vec<AsinShareTrend>,
+    pub outrankers: Vec<Outranker>,
+    pub movements: Vec<CompetitorMovement>,
+}
+
+// 位置权重：近似点击曲线，第 1 位权重 1，第 n 位 1/n
+fn position_weight(position: usize) -> f64 {
+    1.0 / position as f64
+}
+
+// 去重后的 (ASIN, 位置)，同一 ASIN 多次出现只取最靠前的位置
+fn ranked_asins(list: &[String]) -> Vec<(&str, usize)> {
+    let mut seen = HashSet::new();
+    list.iter()
+        .enumerate()
+        .filter(|(_, asin)| !asin.is_empty() && seen.insert(asin.as_str()))
+        .map(|(i, asin)| (asin.as_str(), i + 1))
+        .collect()
+}
+
+// 单个列表内的份额，各 ASIN 之和为 1
+fn list_shares(list: &[String]) -> Vec<(&str, f64)> {
+    let ranked = ranked_asins(list);
+    let total: f64 = ranked.iter().map(|(_, pos)| position_weight(*pos)).sum();
+    if total <= 0.0 {
+        return Vec::new();
+    }
+    ranked.into_iter().map(|(asin, pos)| (asin, position_weight(pos) / total)).collect()
+}
+
+// 关键词权重：有搜索量用搜索量，缺失的取已知搜索量的最小值（保守），都没有则等权
+fn keyword_weights(keywords: &BTreeSet<&str>, volumes: &HashMap<String, f64>) -> (HashMap<String, f64>, bool) {
+    let known: Vec<f64> = keywords
+        .iter()
+        .filter_map(|k| volumes.get(*k).copied())
+        .filter(|v| *v > 0.0)
+        .collect();
+    let fallback = known.iter().copied().fold(f64::INFINITY, f64::min);
+    let fallback = if fallback.is_finite() { fallback } else { 1.0 };
+
+    let weights = keywords
+        .iter()
+        .map(|k| {
+            let weight = volumes.get(*k).copied().filter(|v| *v > 0.0).unwrap_or(fallback);
+            (k.to_string(), weight)
+        })
+        .collect();
+    (weights, !known.is_empty())
+}
+
+// 每天的加权份额：Σ(关键词权重 × 列表内份额) / 当天有快照的关键词权重之和
+fn daily_shares(
+    snapshots: &[&KeywordSnapshot],
+    weights: &HashMap<String, f64>,
+    pick: fn(&KeywordSnapshot) -> &[String],
+) -> HashMap<String, f64> {
+    let mut shares: HashMap<String, f64> = HashMap::new();
+    let mut total_weight = 0.0;
+    for snapshot in snapshots {
+        let list = pick(snapshot);
+        if list.is_empty() {
+            continue;
+        }
+        let weight = weights.get(&snapshot.keyword).copied().unwrap_or(1.0);
+        total_weight += weight;
+        for (asin, share) in list_shares(list) {
+            *shares.entry(asin.to_string()).or_default() += weight * share;
+        }
+    }
+    if total_weight > 0.0 {
+        shares.values_mut().for_each(|v| *v /= total_weight);
+    }
+    shares
+}
+
+fn organic_list(snapshot: &KeywordSnapshot) -> &[String] {
+    &snapshot.organic
+}
+
+fn sponsored_list(snapshot: &KeywordSnapshot) -> &[String] {
+    &snapshot.sponsored
+}
+
+// 同一关键词相邻两次快照之间，自然前 N 位的进出变化
+fn detect_movements(
+    snapshots: &[KeywordSnapshot],
+    our_asins: &HashMap<String, HashSet<String>>,
+) -> Vec<CompetitorMovement> {
+    let mut by_keyword: HashMap<&str, Vec<&KeywordSnapshot>> = HashMap::new();
+    for snapshot in snapshots.iter().filter(|s| !s.organic.is_empty()) {
+        by_keyword.entry(snapshot.keyword.as_str()).or_default().push(snapshot);
+    }
+
+    let top = |s: &KeywordSnapshot| -> HashMap<String, usize> {
+        ranked_asins(&s.organic)
+            .into_iter()
+            .filter(|(_, pos)| *pos <= MOVEMENT_TOP_POSITIONS)
+            .map(|(asin, pos)| (asin.to_string(), pos))
+            .collect()
+    };
+
+    let mut movements = Vec::new();
+    for (keyword, mut list) in by_keyword {
+        list.sort_by(|a, b| a.date.cmp(&b.date));
+        let ours = our_asins.get(keyword);
+        let is_ours = |asin: &str| ours.is_some_and(|set| set.contains(asin));
+
+        for pair in list.windows(2) {
+            let (before, after) = (top(pair[0]), top(pair[1]));
+            for (asin, pos) in after.iter().filter(|(a, _)| !before.contains_key(*a) && !is_ours(a)) {
+                movements.push(CompetitorMovement {
+                    date: pair[1].date.clone(),
+                    keyword: keyword.to_string(),
+                    asin: asin.clone(),
+                    kind: MovementKind::Entered,
+                    position: *pos,
+                });
+            }
+            for (asin, pos) in before.iter().filter(|(a, _)| !after.contains_key(*a) && !is_ours(a)) {
+                movements.push(CompetitorMovement {
+                    date: pair[1].date.clone(),
+                    keyword: keyword.to_string(),
+                    asin: asin.clone(),
+                    kind: MovementKind::Exited,
+                    position: *pos,
+                });
+            }
+        }
+    }
+
+    movements.sort_by(|a, b| {
+        b.date.cmp(&a.date)
+            .then_with(|| a.keyword.cmp(&b.keyword))
+            .then_with(|| a.position.cmp(&b.position))
+    });
+    movements.truncate(MAX_MOVEMENTS);
+    movements
+}
+
+/// 生成搜索份额报告
+/// top_n：份额排行、趋势和"排在我们前面"各保留的 ASIN 数（我们的 ASIN 总是包含在趋势中）
+pub fn build_report(source: &ShareOfSearchSource, top_n: usize) -> ShareOfSearchReport {
+    let keywords: BTreeSet<&str> = source.snapshots.iter().map(|s| s.keyword.as_str()).collect();
+    let (weights, volume_weighted) = keyword_weights(&keywords, &source.volumes);
+    let all_ours: HashSet<&str> = source.our_asins.values().flatten().map(|a| a.as_str()).collect();
+
+    let dates: Vec<String> = source
+        .snapshots
+        .iter()
+        .map(|s| s.date.clone())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let date_index: HashMap<&str, usize> = dates.iter().enumerate().map(|(i, d)| (d.as_str(), i)).collect();
+
+    let mut by_date: Vec<Vec<&KeywordSnapshot>> = vec![Vec::new(); dates.len()];
+    for snapshot in &source.snapshots {
+        by_date[date_index[snapshot.date.as_str()]].push(snapshot);
+    }
+
+    // 份额趋势
+    let mut organic_series: HashMap<String, Vec<f64>> = HashMap::new();
+    let mut sponsored_series: HashMap<String, Vec<f64>> = HashMap::new();
+    for (i, day) in by_date.iter().enumerate() {
+        for (asin, share) in daily_shares(day, &weights, organic_list) {
+            organic_series.entry(asin).or_insert_with(|| vec![0.0; dates.len()])[i] = share;
+        }
+        for (asin, share) in daily_shares(day, &weights, sponsored_list) {
+            sponsored_series.entry(asin).or_insert_with(|| vec![0.0; dates.len()])[i] = share;
+        }
+    }
+
+    // 出现的关键词数、平均自然位置、排在我们前面的次数
+    let mut keyword_sets: HashMap<&str, HashSet<&str>> = HashMap::new();
+    let mut positions: HashMap<&str, (usize, usize)> = HashMap::new();
+    let mut outranks: HashMap<&str, (Vec<i64>, HashSet<&str>)> = HashMap::new();
+    for snapshot in &source.snapshots {
+        let organic = ranked_asins(&snapshot.organic);
+        for (asin, pos) in &organic {
+            keyword_sets.entry(asin).or_default().insert(&snapshot.keyword);
+            let entry = positions.entry(asin).or_default();
+            entry.0 += pos;
+            entry.1 += 1;
+        }
+        for (asin, _) in ranked_asins(&snapshot.sponsored) {
+            keyword_sets.entry(asin).or_default().insert(&snapshot.keyword);
+        }
+
+        // 我们不在列表中时，列表里的 ASIN 都排在我们前面
+        let Some(ours) = source.our_asins.get(&snapshot.keyword) else { continue };
+        if organic.is_empty() {
+            continue;
+        }
+        let our_position = organic
+            .iter()
+            .filter(|(asin, _)| ours.contains(*asin))
+            .map(|(_, pos)| *pos)
+            .min()
+            .unwrap_or(usize::MAX);
+        let day = date_index[snapshot.date.as_str()];
+        for (asin, _) in organic.iter().filter(|(a, pos)| *pos < our_position && !all_ours.contains(a)) {
+            let entry = outranks.entry(asin).or_insert_with(|| (vec![0; dates.len()], HashSet::new()));
+            entry.0[day] += 1;
+            entry.1.insert(&snapshot.keyword);
+        }
+    }
+
+    let day_count = dates.len().max(1) as f64;
+    let mean = |series: Option<&Vec<f64>>| series.map(|s| s.iter().sum::<f64>() / day_count).unwrap_or(0.0);
+
+    let mut shares: Vec<AsinShare> = keyword_sets
+        .iter()
+        .map(|(asin, keywords)| AsinShare {
+            asin: asin.to_string(),
+            is_ours: all_ours.contains(asin),
+            organic_share: mean(organic_series.get(*asin)),
+            sponsored_share: mean(sponsored_series.get(*asin)),
+            keyword_count: keywords.len(),
+            avg_organic_position: positions.get(asin).map(|(sum, n)| *sum as f64 / *n as f64),
+        })
+        .collect();
+    shares.sort_by(|a, b| {
+        (b.organic_share + b.sponsored_share)
+            .total_cmp(&(a.organic_share + a.sponsored_share))
+            .then_with(|| a.asin.cmp(&b.asin))
+    });
+
+    // 只保留份额前 N 的竞品 + 我们的全部 ASIN
+    let mut competitors = 0;
+    shares.retain(|s| {
+        competitors += usize::from(!s.is_ours);
+        s.is_ours || competitors <= top_n
+    });
+
+    let trend = shares
+        .iter()
+        .map(|s| AsinShareTrend {
+            asin: s.asin.clone(),
+            is_ours: s.is_ours,
+            organic: organic_series.get(&s.asin).cloned().unwrap_or_else(|| vec![0.0; dates.len()]),
+            sponsored: sponsored_series.get(&s.asin).cloned().unwrap_or_else(|| vec![0.0; dates.len()]),
+        })
+        .collect();
+
+    let mut outrankers: Vec<Outranker> = outranks
+        .into_iter()
+        .map(|(asin, (trend, keywords))| Outranker {
+            asin: asin.to_string(),
+            times: trend.iter().sum(),
+            keyword_count: keywords.len(),
+            trend,
+        })
+        .collect();
+    outrankers.sort_by(|a, b| b.times.cmp(&a.times).then_with(|| a.asin.cmp(&b.asin)));
+    outrankers.truncate(top_n);
+
+    let mut our_asins: Vec<String> = all_ours.iter().map(|a| a.to_string()).collect();
+    our_asins.sort();
+
+    ShareOfSearchReport {
+        keyword_count: keywords.len(),
+        snapshot_count: source.snapshots.len(),
+        volume_weighted,
+        our_asins,
+        movements: detect_movements(&source.snapshots, &source.our_asins),
+        dates,
+        shares,
+        trend,
+        outrankers,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(keyword: &str, date: &str, organic: &[&str]) -> KeywordSnapshot {
+        KeywordSnapshot {
+            keyword: keyword.to_string(),
+            date: date.to_string(),
+            organic: organic.iter().map(|s| s.to_string()).collect(),
+            sponsored: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_share_weighted_by_volume() {
+        let mut source = ShareOfSearchSource {
+            snapshots: vec![snapshot("big", "2024-01-01", &["A"]), snapshot("small", "2024-01-01", &["B"])],
+            ..Default::default()
+        };
+        source.our_asins.insert("big".into(), HashSet::from(["A".to_string()]));
+        source.volumes.insert("big".into(), 3000.0);
+        source.volumes.insert("small".into(), 1000.0);
+
+        let report = build_report(&source, 10);
+        assert!(report.volume_weighted);
+        let share = |asin: &str| report.shares.iter().find(|s| s.asin == asin).unwrap().organic_share;
+        assert!((share("A") - 0.75).abs() < 1e-9);
+        assert!((share("B") - 0.25).abs() < 1e-9);
+
+        // 列表内按位置衰减：第 1 位权重是第 2 位的 2 倍
+        let list = vec!["X".to_string(), "Y".to_string(), "X".to_string()];
+        let shares = list_shares(&list);
+        assert_eq!(shares.len(), 2);
+        assert!((shares[0].1 - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_movements_and_outrankers() {
+        let mut source = ShareOfSearchSource {
+            snapshots: vec![
+                snapshot("lamp", "2024-01-01", &["C1", "OURS", "C2"]),
+                snapshot("lamp", "2024-01-02", &["C3", "C1", "OURS"]),
+                snapshot("desk lamp", "2024-01-02", &["C1", "C2"]),
+            ],
+            ..Default::default()
+        };
+        source.our_asins.insert("lamp".into(), HashSet::from(["OURS".to_string()]));
+        source.our_asins.insert("desk lamp".into(), HashSet::from(["OURS".to_string()]));
+
+        let report = build_report(&source, 10);
+        assert_eq!(report.dates, vec!["2024-01-01", "2024-01-02"]);
+
+        // C3 进榜、C2 出榜，我们自己的 ASIN 不计入
+        assert_eq!(report.movements.len(), 2);
+        assert!(report.movements.iter().any(|m| m.asin == "C3" && m.kind == MovementKind::Entered && m.position == 1));
+        assert!(report.movements.iter().any(|m| m.asin == "C2" && m.kind == MovementKind::Exited && m.position == 3));
+
+        // C1：lamp 两天 + desk lamp（我们不在榜）一天
+        let c1 = &report.outrankers[0];
+        assert_eq!(c1.asin, "C1");
+        assert_eq!(c1.times, 3);
+        assert_eq!(c1.keyword_count, 2);
+        assert_eq!(c1.trend, vec![1, 2]);
+        assert!(report.trend.iter().any(|t| t.is_ours && t.asin == "OURS"));
+    }
+}
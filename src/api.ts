@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { BackupInfo, Category, KeywordData, KeywordMonitoring, MonitoringSparkline, MonitoringStats, Product, RankingHistory, RankingResult, RankingSnapshot, Root, ScAnalysis, TrafficLevelStats, WorkflowStatus } from "./types";
+import type { BackupInfo, BrandDictionaryEntry, BrandFilter, BrandNegativeSuggestion, BrandSeedResult, BrandType, BulkMonitoringResult, Category, CrawlerEngine, HistoryMaintenanceReport, IntegrityReport, IntentClassifySummary, KeywordData, KeywordDataFilter, KeywordMonitoring, MonitoringFailureStatus, MonitoringImportResult, MonitoringSparkline, MonitoringStats, PipelineResult, PipelineRun, PipelineStep, Product, RankingHistory, RankingResult, RankingSnapshot, Root, RunInfo, ScAnalysis, ShareOfSearchReport, TrafficLevelStats, WorkflowStatus } from "./types";
 
 // ==================== 产品管理 ====================
 
@@ -64,6 +64,7 @@ export async function getRoots(params: {
   productId?: number;
   search?: string;
   categoryIds?: number[];
+  brandTypes?: BrandFilter[];
   sortBy?: string;
   sortOrder?: string;
   page: number;
@@ -73,6 +74,7 @@ export async function getRoots(params: {
     productId: params.productId || null,
     search: params.search || null,
     categoryIds: params.categoryIds?.length ? params.categoryIds : null,
+    brandTypes: params.brandTypes?.length ? params.brandTypes : null,
     sortBy: params.sortBy || null,
     sortOrder: params.sortOrder || null,
     page: params.page,
@@ -167,6 +169,7 @@ export async function getKeywordData(params: {
   relevanceLevels?: string[];
   primaryCategories?: string[];
   orderlinessValues?: string[];
+  brandTypes?: BrandFilter[];
   sortBy?: string;
   sortOrder?: string;
   page: number;
@@ -179,6 +182,7 @@ export async function getKeywordData(params: {
     relevanceLevels: params.relevanceLevels?.length ? params.relevanceLevels : null,
     primaryCategories: params.primaryCategories?.length ? params.primaryCategories : null,
     orderlinessValues: params.orderlinessValues?.length ? params.orderlinessValues : null,
+    brandTypes: params.brandTypes?.length ? params.brandTypes : null,
     sortBy: params.sortBy || null,
     sortOrder: params.sortOrder || null,
     page: params.page,
@@ -286,6 +290,87 @@ export async function getWorkflowStatus(productId: number): Promise<WorkflowStat
   return await invoke("get_workflow_status", { productId });
 }
 
+// ==================== 品牌词典 ====================
+
+export async function getBrandDictionary(productId: number, marketplace?: string): Promise<BrandDictionaryEntry[]> {
+  return await invoke("get_brand_dictionary", { productId, marketplace: marketplace || null });
+}
+
+export async function addBrand(
+  productId: number,
+  brand: string,
+  brandType: BrandType,
+  marketplace?: string
+): Promise<number> {
+  return await invoke("add_brand", { productId, marketplace: marketplace || null, brand, brandType });
+}
+
+export async function updateBrandType(id: number, brandType: BrandType): Promise<void> {
+  return await invoke("update_brand_type", { id, brandType });
+}
+
+export async function deleteBrand(id: number): Promise<void> {
+  return await invoke("delete_brand", { id });
+}
+
+/**
+ * 从竞品标题（智能文案竞品、竞品情报监控 ASIN）提取品牌写入词典
+ */
+export async function seedBrandDictionary(productId: number): Promise<BrandSeedResult> {
+  return await invoke("seed_brand_dictionary", { productId });
+}
+
+/**
+ * 按品牌词典重新标记关键词和词根，返回 [关键词数, 词根数]
+ */
+export async function refreshBrandFlags(productId: number): Promise<[number, number]> {
+  return await invoke("refresh_brand_flags", { productId });
+}
+
+/**
+ * 竞品品牌否词建议；单词品牌可用 batchSetRootsNegativeByWords 直接设为否词
+ */
+export async function getBrandNegativeSuggestions(productId: number): Promise<BrandNegativeSuggestion[]> {
+  return await invoke("get_brand_negative_suggestions", { productId });
+}
+
+// ==================== 搜索意图识别 ====================
+
+/**
+ * 规则 + 词典识别搜索意图，不覆盖人工编辑或 AI 分类写入的意图
+ */
+export async function classifySearchIntent(productId: number): Promise<IntentClassifySummary> {
+  return await invoke("classify_search_intent", { productId });
+}
+
+// ==================== 一键分析流程 ====================
+
+/**
+ * 执行分析流程（流量级别 → 流量占比 → 词组打标 → 有序性）
+ * 进度通过 "analysis-pipeline-progress" 事件推送
+ * @param steps 要执行的步骤，不传表示全部
+ * @param resumeRunId 失败流程的 ID，传入时从失败步骤继续
+ */
+export async function runAnalysisPipeline(
+  productId: number,
+  steps?: PipelineStep[],
+  resumeRunId?: number
+): Promise<PipelineResult> {
+  return await invoke("run_analysis_pipeline", {
+    productId,
+    steps: steps || null,
+    resumeRunId: resumeRunId ?? null,
+  });
+}
+
+export async function getPipelineRuns(productId: number, limit?: number): Promise<PipelineRun[]> {
+  return await invoke("get_pipeline_runs", { productId, limit: limit ?? null });
+}
+
+export async function getLastFailedPipelineRun(productId: number): Promise<PipelineRun | null> {
+  return await invoke("get_last_failed_pipeline_run", { productId });
+}
+
 // ==================== 备份管理 ====================
 
 export async function createBackup(
@@ -310,6 +395,24 @@ export async function deleteBackup(backupId: number): Promise<void> {
   return await invoke("delete_backup", { backupId });
 }
 
+// ==================== 数据完整性检查 ====================
+
+/**
+ * 检查数据库完整性（外键孤儿数据）
+ * @param repair 是否修复发现的孤儿数据
+ */
+export async function checkDbIntegrity(repair: boolean): Promise<IntegrityReport> {
+  return await invoke("check_db_integrity", { repair });
+}
+
+/**
+ * 排名历史归档：按保留天数把原始检测汇总为日/周数据
+ * @param vacuum 完成后是否 VACUUM 回收空间
+ */
+export async function runHistoryMaintenance(vacuum: boolean): Promise<HistoryMaintenanceReport> {
+  return await invoke("run_history_maintenance", { vacuum });
+}
+
 // ==================== API Key 安全存储 ====================
 
 /**
@@ -366,7 +469,8 @@ export async function addKeywordMonitoring(
   keyword: string,
   asin: string,
   country: string,
-  priority?: string
+  priority?: string,
+  location?: string
 ): Promise<number> {
   return await invoke("add_keyword_monitoring", {
     productId,
@@ -374,6 +478,7 @@ export async function addKeywordMonitoring(
     asin,
     country,
     priority: priority || null,
+    location: location || null,
   });
 }
 
@@ -382,17 +487,46 @@ export async function addKeywordMonitoring(
  */
 export async function batchAddKeywordMonitoring(
   productId: number,
-  items: { keyword: string; asin: string; country: string; priority?: string }[]
+  items: { keyword: string; asin: string; country: string; priority?: string; location?: string }[]
 ): Promise<number[]> {
   const tuples = items.map(item => [
     item.keyword,
     item.asin,
     item.country,
     item.priority || null,
-  ] as [string, string, string, string | null]);
+    item.location || null,
+  ] as [string, string, string, string | null, string | null]);
   return await invoke("batch_add_keyword_monitoring", { productId, items: tuples });
 }
 
+/**
+ * 按词库筛选条件 × ASIN × 站点批量添加监控（dryRun 只统计不写入）
+ */
+export async function bulkAddMonitoring(
+  productId: number,
+  filter: KeywordDataFilter,
+  asins: string[],
+  countries: string[],
+  priority: string | null,
+  dryRun: boolean
+): Promise<BulkMonitoringResult> {
+  return await invoke("bulk_add_monitoring", { productId, filter, asins, countries, priority, dryRun });
+}
+
+/**
+ * 导出监控列表为 CSV 文本
+ */
+export async function exportMonitoringCsv(productId: number): Promise<string> {
+  return await invoke("export_monitoring_csv", { productId });
+}
+
+/**
+ * 从 CSV 导入监控列表
+ */
+export async function importMonitoringCsv(productId: number, content: string): Promise<MonitoringImportResult> {
+  return await invoke("import_monitoring_csv", { productId, content });
+}
+
 /**
  * 获取关键词监控列表
  */
@@ -448,6 +582,16 @@ export async function updateKeywordMonitoringTags(
   });
 }
 
+/**
+ * 批量设置关键词的检测页数（null 表示使用全局设置）
+ */
+export async function setMonitoringMaxPages(
+  ids: number[],
+  maxPages: number | null
+): Promise<number> {
+  return await invoke("set_monitoring_max_pages", { ids, maxPages });
+}
+
 /**
  * 删除关键词监控
  */
@@ -490,6 +634,18 @@ export async function getRankingSnapshots(
   return await invoke("get_ranking_snapshots", { keyword, country, days });
 }
 
+/**
+ * 获取搜索份额分析（基于竞品快照）
+ */
+export async function getShareOfSearch(
+  productId: number,
+  country: string,
+  days: number = 30,
+  topN: number = 10
+): Promise<ShareOfSearchReport> {
+  return await invoke("get_share_of_search", { productId, country, days, topN });
+}
+
 /**
  * 获取监控迷你图数据
  */
@@ -519,13 +675,15 @@ export async function checkSingleRanking(
 export async function checkAllRankings(
   productId: number,
   maxPages?: number,
-  hoursSinceLastCheck?: number
+  hoursSinceLastCheck?: number,
+  engine?: CrawlerEngine
 ): Promise<[number, RankingResult][]> {
   return await invoke("check_all_rankings", {
     productId,
     maxPages: maxPages || null,
     // 用 undefined 判断而非 || null，因为 0 是有效值（表示无时间限制）
     hoursSinceLastCheck: hoursSinceLastCheck === undefined ? null : hoursSinceLastCheck,
+    engine: engine || null,
   });
 }
 
@@ -534,17 +692,40 @@ export async function checkAllRankings(
  */
 export async function checkSelectedRankings(
   ids: number[],
-  maxPages?: number
+  maxPages?: number,
+  engine?: CrawlerEngine
 ): Promise<[number, RankingResult][]> {
   return await invoke("check_selected_rankings", {
     ids,
     maxPages: maxPages || null,
+    engine: engine || null,
   });
 }
 
+/**
+ * 获取监控项的检测失败状态
+ */
+export async function getMonitoringFailures(productId: number): Promise<MonitoringFailureStatus[]> {
+  return await invoke("get_monitoring_failures", { productId });
+}
+
+/**
+ * 获取正在运行的批量检测
+ */
+export async function listRankingRuns(): Promise<RunInfo[]> {
+  return await invoke("list_ranking_runs");
+}
+
+/**
+ * 暂停 / 继续 / 取消批量检测
+ */
+export async function controlRankingRun(runId: number, action: 'pause' | 'resume' | 'cancel'): Promise<RunInfo> {
+  return await invoke("control_ranking_run", { runId, action });
+}
+
 // ==================== 调度器管理 ====================
 
-import type { SchedulerSettings, SchedulerStatus, TaskLog } from "./types";
+import type { SchedulerSettings, SchedulerStatus, TaskLog, TaskLogDetail } from "./types";
 
 /**
  * 获取调度器设置
@@ -588,6 +769,13 @@ export async function getTaskLogs(limit?: number): Promise<TaskLog[]> {
   return await invoke("get_task_logs", { limit });
 }
 
+/**
+ * 获取任务详情（每个关键词的检测结果和耗时统计）
+ */
+export async function getTaskLogDetail(taskId: number): Promise<TaskLogDetail | null> {
+  return await invoke("get_task_log_detail", { taskId });
+}
+
 /**
  * 获取正在运行的任务
  */
@@ -602,6 +790,243 @@ export async function clearTaskLogs(): Promise<void> {
   return await invoke("clear_task_logs");
 }
 
+// ==================== 代理池 ====================
+
+import type { ProxyServer, ProxyInput, ProxyCheckResult } from "./types";
+
+/**
+ * 获取代理池
+ */
+export async function getProxies(): Promise<ProxyServer[]> {
+  return await invoke("get_proxies");
+}
+
+/**
+ * 批量添加代理（每行一个），返回新增数量
+ */
+export async function addProxies(urls: string, input: ProxyInput): Promise<number> {
+  return await invoke("add_proxies", { urls, input });
+}
+
+/**
+ * 编辑代理（含启用/停用）
+ */
+export async function updateProxy(id: number, input: ProxyInput): Promise<void> {
+  return await invoke("update_proxy", { id, input });
+}
+
+/**
+ * 删除代理
+ */
+export async function deleteProxy(id: number): Promise<void> {
+  return await invoke("delete_proxy", { id });
+}
+
+/**
+ * 清零代理的成功/失败统计
+ */
+export async function resetProxyStats(id: number): Promise<void> {
+  return await invoke("reset_proxy_stats", { id });
+}
+
+/**
+ * 代理健康检查，不传 ids 时检查全部
+ */
+export async function checkProxies(ids?: number[]): Promise<ProxyCheckResult[]> {
+  return await invoke("check_proxies", { ids });
+}
+
+// ==================== 站点限速 ====================
+
+import type { CrawlRateLimit } from "./types";
+
+/**
+ * 获取各站点的限速状态
+ */
+export async function getCrawlRateLimits(): Promise<CrawlRateLimit[]> {
+  return await invoke("get_crawl_rate_limits");
+}
+
+/**
+ * 设置站点每小时请求预算，0 表示不限
+ */
+export async function setCrawlRateBudget(country: string, hourlyBudget: number): Promise<CrawlRateLimit> {
+  return await invoke("set_crawl_rate_budget", { country, hourlyBudget });
+}
+
+/**
+ * 手动解除站点的限速和冷却
+ */
+export async function resetCrawlRateLimit(country: string): Promise<void> {
+  return await invoke("reset_crawl_rate_limit", { country });
+}
+
+// ==================== 爬取页面归档 ====================
+
+import type { CrawlArchiveEntry } from "./types";
+
+/**
+ * 列出归档的页面，date 为空时列出全部日期
+ */
+export async function getCrawlArchiveEntries(date?: string): Promise<CrawlArchiveEntry[]> {
+  return await invoke("get_crawl_archive_entries", { date: date ?? null });
+}
+
+/**
+ * 从归档的搜索页重新计算排名（不重新爬取，不写入历史），run 为空时使用当天最新一次检测
+ */
+export async function reparseArchivedRanking(
+  date: string,
+  keyword: string,
+  asin: string,
+  country: string,
+  run?: string | null
+): Promise<RankingResult> {
+  return await invoke("reparse_archived_ranking", { date, keyword, asin, country, run: run ?? null });
+}
+
+/**
+ * 从归档的详情页重新提取 Listing 信息
+ */
+export async function reparseArchivedListing(date: string, asin: string, country: string): Promise<ListingResult> {
+  return await invoke("reparse_archived_listing", { date, asin, country });
+}
+
+/**
+ * 按保留天数清理归档，返回删除的天数
+ */
+export async function cleanupCrawlArchive(): Promise<number> {
+  return await invoke("cleanup_crawl_archive");
+}
+
+// ==================== 配送地址 ====================
+
+import type { MarketplaceLocation } from "./types";
+
+/**
+ * 获取各站点爬取使用的配送邮编
+ */
+export async function getDeliveryLocations(): Promise<MarketplaceLocation[]> {
+  return await invoke("get_delivery_locations");
+}
+
+/**
+ * 保存各站点的配送邮编，空值使用默认邮编
+ */
+export async function setDeliveryLocations(locations: MarketplaceLocation[]): Promise<MarketplaceLocation[]> {
+  return await invoke("set_delivery_locations", { locations });
+}
+
+// ==================== 排名检测计划（Cron） ====================
+
+import type { RankingSchedule, RankingScheduleInput } from "./types";
+
+export async function getRankingSchedules(): Promise<RankingSchedule[]> {
+  return await invoke("get_ranking_schedules");
+}
+
+export async function createRankingSchedule(schedule: RankingScheduleInput): Promise<number> {
+  return await invoke("create_ranking_schedule", { schedule });
+}
+
+export async function updateRankingSchedule(id: number, schedule: RankingScheduleInput): Promise<void> {
+  return await invoke("update_ranking_schedule", { id, schedule });
+}
+
+export async function deleteRankingSchedule(id: number): Promise<void> {
+  return await invoke("delete_ranking_schedule", { id });
+}
+
+/**
+ * 预览 cron 表达式接下来的触发时间（同时用于校验表达式）
+ */
+export async function previewCronSchedule(cronExpr: string, timezone: string, count?: number): Promise<string[]> {
+  return await invoke("preview_cron_schedule", { cronExpr, timezone, count: count ?? null });
+}
+
+// ==================== 排名告警 ====================
+
+import type { AlertRule, AlertRuleInput, AlertRecord, AlertStatus } from "./types";
+
+/**
+ * 获取告警规则（指定产品时包含全局规则）
+ */
+export async function getAlertRules(productId?: number): Promise<AlertRule[]> {
+  return await invoke("get_alert_rules", { productId: productId ?? null });
+}
+
+export async function createAlertRule(rule: AlertRuleInput): Promise<number> {
+  return await invoke("create_alert_rule", { rule });
+}
+
+export async function updateAlertRule(id: number, rule: AlertRuleInput): Promise<void> {
+  return await invoke("update_alert_rule", { id, rule });
+}
+
+export async function deleteAlertRule(id: number): Promise<void> {
+  return await invoke("delete_alert_rule", { id });
+}
+
+/**
+ * 获取告警记录
+ */
+export async function getAlertHistory(
+  productId?: number,
+  status?: AlertStatus,
+  limit?: number
+): Promise<AlertRecord[]> {
+  return await invoke("get_alert_history", {
+    productId: productId ?? null,
+    status: status ?? null,
+    limit: limit ?? null,
+  });
+}
+
+/**
+ * 获取未确认的告警数
+ */
+export async function getOpenAlertCount(productId?: number): Promise<number> {
+  return await invoke("get_open_alert_count", { productId: productId ?? null });
+}
+
+export async function acknowledgeAlerts(ids: number[]): Promise<number> {
+  return await invoke("acknowledge_alerts", { ids });
+}
+
+/**
+ * 暂停告警：暂停期间同一规则对同一关键词不再触发
+ */
+export async function snoozeAlerts(ids: number[], hours: number): Promise<number> {
+  return await invoke("snooze_alerts", { ids, hours });
+}
+
+// ==================== 外部通知渠道（Webhook / 邮件） ====================
+
+import type { ChannelConfig, NotificationChannel, NotificationChannelInput } from "./types";
+
+export async function getNotificationChannels(): Promise<NotificationChannel[]> {
+  return await invoke("get_notification_channels");
+}
+
+export async function createNotificationChannel(channel: NotificationChannelInput): Promise<number> {
+  return await invoke("create_notification_channel", { channel });
+}
+
+export async function updateNotificationChannel(id: number, channel: NotificationChannelInput): Promise<void> {
+  return await invoke("update_notification_channel", { id, channel });
+}
+
+export async function deleteNotificationChannel(id: number): Promise<void> {
+  return await invoke("delete_notification_channel", { id });
+}
+
+/**
+ * 测试发送（不重试，可用于保存前的配置）
+ */
+export async function testNotificationChannel(config: ChannelConfig): Promise<void> {
+  return await invoke("test_notification_channel", { config });
+}
+
 // ==================== 依赖安装 ====================
 
 import type { DependencyStatus, InstallResult } from "./types";
@@ -671,12 +1096,14 @@ export async function addOptimizationEvent(
 export async function getOptimizationEvents(
   productId: number,
   startDate?: string,
-  endDate?: string
+  endDate?: string,
+  windowDays?: number
 ): Promise<OptimizationEvent[]> {
   return await invoke("get_optimization_events", {
     productId,
     startDate,
     endDate,
+    windowDays,
   });
 }
 
@@ -1316,6 +1743,7 @@ export interface ListingResult {
   description: string | null;
   fetched_at: string;
   error: string | null;
+  location?: string | null;  // 爬取使用的配送邮编
 }
 
 /**
@@ -22,6 +22,94 @@ export interface WorkflowStatus {
   has_orderliness: boolean;    // 是否有有序性
 }
 
+// ==================== 一键分析流程 ====================
+
+export type PipelineStep = 'traffic_level' | 'traffic_share' | 'phrase_tag' | 'orderliness';
+
+// 分析流程运行记录
+export interface PipelineRun {
+  id: number;
+  product_id: number;
+  steps: PipelineStep[];
+  completed_steps: PipelineStep[];
+  current_step: PipelineStep | null;
+  status: 'running' | 'completed' | 'failed';
+  error_message: string | null;
+  attempts: number;
+  started_at: string;
+  ended_at: string | null;
+}
+
+// "analysis-pipeline-progress" 事件数据
+export interface PipelineProgress {
+  run_id: number;
+  product_id: number;
+  step: PipelineStep;
+  step_index: number;
+  total_steps: number;
+  status: 'running' | 'completed' | 'skipped' | 'failed';
+  error: string | null;
+}
+
+export interface PipelineResult {
+  run: PipelineRun;
+  workflow_status: WorkflowStatus;
+}
+
+// ==================== 品牌词典 ====================
+
+export type BrandType = 'own' | 'competitor';
+
+// 品牌筛选值：none 表示非品牌词
+export type BrandFilter = BrandType | 'none';
+
+export interface BrandDictionaryEntry {
+  id: number;
+  product_id: number;
+  marketplace: string;
+  brand: string;
+  brand_type: BrandType;
+  source: 'manual' | 'competitor_title';
+  title_count: number;    // 以该品牌开头的竞品 ASIN 数
+  keyword_count: number;  // 命中的关键词数
+  created_at: string;
+}
+
+export interface BrandSeedResult {
+  titles_scanned: number;
+  brands_added: number;
+  brands_updated: number;
+  brands_removed: number;
+  keywords_flagged: number;
+  roots_flagged: number;
+}
+
+export interface BrandNegativeSuggestion {
+  brand: string;
+  keyword_count: number;
+  total_search_volume: number;
+  sample_keywords: string[];
+  root_words: string[];     // 可直接设为否词的词根（单词品牌）
+  negative_phrase: string;  // 广告否定词组
+  already_negative: boolean;
+}
+
+// ==================== 搜索意图识别 ====================
+
+export interface IntentCount {
+  intent: string;
+  label: string;
+  count: number;
+}
+
+export interface IntentClassifySummary {
+  total_keywords: number;
+  classified: number;
+  skipped_manual: number;  // 人工或 AI 填写的意图，保持不变
+  avg_confidence: number;
+  by_intent: IntentCount[];
+}
+
 export interface Category {
   id: number;
   name: string;
@@ -37,6 +125,8 @@ export interface Root {
   percentage: number;
   categories: number[];
   is_negative: boolean;  // 是否为否词词根
+  brand_name?: string | null;     // 命中的品牌
+  brand_type?: BrandType | null;  // 自有/竞品品牌
 }
 
 export interface Stats {
@@ -73,6 +163,10 @@ export interface KeywordData {
   primary_category: string | null;       // 一级分类
   secondary_category: string | null;     // 二级分类
   search_intent: string | null;          // 搜索意图
+  search_intent_source?: 'rule' | 'ai' | 'manual' | null;  // 意图来源
+  search_intent_confidence?: number | null;                // 规则识别置信度
+  brand_name?: string | null;                              // 命中的品牌
+  brand_type?: BrandType | null;                           // 自有/竞品品牌
   traffic_share: number | null;          // 流量占比
   // ASIN动态列
   asin_data: string | null;
@@ -87,6 +181,44 @@ export interface BackupInfo {
   keyword_data_count: number;
 }
 
+// ==================== 数据完整性检查 ====================
+
+// 孤儿数据问题
+export interface OrphanIssue {
+  table: string;
+  column: string;
+  parent_table: string;
+  action: 'delete' | 'set_null';
+  count: number;
+}
+
+// 完整性检查报告
+export interface IntegrityReport {
+  integrity_ok: boolean;
+  integrity_messages: string[];
+  foreign_keys_enabled: boolean;
+  tables_checked: number;
+  issues: OrphanIssue[];
+  total_orphans: number;
+  repaired: boolean;
+  remaining_orphans: number;
+}
+
+// 排名历史归档结果
+export interface HistoryMaintenanceReport {
+  raw_retention_days: number;
+  daily_retention_days: number;
+  check_log_retention_days: number;
+  raw_rows_compacted: number;
+  daily_rows_compacted: number;
+  raw_rows: number;
+  daily_rows: number;
+  weekly_rows: number;
+  size_before: number;
+  size_after: number;
+  vacuumed: boolean;
+}
+
 // ==================== 关键词排名监控 ====================
 
 // 关键词监控
@@ -114,6 +246,8 @@ export interface KeywordMonitoring {
   last_checked: string | null;
   created_at: string;
   tags: string | null;  // JSON array: ["high_traffic", "high_conversion"]
+  max_pages?: number | null;  // 单独设置的检测页数，null 使用全局设置
+  location?: string | null;  // 单独指定的配送邮编，null 使用站点设置
 }
 
 // 关键词标签定义
@@ -138,6 +272,16 @@ export interface RankingHistory {
   sponsored_rank: number | null;
   sponsored_page: number | null;
 
+  // 周期内自然结果的最佳/平均/最差绝对位置（没有位置信息的旧记录不参与）
+  organic_position: number | null;
+  organic_avg: number | null;
+  organic_worst: number | null;
+  sponsored_avg: number | null;
+  sponsored_worst: number | null;
+  check_count: number;
+  resolution: 'day' | 'week';  // 范围超过 120 天时按周聚合
+  location?: string | null;  // 检测使用的邮编（多个用逗号分隔，汇总后的数据为 null）
+
   checked_at: string;
 }
 
@@ -152,6 +296,101 @@ export interface RankingSnapshot {
   sponsored_top_20: string | null;  // JSON
 
   created_at: string;
+  location: string | null;          // 检测使用的配送邮编，旧快照为 null
+}
+
+// 搜索份额分析（份额为 0-1，趋势数组与 dates 一一对应）
+export interface AsinShare {
+  asin: string;
+  is_ours: boolean;
+  organic_share: number;
+  sponsored_share: number;
+  keyword_count: number;
+  avg_organic_position: number | null;
+}
+
+export interface AsinShareTrend {
+  asin: string;
+  is_ours: boolean;
+  organic: number[];
+  sponsored: number[];
+}
+
+export interface Outranker {
+  asin: string;
+  times: number;  // 排在我们前面的（关键词, 天）次数
+  keyword_count: number;
+  trend: number[];
+}
+
+export interface CompetitorMovement {
+  date: string;
+  keyword: string;
+  asin: string;
+  kind: 'entered' | 'exited';
+  position: number;
+}
+
+export interface ShareOfSearchReport {
+  keyword_count: number;
+  snapshot_count: number;
+  volume_weighted: boolean;
+  our_asins: string[];
+  dates: string[];
+  shares: AsinShare[];
+  trend: AsinShareTrend[];
+  outrankers: Outranker[];
+  movements: CompetitorMovement[];
+}
+
+// 按词库筛选批量添加监控（各列表为空表示不限）
+export interface KeywordDataFilter {
+  search?: string | null;
+  traffic_levels: string[];
+  relevance_levels: string[];
+  primary_categories: string[];
+  brand_types: string[];
+  min_search_volume?: number | null;
+}
+
+export interface BulkMonitoringResult {
+  keyword_count: number;  // 命中筛选的关键词数
+  candidates: number;     // 关键词 × ASIN × 站点
+  created: number;
+  existing: number;       // 已在监控中
+}
+
+export interface MonitoringImportResult {
+  created: number;
+  updated: number;
+  errors: string[];
+}
+
+// 检测失败类型
+export type FailureKind = 'captcha' | 'geo_restricted' | 'timeout' | 'parse_error' | 'proxy_error' | 'rate_limited' | 'other';
+
+export const FAILURE_KIND_LABELS: Record<FailureKind, string> = {
+  captcha: '验证码',
+  geo_restricted: '地区限制',
+  timeout: '超时',
+  parse_error: '解析失败',
+  proxy_error: '代理失败',
+  rate_limited: '限速跳过',
+  other: '其他',
+};
+
+// 监控项的检测失败状态（时间均为 UTC）
+export interface MonitoringFailureStatus {
+  monitoring_id: number;
+  consecutive_failures: number;
+  last_failure_kind: FailureKind | null;
+  last_failure_message: string | null;
+  last_failure_at: string | null;
+  auto_paused_at: string | null;       // 连续失败过多被自动暂停的时间
+  retry_attempts: number | null;       // 在重试队列中时的已重试次数
+  next_retry_at: string | null;
+  recent_failures: number;             // 最近 30 天失败次数
+  recent_by_kind: [FailureKind, number][];
 }
 
 // 监控统计
@@ -178,6 +417,7 @@ export interface RankingResult {
 
   organic_rank: number | null;
   organic_page: number | null;
+  organic_position?: number | null;  // 自然结果中的绝对位置（按各页实际自然结果数累计）
   sponsored_rank: number | null;
   sponsored_page: number | null;
 
@@ -197,6 +437,37 @@ export interface RankingResult {
   checked_at: string;
   error: string | null;
   warning: string | null;  // 警告信息（如地理限制）
+  pages_checked?: number | null;  // 实际检测的页数
+  location?: string | null;  // 检测使用的配送邮编
+}
+
+// 排名检测引擎：Python + Playwright 浏览器，或原生 HTTP 请求（验证码/解析失败时回退到 Playwright）
+export type CrawlerEngine = 'playwright' | 'native';
+
+export const CRAWLER_ENGINE_LABELS: Record<CrawlerEngine, string> = {
+  playwright: '浏览器 (Playwright)',
+  native: '原生 HTTP',
+};
+
+// 批量检测运行状态
+export type RunState = 'running' | 'paused' | 'cancelled';
+
+export interface RunInfo {
+  run_id: number;
+  source: string;       // manual / auto / cron / cli
+  state: RunState;
+  total: number;
+  completed: number;
+  started_at: string;
+}
+
+// ranking-check-result 事件：单条检测结果（已写入数据库）
+export interface RankingCheckResultEvent {
+  run_id: number;
+  monitoring_id: number;
+  current: number;
+  total: number;
+  result: RankingResult;
 }
 
 // 国家选项（使用SVG国旗）
@@ -243,15 +514,47 @@ export interface SchedulerSettings {
   notify_on_exit_top10: boolean;
   notify_on_new_rank: boolean;
   notify_on_lost_rank: boolean;
-  max_pages: number;         // 监控页数: 1/3/5
+  max_pages: number;         // 监控页数: 1/3/5（关键词可单独设置）
+  // 各优先级的检测间隔（小时）
+  high_priority_hours: number;    // 4
+  medium_priority_hours: number;  // 8
+  low_priority_hours: number;     // 20
+  // 按站点配置的检测窗口（站点当地时间），未配置的站点使用上面的默认窗口
+  marketplace_windows: Record<string, MarketplaceWindow>;
+  // 排名历史保留：原始检测保留天数（至少 90），之后汇总为日数据；日数据保留天数，之后汇总为周数据
+  history_raw_days: number;    // 90
+  history_daily_days: number;  // 365
+  // 检测失败记录和任务明细（每次任务中各关键词的检测结果）保留天数
+  check_log_days: number;      // 30
+  // 连续检测失败多少次后自动暂停该关键词，0 表示不自动暂停
+  auto_pause_after_failures: number;  // 5
+}
+
+// 站点检测窗口（站点当地时间，小时，左闭右开）
+export interface MarketplaceWindow {
+  timezone: string | null;   // IANA 时区，为空时按站点默认时区
+  morning_start: number;
+  morning_end: number;
+  evening_start: number;
+  evening_end: number;
 }
 
 // 调度器状态
 export interface SchedulerStatus {
   is_running: boolean;
   last_check_time: string | null;
-  next_check_time: string | null;
+  next_check_time: string | null;   // 各站点中最早的下次检测时间
   current_task: string | null;
+  marketplaces: MarketplaceCheckStatus[];
+}
+
+// 单个站点的检测窗口状态
+export interface MarketplaceCheckStatus {
+  country: string;
+  timezone: string;
+  local_time: string;        // 站点当地时间 HH:MM
+  in_window: boolean;
+  next_check_time: string | null;
 }
 
 // 任务记录
@@ -263,10 +566,306 @@ export interface TaskLog {
   total_keywords: number;
   success_count: number;
   failed_count: number;
-  trigger_type: 'auto' | 'manual';
+  trigger_type: 'auto' | 'manual' | 'cron' | 'cli' | 'retry';
   error_message: string | null;
 }
 
+// 任务中单个关键词的检测结果
+export interface TaskItem {
+  id: number;
+  monitoring_id: number | null;   // 监控项已删除时为 null
+  keyword: string;
+  asin: string;
+  country: string;
+  status: 'success' | 'warning' | 'failed';
+  failure_kind: FailureKind | null;
+  error: string | null;
+  warning: string | null;
+  organic_rank: number | null;
+  organic_page: number | null;
+  sponsored_rank: number | null;
+  duration_ms: number | null;
+  proxy: string | null;
+  pages_checked: number | null;
+  checked_at: string;             // UTC
+}
+
+// 按代理或站点分组的统计
+export interface TaskGroupStat {
+  key: string;
+  total: number;
+  failed: number;
+  duration_p50_ms: number | null;
+}
+
+export interface TaskRunStats {
+  item_count: number;
+  success_count: number;
+  warning_count: number;
+  failed_count: number;
+  duration_avg_ms: number | null;
+  duration_p50_ms: number | null;
+  duration_p90_ms: number | null;
+  duration_p95_ms: number | null;
+  duration_max_ms: number | null;
+  avg_pages_checked: number | null;
+  failures_by_kind: [FailureKind, number][];
+  by_proxy: TaskGroupStat[];
+  by_country: TaskGroupStat[];
+}
+
+// 任务中爬虫子进程的日志、警告、错误和 stderr 输出
+export interface TaskMessage {
+  id: number;
+  level: 'info' | 'warning' | 'error' | 'stderr';
+  source: string;                 // 产生消息的脚本
+  message: string;
+  created_at: string;             // UTC
+}
+
+export interface TaskLogDetail {
+  log: TaskLog;
+  items: TaskItem[];
+  stats: TaskRunStats;
+  messages: TaskMessage[];
+}
+
+// 代理池中的代理（时间均为 UTC）
+export type ProxyType = 'datacenter' | 'residential' | 'mobile';
+
+export const PROXY_TYPE_LABELS: Record<ProxyType, string> = {
+  datacenter: '机房',
+  residential: '住宅',
+  mobile: '移动',
+};
+
+export interface ProxyServer {
+  id: number;
+  url: string;
+  label: string | null;
+  proxy_type: ProxyType;
+  countries: string[];              // 分配的站点，空表示所有站点
+  is_enabled: boolean;
+  disabled_reason: 'auto' | 'manual' | null;
+  disabled_at: string | null;
+  success_count: number;
+  failure_count: number;
+  consecutive_failures: number;
+  avg_duration_ms: number | null;   // 检测单个关键词的平均耗时
+  last_used_at: string | null;
+  last_error: string | null;
+  last_check_at: string | null;
+  last_check_ok: boolean | null;
+  last_check_latency_ms: number | null;
+  created_at: string;
+}
+
+export interface ProxyInput {
+  label: string | null;
+  proxy_type: ProxyType;
+  countries: string[];
+  is_enabled: boolean;
+}
+
+export interface ProxyCheckResult {
+  proxy_id: number;
+  url: string;
+  ok: boolean;
+  latency_ms: number | null;
+  error: string | null;
+  skipped: boolean;
+  auto_disabled: boolean;
+}
+
+// 站点限速状态（时间均为 UTC）
+export interface CrawlRateLimit {
+  country: string;
+  hourly_budget: number;          // 每小时请求预算，0 表示不限
+  level: number;                  // 限速等级，验证码 / 503 后升级
+  success_streak: number;
+  window_start: string | null;
+  window_requests: number;        // 本小时已用请求数
+  cooldown_until: string | null;
+  blocked_count: number;
+  last_blocked_at: string | null;
+  last_blocked_reason: string | null;
+  updated_at: string | null;
+}
+
+// 爬取页面归档中的一个关键词（所有页）或一个 Listing 页面，日期为 UTC
+export interface MarketplaceLocation {
+  country: string;
+  default_zipcode: string;
+  zipcode: string | null;         // 设置的邮编，null 使用默认邮编
+}
+
+export interface CrawlArchiveEntry {
+  date: string;                   // YYYY-MM-DD
+  kind: 'search' | 'listing';
+  country: string;
+  name: string;                   // 关键词目录名或 ASIN
+  run: string | null;             // 检测目录名（HHMMSS-引擎[-邮编]），旧版归档和 Listing 为 null
+  engine: 'native' | 'playwright' | null;
+  location: string | null;        // 检测时使用的邮编
+  pages: number;
+  size_bytes: number;
+}
+
+// 排名变化类型
+export type RankChangeType =
+  | 'improved' | 'declined' | 'entered_top10' | 'exited_top10' | 'new_rank' | 'lost_rank';
+
+// 定时检测中达到通知条件的排名变化
+export interface RankChange {
+  monitoring_id: number;
+  product_id: number;
+  keyword: string;
+  asin: string;
+  country: string;
+  old_rank: number | null;
+  new_rank: number | null;
+  change: number;  // 正数表示排名上升
+  change_type: RankChangeType;
+}
+
+// ranking-changes 事件：一次定时检测（auto/cron）的排名变化汇总
+export interface RankChangeDigest {
+  task_id: number | null;
+  trigger_type: 'auto' | 'cron';
+  checked: number;
+  failed: number;
+  changes: RankChange[];
+}
+
+// 排名告警条件
+export type AlertConditionType =
+  | 'rank_crosses' | 'rank_drop' | 'sponsored_lost' | 'price_changed' | 'rating_below';
+
+export const ALERT_CONDITION_OPTIONS: { value: AlertConditionType; label: string; thresholdLabel?: string }[] = [
+  { value: 'rank_crosses', label: '排名越过第 N 名', thresholdLabel: '名次 N' },
+  { value: 'rank_drop', label: 'Y 天内下降超过 X 名', thresholdLabel: '下降名次 X' },
+  { value: 'sponsored_lost', label: '广告位丢失' },
+  { value: 'price_changed', label: '价格变化', thresholdLabel: '变化幅度 %' },
+  { value: 'rating_below', label: '评分低于 Z', thresholdLabel: '评分 Z' },
+];
+
+export type AlertChannel = 'desktop' | 'none';
+export type AlertStatus = 'open' | 'acknowledged' | 'snoozed';
+
+// 告警规则，作用范围字段为空表示不限
+export interface AlertRule {
+  id: number;
+  name: string;
+  product_id: number | null;
+  keyword: string | null;
+  tag: string | null;
+  country: string | null;
+  condition_type: AlertConditionType;
+  threshold: number | null;
+  window_days: number | null;  // rank_drop 的统计天数
+  channel: AlertChannel;
+  channel_id: number | null;   // 同时发送到的外部通知渠道
+  is_enabled: boolean;
+  created_at: string;
+}
+
+export type AlertRuleInput = Omit<AlertRule, 'id' | 'created_at'>;
+
+// 告警记录（时间字段为 UTC）
+export interface AlertRecord {
+  id: number;
+  rule_id: number;
+  rule_name: string;
+  condition_type: AlertConditionType;
+  channel: AlertChannel;
+  channel_id: number | null;
+  monitoring_id: number;
+  product_id: number;
+  keyword: string;
+  asin: string;
+  country: string;
+  message: string;
+  status: AlertStatus;
+  fired_at: string;
+  acknowledged_at: string | null;
+  snoozed_until: string | null;
+  delivered_at: string | null;
+  delivery_error: string | null;
+}
+
+// 外部通知渠道配置
+export type WebhookFormat = 'generic' | 'dingtalk' | 'feishu' | 'wecom' | 'slack';
+export type SmtpSecurity = 'starttls' | 'tls' | 'none';
+
+export const WEBHOOK_FORMAT_OPTIONS: { value: WebhookFormat; label: string }[] = [
+  { value: 'generic', label: '通用 JSON' },
+  { value: 'dingtalk', label: '钉钉机器人' },
+  { value: 'feishu', label: '飞书机器人' },
+  { value: 'wecom', label: '企业微信机器人' },
+  { value: 'slack', label: 'Slack' },
+];
+
+export interface WebhookChannelConfig {
+  type: 'webhook';
+  url: string;
+  format: WebhookFormat;
+  template: string | null;  // 仅通用格式，占位符 {{title}} {{body}} {{text}}
+}
+
+export interface EmailChannelConfig {
+  type: 'email';
+  host: string;
+  port: number;
+  security: SmtpSecurity;
+  username: string | null;
+  password: string | null;
+  from: string;
+  to: string[];
+}
+
+export type ChannelConfig = WebhookChannelConfig | EmailChannelConfig;
+
+export interface NotificationChannel {
+  id: number;
+  name: string;
+  channel_type: 'webhook' | 'email';
+  config: ChannelConfig;
+  is_enabled: boolean;
+  send_rank_digest: boolean;  // 接收定时检测的排名变化汇总
+  last_sent_at: string | null;
+  last_error: string | null;
+  created_at: string;
+}
+
+export interface NotificationChannelInput {
+  name: string;
+  config: ChannelConfig;
+  is_enabled: boolean;
+  send_rank_digest: boolean;
+}
+
+// 排名检测计划（Cron），时间字段均为 UTC（YYYY-MM-DD HH:MM:SS）
+export interface RankingSchedule {
+  id: number;
+  name: string;
+  product_id: number | null;          // 为空表示全部产品
+  priority: 'high' | 'medium' | 'low' | null;  // 为空表示全部优先级
+  cron_expr: string;
+  timezone: string;
+  catch_up: boolean;                  // 错过的计划（如电脑休眠）唤醒后补跑
+  is_enabled: boolean;
+  last_run_at: string | null;
+  last_run_status: 'running' | 'completed' | 'skipped' | 'failed' | null;
+  last_run_message: string | null;
+  next_run_at: string | null;
+  created_at: string;
+}
+
+export type RankingScheduleInput = Pick<
+  RankingSchedule,
+  'name' | 'product_id' | 'priority' | 'cron_expr' | 'timezone' | 'catch_up' | 'is_enabled'
+>;
+
 // ==================== 优化事件 ====================
 
 // 主类型
@@ -318,8 +917,51 @@ export interface OptimizationEvent {
   affected_keywords?: string;      // JSON字符串，存储关键词数组（可选）
   screenshots?: string;            // JSON字符串，存储截图文件名数组（可选）
   created_at: string;
+  impact: EventImpact | null;      // 事件前后排名对比
 }
 
+// 事件影响分析（delta = 之后 - 之前，负数表示排名上升）
+export type ImpactSignificance = 'significant' | 'weak' | 'none' | 'insufficient';
+
+export interface RankComparison {
+  before_mean: number | null;
+  after_mean: number | null;
+  before_days: number;
+  after_days: number;
+  delta: number | null;
+  significance: ImpactSignificance;
+}
+
+export interface ImpactSummary {
+  keyword_count: number;
+  improved: number;
+  worsened: number;
+  mean_delta: number | null;
+  significance: ImpactSignificance;
+}
+
+export interface EventImpact {
+  window_days: number;
+  after_complete: boolean;
+  organic: ImpactSummary;
+  sponsored: ImpactSummary;
+  keywords: {
+    monitoring_id: number;
+    keyword: string;
+    asin: string;
+    country: string;
+    organic: RankComparison;
+    sponsored: RankComparison;
+  }[];
+}
+
+export const IMPACT_SIGNIFICANCE_LABELS: Record<ImpactSignificance, string> = {
+  significant: '显著',
+  weak: '有迹象',
+  none: '不显著',
+  insufficient: '数据不足',
+};
+
 // 兼容旧代码的 EVENT_TYPE_LABELS（基于主类型）
 export const EVENT_TYPE_LABELS = EVENT_MAIN_TYPES;
 
@@ -336,6 +978,14 @@ export const DEFAULT_SCHEDULER_SETTINGS: SchedulerSettings = {
   notify_on_new_rank: true,
   notify_on_lost_rank: true,
   max_pages: 5,              // 默认监控前5页
+  high_priority_hours: 4,
+  medium_priority_hours: 8,
+  low_priority_hours: 20,
+  marketplace_windows: {},
+  history_raw_days: 90,
+  history_daily_days: 365,
+  check_log_days: 30,
+  auto_pause_after_failures: 5,
 };
 
 // ==================== 依赖安装 ====================